@@ -99,7 +99,7 @@ fn bench_csv_seeking(c: &mut Criterion) {
             || CsvDataSource::new(csv_file.path()).unwrap(),
             |mut data_source| {
                 let target_timestamp = base_timestamp + (num_events as u128 / 2 * 1000000);
-                black_box(data_source.seek_to_time(target_timestamp).unwrap())
+                black_box(data_source.seek_to_time(target_timestamp)).unwrap()
             },
             criterion::BatchSize::SmallInput,
         );
@@ -200,7 +200,7 @@ fn bench_event_validation(c: &mut Criterion) {
     group.bench_function("validate_events", |b| {
         b.iter(|| {
             for event in &events {
-                black_box(event.validate().unwrap());
+                black_box(event.validate()).unwrap();
             }
         });
     });
@@ -274,7 +274,7 @@ fn bench_memory_usage_during_ingestion(c: &mut Criterion) {
                     match event {
                         MarketEvent::Trade { price, qty, .. } => {
                             // Simulate processing
-                            let _ = price * qty as u64;
+                            let _ = price * qty;
                         }
                         MarketEvent::Quote { bid, ask, .. } => {
                             // Simulate spread calculation
@@ -212,7 +212,7 @@ fn bench_mixed_workload(c: &mut Criterion) {
     
     group.bench_function("realistic_trading", |b| {
         b.iter_batched(
-            || TestOrderBook::new(),
+            TestOrderBook::new,
             |mut book| {
                 let mut order_id = 1;
                 
@@ -275,7 +275,7 @@ fn bench_memory_efficiency(c: &mut Criterion) {
     
     group.bench_function("large_order_book", |b| {
         b.iter_batched(
-            || TestOrderBook::new(),
+            TestOrderBook::new,
             |mut book| {
                 // Create a very large order book to test memory efficiency
                 for i in 0..50000 {
@@ -347,6 +347,62 @@ fn bench_price_level_operations(c: &mut Criterion) {
     group.finish();
 }
 
+fn bench_cancel_heavy_hft(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cancel_heavy_hft");
+    
+    for op_count in [1000, 10000].iter() {
+        group.throughput(Throughput::Elements(*op_count as u64));
+        
+        group.bench_with_input(
+            BenchmarkId::new("cancel_replace_20_to_1", op_count),
+            op_count,
+            |b, &op_count| {
+                b.iter_batched(
+                    || {
+                        // 20 cancel/replaces per trade, clustered at the best 3 levels
+                        let workload = HftWorkload::new().generate(op_count);
+                        let mut book = TestOrderBook::new();
+                        for order in workload.initial {
+                            book.place(order).unwrap();
+                        }
+                        (book, workload.ops)
+                    },
+                    |(mut book, ops)| {
+                        for op in &ops {
+                            black_box(op.apply(&mut book).unwrap());
+                        }
+                        black_box(book)
+                    },
+                    criterion::BatchSize::SmallInput,
+                );
+            },
+        );
+    }
+    
+    group.finish();
+}
+
+fn bench_queue_disciplines(c: &mut Criterion) {
+    let mut group = c.benchmark_group("queue_disciplines");
+
+    // Every discipline replays the same recorded command stream
+    let commands = HftWorkload::new().with_seed(11).generate(10000).commands();
+    let comparison = DisciplineComparison::builtin();
+    group.throughput(Throughput::Elements(commands.len() as u64));
+
+    for name in comparison.names() {
+        group.bench_with_input(
+            BenchmarkId::new("replay", name),
+            &commands,
+            |b, commands| {
+                b.iter(|| black_box(comparison.run(name, commands)));
+            },
+        );
+    }
+
+    group.finish();
+}
+
 criterion_group!(
     benches,
     bench_order_placement,
@@ -355,7 +411,9 @@ criterion_group!(
     bench_snapshot_generation,
     bench_mixed_workload,
     bench_memory_efficiency,
-    bench_price_level_operations
+    bench_price_level_operations,
+    bench_cancel_heavy_hft,
+    bench_queue_disciplines
 );
 
 criterion_main!(benches);
\ No newline at end of file
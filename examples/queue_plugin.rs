@@ -0,0 +1,189 @@
+//! Registering a queue discipline and an agent kind from outside the crate
+//!
+//! `LifoLevel` fills the newest order at a price first, and `pegger` agents
+//! join the best bid and ask with a fixed size. Both are registered with
+//! `Plugins` under names the configuration then picks, the same way a
+//! downstream crate would add its own. Pass `--serve` to start the server on
+//! the configured port with them instead of running a few steps.
+//!
+//! The engine matches, cancels and prevents self-matches by the orders a
+//! level lists, so `LifoLevel` lists every order it holds in `orders` and
+//! gives them in fill order in `resting`.
+
+use std::collections::VecDeque;
+use orderbook::{
+    now_ns, price_utils, start_server_with_plugins, Agent, AgentOrder, AgentSpec, Config, MarketView, Order,
+    OrderId, Plugins, Price, Qty, QueueDiscipline, Side, Simulator, SyntheticAgent, Trade,
+};
+use rand::rngs::StdRng;
+
+/// Last-in-first-out price level
+#[derive(Debug, Default)]
+struct LifoLevel {
+    /// Orders in arrival order; the back fills first
+    orders: VecDeque<Order>,
+    total_qty: Qty,
+    last_activity_ts: u128,
+}
+
+impl QueueDiscipline for LifoLevel {
+    fn enqueue(&mut self, order: Order) {
+        self.total_qty += order.qty;
+        let ts = order.ts;
+        self.orders.push_back(order);
+        self.touch(ts);
+    }
+
+    fn match_against(&mut self, taker_id: OrderId, taker_side: Side, taker_qty: Qty, price: Price) -> (Qty, Vec<Trade>) {
+        self.match_against_at(taker_id, taker_side, taker_qty, price, now_ns())
+    }
+
+    fn match_against_filtered(
+        &mut self,
+        taker_id: OrderId,
+        taker_side: Side,
+        taker_qty: Qty,
+        price: Price,
+        filter: &dyn Fn(OrderId, OrderId) -> bool,
+    ) -> (Qty, Vec<Trade>) {
+        self.match_against_filtered_at(taker_id, taker_side, taker_qty, price, now_ns(), filter)
+    }
+
+    /// The engine matches through the `_at` methods with its own time, so
+    /// the level never reads the wall clock itself
+    fn match_against_at(&mut self, taker_id: OrderId, taker_side: Side, taker_qty: Qty, price: Price, ts: u128) -> (Qty, Vec<Trade>) {
+        self.match_against_filtered_at(taker_id, taker_side, taker_qty, price, ts, &|_, _| true)
+    }
+
+    fn match_against_filtered_at(
+        &mut self,
+        taker_id: OrderId,
+        taker_side: Side,
+        mut taker_qty: Qty,
+        price: Price,
+        ts: u128,
+        filter: &dyn Fn(OrderId, OrderId) -> bool,
+    ) -> (Qty, Vec<Trade>) {
+        let mut trades = Vec::new();
+        let mut index = self.orders.len();
+        while taker_qty > 0 && index > 0 {
+            index -= 1;
+            let maker = &mut self.orders[index];
+            if !filter(maker.id, taker_id) {
+                continue;
+            }
+            let qty = taker_qty.min(maker.qty);
+            trades.push(Trade { maker_id: maker.id, taker_id, price, qty, ts, aggressor: taker_side });
+            taker_qty -= qty;
+            maker.qty -= qty;
+            self.total_qty -= qty;
+            if maker.qty == 0 {
+                self.orders.remove(index);
+            }
+        }
+        self.touch(ts);
+        (taker_qty, trades)
+    }
+
+    fn cancel(&mut self, order_id: OrderId) -> Qty {
+        let Some(index) = self.orders.iter().position(|order| order.id == order_id) else {
+            return 0;
+        };
+        let order = self.orders.remove(index).unwrap();
+        self.total_qty -= order.qty;
+        order.qty
+    }
+
+    fn total_qty(&self) -> Qty {
+        self.total_qty
+    }
+
+    fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+
+    fn touch(&mut self, ts: u128) {
+        self.last_activity_ts = ts;
+    }
+
+    fn last_ts(&self) -> u128 {
+        self.last_activity_ts
+    }
+
+    fn order_count(&self) -> usize {
+        self.orders.len()
+    }
+
+    fn oldest_order_ts(&self) -> Option<u128> {
+        self.orders.front().map(|order| order.ts)
+    }
+
+    fn resting(&self) -> Vec<(OrderId, Qty)> {
+        self.orders.iter().rev().map(|order| (order.id, order.qty)).collect()
+    }
+
+    /// In arrival order, so placing them again rebuilds the same queue
+    fn orders(&self) -> Vec<Order> {
+        self.orders.iter().cloned().collect()
+    }
+}
+
+/// Joins the best bid and ask every step
+struct Pegger {
+    qty: Qty,
+}
+
+impl SyntheticAgent for Pegger {
+    fn role(&self) -> Agent {
+        Agent::MarketMaker
+    }
+
+    fn on_step(&mut self, market: &MarketView<'_>) -> Vec<AgentOrder> {
+        let qty = market.whole_lots(self.qty);
+        market.best_bid.map(|bid| AgentOrder::limit(Side::Buy, qty, bid)).into_iter()
+            .chain(market.best_ask.map(|ask| AgentOrder::limit(Side::Sell, qty, ask)))
+            .collect()
+    }
+}
+
+fn pegger(params: &toml::Table, _rng: StdRng) -> Result<Box<dyn SyntheticAgent>, String> {
+    let qty = match params.get("qty") {
+        Some(value) => value.as_integer().filter(|&qty| qty > 0).ok_or("qty must be a positive integer")? as Qty,
+        None => 50,
+    };
+    Ok(Box::new(Pegger { qty }))
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let plugins = Plugins::new()
+        .with_discipline("lifo", || Box::<LifoLevel>::default())
+        .with_agent("pegger", pegger);
+
+    let mut config = Config::default();
+    config.simulation.queue_discipline = "lifo".to_string();
+    config.agents = vec![
+        AgentSpec::new("market_maker"),
+        AgentSpec::new("taker"),
+        AgentSpec::new("pegger").with_param("qty", 20),
+    ];
+
+    let registry = plugins.agent_registry(&config.market_maker, &config.order_generation);
+    let agents = registry.build(&config.agents, 7)?;
+    let engine = plugins.book(&config.simulation.queue_discipline)?;
+    let mut simulator = Simulator::with_seed(engine, 7).with_agents(agents);
+
+    if std::env::args().any(|arg| arg == "--serve") {
+        return start_server_with_plugins(simulator, &config, &plugins).await;
+    }
+
+    let trades = simulator.run_steps(500)?;
+    let snapshot = simulator.snapshot();
+    println!("Disciplines: {}", plugins.disciplines().names().collect::<Vec<_>>().join(", "));
+    println!("{} trades under LIFO; bid {:?}, ask {:?}", trades.len(),
+             snapshot.best_bid.map(price_utils::format), snapshot.best_ask.map(price_utils::format));
+    for agent in simulator.agent_stats() {
+        println!("  {} #{}: {} orders, inventory {}", agent.kind, agent.id, agent.orders, agent.metrics.inventory);
+    }
+    Ok(())
+}
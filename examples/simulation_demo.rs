@@ -1,5 +1,5 @@
 use orderbook::{
-    Simulator, OrderBook, FifoLevel, NetModel, MarketMakerConfig, OrderGenerationConfig,
+    Simulator, OrderBook, FifoLevel, NetModel, MarketMakerConfig, InventoryBand, OrderGenerationConfig,
     SimulationMode, price_utils
 };
 
@@ -27,6 +27,13 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         order_size: 100,                            // 100 share orders
         mm_probability: 0.8,                        // 80% chance of market making
         inventory_skew: 0.002,                      // 0.2% price skew per inventory unit
+        inventory_bands: vec![
+            // Past half the limit quote twice as wide at half size,
+            // past 80% four times as wide at a quarter
+            InventoryBand { threshold: 0.5, spread_multiplier: 2.0, size_multiplier: 0.5 },
+            InventoryBand { threshold: 0.8, spread_multiplier: 4.0, size_multiplier: 0.25 },
+        ],
+        two_sided_quotes: false,                    // built-in flow adds orders
     };
 
     // Configure order generation
@@ -36,6 +43,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         min_order_size: 10,
         max_order_size: 200,
         price_range_fraction: 0.015,               // ±1.5% price range
+        buy_probability: 0.5,                      // balanced taker flow
+        max_slippage_ticks: None,                  // takers ignore book depth
     };
 
     // Create simulator with configurations
@@ -0,0 +1,449 @@
+//! A/B shadow execution
+//!
+//! An [`AbShadow`] keeps a second book under its own engine settings and is
+//! sent every command the simulator sends the primary engine, as it is sent.
+//! The primary alone serves clients; the shadow book only compares. When a
+//! command's outcome differs between the two books, or leaves their top of
+//! book apart, the shadow records an [`AbDivergence`], so an engine change can
+//! be judged under live simulated load before the primary runs it.
+
+use std::collections::{BTreeMap, VecDeque};
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::engine::{BookLevelPoint, CommandEngine, CrossedBookPolicy, DepthLimit, DepthSnapshot, EngineCommand, EngineEvent, OrderBook, OrderBookEngine};
+use crate::error::EngineResult;
+use crate::memory::{shrink_deque, Compaction};
+use crate::plugin::DynLevel;
+use crate::types::{price_utils, LotSize, Order, Price, Qty, Side};
+
+/// Levels per side compared when reporting depth differences
+const DEPTH_LEVELS_COMPARED: usize = 20;
+
+/// Engine settings of the shadow book
+///
+/// A setting left unset follows the primary's, including changes made while
+/// the simulator runs; a set one is pinned for the shadow book.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AbShadowConfig {
+    /// Run a shadow book beside the primary engine
+    pub enabled: bool,
+    #[serde(default)]
+    pub lot_size: Option<LotSize>,
+    #[serde(default)]
+    pub depth_limit: Option<DepthLimit>,
+    #[serde(default)]
+    pub crossed_book_policy: Option<CrossedBookPolicy>,
+    /// Queue discipline of the shadow book's levels, by registered name;
+    /// the primary's if unset
+    #[serde(default)]
+    pub queue_discipline: Option<String>,
+    /// Divergences kept for the report, newest last
+    #[serde(default = "default_max_divergences")]
+    pub max_divergences: usize,
+}
+
+impl Default for AbShadowConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            lot_size: None,
+            depth_limit: None,
+            crossed_book_policy: None,
+            queue_discipline: None,
+            max_divergences: default_max_divergences(),
+        }
+    }
+}
+
+fn default_max_divergences() -> usize {
+    100
+}
+
+impl AbShadowConfig {
+    /// Check the pinned settings
+    pub fn validate(&self) -> Result<(), String> {
+        if self.lot_size.is_some_and(|lot_size| lot_size.size == 0) {
+            return Err("Shadow book lot size must be greater than 0".to_string());
+        }
+        if let Some(limit) = &self.depth_limit {
+            limit.validate()?;
+        }
+        if self.queue_discipline.as_deref().is_some_and(str::is_empty) {
+            return Err("Shadow book queue discipline cannot be empty".to_string());
+        }
+        if self.max_divergences == 0 {
+            return Err("Shadow book must keep at least one divergence".to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether the shadow book ignores the primary's changes to a setting
+    fn pins(&self, command: &EngineCommand) -> bool {
+        match command {
+            EngineCommand::SetLotSize(_) => self.lot_size.is_some(),
+            EngineCommand::SetDepthLimit(_) => self.depth_limit.is_some(),
+            EngineCommand::SetCrossedBookPolicy(_) => self.crossed_book_policy.is_some(),
+            // Book statistics and pruned orders are the primary's to collect,
+            // and each book is compacted on its own
+            EngineCommand::Stats | EngineCommand::TakePruned | EngineCommand::Compact => true,
+            _ => false,
+        }
+    }
+}
+
+/// How the two books came apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AbDivergenceKind {
+    /// A command got a different reply: other fills, cancelled quantity or a rejection
+    Outcome,
+    /// The best bid or ask differs after a command that matched before it
+    TopOfBook,
+}
+
+/// A command after which the books differed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AbDivergence {
+    /// Position of the command among those mirrored, from 1
+    pub command: u64,
+    pub kind: AbDivergenceKind,
+    /// What the command was
+    pub request: String,
+    pub primary: String,
+    pub shadow: String,
+}
+
+/// Fills and rejections on one of the books
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AbTotals {
+    pub trades: u64,
+    pub volume: u64,
+    /// Orders refused
+    pub rejected: u64,
+}
+
+impl AbTotals {
+    fn record(&mut self, command: &EngineCommand, outcome: &EngineResult<EngineEvent>) {
+        match (command, outcome) {
+            (EngineCommand::Place(_), Ok(EngineEvent::Placed(trades))) => {
+                self.trades += trades.len() as u64;
+                self.volume += trades.iter().map(|trade| trade.qty).sum::<Qty>();
+            }
+            (EngineCommand::Place(_), Err(_)) => self.rejected += 1,
+            (EngineCommand::Quote(_), Ok(EngineEvent::Quoted(outcome))) => {
+                self.trades += outcome.trades.len() as u64;
+                self.volume += outcome.trades.iter().map(|trade| trade.qty).sum::<Qty>();
+                self.rejected += outcome.refused.len() as u64;
+            }
+            (EngineCommand::Quote(_), Err(_)) => self.rejected += 1,
+            _ => {}
+        }
+    }
+}
+
+/// A price level resting in different size on the two books
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LevelDifference {
+    pub side: Side,
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub price: Price,
+    pub primary: Qty,
+    pub shadow: Qty,
+}
+
+/// How the shadow book compared with the primary
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AbReport {
+    /// Commands mirrored to the shadow book
+    pub commands: u64,
+    pub primary: AbTotals,
+    pub shadow: AbTotals,
+    /// Commands whose reply differed
+    pub outcome_divergences: u64,
+    /// Times the top of book came apart
+    pub top_of_book_divergences: u64,
+    /// Whether the top of book differs now
+    pub top_of_book_diverged: bool,
+    /// First command after which the books differed
+    pub first_divergence: Option<u64>,
+    /// Latest divergences, oldest first
+    pub recent: Vec<AbDivergence>,
+    /// Levels near the touch resting in different size now
+    pub depth: Vec<LevelDifference>,
+}
+
+/// Second book fed the primary engine's commands
+pub struct AbShadow {
+    config: AbShadowConfig,
+    book: OrderBook<DynLevel>,
+    commands: u64,
+    primary: AbTotals,
+    shadow: AbTotals,
+    outcome_divergences: u64,
+    top_of_book_divergences: u64,
+    top_of_book_diverged: bool,
+    first_divergence: Option<u64>,
+    recent: VecDeque<AbDivergence>,
+}
+
+impl AbShadow {
+    /// A shadow of `book` under `config`'s settings, starting from the
+    /// orders resting on the primary
+    ///
+    /// `book` should be empty and of the discipline `config` names, or the
+    /// primary's when it names none.
+    pub fn new(config: AbShadowConfig, mut book: OrderBook<DynLevel>, resting: Vec<Order>) -> Self {
+        let settings = [
+            config.lot_size.map(EngineCommand::SetLotSize),
+            config.depth_limit.map(EngineCommand::SetDepthLimit),
+            config.crossed_book_policy.map(EngineCommand::SetCrossedBookPolicy),
+        ];
+        for command in settings.into_iter().flatten() {
+            let _ = book.execute(command);
+        }
+        for order in resting {
+            book.set_clock(order.ts);
+            let _ = book.submit(order);
+        }
+        Self {
+            config,
+            book,
+            commands: 0,
+            primary: AbTotals::default(),
+            shadow: AbTotals::default(),
+            outcome_divergences: 0,
+            top_of_book_divergences: 0,
+            top_of_book_diverged: false,
+            first_divergence: None,
+            recent: VecDeque::new(),
+        }
+    }
+
+    /// Get the shadow book's settings
+    pub fn config(&self) -> &AbShadowConfig {
+        &self.config
+    }
+
+    /// The shadow book
+    pub fn book(&self) -> &OrderBook<DynLevel> {
+        &self.book
+    }
+
+    /// Release spare capacity in the shadow book and its divergence list
+    pub fn compact(&mut self) -> Compaction {
+        self.book.compact() + shrink_deque(&mut self.recent, self.config.max_divergences)
+    }
+
+    /// Apply a command the primary engine just applied, comparing its reply
+    /// `primary` and the primary's top of book after it with the shadow's
+    pub fn mirror(
+        &mut self,
+        command: &EngineCommand,
+        primary: &EngineResult<EngineEvent>,
+        primary_top: (Option<Price>, Option<Price>),
+    ) {
+        if self.config.pins(command) {
+            return;
+        }
+        self.commands += 1;
+        let shadow = self.book.execute(command.clone());
+        // Nothing settles the shadow book's pruned orders
+        self.book.take_pruned();
+        self.primary.record(command, primary);
+        self.shadow.record(command, &shadow);
+
+        let (primary_outcome, shadow_outcome) = (describe_outcome(primary), describe_outcome(&shadow));
+        if primary_outcome != shadow_outcome {
+            self.outcome_divergences += 1;
+            self.record(AbDivergenceKind::Outcome, command, primary_outcome, shadow_outcome);
+        }
+
+        if matches!(command, EngineCommand::Place(_) | EngineCommand::Cancel(_) | EngineCommand::Clear | EngineCommand::Quote(_)) {
+            let shadow_top = self.book.best_bid_ask();
+            let diverged = primary_top != shadow_top;
+            if diverged && !self.top_of_book_diverged {
+                self.top_of_book_divergences += 1;
+                self.record(AbDivergenceKind::TopOfBook, command, describe_top(primary_top), describe_top(shadow_top));
+            }
+            self.top_of_book_diverged = diverged;
+        }
+    }
+
+    fn record(&mut self, kind: AbDivergenceKind, command: &EngineCommand, primary: String, shadow: String) {
+        self.first_divergence.get_or_insert(self.commands);
+        if self.recent.len() == self.config.max_divergences {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(AbDivergence {
+            command: self.commands,
+            kind,
+            request: describe_command(command),
+            primary,
+            shadow,
+        });
+    }
+
+    /// Compare the shadow book with the primary's `depth`
+    pub fn report(&self, depth: &DepthSnapshot) -> AbReport {
+        let shadow_depth = self.book.depth_snapshot();
+        let mut differences = level_differences(Side::Buy, &depth.bids, &shadow_depth.bids);
+        differences.extend(level_differences(Side::Sell, &depth.asks, &shadow_depth.asks));
+        AbReport {
+            commands: self.commands,
+            primary: self.primary,
+            shadow: self.shadow,
+            outcome_divergences: self.outcome_divergences,
+            top_of_book_divergences: self.top_of_book_divergences,
+            top_of_book_diverged: self.top_of_book_diverged,
+            first_divergence: self.first_divergence,
+            recent: self.recent.iter().cloned().collect(),
+            depth: differences,
+        }
+    }
+}
+
+/// Levels near the touch whose size differs, best first
+fn level_differences(side: Side, primary: &[BookLevelPoint], shadow: &[BookLevelPoint]) -> Vec<LevelDifference> {
+    let sizes = |levels: &[BookLevelPoint]| -> BTreeMap<Price, Qty> {
+        levels.iter().take(DEPTH_LEVELS_COMPARED).map(|level| (level.price, level.qty)).collect()
+    };
+    let (primary, shadow) = (sizes(primary), sizes(shadow));
+    let mut prices: Vec<Price> = primary.keys().chain(shadow.keys()).copied().collect();
+    prices.sort_unstable();
+    prices.dedup();
+    if side == Side::Buy {
+        prices.reverse();
+    }
+    prices.into_iter()
+        .map(|price| LevelDifference {
+            side,
+            price,
+            primary: primary.get(&price).copied().unwrap_or(0),
+            shadow: shadow.get(&price).copied().unwrap_or(0),
+        })
+        .filter(|difference| difference.primary != difference.shadow)
+        .collect()
+}
+
+/// A command, without the order timestamps that never differ
+fn describe_command(command: &EngineCommand) -> String {
+    match command {
+        EngineCommand::Place(order) => match order.price() {
+            Some(price) => format!("place #{} {:?} {} @ {}", order.id, order.side, order.qty, price_utils::format(price)),
+            None => format!("place #{} {:?} {} at market", order.id, order.side, order.qty),
+        },
+        EngineCommand::Cancel(order_id) => format!("cancel #{}", order_id),
+        command => format!("{:?}", command),
+    }
+}
+
+/// A reply, without trade timestamps, so equal replies compare equal
+fn describe_outcome(outcome: &EngineResult<EngineEvent>) -> String {
+    match outcome {
+        Ok(EngineEvent::Placed(trades)) if trades.is_empty() => "rested".to_string(),
+        Ok(EngineEvent::Placed(trades)) => trades.iter()
+            .map(|trade| format!("{} @ {} vs #{}", trade.qty, price_utils::format(trade.price), trade.maker_id))
+            .collect::<Vec<_>>()
+            .join(", "),
+        Ok(EngineEvent::Cancelled(qty)) => format!("cancelled {}", qty),
+        Ok(EngineEvent::Pruned(orders)) => format!(
+            "pruned [{}]",
+            orders.iter().map(|pruned| format!("#{}", pruned.order_id)).collect::<Vec<_>>().join(", "),
+        ),
+        Ok(EngineEvent::PriorityFeePaid(fee)) => format!("fee {}", fee),
+        Ok(EngineEvent::Quoted(outcome)) => format!(
+            "quoted: pulled {}, {} trades, resting [{}], refused {}",
+            outcome.cancelled.len(),
+            outcome.trades.len(),
+            outcome.resting.iter().map(|order_id| format!("#{}", order_id)).collect::<Vec<_>>().join(", "),
+            outcome.refused.len(),
+        ),
+        Ok(EngineEvent::Applied | EngineEvent::Stats(_) | EngineEvent::Compacted(_)) => "applied".to_string(),
+        Err(e) => format!("rejected: {}", e),
+    }
+}
+
+fn describe_top((bid, ask): (Option<Price>, Option<Price>)) -> String {
+    let side = |price: Option<Price>| price.map_or_else(|| "-".to_string(), price_utils::format);
+    format!("{} / {}", side(bid), side(ask))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::DepthPolicy;
+    use crate::plugin::Plugins;
+    use crate::queue_fifo::FifoLevel;
+
+    /// Apply a command to the primary and mirror it, as the simulator does
+    fn send(primary: &mut OrderBook<FifoLevel>, shadow: &mut AbShadow, command: EngineCommand) {
+        let outcome = primary.execute(command.clone());
+        shadow.mirror(&command, &outcome, primary.best_bid_ask());
+    }
+
+    #[test]
+    fn test_shadow_book_records_divergences_from_the_primary() {
+        let config = AbShadowConfig {
+            enabled: true,
+            depth_limit: Some(DepthLimit { policy: DepthPolicy::Prune, ..DepthLimit::levels(1) }),
+            ..AbShadowConfig::default()
+        };
+        config.validate().unwrap();
+        let mut primary = OrderBook::<FifoLevel>::new();
+        primary.set_clock(0);
+        primary.submit(Order::new_limit(1, Side::Buy, 10, 99_0000, 0)).unwrap();
+        let mut shadow = AbShadow::new(config, OrderBook::new(), primary.open_orders());
+
+        // Both books agree while a single level is quoted
+        send(&mut primary, &mut shadow, EngineCommand::Place(Order::new_limit(2, Side::Sell, 10, 101_0000, 0)));
+        let report = shadow.report(&primary.depth_snapshot());
+        assert_eq!((report.commands, report.first_divergence), (1, None));
+        assert!(report.depth.is_empty());
+
+        // A second bid level is pruned from the shadow only; the primary's
+        // depth limit changes are ignored
+        send(&mut primary, &mut shadow, EngineCommand::SetDepthLimit(DepthLimit::default()));
+        send(&mut primary, &mut shadow, EngineCommand::Place(Order::new_limit(3, Side::Buy, 5, 98_0000, 0)));
+        let report = shadow.report(&primary.depth_snapshot());
+        assert_eq!(report.commands, 2);
+        assert_eq!(report.depth, vec![LevelDifference { side: Side::Buy, price: 98_0000, primary: 5, shadow: 0 }]);
+
+        // A sell larger than the shadow's bids fills on the primary only
+        send(&mut primary, &mut shadow, EngineCommand::Place(Order::new_market(4, Side::Sell, 12, 0)));
+        let report = shadow.report(&primary.depth_snapshot());
+        assert_eq!((report.primary.volume, report.shadow.volume), (12, 0));
+        assert_eq!(report.shadow.rejected, 1);
+        assert_eq!(report.outcome_divergences, 1);
+        assert_eq!(report.top_of_book_divergences, 1);
+        assert!(report.top_of_book_diverged);
+        assert_eq!(report.first_divergence, Some(3));
+        assert_eq!(report.recent.iter().map(|divergence| divergence.kind).collect::<Vec<_>>(),
+                   vec![AbDivergenceKind::Outcome, AbDivergenceKind::TopOfBook]);
+        assert_eq!(report.recent[0].primary, "10 @ 99.0000 vs #1, 2 @ 98.0000 vs #3");
+
+        // Clearing both books brings them back together
+        send(&mut primary, &mut shadow, EngineCommand::Clear);
+        assert!(!shadow.report(&primary.depth_snapshot()).top_of_book_diverged);
+    }
+
+    #[test]
+    fn test_shadow_book_runs_its_own_discipline() {
+        let config = AbShadowConfig { enabled: true, queue_discipline: Some("priority_fee".to_string()), ..AbShadowConfig::default() };
+        let book = Plugins::new().book(config.queue_discipline.as_deref().unwrap()).unwrap();
+        let mut primary = OrderBook::<FifoLevel>::new();
+        let mut shadow = AbShadow::new(config, book, primary.open_orders());
+        send(&mut primary, &mut shadow, EngineCommand::SetClock(0));
+
+        // A fee moves the later order ahead on the shadow's levels only, so
+        // a taker fills a different maker there
+        send(&mut primary, &mut shadow, EngineCommand::Place(Order::new_limit(1, Side::Sell, 10, 101_0000, 0)));
+        send(&mut primary, &mut shadow, EngineCommand::Place(Order::new_limit(2, Side::Sell, 10, 101_0000, 0)));
+        send(&mut primary, &mut shadow, EngineCommand::PayPriorityFee { order_id: 2, fee: 5 });
+        send(&mut primary, &mut shadow, EngineCommand::Place(Order::new_market(3, Side::Buy, 10, 0)));
+        let report = shadow.report(&primary.depth_snapshot());
+        assert_eq!(report.first_divergence, Some(4));
+        assert_eq!((report.recent[0].primary.as_str(), report.recent[0].shadow.as_str()), ("fee 0", "fee 5"));
+        let fills = report.recent.iter().find(|divergence| divergence.command == 5).unwrap();
+        assert_eq!((fills.primary.as_str(), fills.shadow.as_str()), ("10 @ 101.0000 vs #1", "10 @ 101.0000 vs #2"));
+    }
+}
@@ -0,0 +1,237 @@
+//! Activity timeline
+//!
+//! Significant events of a run (market status changes such as halts, large
+//! trades, feed and gateway outages, memory pressure, flow throttling, errors, health alarms) are numbered and kept in a
+//! bounded [`ActivityLog`] so dashboards can show a timeline and catch up
+//! after a reconnect with `since=<seq>` instead of parsing logs.
+
+use std::collections::VecDeque;
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::data::MarketStatusType;
+use crate::memory::{shrink_deque, Compaction};
+use crate::types::{price_utils, Price, Qty, Trade};
+
+/// Activity log settings
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ActivityConfig {
+    /// Activity events kept for `GET /events`
+    pub capacity: usize,
+    /// Trades of at least this quantity are logged
+    pub large_trade_qty: Qty,
+}
+
+impl Default for ActivityConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 1000,
+            large_trade_qty: 500,
+        }
+    }
+}
+
+impl ActivityConfig {
+    /// Check the settings
+    pub fn validate(&self) -> Result<(), String> {
+        if self.capacity == 0 {
+            return Err("Activity log capacity must be greater than 0".to_string());
+        }
+        if self.large_trade_qty == 0 {
+            return Err("Large trade quantity must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// What an activity event is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityKind {
+    /// The market opened, closed, halted or changed session
+    MarketStatus,
+    /// A trade at or over the large-trade quantity
+    LargeTrade,
+    /// A data source was attached, detached or finished
+    DataSource,
+    /// The data feed went stale or resumed
+    Feed,
+    /// Memory pressure changed
+    Memory,
+    /// Order entry went down or came back in a simulated gateway outage
+    Gateway,
+    /// Synthetic order flow was throttled or restored with simulator load
+    Throttle,
+    /// A simulation step or client request failed
+    Error,
+    /// A health alarm fired or resolved
+    HealthAlarm,
+    /// The server degraded under sustained step overruns or recovered
+    Degradation,
+    /// A settlement cycle closed with an account that did not reconcile
+    Settlement,
+}
+
+/// One entry on the activity timeline
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Activity {
+    /// Position in the log; 0 until the log numbers it
+    pub seq: u64,
+    /// Wall-clock time it was logged (milliseconds since the epoch)
+    pub time_ms: u64,
+    /// Simulation time it happened at, when it comes from the market
+    #[serde(default)]
+    pub sim_time: Option<u128>,
+    pub kind: ActivityKind,
+    pub message: String,
+    /// New market status, for `MarketStatus` events
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<MarketStatusType>,
+    /// Trade price, for `LargeTrade` events
+    #[serde(default, skip_serializing_if = "Option::is_none", serialize_with = "crate::price_format::ticks")]
+    pub price: Option<Price>,
+    /// Trade quantity, for `LargeTrade` events
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub qty: Option<Qty>,
+}
+
+impl Activity {
+    /// Create an unnumbered activity event
+    pub fn new<S: Into<String>>(kind: ActivityKind, message: S) -> Self {
+        Self {
+            seq: 0,
+            time_ms: 0,
+            sim_time: None,
+            kind,
+            message: message.into(),
+            status: None,
+            price: None,
+            qty: None,
+        }
+    }
+
+    /// Set the simulation time it happened at
+    pub fn at(mut self, sim_time: u128) -> Self {
+        self.sim_time = Some(sim_time);
+        self
+    }
+
+    /// A market status change
+    pub fn market_status(status: MarketStatusType, sim_time: u128, message: Option<&str>) -> Self {
+        let text = match message {
+            Some(message) => format!("Market {:?}: {}", status, message),
+            None => format!("Market {:?}", status),
+        };
+        Self {
+            status: Some(status),
+            ..Self::new(ActivityKind::MarketStatus, text).at(sim_time)
+        }
+    }
+
+    /// A trade at or over the large-trade quantity
+    pub fn large_trade(trade: &Trade) -> Self {
+        let text = format!("Large {:?} trade: {} @ {}", trade.aggressor, trade.qty, price_utils::format(trade.price));
+        Self {
+            price: Some(trade.price),
+            qty: Some(trade.qty),
+            ..Self::new(ActivityKind::LargeTrade, text).at(trade.ts)
+        }
+    }
+}
+
+/// Recent activity events, numbered in order
+#[derive(Debug, Clone)]
+pub struct ActivityLog {
+    next_seq: u64,
+    events: VecDeque<Activity>,
+    capacity: usize,
+}
+
+impl ActivityLog {
+    /// Keep up to `capacity` events
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            next_seq: 1,
+            events: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Number an event, stamp it with `time_ms` and keep it
+    pub fn push(&mut self, mut activity: Activity, time_ms: u64) -> Activity {
+        activity.seq = self.next_seq;
+        activity.time_ms = time_ms;
+        self.next_seq += 1;
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back(activity.clone());
+        activity
+    }
+
+    /// Kept events numbered after `since`, or all of them
+    pub fn since(&self, since: Option<u64>) -> Vec<Activity> {
+        let since = since.unwrap_or(0);
+        let skip = self.events.partition_point(|activity| activity.seq <= since);
+        self.events.iter().skip(skip).cloned().collect()
+    }
+
+    /// The last `count` kept events, oldest first
+    pub fn recent(&self, count: usize) -> Vec<Activity> {
+        self.events.iter().skip(self.events.len().saturating_sub(count)).cloned().collect()
+    }
+
+    /// Sequence number of the last event, if any
+    pub fn last_seq(&self) -> Option<u64> {
+        self.events.back().map(|activity| activity.seq)
+    }
+
+    /// Release capacity beyond the log's budget
+    pub fn compact(&mut self) -> Compaction {
+        shrink_deque(&mut self.events, self.capacity)
+    }
+
+    /// Number of events kept
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Whether no events are kept
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_are_numbered_and_bounded() {
+        let mut log = ActivityLog::new(3);
+        for i in 0..5 {
+            let activity = log.push(Activity::new(ActivityKind::Error, format!("error {}", i)), 1_000 + i);
+            assert_eq!(activity.seq, i + 1);
+        }
+        assert_eq!(log.len(), 3);
+        assert_eq!(log.last_seq(), Some(5));
+
+        // Only the kept events after `since` come back
+        let seqs = |events: Vec<Activity>| events.iter().map(|activity| activity.seq).collect::<Vec<_>>();
+        assert_eq!(seqs(log.since(None)), vec![3, 4, 5]);
+        assert_eq!(seqs(log.since(Some(3))), vec![4, 5]);
+        assert_eq!(seqs(log.since(Some(5))), Vec::<u64>::new());
+        assert_eq!(log.since(Some(4))[0].time_ms, 1_004);
+    }
+
+    #[test]
+    fn test_market_status_activity() {
+        let activity = Activity::market_status(MarketStatusType::Halted, 42, Some("circuit breaker"));
+        assert_eq!(activity.kind, ActivityKind::MarketStatus);
+        assert_eq!(activity.sim_time, Some(42));
+        assert_eq!(activity.message, "Market Halted: circuit breaker");
+
+        let json = serde_json::to_value(&activity).unwrap();
+        assert_eq!(json["kind"], "market_status");
+        assert!(ActivityConfig { capacity: 0, ..ActivityConfig::default() }.validate().is_err());
+    }
+}
@@ -0,0 +1,623 @@
+//! Adversarial message bursts for robustness testing
+//!
+//! An [`AdversaryAgent`] is a synthetic agent, registered as the `adversary`
+//! kind, that floods order entry the way a misbehaving participant would:
+//! quote stuffing (orders pulled as soon as they are placed), cancel storms
+//! (a burst of quotes, then cancels of them and of orders long gone) and
+//! deep-book spam (one-lot orders on ever more distant levels that are never
+//! pulled). [`run`] adds one to a server's simulator through
+//! `POST /admin/agents`, raises its intensity stage by stage through
+//! `PATCH /admin/agents/:id`, and steps the simulator so every message goes
+//! through the gateway's rate limit and the engine, with each snapshot
+//! broadcast and handed to a [`FeedPublisher`]. It reports the first stage
+//! at which each of them degraded.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use axum::body::Body;
+use axum::http::{header, Method, Request};
+use axum::Router;
+use rand::rngs::StdRng;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tower::ServiceExt;
+use crate::agents::{AgentOrder, MarketView, SyntheticAgent};
+use crate::engine::{DepthSnapshot, OrderBook, OrderBookEngine};
+use crate::error::{EngineError, EngineResult};
+use crate::feed::FeedFormat;
+use crate::gateway::BusyConfig;
+use crate::generator::HftWorkload;
+use crate::publish::{FeedPublisher, FeedSink, PublisherConfig, SendFuture};
+use crate::server::{create_router, AppState, ServerDiscipline};
+use crate::sim::{Agent, NetModel, OrderGenerationConfig, Simulator};
+use crate::stress::LatencyHistogram;
+use crate::types::{order_ids, price_utils, Price, Side};
+
+/// Kind the adversary is registered as in [`crate::agents::AgentRegistry`]
+pub const ADVERSARY_KIND: &str = "adversary";
+/// Costs below this are timer noise, so a stage is never compared against a
+/// smaller baseline
+const NOISE_FLOOR_NS: u64 = 1_000;
+
+/// Shape of the message bursts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AdversaryPattern {
+    /// Quotes near the touch, each cancelled right after it is placed
+    #[default]
+    QuoteStuffing,
+    /// Half a burst of quotes, then cancels of them and of orders already gone
+    CancelStorm,
+    /// One-lot orders on new levels beyond the deepest on the book
+    DeepBookSpam,
+}
+
+impl AdversaryPattern {
+    /// Name on the command line and in reports
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::QuoteStuffing => "quote-stuffing",
+            Self::CancelStorm => "cancel-storm",
+            Self::DeepBookSpam => "deep-book-spam",
+        }
+    }
+}
+
+impl FromStr for AdversaryPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('_', "-").as_str() {
+            "quote-stuffing" => Ok(Self::QuoteStuffing),
+            "cancel-storm" => Ok(Self::CancelStorm),
+            "deep-book-spam" => Ok(Self::DeepBookSpam),
+            _ => Err(format!(
+                "Unknown adversary pattern: {} (expected quote-stuffing, cancel-storm or deep-book-spam)", s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for AdversaryPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Parameters of an `adversary` agent entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AdversaryParams {
+    pub pattern: AdversaryPattern,
+    /// Messages per step, orders and cancels together
+    pub intensity: u32,
+}
+
+impl Default for AdversaryParams {
+    fn default() -> Self {
+        Self { pattern: AdversaryPattern::default(), intensity: 4 }
+    }
+}
+
+impl AdversaryParams {
+    /// Check the intensity
+    pub fn validate(&self) -> Result<(), String> {
+        if self.intensity == 0 {
+            return Err("Adversary intensity must be at least 1 message per step".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Sends a burst of order entry messages in one pattern every step
+pub struct AdversaryAgent {
+    params: AdversaryParams,
+    rng: StdRng,
+    tick: Price,
+}
+
+impl AdversaryAgent {
+    pub fn new(params: AdversaryParams, rng: StdRng) -> Self {
+        Self { params, rng, tick: price_utils::from_f64(0.01) }
+    }
+
+    /// Pattern of the bursts
+    pub fn pattern(&self) -> AdversaryPattern {
+        self.params.pattern
+    }
+
+    /// Mid the quotes are placed around, or [`HftWorkload`]'s on an empty book
+    fn reference(&self, market: &MarketView<'_>) -> Price {
+        price_utils::from_f64(market.mid.unwrap_or(100.0))
+    }
+
+    /// A one-lot-or-more quote `offset` ticks from the mid on a random side
+    fn quote(&mut self, market: &MarketView<'_>, offset: Price) -> AgentOrder {
+        let side = if self.rng.gen_bool(0.5) { Side::Buy } else { Side::Sell };
+        let mid = self.reference(market);
+        let price = match side {
+            Side::Buy => mid.saturating_sub(offset * self.tick).max(1),
+            Side::Sell => mid + offset * self.tick,
+        };
+        AgentOrder::limit(side, market.whole_lots(100), price)
+    }
+
+    /// One-lot orders on `count` new levels past the deepest of each side
+    fn spam(&mut self, market: &MarketView<'_>, count: usize) -> Vec<AgentOrder> {
+        let mid = self.reference(market);
+        let mut next_bid = market.book.bids.last().map_or(mid, |level| level.price).saturating_sub(self.tick);
+        let mut next_ask = market.book.asks.last().map_or(mid, |level| level.price) + self.tick;
+        let lot = market.whole_lots(1);
+        let mut orders = Vec::with_capacity(count);
+        for _ in 0..count {
+            if next_bid > 0 && self.rng.gen_bool(0.5) {
+                orders.push(AgentOrder::limit(Side::Buy, lot, next_bid));
+                next_bid = next_bid.saturating_sub(self.tick);
+            } else {
+                orders.push(AgentOrder::limit(Side::Sell, lot, next_ask));
+                next_ask += self.tick;
+            }
+        }
+        orders
+    }
+}
+
+impl SyntheticAgent for AdversaryAgent {
+    fn role(&self) -> Agent {
+        Agent::MarketMaker
+    }
+
+    fn on_step(&mut self, market: &MarketView<'_>) -> Vec<AgentOrder> {
+        let messages = self.params.intensity as usize;
+        match self.params.pattern {
+            AdversaryPattern::QuoteStuffing => (0..messages.div_ceil(2))
+                .map(|_| {
+                    let offset = self.rng.gen_range(1..=3);
+                    self.quote(market, offset)
+                })
+                .collect(),
+            AdversaryPattern::CancelStorm => (0..messages / 2)
+                .map(|_| {
+                    let offset = self.rng.gen_range(1..=3);
+                    self.quote(market, offset)
+                })
+                .collect(),
+            AdversaryPattern::DeepBookSpam => self.spam(market, messages),
+        }
+    }
+
+    fn on_cancel(&mut self, _market: &MarketView<'_>) -> usize {
+        let messages = self.params.intensity as usize;
+        match self.params.pattern {
+            AdversaryPattern::QuoteStuffing => messages / 2,
+            AdversaryPattern::CancelStorm => messages - messages / 2,
+            AdversaryPattern::DeepBookSpam => 0,
+        }
+    }
+}
+
+/// Part of the system under test
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Component {
+    /// Order entry rate limit at the simulator's gateway
+    RateLimiter,
+    /// Simulator steps carrying the messages through the engine
+    CommandPipeline,
+    /// Snapshot broadcast and the feed publisher
+    Publisher,
+}
+
+impl fmt::Display for Component {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::RateLimiter => "rate limiter",
+            Self::CommandPipeline => "command pipeline",
+            Self::Publisher => "publisher",
+        })
+    }
+}
+
+/// Adversarial run settings
+#[derive(Debug, Clone)]
+pub struct AdversaryConfig {
+    pub pattern: AdversaryPattern,
+    /// Messages per burst at the first stage
+    pub intensity: u32,
+    /// Factor the intensity grows by from one stage to the next
+    pub ramp: f64,
+    /// Number of stages
+    pub stages: u32,
+    /// Bursts sent at each stage, one per simulator step
+    pub bursts_per_stage: u32,
+    /// Mean simulation time between bursts (microseconds)
+    pub burst_interval_us: u64,
+    /// Order entry rate limit the bursts go through
+    pub rate_limit: BusyConfig,
+    /// Encoding of the feed messages published after each burst
+    pub format: FeedFormat,
+    /// Command or publish cost, at the 99th percentile, that counts as
+    /// degraded, as a multiple of the first stage's
+    pub latency_factor: f64,
+    pub seed: u64,
+}
+
+impl Default for AdversaryConfig {
+    fn default() -> Self {
+        Self {
+            pattern: AdversaryPattern::default(),
+            intensity: 4,
+            ramp: 2.0,
+            stages: 6,
+            bursts_per_stage: 50,
+            burst_interval_us: 10_000,
+            rate_limit: BusyConfig { enabled: true, ..BusyConfig::default() },
+            format: FeedFormat::Json,
+            latency_factor: 4.0,
+            seed: 42,
+        }
+    }
+}
+
+impl AdversaryConfig {
+    /// Check the ramp and thresholds
+    pub fn validate(&self) -> Result<(), String> {
+        if self.intensity == 0 {
+            return Err("Adversary intensity must be at least 1 message per burst".to_string());
+        }
+        if !(self.ramp >= 1.0 && self.ramp.is_finite()) {
+            return Err("Adversary ramp must be at least 1".to_string());
+        }
+        if self.stages == 0 || self.bursts_per_stage == 0 {
+            return Err("Adversary runs need at least one stage and one burst per stage".to_string());
+        }
+        if self.burst_interval_us == 0 {
+            return Err("Adversary burst interval must be greater than 0".to_string());
+        }
+        if !(self.latency_factor > 1.0 && self.latency_factor.is_finite()) {
+            return Err("Adversary latency factor must be greater than 1".to_string());
+        }
+        if self.rate_limit.enabled {
+            self.rate_limit.validate()?;
+        }
+        Ok(())
+    }
+
+    /// Messages per burst at `stage`, counting from 0
+    pub fn intensity_at(&self, stage: u32) -> u32 {
+        (self.intensity as f64 * self.ramp.powi(stage as i32)).round().min(u32::MAX as f64) as u32
+    }
+
+    /// Most messages the rate limit may let through in `elapsed_ns` of
+    /// simulation time: its sustained rate plus a full burst banked at the
+    /// start, or `None` without a rate limit
+    pub fn allowance(&self, elapsed_ns: u128) -> Option<u64> {
+        self.rate_limit.enabled.then(|| {
+            (self.rate_limit.orders_per_sec * elapsed_ns as f64 / 1e9).ceil() as u64 + self.rate_limit.burst as u64
+        })
+    }
+}
+
+/// What one stage of bursts did to the system
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct StageReport {
+    pub stage: u32,
+    /// Messages per burst
+    pub intensity: u32,
+    /// Messages sent
+    pub messages: u64,
+    /// Messages the rate limiter refused
+    pub throttled: u64,
+    /// Messages the rate limiter let through
+    pub admitted: u64,
+    /// Most messages the rate limit allows over the stage's simulation time,
+    /// if there is one
+    pub allowed: Option<u64>,
+    /// Step time per message sent
+    pub command_p99_ns: u64,
+    pub command_max_ns: u64,
+    /// Time to broadcast one snapshot and publish its feed messages
+    pub publish_p99_ns: u64,
+    pub publish_max_ns: u64,
+    /// Feed messages published and their encoded size
+    pub feed_messages: u64,
+    pub feed_bytes: u64,
+    /// Price levels on the book at the end of the stage
+    pub book_levels: usize,
+}
+
+impl StageReport {
+    /// Share of the messages the rate limiter refused
+    pub fn throttled_share(&self) -> f64 {
+        if self.messages == 0 {
+            return 0.0;
+        }
+        self.throttled as f64 / self.messages as f64
+    }
+
+    /// Messages let through beyond what the rate limit allows
+    pub fn leaked(&self) -> u64 {
+        self.allowed.map_or(0, |allowed| self.admitted.saturating_sub(allowed))
+    }
+}
+
+/// A component that first degraded at a stage
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Degradation {
+    pub component: Component,
+    pub stage: u32,
+    /// Messages per burst at the stage
+    pub intensity: u32,
+    pub detail: String,
+}
+
+impl fmt::Display for Degradation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} degraded at stage {} ({} messages per burst): {}", self.component, self.stage, self.intensity, self.detail)
+    }
+}
+
+/// Every stage of an adversarial run and where the system degraded
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AdversaryReport {
+    pub stages: Vec<StageReport>,
+    /// The first degradation of each component, in the order they happened
+    pub degradations: Vec<Degradation>,
+}
+
+impl AdversaryReport {
+    /// The component that gave way first
+    pub fn first_degraded(&self) -> Option<&Degradation> {
+        self.degradations.first()
+    }
+
+    /// When `component` first degraded
+    pub fn degradation(&self, component: Component) -> Option<&Degradation> {
+        self.degradations.iter().find(|degradation| degradation.component == component)
+    }
+
+    /// Record the components `stage` degraded for the first time, against
+    /// the first stage's costs
+    ///
+    /// Throttling is the rate limiter doing its job; it degrades when it
+    /// lets through more than its rate allows.
+    fn assess(&mut self, config: &AdversaryConfig, stage: &StageReport) {
+        let baseline = self.stages.first().copied().unwrap_or(*stage);
+        let slower = |actual: u64, base: u64| actual as f64 > base.max(NOISE_FLOOR_NS) as f64 * config.latency_factor;
+        let checks = [
+            (Component::RateLimiter, stage.leaked() > 0,
+             format!("{} messages admitted where the rate allows {}", stage.admitted, stage.allowed.unwrap_or_default())),
+            (Component::CommandPipeline, slower(stage.command_p99_ns, baseline.command_p99_ns),
+             format!("command p99 {}ns against {}ns at stage {}", stage.command_p99_ns, baseline.command_p99_ns, baseline.stage)),
+            (Component::Publisher, slower(stage.publish_p99_ns, baseline.publish_p99_ns),
+             format!("publish p99 {}ns against {}ns at stage {}", stage.publish_p99_ns, baseline.publish_p99_ns, baseline.stage)),
+        ];
+        for (component, degraded, detail) in checks {
+            if degraded && self.degradation(component).is_none() {
+                self.degradations.push(Degradation { component, stage: stage.stage, intensity: stage.intensity, detail });
+            }
+        }
+    }
+}
+
+/// Counts the bytes the publisher sends, in place of a broker
+struct CountingSink(Arc<AtomicU64>);
+
+impl FeedSink for CountingSink {
+    fn send<'a>(&'a mut self, _topic: &'a str, payload: Vec<u8>) -> SendFuture<'a> {
+        self.0.fetch_add(payload.len() as u64, Ordering::Relaxed);
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Send a JSON admin request through the router, returning the reply body
+async fn admin_request(router: &Router, method: Method, uri: &str, body: serde_json::Value) -> EngineResult<serde_json::Value> {
+    let request = Request::builder()
+        .method(method.clone())
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .map_err(|e| EngineError::internal(e.to_string()))?;
+    let response = router.clone().oneshot(request).await.map_err(|e| EngineError::internal(e.to_string()))?;
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await
+        .map_err(|e| EngineError::network(e.to_string()))?;
+    if !status.is_success() {
+        return Err(EngineError::reject(format!("{} {} returned {}: {}", method, uri, status, String::from_utf8_lossy(&body))));
+    }
+    serde_json::from_slice(&body).map_err(|e| EngineError::serialization(e.to_string()))
+}
+
+/// Messages the gateway has throttled and admitted, and the simulation time
+async fn gateway_counts<D: ServerDiscipline>(state: &AppState<D>) -> (u64, u64, u128) {
+    state.simulator.call(|simulator| {
+        let stats = simulator.gateway_stats();
+        (stats.throttled, stats.admitted, simulator.current_time())
+    }).await
+}
+
+/// Seed `book`, serve it, and flood the server's order entry with an
+/// adversary agent stage by stage, calling `on_stage` after each, and return
+/// the whole run's report
+///
+/// Orders the gateway or the engine refuses are the point, not failures;
+/// only a failure to seed the book, reach the admin API or step the
+/// simulator stops the run.
+pub async fn run<D: ServerDiscipline>(
+    mut book: OrderBook<D>,
+    config: &AdversaryConfig,
+    mut on_stage: impl FnMut(&StageReport),
+) -> EngineResult<AdversaryReport> {
+    // Seed orders take historical ids so the simulator's own never collide
+    for mut order in HftWorkload::new().with_seed(config.seed).stream().seed_book() {
+        order.id = order_ids::historical(order.id)
+            .ok_or_else(|| EngineError::internal(format!("Seed order id {} is out of range", order.id)))?;
+        book.place(order)?;
+    }
+
+    let mut net = NetModel::new(0, 0, 0.0, 0.0);
+    if config.rate_limit.enabled {
+        net = net.with_busy(config.rate_limit);
+    }
+    let generation = OrderGenerationConfig {
+        mean_order_interval_ns: config.burst_interval_us * 1_000,
+        ..OrderGenerationConfig::default()
+    };
+    let simulator = Simulator::with_seed(book, config.seed)
+        .with_network_model(net)
+        .with_order_generation_config(generation);
+
+    let bytes = Arc::new(AtomicU64::new(0));
+    let publisher_config = PublisherConfig { format: config.format, ..PublisherConfig::default() };
+    let mut publisher = FeedPublisher::with_sink(publisher_config, Box::new(CountingSink(bytes.clone())));
+    let state = AppState::new(simulator)
+        .with_delta_feed(publisher.sequencer())
+        .with_price_alerts(publisher.alerts());
+    let router = create_router(state.clone());
+    let mut snapshots = state.subscribe();
+
+    let params = AdversaryParams { pattern: config.pattern, intensity: config.intensity_at(0) };
+    let spec = serde_json::json!({ "kind": ADVERSARY_KIND, "seed": config.seed, "params": params });
+    let added = admin_request(&router, Method::POST, "/admin/agents", spec).await?;
+    let agent_id = added["agents"][0]["id"].as_u64()
+        .ok_or_else(|| EngineError::internal("Adding the adversary returned no agent"))?;
+
+    let mut report = AdversaryReport::default();
+    for stage in 0..config.stages {
+        let intensity = config.intensity_at(stage);
+        if stage > 0 {
+            let update = serde_json::json!({ "params": { "intensity": intensity } });
+            admin_request(&router, Method::PATCH, &format!("/admin/agents/{}", agent_id), update).await?;
+        }
+        let mut result = StageReport { stage, intensity, ..StageReport::default() };
+        let mut commands = LatencyHistogram::new();
+        let mut publishes = LatencyHistogram::new();
+        let (throttled_before, admitted_before, started) = gateway_counts(&state).await;
+        let (published_before, bytes_before) = (publisher.published(), bytes.load(Ordering::Relaxed));
+
+        for _ in 0..config.bursts_per_stage {
+            let start = Instant::now();
+            let snapshot = state.simulator.call(|simulator| {
+                let trades = simulator.step()?;
+                Ok::<_, EngineError>(DepthSnapshot { trades, ..simulator.snapshot() })
+            }).await?;
+            commands.record(start.elapsed().as_nanos() as u64 / intensity as u64);
+            result.messages += intensity as u64;
+            result.book_levels = snapshot.bids.len() + snapshot.asks.len();
+
+            let start = Instant::now();
+            state.broadcast_snapshot(snapshot).await;
+            while let Ok(snapshot) = snapshots.try_recv() {
+                publisher.publish_snapshot(&snapshot).await.map_err(|e| EngineError::network(e.to_string()))?;
+            }
+            publishes.record(start.elapsed().as_nanos() as u64);
+        }
+
+        let (throttled, admitted, ended) = gateway_counts(&state).await;
+        result.throttled = throttled - throttled_before;
+        result.admitted = admitted - admitted_before;
+        result.allowed = config.allowance(ended.saturating_sub(started));
+        result.feed_messages = publisher.published() - published_before;
+        result.feed_bytes = bytes.load(Ordering::Relaxed) - bytes_before;
+        result.command_p99_ns = commands.percentile(0.99);
+        result.command_max_ns = commands.max();
+        result.publish_p99_ns = publishes.percentile(0.99);
+        result.publish_max_ns = publishes.max();
+        report.assess(config, &result);
+        report.stages.push(result);
+        on_stage(&result);
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use crate::types::LotSize;
+    use crate::queue_fifo::FifoLevel;
+
+    async fn run_pattern(pattern: AdversaryPattern, rate_limit: bool) -> AdversaryReport {
+        let mut config = AdversaryConfig { pattern, stages: 4, bursts_per_stage: 30, ..AdversaryConfig::default() };
+        config.rate_limit.enabled = rate_limit;
+        config.validate().unwrap();
+        let mut stages = 0;
+        let report = run(OrderBook::<FifoLevel>::new(), &config, |_| stages += 1).await.unwrap();
+        assert_eq!(stages, 4);
+        report
+    }
+
+    #[test]
+    fn test_bursts_follow_their_pattern() {
+        let mut book = OrderBook::<FifoLevel>::new();
+        for order in HftWorkload::new().with_seed(1).stream().seed_book() {
+            book.place(order).unwrap();
+        }
+        let depth = book.snapshot();
+        let market = MarketView {
+            ts: 0,
+            best_bid: depth.bids.first().map(|level| level.price),
+            best_ask: depth.asks.first().map(|level| level.price),
+            mid: Some(100.0),
+            inventory: 0,
+            lot_size: LotSize::default(),
+            book: &depth,
+        };
+        let agent = |pattern, intensity| AdversaryAgent::new(AdversaryParams { pattern, intensity }, StdRng::seed_from_u64(1));
+
+        // Stuffing pulls every quote but the odd one out in the same step
+        let mut stuffing = agent(AdversaryPattern::QuoteStuffing, 7);
+        assert_eq!(stuffing.on_step(&market).len(), 4);
+        assert_eq!(stuffing.on_cancel(&market), 3);
+
+        // A storm cancels more than it placed
+        let mut storm = agent(AdversaryPattern::CancelStorm, 10);
+        assert_eq!(storm.on_step(&market).len(), 5);
+        assert_eq!(storm.on_cancel(&market), 5);
+        assert_eq!(agent(AdversaryPattern::CancelStorm, 1).on_cancel(&market), 1);
+
+        // Spam lands past the deepest level on either side, one level each
+        let mut spam = agent(AdversaryPattern::DeepBookSpam, 8);
+        let orders = spam.on_step(&market);
+        assert_eq!(orders.len(), 8);
+        assert_eq!(spam.on_cancel(&market), 0);
+        let deepest_bid = depth.bids.last().unwrap().price;
+        let deepest_ask = depth.asks.last().unwrap().price;
+        assert!(orders.iter().all(|order| order.qty == 1 && match order.side {
+            Side::Buy => order.price.unwrap() < deepest_bid,
+            Side::Sell => order.price.unwrap() > deepest_ask,
+        }));
+        let mut prices: Vec<_> = orders.iter().map(|order| order.price).collect();
+        prices.sort_unstable();
+        prices.dedup();
+        assert_eq!(prices.len(), 8);
+
+        assert!("cancel_storm".parse::<AdversaryPattern>().is_ok());
+        assert!("flood".parse::<AdversaryPattern>().is_err());
+        assert!(AdversaryParams { intensity: 0, ..AdversaryParams::default() }.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_reports_where_the_system_degraded() {
+        // Unthrottled stuffing takes the ramp through the registered agent
+        let report = run_pattern(AdversaryPattern::QuoteStuffing, false).await;
+        assert_eq!(report.stages.len(), 4);
+        assert_eq!(report.stages.iter().map(|stage| stage.intensity).collect::<Vec<_>>(), vec![4, 8, 16, 32]);
+        assert!(report.stages.iter().all(|stage| stage.throttled == 0 && stage.allowed.is_none()));
+        assert!(report.degradation(Component::RateLimiter).is_none());
+
+        // The rate limit refuses the later stages but never leaks past its rate
+        let report = run_pattern(AdversaryPattern::QuoteStuffing, true).await;
+        assert_eq!(report.stages[0].throttled, 0);
+        assert!(report.stages[3].throttled > 0);
+        assert!(report.stages.iter().all(|stage| stage.admitted <= stage.allowed.unwrap()));
+        assert!(report.degradation(Component::RateLimiter).is_none());
+
+        // Spam deepens the book, and the publisher sends out every new level
+        let report = run_pattern(AdversaryPattern::DeepBookSpam, false).await;
+        assert!(report.stages.windows(2).all(|pair| pair[1].book_levels > pair[0].book_levels));
+        assert!(report.stages.iter().all(|stage| stage.feed_messages >= 30 && stage.feed_bytes > 0));
+    }
+}
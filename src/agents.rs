@@ -0,0 +1,576 @@
+//! Config-driven synthetic agent population
+//!
+//! Each `[[agents]]` entry in the config names a kind, how many copies to run,
+//! a seed and kind-specific parameters. An [`AgentRegistry`] maps kinds to
+//! factories and builds the population at startup, so experiments can change
+//! who trades without code changes. The built-in `market_maker` and `taker`
+//! kinds take the `[market_maker]` and `[order_generation]` sections as
+//! defaults and apply an entry's `params` on top. The `adversary` kind
+//! floods order entry for robustness testing; see [`crate::adversary`].
+//!
+//! ```toml
+//! [[agents]]
+//! kind = "market_maker"
+//! count = 3
+//! seed = 7
+//! params = { target_spread = 200, order_size = 50 }
+//!
+//! [[agents]]
+//! kind = "taker"
+//! params = { buy_probability = 0.6 }
+//! data_path = { base_latency_ns = 2000000 }
+//! ```
+//!
+//! `ack_path` and `data_path` give an entry its own latency for fill reports
+//! and market data in place of the `[network]` ones.
+
+use std::collections::BTreeMap;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use serde::de::DeserializeOwned;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use crate::adversary::{AdversaryAgent, AdversaryParams, ADVERSARY_KIND};
+use crate::cost_model::CostModel;
+use crate::engine::DepthSnapshot;
+use crate::gateway::GatewayEvent;
+use crate::latency::LatencyCost;
+use crate::sim::{Agent, BandStatus, MarketMakerConfig, OrderGenerationConfig, PathLatency};
+use crate::types::{LotSize, Metrics, Price, Qty, Side, price_utils};
+
+/// Identifier of an agent in a running simulation
+pub type AgentId = u64;
+
+/// One entry of the configured agent population
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct AgentSpec {
+    /// Registered agent kind, e.g. `market_maker` or `taker`
+    pub kind: String,
+    /// Copies of this agent to run
+    #[serde(default = "default_count")]
+    pub count: usize,
+    /// Seed for the first copy; later copies take the following seeds.
+    /// Derived from the simulation seed if unset
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Kind-specific settings
+    #[serde(default)]
+    #[schemars(with = "BTreeMap<String, serde_json::Value>")]
+    pub params: toml::Table,
+    /// Latency of its fill reports, in place of the network model's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ack_path: Option<PathLatency>,
+    /// Latency of the market data it sees, in place of the network model's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_path: Option<PathLatency>,
+    /// Self-match prevention group; its agents never trade with others in
+    /// the group when `[self_match]` is enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stp_group: Option<String>,
+}
+
+fn default_count() -> usize {
+    1
+}
+
+impl AgentSpec {
+    /// One agent of `kind` with default parameters
+    pub fn new(kind: impl Into<String>) -> Self {
+        Self {
+            kind: kind.into(),
+            count: default_count(),
+            seed: None,
+            params: toml::Table::new(),
+            ack_path: None,
+            data_path: None,
+            stp_group: None,
+        }
+    }
+
+    /// Run `count` copies
+    pub fn with_count(mut self, count: usize) -> Self {
+        self.count = count;
+        self
+    }
+
+    /// Seed the first copy with `seed`
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Set one kind-specific parameter
+    pub fn with_param(mut self, key: impl Into<String>, value: impl Into<toml::Value>) -> Self {
+        self.params.insert(key.into(), value.into());
+        self
+    }
+
+    /// Report its fills after `ack_path` latency
+    pub fn with_ack_path(mut self, ack_path: PathLatency) -> Self {
+        self.ack_path = Some(ack_path);
+        self
+    }
+
+    /// Show it market data after `data_path` latency
+    pub fn with_data_path(mut self, data_path: PathLatency) -> Self {
+        self.data_path = Some(data_path);
+        self
+    }
+
+    /// Put its agents in a self-match prevention group
+    pub fn with_stp_group(mut self, group: impl Into<String>) -> Self {
+        self.stp_group = Some(group.into());
+        self
+    }
+
+    /// Check the entry, independent of which kinds are registered
+    pub fn validate(&self) -> Result<(), String> {
+        if self.kind.is_empty() {
+            return Err("Agent kind cannot be empty".to_string());
+        }
+        if self.count == 0 {
+            return Err(format!("Agent count for '{}' must be greater than 0", self.kind));
+        }
+        Ok(())
+    }
+}
+
+/// A built agent and the single-copy entry it was built from
+pub struct AgentInstance {
+    /// Entry with a count of one and the seed the agent was built with
+    pub spec: AgentSpec,
+    pub agent: Box<dyn SyntheticAgent>,
+}
+
+/// Activity and fills of one agent in a running simulation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentStats {
+    pub id: AgentId,
+    pub kind: String,
+    /// Role its fills are attributed to
+    pub role: Agent,
+    pub paused: bool,
+    /// Orders accepted by the engine
+    pub orders: u64,
+    /// Inventory, cash and maker/taker PnL from its own fills
+    pub metrics: Metrics,
+    /// Latency of its orders and what it cost them, under latency accounting
+    #[serde(default)]
+    pub latency: LatencyCost,
+    /// Inventory band it quotes in, for agents with inventory bands
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inventory_band: Option<BandStatus>,
+}
+
+/// Agent population error types
+#[derive(Debug, thiserror::Error)]
+pub enum AgentError {
+    #[error("Unknown agent kind '{kind}', expected one of: {known}")]
+    UnknownKind { kind: String, known: String },
+
+    #[error("Invalid parameters for {kind} agent: {message}")]
+    InvalidParams { kind: String, message: String },
+}
+
+/// The book as an agent sees it when its turn comes in a step
+#[derive(Debug, Clone, Copy)]
+pub struct MarketView<'a> {
+    /// Simulation time
+    pub ts: u128,
+    pub best_bid: Option<Price>,
+    pub best_ask: Option<Price>,
+    /// Quoted mid price
+    pub mid: Option<f64>,
+    /// Net position from the fills reported to this agent so far
+    pub inventory: i64,
+    /// Lot size the engine enforces
+    pub lot_size: LotSize,
+    /// Visible depth
+    pub book: &'a DepthSnapshot,
+}
+
+impl MarketView<'_> {
+    /// Fit a size to whole lots, never going below one lot
+    pub fn whole_lots(&self, qty: Qty) -> Qty {
+        self.lot_size.round_down(qty).max(self.lot_size.size)
+    }
+}
+
+/// An order an agent wants placed; the simulator assigns its id and time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AgentOrder {
+    pub side: Side,
+    pub qty: Qty,
+    /// Limit price, or `None` for a market order
+    pub price: Option<Price>,
+}
+
+impl AgentOrder {
+    pub fn limit(side: Side, qty: Qty, price: Price) -> Self {
+        Self { side, qty, price: Some(price) }
+    }
+
+    pub fn market(side: Side, qty: Qty) -> Self {
+        Self { side, qty, price: None }
+    }
+}
+
+/// A two-sided quote an agent wants in the book in place of its last one;
+/// a missing leg pulls that side
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AgentQuote {
+    /// Bid price and quantity
+    pub bid: Option<(Price, Qty)>,
+    /// Ask price and quantity
+    pub ask: Option<(Price, Qty)>,
+}
+
+/// A synthetic participant that decides what to trade each step
+pub trait SyntheticAgent: Send {
+    /// Role this agent's fills are attributed to
+    fn role(&self) -> Agent;
+
+    /// Orders to submit this step
+    fn on_step(&mut self, market: &MarketView<'_>) -> Vec<AgentOrder>;
+
+    /// Two-sided quote to replace this agent's last one with, after this
+    /// step's orders; agents that only send orders keep the default
+    ///
+    /// Quotes skip the order latency model and are never held by the
+    /// gateway: a stale quote is worth nothing.
+    fn on_quote(&mut self, _market: &MarketView<'_>) -> Option<AgentQuote> {
+        None
+    }
+
+    /// Number of its own resting orders to pull, newest first, after this
+    /// step's orders and quote; agents that never cancel keep the default
+    ///
+    /// Each cancel passes the gateway like an order. Once none of its
+    /// orders are left, the last one pulled is cancelled again.
+    fn on_cancel(&mut self, _market: &MarketView<'_>) -> usize {
+        0
+    }
+
+    /// The gateway went down, came back, or refused one of this agent's
+    /// orders during an outage; agents that keep sending ignore this
+    fn on_gateway(&mut self, _event: &GatewayEvent<AgentOrder>) {}
+
+    /// Inventory band it quotes in, for agents that quote by band
+    fn inventory_band(&self) -> Option<BandStatus> {
+        None
+    }
+}
+
+/// Builds one agent from an entry's parameters and its own random generator
+pub type AgentFactory = Box<dyn Fn(&toml::Table, StdRng) -> Result<Box<dyn SyntheticAgent>, String> + Send + Sync>;
+
+/// Agent factories by kind
+#[derive(Default)]
+pub struct AgentRegistry {
+    factories: BTreeMap<String, AgentFactory>,
+}
+
+impl AgentRegistry {
+    /// An empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The built-in `market_maker`, `taker` and `adversary` kinds, the first
+    /// two defaulting to the given configs
+    pub fn builtin(market_maker: &MarketMakerConfig, order_generation: &OrderGenerationConfig) -> Self {
+        let mut registry = Self::new();
+        let base = market_maker.clone();
+        registry.register("market_maker", move |params, rng| {
+            let config: MarketMakerConfig = with_overrides(&base, params)?;
+            config.validate()?;
+            Ok(Box::new(MarketMakerAgent::new(config, rng)) as Box<dyn SyntheticAgent>)
+        });
+        let base = order_generation.clone();
+        registry.register("taker", move |params, rng| {
+            let config: OrderGenerationConfig = with_overrides(&base, params)?;
+            config.validate()?;
+            Ok(Box::new(TakerAgent::new(config, rng)) as Box<dyn SyntheticAgent>)
+        });
+        registry.register(ADVERSARY_KIND, |params, rng| {
+            let params: AdversaryParams = with_overrides(&AdversaryParams::default(), params)?;
+            params.validate()?;
+            Ok(Box::new(AdversaryAgent::new(params, rng)) as Box<dyn SyntheticAgent>)
+        });
+        registry
+    }
+
+    /// Register or replace the factory for `kind`
+    pub fn register<F>(&mut self, kind: impl Into<String>, factory: F) -> &mut Self
+    where
+        F: Fn(&toml::Table, StdRng) -> Result<Box<dyn SyntheticAgent>, String> + Send + Sync + 'static,
+    {
+        self.factories.insert(kind.into(), Box::new(factory));
+        self
+    }
+
+    /// Registered kinds, sorted
+    pub fn kinds(&self) -> impl Iterator<Item = &str> {
+        self.factories.keys().map(String::as_str)
+    }
+
+    /// Build every copy of every entry, in order
+    ///
+    /// Entries without a seed derive one per copy from `base_seed`, so a
+    /// population is reproducible from the simulation seed alone.
+    pub fn build(&self, specs: &[AgentSpec], base_seed: u64) -> Result<Vec<AgentInstance>, AgentError> {
+        let mut agents = Vec::new();
+        for spec in specs {
+            for copy in 0..spec.count {
+                let seed = match spec.seed {
+                    Some(seed) => seed.wrapping_add(copy as u64),
+                    None => derived_seed(base_seed, agents.len()),
+                };
+                agents.push(self.create(&AgentSpec { count: 1, seed: Some(seed), ..spec.clone() })?);
+            }
+        }
+        Ok(agents)
+    }
+
+    /// Build a single agent from an entry, seeding it with the entry's seed or 0
+    pub fn create(&self, spec: &AgentSpec) -> Result<AgentInstance, AgentError> {
+        let factory = self.factories.get(&spec.kind).ok_or_else(|| AgentError::UnknownKind {
+            kind: spec.kind.clone(),
+            known: self.kinds().collect::<Vec<_>>().join(", "),
+        })?;
+        let agent = factory(&spec.params, StdRng::seed_from_u64(spec.seed.unwrap_or_default()))
+            .map_err(|message| AgentError::InvalidParams { kind: spec.kind.clone(), message })?;
+        Ok(AgentInstance { spec: AgentSpec { count: 1, ..spec.clone() }, agent })
+    }
+}
+
+/// Seed for the `index`th agent of a population seeded with `base_seed`
+fn derived_seed(base_seed: u64, index: usize) -> u64 {
+    base_seed ^ (index as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+}
+
+/// `base` with the keys in `params` replaced
+fn with_overrides<T: Serialize + DeserializeOwned>(base: &T, params: &toml::Table) -> Result<T, String> {
+    let mut table = toml::Table::try_from(base).map_err(|e| e.to_string())?;
+    table.extend(params.clone());
+    table.try_into().map_err(|e: toml::de::Error| e.message().to_string())
+}
+
+/// Quotes both sides around the mid, skewed against its inventory and
+/// wider and smaller in each inventory band it reaches
+pub struct MarketMakerAgent {
+    config: MarketMakerConfig,
+    rng: StdRng,
+    band: BandStatus,
+}
+
+impl MarketMakerAgent {
+    pub fn new(config: MarketMakerConfig, rng: StdRng) -> Self {
+        Self { config, rng, band: BandStatus::default() }
+    }
+
+    /// Bid and ask to quote at, skewed against inventory, and the size
+    fn targets(&mut self, market: &MarketView<'_>) -> (Price, Price, Qty) {
+        self.band.enter(self.config.inventory_band(market.inventory));
+        let (half_spread, order_size) = self.config.quote_shape(market.inventory);
+        let (target_bid, target_ask) = match market.mid {
+            Some(mid) => {
+                let mid_ticks = price_utils::from_f64(mid);
+                let adjustment = price_utils::from_f64(market.inventory as f64 * self.config.inventory_skew);
+                (
+                    mid_ticks.saturating_sub(half_spread).saturating_sub(adjustment),
+                    mid_ticks.saturating_add(half_spread).saturating_sub(adjustment),
+                )
+            }
+            None => {
+                let base_price = price_utils::from_f64(100.0);
+                (base_price - half_spread, base_price + half_spread)
+            }
+        };
+        (target_bid, target_ask, market.whole_lots(order_size))
+    }
+}
+
+impl SyntheticAgent for MarketMakerAgent {
+    fn role(&self) -> Agent {
+        Agent::MarketMaker
+    }
+
+    fn on_step(&mut self, market: &MarketView<'_>) -> Vec<AgentOrder> {
+        if self.config.two_sided_quotes {
+            return Vec::new();
+        }
+        let (target_bid, target_ask, qty) = self.targets(market);
+
+        let place_bid = self.rng.gen::<f64>() < self.config.mm_probability
+            && market.inventory < self.config.max_inventory
+            && market.best_bid.is_none_or(|bid| bid < target_bid);
+        let place_ask = self.rng.gen::<f64>() < self.config.mm_probability
+            && market.inventory > -self.config.max_inventory
+            && market.best_ask.is_none_or(|ask| ask > target_ask);
+
+        let mut orders = Vec::new();
+        if place_bid && target_bid > 0 {
+            orders.push(AgentOrder::limit(Side::Buy, qty, target_bid));
+        }
+        if place_ask && target_ask > 0 {
+            orders.push(AgentOrder::limit(Side::Sell, qty, target_ask));
+        }
+        orders
+    }
+
+    fn on_quote(&mut self, market: &MarketView<'_>) -> Option<AgentQuote> {
+        // Between refreshes the last quote stays in the book
+        if !self.config.two_sided_quotes || self.rng.gen::<f64>() >= self.config.mm_probability {
+            return None;
+        }
+        let (target_bid, target_ask, qty) = self.targets(market);
+        Some(AgentQuote {
+            bid: (market.inventory < self.config.max_inventory && target_bid > 0).then_some((target_bid, qty)),
+            ask: (market.inventory > -self.config.max_inventory && target_ask > 0).then_some((target_ask, qty)),
+        })
+    }
+
+    fn inventory_band(&self) -> Option<BandStatus> {
+        (!self.config.inventory_bands.is_empty()).then_some(self.band)
+    }
+}
+
+/// Sends one random market or limit order per step
+pub struct TakerAgent {
+    config: OrderGenerationConfig,
+    rng: StdRng,
+}
+
+impl TakerAgent {
+    pub fn new(config: OrderGenerationConfig, rng: StdRng) -> Self {
+        Self { config, rng }
+    }
+
+    fn limit_price(&mut self, side: Side, mid: f64) -> Option<Price> {
+        let mid_ticks = price_utils::from_f64(mid);
+        let range_ticks = price_utils::from_f64(mid * self.config.price_range_fraction);
+        let offset = self.rng.gen_range(0..=range_ticks);
+        // Usually passive, sometimes through the mid
+        let passive = self.rng.gen::<bool>();
+        let price = match (side, passive) {
+            (Side::Buy, true) | (Side::Sell, false) => mid_ticks.saturating_sub(offset),
+            (Side::Buy, false) | (Side::Sell, true) => mid_ticks.saturating_add(offset),
+        };
+        (price > 0).then_some(price)
+    }
+}
+
+impl SyntheticAgent for TakerAgent {
+    fn role(&self) -> Agent {
+        Agent::Taker
+    }
+
+    fn on_step(&mut self, market: &MarketView<'_>) -> Vec<AgentOrder> {
+        let side = if self.rng.gen_bool(self.config.buy_probability) { Side::Buy } else { Side::Sell };
+        let qty = market.whole_lots(self.rng.gen_range(self.config.min_order_size..=self.config.max_order_size));
+
+        if self.rng.gen::<f64>() < self.config.market_order_prob {
+            let qty = match self.config.max_slippage_ticks {
+                Some(budget) => {
+                    let model = CostModel::from_snapshot(market.book);
+                    market.lot_size.round_down(qty.min(model.max_qty_within(side, budget as f64)))
+                }
+                None => qty,
+            };
+            return if qty == 0 { Vec::new() } else { vec![AgentOrder::market(side, qty)] };
+        }
+
+        match market.mid.and_then(|mid| self.limit_price(side, mid)) {
+            Some(price) => vec![AgentOrder::limit(side, qty, price)],
+            None => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Bidder {
+        price: Price,
+    }
+
+    impl SyntheticAgent for Bidder {
+        fn role(&self) -> Agent {
+            Agent::MarketMaker
+        }
+
+        fn on_step(&mut self, market: &MarketView<'_>) -> Vec<AgentOrder> {
+            vec![AgentOrder::limit(Side::Buy, market.whole_lots(1), self.price)]
+        }
+    }
+
+    #[test]
+    fn test_registry_builds_configured_population() {
+        let mut registry = AgentRegistry::builtin(&MarketMakerConfig::default(), &OrderGenerationConfig::default());
+        registry.register("bidder", |params, _rng| {
+            let price = params.get("price").and_then(toml::Value::as_integer).ok_or("price is required")?;
+            Ok(Box::new(Bidder { price: price as Price }) as Box<dyn SyntheticAgent>)
+        });
+        assert_eq!(registry.kinds().collect::<Vec<_>>(), vec!["adversary", "bidder", "market_maker", "taker"]);
+
+        let specs = vec![
+            AgentSpec::new("market_maker").with_count(2).with_param("target_spread", 200),
+            AgentSpec::new("taker").with_seed(9),
+            AgentSpec::new("bidder").with_param("price", 99_0000),
+        ];
+        let agents = registry.build(&specs, 42).unwrap();
+        let roles: Vec<Agent> = agents.iter().map(|instance| instance.agent.role()).collect();
+        assert_eq!(roles, vec![Agent::MarketMaker, Agent::MarketMaker, Agent::Taker, Agent::MarketMaker]);
+        // Each instance keeps a single-copy entry with the seed it was built with
+        assert!(agents.iter().all(|instance| instance.spec.count == 1));
+        assert_eq!(agents[2].spec, AgentSpec::new("taker").with_seed(9));
+        assert_eq!(agents[1].spec.params["target_spread"].as_integer(), Some(200));
+        assert_ne!(agents[0].spec.seed, agents[1].spec.seed);
+
+        let err = registry.build(&[AgentSpec::new("arbitrageur")], 42).err().unwrap();
+        assert!(matches!(err, AgentError::UnknownKind { ref kind, .. } if kind == "arbitrageur"));
+        assert!(err.to_string().contains("market_maker, taker"));
+
+        let err = registry.build(&[AgentSpec::new("bidder")], 42).err().unwrap();
+        assert!(matches!(err, AgentError::InvalidParams { .. }));
+        let err = registry.build(&[AgentSpec::new("taker").with_param("buy_probability", "high")], 42).err().unwrap();
+        assert!(matches!(err, AgentError::InvalidParams { .. }));
+        // Values out of range are refused before they can reach the generator
+        for spec in [
+            AgentSpec::new("taker").with_param("buy_probability", 5.0),
+            AgentSpec::new("taker").with_param("min_order_size", 600),
+            AgentSpec::new("market_maker").with_param("mm_probability", -0.5),
+        ] {
+            let err = registry.create(&spec).err().unwrap();
+            assert!(matches!(err, AgentError::InvalidParams { ref kind, .. } if *kind == spec.kind), "{}", err);
+        }
+
+        assert!(AgentSpec::new("taker").with_count(0).validate().is_err());
+    }
+
+    #[test]
+    fn test_agent_spec_from_toml() {
+        let specs: BTreeMap<String, Vec<AgentSpec>> = toml::from_str(
+            r#"
+            [[agents]]
+            kind = "market_maker"
+            count = 3
+            seed = 7
+            params = { target_spread = 200 }
+
+            [[agents]]
+            kind = "taker"
+            data_path = { base_latency_ns = 2000000 }
+            "#,
+        ).unwrap();
+        let specs = &specs["agents"];
+        assert_eq!(specs[0], AgentSpec::new("market_maker").with_count(3).with_seed(7).with_param("target_spread", 200));
+        assert_eq!(specs[1], AgentSpec::new("taker").with_data_path(PathLatency::new(2_000_000, 0)));
+
+        let config = with_overrides(&MarketMakerConfig::default(), &specs[0].params).unwrap();
+        assert_eq!(config.target_spread, 200);
+        assert_eq!(config.order_size, MarketMakerConfig::default().order_size);
+    }
+}
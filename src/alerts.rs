@@ -0,0 +1,320 @@
+//! Price-level alerts for feed subscribers
+//!
+//! Clients register conditions on the published book ("best bid at or above
+//! X", "depth at price Y below Z") and the streaming publisher checks them
+//! against each incremental depth update as it goes out. Only subscriptions
+//! an update can affect are looked at: level conditions are indexed by price
+//! and checked when their level appears in the update, quote conditions when
+//! the best price on their side moves.
+//!
+//! An alert fires when an update makes its condition true, and fires again
+//! only after a later update has made it false in between. Alerts are
+//! published on their own topic. Each client may hold at most
+//! [`MAX_ALERTS_PER_CLIENT`] subscriptions.
+
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use serde::{Deserialize, Serialize};
+use crate::feed::DepthUpdate;
+use crate::types::{Price, Qty, Side};
+
+/// Identifies an alert subscription
+pub type AlertId = u64;
+
+/// Most subscriptions one client may hold at once
+pub const MAX_ALERTS_PER_CLIENT: usize = 100;
+
+/// Why a subscription was refused
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum AlertError {
+    #[error("{0}")]
+    Invalid(String),
+
+    #[error("Client already holds the maximum of {max} alert subscriptions")]
+    TooMany { max: usize },
+}
+
+/// Condition on the published book
+///
+/// Externally tagged, so alerts decode from bincode feeds as well as JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertCondition {
+    /// Best bid at or above `price`
+    BidAtLeast { #[serde(serialize_with = "crate::price_format::ticks")] price: Price },
+    /// Best bid at or below `price`, or no bid at all
+    BidAtMost { #[serde(serialize_with = "crate::price_format::ticks")] price: Price },
+    /// Best ask at or below `price`
+    AskAtMost { #[serde(serialize_with = "crate::price_format::ticks")] price: Price },
+    /// Best ask at or above `price`, or no ask at all
+    AskAtLeast { #[serde(serialize_with = "crate::price_format::ticks")] price: Price },
+    /// Resting quantity at one level below `qty`
+    DepthBelow { side: Side, #[serde(serialize_with = "crate::price_format::ticks")] price: Price, qty: Qty },
+    /// Resting quantity at one level at or above `qty`
+    DepthAtLeast { side: Side, #[serde(serialize_with = "crate::price_format::ticks")] price: Price, qty: Qty },
+}
+
+impl AlertCondition {
+    /// Check the thresholds
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Self::DepthBelow { qty: 0, .. } => Err("Depth below 0 can never be reached".to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Side and price of the level a depth condition watches
+    fn level(&self) -> Option<(Side, Price)> {
+        match *self {
+            Self::DepthBelow { side, price, .. } | Self::DepthAtLeast { side, price, .. } => Some((side, price)),
+            _ => None,
+        }
+    }
+
+    /// Side whose best price a quote condition watches
+    fn quote_side(&self) -> Option<Side> {
+        match self {
+            Self::BidAtLeast { .. } | Self::BidAtMost { .. } => Some(Side::Buy),
+            Self::AskAtMost { .. } | Self::AskAtLeast { .. } => Some(Side::Sell),
+            _ => None,
+        }
+    }
+
+    /// Whether the condition holds for a best price or level quantity of
+    /// `value`, where `None` means no quote
+    fn holds(&self, value: Option<u64>) -> bool {
+        match *self {
+            Self::BidAtLeast { price } => value.is_some_and(|bid| bid >= price),
+            Self::BidAtMost { price } => value.is_none_or(|bid| bid <= price),
+            Self::AskAtMost { price } => value.is_some_and(|ask| ask <= price),
+            Self::AskAtLeast { price } => value.is_none_or(|ask| ask >= price),
+            Self::DepthBelow { qty, .. } => value.unwrap_or(0) < qty,
+            Self::DepthAtLeast { qty, .. } => value.unwrap_or(0) >= qty,
+        }
+    }
+}
+
+/// A registered condition
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlertSubscription {
+    pub alert_id: AlertId,
+    /// Client that registered it, if known
+    pub client: Option<IpAddr>,
+    pub condition: AlertCondition,
+    /// Remove the subscription once it fires
+    pub once: bool,
+    /// Whether the condition held at the last update that checked it
+    pub triggered: bool,
+}
+
+/// A condition that became true
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Alert {
+    pub alert_id: AlertId,
+    /// Sequence number of the depth update that set it off
+    pub seq: u64,
+    pub ts: u128,
+    pub condition: AlertCondition,
+    /// Best price or level quantity that met it; `None` for a missing quote
+    pub value: Option<u64>,
+}
+
+/// Alert subscriptions, indexed by what they watch
+#[derive(Debug, Clone, Default)]
+pub struct PriceAlerts {
+    next_id: AlertId,
+    subscriptions: BTreeMap<AlertId, AlertSubscription>,
+    bid_levels: HashMap<Price, Vec<AlertId>>,
+    ask_levels: HashMap<Price, Vec<AlertId>>,
+    /// Subscriptions on the best bid and on the best ask
+    quote_alerts: (Vec<AlertId>, Vec<AlertId>),
+    /// Best bid and ask seen in the last update
+    quotes: (Option<Price>, Option<Price>),
+}
+
+impl PriceAlerts {
+    /// No subscriptions
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn levels_mut(&mut self, side: Side) -> &mut HashMap<Price, Vec<AlertId>> {
+        match side {
+            Side::Buy => &mut self.bid_levels,
+            Side::Sell => &mut self.ask_levels,
+        }
+    }
+
+    fn quote_alerts_mut(&mut self, side: Side) -> &mut Vec<AlertId> {
+        match side {
+            Side::Buy => &mut self.quote_alerts.0,
+            Side::Sell => &mut self.quote_alerts.1,
+        }
+    }
+
+    /// Register a condition for a client, returning its id
+    ///
+    /// It is first checked at the next update that can affect it. Clients
+    /// of unknown address share one allowance.
+    pub fn subscribe(&mut self, client: Option<IpAddr>, condition: AlertCondition, once: bool) -> Result<AlertId, AlertError> {
+        condition.validate().map_err(AlertError::Invalid)?;
+        if self.subscriptions.values().filter(|subscription| subscription.client == client).count() >= MAX_ALERTS_PER_CLIENT {
+            return Err(AlertError::TooMany { max: MAX_ALERTS_PER_CLIENT });
+        }
+        self.next_id += 1;
+        let alert_id = self.next_id;
+        if let Some((side, price)) = condition.level() {
+            self.levels_mut(side).entry(price).or_default().push(alert_id);
+        }
+        if let Some(side) = condition.quote_side() {
+            self.quote_alerts_mut(side).push(alert_id);
+        }
+        self.subscriptions.insert(alert_id, AlertSubscription { alert_id, client, condition, once, triggered: false });
+        Ok(alert_id)
+    }
+
+    /// Remove a subscription, returning it if it existed
+    pub fn unsubscribe(&mut self, alert_id: AlertId) -> Option<AlertSubscription> {
+        let subscription = self.subscriptions.remove(&alert_id)?;
+        if let Some((side, price)) = subscription.condition.level() {
+            let levels = self.levels_mut(side);
+            if let Some(ids) = levels.get_mut(&price) {
+                ids.retain(|&id| id != alert_id);
+                if ids.is_empty() {
+                    levels.remove(&price);
+                }
+            }
+        }
+        if let Some(side) = subscription.condition.quote_side() {
+            self.quote_alerts_mut(side).retain(|&id| id != alert_id);
+        }
+        Some(subscription)
+    }
+
+    /// Registered subscriptions in id order
+    pub fn subscriptions(&self) -> Vec<AlertSubscription> {
+        self.subscriptions.values().copied().collect()
+    }
+
+    /// Check the subscriptions `update` can affect, given the best bid and
+    /// ask once it is applied, returning the alerts that fire
+    pub fn on_update(&mut self, update: &DepthUpdate, best_bid: Option<Price>, best_ask: Option<Price>) -> Vec<Alert> {
+        let mut checks: Vec<(AlertId, Option<u64>)> = Vec::new();
+        let sides = [
+            (&update.bids, &self.bid_levels, &self.quote_alerts.0, self.quotes.0, best_bid),
+            (&update.asks, &self.ask_levels, &self.quote_alerts.1, self.quotes.1, best_ask),
+        ];
+        for (levels, index, quote_alerts, previous, current) in sides {
+            for level in levels {
+                if let Some(ids) = index.get(&level.price) {
+                    checks.extend(ids.iter().map(|&id| (id, Some(level.qty))));
+                }
+            }
+            if previous != current {
+                checks.extend(quote_alerts.iter().map(|&id| (id, current)));
+            }
+        }
+        self.quotes = (best_bid, best_ask);
+
+        let mut alerts = Vec::new();
+        for (alert_id, value) in checks {
+            let Some(subscription) = self.subscriptions.get_mut(&alert_id) else {
+                continue;
+            };
+            let holds = subscription.condition.holds(value);
+            if holds && !subscription.triggered {
+                alerts.push(Alert { alert_id, seq: update.seq, ts: update.ts, condition: subscription.condition, value });
+            }
+            subscription.triggered = holds;
+        }
+        for alert in &alerts {
+            if self.subscriptions.get(&alert.alert_id).is_some_and(|subscription| subscription.once) {
+                self.unsubscribe(alert.alert_id);
+            }
+        }
+        alerts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feed::LevelUpdate;
+
+    fn update(seq: u64, bids: &[(Price, Qty)], asks: &[(Price, Qty)]) -> DepthUpdate {
+        let levels = |levels: &[(Price, Qty)]| levels.iter().map(|&(price, qty)| LevelUpdate { price, qty }).collect();
+        DepthUpdate { seq, ts: seq as u128, bids: levels(bids), asks: levels(asks), watermark: None }
+    }
+
+    #[test]
+    fn test_quote_alerts_fire_on_crossing_and_rearm() {
+        let mut alerts = PriceAlerts::new();
+        let bid_up = alerts.subscribe(None, AlertCondition::BidAtLeast { price: 100_0000 }, false).unwrap();
+        let ask_once = alerts.subscribe(None, AlertCondition::AskAtMost { price: 101_0000 }, true).unwrap();
+
+        assert!(alerts.on_update(&update(1, &[(99_0000, 10)], &[(102_0000, 10)]), Some(99_0000), Some(102_0000)).is_empty());
+        let fired = alerts.on_update(&update(2, &[(100_0000, 10)], &[(101_0000, 5)]), Some(100_0000), Some(101_0000));
+        assert_eq!(fired.iter().map(|alert| (alert.alert_id, alert.value)).collect::<Vec<_>>(),
+                   vec![(bid_up, Some(100_0000)), (ask_once, Some(101_0000))]);
+        assert_eq!(fired[0].seq, 2);
+        // The one-shot alert is gone
+        assert_eq!(alerts.subscriptions().len(), 1);
+
+        // Still true: no repeat until the condition has been false
+        assert!(alerts.on_update(&update(3, &[(100_5000, 10)], &[]), Some(100_5000), Some(101_0000)).is_empty());
+        assert!(alerts.on_update(&update(4, &[(100_5000, 0), (100_0000, 0)], &[]), Some(99_0000), Some(101_0000)).is_empty());
+        assert_eq!(alerts.on_update(&update(5, &[(100_0000, 10)], &[]), Some(100_0000), Some(101_0000)).len(), 1);
+    }
+
+    #[test]
+    fn test_depth_alerts_only_check_updated_levels() {
+        let mut alerts = PriceAlerts::new();
+        let thin = alerts.subscribe(None, AlertCondition::DepthBelow { side: Side::Sell, price: 101_0000, qty: 20 }, false).unwrap();
+        alerts.subscribe(None, AlertCondition::DepthAtLeast { side: Side::Buy, price: 101_0000, qty: 1 }, false).unwrap();
+
+        assert!(alerts.on_update(&update(1, &[], &[(101_0000, 50)]), None, Some(101_0000)).is_empty());
+        // An update elsewhere on the book does not touch the level
+        assert!(alerts.on_update(&update(2, &[], &[(102_0000, 5)]), None, Some(101_0000)).is_empty());
+        let fired = alerts.on_update(&update(3, &[], &[(101_0000, 0)]), None, Some(102_0000));
+        assert_eq!(fired.len(), 1);
+        assert_eq!((fired[0].alert_id, fired[0].value), (thin, Some(0)));
+
+        assert!(alerts.unsubscribe(thin).is_some());
+        assert!(alerts.unsubscribe(thin).is_none());
+        assert!(!alerts.ask_levels.contains_key(&101_0000));
+        assert!(AlertCondition::DepthBelow { side: Side::Buy, price: 1, qty: 0 }.validate().is_err());
+    }
+
+    #[test]
+    fn test_alerts_round_trip_through_bincode() {
+        use crate::feed::FeedFormat;
+
+        let alert = Alert {
+            alert_id: 3,
+            seq: 9,
+            ts: 12,
+            condition: AlertCondition::DepthBelow { side: Side::Sell, price: 101_0000, qty: 20 },
+            value: Some(5),
+        };
+        let bytes = FeedFormat::Bincode.encode(&alert).unwrap();
+        assert_eq!(bincode::deserialize::<Alert>(&bytes).unwrap(), alert);
+        let json = FeedFormat::Json.encode(&alert).unwrap();
+        assert_eq!(serde_json::from_slice::<Alert>(&json).unwrap(), alert);
+    }
+
+    #[test]
+    fn test_subscriptions_are_capped_per_client() {
+        let mut alerts = PriceAlerts::new();
+        let (first, second) = (Some(IpAddr::from([10, 0, 0, 1])), Some(IpAddr::from([10, 0, 0, 2])));
+        let condition = AlertCondition::BidAtLeast { price: 100_0000 };
+        for _ in 0..MAX_ALERTS_PER_CLIENT {
+            alerts.subscribe(first, condition, false).unwrap();
+        }
+        assert_eq!(alerts.subscribe(first, condition, false), Err(AlertError::TooMany { max: MAX_ALERTS_PER_CLIENT }));
+        assert!(alerts.subscribe(second, condition, false).is_ok());
+
+        // Removing one frees its place
+        alerts.unsubscribe(1);
+        assert!(alerts.subscribe(first, condition, false).is_ok());
+    }
+}
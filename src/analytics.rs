@@ -0,0 +1,557 @@
+//! Run analytics derived from executed trades
+//!
+//! The volume profile buckets traded quantity by price over the whole run and
+//! per session. Bucket maps are bounded: when a profile would exceed its
+//! bucket limit it doubles its bucket width and merges neighbouring buckets,
+//! so no volume is ever dropped, only resolution.
+//!
+//! Signed volume splits traded quantity by aggressor side. Engine trades carry
+//! their aggressor; for trade-only market data without one,
+//! [`TradeClassifier`] infers it Lee-Ready style from the prevailing quote,
+//! falling back to the tick test.
+//!
+//! With a lot size set, trades that are not a whole number of lots (typically
+//! historical odd-lot prints) are also counted separately as odd-lot volume.
+
+use std::collections::{BTreeMap, VecDeque};
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::spread_history::SpreadTier;
+use crate::types::{LotSize, Price, Qty, Side, Trade};
+
+/// Analytics configuration
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AnalyticsConfig {
+    /// Initial width of a volume profile bucket in ticks
+    pub bucket_ticks: Price,
+    /// Most buckets a profile keeps before widening them
+    pub max_buckets: usize,
+    /// Length of a session in seconds of trade time
+    pub session_length_secs: u64,
+    /// Number of most recent sessions to keep profiles for
+    pub max_sessions: usize,
+    /// Order sizes `/analytics` reports the cost of sweeping
+    #[serde(default = "default_cost_sizes")]
+    pub cost_sizes: Vec<Qty>,
+    /// Resolutions spread history is downsampled to, finest first
+    #[serde(default = "SpreadTier::defaults")]
+    pub spread_tiers: Vec<SpreadTier>,
+    /// Top-of-book level counts `/analytics` reports the depth share of
+    #[serde(default = "default_concentration_levels")]
+    pub concentration_levels: Vec<usize>,
+    /// Include resting order ages in snapshots
+    #[serde(default)]
+    pub order_ages: bool,
+}
+
+fn default_cost_sizes() -> Vec<Qty> {
+    vec![100, 500, 1000]
+}
+
+fn default_concentration_levels() -> Vec<usize> {
+    vec![1, 5, 10]
+}
+
+impl Default for AnalyticsConfig {
+    fn default() -> Self {
+        Self {
+            bucket_ticks: 100,       // $0.01
+            max_buckets: 500,
+            session_length_secs: 86_400,
+            max_sessions: 30,
+            cost_sizes: default_cost_sizes(),
+            spread_tiers: SpreadTier::defaults(),
+            concentration_levels: default_concentration_levels(),
+            order_ages: false,
+        }
+    }
+}
+
+impl AnalyticsConfig {
+    /// Check the configured limits
+    pub fn validate(&self) -> Result<(), String> {
+        if self.bucket_ticks == 0 {
+            return Err("Volume profile bucket width cannot be 0".to_string());
+        }
+        if self.max_buckets < 2 {
+            return Err("Volume profile needs at least 2 buckets".to_string());
+        }
+        if self.session_length_secs == 0 {
+            return Err("Session length cannot be 0".to_string());
+        }
+        if self.max_sessions == 0 {
+            return Err("Must keep at least 1 session profile".to_string());
+        }
+        if self.cost_sizes.contains(&0) {
+            return Err("Execution cost sizes must be greater than 0".to_string());
+        }
+        if self.concentration_levels.contains(&0) {
+            return Err("Concentration level counts must be greater than 0".to_string());
+        }
+        SpreadTier::validate_chain(&self.spread_tiers)
+    }
+
+    fn session_length_ns(&self) -> u128 {
+        self.session_length_secs as u128 * 1_000_000_000
+    }
+}
+
+/// Volume traded within one price bucket
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VolumeBucket {
+    /// Lowest price in the bucket
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub price: Price,
+    pub qty: Qty,
+    pub trades: u64,
+}
+
+/// Serializable view of a volume profile
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VolumeProfileSummary {
+    /// Session start (nanoseconds since the epoch); `None` for the whole run
+    pub session_start_ns: Option<u128>,
+    pub bucket_ticks: Price,
+    pub total_qty: Qty,
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub point_of_control: Option<Price>,
+    /// Buckets in ascending price order
+    pub buckets: Vec<VolumeBucket>,
+}
+
+/// Traded volume per price bucket
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VolumeProfile {
+    /// Current bucket width in ticks
+    pub bucket_ticks: Price,
+    max_buckets: usize,
+    buckets: BTreeMap<Price, VolumeBucket>,
+    total_qty: Qty,
+}
+
+impl VolumeProfile {
+    /// Create an empty profile
+    pub fn new(bucket_ticks: Price, max_buckets: usize) -> Self {
+        Self {
+            bucket_ticks: bucket_ticks.max(1),
+            max_buckets: max_buckets.max(2),
+            buckets: BTreeMap::new(),
+            total_qty: 0,
+        }
+    }
+
+    /// Add a trade's volume to its price bucket
+    pub fn record(&mut self, price: Price, qty: Qty) {
+        let mut key = price - price % self.bucket_ticks;
+        while !self.buckets.contains_key(&key) && self.buckets.len() >= self.max_buckets {
+            self.widen();
+            key = price - price % self.bucket_ticks;
+        }
+        let bucket = self.buckets.entry(key).or_insert(VolumeBucket { price: key, qty: 0, trades: 0 });
+        bucket.qty += qty;
+        bucket.trades += 1;
+        self.total_qty += qty;
+    }
+
+    /// Double the bucket width, merging neighbouring buckets
+    fn widen(&mut self) {
+        self.bucket_ticks *= 2;
+        let width = self.bucket_ticks;
+        let old = std::mem::take(&mut self.buckets);
+        for bucket in old.into_values() {
+            let key = bucket.price - bucket.price % width;
+            let merged = self.buckets.entry(key).or_insert(VolumeBucket { price: key, qty: 0, trades: 0 });
+            merged.qty += bucket.qty;
+            merged.trades += bucket.trades;
+        }
+    }
+
+    /// Buckets in ascending price order
+    pub fn buckets(&self) -> Vec<VolumeBucket> {
+        self.buckets.values().copied().collect()
+    }
+
+    /// Total traded quantity
+    pub fn total_qty(&self) -> Qty {
+        self.total_qty
+    }
+
+    /// Bucket with the most traded quantity (lowest price on ties)
+    pub fn point_of_control(&self) -> Option<Price> {
+        self.buckets
+            .values()
+            .max_by(|a, b| a.qty.cmp(&b.qty).then(b.price.cmp(&a.price)))
+            .map(|bucket| bucket.price)
+    }
+
+    /// Whether any volume has been recorded
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+
+    /// Serializable view of the profile
+    pub fn summary(&self, session_start_ns: Option<u128>) -> VolumeProfileSummary {
+        VolumeProfileSummary {
+            session_start_ns,
+            bucket_ticks: self.bucket_ticks,
+            total_qty: self.total_qty,
+            point_of_control: self.point_of_control(),
+            buckets: self.buckets(),
+        }
+    }
+}
+
+/// Traded quantity split by aggressor side
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedVolume {
+    pub buy_qty: Qty,
+    pub sell_qty: Qty,
+    pub buy_trades: u64,
+    pub sell_trades: u64,
+}
+
+impl SignedVolume {
+    /// Count a trade under its aggressor side
+    pub fn record(&mut self, trade: &Trade) {
+        match trade.aggressor {
+            Side::Buy => {
+                self.buy_qty += trade.qty;
+                self.buy_trades += 1;
+            }
+            Side::Sell => {
+                self.sell_qty += trade.qty;
+                self.sell_trades += 1;
+            }
+        }
+    }
+
+    /// Buyer-initiated minus seller-initiated quantity
+    pub fn net(&self) -> i64 {
+        self.buy_qty as i64 - self.sell_qty as i64
+    }
+
+    /// Net quantity as a fraction of total quantity (-1.0 to 1.0)
+    pub fn imbalance(&self) -> f64 {
+        let total = self.buy_qty + self.sell_qty;
+        if total == 0 {
+            0.0
+        } else {
+            self.net() as f64 / total as f64
+        }
+    }
+}
+
+/// Volume from trades that were not a whole number of lots
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OddLotVolume {
+    /// Lot size the trades were measured against
+    pub lot_size: Qty,
+    pub qty: Qty,
+    pub trades: u64,
+}
+
+/// Lee-Ready style trade sign classification
+///
+/// A trade above the prevailing mid is buyer-initiated and one below it is
+/// seller-initiated. Trades at the mid, or before any quote has been seen,
+/// fall back to the tick test: an uptick is a buy, a downtick a sell, and a
+/// zero tick repeats the previous classification.
+#[derive(Debug, Clone, Default)]
+pub struct TradeClassifier {
+    bid: Option<Price>,
+    ask: Option<Price>,
+    last_price: Option<Price>,
+    last_side: Option<Side>,
+}
+
+impl TradeClassifier {
+    /// Create a classifier with no market state
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Update the prevailing quote; a missing side keeps its previous price
+    pub fn observe_quote(&mut self, bid: Option<Price>, ask: Option<Price>) {
+        self.bid = bid.or(self.bid);
+        self.ask = ask.or(self.ask);
+    }
+
+    /// Record a trade whose side is already known, for later tick tests
+    pub fn observe_trade(&mut self, price: Price, side: Side) {
+        self.last_price = Some(price);
+        self.last_side = Some(side);
+    }
+
+    /// Classify a trade and remember it for later tick tests
+    ///
+    /// Returns `None` only when there is no quote and no earlier trade to
+    /// compare against.
+    pub fn classify(&mut self, price: Price) -> Option<Side> {
+        let by_quote = match (self.bid, self.ask) {
+            (Some(bid), Some(ask)) if bid < ask => {
+                // Compare doubled prices to keep the mid exact
+                let doubled_mid = bid as u128 + ask as u128;
+                match (price as u128 * 2).cmp(&doubled_mid) {
+                    std::cmp::Ordering::Greater => Some(Side::Buy),
+                    std::cmp::Ordering::Less => Some(Side::Sell),
+                    std::cmp::Ordering::Equal => None,
+                }
+            }
+            _ => None,
+        };
+        let by_tick = || match self.last_price.map(|last| price.cmp(&last)) {
+            Some(std::cmp::Ordering::Greater) => Some(Side::Buy),
+            Some(std::cmp::Ordering::Less) => Some(Side::Sell),
+            Some(std::cmp::Ordering::Equal) => self.last_side,
+            None => None,
+        };
+        let side = by_quote.or_else(by_tick);
+        self.last_price = Some(price);
+        if side.is_some() {
+            self.last_side = side;
+        }
+        side
+    }
+
+    /// Forget all market state
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Volume profile for one session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionProfile {
+    /// Session start (nanoseconds since the epoch)
+    pub start_ns: u128,
+    pub profile: VolumeProfile,
+}
+
+/// Volume profiles for the whole run and its most recent sessions
+#[derive(Debug, Clone)]
+pub struct VolumeProfiles {
+    config: AnalyticsConfig,
+    run: VolumeProfile,
+    sessions: VecDeque<SessionProfile>,
+    signed: SignedVolume,
+    lot_size: LotSize,
+    odd_lots: OddLotVolume,
+}
+
+impl VolumeProfiles {
+    /// Create empty profiles
+    pub fn new(config: AnalyticsConfig) -> Self {
+        Self {
+            run: VolumeProfile::new(config.bucket_ticks, config.max_buckets),
+            sessions: VecDeque::new(),
+            signed: SignedVolume::default(),
+            lot_size: LotSize::default(),
+            odd_lots: OddLotVolume { lot_size: 1, ..Default::default() },
+            config,
+        }
+    }
+
+    /// Settings the profiles were created with
+    pub fn config(&self) -> &AnalyticsConfig {
+        &self.config
+    }
+
+    /// Count trades that are not whole lots of `lot_size` as odd-lot volume
+    pub fn with_lot_size(mut self, lot_size: LotSize) -> Self {
+        self.set_lot_size(lot_size);
+        self
+    }
+
+    /// Change the lot size, restarting the odd-lot count
+    pub fn set_lot_size(&mut self, lot_size: LotSize) {
+        self.lot_size = lot_size;
+        self.odd_lots = OddLotVolume { lot_size: lot_size.size, ..Default::default() };
+    }
+
+    /// Add a trade to the run profile and its session's profile
+    pub fn record(&mut self, trade: &Trade) {
+        self.run.record(trade.price, trade.qty);
+        self.signed.record(trade);
+        if !self.lot_size.is_round_lot(trade.qty) {
+            self.odd_lots.qty += trade.qty;
+            self.odd_lots.trades += 1;
+        }
+
+        let session_length = self.config.session_length_ns();
+        let start_ns = trade.ts - trade.ts % session_length;
+        let position = self.sessions.iter().rposition(|session| session.start_ns == start_ns);
+        let session = match position {
+            Some(index) => &mut self.sessions[index],
+            None => {
+                // Sessions normally arrive in order; a trade for a session that
+                // has already been evicted is only counted in the run profile
+                if self.sessions.len() >= self.config.max_sessions
+                    && self.sessions.front().is_some_and(|oldest| start_ns < oldest.start_ns)
+                {
+                    return;
+                }
+                let index = self.sessions.partition_point(|session| session.start_ns < start_ns);
+                self.sessions.insert(index, SessionProfile {
+                    start_ns,
+                    profile: VolumeProfile::new(self.config.bucket_ticks, self.config.max_buckets),
+                });
+                let mut index = index;
+                while self.sessions.len() > self.config.max_sessions.max(1) {
+                    self.sessions.pop_front();
+                    index -= 1;
+                }
+                &mut self.sessions[index]
+            }
+        };
+        session.profile.record(trade.price, trade.qty);
+    }
+
+    /// Run volume split by aggressor side
+    pub fn signed_volume(&self) -> &SignedVolume {
+        &self.signed
+    }
+
+    /// Run volume from trades that were not whole lots
+    pub fn odd_lot_volume(&self) -> &OddLotVolume {
+        &self.odd_lots
+    }
+
+    /// Profile over the whole run
+    pub fn run(&self) -> &VolumeProfile {
+        &self.run
+    }
+
+    /// Profiles for the retained sessions, oldest first
+    pub fn sessions(&self) -> impl Iterator<Item = &SessionProfile> {
+        self.sessions.iter()
+    }
+
+    /// Summaries of the retained sessions, oldest first
+    pub fn session_summaries(&self) -> Vec<VolumeProfileSummary> {
+        self.sessions
+            .iter()
+            .map(|session| session.profile.summary(Some(session.start_ns)))
+            .collect()
+    }
+
+    /// Discard all recorded volume
+    pub fn reset(&mut self) {
+        *self = Self::new(self.config.clone()).with_lot_size(self.lot_size);
+    }
+}
+
+impl Default for VolumeProfiles {
+    fn default() -> Self {
+        Self::new(AnalyticsConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(price: Price, qty: Qty, ts: u128) -> Trade {
+        Trade { maker_id: 1, taker_id: 2, price, qty, ts, aggressor: Side::Buy }
+    }
+
+    #[test]
+    fn test_profile_buckets_and_point_of_control() {
+        let mut profile = VolumeProfile::new(100, 10);
+        profile.record(500_050, 10);
+        profile.record(500_099, 5);
+        profile.record(500_100, 30);
+        profile.record(499_900, 1);
+
+        let buckets = profile.buckets();
+        assert_eq!(buckets.len(), 3);
+        assert_eq!(buckets[1], VolumeBucket { price: 500_000, qty: 15, trades: 2 });
+        assert_eq!(profile.point_of_control(), Some(500_100));
+        assert_eq!(profile.total_qty(), 46);
+    }
+
+    #[test]
+    fn test_profile_widens_instead_of_growing() {
+        let mut profile = VolumeProfile::new(1, 4);
+        for price in 0..100 {
+            profile.record(1_000 + price, 1);
+        }
+        assert!(profile.buckets().len() <= 4);
+        assert!(profile.bucket_ticks >= 32);
+        assert_eq!(profile.total_qty(), 100);
+        assert_eq!(profile.buckets().iter().map(|b| b.qty).sum::<Qty>(), 100);
+    }
+
+    #[test]
+    fn test_sessions_are_bounded() {
+        let config = AnalyticsConfig {
+            session_length_secs: 1,
+            max_sessions: 2,
+            ..AnalyticsConfig::default()
+        };
+        let mut profiles = VolumeProfiles::new(config);
+        for second in 0..4u128 {
+            profiles.record(&trade(500_000, 10, second * 1_000_000_000 + 5));
+        }
+        profiles.record(&trade(500_000, 7, 3_500_000_000));
+
+        let sessions: Vec<_> = profiles.sessions().collect();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].start_ns, 2_000_000_000);
+        assert_eq!(sessions[1].profile.total_qty(), 17);
+        assert_eq!(profiles.run().total_qty(), 47);
+
+        // A straggler for an evicted session only counts toward the run
+        profiles.record(&trade(500_000, 1, 5));
+        assert_eq!(profiles.sessions().count(), 2);
+        assert_eq!(profiles.run().total_qty(), 48);
+    }
+
+    #[test]
+    fn test_odd_lot_volume() {
+        let mut profiles = VolumeProfiles::default().with_lot_size(LotSize::new(100));
+        profiles.record(&trade(500_000, 200, 0));
+        profiles.record(&trade(500_000, 37, 0));
+        profiles.record(&trade(500_000, 150, 0));
+        assert_eq!(profiles.odd_lot_volume().qty, 187);
+        assert_eq!(profiles.odd_lot_volume().trades, 2);
+        assert_eq!(profiles.run().total_qty(), 387);
+
+        profiles.reset();
+        assert_eq!(profiles.odd_lot_volume().trades, 0);
+        assert_eq!(profiles.odd_lot_volume().lot_size, 100);
+    }
+
+    #[test]
+    fn test_signed_volume() {
+        let mut signed = SignedVolume::default();
+        signed.record(&trade(500_000, 30, 0));
+        signed.record(&Trade { aggressor: Side::Sell, ..trade(500_000, 10, 0) });
+        assert_eq!(signed.net(), 20);
+        assert_eq!(signed.buy_trades, 1);
+        assert!((signed.imbalance() - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_lee_ready_classification() {
+        let mut classifier = TradeClassifier::new();
+        // Nothing to compare against yet
+        assert_eq!(classifier.classify(500_000), None);
+
+        classifier.observe_quote(Some(499_900), Some(500_100));
+        assert_eq!(classifier.classify(500_100), Some(Side::Buy));
+        assert_eq!(classifier.classify(499_950), Some(Side::Sell));
+
+        // At the mid the tick test decides: an uptick from 499_950
+        assert_eq!(classifier.classify(500_000), Some(Side::Buy));
+        // Zero tick at the mid repeats the last sign
+        assert_eq!(classifier.classify(500_000), Some(Side::Buy));
+
+        // One-sided quote update keeps the other side
+        classifier.observe_quote(None, Some(500_300));
+        assert_eq!(classifier.classify(500_050), Some(Side::Sell));
+
+        classifier.reset();
+        classifier.observe_trade(500_000, Side::Sell);
+        assert_eq!(classifier.classify(500_000), Some(Side::Sell));
+    }
+}
@@ -0,0 +1,446 @@
+//! Pre-open order queue and auction imbalance messages
+//!
+//! While the market is in a pre-open state (`PreMarket` or `Auction`), orders
+//! do not match. They wait in a queue instead, and the queue periodically
+//! publishes what an uncross would do right now: the indicative price, the
+//! quantity that would pair off there and the quantity left over on one side.
+//! This mirrors the imbalance messages real exchanges send before the open.
+//! When the market leaves the pre-open state the queue uncrosses: the
+//! quantity that pairs off at the indicative price trades there, in price
+//! then arrival priority, and what is left of the queued orders is handed
+//! back, in arrival order, to be entered into the book.
+
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::data::MarketStatusType;
+use crate::time::ms_to_ns;
+use crate::types::{Order, OrderId, OrderType, Price, Qty, Side, Trade};
+
+/// How often to publish the pre-open imbalance
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct AuctionConfig {
+    /// Queue orders during pre-open states instead of matching them
+    pub enabled: bool,
+    /// Simulated time between imbalance messages
+    pub imbalance_interval_ms: u64,
+    /// Orders the queue holds; orders beyond it are refused
+    pub max_orders: usize,
+}
+
+impl Default for AuctionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            imbalance_interval_ms: 1_000,
+            max_orders: 10_000,
+        }
+    }
+}
+
+impl AuctionConfig {
+    /// Check the publishing interval
+    pub fn validate(&self) -> Result<(), String> {
+        if self.imbalance_interval_ms == 0 {
+            return Err("Imbalance interval must be greater than 0".to_string());
+        }
+        if self.max_orders == 0 {
+            return Err("Pre-open queue must hold at least one order".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// What an uncross of the queued orders would do at one moment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuctionImbalance {
+    /// Simulated time the message was published
+    pub ts: u128,
+    /// Price that would pair off the most quantity; none if nothing would trade
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub indicative_price: Option<Price>,
+    /// Quantity that would trade at the indicative price
+    pub paired_qty: Qty,
+    /// Quantity left unmatched at the indicative price
+    pub imbalance_qty: Qty,
+    /// Side with more quantity than the other can absorb
+    pub imbalance_side: Option<Side>,
+    /// Orders waiting for the open
+    pub queued_orders: usize,
+}
+
+/// A trade of the opening uncross, with the tags of both its orders
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UncrossFill<T> {
+    /// Priced at the indicative price; the order that arrived first is the maker
+    pub trade: Trade,
+    pub maker: T,
+    pub taker: T,
+}
+
+/// What leaving the pre-open state does with the queued orders
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Uncross<T> {
+    /// Trades at the indicative price
+    pub fills: Vec<UncrossFill<T>>,
+    /// What is left of the queued orders, in arrival order
+    pub released: Vec<(Order, T)>,
+}
+
+impl<T> Default for Uncross<T> {
+    fn default() -> Self {
+        Self { fills: Vec::new(), released: Vec::new() }
+    }
+}
+
+/// Orders held back while the market is in a pre-open state
+///
+/// `T` is whatever the caller needs to enter an order later, such as who
+/// placed it.
+#[derive(Debug, Clone)]
+pub struct PreOpenQueue<T> {
+    config: AuctionConfig,
+    collecting: bool,
+    orders: Vec<(Order, T)>,
+    latest: Option<AuctionImbalance>,
+}
+
+impl<T> PreOpenQueue<T> {
+    /// Start outside any pre-open state
+    pub fn new(config: AuctionConfig) -> Self {
+        Self {
+            config,
+            collecting: false,
+            orders: Vec::new(),
+            latest: None,
+        }
+    }
+
+    /// Get the publishing interval
+    pub fn config(&self) -> &AuctionConfig {
+        &self.config
+    }
+
+    /// Whether orders are being queued rather than matched
+    pub fn is_collecting(&self) -> bool {
+        self.collecting
+    }
+
+    /// Number of queued orders
+    pub fn len(&self) -> usize {
+        self.orders.len()
+    }
+
+    /// Whether no orders are queued
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+
+    /// Most recently published imbalance, while still in pre-open
+    pub fn latest(&self) -> Option<&AuctionImbalance> {
+        self.latest.as_ref()
+    }
+
+    /// Follow a market status change at time `now`, uncrossing the queue
+    /// when the market leaves its pre-open state
+    ///
+    /// `reference` picks the indicative price as for [`Self::imbalance`].
+    pub fn set_status(&mut self, status: MarketStatusType, now: u128, reference: Option<Price>) -> Uncross<T>
+    where
+        T: Clone,
+    {
+        let pre_open = matches!(status, MarketStatusType::PreMarket | MarketStatusType::Auction);
+        let leaving = self.collecting && !pre_open;
+        self.collecting = pre_open;
+        if !leaving {
+            return Uncross::default();
+        }
+        let imbalance = self.imbalance(now, reference);
+        self.latest = None;
+        let orders = std::mem::take(&mut self.orders);
+        match imbalance.indicative_price {
+            Some(price) => Self::uncross(orders, price, imbalance.paired_qty, now),
+            None => Uncross { fills: Vec::new(), released: orders },
+        }
+    }
+
+    /// Drop every queued order and leave the pre-open state, as when the
+    /// book is cleared or replay starts over
+    pub fn reset(&mut self) {
+        self.collecting = false;
+        self.orders.clear();
+        self.latest = None;
+    }
+
+    /// Hold an order until the open, handing it back if the queue is full
+    pub fn push(&mut self, order: Order, tag: T) -> Result<(), Order> {
+        if self.orders.len() >= self.config.max_orders {
+            return Err(order);
+        }
+        self.orders.push((order, tag));
+        Ok(())
+    }
+
+    /// Withdraw a queued order, returning its quantity
+    pub fn cancel(&mut self, order_id: OrderId) -> Option<Qty> {
+        let index = self.orders.iter().position(|(order, _)| order.id == order_id)?;
+        Some(self.orders.remove(index).0.qty)
+    }
+
+    /// Quantity of a queued order
+    pub fn queued_qty(&self, order_id: OrderId) -> Option<Qty> {
+        self.orders.iter().find(|(order, _)| order.id == order_id).map(|(order, _)| order.qty)
+    }
+
+    /// Publish the imbalance if the interval has passed since the last one
+    ///
+    /// `reference` breaks ties between equally good prices, and prices an
+    /// uncross of market orders alone; the last trade price suits.
+    pub fn poll(&mut self, now: u128, reference: Option<Price>) -> Option<AuctionImbalance> {
+        if !self.collecting {
+            return None;
+        }
+        let interval = ms_to_ns(self.config.imbalance_interval_ms);
+        if self.latest.is_some_and(|latest| now < latest.ts + interval) {
+            return None;
+        }
+        let imbalance = self.imbalance(now, reference);
+        self.latest = Some(imbalance);
+        Some(imbalance)
+    }
+
+    /// Uncross the queued orders on paper at time `now`
+    ///
+    /// The indicative price maximizes the paired quantity, then minimizes the
+    /// imbalance, then stays closest to `reference`.
+    pub fn imbalance(&self, now: u128, reference: Option<Price>) -> AuctionImbalance {
+        // Limits of each side in ascending price, and the market quantity,
+        // so each candidate price is one step of a sweep
+        let (mut bids, mut asks) = (Vec::new(), Vec::new());
+        let (mut market_buy, mut market_sell) = (0, 0);
+        for (order, _) in &self.orders {
+            match (order.side, limit_price(order)) {
+                (Side::Buy, Some(price)) => bids.push((price, order.qty)),
+                (Side::Sell, Some(price)) => asks.push((price, order.qty)),
+                (Side::Buy, None) => market_buy += order.qty,
+                (Side::Sell, None) => market_sell += order.qty,
+            }
+        }
+        bids.sort_unstable();
+        asks.sort_unstable();
+        let total_buy = market_buy + bids.iter().map(|&(_, qty)| qty).sum::<Qty>();
+        let total_sell = market_sell + asks.iter().map(|&(_, qty)| qty).sum::<Qty>();
+
+        let mut candidates: Vec<Price> = bids.iter().chain(&asks).map(|&(price, _)| price).chain(reference).collect();
+        candidates.sort_unstable();
+        candidates.dedup();
+
+        // Buyers accept any price up to their limit, sellers any down to theirs
+        let (mut below, mut bid_index) = (0, 0);
+        let (mut supply, mut ask_index) = (market_sell, 0);
+        let mut best: Option<(Price, Qty, Qty)> = None;
+        let rank = |(price, buy, sell): (Price, Qty, Qty)| (
+            std::cmp::Reverse(Qty::min(buy, sell)),
+            buy.abs_diff(sell),
+            reference.map_or(0, |reference| price.abs_diff(reference)),
+        );
+        for price in candidates {
+            while bids.get(bid_index).is_some_and(|&(limit, _)| limit < price) {
+                below += bids[bid_index].1;
+                bid_index += 1;
+            }
+            while asks.get(ask_index).is_some_and(|&(limit, _)| limit <= price) {
+                supply += asks[ask_index].1;
+                ask_index += 1;
+            }
+            let candidate = (price, total_buy - below, supply);
+            if candidate.1.min(candidate.2) > 0 && best.is_none_or(|best| rank(candidate) < rank(best)) {
+                best = Some(candidate);
+            }
+        }
+
+        // Nothing would trade, so the whole of each side is unmatched
+        let (indicative_price, buy, sell) = match best {
+            Some((price, buy, sell)) => (Some(price), buy, sell),
+            None => (None, total_buy, total_sell),
+        };
+        let imbalance_side = match buy.cmp(&sell) {
+            std::cmp::Ordering::Greater => Some(Side::Buy),
+            std::cmp::Ordering::Less => Some(Side::Sell),
+            std::cmp::Ordering::Equal => None,
+        };
+        AuctionImbalance {
+            ts: now,
+            indicative_price,
+            paired_qty: if indicative_price.is_some() { buy.min(sell) } else { 0 },
+            imbalance_qty: buy.abs_diff(sell),
+            imbalance_side,
+            queued_orders: self.orders.len(),
+        }
+    }
+
+    /// Trade `paired` at `price` between the orders willing to, market orders
+    /// first, then the best limits, then the earliest arrivals
+    fn uncross(mut orders: Vec<(Order, T)>, price: Price, paired: Qty, now: u128) -> Uncross<T>
+    where
+        T: Clone,
+    {
+        let priority = |side: Side| {
+            let mut eligible: Vec<usize> = (0..orders.len())
+                .filter(|&index| orders[index].0.side == side)
+                .filter(|&index| limit_price(&orders[index].0).is_none_or(|limit| match side {
+                    Side::Buy => limit >= price,
+                    Side::Sell => limit <= price,
+                }))
+                .collect();
+            // Market orders, as the most aggressive, sort ahead of any limit
+            eligible.sort_by_key(|&index| match (side, limit_price(&orders[index].0)) {
+                (_, None) => (0, 0, index),
+                (Side::Buy, Some(limit)) => (1, Price::MAX - limit, index),
+                (Side::Sell, Some(limit)) => (1, limit, index),
+            });
+            eligible
+        };
+        let (buys, sells) = (priority(Side::Buy), priority(Side::Sell));
+
+        let mut fills = Vec::new();
+        let (mut buy, mut sell, mut left) = (0, 0, paired);
+        while left > 0 && buy < buys.len() && sell < sells.len() {
+            let (bid, ask) = (buys[buy], sells[sell]);
+            let qty = orders[bid].0.qty.min(orders[ask].0.qty).min(left);
+            let (maker, taker) = if bid < ask { (bid, ask) } else { (ask, bid) };
+            fills.push(UncrossFill {
+                trade: Trade {
+                    maker_id: orders[maker].0.id,
+                    taker_id: orders[taker].0.id,
+                    price,
+                    qty,
+                    ts: now,
+                    aggressor: orders[taker].0.side,
+                },
+                maker: orders[maker].1.clone(),
+                taker: orders[taker].1.clone(),
+            });
+            orders[bid].0.qty -= qty;
+            orders[ask].0.qty -= qty;
+            left -= qty;
+            if orders[bid].0.qty == 0 {
+                buy += 1;
+            }
+            if orders[ask].0.qty == 0 {
+                sell += 1;
+            }
+        }
+        orders.retain(|(order, _)| order.qty > 0);
+        Uncross { fills, released: orders }
+    }
+}
+
+fn limit_price(order: &Order) -> Option<Price> {
+    match order.order_type {
+        OrderType::Limit { price } => Some(price),
+        OrderType::Market => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queued(orders: &[(Side, Qty, Option<Price>)]) -> PreOpenQueue<()> {
+        let mut queue = PreOpenQueue::new(AuctionConfig { enabled: true, ..AuctionConfig::default() });
+        queue.set_status(MarketStatusType::PreMarket, 0, None);
+        for (id, &(side, qty, price)) in orders.iter().enumerate() {
+            let order = match price {
+                Some(price) => Order::new_limit(id as OrderId + 1, side, qty, price, 0),
+                None => Order::new_market(id as OrderId + 1, side, qty, 0),
+            };
+            queue.push(order, ()).unwrap();
+        }
+        queue
+    }
+
+    #[test]
+    fn test_indicative_price_pairs_the_most_quantity() {
+        let queue = queued(&[
+            (Side::Buy, 100, Some(102)),
+            (Side::Buy, 50, Some(100)),
+            (Side::Buy, 30, None),
+            (Side::Sell, 60, Some(99)),
+            (Side::Sell, 80, Some(101)),
+            (Side::Sell, 100, Some(103)),
+        ]);
+        // At 101: 130 bought against 140 sold pairs 130, more than any other price
+        let imbalance = queue.imbalance(7, None);
+        assert_eq!(imbalance, AuctionImbalance {
+            ts: 7,
+            indicative_price: Some(101),
+            paired_qty: 130,
+            imbalance_qty: 10,
+            imbalance_side: Some(Side::Sell),
+            queued_orders: 6,
+        });
+
+        // Market orders alone uncross at the reference price
+        let queue = queued(&[(Side::Buy, 40, None), (Side::Sell, 25, None)]);
+        let imbalance = queue.imbalance(0, Some(100));
+        assert_eq!((imbalance.indicative_price, imbalance.paired_qty), (Some(100), 25));
+        assert_eq!((imbalance.imbalance_qty, imbalance.imbalance_side), (15, Some(Side::Buy)));
+
+        // Books that do not cross have no indicative price
+        let queue = queued(&[(Side::Buy, 40, Some(99)), (Side::Sell, 25, Some(101))]);
+        let imbalance = queue.imbalance(0, None);
+        assert_eq!((imbalance.indicative_price, imbalance.paired_qty, imbalance.imbalance_qty), (None, 0, 15));
+    }
+
+    #[test]
+    fn test_publishes_on_interval_and_releases_on_open() {
+        let mut queue = queued(&[(Side::Buy, 10, Some(100)), (Side::Sell, 10, Some(100)), (Side::Buy, 5, Some(99))]);
+        assert!(queue.poll(0, None).is_some());
+        assert!(queue.poll(ms_to_ns(500), None).is_none());
+        assert_eq!(queue.poll(ms_to_ns(1_000), None).map(|imbalance| imbalance.ts), Some(ms_to_ns(1_000)));
+
+        assert_eq!(queue.cancel(3), Some(5));
+        assert_eq!(queue.cancel(3), None);
+
+        // Moving between pre-open states keeps the queue
+        assert_eq!(queue.set_status(MarketStatusType::Auction, 0, None), Uncross::default());
+        let uncross = queue.set_status(MarketStatusType::Open, 9, None);
+        assert_eq!(uncross.fills.iter().map(|fill| (fill.trade.maker_id, fill.trade.taker_id, fill.trade.qty)).collect::<Vec<_>>(), vec![(1, 2, 10)]);
+        assert!(uncross.released.is_empty());
+        assert!(!queue.is_collecting() && queue.is_empty() && queue.latest().is_none());
+        assert!(queue.poll(ms_to_ns(5_000), None).is_none());
+
+        // A full queue refuses orders, and a reset drops what it holds
+        let mut queue = queued(&[(Side::Buy, 10, Some(100))]);
+        queue.config.max_orders = 1;
+        assert!(queue.push(Order::new_limit(2, Side::Sell, 10, 101, 0), ()).is_err());
+        queue.reset();
+        assert!(!queue.is_collecting() && queue.is_empty());
+    }
+
+    #[test]
+    fn test_open_uncrosses_at_the_indicative_price() {
+        let mut queue = queued(&[
+            (Side::Buy, 100, Some(102)),
+            (Side::Buy, 50, Some(100)),
+            (Side::Buy, 30, None),
+            (Side::Sell, 60, Some(99)),
+            (Side::Sell, 80, Some(101)),
+            (Side::Sell, 100, Some(103)),
+        ]);
+        let uncross = queue.set_status(MarketStatusType::Open, 7, None);
+
+        // 130 pairs off at 101: the market buy first, then the 102 bid,
+        // against the 99 offer and then the 101 one
+        let trades: Vec<(OrderId, OrderId, Qty)> = uncross.fills.iter()
+            .map(|fill| (fill.trade.maker_id, fill.trade.taker_id, fill.trade.qty))
+            .collect();
+        assert_eq!(trades, vec![(3, 4, 30), (1, 4, 30), (1, 5, 70)]);
+        assert!(uncross.fills.iter().all(|fill| fill.trade.price == 101 && fill.trade.ts == 7));
+
+        // What is left enters the book in arrival order
+        let released: Vec<(OrderId, Qty)> = uncross.released.iter().map(|(order, _)| (order.id, order.qty)).collect();
+        assert_eq!(released, vec![(2, 50), (5, 10), (6, 100)]);
+    }
+}
@@ -1,7 +1,9 @@
-use orderbook::{start_server, Simulator, OrderBook, FifoLevel, Config, ConfigError};
+use orderbook::{start_server_with_tenants, Tenant, Simulator, OrderBook, FifoLevel, DynLevel, Plugins, QueueDiscipline, ServerDiscipline, Config, ConfigError, DataFormatDetector, DataSource, DataResult, ThinningDataSource, ThinningPolicy, DataFormat, DataGenerator, OrderStreamGenerator, MarketProfile, ErrorPolicy, MonotonicDataSource, OrderingPolicy, ExternalSorter, JsonDataSource, HeartbeatDataSource, CachedDataSource, ChainedDataSource, NormalizedDataSource, LotSize, Qty, HftWorkload, StressConfig, StressReport, AdversaryConfig, AdversaryPattern, StageReport, EngineCommand, EngineEvent, OrderId, OrderType, EngineDebugger, EngineSettings, bisect, journal_replay, Baseline, RunOutcome, ConformanceConfig, CheckStatus, Scenario, SCENARIOS, LogMode, AnonymizeConfig, anonymize_source, even_checkpoints, verify_restores, FlowTransformDataSource};
+use orderbook::{adversary, conformance, stress, verify};
 use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::time::Duration;
 use std::env;
 
 /// Order Book Server CLI
@@ -17,6 +19,10 @@ struct Cli {
     #[arg(short, long, value_name = "FILE")]
     config: Option<PathBuf>,
     
+    /// Named profile of the configuration file to apply over its base settings
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+    
     /// Server port (overrides config file)
     #[arg(short, long)]
     port: Option<u16>,
@@ -33,14 +39,83 @@ struct Cli {
     #[arg(long, value_name = "FILE")]
     json_file: Option<PathBuf>,
     
+    /// Stream a data file's reconstructed book to clients without synthetic agents
+    #[arg(long, value_name = "FILE")]
+    replay: Option<PathBuf>,
+    
+    /// Play these data files after the data or replay file, in order (e.g. one file per day)
+    #[arg(long, value_name = "FILE", value_delimiter = ',')]
+    then: Vec<PathBuf>,
+    
+    /// Initial replay speed multiplier (used with --replay)
+    #[arg(long, default_value_t = 1.0)]
+    replay_speed: f64,
+    
+    /// Only feed events for these symbols from a multi-symbol data file,
+    /// in place of `data_source.symbols`
+    #[arg(long, value_name = "SYMBOL", value_delimiter = ',')]
+    symbols: Vec<String>,
+    
+    /// Skip data before this timestamp (nanoseconds)
+    #[arg(long, value_name = "NS")]
+    start_time: Option<u128>,
+    
+    /// Stop the data source after this timestamp (nanoseconds)
+    #[arg(long, value_name = "NS")]
+    end_time: Option<u128>,
+    
+    /// Keep only every Nth quote update (trades and order events are kept)
+    #[arg(long, value_name = "N", conflicts_with = "max_quote_rate")]
+    thin_every: Option<usize>,
+    
+    /// Keep at most this many quote updates per second of data time
+    #[arg(long, value_name = "RATE")]
+    max_quote_rate: Option<f64>,
+    
+    /// Report events that go back in time as errors instead of feeding them to the engine
+    #[arg(long, conflicts_with = "sort_window")]
+    strict_order: bool,
+    
+    /// Reorder events through a buffer of this many events; later stragglers are reported as errors
+    #[arg(long, value_name = "N")]
+    sort_window: Option<usize>,
+    
+    /// Sort the data file by timestamp into a temporary file before replaying it
+    #[arg(long)]
+    sort_prepass: bool,
+    
+    /// What to do with malformed data records: fail-fast, skip or quarantine (overrides config file)
+    #[arg(long, value_name = "POLICY")]
+    on_error: Option<ErrorPolicy>,
+    
+    /// Relay this engine server's feed (ws://host:port) instead of running a simulation
+    #[arg(long, value_name = "URL")]
+    upstream: Option<String>,
+    
     /// Random seed for deterministic simulation
     #[arg(long)]
     seed: Option<u64>,
     
+    /// Write an end-of-run report (.html or .md) on shutdown (overrides config file)
+    #[arg(long, value_name = "FILE")]
+    report: Option<PathBuf>,
+    
+    /// Export sampled book features (.csv or .parquet) during the run (overrides config file)
+    #[arg(long, value_name = "FILE")]
+    features_out: Option<PathBuf>,
+    
     /// Log level (error, warn, info, debug, trace)
     #[arg(long)]
     log_level: Option<String>,
     
+    /// Log mode: standard, or profile to log step time by phase (overrides config file)
+    #[arg(long)]
+    log_mode: Option<LogMode>,
+    
+    /// Panic on any wall-clock read inside the engine, to check a run is deterministic
+    #[arg(long)]
+    isolate_wall_clock: bool,
+    
     /// Enable verbose output
     #[arg(short, long)]
     verbose: bool,
@@ -56,12 +131,196 @@ enum Commands {
         #[arg(short, long, default_value = "config.toml")]
         output: PathBuf,
     },
-    /// Validate configuration file
+    /// Validate configuration file, including keys no setting reads
     ValidateConfig {
         /// Configuration file to validate
         #[arg(short, long, default_value = "config.toml")]
         config: PathBuf,
     },
+    /// Print the JSON schema of the configuration file, with documentation and defaults
+    ConfigSchema,
+    /// Generate a reproducible sample data file from simulated order flow
+    GenData {
+        /// Number of events to generate
+        #[arg(short, long, default_value = "10000")]
+        events: usize,
+        /// Market profile: calm, volatile or trending
+        #[arg(short, long, default_value = "calm")]
+        profile: MarketProfile,
+        /// Output file; the format is taken from the extension unless --format is given
+        #[arg(short, long)]
+        out: PathBuf,
+        /// Output format: csv, jsonl or bin
+        #[arg(short, long)]
+        format: Option<DataFormat>,
+        /// Random seed
+        #[arg(long, default_value = "42")]
+        seed: u64,
+        /// Timestamp of the first event (nanoseconds)
+        #[arg(long, default_value_t = DataGenerator::DEFAULT_START_TIME)]
+        start_time: u128,
+        /// Generate order sizes in whole lots of this size
+        #[arg(long, default_value = "1")]
+        lot_size: Qty,
+        /// Write only the order stream, generated without running an engine,
+        /// so the same file can be replayed against different engine configs
+        #[arg(long)]
+        open_loop: bool,
+    },
+    /// Rewrite a data file with shifted timestamps, scaled prices and
+    /// renumbered order ids, so it can be shared without leaking the
+    /// original market data
+    Anonymize {
+        /// Data file to rewrite
+        input: PathBuf,
+        /// Output file; the format is taken from the extension unless --format is given
+        #[arg(short, long)]
+        out: PathBuf,
+        /// Output format: csv, jsonl or bin
+        #[arg(short, long)]
+        format: Option<DataFormat>,
+        /// Timestamp the first event is moved to (nanoseconds)
+        #[arg(long, default_value_t = DataGenerator::DEFAULT_START_TIME)]
+        start_time: u128,
+        /// Whole factor prices are multiplied by
+        #[arg(long, default_value_t = 1)]
+        price_scale: u64,
+        /// Ticks added to every price after scaling
+        #[arg(long, default_value = "0", allow_hyphen_values = true)]
+        price_offset: i64,
+        /// Whole factor quantities are multiplied by
+        #[arg(long, default_value_t = 1)]
+        qty_scale: u64,
+        /// Keep the original order ids
+        #[arg(long)]
+        keep_ids: bool,
+        /// Keep trade ids, cancel reasons and status messages
+        #[arg(long)]
+        keep_text: bool,
+    },
+    /// Push synthetic HFT order flow through the engine at full speed and report live stats
+    Stress {
+        /// How long to run, in seconds
+        #[arg(short, long, default_value = "30")]
+        duration: u64,
+        /// Seconds between rolling reports
+        #[arg(long, default_value = "1")]
+        report_interval: u64,
+        /// Cancel/replaces per trade in the generated order flow
+        #[arg(long, default_value = "20")]
+        cancels_per_trade: u32,
+        /// Random seed
+        #[arg(long, default_value = "42")]
+        seed: u64,
+    },
+    /// Flood a fresh server's order entry with an adversary agent of rising
+    /// intensity and report where the system degraded
+    Adversary {
+        /// Burst pattern: quote-stuffing, cancel-storm or deep-book-spam
+        #[arg(short, long, default_value = "quote-stuffing")]
+        pattern: AdversaryPattern,
+        /// Messages per burst at the first stage
+        #[arg(short, long, default_value = "4")]
+        intensity: u32,
+        /// Factor the intensity grows by from one stage to the next
+        #[arg(long, default_value = "2")]
+        ramp: f64,
+        /// Number of stages
+        #[arg(long, default_value = "6")]
+        stages: u32,
+        /// Bursts sent at each stage
+        #[arg(long, default_value = "50")]
+        bursts: u32,
+        /// Orders per second of simulation time the rate limit sustains (0 disables it)
+        #[arg(long, default_value = "500")]
+        rate_limit: f64,
+        /// Random seed
+        #[arg(long, default_value = "42")]
+        seed: u64,
+    },
+    /// Step the engine command by command through a data file's replay, or
+    /// find where two engine configurations first diverge
+    Debug {
+        /// Data file to replay
+        #[arg(short, long)]
+        data: PathBuf,
+        /// Start after this many commands
+        #[arg(long, default_value = "0")]
+        from: usize,
+        /// Number of commands to step through
+        #[arg(short, long, default_value = "20")]
+        steps: usize,
+        /// Order ids whose state to show after each step (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        watch: Vec<OrderId>,
+        /// Configuration to compare engine settings against; steps both books
+        /// together to the first command after which they differ
+        #[arg(long, value_name = "FILE")]
+        against: Option<PathBuf>,
+    },
+    /// Save and restore the book at checkpoints through a data file's
+    /// replay, and check each restore carries on as a continuous run does,
+    /// exiting nonzero on any divergence
+    RestoreVerify {
+        /// Data file to replay
+        #[arg(short, long)]
+        data: PathBuf,
+        /// Checkpoints spread evenly through the replay
+        #[arg(short, long, default_value = "4")]
+        checkpoints: usize,
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Repeat a seeded simulation or a data file's replay and check its
+    /// results against a baseline file, exiting nonzero on any mismatch
+    Verify {
+        /// Baseline file (JSON) with the run's parameters and expected results
+        #[arg(short, long)]
+        baseline: PathBuf,
+        /// Write this run's results as the baseline instead of checking them
+        #[arg(long)]
+        update: bool,
+        /// Steps of a synthetic run (with --update)
+        #[arg(short, long, default_value = "1000")]
+        steps: usize,
+        /// Data file to replay instead of synthetic flow (with --update)
+        #[arg(short, long)]
+        data: Option<PathBuf>,
+        /// PnL drift to allow, in ticks (with --update)
+        #[arg(long, default_value = "0")]
+        pnl_tolerance: u64,
+    },
+    /// Put a running server through the protocol conformance suite and
+    /// report which documented behaviors hold, exiting nonzero on any failure
+    Conformance {
+        /// Base URL of the server
+        #[arg(short, long, default_value = "http://localhost:3000")]
+        url: String,
+        /// Longest wait for any one reply or frame, in milliseconds
+        #[arg(long, default_value = "5000")]
+        timeout_ms: u64,
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Run a canned scenario shipped with the crate and check the features it
+    /// exercises, exiting nonzero on any failure
+    Scenario {
+        #[command(subcommand)]
+        action: ScenarioAction,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+enum ScenarioAction {
+    /// Run a scenario by name
+    Run {
+        /// Scenario name, such as latency-mm
+        name: String,
+    },
+    /// List the shipped scenarios
+    List,
 }
 
 #[tokio::main]
@@ -83,7 +342,45 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             init_config_command(output)
         }
         Commands::ValidateConfig { config } => {
-            validate_config_command(config)
+            validate_config_command(config, cli.profile.as_deref())
+        }
+        Commands::ConfigSchema => {
+            println!("{}", serde_json::to_string_pretty(&Config::json_schema())?);
+            Ok(())
+        }
+        Commands::GenData { events, profile, out, format, seed, start_time, lot_size, open_loop } => {
+            gen_data_command(events, profile, out, format, seed, start_time, lot_size, open_loop)
+        }
+        Commands::Anonymize { ref input, ref out, format, start_time, price_scale, price_offset, qty_scale, keep_ids, keep_text } => {
+            let config = AnonymizeConfig { start_time, price_scale, price_offset, qty_scale, remap_ids: !keep_ids, strip_text: !keep_text };
+            anonymize_command(input, out, format, config)
+        }
+        Commands::Stress { duration, report_interval, cancels_per_trade, seed } => {
+            stress_command(duration, report_interval, cancels_per_trade, seed)
+        }
+        Commands::Adversary { pattern, intensity, ramp, stages, bursts, rate_limit, seed } => {
+            adversary_command(pattern, intensity, ramp, stages, bursts, rate_limit, seed).await
+        }
+        Commands::Debug { ref data, from, steps, ref watch, ref against } => {
+            debug_command(&cli, data, from, steps, watch, against.as_deref())
+        }
+        Commands::RestoreVerify { ref data, checkpoints, json } => {
+            restore_verify_command(&cli, data, checkpoints, json)
+        }
+        Commands::Verify { ref baseline, update, steps, ref data, pnl_tolerance } => {
+            verify_command(&cli, baseline, update, steps, data.as_deref(), pnl_tolerance)
+        }
+        Commands::Conformance { url, timeout_ms, json } => {
+            conformance_command(url, timeout_ms, json).await
+        }
+        Commands::Scenario { action: ScenarioAction::Run { name } } => {
+            scenario_command(&name)
+        }
+        Commands::Scenario { action: ScenarioAction::List } => {
+            for scenario in &SCENARIOS {
+                println!("{:<14} {}", scenario.name, scenario.description);
+            }
+            Ok(())
         }
     }
 }
@@ -93,7 +390,7 @@ async fn start_server_command(cli: Cli) -> Result<(), Box<dyn std::error::Error>
     println!("📦 Version: {}", env!("CARGO_PKG_VERSION"));
     
     // Load configuration
-    let mut config = load_config(cli.config.as_deref())?;
+    let mut config = load_config(cli.config.as_deref(), cli.profile.as_deref())?;
     
     // Apply CLI overrides
     apply_cli_overrides(&mut config, &cli);
@@ -119,44 +416,77 @@ async fn start_server_command(cli: Cli) -> Result<(), Box<dyn std::error::Error>
     // Create order book engine and simulator
     println!("🏗️  Initializing components...");
     
-    let engine = OrderBook::<FifoLevel>::new();
-    println!("✅ Order book engine created");
-    
-    let mut simulator = if let Some(seed) = config.simulation.random_seed {
-        Simulator::with_seed(engine, seed)
+    // The built-in FIFO book keeps its levels unboxed; other disciplines are
+    // looked up by name
+    let plugins = Plugins::default();
+    if config.simulation.queue_discipline == "fifo" {
+        let simulator = configure_simulator(OrderBook::<FifoLevel>::new(), &config, &plugins)?;
+        serve_book(simulator, &config, &cli, &plugins).await
     } else {
-        Simulator::new(engine)
-    };
+        serve_book(build_simulator(&config, &plugins)?, &config, &cli, &plugins).await
+    }
+}
+
+/// Attach data to the simulator and serve it with any configured tenants
+async fn serve_book<D: ServerDiscipline>(
+    mut simulator: Simulator<OrderBook<D>>,
+    config: &Config,
+    cli: &Cli,
+    plugins: &Plugins,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(limit_mb) = config.memory.rss_limit_mb {
+        println!("✅ Memory limit {}MB (warning at {:.0}%)", limit_mb, config.memory.warning_threshold * 100.0);
+    }
     
-    // Configure simulator with loaded configuration
-    simulator = simulator
-        .with_network_model(config.network.clone())
-        .with_market_maker_config(config.market_maker.clone())
-        .with_order_generation_config(config.order_generation.clone());
+    if config.block_trades.enabled {
+        println!("✅ Block trades of {}+ print publicly after {}ms",
+                 config.block_trades.min_qty, config.block_trades.public_delay_ms);
+    }
+    
+    if let Some(stale_ms) = config.data_source.stale_feed_ms {
+        println!("✅ Stale feed alert after {}ms without events", stale_ms);
+    }
     
     // Set up data source if specified
-    if let Some(csv_file) = &config.data_source.default_csv_file {
-        if csv_file.exists() {
-            println!("📊 Loading CSV data source: {}", csv_file.display());
-            // Note: This would require implementing CsvDataSource integration
-            // For now, we'll just log the intention
-        }
+    simulator = attach_default_data(simulator, config, cli)?;
+    
+    // Replay bridge mode: the data file alone drives the book
+    if let Some(replay_file) = &cli.replay {
+        let data_source = open_data_file(replay_file, config, cli).map_err(|e| {
+            eprintln!("❌ Failed to open replay file {}: {}", replay_file.display(), e);
+            e
+        })?;
+        let data_source = apply_data_filters(data_source, config, cli)?;
+        simulator = simulator.with_replay_source(data_source);
+        simulator.set_replay_speed(cli.replay_speed)?;
+        println!("📼 Replay mode: streaming {} at {}x", replay_file.display(), cli.replay_speed);
     }
     
-    if let Some(json_file) = &config.data_source.default_json_file {
-        if json_file.exists() {
-            println!("📊 Loading JSON data source: {}", json_file.display());
-            // Note: This would require implementing JsonDataSource integration
-            // For now, we'll just log the intention
+    if config.warmup.enabled {
+        if cli.replay.is_some() {
+            println!("⚠️  Warm-up skipped: replay reconstructs the recorded book");
+        } else {
+            let boundary = simulator.warm_up(config.warmup.clone())?;
+            if boundary.converged {
+                println!("✅ Warm-up reached steady state after {} steps", boundary.steps);
+            } else {
+                println!("⚠️  Warm-up stopped after {} steps without reaching steady state", boundary.steps);
+            }
         }
     }
     
     println!("✅ Market simulator created");
     
-    println!("🌐 Starting WebSocket server...");
+    if config.server.tls.is_some() {
+        println!("🔒 Starting WebSocket server with TLS (wss://)...");
+    } else {
+        println!("🌐 Starting WebSocket server...");
+    }
+    
+    let tenants = build_tenants(config, cli)?;
     
     // Start the WebSocket server with configuration
-    match start_server(simulator, config.server.port, config.simulation.step_interval_ms).await {
+    match start_server_with_tenants(simulator, config, plugins, tenants).await {
         Ok(_) => {
             println!("✅ Server shutdown gracefully");
             Ok(())
@@ -176,6 +506,291 @@ async fn start_server_command(cli: Cli) -> Result<(), Box<dyn std::error::Error>
     }
 }
 
+/// Attach the configured default data file, if any
+fn attach_default_data<D: QueueDiscipline>(
+    simulator: Simulator<OrderBook<D>>,
+    config: &Config,
+    cli: &Cli,
+) -> Result<Simulator<OrderBook<D>>, Box<dyn std::error::Error>> {
+    let default_data_file = config.data_source.default_csv_file.as_ref()
+        .or(config.data_source.default_json_file.as_ref());
+    let Some(data_file) = default_data_file else {
+        return Ok(simulator);
+    };
+    
+    println!("📊 Loading data source: {}", data_file.display());
+    let data_source = open_data_file(data_file, config, cli).map_err(|e| {
+        eprintln!("❌ Failed to open data file {}: {}", data_file.display(), e);
+        e
+    })?;
+    let mut data_source = apply_data_filters(data_source, config, cli)?;
+    data_source.set_playback_speed(config.data_source.default_playback_speed)?;
+    println!("✅ Historical data source attached");
+    Ok(simulator.with_data_source(data_source))
+}
+
+/// Build each configured tenant's simulator from its resolved settings
+fn build_tenants(config: &Config, cli: &Cli) -> Result<Vec<Tenant>, Box<dyn std::error::Error>> {
+    let mut tenants = Vec::with_capacity(config.tenants.len());
+    for tenant in &config.tenants {
+        println!("🏘️  Tenant '{}' at /t/{}", tenant.name, tenant.name);
+        let settings = config.tenant_config(tenant)?;
+        let simulator = build_simulator(&settings, &Plugins::default())?;
+        let simulator = attach_default_data(simulator, &settings, cli)?;
+        tenants.push(Tenant { config: tenant.clone(), settings, simulator });
+    }
+    Ok(tenants)
+}
+
+/// Create the simulator and everything the configuration attaches to it
+/// before data sources
+fn build_simulator(config: &Config, plugins: &Plugins) -> Result<Simulator<OrderBook<DynLevel>>, Box<dyn std::error::Error>> {
+    let engine = plugins.book(&config.simulation.queue_discipline)?;
+    configure_simulator(engine, config, plugins)
+}
+
+/// Create a simulator of `engine` with everything the configuration
+/// attaches to it before data sources
+fn configure_simulator<D: QueueDiscipline>(
+    engine: OrderBook<D>,
+    config: &Config,
+    plugins: &Plugins,
+) -> Result<Simulator<OrderBook<D>>, Box<dyn std::error::Error>> {
+    println!("✅ Order book engine created ({} queue discipline)", config.simulation.queue_discipline);
+    
+    let mut simulator = if let Some(seed) = config.simulation.random_seed {
+        Simulator::with_seed(engine, seed)
+    } else {
+        Simulator::new(engine)
+    };
+    
+    // Before the engine settings, so the shadow book takes those it does not pin
+    if config.ab_shadow.enabled {
+        let discipline = config.ab_shadow.queue_discipline.as_deref().unwrap_or(&config.simulation.queue_discipline);
+        simulator = simulator.with_ab_shadow(config.ab_shadow.clone(), plugins.book(discipline)?);
+        println!("✅ A/B shadow book mirrors every engine command ({} queue discipline)", discipline);
+    }
+    
+    // Configure simulator with loaded configuration
+    simulator = simulator
+        .with_network_model(config.network.clone())
+        .with_market_maker_config(config.market_maker.clone())
+        .with_order_generation_config(config.order_generation.clone())
+        .with_hybrid_interaction(config.simulation.hybrid_interaction)
+        .with_unmatched_order_policy(config.data_source.unmatched_orders)
+        .with_crossed_book_policy(config.simulation.crossed_book_policy)
+        .with_trade_timestamps(config.simulation.trade_timestamps)
+        .with_wall_clock_isolation(config.simulation.isolate_wall_clock)
+        .with_analytics_config(config.analytics.clone())
+        .with_lot_size(config.simulation.lot_size)
+        .with_contract(config.simulation.contract)
+        .with_memory_config(&config.memory)
+        .with_spread_history(config.simulation.max_spread_history);
+    
+    if !config.agents.is_empty() {
+        let registry = plugins.agent_registry(&config.market_maker, &config.order_generation);
+        let agents = registry.build(&config.agents, config.simulation.random_seed.unwrap_or(42))?;
+        println!("✅ Agent population loaded ({} agents from {} entries)", agents.len(), config.agents.len());
+        simulator = simulator.with_agents(agents);
+    }
+    
+    if config.hedging.enabled {
+        simulator = simulator.with_hedger(config.hedging.clone());
+        println!("✅ Inventory hedger enabled (every {} steps)", config.hedging.interval_steps);
+    }
+    
+    let depth_limit = config.simulation.depth_limit;
+    if depth_limit.is_bounded() {
+        simulator = simulator.with_depth_limit(depth_limit);
+        let bound = |bound: Option<String>| bound.unwrap_or_else(|| "unbounded".to_string());
+        println!("✅ Depth limit enabled ({} levels, {} ticks from touch, {:?} past it)",
+                 bound(depth_limit.max_levels.map(|levels| levels.to_string())),
+                 bound(depth_limit.max_ticks_from_touch.map(|ticks| ticks.to_string())),
+                 depth_limit.policy);
+    }
+    
+    if config.liquidity_ttl.enabled {
+        simulator = simulator.with_liquidity_ttl(config.liquidity_ttl.clone());
+        let ttl = |ms: Option<u64>| ms.map_or("never".to_string(), |ms| format!("{}ms", ms));
+        println!("✅ Synthetic liquidity expiry enabled (market maker {}, taker {})",
+                 ttl(config.liquidity_ttl.market_maker_ms), ttl(config.liquidity_ttl.taker_ms));
+    }
+    
+    if config.self_match.enabled {
+        simulator = simulator.with_self_match(&config.self_match);
+        println!("✅ Self-match prevention enabled ({} groups, {:?})",
+                 config.self_match.groups.len(), config.self_match.mode);
+    }
+    
+    if config.margin.enabled {
+        simulator = simulator.with_margin(config.margin.clone());
+        println!("✅ Paper account margin enabled ({:.0}% initial, {:.0}% maintenance)",
+                 config.margin.initial_margin * 100.0, config.margin.maintenance_margin * 100.0);
+    }
+    
+    if config.velocity.enabled {
+        simulator = simulator.with_velocity(config.velocity.clone());
+        println!("✅ Paper account velocity limit enabled ({} notional per {}s)",
+                 config.velocity.max_notional, config.velocity.window_secs);
+    }
+    
+    if config.funding.enabled {
+        simulator = simulator.with_funding(config.funding.clone());
+        println!("✅ Funding enabled (every {}ms, capped at {:.4}%)",
+                 config.funding.interval_ms, config.funding.max_rate * 100.0);
+    }
+    
+    if config.obligations.enabled {
+        simulator = simulator.with_obligations(config.obligations.clone());
+        println!("✅ Market-maker obligations enabled ({} ticks max spread, {} min size, {:.0}% of each {}ms session)",
+                 config.obligations.max_spread_ticks, config.obligations.min_size,
+                 config.obligations.min_quoted_pct, config.obligations.session_ms);
+    }
+    
+    if config.settlement.enabled {
+        simulator = simulator.with_settlement(config.settlement.clone());
+        println!("✅ Settlement enabled (T+{} on {}ms cycles, {:.2}% fail rate)",
+                 config.settlement.lag_cycles, config.settlement.cycle_ms,
+                 config.settlement.fail_probability * 100.0);
+    }
+    if config.price_bands.enabled {
+        simulator = simulator.with_price_bands(config.price_bands.clone());
+        println!("✅ Price bands enabled (±{:.1}% around the {:?} reference price, hybrid mode)",
+                 config.price_bands.band_pct * 100.0, config.price_bands.reference);
+    }
+    
+    if config.latency.enabled {
+        simulator = simulator.with_latency(config.latency.clone());
+        println!("✅ Strategy latency accounting enabled ({}ns decision latency plus network)",
+                 config.latency.decision_ns);
+    }
+    
+    if config.throttle.enabled {
+        simulator = simulator.with_throttle(config.throttle.clone());
+        println!("✅ Synthetic flow throttles above {:.0}% of the step interval",
+                 config.throttle.high_water * 100.0);
+    }
+    
+    if config.auction.enabled {
+        simulator = simulator.with_auction(config.auction.clone());
+        println!("✅ Pre-open orders queue for the open, imbalance published every {}ms",
+                 config.auction.imbalance_interval_ms);
+    }
+    
+    if config.shadow_book.enabled {
+        simulator = simulator.with_shadow_book(config.shadow_book.clone());
+        println!("✅ Shadow book follows external quotes with {} levels a side, {} ticks apart",
+                 config.shadow_book.levels, config.shadow_book.level_spacing);
+    }
+    
+    Ok(simulator)
+}
+
+/// Open a data file and any files chained after it with `--then`
+fn open_data_file(path: &Path, config: &Config, cli: &Cli) -> DataResult<Box<dyn DataSource>> {
+    if cli.then.is_empty() {
+        return open_single_file(path, config, cli);
+    }
+    
+    let sources = std::iter::once(path)
+        .chain(cli.then.iter().map(PathBuf::as_path))
+        .map(|path| open_single_file(path, config, cli))
+        .collect::<DataResult<Vec<_>>>()?;
+    println!("🔗 Chaining {} data files", sources.len());
+    Ok(Box::new(ChainedDataSource::new(sources)?))
+}
+
+/// Open one data file, sorting it into a temporary file first if requested
+fn open_single_file(path: &Path, config: &Config, cli: &Cli) -> DataResult<Box<dyn DataSource>> {
+    if !cli.sort_prepass {
+        return DataFormatDetector::create_data_source(path);
+    }
+    
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let sorted_path = env::temp_dir().join(format!("{}.{}.sorted.jsonl", file_name, process::id()));
+    let report = ExternalSorter::new()
+        .with_error_policy(config.data_source.error_policy)
+        .sort_file(path, &sorted_path)?;
+    println!("🧹 Sorted {} events ({} out of order) into {}", report.events, report.out_of_order, sorted_path.display());
+    
+    // The sorted copy is only needed while it is replayed
+    match JsonDataSource::new(&sorted_path) {
+        Ok(source) => Ok(Box::new(source.removing_file_on_drop())),
+        Err(e) => {
+            let _ = std::fs::remove_file(&sorted_path);
+            Err(e)
+        }
+    }
+}
+
+/// Apply timestamp normalization, the error policy, the symbol filter, the CLI's time window and thinning, and heartbeats to a data source
+fn apply_data_filters(mut data_source: Box<dyn DataSource>, config: &Config, cli: &Cli) -> DataResult<Box<dyn DataSource>> {
+    let timestamps = &config.data_source.timestamps;
+    if !timestamps.is_identity() {
+        data_source = Box::new(NormalizedDataSource::new(data_source, timestamps.clone())?);
+        println!("🕰️  Timestamps: {:?} at UTC{:+}min, corrected by {}ns", timestamps.unit, timestamps.utc_offset_minutes, timestamps.offset_ns);
+    }
+    
+    let error_policy = config.data_source.error_policy;
+    if error_policy != ErrorPolicy::FailFast {
+        data_source.set_error_policy(error_policy)?;
+        println!("🩹 Malformed records: {}", error_policy);
+    }
+    
+    let symbols = if cli.symbols.is_empty() { &config.data_source.symbols } else { &cli.symbols };
+    if !symbols.is_empty() {
+        data_source.filter_symbols(symbols.clone())?;
+        println!("🔎 Symbol filter: {}", symbols.join(", "));
+    }
+    
+    if cli.start_time.is_some() || cli.end_time.is_some() {
+        let start = cli.start_time.unwrap_or(0);
+        let end = cli.end_time.unwrap_or(u128::MAX);
+        data_source.set_time_window(start, end)?;
+        println!("⏱️  Time window: {} - {}", start, cli.end_time.map_or("end".to_string(), |end| end.to_string()));
+    }
+    
+    let ordering = match (cli.strict_order, cli.sort_window) {
+        (true, _) => Some(OrderingPolicy::Strict),
+        (false, Some(size)) => Some(OrderingPolicy::SortWindow(size)),
+        (false, None) => None,
+    };
+    if let Some(ordering) = ordering {
+        data_source = Box::new(MonotonicDataSource::new(data_source, ordering)?);
+        println!("⏳ Timestamp ordering: {}", ordering);
+    }
+    
+    let policy = match (cli.thin_every, cli.max_quote_rate) {
+        (Some(n), _) => Some(ThinningPolicy::EveryNth(n)),
+        (None, Some(rate)) => Some(ThinningPolicy::MaxRate(rate)),
+        (None, None) => None,
+    };
+    if let Some(policy) = policy {
+        data_source = Box::new(ThinningDataSource::new(data_source, policy)?);
+        println!("🪶 Thinning quotes: {}", policy);
+    }
+    
+    let cache = &config.data_source.cache;
+    if cache.enabled {
+        data_source = Box::new(CachedDataSource::new(data_source, cache.clone())?);
+        println!("🗃️  Caching parsed events in blocks of {} (up to {} bytes)", cache.block_events, cache.budget_bytes);
+    }
+    
+    // Above the cache, so a cached replay is transformed afresh from the same seed
+    let transform = &config.data_source.transform;
+    if transform.enabled && !transform.is_identity() {
+        data_source = Box::new(FlowTransformDataSource::new(data_source, transform.clone())?);
+        println!("🌪️  Flow transform: {}", transform);
+    }
+    
+    if let Some(interval_ms) = config.data_source.heartbeat_interval_ms {
+        data_source = Box::new(HeartbeatDataSource::new(data_source, Duration::from_millis(interval_ms))?);
+        println!("💓 Heartbeats after {}ms of quiet", interval_ms);
+    }
+    
+    Ok(data_source)
+}
+
 fn init_config_command(output: PathBuf) -> Result<(), Box<dyn std::error::Error>> {
     println!("📝 Generating default configuration file...");
     
@@ -188,15 +803,31 @@ fn init_config_command(output: PathBuf) -> Result<(), Box<dyn std::error::Error>
     Ok(())
 }
 
-fn validate_config_command(config_path: PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+fn validate_config_command(config_path: PathBuf, profile: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
     println!("🔍 Validating configuration file: {}", config_path.display());
+    if let Some(profile) = profile {
+        println!("🧩 Profile: {}", profile);
+    }
     
     if !config_path.exists() {
         eprintln!("❌ Configuration file does not exist: {}", config_path.display());
         process::exit(1);
     }
     
-    match Config::load_from_file(&config_path) {
+    // Misspelled keys would otherwise leave their defaults in place unnoticed
+    let content = std::fs::read_to_string(&config_path)?;
+    match Config::unknown_keys(&content) {
+        Ok(unknown) if !unknown.is_empty() => {
+            for key in &unknown {
+                eprintln!("❌ Unknown configuration key: {}", key);
+            }
+            eprintln!("💡 Run `config-schema` to list the valid keys");
+            process::exit(1);
+        }
+        _ => {}
+    }
+    
+    match Config::load_profile_from_file(&config_path, profile) {
         Ok(config) => {
             match config.validate() {
                 Ok(_) => {
@@ -224,23 +855,402 @@ fn validate_config_command(config_path: PathBuf) -> Result<(), Box<dyn std::erro
     }
 }
 
-fn load_config(config_path: Option<&std::path::Path>) -> Result<Config, ConfigError> {
-    match config_path {
-        Some(path) => {
-            println!("📄 Loading configuration from: {}", path.display());
-            Config::load_from_file(path)
+#[allow(clippy::too_many_arguments)]
+fn gen_data_command(
+    events: usize,
+    profile: MarketProfile,
+    out: PathBuf,
+    format: Option<DataFormat>,
+    seed: u64,
+    start_time: u128,
+    lot_size: Qty,
+    open_loop: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let format = match format.or_else(|| DataFormat::from_path(&out)) {
+        Some(format) => format,
+        None => {
+            eprintln!("❌ Cannot infer format from {}; pass --format", out.display());
+            process::exit(1);
         }
+    };
+
+    println!("🎲 Generating {} {} events (seed {})...", events, profile, seed);
+
+    let generated = if open_loop {
+        OrderStreamGenerator::for_profile(profile)
+            .with_seed(seed)
+            .with_start_time(start_time)
+            .with_lot_size(LotSize::new(lot_size))
+            .generate(events)
+    } else {
+        DataGenerator::new(profile)
+            .with_seed(seed)
+            .with_start_time(start_time)
+            .with_lot_size(LotSize::new(lot_size))
+            .generate(events)?
+    };
+    format.write_file(&out, &generated)?;
+
+    println!("✅ Wrote {} ({})", out.display(), format.description());
+
+    Ok(())
+}
+
+fn anonymize_command(
+    input: &Path,
+    out: &Path,
+    format: Option<DataFormat>,
+    config: AnonymizeConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let format = match format.or_else(|| DataFormat::from_path(out)) {
+        Some(format) => format,
         None => {
-            // Try to load from default locations
-            if std::path::Path::new("config.toml").exists() {
-                println!("📄 Loading configuration from: config.toml");
-                Config::load_from_file("config.toml")
-            } else {
-                println!("📄 Using default configuration (no config file found)");
-                Ok(Config::default())
+            eprintln!("❌ Cannot infer format from {}; pass --format", out.display());
+            process::exit(1);
+        }
+    };
+    if let Err(e) = config.validate() {
+        eprintln!("❌ {}", e);
+        process::exit(1);
+    }
+
+    println!("🕶️  Anonymizing {}...", input.display());
+    let source = DataFormatDetector::create_data_source(input)?;
+    let (events, report) = anonymize_source(source, config)?;
+    format.write_file(out, &events)?;
+
+    println!("✅ Wrote {} events with {} order ids to {} ({})", report.events, report.orders, out.display(), format.description());
+    println!("   Timestamps shifted by {}ns", report.time_shift_ns);
+    if report.clamped_prices > 0 {
+        println!("⚠️  {} prices scaled to zero or below were raised to one tick", report.clamped_prices);
+    }
+
+    Ok(())
+}
+
+fn stress_command(
+    duration: u64,
+    report_interval: u64,
+    cancels_per_trade: u32,
+    seed: u64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if duration == 0 || report_interval == 0 {
+        eprintln!("❌ Duration and report interval must be at least 1 second");
+        process::exit(1);
+    }
+
+    let config = StressConfig {
+        duration: Duration::from_secs(duration),
+        report_interval: Duration::from_secs(report_interval),
+        workload: HftWorkload::new()
+            .with_seed(seed)
+            .with_cancels_per_trade(cancels_per_trade),
+    };
+
+    println!("🔥 Stress testing for {}s ({} cancel/replaces per trade, seed {})...", duration, cancels_per_trade, seed);
+    println!("{:>8} {:>12} {:>9} {:>9} {:>9} {:>10} {:>10} {:>10}",
+             "elapsed", "ops/s", "p50", "p99", "p99.9", "max", "rss", "growth");
+
+    let mut engine = OrderBook::<FifoLevel>::new();
+    let mut elapsed = Duration::ZERO;
+    let total = stress::run(&mut engine, &config, |report| {
+        elapsed += report.elapsed;
+        print_stress_row(&format!("{:.0}s", elapsed.as_secs_f64()), report);
+    })?;
+
+    println!("{}", "-".repeat(86));
+    print_stress_row("total", &total);
+    println!("✅ {} operations, {} trades in {:.1}s", total.ops, total.trades, total.elapsed.as_secs_f64());
+
+    Ok(())
+}
+
+async fn adversary_command(
+    pattern: AdversaryPattern,
+    intensity: u32,
+    ramp: f64,
+    stages: u32,
+    bursts: u32,
+    rate_limit: f64,
+    seed: u64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut config = AdversaryConfig {
+        pattern,
+        intensity,
+        ramp,
+        stages,
+        bursts_per_stage: bursts,
+        seed,
+        ..AdversaryConfig::default()
+    };
+    config.rate_limit.enabled = rate_limit > 0.0;
+    config.rate_limit.orders_per_sec = rate_limit;
+    config.validate()?;
+
+    println!("🧨 Sending {} bursts of {} messages per stage over {} stages (seed {})...", bursts, pattern, stages, seed);
+    println!("{:>5} {:>9} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>12} {:>8}",
+             "stage", "intensity", "messages", "throttled", "admitted", "allowed", "cmd p99", "pub p99", "feed bytes", "levels");
+
+    let report = adversary::run(OrderBook::<FifoLevel>::new(), &config, print_stage_row).await?;
+
+    println!("{}", "-".repeat(102));
+    if report.degradations.is_empty() {
+        println!("✅ Nothing degraded up to {} messages per burst", config.intensity_at(stages - 1));
+    }
+    for degradation in &report.degradations {
+        println!("⚠️  {}", degradation);
+    }
+    Ok(())
+}
+
+fn debug_command(
+    cli: &Cli,
+    data: &Path,
+    from: usize,
+    steps: usize,
+    watch: &[OrderId],
+    against: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let config = load_config(cli.config.as_deref(), cli.profile.as_deref())?;
+    let source = open_data_file(data, &config, cli)?;
+    let journal = journal_replay(source)?;
+    let settings = EngineSettings::from_simulation(&config.simulation);
+    println!("📒 Journaled {} engine commands from {}", journal.len(), data.display());
+    
+    if let Some(against) = against {
+        let other = EngineSettings::from_simulation(&Config::load_from_file(against)?.simulation);
+        match bisect(&journal, settings, other) {
+            Some(divergence) => {
+                println!("🔀 Books first differ after command #{}: {}", divergence.index, describe_command(&divergence.command));
+                for (label, settings) in [("this config", settings), ("against", other)] {
+                    let debugger = EngineDebugger::at(journal.clone(), settings, divergence.index + 1);
+                    let depth = debugger.depth();
+                    println!("   {:<12} hash {:016x}  bid {:?}  ask {:?}  levels {}/{}",
+                             label, debugger.state_hash(), depth.best_bid, depth.best_ask, depth.bids.len(), depth.asks.len());
+                }
             }
+            None => println!("✅ Both configurations end with the same book"),
         }
+        return Ok(());
     }
+    
+    let mut debugger = EngineDebugger::at(journal, settings, from);
+    println!("⏩ Checkpoint after {} commands, hash {:016x}", debugger.position(), debugger.state_hash());
+    for _ in 0..steps {
+        let Some(step) = debugger.step() else {
+            println!("🏁 End of journal");
+            break;
+        };
+        let outcome = match &step.outcome {
+            Ok(EngineEvent::Placed(trades)) if trades.is_empty() => "rested".to_string(),
+            Ok(EngineEvent::Placed(trades)) => format!("{} trades", trades.len()),
+            Ok(EngineEvent::Cancelled(qty)) => format!("cancelled {}", qty),
+            Ok(EngineEvent::Pruned(orders)) => format!("{} pruned", orders.len()),
+            Ok(EngineEvent::Applied) => "applied".to_string(),
+            Ok(EngineEvent::PriorityFeePaid(fee)) => format!("paid fee {}", fee),
+            Ok(EngineEvent::Stats(stats)) => format!("{} resting", stats.resting_orders),
+            Ok(EngineEvent::Compacted(compaction)) => format!("compacted {} entries", compaction.entries),
+            Ok(EngineEvent::Quoted(outcome)) => format!("quoted, {} resting", outcome.resting.len()),
+            Err(e) => format!("rejected: {}", e),
+        };
+        let depth = debugger.depth();
+        println!("#{:<6} {:<40} {:<24} bid {:?} ask {:?} hash {:016x}",
+                 step.index, describe_command(&step.command), outcome, depth.best_bid, depth.best_ask, step.state_hash);
+        for order_id in watch {
+            match debugger.order(*order_id) {
+                Some(order) => println!("         order {}: {:?} {} @ {}, {} ahead", order_id, order.side, order.qty, order.price, order.queue_position),
+                None => println!("         order {}: not resting", order_id),
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+fn restore_verify_command(cli: &Cli, data: &Path, checkpoints: usize, json: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let config = load_config(cli.config.as_deref(), cli.profile.as_deref())?;
+    let source = open_data_file(data, &config, cli)?;
+    let journal = journal_replay(source)?;
+    let settings = EngineSettings::from_simulation(&config.simulation);
+    let report = verify_restores(&journal, settings, &even_checkpoints(journal.len(), checkpoints))?;
+    
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        let ms = |ns: u64| ns as f64 / 1_000_000.0;
+        println!("📒 Journaled {} engine commands from {}; continuous replay took {:.2}ms", report.commands, data.display(), ms(report.continuous_ns));
+        println!("{:>10} {:>8} {:>10} {:>11} {:>11}  result", "checkpoint", "orders", "image", "cold ms", "warm ms");
+        for check in &report.checks {
+            let result = match (check.restored_match, check.divergence) {
+                (true, None) if check.final_match => "✅ matches the continuous run".to_string(),
+                (false, _) => "❌ restored book differs at the checkpoint".to_string(),
+                (true, Some(index)) => format!("❌ diverged after command #{}", index),
+                (true, None) => "❌ differs at the end of the journal".to_string(),
+            };
+            println!("{:>10} {:>8} {:>9}B {:>11.2} {:>11.2}  {}",
+                     check.checkpoint, check.image_orders, check.image_bytes, ms(check.cold_start_ns), ms(check.warm_start_ns), result);
+        }
+    }
+    
+    if !report.passed() {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+fn verify_command(
+    cli: &Cli,
+    baseline_path: &Path,
+    update: bool,
+    steps: usize,
+    data: Option<&Path>,
+    pnl_tolerance: u64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut config = load_config(cli.config.as_deref(), cli.profile.as_deref())?;
+    apply_cli_overrides(&mut config, cli);
+    
+    let baseline = if update {
+        Baseline {
+            seed: config.simulation.random_seed.unwrap_or(42),
+            steps,
+            data_file: data.map(Path::to_path_buf),
+            pnl_tolerance,
+            expected: RunOutcome::default(),
+        }
+    } else {
+        Baseline::load(baseline_path).map_err(|e| {
+            eprintln!("❌ Failed to read baseline {}: {}", baseline_path.display(), e);
+            e
+        })?
+    };
+    config.simulation.random_seed = Some(baseline.seed);
+    
+    let mut simulator = build_simulator(&config, &Plugins::default())?;
+    let outcome = match &baseline.data_file {
+        Some(data_file) => {
+            println!("📼 Replaying {} (seed {})", data_file.display(), baseline.seed);
+            let source = apply_data_filters(open_data_file(data_file, &config, cli)?, &config, cli)?;
+            simulator = simulator.with_replay_source(source);
+            verify::run_replay(&mut simulator)?
+        }
+        None => {
+            println!("🎲 Running {} steps (seed {})", baseline.steps, baseline.seed);
+            verify::run_synthetic(&mut simulator, baseline.steps)?
+        }
+    };
+    println!("   trades {}  volume {}  hash {}  pnl {}", outcome.trades, outcome.volume, outcome.final_hash, outcome.pnl);
+    
+    if update {
+        Baseline { expected: outcome, ..baseline }.save(baseline_path)?;
+        println!("✅ Wrote baseline {}", baseline_path.display());
+        return Ok(());
+    }
+    
+    let mismatches = baseline.check(&outcome);
+    if mismatches.is_empty() {
+        println!("✅ Run matches baseline {}", baseline_path.display());
+        return Ok(());
+    }
+    for mismatch in &mismatches {
+        eprintln!("❌ {}", mismatch);
+    }
+    process::exit(1);
+}
+
+async fn conformance_command(url: String, timeout_ms: u64, json: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let config = ConformanceConfig { timeout: Duration::from_millis(timeout_ms), ..ConformanceConfig::new(url) };
+    if !json {
+        println!("🧪 Checking {} against protocol v{}...", config.url, orderbook::PROTOCOL_VERSION);
+    }
+    let report = conformance::run(&config).await;
+    
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        for check in &report.checks {
+            match &check.status {
+                CheckStatus::Pass => println!("✅ {:<28} {}", check.name, check.behavior),
+                CheckStatus::Fail(reason) => println!("❌ {:<28} {}\n   {}", check.name, check.behavior, reason),
+                CheckStatus::Skip(reason) => println!("⏭️  {:<28} {}", check.name, reason),
+            }
+        }
+        println!("{} passed, {} failed, {} skipped", report.passed(), report.failed(), report.skipped());
+    }
+    if !report.conforms() {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+fn scenario_command(name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(scenario) = Scenario::find(name) else {
+        let names: Vec<&str> = SCENARIOS.iter().map(|scenario| scenario.name).collect();
+        eprintln!("❌ Unknown scenario '{}' (available: {})", name, names.join(", "));
+        process::exit(1);
+    };
+    println!("🎬 Scenario {}: {}", scenario.name, scenario.description);
+    let config = scenario.config()?;
+    let simulator = build_simulator(&config, &Plugins::default())?;
+    let report = scenario.run(simulator)?;
+    
+    println!("   {} steps  trades {}  volume {}", report.steps, report.trades, report.volume);
+    for check in &report.checks {
+        let mark = if check.passed { "✅" } else { "❌" };
+        println!("{} {:<14} {}", mark, check.name, check.detail);
+    }
+    if !report.passed() {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+/// One-line summary of an engine command
+fn describe_command(command: &EngineCommand) -> String {
+    match command {
+        EngineCommand::Place(order) => match order.order_type {
+            OrderType::Limit { price } => format!("place {} {:?} {} @ {}", order.id, order.side, order.qty, price),
+            OrderType::Market => format!("place {} {:?} {} @ market", order.id, order.side, order.qty),
+        },
+        EngineCommand::Cancel(order_id) => format!("cancel {}", order_id),
+        command => format!("{:?}", command),
+    }
+}
+
+fn print_stage_row(stage: &StageReport) {
+    let allowed = stage.allowed.map_or_else(|| "-".to_string(), |allowed| allowed.to_string());
+    println!("{:>5} {:>9} {:>10} {:>10} {:>10} {:>10} {:>8}ns {:>8}ns {:>12} {:>8}",
+             stage.stage, stage.intensity, stage.messages, stage.throttled, stage.admitted, allowed,
+             stage.command_p99_ns, stage.publish_p99_ns, stage.feed_bytes, stage.book_levels);
+}
+
+fn print_stress_row(label: &str, report: &StressReport) {
+    println!("{:>8} {:>12.0} {:>7}ns {:>7}ns {:>7}ns {:>8}ns {:>8.1}MB {:>+8.1}MB",
+             label, report.ops_per_sec, report.p50_ns, report.p99_ns, report.p999_ns, report.max_ns,
+             report.rss_bytes as f64 / 1_048_576.0, report.rss_growth_bytes as f64 / 1_048_576.0);
+}
+
+fn load_config(config_path: Option<&std::path::Path>, profile: Option<&str>) -> Result<Config, ConfigError> {
+    let env_profile = env::var("ORDERBOOK_PROFILE").ok();
+    let profile = profile.or(env_profile.as_deref());
+    let path = match config_path {
+        Some(path) => path,
+        // Try to load from default locations
+        None if std::path::Path::new("config.toml").exists() || profile.is_some() => std::path::Path::new("config.toml"),
+        None => {
+            println!("📄 Using default configuration (no config file found)");
+            return Ok(Config::default());
+        }
+    };
+    match profile {
+        Some(profile) => println!("📄 Loading configuration from: {} (profile '{}')", path.display(), profile),
+        None => println!("📄 Loading configuration from: {}", path.display()),
+    }
+    if let Ok(content) = std::fs::read_to_string(path) {
+        for key in Config::unknown_keys(&content).unwrap_or_default() {
+            println!("⚠️  Ignoring unknown configuration key: {}", key);
+        }
+    }
+    Config::load_profile_from_file(path, profile)
 }
 
 fn apply_cli_overrides(config: &mut Config, cli: &Cli) {
@@ -260,13 +1270,38 @@ fn apply_cli_overrides(config: &mut Config, cli: &Cli) {
         config.data_source.default_json_file = Some(json_file.clone());
     }
     
+    if let Some(error_policy) = cli.on_error {
+        config.data_source.error_policy = error_policy;
+    }
+    
     if let Some(seed) = cli.seed {
         config.simulation.random_seed = Some(seed);
     }
     
+    if let Some(ref upstream) = cli.upstream {
+        config.relay.upstream = Some(upstream.clone());
+    }
+    
+    if let Some(ref report) = cli.report {
+        config.report.output = Some(report.clone());
+    }
+    
+    if let Some(ref features_out) = cli.features_out {
+        config.features.enabled = true;
+        config.features.path = features_out.clone();
+    }
+    
     if let Some(ref log_level) = cli.log_level {
         config.logging.level = log_level.clone();
     }
+    
+    if let Some(log_mode) = cli.log_mode {
+        config.logging.mode = log_mode;
+    }
+    
+    if cli.isolate_wall_clock {
+        config.simulation.isolate_wall_clock = true;
+    }
 }
 
 fn setup_logging(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
@@ -292,7 +1327,7 @@ fn print_config_summary(config: &Config, verbose: bool) {
         println!("   Message buffer size: {}", config.server.message_buffer_size);
         println!("   Random seed: {:?}", config.simulation.random_seed);
         println!("   Max depth levels: {}", config.simulation.max_depth_levels);
-        println!("   Network latency: {}μs", config.network.base_latency_ns / 1000);
+        println!("   Network latency: {}μs", config.network.order_path.base_latency_ns / 1000);
         println!("   Market maker spread: {} ticks", config.market_maker.target_spread);
         println!("   Data directory: {}", config.data_source.data_directory.display());
         
@@ -319,7 +1354,7 @@ fn validate_environment() -> Result<(), String> {
         let log_parts: Vec<&str> = rust_log.split(',').collect();
         
         for part in log_parts {
-            let level = part.split('=').last().unwrap_or(part);
+            let level = part.split('=').next_back().unwrap_or(part);
             if !valid_levels.contains(&level) && level != "off" {
                 return Err(format!("Invalid RUST_LOG level: {}", level));
             }
@@ -334,4 +1369,24 @@ fn validate_environment() -> Result<(), String> {
     }
     
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scenarios_pass_on_the_cli_simulator() {
+        for scenario in &SCENARIOS {
+            let config = scenario.config().unwrap();
+            let report = scenario.run(build_simulator(&config, &Plugins::default()).unwrap()).unwrap();
+            for check in &report.checks {
+                assert!(check.passed, "{} {}: {}", scenario.name, check.name, check.detail);
+            }
+
+            // Seeded agents over fixed data repeat exactly
+            let again = scenario.run(build_simulator(&config, &Plugins::default()).unwrap()).unwrap();
+            assert_eq!((again.trades, again.volume, again.cancels), (report.trades, report.volume, report.cancels));
+        }
+    }
+}
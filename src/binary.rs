@@ -0,0 +1,385 @@
+//! Fixed-layout binary frames for high-frequency depth
+//!
+//! `/ws` clients that negotiate the `binary_depth` capability get the book
+//! as WebSocket binary frames instead of JSON snapshots: a trade frame for
+//! each trade the snapshot prints, a BBO frame when the top of the book
+//! changes, then a depth frame listing the levels that changed, the first one
+//! listing the whole book, and a watermark frame when the snapshot carries a
+//! pipeline watermark. Command replies and session reports stay JSON text
+//! frames.
+//!
+//! Every field is little-endian and 8-byte aligned, so a client can read a
+//! frame in place as a C struct. The first byte gives the frame kind.
+//!
+//! BBO frame, 48 bytes:
+//!
+//! | Offset | Type    | Field                                     |
+//! |--------|---------|-------------------------------------------|
+//! | 0      | u8      | kind = 1                                  |
+//! | 1      | u8      | flags: bit 0 bid present, bit 1 ask present |
+//! | 2      | [u8; 6] | reserved, zero                            |
+//! | 8      | u64     | ts (ns)                                   |
+//! | 16     | u64     | bid price (ticks, 0 if absent)            |
+//! | 24     | u64     | bid qty                                   |
+//! | 32     | u64     | ask price (ticks, 0 if absent)            |
+//! | 40     | u64     | ask qty                                   |
+//!
+//! Depth frame, 24 bytes plus 16 per level:
+//!
+//! | Offset | Type    | Field                                     |
+//! |--------|---------|-------------------------------------------|
+//! | 0      | u8      | kind = 2                                  |
+//! | 1      | u8      | reserved, zero                            |
+//! | 2      | u16     | bid level count                           |
+//! | 4      | u16     | ask level count                           |
+//! | 6      | u16     | reserved, zero                            |
+//! | 8      | u64     | seq, contiguous per connection            |
+//! | 16     | u64     | ts (ns)                                   |
+//! | 24     | levels  | bids then asks, each u64 price and u64 qty in price order; qty 0 removes the level |
+//!
+//! Trade frame, 48 bytes:
+//!
+//! | Offset | Type    | Field                                     |
+//! |--------|---------|-------------------------------------------|
+//! | 0      | u8      | kind = 4                                  |
+//! | 1      | u8      | aggressor: 0 buy, 1 sell                  |
+//! | 2      | u8      | flags: bit 0 printed late as a block trade |
+//! | 3      | [u8; 5] | reserved, zero                            |
+//! | 8      | u64     | ts (ns)                                   |
+//! | 16     | u64     | price (ticks)                             |
+//! | 24     | u64     | qty                                       |
+//! | 32     | u64     | maker order id                            |
+//! | 40     | u64     | taker order id                            |
+//!
+//! Watermark frame, 40 bytes:
+//!
+//! | Offset | Type    | Field                                     |
+//! |--------|---------|-------------------------------------------|
+//! | 0      | u8      | kind = 3                                  |
+//! | 1      | [u8; 7] | reserved, zero                            |
+//! | 8      | u64     | events                                    |
+//! | 16     | u64     | first ingest (ns)                         |
+//! | 24     | u64     | last match (ns)                           |
+//! | 32     | u64     | publish (ns)                              |
+
+use crate::engine::{BboUpdate, DepthSnapshot};
+use crate::feed::{DepthUpdate, FeedSequencer, LevelUpdate};
+use crate::metrics::PipelineWatermark;
+use crate::types::{Side, Trade};
+
+/// Kind byte of a BBO frame
+pub const BBO_FRAME: u8 = 1;
+/// Kind byte of a depth frame
+pub const DEPTH_FRAME: u8 = 2;
+/// Kind byte of a watermark frame
+pub const WATERMARK_FRAME: u8 = 3;
+/// Kind byte of a trade frame
+pub const TRADE_FRAME: u8 = 4;
+/// Size of a BBO frame
+pub const BBO_FRAME_LEN: usize = 48;
+/// Size of a depth frame's fixed part
+pub const DEPTH_HEADER_LEN: usize = 24;
+/// Size of each level in a depth frame
+pub const LEVEL_LEN: usize = 16;
+/// Size of a watermark frame
+pub const WATERMARK_FRAME_LEN: usize = 40;
+/// Size of a trade frame
+pub const TRADE_FRAME_LEN: usize = 48;
+
+const BID_PRESENT: u8 = 1;
+const ASK_PRESENT: u8 = 2;
+const BLOCK_TRADE: u8 = 1;
+
+/// A decoded binary frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BinaryFrame {
+    Bbo(BboUpdate),
+    Depth(DepthUpdate),
+    Watermark(PipelineWatermark),
+    Trade { trade: Trade, block: bool },
+}
+
+fn timestamp(ts: u128) -> Result<u64, String> {
+    u64::try_from(ts).map_err(|_| format!("Timestamp {} does not fit a binary frame", ts))
+}
+
+/// Encode a BBO update as a BBO frame
+pub fn encode_bbo(bbo: &BboUpdate) -> Result<Vec<u8>, String> {
+    let mut frame = Vec::with_capacity(BBO_FRAME_LEN);
+    let flags = if bbo.best_bid.is_some() { BID_PRESENT } else { 0 } | if bbo.best_ask.is_some() { ASK_PRESENT } else { 0 };
+    frame.extend_from_slice(&[BBO_FRAME, flags, 0, 0, 0, 0, 0, 0]);
+    for field in [timestamp(bbo.ts)?, bbo.best_bid.unwrap_or(0), bbo.bid_qty, bbo.best_ask.unwrap_or(0), bbo.ask_qty] {
+        frame.extend_from_slice(&field.to_le_bytes());
+    }
+    Ok(frame)
+}
+
+/// Encode a depth update as a depth frame
+pub fn encode_depth(update: &DepthUpdate) -> Result<Vec<u8>, String> {
+    let count = |levels: &[LevelUpdate]| {
+        u16::try_from(levels.len()).map_err(|_| format!("{} levels do not fit a depth frame", levels.len()))
+    };
+    let (bids, asks) = (count(&update.bids)?, count(&update.asks)?);
+    let mut frame = Vec::with_capacity(DEPTH_HEADER_LEN + LEVEL_LEN * (update.bids.len() + update.asks.len()));
+    frame.extend_from_slice(&[DEPTH_FRAME, 0]);
+    frame.extend_from_slice(&bids.to_le_bytes());
+    frame.extend_from_slice(&asks.to_le_bytes());
+    frame.extend_from_slice(&[0, 0]);
+    frame.extend_from_slice(&update.seq.to_le_bytes());
+    frame.extend_from_slice(&timestamp(update.ts)?.to_le_bytes());
+    for level in update.bids.iter().chain(&update.asks) {
+        frame.extend_from_slice(&level.price.to_le_bytes());
+        frame.extend_from_slice(&level.qty.to_le_bytes());
+    }
+    Ok(frame)
+}
+
+/// Encode a pipeline watermark as a watermark frame
+pub fn encode_watermark(watermark: &PipelineWatermark) -> Result<Vec<u8>, String> {
+    let mut frame = Vec::with_capacity(WATERMARK_FRAME_LEN);
+    frame.extend_from_slice(&[WATERMARK_FRAME, 0, 0, 0, 0, 0, 0, 0]);
+    let stamps = [watermark.first_ingest_ns, watermark.last_match_ns, watermark.publish_ns];
+    frame.extend_from_slice(&(watermark.events as u64).to_le_bytes());
+    for stamp in stamps {
+        frame.extend_from_slice(&timestamp(stamp)?.to_le_bytes());
+    }
+    Ok(frame)
+}
+
+/// Encode a trade as a trade frame, `block` if it printed late as a block trade
+pub fn encode_trade(trade: &Trade, block: bool) -> Result<Vec<u8>, String> {
+    let mut frame = Vec::with_capacity(TRADE_FRAME_LEN);
+    let aggressor = match trade.aggressor {
+        Side::Buy => 0,
+        Side::Sell => 1,
+    };
+    frame.extend_from_slice(&[TRADE_FRAME, aggressor, if block { BLOCK_TRADE } else { 0 }, 0, 0, 0, 0, 0]);
+    for field in [timestamp(trade.ts)?, trade.price, trade.qty, trade.maker_id, trade.taker_id] {
+        frame.extend_from_slice(&field.to_le_bytes());
+    }
+    Ok(frame)
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().expect("8 bytes"))
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(bytes[offset..offset + 2].try_into().expect("2 bytes"))
+}
+
+/// Decode a BBO or depth frame
+pub fn decode(bytes: &[u8]) -> Result<BinaryFrame, String> {
+    match bytes.first() {
+        Some(&BBO_FRAME) => {
+            if bytes.len() != BBO_FRAME_LEN {
+                return Err(format!("BBO frame is {} bytes, expected {}", bytes.len(), BBO_FRAME_LEN));
+            }
+            let flags = bytes[1];
+            let price = |offset: usize, present: u8| (flags & present != 0).then(|| read_u64(bytes, offset));
+            Ok(BinaryFrame::Bbo(BboUpdate {
+                ts: read_u64(bytes, 8) as u128,
+                best_bid: price(16, BID_PRESENT),
+                bid_qty: read_u64(bytes, 24),
+                best_ask: price(32, ASK_PRESENT),
+                ask_qty: read_u64(bytes, 40),
+            }))
+        }
+        Some(&DEPTH_FRAME) => {
+            if bytes.len() < DEPTH_HEADER_LEN {
+                return Err(format!("Depth frame is {} bytes, shorter than its header", bytes.len()));
+            }
+            let (bids, asks) = (read_u16(bytes, 2) as usize, read_u16(bytes, 4) as usize);
+            let expected = DEPTH_HEADER_LEN + LEVEL_LEN * (bids + asks);
+            if bytes.len() != expected {
+                return Err(format!("Depth frame is {} bytes, expected {} for {} levels", bytes.len(), expected, bids + asks));
+            }
+            let mut levels = (0..bids + asks).map(|i| {
+                let offset = DEPTH_HEADER_LEN + LEVEL_LEN * i;
+                LevelUpdate { price: read_u64(bytes, offset), qty: read_u64(bytes, offset + 8) }
+            });
+            Ok(BinaryFrame::Depth(DepthUpdate {
+                seq: read_u64(bytes, 8),
+                ts: read_u64(bytes, 16) as u128,
+                bids: levels.by_ref().take(bids).collect(),
+                asks: levels.collect(),
+                watermark: None,
+            }))
+        }
+        Some(&WATERMARK_FRAME) => {
+            if bytes.len() != WATERMARK_FRAME_LEN {
+                return Err(format!("Watermark frame is {} bytes, expected {}", bytes.len(), WATERMARK_FRAME_LEN));
+            }
+            Ok(BinaryFrame::Watermark(PipelineWatermark {
+                events: read_u64(bytes, 8) as usize,
+                first_ingest_ns: read_u64(bytes, 16) as u128,
+                last_match_ns: read_u64(bytes, 24) as u128,
+                publish_ns: read_u64(bytes, 32) as u128,
+            }))
+        }
+        Some(&TRADE_FRAME) => {
+            if bytes.len() != TRADE_FRAME_LEN {
+                return Err(format!("Trade frame is {} bytes, expected {}", bytes.len(), TRADE_FRAME_LEN));
+            }
+            let aggressor = match bytes[1] {
+                0 => Side::Buy,
+                1 => Side::Sell,
+                other => return Err(format!("Unknown aggressor {} in trade frame", other)),
+            };
+            Ok(BinaryFrame::Trade {
+                trade: Trade {
+                    maker_id: read_u64(bytes, 32),
+                    taker_id: read_u64(bytes, 40),
+                    price: read_u64(bytes, 16),
+                    qty: read_u64(bytes, 24),
+                    ts: read_u64(bytes, 8) as u128,
+                    aggressor,
+                },
+                block: bytes[2] & BLOCK_TRADE != 0,
+            })
+        }
+        Some(kind) => Err(format!("Unknown binary frame kind {}", kind)),
+        None => Err("Empty binary frame".to_string()),
+    }
+}
+
+/// Binary frames for one connection's stream of snapshots
+#[derive(Debug, Clone, Default)]
+pub struct BinaryDepthStream {
+    depth: FeedSequencer,
+    last_bbo: Option<BboUpdate>,
+}
+
+impl BinaryDepthStream {
+    /// Start a stream whose first depth frame lists the whole book
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Frames for a snapshot: one per trade, a BBO frame if the top of the
+    /// book changed, then a depth frame if any level did, then its watermark
+    /// if it has one
+    ///
+    /// A snapshot that cannot be encoded leaves the stream as it was, so the
+    /// next depth frame still follows on from the last one sent.
+    pub fn frames(&mut self, snapshot: &DepthSnapshot) -> Result<Vec<Vec<u8>>, String> {
+        let mut frames = snapshot.trades.iter()
+            .map(|trade| encode_trade(trade, snapshot.block_trades.contains(trade)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let watermark = snapshot.watermark.as_ref().map(encode_watermark).transpose()?;
+        // Check what could fail before any state moves on
+        timestamp(snapshot.ts)?;
+        for (side, levels) in [(Side::Buy, &snapshot.bids), (Side::Sell, &snapshot.asks)] {
+            let most = self.depth.level_count(side) + levels.len();
+            if most > u16::MAX as usize {
+                return Err(format!("{} levels may not fit a depth frame", most));
+            }
+        }
+
+        let bbo = BboUpdate::of(snapshot);
+        if !self.last_bbo.is_some_and(|last| last.same_quote(&bbo)) {
+            self.last_bbo = Some(bbo);
+            frames.push(encode_bbo(&bbo)?);
+        }
+        if let Some(update) = self.depth.on_depth(snapshot) {
+            frames.push(encode_depth(&update)?);
+        }
+        frames.extend(watermark);
+        Ok(frames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{OrderBook, OrderBookEngine};
+    use crate::queue_fifo::FifoLevel;
+    use crate::types::{Order, Side};
+
+    #[test]
+    fn test_frames_round_trip_at_their_documented_layout() {
+        let bbo = BboUpdate { ts: 7, best_bid: Some(100_0000), bid_qty: 30, best_ask: None, ask_qty: 0 };
+        let frame = encode_bbo(&bbo).unwrap();
+        assert_eq!(frame.len(), BBO_FRAME_LEN);
+        assert_eq!(&frame[..2], &[BBO_FRAME, BID_PRESENT]);
+        assert_eq!(frame[16..24], 100_0000u64.to_le_bytes());
+        assert_eq!(decode(&frame).unwrap(), BinaryFrame::Bbo(bbo));
+
+        let update = DepthUpdate {
+            seq: 5,
+            ts: 9,
+            bids: vec![LevelUpdate { price: 99_0000, qty: 0 }, LevelUpdate { price: 100_0000, qty: 30 }],
+            asks: vec![LevelUpdate { price: 101_0000, qty: 20 }],
+            watermark: None,
+        };
+        let frame = encode_depth(&update).unwrap();
+        assert_eq!(frame.len(), DEPTH_HEADER_LEN + 3 * LEVEL_LEN);
+        assert_eq!((read_u16(&frame, 2), read_u16(&frame, 4), read_u64(&frame, 8)), (2, 1, 5));
+        assert_eq!(decode(&frame).unwrap(), BinaryFrame::Depth(update));
+
+        let watermark = PipelineWatermark { events: 2, first_ingest_ns: 1_000, last_match_ns: 2_100, publish_ns: 3_000 };
+        let marked = encode_watermark(&watermark).unwrap();
+        assert_eq!((marked.len(), marked[0]), (WATERMARK_FRAME_LEN, WATERMARK_FRAME));
+        assert_eq!(decode(&marked).unwrap(), BinaryFrame::Watermark(watermark));
+
+        let trade = Trade { maker_id: 3, taker_id: 4, price: 101_0000, qty: 15, ts: 11, aggressor: Side::Sell };
+        let printed = encode_trade(&trade, true).unwrap();
+        assert_eq!((printed.len(), &printed[..3]), (TRADE_FRAME_LEN, &[TRADE_FRAME, 1, BLOCK_TRADE][..]));
+        assert_eq!(decode(&printed).unwrap(), BinaryFrame::Trade { trade, block: true });
+
+        assert!(decode(&frame[..frame.len() - 1]).is_err());
+        assert!(decode(&[9]).is_err());
+        assert!(decode(&[]).is_err());
+    }
+
+    #[test]
+    fn test_stream_sends_changes_only() {
+        let mut book = OrderBook::<FifoLevel>::new();
+        book.set_clock(1);
+        book.place(Order::new_limit(1, Side::Buy, 30, 100_0000, 1)).unwrap();
+        book.place(Order::new_limit(2, Side::Sell, 20, 101_0000, 1)).unwrap();
+        let mut stream = BinaryDepthStream::new();
+
+        let frames = stream.frames(&book.snapshot()).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert!(matches!(decode(&frames[1]).unwrap(), BinaryFrame::Depth(update) if update.seq == 1 && update.bids.len() == 1));
+        assert!(stream.frames(&book.snapshot()).unwrap().is_empty());
+
+        // A level behind the touch moves depth but not the BBO
+        book.place(Order::new_limit(3, Side::Buy, 10, 99_0000, 2)).unwrap();
+        let frames = stream.frames(&book.snapshot()).unwrap();
+        assert_eq!(frames.len(), 1);
+        let BinaryFrame::Depth(update) = decode(&frames[0]).unwrap() else { panic!("expected a depth frame") };
+        assert_eq!((update.seq, update.bids.clone()), (2, vec![LevelUpdate { price: 99_0000, qty: 10 }]));
+
+        // A watermark goes out even when the book did not move
+        let mut snapshot = book.snapshot();
+        snapshot.watermark = Some(PipelineWatermark { events: 1, first_ingest_ns: 5, last_match_ns: 6, publish_ns: 7 });
+        let frames = stream.frames(&snapshot).unwrap();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(decode(&frames[0]).unwrap(), BinaryFrame::Watermark(snapshot.watermark.unwrap()));
+    }
+
+    #[test]
+    fn test_stream_sends_trades_and_survives_encode_errors() {
+        let mut book = OrderBook::<FifoLevel>::new();
+        book.set_clock(1);
+        book.place(Order::new_limit(1, Side::Sell, 20, 101_0000, 1)).unwrap();
+        let mut stream = BinaryDepthStream::new();
+        assert_eq!(stream.frames(&book.snapshot()).unwrap().len(), 2);
+
+        // A snapshot that cannot be encoded sends nothing and moves nothing on
+        let trades = book.place(Order::new_market(2, Side::Buy, 5, 1)).unwrap();
+        let mut snapshot = book.snapshot();
+        snapshot.trades = trades.clone();
+        let unencodable = DepthSnapshot { ts: u64::MAX as u128 + 1, ..snapshot.clone() };
+        assert!(stream.frames(&unencodable).is_err());
+
+        // The trade comes first, and the depth frame follows on from the last sent
+        let frames = stream.frames(&snapshot).unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(decode(&frames[0]).unwrap(), BinaryFrame::Trade { trade: trades[0].clone(), block: false });
+        assert!(matches!(decode(&frames[1]).unwrap(), BinaryFrame::Bbo(bbo) if bbo.ask_qty == 15));
+        assert!(matches!(decode(&frames[2]).unwrap(), BinaryFrame::Depth(update) if update.seq == 2));
+    }
+}
@@ -0,0 +1,153 @@
+//! Shape of the visible book
+//!
+//! Microstructure descriptors commonly used to compare a simulated book with
+//! a real one: how concentrated each side's depth is in its top levels, how
+//! quickly depth builds up away from the touch (the book slope), and how
+//! evenly depth is spread across levels (the depth entropy). All of them are
+//! taken over the levels of one snapshot.
+
+use serde::{Deserialize, Serialize};
+use crate::engine::{BookLevelPoint, DepthSnapshot};
+use crate::types::{Price, Qty};
+
+/// Share of a side's depth within its top levels
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Concentration {
+    /// Levels counted from the touch
+    pub levels: usize,
+    /// Fraction of the side's depth in them
+    pub share: f64,
+}
+
+/// Descriptors of one side of the book
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SideShape {
+    /// Non-empty price levels
+    pub levels: usize,
+    pub depth: Qty,
+    pub concentration: Vec<Concentration>,
+    /// Least-squares slope of cumulative depth against distance from the
+    /// touch (quantity per tick); needs at least two levels
+    pub slope: Option<f64>,
+    /// Shannon entropy of depth across levels (nats)
+    pub entropy: f64,
+    /// Entropy as a fraction of its maximum for this many levels: 1 when
+    /// depth is spread evenly, 0 when it sits on a single level
+    pub normalized_entropy: f64,
+}
+
+impl SideShape {
+    /// Describe one side from its levels, best first
+    pub fn from_levels(levels: &[BookLevelPoint], concentration_levels: &[usize]) -> Self {
+        let levels: Vec<(Price, Qty)> = levels.iter()
+            .filter(|level| level.qty > 0)
+            .map(|level| (level.price, level.qty))
+            .collect();
+        let depth: Qty = levels.iter().map(|&(_, qty)| qty).sum();
+        if depth == 0 {
+            return Self {
+                concentration: concentration_levels.iter().map(|&levels| Concentration { levels, share: 0.0 }).collect(),
+                ..Self::default()
+            };
+        }
+
+        let concentration = concentration_levels.iter()
+            .map(|&top| {
+                let qty: Qty = levels.iter().take(top).map(|&(_, qty)| qty).sum();
+                Concentration { levels: top, share: qty as f64 / depth as f64 }
+            })
+            .collect();
+
+        let entropy = -levels.iter()
+            .map(|&(_, qty)| qty as f64 / depth as f64)
+            .map(|p| p * p.ln())
+            .sum::<f64>();
+        let normalized_entropy = if levels.len() > 1 { entropy / (levels.len() as f64).ln() } else { 0.0 };
+
+        Self {
+            levels: levels.len(),
+            depth,
+            concentration,
+            slope: Self::slope(&levels),
+            entropy,
+            normalized_entropy,
+        }
+    }
+
+    fn slope(levels: &[(Price, Qty)]) -> Option<f64> {
+        if levels.len() < 2 {
+            return None;
+        }
+        let touch = levels[0].0;
+        let mut cumulative: Qty = 0;
+        let points: Vec<(f64, f64)> = levels.iter()
+            .map(|&(price, qty)| {
+                cumulative += qty;
+                (price.abs_diff(touch) as f64, cumulative as f64)
+            })
+            .collect();
+        let n = points.len() as f64;
+        let mean_x = points.iter().map(|&(x, _)| x).sum::<f64>() / n;
+        let mean_y = points.iter().map(|&(_, y)| y).sum::<f64>() / n;
+        let covariance: f64 = points.iter().map(|&(x, y)| (x - mean_x) * (y - mean_y)).sum();
+        let variance: f64 = points.iter().map(|&(x, _)| (x - mean_x).powi(2)).sum();
+        (variance > 0.0).then(|| covariance / variance)
+    }
+}
+
+/// Descriptors of both sides of the book
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BookShape {
+    pub bids: SideShape,
+    pub asks: SideShape,
+}
+
+impl BookShape {
+    /// Describe the book in `snapshot`, reporting the depth share of each of
+    /// the top `concentration_levels`
+    pub fn from_snapshot(snapshot: &DepthSnapshot, concentration_levels: &[usize]) -> Self {
+        Self {
+            bids: SideShape::from_levels(&snapshot.bids, concentration_levels),
+            asks: SideShape::from_levels(&snapshot.asks, concentration_levels),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn levels(levels: &[(Price, Qty)]) -> Vec<BookLevelPoint> {
+        levels.iter().map(|&(price, qty)| BookLevelPoint { price, qty, latency_ms: 0, orders: 1, notional: price as u128 * qty as u128 }).collect()
+    }
+
+    #[test]
+    fn test_concentration_slope_and_entropy() {
+        // Bids 100 ticks apart, best first, depth building away from the touch
+        let bids = levels(&[(100_0000, 10), (99_9900, 20), (99_9800, 30), (99_9700, 40)]);
+        let shape = SideShape::from_levels(&bids, &[1, 2, 10]);
+        assert_eq!((shape.levels, shape.depth), (4, 100));
+        let shares: Vec<f64> = shape.concentration.iter().map(|c| c.share).collect();
+        assert_eq!(shares, vec![0.1, 0.3, 1.0]);
+
+        // Cumulative depth 10, 30, 60, 100 over 0..300 ticks
+        assert!((shape.slope.unwrap() - 0.3).abs() < 1e-9);
+        let expected: f64 = -[0.1f64, 0.2, 0.3, 0.4].iter().map(|p| p * p.ln()).sum::<f64>();
+        assert!((shape.entropy - expected).abs() < 1e-12);
+        assert!(shape.normalized_entropy > 0.9 && shape.normalized_entropy < 1.0);
+    }
+
+    #[test]
+    fn test_degenerate_sides() {
+        let even = SideShape::from_levels(&levels(&[(101_0000, 5), (101_0100, 5)]), &[1]);
+        assert!((even.normalized_entropy - 1.0).abs() < 1e-12);
+
+        let single = SideShape::from_levels(&levels(&[(101_0000, 5)]), &[1, 5]);
+        assert_eq!((single.entropy, single.normalized_entropy, single.slope), (0.0, 0.0, None));
+        assert_eq!(single.concentration[1].share, 1.0);
+
+        let empty = SideShape::from_levels(&[], &[5]);
+        assert_eq!(empty.depth, 0);
+        assert_eq!(empty.concentration, vec![Concentration { levels: 5, share: 0.0 }]);
+    }
+}
@@ -0,0 +1,171 @@
+//! Named moments in a dataset's replay
+//!
+//! Analysts going through a replay together want to mark the moments worth
+//! coming back to. A [`Bookmark`] names a replay timestamp and carries a
+//! note. Bookmarks are kept per dataset, one JSON file each under
+//! `bookmarks.dir`, so everyone replaying the same file shares them and
+//! they outlive the server. A dataset is named by its canonical path, so
+//! files of the same name in different directories keep their own. Seeking the replay to a bookmark rebuilds the
+//! book as it stood at that moment.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use sha2::{Digest, Sha256};
+
+/// Where bookmarks are saved
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct BookmarkConfig {
+    /// Directory holding one bookmark file per dataset
+    pub dir: PathBuf,
+}
+
+impl Default for BookmarkConfig {
+    fn default() -> Self {
+        Self { dir: PathBuf::from("bookmarks") }
+    }
+}
+
+impl BookmarkConfig {
+    /// Check the directory
+    pub fn validate(&self) -> Result<(), String> {
+        if self.dir.as_os_str().is_empty() {
+            return Err("Bookmark directory must not be empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Bookmark error types
+#[derive(Debug, thiserror::Error)]
+pub enum BookmarkError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Serialization error: {0}")]
+    Serialize(#[from] serde_json::Error),
+}
+
+pub type BookmarkResult<T> = Result<T, BookmarkError>;
+
+/// A named moment in a dataset's replay
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub name: String,
+    /// Replay time it marks (nanoseconds, as the data has it)
+    pub timestamp: u128,
+    #[serde(default)]
+    pub note: String,
+    /// Wall-clock time it was made (milliseconds since the epoch)
+    pub created_at_ms: u64,
+}
+
+/// Bookmarks saved to disk, by dataset
+#[derive(Debug, Clone)]
+pub struct BookmarkStore {
+    dir: PathBuf,
+}
+
+impl BookmarkStore {
+    pub fn new(config: &BookmarkConfig) -> Self {
+        Self { dir: config.dir.clone() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// A dataset's bookmarks in timestamp order
+    pub fn list(&self, dataset: &str) -> BookmarkResult<Vec<Bookmark>> {
+        let path = self.path(dataset);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    /// One of a dataset's bookmarks by name
+    pub fn get(&self, dataset: &str, name: &str) -> BookmarkResult<Option<Bookmark>> {
+        Ok(self.list(dataset)?.into_iter().find(|bookmark| bookmark.name == name))
+    }
+
+    /// Save a bookmark for a dataset, replacing any of the same name
+    pub fn save(&self, dataset: &str, bookmark: Bookmark) -> BookmarkResult<()> {
+        let mut bookmarks = self.list(dataset)?;
+        bookmarks.retain(|existing| existing.name != bookmark.name);
+        bookmarks.push(bookmark);
+        bookmarks.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then_with(|| a.name.cmp(&b.name)));
+        self.write(dataset, &bookmarks)
+    }
+
+    /// Remove a dataset's bookmark, returning whether it existed
+    pub fn remove(&self, dataset: &str, name: &str) -> BookmarkResult<bool> {
+        let mut bookmarks = self.list(dataset)?;
+        let before = bookmarks.len();
+        bookmarks.retain(|bookmark| bookmark.name != name);
+        if bookmarks.len() == before {
+            return Ok(false);
+        }
+        self.write(dataset, &bookmarks)?;
+        Ok(true)
+    }
+
+    /// Write through a temporary file, so a crash never leaves half a list
+    fn write(&self, dataset: &str, bookmarks: &[Bookmark]) -> BookmarkResult<()> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(dataset);
+        let temp = path.with_extension("json.tmp");
+        fs::write(&temp, serde_json::to_vec_pretty(bookmarks)?)?;
+        fs::rename(temp, path)?;
+        Ok(())
+    }
+
+    /// File for a dataset: its file name reduced to characters safe in a
+    /// path, and a digest of the whole name to tell apart datasets whose
+    /// file names match
+    fn path(&self, dataset: &str) -> PathBuf {
+        let file_name = Path::new(dataset).file_name().map_or_else(|| dataset.into(), |name| name.to_string_lossy());
+        let file: String = file_name.chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+            .collect();
+        let digest = hex::encode(&Sha256::digest(dataset.as_bytes())[..8]);
+        self.dir.join(format!("{}-{}.json", file.trim_start_matches('.'), digest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bookmark(name: &str, timestamp: u128) -> Bookmark {
+        Bookmark { name: name.to_string(), timestamp, note: String::new(), created_at_ms: 0 }
+    }
+
+    #[test]
+    fn test_bookmarks_persist_per_dataset() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = BookmarkStore::new(&BookmarkConfig { dir: dir.path().join("marks") });
+        store.save("aapl.csv", bookmark("open", 2_000)).unwrap();
+        store.save("aapl.csv", bookmark("flash", 1_000)).unwrap();
+        store.save("msft.csv", bookmark("open", 5_000)).unwrap();
+        store.save("/data/2024/aapl.csv", bookmark("close", 9_000)).unwrap();
+        store.save("aapl.csv", Bookmark { note: "the big one".to_string(), ..bookmark("flash", 1_500) }).unwrap();
+
+        // A second store on the same directory sees them, as after a restart
+        let reopened = BookmarkStore::new(&BookmarkConfig { dir: dir.path().join("marks") });
+        let names: Vec<_> = reopened.list("aapl.csv").unwrap().into_iter().map(|bookmark| (bookmark.name, bookmark.timestamp)).collect();
+        assert_eq!(names, vec![("flash".to_string(), 1_500), ("open".to_string(), 2_000)]);
+        assert_eq!(reopened.get("aapl.csv", "flash").unwrap().unwrap().note, "the big one");
+        assert_eq!(reopened.get("msft.csv", "open").unwrap().unwrap().timestamp, 5_000);
+        // A file of the same name elsewhere is another dataset
+        assert_eq!(reopened.list("/data/2024/aapl.csv").unwrap().len(), 1);
+        assert!(reopened.get("/data/2025/aapl.csv", "close").unwrap().is_none());
+
+        assert!(reopened.remove("aapl.csv", "open").unwrap());
+        assert!(!reopened.remove("aapl.csv", "open").unwrap());
+        assert!(reopened.list("../escape").unwrap().is_empty());
+        assert!(reopened.path("../escape").starts_with(reopened.dir()));
+    }
+}
@@ -0,0 +1,292 @@
+use crate::data::{DataError, DataFormat, DataFormatDetector, DataResult, MarketEvent};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Stop scanning a file after this many consecutive unreadable records
+const MAX_CONSECUTIVE_SCAN_ERRORS: usize = 1000;
+
+/// Summary of a single data file in the catalog
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DatasetEntry {
+    /// Path relative to the catalog root
+    pub path: PathBuf,
+    /// Detected data format
+    pub format: String,
+    /// File size in bytes
+    pub file_size: u64,
+    /// Last modification time (seconds since UNIX epoch)
+    pub modified: u64,
+    /// Number of events successfully parsed
+    pub event_count: usize,
+    /// Number of records that failed to parse
+    pub error_count: usize,
+    /// First and last event timestamps (nanoseconds)
+    pub time_range: Option<(u128, u128)>,
+    /// Symbols present in the file
+    pub symbols: Vec<String>,
+    /// Event counts keyed by event type
+    pub event_types: BTreeMap<String, usize>,
+}
+
+impl DatasetEntry {
+    /// Scan a data file and summarize its contents
+    pub fn scan(root: &Path, path: &Path) -> DataResult<Self> {
+        let file_metadata = fs::metadata(path)?;
+        let format = DataFormatDetector::detect_format(path)?;
+        let mut data_source = DataFormatDetector::create_data_source(path)?;
+
+        // Read as fast as possible rather than at playback speed
+        data_source.set_paused(true)?;
+
+        let mut event_count = 0;
+        let mut error_count = 0;
+        let mut consecutive_errors = 0;
+        let mut time_range: Option<(u128, u128)> = None;
+        let mut event_types = BTreeMap::new();
+
+        loop {
+            match data_source.next_event() {
+                Ok(Some(event)) => {
+                    consecutive_errors = 0;
+                    event_count += 1;
+                    *event_types.entry(event_type_name(&event).to_string()).or_insert(0) += 1;
+
+                    let ts = event.timestamp();
+                    time_range = Some(match time_range {
+                        Some((start, end)) => (start.min(ts), end.max(ts)),
+                        None => (ts, ts),
+                    });
+                }
+                Ok(None) => break,
+                Err(_) => {
+                    error_count += 1;
+                    consecutive_errors += 1;
+                    if consecutive_errors >= MAX_CONSECUTIVE_SCAN_ERRORS {
+                        break;
+                    }
+                }
+            }
+        }
+
+        let mut symbols: Vec<String> = data_source.metadata().symbol_counts.into_keys().collect();
+        symbols.sort();
+
+        Ok(Self {
+            path: path.strip_prefix(root).unwrap_or(path).to_path_buf(),
+            format: format.description().to_string(),
+            file_size: file_metadata.len(),
+            modified: modified_secs(&file_metadata),
+            event_count,
+            error_count,
+            time_range,
+            symbols,
+            event_types,
+        })
+    }
+
+    /// Check whether the entry still describes the file on disk
+    fn is_current(&self, metadata: &fs::Metadata) -> bool {
+        self.file_size == metadata.len() && self.modified == modified_secs(metadata)
+    }
+}
+
+/// Index of the data files available under a directory
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DatasetCatalog {
+    /// Directory the catalog was built from
+    pub root: PathBuf,
+    /// Datasets sorted by path
+    pub datasets: Vec<DatasetEntry>,
+}
+
+impl DatasetCatalog {
+    /// Name of the index file written into the catalog root
+    pub const INDEX_FILE: &'static str = ".orderbook-catalog.json";
+
+    /// Build a catalog from scratch, scanning every data file
+    pub fn scan<P: AsRef<Path>>(root: P) -> DataResult<Self> {
+        Self::build(root.as_ref(), None)
+    }
+
+    /// Rebuild the catalog, reusing index entries for unchanged files,
+    /// and write the updated index back to the directory
+    pub fn refresh<P: AsRef<Path>>(root: P) -> DataResult<Self> {
+        let root = root.as_ref();
+        let previous = Self::load_index(root).ok();
+        let catalog = Self::build(root, previous.as_ref())?;
+
+        // A read-only data directory still gets a usable catalog
+        if let Err(e) = catalog.save_index() {
+            tracing::warn!("Failed to write catalog index in {}: {}", root.display(), e);
+        }
+        Ok(catalog)
+    }
+
+    /// Load a previously written index file
+    pub fn load_index<P: AsRef<Path>>(root: P) -> DataResult<Self> {
+        let index_path = root.as_ref().join(Self::INDEX_FILE);
+        let contents = fs::read_to_string(&index_path)?;
+        serde_json::from_str(&contents).map_err(|e| {
+            DataError::invalid_format(index_path.display().to_string(), e.to_string())
+        })
+    }
+
+    /// Write the index file into the catalog root
+    pub fn save_index(&self) -> DataResult<()> {
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|e| DataError::validation(format!("Failed to serialize catalog: {}", e)))?;
+        fs::write(self.root.join(Self::INDEX_FILE), contents)?;
+        Ok(())
+    }
+
+    /// Look up a dataset by its path relative to the root
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> Option<&DatasetEntry> {
+        self.datasets.iter().find(|entry| entry.path == path.as_ref())
+    }
+
+    fn build(root: &Path, previous: Option<&DatasetCatalog>) -> DataResult<Self> {
+        let mut files = Vec::new();
+        collect_data_files(root, &mut files)?;
+        files.sort();
+
+        let mut datasets = Vec::with_capacity(files.len());
+        for path in files {
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            let metadata = fs::metadata(&path)?;
+
+            let cached = previous
+                .and_then(|catalog| catalog.get(relative))
+                .filter(|entry| entry.is_current(&metadata));
+
+            match cached {
+                Some(entry) => datasets.push(entry.clone()),
+                None => match DatasetEntry::scan(root, &path) {
+                    Ok(entry) => datasets.push(entry),
+                    Err(e) => tracing::warn!("Skipping dataset {}: {}", path.display(), e),
+                },
+            }
+        }
+
+        Ok(Self {
+            root: root.to_path_buf(),
+            datasets,
+        })
+    }
+}
+
+/// Recursively collect files with a known data format extension
+fn collect_data_files(dir: &Path, files: &mut Vec<PathBuf>) -> DataResult<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let hidden = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with('.'));
+        if hidden {
+            continue;
+        }
+
+        if path.is_dir() {
+            collect_data_files(&path, files)?;
+        } else if is_data_file(&path) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn is_data_file(path: &Path) -> bool {
+    let Some(extension) = path.extension().and_then(|ext| ext.to_str()) else {
+        return false;
+    };
+    let extension = extension.to_lowercase();
+    [DataFormat::Csv, DataFormat::Json, DataFormat::Binary]
+        .iter()
+        .any(|format| format.extensions().contains(&extension.as_str()))
+}
+
+fn modified_secs(metadata: &fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+fn event_type_name(event: &MarketEvent) -> &'static str {
+    match event {
+        MarketEvent::Trade { .. } => "trade",
+        MarketEvent::Quote { .. } => "quote",
+        MarketEvent::OrderPlacement(_) => "order",
+        MarketEvent::OrderCancellation { .. } => "cancel",
+        MarketEvent::OrderModification { .. } => "modify",
+        MarketEvent::MarketStatus { .. } => "status",
+        MarketEvent::BestBidOffer { .. } => "bbo",
+        MarketEvent::Heartbeat { .. } => "heartbeat",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn write_csv(dir: &Path, name: &str) {
+        let mut file = fs::File::create(dir.join(name)).unwrap();
+        writeln!(file, "type,timestamp,price,qty,side,trade_id").unwrap();
+        writeln!(file, "trade,1000000000,100.25,500,buy,T001").unwrap();
+        writeln!(file, "trade,1000000100,100.30,200,sell,T002").unwrap();
+        writeln!(file, "quote,1000000200,100.20,100.35,1000,1500").unwrap();
+    }
+
+    #[test]
+    fn test_catalog_scan_extracts_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        write_csv(dir.path(), "session.csv");
+        fs::create_dir(dir.path().join("nested")).unwrap();
+        write_csv(&dir.path().join("nested"), "other.csv");
+        fs::write(dir.path().join("notes.txt"), "not data").unwrap();
+
+        let catalog = DatasetCatalog::scan(dir.path()).unwrap();
+        assert_eq!(catalog.datasets.len(), 2);
+
+        let entry = catalog.get("session.csv").unwrap();
+        assert_eq!(entry.event_count, 3);
+        assert_eq!(entry.time_range, Some((1000000000, 1000000200)));
+        assert_eq!(entry.event_types.get("trade"), Some(&2));
+        assert_eq!(entry.event_types.get("quote"), Some(&1));
+        assert!(catalog.get(Path::new("nested").join("other.csv")).is_some());
+    }
+
+    #[test]
+    fn test_catalog_lists_symbols() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut file = fs::File::create(dir.path().join("multi.csv")).unwrap();
+        writeln!(file, "type,symbol,timestamp,price,qty,side").unwrap();
+        writeln!(file, "trade,MSFT,1000000000,300.10,500,buy").unwrap();
+        writeln!(file, "trade,AAPL,1000000100,100.30,200,sell").unwrap();
+        drop(file);
+
+        let catalog = DatasetCatalog::scan(dir.path()).unwrap();
+        let entry = catalog.get("multi.csv").unwrap();
+        assert_eq!(entry.symbols, vec!["AAPL".to_string(), "MSFT".to_string()]);
+    }
+
+    #[test]
+    fn test_catalog_refresh_writes_and_reuses_index() {
+        let dir = tempfile::tempdir().unwrap();
+        write_csv(dir.path(), "session.csv");
+
+        let catalog = DatasetCatalog::refresh(dir.path()).unwrap();
+        assert!(dir.path().join(DatasetCatalog::INDEX_FILE).exists());
+
+        // The index file itself is never cataloged
+        let reloaded = DatasetCatalog::refresh(dir.path()).unwrap();
+        assert_eq!(catalog, reloaded);
+        assert_eq!(DatasetCatalog::load_index(dir.path()).unwrap(), catalog);
+    }
+}
@@ -0,0 +1,623 @@
+//! Typed async client for the `/ws` endpoint
+//!
+//! [`OrderBookClient`] wraps a WebSocket connection: commands go out as
+//! [`ClientRequest`]s and are matched to their [`ServerReply`] by request id,
+//! while snapshots and the trades in them are fanned out to subscribers.
+//! On connect the client says hello and keeps the protocol version and
+//! capabilities the server agreed to.
+//!
+//! After [`OrderBookClient::follow_orders`] the client also rebuilds the book
+//! order by order from the server's market-by-order feed in a
+//! [`BookBuilder`], and checks it against every snapshot the feed numbered.
+//! A `BookBuilder` works on its own too, as a reference for consumers that
+//! build books from the feed themselves.
+//!
+//! ```no_run
+//! # async fn demo() -> orderbook::client::ClientResult<()> {
+//! use orderbook::client::OrderBookClient;
+//! use orderbook::Side;
+//!
+//! let client = OrderBookClient::connect("ws://localhost:8080/ws").await?;
+//! let mut depth = client.subscribe_depth();
+//! let ack = client.place_limit(Side::Buy, 100, 99_0000).await?;
+//! println!("order {:?} traded {} times", ack.order_id, ack.trades.len());
+//! let snapshot = depth.recv().await.unwrap();
+//! println!("best bid {:?}", snapshot.best_bid);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use futures_util::stream::{SplitSink, StreamExt};
+use futures_util::SinkExt;
+use tokio::net::TcpStream;
+use tokio::sync::{broadcast, oneshot, Mutex};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use tracing::warn;
+use crate::engine::{BookLevelPoint, DepthSnapshot};
+use crate::mbo::{OrderUpdate, OrderUpdates};
+use crate::price_format::PriceFormat;
+use crate::sim::ReplayStatus;
+use crate::protocol::{ApiError, Capability, ClientCommand, ClientRequest, CommandAck, ErrorCode, Handshake, PlaybackFrame, ServerMessage, ServerReply, PROTOCOL_VERSION};
+use crate::types::{OrderId, Price, Qty, Side, Trade};
+
+type WsSink = SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>;
+type Pending = Arc<std::sync::Mutex<HashMap<u64, oneshot::Sender<ServerReply>>>>;
+type SharedBook = Arc<std::sync::Mutex<BookBuilder>>;
+
+/// Client error types
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    #[error("WebSocket error: {0}")]
+    WebSocket(#[from] tokio_tungstenite::tungstenite::Error),
+
+    #[error("Failed to encode request: {0}")]
+    Encode(#[from] serde_json::Error),
+
+    #[error("Command rejected: {0}")]
+    Rejected(ApiError),
+
+    #[error("Unexpected reply: {0}")]
+    UnexpectedReply(String),
+
+    #[error("No reply within {0:?}")]
+    Timeout(Duration),
+
+    #[error("Connection closed")]
+    Closed,
+}
+
+pub type ClientResult<T> = Result<T, ClientError>;
+
+/// Ways a rebuilt book falls out of step with the server's
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum BookBuildError {
+    #[error("Order updates {got} arrived after {applied}; the ones between are missing")]
+    Gap { applied: u64, got: u64 },
+
+    #[error("Rebuilt {side:?} level {rebuilt:?} differs from {published:?} in snapshot {seq} (price, qty, orders)")]
+    Mismatch {
+        seq: u64,
+        side: Side,
+        rebuilt: Option<(Price, Qty, usize)>,
+        published: Option<(Price, Qty, usize)>,
+    },
+}
+
+/// Book rebuilt order by order from the market-by-order feed
+///
+/// Apply each [`OrderUpdates`] as it arrives and [`check`](Self::check) the
+/// snapshots that follow; a snapshot whose `order_seq` matches the last
+/// updates applied must show the same levels as the rebuilt book.
+#[derive(Debug, Default)]
+pub struct BookBuilder {
+    seq: u64,
+    started: bool,
+    orders: HashMap<OrderId, OrderUpdate>,
+    /// Order ids at each price, in queue priority order
+    bids: BTreeMap<Price, Vec<OrderId>>,
+    asks: BTreeMap<Price, Vec<OrderId>>,
+    checked: u64,
+    mismatches: u64,
+}
+
+impl BookBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of the last updates applied
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    /// Snapshots found to match the rebuilt book
+    pub fn checked(&self) -> u64 {
+        self.checked
+    }
+
+    /// Snapshots found to differ from it
+    pub fn mismatches(&self) -> u64 {
+        self.mismatches
+    }
+
+    /// Apply the next updates; an image replaces the book, and updates the
+    /// book already includes are ignored
+    ///
+    /// # Errors
+    /// * `Gap` - If updates were missed since the last ones applied; the
+    ///   book is left as it was
+    pub fn apply(&mut self, updates: &OrderUpdates) -> Result<(), BookBuildError> {
+        if updates.reset {
+            self.orders.clear();
+            self.bids.clear();
+            self.asks.clear();
+        } else if updates.seq <= self.seq {
+            return Ok(());
+        } else if updates.seq != self.seq + 1 {
+            return Err(BookBuildError::Gap { applied: self.seq, got: updates.seq });
+        }
+        for update in &updates.updates {
+            self.update(*update);
+        }
+        self.seq = updates.seq;
+        self.started = true;
+        Ok(())
+    }
+
+    /// Set one order's resting state; it keeps its place in the queue
+    /// unless it moved or paid for a better one
+    fn update(&mut self, update: OrderUpdate) {
+        let previous = match update.is_removal() {
+            true => self.orders.remove(&update.order_id),
+            false => self.orders.insert(update.order_id, update),
+        };
+        let queued = previous.is_some_and(|previous| {
+            !update.is_removal() && previous.side == update.side && previous.price == update.price
+                && previous.queue_key() == update.queue_key()
+        });
+        if queued {
+            return;
+        }
+        if let Some(previous) = previous {
+            let levels = self.levels_mut(previous.side);
+            if let Some(queue) = levels.get_mut(&previous.price) {
+                queue.retain(|&id| id != previous.order_id);
+                if queue.is_empty() {
+                    levels.remove(&previous.price);
+                }
+            }
+        }
+        if !update.is_removal() {
+            let levels = match update.side {
+                Side::Buy => &mut self.bids,
+                Side::Sell => &mut self.asks,
+            };
+            let queue = levels.entry(update.price).or_default();
+            let place = queue.partition_point(|id| self.orders[id].queue_key() <= update.queue_key());
+            queue.insert(place, update.order_id);
+        }
+    }
+
+    fn levels_mut(&mut self, side: Side) -> &mut BTreeMap<Price, Vec<OrderId>> {
+        match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        }
+    }
+
+    /// Resting state of an order
+    pub fn order(&self, order_id: OrderId) -> Option<&OrderUpdate> {
+        self.orders.get(&order_id)
+    }
+
+    /// Orders resting at a price, first in line first
+    pub fn queue(&self, side: Side, price: Price) -> Vec<OrderUpdate> {
+        let levels = match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks,
+        };
+        levels.get(&price).into_iter().flatten().map(|id| self.orders[id]).collect()
+    }
+
+    /// Levels of one side, best first, as a snapshot lists them; the
+    /// latency of the rebuilt levels is not known and left at zero
+    pub fn levels(&self, side: Side) -> Vec<BookLevelPoint> {
+        let point = |(&price, queue): (&Price, &Vec<OrderId>)| {
+            let qty: Qty = queue.iter().map(|id| self.orders[id].qty).sum();
+            BookLevelPoint { price, qty, latency_ms: 0, orders: queue.len(), notional: price as u128 * qty as u128 }
+        };
+        match side {
+            Side::Buy => self.bids.iter().rev().map(point).collect(),
+            Side::Sell => self.asks.iter().map(point).collect(),
+        }
+    }
+
+    pub fn best_bid(&self) -> Option<Price> {
+        self.bids.keys().next_back().copied()
+    }
+
+    pub fn best_ask(&self) -> Option<Price> {
+        self.asks.keys().next().copied()
+    }
+
+    /// Compare the rebuilt book with a snapshot, returning whether it could;
+    /// only a snapshot numbered like the last updates applied is compared
+    ///
+    /// # Errors
+    /// * `Mismatch` - If a level differs in price, quantity or order count
+    pub fn check(&mut self, snapshot: &DepthSnapshot) -> Result<bool, BookBuildError> {
+        if !self.started || snapshot.order_seq != Some(self.seq) {
+            return Ok(false);
+        }
+        let compared = [(Side::Buy, &snapshot.bids), (Side::Sell, &snapshot.asks)]
+            .into_iter()
+            .try_for_each(|(side, published)| self.compare(side, published));
+        match compared {
+            Ok(()) => self.checked += 1,
+            Err(_) => self.mismatches += 1,
+        }
+        compared.map(|_| true)
+    }
+
+    fn compare(&self, side: Side, published: &[BookLevelPoint]) -> Result<(), BookBuildError> {
+        let key = |level: &BookLevelPoint| (level.price, level.qty, level.orders);
+        let rebuilt = self.levels(side);
+        for index in 0..rebuilt.len().max(published.len()) {
+            let (rebuilt, published) = (rebuilt.get(index).map(key), published.get(index).map(key));
+            if rebuilt != published {
+                return Err(BookBuildError::Mismatch { seq: self.seq, side, rebuilt, published });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Async client for an order book server
+pub struct OrderBookClient {
+    sink: Mutex<WsSink>,
+    pending: Pending,
+    next_request_id: AtomicU64,
+    subscribers: Subscribers,
+    book: SharedBook,
+    reply_timeout: Duration,
+    protocol: Handshake,
+    reader: JoinHandle<()>,
+}
+
+/// Where the reader fans incoming messages out to
+#[derive(Clone)]
+struct Subscribers {
+    depth_tx: broadcast::Sender<DepthSnapshot>,
+    trade_tx: broadcast::Sender<Trade>,
+    block_tx: broadcast::Sender<Trade>,
+    playback_tx: broadcast::Sender<PlaybackFrame>,
+    orders_tx: broadcast::Sender<Arc<OrderUpdates>>,
+}
+
+impl OrderBookClient {
+    /// Connect to a server's `/ws` endpoint, e.g. `ws://localhost:8080/ws`
+    pub async fn connect(url: &str) -> ClientResult<Self> {
+        let (stream, _) = tokio_tungstenite::connect_async(url).await?;
+        let (sink, stream) = stream.split();
+        let pending: Pending = Arc::default();
+        let subscribers = Subscribers {
+            depth_tx: broadcast::channel(100).0,
+            trade_tx: broadcast::channel(1000).0,
+            block_tx: broadcast::channel(100).0,
+            playback_tx: broadcast::channel(100).0,
+            orders_tx: broadcast::channel(100).0,
+        };
+        let book = SharedBook::default();
+
+        let reader = tokio::spawn(Self::read(stream, pending.clone(), subscribers.clone(), book.clone()));
+        let mut client = Self {
+            sink: Mutex::new(sink),
+            pending,
+            next_request_id: AtomicU64::new(1),
+            subscribers,
+            book,
+            reply_timeout: Duration::from_secs(5),
+            protocol: Handshake::legacy(),
+            reader,
+        };
+
+        // Servers from before the handshake reject hello; they speak version 1
+        client.protocol = match client.hello(&[]).await {
+            Ok(handshake) => handshake,
+            Err(ClientError::Rejected(_)) => Handshake::legacy(),
+            Err(e) => return Err(e),
+        };
+
+        // Typed messages carry prices in ticks whatever the server's default;
+        // servers without the command only write ticks
+        match client.send(ClientCommand::SetPriceFormat { format: PriceFormat::Ticks }).await {
+            Ok(_) | Err(ClientError::Rejected(_)) => Ok(client),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Wait this long for each command's reply (default 5s)
+    pub fn with_reply_timeout(mut self, timeout: Duration) -> Self {
+        self.reply_timeout = timeout;
+        self
+    }
+
+    /// Protocol version and capabilities agreed with the server
+    pub fn protocol(&self) -> &Handshake {
+        &self.protocol
+    }
+
+    /// Offer this client's protocol version, asking for `capabilities` (or
+    /// every capability if empty), and return what the server agreed to
+    ///
+    /// Replies are matched to commands by request id, so a list always
+    /// includes `command_replies`.
+    pub async fn hello(&self, capabilities: &[Capability]) -> ClientResult<Handshake> {
+        let mut capabilities = capabilities.to_vec();
+        if !capabilities.is_empty() && !capabilities.contains(&Capability::CommandReplies) {
+            capabilities.push(Capability::CommandReplies);
+        }
+        let command = ClientCommand::Hello { version: PROTOCOL_VERSION, capabilities };
+        match self.request(command).await? {
+            ServerReply::Hello { handshake, .. } => Ok(handshake),
+            ServerReply::Error { error, .. } => Err(ClientError::Rejected(error)),
+            ServerReply::Ack { .. } => Err(ClientError::UnexpectedReply("acknowledgement to hello".to_string())),
+        }
+    }
+
+    /// Receive every depth snapshot from now on
+    pub fn subscribe_depth(&self) -> broadcast::Receiver<DepthSnapshot> {
+        self.subscribers.depth_tx.subscribe()
+    }
+
+    /// Receive every trade from now on
+    pub fn subscribe_trades(&self) -> broadcast::Receiver<Trade> {
+        self.subscribers.trade_tx.subscribe()
+    }
+
+    /// Receive private reports of block trades this connection's orders took
+    /// part in, sent before the trades print publicly
+    pub fn subscribe_block_trades(&self) -> broadcast::Receiver<Trade> {
+        self.subscribers.block_tx.subscribe()
+    }
+
+    /// Receive the frames of recordings played back to this connection
+    pub fn subscribe_playback(&self) -> broadcast::Receiver<PlaybackFrame> {
+        self.subscribers.playback_tx.subscribe()
+    }
+
+    /// Receive the market-by-order updates from now on, once following them
+    pub fn subscribe_orders(&self) -> broadcast::Receiver<Arc<OrderUpdates>> {
+        self.subscribers.orders_tx.subscribe()
+    }
+
+    /// Ask the server for its market-by-order feed on top of the capabilities
+    /// in use, and rebuild the book from it from now on
+    ///
+    /// # Errors
+    /// * `Rejected` - If the server runs no order feed
+    pub async fn follow_orders(&mut self) -> ClientResult<()> {
+        let mut capabilities = self.protocol.capabilities.clone();
+        capabilities.push(Capability::MarketByOrder);
+        let handshake = self.hello(&capabilities).await?;
+        if !handshake.supports(Capability::MarketByOrder) {
+            return Err(ClientError::Rejected(ApiError::new(ErrorCode::Rejected, "Server did not grant the market_by_order capability")));
+        }
+        self.protocol = handshake;
+        Ok(())
+    }
+
+    /// Book rebuilt from the market-by-order feed; hold it only briefly, as
+    /// incoming messages wait for it
+    pub fn book(&self) -> std::sync::MutexGuard<'_, BookBuilder> {
+        self.book.lock().unwrap()
+    }
+
+    /// Send a command and wait for the server to acknowledge it
+    pub async fn send(&self, command: ClientCommand) -> ClientResult<CommandAck> {
+        match self.request(command).await? {
+            ServerReply::Ack { ack, .. } => Ok(ack),
+            ServerReply::Error { error, .. } => Err(ClientError::Rejected(error)),
+            ServerReply::Hello { .. } => Err(ClientError::UnexpectedReply("hello".to_string())),
+        }
+    }
+
+    /// Send a command and wait for its reply
+    async fn request(&self, command: ClientCommand) -> ClientResult<ServerReply> {
+        let request_id = self.next_request_id.fetch_add(1, Ordering::Relaxed);
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(request_id, reply_tx);
+
+        let request = ClientRequest { request_id: Some(request_id), command };
+        let sent = match serde_json::to_string(&request) {
+            Ok(json) => self.sink.lock().await.send(Message::Text(json)).await.map_err(ClientError::from),
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = sent {
+            self.pending.lock().unwrap().remove(&request_id);
+            return Err(e);
+        }
+
+        let reply = tokio::time::timeout(self.reply_timeout, reply_rx).await;
+        self.pending.lock().unwrap().remove(&request_id);
+        match reply {
+            Ok(Ok(reply)) => Ok(reply),
+            Ok(Err(_)) => Err(ClientError::Closed),
+            Err(_) => Err(ClientError::Timeout(self.reply_timeout)),
+        }
+    }
+
+    /// Place a limit order
+    pub async fn place_limit(&self, side: Side, qty: Qty, price: Price) -> ClientResult<CommandAck> {
+        self.send(ClientCommand::PlaceTestOrder { side, qty, price: Some(price) }).await
+    }
+
+    /// Place a market order
+    pub async fn place_market(&self, side: Side, qty: Qty) -> ClientResult<CommandAck> {
+        self.send(ClientCommand::PlaceTestOrder { side, qty, price: None }).await
+    }
+
+    /// Have the server cancel this connection's resting orders when it drops
+    pub async fn set_cancel_on_disconnect(&self, enabled: bool) -> ClientResult<()> {
+        self.send(ClientCommand::SetCancelOnDisconnect { enabled }).await.map(|_| ())
+    }
+
+    /// Replay transport state: clock, speed and whether it is paused
+    pub async fn replay_status(&self) -> ClientResult<ReplayStatus> {
+        let ack = self.send(ClientCommand::ReplayStatus).await?;
+        ack.replay.ok_or_else(|| ClientError::UnexpectedReply("replay_status ack without a status".to_string()))
+    }
+
+    /// Have the server play a recorded session back to this connection at
+    /// `speed` times its recorded pace
+    pub async fn play_recording(&self, name: &str, speed: f64) -> ClientResult<()> {
+        self.send(ClientCommand::PlayRecording { name: name.to_string(), speed }).await.map(|_| ())
+    }
+
+    /// Close the connection
+    pub async fn close(self) -> ClientResult<()> {
+        self.sink.lock().await.close().await?;
+        Ok(())
+    }
+
+    /// Route incoming frames until the connection closes
+    async fn read(
+        mut stream: futures_util::stream::SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
+        pending: Pending,
+        subscribers: Subscribers,
+        book: SharedBook,
+    ) {
+        while let Some(message) = stream.next().await {
+            let text = match message {
+                Ok(Message::Text(text)) => text,
+                Ok(Message::Close(_)) => break,
+                Ok(_) => continue,
+                Err(e) => {
+                    warn!("Order book client connection error: {}", e);
+                    break;
+                }
+            };
+            match serde_json::from_str::<ServerMessage>(&text) {
+                Ok(ServerMessage::Reply(reply)) => {
+                    let waiter = reply.request_id().and_then(|id| pending.lock().unwrap().remove(&id));
+                    if let Some(waiter) = waiter {
+                        let _ = waiter.send(*reply);
+                    }
+                }
+                Ok(ServerMessage::Report(report)) => {
+                    let _ = subscribers.block_tx.send(report.trade);
+                }
+                Ok(ServerMessage::Playback(frame)) => {
+                    let _ = subscribers.playback_tx.send(frame);
+                }
+                Ok(ServerMessage::Orders(orders)) => {
+                    if let Err(e) = book.lock().unwrap().apply(&orders) {
+                        warn!("Order book client lost its place in the order feed: {}", e);
+                    }
+                    let _ = subscribers.orders_tx.send(Arc::new(orders));
+                }
+                Ok(ServerMessage::Snapshot(snapshot)) => {
+                    if let Err(e) = book.lock().unwrap().check(&snapshot) {
+                        warn!("Order book client rebuilt a different book: {}", e);
+                    }
+                    for trade in &snapshot.trades {
+                        let _ = subscribers.trade_tx.send(trade.clone());
+                    }
+                    let _ = subscribers.depth_tx.send(*snapshot);
+                }
+                Err(e) => warn!("Order book client received an unknown message: {}", e),
+            }
+        }
+        // Dropping the waiters fails their requests with `Closed`
+        pending.lock().unwrap().clear();
+    }
+}
+
+impl Drop for OrderBookClient {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+    use crate::engine::OrderBook;
+    use crate::config::BroadcastConfig;
+    use crate::queue_fifo::FifoLevel;
+    use crate::server::{create_router, start_simulation_loop, AppState};
+    use crate::sim::Simulator;
+    use crate::types::Order;
+
+    async fn serve() -> (AppState, String) {
+        listen(AppState::new(Simulator::new(OrderBook::<FifoLevel>::new()))).await
+    }
+
+    async fn listen(state: AppState) -> (AppState, String) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = create_router(state.clone());
+        tokio::spawn(async move {
+            axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
+        });
+        (state, format!("ws://{}/ws", addr))
+    }
+
+    #[tokio::test]
+    async fn test_orders_are_acknowledged_and_trades_streamed() {
+        let (state, url) = serve().await;
+        let client = OrderBookClient::connect(&url).await.unwrap();
+        assert_eq!(client.protocol().version, PROTOCOL_VERSION);
+        assert!(client.protocol().supports(Capability::SessionReports));
+        let mut trades = client.subscribe_trades();
+
+        let resting = client.place_limit(Side::Sell, 100, 101_0000).await.unwrap();
+        assert!(resting.order_id.is_some());
+        assert!(resting.trades.is_empty());
+
+        let taker = client.place_market(Side::Buy, 40).await.unwrap();
+        assert_eq!(taker.trades.len(), 1);
+        assert_eq!(taker.trades[0].price, 101_0000);
+        let execution = taker.execution.as_ref().unwrap();
+        assert_eq!((execution.qty, execution.levels_swept), (40, 1));
+
+        // Trades reach subscribers through the snapshot broadcast
+        let mut snapshot = state.simulator.call(|simulator| simulator.snapshot()).await;
+        snapshot.trades = taker.trades.clone();
+        state.broadcast_snapshot(snapshot).await;
+        let trade = tokio::time::timeout(Duration::from_secs(5), trades.recv()).await.unwrap().unwrap();
+        assert_eq!(trade, taker.trades[0]);
+
+        let err = client.place_market(Side::Buy, 0).await.unwrap_err();
+        match err {
+            ClientError::Rejected(error) => assert_eq!((error.code, error.field.as_deref()), (ErrorCode::QtyZero, Some("qty"))),
+            err => panic!("unexpected error: {}", err),
+        }
+        client.close().await.unwrap();
+
+        // This server runs no order feed
+        let mut client = OrderBookClient::connect(&url).await.unwrap();
+        assert!(matches!(client.follow_orders().await, Err(ClientError::Rejected(_))));
+        assert!(!client.protocol().supports(Capability::MarketByOrder));
+    }
+
+    #[tokio::test]
+    async fn test_book_rebuilt_from_order_feed_matches_snapshots() {
+        let broadcast = BroadcastConfig { order_feed: true, ..BroadcastConfig::default() };
+        let mut simulator = Simulator::new(OrderBook::<FifoLevel>::new());
+        // Resting far from the touch, these keep both sides quoted
+        let (bid_id, ask_id) = (simulator.reserve_order_id(), simulator.reserve_order_id());
+        simulator.place_order(Order::new_limit(bid_id, Side::Buy, 10, 90_0000, 0)).unwrap();
+        simulator.place_order(Order::new_limit(ask_id, Side::Sell, 10, 110_0000, 0)).unwrap();
+        let (state, url) = listen(AppState::new(simulator).with_broadcast_config(broadcast)).await;
+        tokio::spawn(start_simulation_loop(state, 5));
+
+        let mut client = OrderBookClient::connect(&url).await.unwrap();
+        assert!(!client.protocol().supports(Capability::MarketByOrder));
+        client.follow_orders().await.unwrap();
+        assert!(client.protocol().supports(Capability::MarketByOrder));
+        let mut orders = client.subscribe_orders();
+        let mut depth = client.subscribe_depth();
+
+        let mut last = None;
+        while client.book().checked() < 30 {
+            last = Some(tokio::time::timeout(Duration::from_secs(5), depth.recv()).await.unwrap().unwrap());
+        }
+        let last = last.unwrap();
+        let book = client.book();
+        assert_eq!(book.mismatches(), 0);
+        assert!(!book.levels(Side::Buy).is_empty() && !book.levels(Side::Sell).is_empty());
+        if last.order_seq == Some(book.seq()) {
+            assert_eq!((book.best_bid(), book.best_ask()), (last.best_bid, last.best_ask));
+        }
+        let updates = orders.try_recv().unwrap();
+        assert!(updates.seq <= book.seq());
+    }
+}
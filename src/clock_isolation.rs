@@ -0,0 +1,136 @@
+//! Wall-clock isolation test mode
+//!
+//! A deterministic run should take every timestamp from the simulation clock.
+//! The engine stamps level activity, snapshots and its order age checks from
+//! its own clock once the simulator sets it, and queue levels take theirs
+//! from the orders and trades they see. Wrapping an engine in an
+//! [`IsolatedEngine`] opens a [`WallClockIsolation`] guard around every call
+//! into it, so any wall-clock read that remains panics at the offending line
+//! instead of quietly making the run depend on the host. An [`OrderBook`]
+//! can isolate itself the same way, which is how the
+//! `simulation.isolate_wall_clock` setting runs.
+//!
+//! [`OrderBook`]: crate::engine::OrderBook
+
+use crate::engine::{CommandEngine, DepthSnapshot, EngineCommand, EngineEvent, OrderPreview, StpGroup};
+use crate::order_age::OrderAges;
+use crate::error::EngineResult;
+use crate::time::WallClockIsolation;
+use crate::types::{Order, OrderId, Price, Qty};
+
+/// Wraps an engine and forbids wall-clock reads inside it
+#[derive(Debug)]
+pub struct IsolatedEngine<E> {
+    inner: E,
+}
+
+impl<E: CommandEngine> IsolatedEngine<E> {
+    /// Isolate `inner` from the wall clock
+    pub fn new(inner: E) -> Self {
+        Self { inner }
+    }
+
+    /// The wrapped engine, for inspection outside isolation
+    pub fn inner(&self) -> &E {
+        &self.inner
+    }
+
+    /// Take back the wrapped engine
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+}
+
+impl<E: CommandEngine> CommandEngine for IsolatedEngine<E> {
+    fn execute(&mut self, command: EngineCommand) -> EngineResult<EngineEvent> {
+        let _isolation = WallClockIsolation::enter();
+        self.inner.execute(command)
+    }
+
+    fn best_bid_ask(&self) -> (Option<Price>, Option<Price>) {
+        let _isolation = WallClockIsolation::enter();
+        self.inner.best_bid_ask()
+    }
+
+    fn depth_snapshot(&self) -> DepthSnapshot {
+        let _isolation = WallClockIsolation::enter();
+        self.inner.depth_snapshot()
+    }
+
+    fn depth_snapshot_top(&self, max_levels: usize) -> DepthSnapshot {
+        let _isolation = WallClockIsolation::enter();
+        self.inner.depth_snapshot_top(max_levels)
+    }
+
+    fn open_orders(&self) -> Vec<Order> {
+        let _isolation = WallClockIsolation::enter();
+        self.inner.open_orders()
+    }
+
+    fn order_priority_fee(&self, order_id: OrderId) -> u64 {
+        self.inner.order_priority_fee(order_id)
+    }
+
+    fn order_resting_qty(&self, order_id: OrderId) -> Option<Qty> {
+        self.inner.order_resting_qty(order_id)
+    }
+
+    fn open_order_ages(&self, now: u128) -> OrderAges {
+        let _isolation = WallClockIsolation::enter();
+        self.inner.open_order_ages(now)
+    }
+
+    fn preview_order(&self, order: Order, stp_group: Option<StpGroup>) -> EngineResult<OrderPreview> {
+        let _isolation = WallClockIsolation::enter();
+        self.inner.preview_order(order, stp_group)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{OrderBook, OrderBookEngine};
+    use crate::generator::DataGenerator;
+    use crate::queue_fifo::FifoLevel;
+    use crate::sim::Simulator;
+    use crate::types::Side;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    #[test]
+    fn test_wall_clock_reads_inside_the_engine_panic() {
+        let mut engine = IsolatedEngine::new(OrderBook::<FifoLevel>::new());
+        // Reads that never touch the clock pass through
+        assert_eq!(engine.best_bid_ask(), (None, None));
+        assert!(engine.open_orders().is_empty());
+
+        // Placing into a book whose clock was never set checks the order's
+        // age against the wall clock
+        let placed = catch_unwind(AssertUnwindSafe(|| engine.submit(Order::new_limit(1, Side::Buy, 10, 99_0000, 0))));
+        let panic = placed.unwrap_err();
+        assert!(panic.downcast_ref::<&str>().is_some_and(|message| message.contains("wall-clock isolation")));
+
+        // Once the book has a clock, everything it stamps comes from it
+        let mut engine = IsolatedEngine::new(OrderBook::<FifoLevel>::new());
+        engine.execute(EngineCommand::SetClock(5_000)).unwrap();
+        engine.submit(Order::new_limit(1, Side::Buy, 10, 99_0000, 5_000)).unwrap();
+        engine.submit(Order::new_market(2, Side::Sell, 4, 6_000)).unwrap();
+        engine.withdraw(1).unwrap();
+        assert_eq!(engine.depth_snapshot().ts, 5_000);
+    }
+
+    #[test]
+    fn test_isolated_simulation_runs_on_the_simulation_clock() {
+        let mut sim = Simulator::with_seed(IsolatedEngine::new(OrderBook::<FifoLevel>::new()), 7);
+        sim.set_time(DataGenerator::DEFAULT_START_TIME);
+        let trades = sim.run_steps(200).unwrap();
+        assert!(!trades.is_empty());
+
+        // The same run with the book isolating itself, as configured runs do
+        let mut configured = Simulator::with_seed(OrderBook::<FifoLevel>::new(), 7).with_wall_clock_isolation(true);
+        assert!(configured.snapshot().bids.is_empty());
+        configured.set_time(DataGenerator::DEFAULT_START_TIME);
+        assert_eq!(configured.run_steps(200).unwrap(), trades);
+        assert!(configured.engine.isolates_wall_clock());
+        assert!(!crate::time::wall_clock_isolated());
+    }
+}
@@ -1,30 +1,197 @@
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use schemars::JsonSchema;
+use std::path::{Path, PathBuf};
 use std::fs;
 use std::env;
-use crate::sim::{NetModel, MarketMakerConfig, OrderGenerationConfig};
+use crate::sim::{NetModel, MarketMakerConfig, OrderGenerationConfig, HedgeConfig, HybridInteraction, LiquidityTtlConfig, SelfMatchConfig};
+use crate::data::{BlockCacheConfig, ErrorPolicy, TimestampConfig};
+use crate::margin::MarginConfig;
+use crate::funding::FundingConfig;
+use crate::obligations::ObligationConfig;
+use crate::settlement::SettlementConfig;
+use crate::price_bands::PriceBandConfig;
+use crate::latency::LatencyConfig;
+use crate::velocity::VelocityConfig;
+use crate::warmup::WarmupConfig;
+use crate::throttle::ThrottleConfig;
+use crate::auction::AuctionConfig;
+use crate::shadow::ShadowConfig;
+use crate::ab_shadow::AbShadowConfig;
+use crate::memory::MemoryConfig;
+use crate::metrics_store::MetricsStoreConfig;
+use crate::features::FeatureExportConfig;
+use crate::depth_tensor::DepthTensorConfig;
+use crate::playback::RecordingConfig;
+use crate::publish::PublisherConfig;
+use crate::redis_bridge::RedisBridgeConfig;
+use crate::relay::RelayConfig;
+use crate::activity::ActivityConfig;
+use crate::health_alarms::HealthAlarmConfig;
+use crate::webhooks::WebhookConfig;
+use crate::tape::BlockTradeConfig;
+use crate::masking::MaskingConfig;
+use crate::entitlements::EntitlementConfig;
+use crate::degradation::DegradationConfig;
+use crate::order_refs::UnmatchedOrderPolicy;
+use crate::flow_transform::FlowTransformConfig;
+use crate::tenants::TenantConfig;
+use crate::bookmarks::BookmarkConfig;
+use crate::price_format::{PriceFormat, PricePrecision};
+use crate::analytics::AnalyticsConfig;
+use crate::agents::AgentSpec;
+use crate::types::{ContractSpec, LotSize};
+use crate::engine::{CrossedBookPolicy, DepthLimit, TradeTimestamps};
+use crate::report::ReportFormat;
 
 /// Main application configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 pub struct Config {
     /// Server configuration
     pub server: ServerConfig,
     /// Simulation configuration
     pub simulation: SimulationConfig,
+    /// How often the simulation loop publishes to clients
+    #[serde(default)]
+    pub broadcast: BroadcastConfig,
     /// Network simulation parameters
     pub network: NetModel,
     /// Market maker configuration
     pub market_maker: MarketMakerConfig,
     /// Order generation configuration
     pub order_generation: OrderGenerationConfig,
+    /// Synthetic agent population; the built-in market maker and taker when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub agents: Vec<AgentSpec>,
     /// Data source configuration
     pub data_source: DataSourceConfig,
     /// Logging configuration
     pub logging: LoggingConfig,
+    /// Inventory hedging configuration
+    #[serde(default)]
+    pub hedging: HedgeConfig,
+    /// Expiry of untouched synthetic liquidity
+    #[serde(default)]
+    pub liquidity_ttl: LiquidityTtlConfig,
+    /// Self-match prevention between order sources sharing the engine
+    #[serde(default)]
+    pub self_match: SelfMatchConfig,
+    /// Margin and buying-power rules for paper accounts
+    #[serde(default)]
+    pub margin: MarginConfig,
+    /// Notional velocity limit for paper accounts
+    #[serde(default)]
+    pub velocity: VelocityConfig,
+    /// Periodic funding between longs and shorts for perpetual-style instruments
+    #[serde(default)]
+    pub funding: FundingConfig,
+    /// Quoting obligations market makers are scored against each session
+    #[serde(default)]
+    pub obligations: ObligationConfig,
+    /// T+N trade settlement and position reconciliation
+    #[serde(default)]
+    pub settlement: SettlementConfig,
+    /// Limit-up/limit-down bands on synthetic orders around the replayed price
+    #[serde(default)]
+    pub price_bands: PriceBandConfig,
+    /// Modeled decision latency of agent orders and its cost
+    #[serde(default)]
+    pub latency: LatencyConfig,
+    /// Steady-state criteria to reach before metrics collection starts
+    #[serde(default)]
+    pub warmup: WarmupConfig,
+    /// Scaling synthetic order flow down when the simulator falls behind
+    #[serde(default)]
+    pub throttle: ThrottleConfig,
+    /// Pre-open order queue and auction imbalance messages
+    #[serde(default)]
+    pub auction: AuctionConfig,
+    /// Liquidity re-anchored to the data source's quotes for pseudo-live runs
+    #[serde(default)]
+    pub shadow_book: ShadowConfig,
+    /// Second engine configuration run beside the primary for comparison
+    #[serde(default)]
+    pub ab_shadow: AbShadowConfig,
+    /// End-of-run report output
+    #[serde(default)]
+    pub report: ReportConfig,
+    /// Trade analytics (volume profile) settings
+    #[serde(default)]
+    pub analytics: AnalyticsConfig,
+    /// History budgets and the process memory limit
+    #[serde(default)]
+    pub memory: MemoryConfig,
+    /// Periodic metrics samples persisted to SQLite
+    #[serde(default)]
+    pub metrics_store: MetricsStoreConfig,
+    /// Sampled book features exported for model training
+    #[serde(default)]
+    pub features: FeatureExportConfig,
+    /// Depth-over-time tensor served to 3D visualizations
+    #[serde(default)]
+    pub depth_tensor: DepthTensorConfig,
+    /// Saving recorded sessions to disk as checksummed segments
+    #[serde(default)]
+    pub recordings: RecordingConfig,
+    /// Trade and depth publishing to Kafka, NATS or Redis
+    #[serde(default)]
+    pub publisher: PublisherConfig,
+    /// Snapshot mirror to Redis pub/sub
+    #[serde(default)]
+    pub redis: RedisBridgeConfig,
+    /// Feed replay for relays, and the upstream to relay when this is one
+    #[serde(default)]
+    pub relay: RelayConfig,
+    /// Activity timeline served at `/events`
+    #[serde(default)]
+    pub events: ActivityConfig,
+    /// Rate-of-change alarms on server health
+    #[serde(default)]
+    pub health_alarms: HealthAlarmConfig,
+    /// Outbound webhooks for fills and risk events
+    #[serde(default)]
+    pub webhooks: WebhookConfig,
+    /// Block trade tagging and delayed public reporting
+    #[serde(default)]
+    pub block_trades: BlockTradeConfig,
+    /// Depth detail shown on each outgoing channel and to each client role
+    #[serde(default)]
+    pub masking: MaskingConfig,
+    /// Data tiers `/ws` sessions are licensed for, by API key
+    #[serde(default)]
+    pub entitlements: EntitlementConfig,
+    /// Shedding depth and analytics while steps overrun their interval
+    #[serde(default)]
+    pub degradation: DegradationConfig,
+    /// Where replay bookmarks are saved
+    #[serde(default)]
+    pub bookmarks: BookmarkConfig,
+    /// Isolated books served under `/t/{name}`, each with its own simulator
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tenants: Vec<TenantConfig>,
+}
+
+/// End-of-run report configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct ReportConfig {
+    /// Write a report here when the server shuts down
+    #[serde(default)]
+    pub output: Option<PathBuf>,
+    /// Report format (inferred from the output extension if unset, else HTML)
+    #[serde(default)]
+    pub format: Option<ReportFormat>,
+}
+
+impl ReportConfig {
+    /// The format to write `output` in
+    pub fn resolved_format(&self) -> ReportFormat {
+        self.format
+            .or_else(|| self.output.as_deref().and_then(ReportFormat::from_path))
+            .unwrap_or_default()
+    }
 }
 
 /// Server configuration parameters
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ServerConfig {
     /// Port to bind the WebSocket server
     pub port: u16,
@@ -32,12 +199,31 @@ pub struct ServerConfig {
     pub host: String,
     /// Maximum number of concurrent WebSocket connections
     pub max_connections: usize,
+    /// Optional cap on concurrent WebSocket connections from a single IP
+    #[serde(default)]
+    pub max_connections_per_ip: Option<usize>,
     /// WebSocket message buffer size
     pub message_buffer_size: usize,
     /// Enable CORS for cross-origin requests
     pub enable_cors: bool,
     /// Health check endpoint path
     pub health_endpoint: String,
+    /// Optional TLS termination; when set the server listens on https/wss
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// How `/ws` and paper trading responses write prices unless a client
+    /// or request asks otherwise
+    #[serde(default)]
+    pub price_format: PriceFormat,
+}
+
+/// TLS certificate configuration
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TlsConfig {
+    /// Path to the PEM-encoded certificate chain
+    pub cert_path: PathBuf,
+    /// Path to the PEM-encoded private key
+    pub key_path: PathBuf,
 }
 
 impl Default for ServerConfig {
@@ -46,15 +232,18 @@ impl Default for ServerConfig {
             port: 3000,
             host: "0.0.0.0".to_string(),
             max_connections: 1000,
+            max_connections_per_ip: None,
             message_buffer_size: 100,
             enable_cors: true,
             health_endpoint: "/health".to_string(),
+            tls: None,
+            price_format: PriceFormat::default(),
         }
     }
 }
 
 /// Simulation configuration parameters
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct SimulationConfig {
     /// Interval between simulation steps in milliseconds
     pub step_interval_ms: u64,
@@ -66,6 +255,38 @@ pub struct SimulationConfig {
     pub max_spread_history: usize,
     /// Enable performance monitoring
     pub enable_monitoring: bool,
+    /// How synthetic orders trade with historical liquidity in hybrid mode
+    #[serde(default)]
+    pub hybrid_interaction: HybridInteraction,
+    /// Lot size for the instrument and how odd lots are handled
+    #[serde(default)]
+    pub lot_size: LotSize,
+    /// Price levels and distance from the touch the book retains per side
+    #[serde(default)]
+    pub depth_limit: DepthLimit,
+    /// Handling of orders that would lock or cross the book
+    #[serde(default)]
+    pub crossed_book_policy: CrossedBookPolicy,
+    /// Where trades take their timestamps from
+    #[serde(default)]
+    pub trade_timestamps: TradeTimestamps,
+    /// Linear or inverse payoff of the traded contract, and its size
+    #[serde(default)]
+    pub contract: ContractSpec,
+    /// Tick, decimal places and rounding rule prices are shown with
+    #[serde(default)]
+    pub price_precision: PricePrecision,
+    /// Queue discipline of the book's price levels, by registered name
+    #[serde(default = "default_queue_discipline")]
+    pub queue_discipline: String,
+    /// Panic on any wall-clock read inside the engine or its queue levels,
+    /// to check every timestamp there comes from the simulation clock
+    #[serde(default)]
+    pub isolate_wall_clock: bool,
+}
+
+fn default_queue_discipline() -> String {
+    "fifo".to_string()
 }
 
 impl Default for SimulationConfig {
@@ -76,12 +297,66 @@ impl Default for SimulationConfig {
             max_depth_levels: 20,
             max_spread_history: 400,
             enable_monitoring: true,
+            hybrid_interaction: HybridInteraction::default(),
+            lot_size: LotSize::default(),
+            depth_limit: DepthLimit::default(),
+            crossed_book_policy: CrossedBookPolicy::default(),
+            trade_timestamps: TradeTimestamps::default(),
+            contract: ContractSpec::default(),
+            price_precision: PricePrecision::default(),
+            queue_discipline: default_queue_discipline(),
+            isolate_wall_clock: false,
+        }
+    }
+}
+
+/// Publishing cadence of the simulation loop, independent of its step interval
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct BroadcastConfig {
+    /// Least time between snapshot broadcasts in milliseconds; every step when
+    /// unset. Trades from the steps in between go out with the next snapshot.
+    #[serde(default)]
+    pub snapshot_interval_ms: Option<u64>,
+    /// Publish the best bid and offer on `/bbo/ws` at every step that changes it
+    #[serde(default = "default_bbo_on_change")]
+    pub bbo_on_change: bool,
+    /// Publish every resting order's changes ahead of each snapshot to `/ws`
+    /// clients that ask for the `market_by_order` capability
+    #[serde(default)]
+    pub order_feed: bool,
+}
+
+fn default_bbo_on_change() -> bool {
+    true
+}
+
+impl Default for BroadcastConfig {
+    fn default() -> Self {
+        Self {
+            snapshot_interval_ms: None,
+            bbo_on_change: default_bbo_on_change(),
+            order_feed: false,
+        }
+    }
+}
+
+impl BroadcastConfig {
+    /// Least time between snapshot broadcasts, if they are throttled
+    pub fn snapshot_interval(&self) -> Option<std::time::Duration> {
+        self.snapshot_interval_ms.map(std::time::Duration::from_millis)
+    }
+
+    /// Check the settings are usable
+    pub fn validate(&self) -> Result<(), String> {
+        if self.snapshot_interval_ms.is_some_and(|interval| interval > 60000) {
+            return Err("Snapshot broadcast interval cannot exceed 60 seconds".to_string());
         }
+        Ok(())
     }
 }
 
 /// Data source configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct DataSourceConfig {
     /// Default data directory for CSV/JSON files
     pub data_directory: PathBuf,
@@ -95,6 +370,35 @@ pub struct DataSourceConfig {
     pub default_playback_speed: f64,
     /// Enable data validation
     pub validate_data: bool,
+    /// What to do with records that fail to parse: fail-fast, skip or quarantine
+    #[serde(default)]
+    pub error_policy: ErrorPolicy,
+    /// Send a heartbeat event after this long without data (milliseconds)
+    #[serde(default)]
+    pub heartbeat_interval_ms: Option<u64>,
+    /// How the files' raw timestamps map onto nanoseconds since the epoch (UTC)
+    #[serde(default)]
+    pub timestamps: TimestampConfig,
+    /// Report the feed as stale after this long without any event,
+    /// heartbeats included (milliseconds)
+    #[serde(default)]
+    pub stale_feed_ms: Option<u64>,
+    /// In-memory cache of parsed event blocks for repeated seeks and replays
+    #[serde(default)]
+    pub cache: BlockCacheConfig,
+    /// What to do with cancels and modifications of orders the book never
+    /// saw, such as those resting before a partial-day file starts
+    #[serde(default)]
+    pub unmatched_orders: UnmatchedOrderPolicy,
+    /// Rewrite the replayed flow into a stress variant: scaled sizes, limits
+    /// turned into markets and cancels dropped
+    #[serde(default)]
+    pub transform: FlowTransformConfig,
+    /// Only feed events for these symbols from a multi-symbol file (empty
+    /// feeds everything). Tenants can each select their own, so one capture
+    /// drives a book per symbol.
+    #[serde(default)]
+    pub symbols: Vec<String>,
 }
 
 impl Default for DataSourceConfig {
@@ -106,12 +410,61 @@ impl Default for DataSourceConfig {
             max_file_size: 1024 * 1024 * 1024, // 1GB
             default_playback_speed: 1.0,
             validate_data: true,
+            error_policy: ErrorPolicy::default(),
+            heartbeat_interval_ms: None,
+            timestamps: TimestampConfig::default(),
+            stale_feed_ms: None,
+            cache: BlockCacheConfig::default(),
+            unmatched_orders: UnmatchedOrderPolicy::default(),
+            transform: FlowTransformConfig::default(),
+            symbols: Vec::new(),
         }
     }
 }
 
+impl DataSourceConfig {
+    /// Resolve a client-supplied path against the data directory
+    /// 
+    /// Relative paths are taken relative to `data_directory`; the resolved file
+    /// must exist, live inside the data directory and respect `max_file_size`.
+    pub fn resolve_data_path<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf, ConfigError> {
+        let path = path.as_ref();
+        let candidate = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            self.data_directory.join(path)
+        };
+        
+        let root = self.data_directory.canonicalize().map_err(|e| {
+            ConfigError::ValidationError(format!(
+                "Data directory {} is not accessible: {}", self.data_directory.display(), e
+            ))
+        })?;
+        let resolved = candidate.canonicalize().map_err(|_| {
+            ConfigError::ValidationError(format!("Data file not found: {}", path.display()))
+        })?;
+        
+        if !resolved.starts_with(&root) {
+            return Err(ConfigError::ValidationError(format!(
+                "Data file {} is outside the data directory", path.display()
+            )));
+        }
+        
+        let size = fs::metadata(&resolved)
+            .map_err(|e| ConfigError::IoError(format!("Failed to read data file metadata: {}", e)))?
+            .len();
+        if !resolved.is_file() || size > self.max_file_size {
+            return Err(ConfigError::ValidationError(format!(
+                "Data file {} is not a regular file or exceeds the {} byte limit", path.display(), self.max_file_size
+            )));
+        }
+        
+        Ok(resolved)
+    }
+}
+
 /// Logging configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct LoggingConfig {
     /// Log level (error, warn, info, debug, trace)
     pub level: String,
@@ -123,6 +476,39 @@ pub struct LoggingConfig {
     pub enable_performance_logs: bool,
     /// Enable WebSocket event logging
     pub enable_websocket_logs: bool,
+    /// `profile` times each simulation step by phase and logs where the time
+    /// went every `profile_interval_steps` steps
+    #[serde(default)]
+    pub mode: LogMode,
+    /// Steps between step profile log lines in `profile` mode
+    #[serde(default = "default_profile_interval_steps")]
+    pub profile_interval_steps: u64,
+}
+
+fn default_profile_interval_steps() -> u64 {
+    1000
+}
+
+/// What the server logs beyond its log level
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LogMode {
+    #[default]
+    Standard,
+    /// Profile simulation steps by phase
+    Profile,
+}
+
+impl std::str::FromStr for LogMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "standard" => Ok(Self::Standard),
+            "profile" => Ok(Self::Profile),
+            _ => Err(format!("Unknown log mode: {} (expected standard or profile)", s)),
+        }
+    }
 }
 
 impl Default for LoggingConfig {
@@ -133,46 +519,153 @@ impl Default for LoggingConfig {
             log_file: None,
             enable_performance_logs: true,
             enable_websocket_logs: true,
+            mode: LogMode::Standard,
+            profile_interval_steps: default_profile_interval_steps(),
         }
     }
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            server: ServerConfig::default(),
-            simulation: SimulationConfig::default(),
-            network: NetModel::default(),
-            market_maker: MarketMakerConfig::default(),
-            order_generation: OrderGenerationConfig::default(),
-            data_source: DataSourceConfig::default(),
-            logging: LoggingConfig::default(),
-        }
-    }
-}
+
+/// Table holding the named profiles of a config file
+const PROFILE_TABLE: &str = "profile";
+
+/// Key naming the profile a profile builds on instead of the base section
+const PROFILE_PARENT_KEY: &str = "inherits";
 
 impl Config {
     /// Load configuration from file, falling back to defaults
     pub fn load_from_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self, ConfigError> {
+        Self::load_profile_from_file(path, None)
+    }
+    
+    /// Load configuration from file with a named profile applied over the
+    /// base section, falling back to defaults when there is no file and no
+    /// profile was asked for
+    pub fn load_profile_from_file<P: AsRef<std::path::Path>>(path: P, profile: Option<&str>) -> Result<Self, ConfigError> {
         let path = path.as_ref();
         
         if !path.exists() {
-            return Ok(Self::default());
+            return match profile {
+                Some(name) => Err(ConfigError::ValidationError(format!(
+                    "Profile '{}' requested but config file {} does not exist", name, path.display()
+                ))),
+                None => Ok(Self::default()),
+            };
         }
         
         let content = fs::read_to_string(path)
             .map_err(|e| ConfigError::IoError(format!("Failed to read config file: {}", e)))?;
         
-        let config: Config = toml::from_str(&content)
-            .map_err(|e| ConfigError::ParseError(format!("Failed to parse config file: {}", e)))?;
-        
+        let config = Self::from_toml_profile(&content, profile)?;
         config.validate()?;
         Ok(config)
     }
     
+    /// Parse a config file's contents with a named profile applied
+    ///
+    /// Settings outside `[profile.*]` form the base. A profile's settings
+    /// override the base's, merging table by table, so a profile only names
+    /// what it changes. A profile with `inherits = "other"` builds on that
+    /// profile instead, which in turn builds on its own parent or the base.
+    pub fn from_toml_profile(content: &str, profile: Option<&str>) -> Result<Self, ConfigError> {
+        toml::Value::Table(merged_table(content, profile)?).try_into()
+            .map_err(|e| ConfigError::ParseError(format!("Failed to parse config file: {}", e)))
+    }
+    
+    /// Parse a partial config file's contents, taking every setting it
+    /// leaves out from the defaults
+    pub fn from_toml_over_defaults(content: &str) -> Result<Self, ConfigError> {
+        let mut base = toml::Table::try_from(Self::default())
+            .map_err(|e| ConfigError::SerializeError(format!("Failed to serialize default config: {}", e)))?;
+        merge_table(&mut base, merged_table(content, None)?);
+        toml::Value::Table(base).try_into()
+            .map_err(|e| ConfigError::ParseError(format!("Failed to parse config file: {}", e)))
+    }
+    
+    /// Keys of a config file's contents that no setting reads, such as
+    /// misspelled ones
+    ///
+    /// Parsing ignores these, so a typo would otherwise leave the default in
+    /// place without a word. Keys are dotted paths like `server.prot`; keys of
+    /// a profile are reported under it, like `profile.demo.logging.levl`,
+    /// whether or not that profile is selected, and keys of a tenant's
+    /// overrides under the tenant, like `tenants.class-a.simulation.sed`.
+    pub fn unknown_keys(content: &str) -> Result<Vec<String>, ConfigError> {
+        let merged = merged_table(content, None)?;
+        let tenants: Vec<TenantConfig> = match merged.get("tenants") {
+            Some(tenants) => tenants.clone().try_into()
+                .map_err(|e| ConfigError::ParseError(format!("Failed to parse tenants: {}", e)))?,
+            None => Vec::new(),
+        };
+        let mut unknown = unread_keys(merged)?;
+        
+        let file: toml::Table = toml::from_str(content)
+            .map_err(|e| ConfigError::ParseError(format!("Failed to parse config file: {}", e)))?;
+        let profiles = match file.get(PROFILE_TABLE) {
+            Some(toml::Value::Table(profiles)) => profiles.clone(),
+            _ => toml::Table::new(),
+        };
+        let defaults = toml::Table::try_from(Self::default())
+            .map_err(|e| ConfigError::SerializeError(format!("Failed to serialize default config: {}", e)))?;
+        for name in profiles.keys() {
+            // A profile's own layer comes last in its chain; laid over the
+            // defaults, anything unread there is the profile's doing
+            let mut table = defaults.clone();
+            if let Some(layer) = profile_chain(&profiles, name)?.pop() {
+                merge_table(&mut table, layer);
+            }
+            unknown.extend(unread_keys(table)?.into_iter().map(|key| format!("{}.{}.{}", PROFILE_TABLE, name, key)));
+        }
+        // Overrides are kept as a raw table, so only laid over the defaults
+        // are their keys read
+        for tenant in tenants {
+            let mut table = defaults.clone();
+            merge_table(&mut table, tenant.overrides);
+            unknown.extend(unread_keys(table)?.into_iter().map(|key| format!("tenants.{}.{}", tenant.name, key)));
+        }
+        Ok(unknown)
+    }
+    
+    /// JSON schema of the configuration file, with each setting's
+    /// documentation and default
+    pub fn json_schema() -> serde_json::Value {
+        let mut schema = serde_json::to_value(schemars::schema_for!(Config))
+            .expect("config schema serializes to JSON");
+        let defaults = serde_json::to_value(Config::default())
+            .expect("default config serializes to JSON");
+        
+        // Types that do not default as a whole still have defaults through
+        // the default config, so fill those in wherever the schema lacks one
+        let mut definitions = schema.get_mut("definitions").map(serde_json::Value::take).unwrap_or_default();
+        let mut visited = std::collections::HashSet::new();
+        fill_schema_defaults(&mut schema, &defaults, &mut definitions, &mut visited);
+        if definitions.is_object() {
+            schema["definitions"] = definitions;
+        }
+        schema
+    }
+    
+    /// Names of the profiles defined in a config file's contents
+    pub fn profile_names(content: &str) -> Result<Vec<String>, ConfigError> {
+        let file: toml::Table = toml::from_str(content)
+            .map_err(|e| ConfigError::ParseError(format!("Failed to parse config file: {}", e)))?;
+        Ok(match file.get(PROFILE_TABLE) {
+            Some(toml::Value::Table(profiles)) => profiles.keys().cloned().collect(),
+            _ => Vec::new(),
+        })
+    }
+    
     /// Load configuration from environment variables and file
+    ///
+    /// `ORDERBOOK_PROFILE` selects a profile of the file.
     pub fn load() -> Result<Self, ConfigError> {
-        let mut config = Self::load_from_file("config.toml")?;
+        let profile = env::var("ORDERBOOK_PROFILE").ok();
+        let mut config = Self::load_profile_from_file("config.toml", profile.as_deref())?;
+        if let Ok(content) = fs::read_to_string("config.toml") {
+            for key in Self::unknown_keys(&content)? {
+                tracing::warn!("Ignoring unknown configuration key: {}", key);
+            }
+        }
         
         // Override with environment variables
         config.apply_env_overrides();
@@ -200,6 +693,19 @@ impl Config {
             }
         }
         
+        if let (Ok(cert), Ok(key)) = (env::var("ORDERBOOK_TLS_CERT"), env::var("ORDERBOOK_TLS_KEY")) {
+            self.server.tls = Some(TlsConfig {
+                cert_path: PathBuf::from(cert),
+                key_path: PathBuf::from(key),
+            });
+        }
+        
+        if let Ok(max_per_ip) = env::var("ORDERBOOK_MAX_CONNECTIONS_PER_IP") {
+            if let Ok(max_per_ip) = max_per_ip.parse() {
+                self.server.max_connections_per_ip = Some(max_per_ip);
+            }
+        }
+        
         // Simulation configuration
         if let Ok(interval) = env::var("ORDERBOOK_SIMULATION_INTERVAL") {
             if let Ok(interval) = interval.parse() {
@@ -207,6 +713,12 @@ impl Config {
             }
         }
         
+        if let Ok(interval) = env::var("ORDERBOOK_SNAPSHOT_INTERVAL") {
+            if let Ok(interval) = interval.parse() {
+                self.broadcast.snapshot_interval_ms = Some(interval);
+            }
+        }
+        
         if let Ok(seed) = env::var("ORDERBOOK_RANDOM_SEED") {
             if let Ok(seed) = seed.parse() {
                 self.simulation.random_seed = Some(seed);
@@ -238,13 +750,13 @@ impl Config {
         // Network configuration
         if let Ok(latency) = env::var("ORDERBOOK_BASE_LATENCY_NS") {
             if let Ok(latency) = latency.parse() {
-                self.network.base_latency_ns = latency;
+                self.network.order_path.base_latency_ns = latency;
             }
         }
         
         if let Ok(jitter) = env::var("ORDERBOOK_JITTER_NS") {
             if let Ok(jitter) = jitter.parse() {
-                self.network.jitter_ns = jitter;
+                self.network.order_path.jitter_ns = jitter;
             }
         }
     }
@@ -260,6 +772,28 @@ impl Config {
             return Err(ConfigError::ValidationError("Max connections cannot be 0".to_string()));
         }
         
+        if let Some(max_per_ip) = self.server.max_connections_per_ip {
+            if max_per_ip == 0 || max_per_ip > self.server.max_connections {
+                return Err(ConfigError::ValidationError(
+                    "Max connections per IP must be between 1 and max connections".to_string()
+                ));
+            }
+        }
+        
+        if let Some(tls) = &self.server.tls {
+            if !tls.cert_path.is_file() {
+                return Err(ConfigError::ValidationError(
+                    format!("TLS certificate not found: {}", tls.cert_path.display())
+                ));
+            }
+            
+            if !tls.key_path.is_file() {
+                return Err(ConfigError::ValidationError(
+                    format!("TLS private key not found: {}", tls.key_path.display())
+                ));
+            }
+        }
+        
         if self.server.message_buffer_size == 0 {
             return Err(ConfigError::ValidationError("Message buffer size cannot be 0".to_string()));
         }
@@ -277,6 +811,21 @@ impl Config {
             return Err(ConfigError::ValidationError("Max depth levels cannot be 0".to_string()));
         }
         
+        if self.simulation.lot_size.size == 0 {
+            return Err(ConfigError::ValidationError("Lot size cannot be 0".to_string()));
+        }
+        
+        if self.simulation.isolate_wall_clock && self.simulation.trade_timestamps == TradeTimestamps::WallClock {
+            return Err(ConfigError::ValidationError(
+                "Wall-clock isolation needs trades stamped with the taker order's time".to_string()
+            ));
+        }
+        
+        self.simulation.depth_limit.validate().map_err(ConfigError::ValidationError)?;
+        self.simulation.contract.validate().map_err(ConfigError::ValidationError)?;
+        self.simulation.price_precision.validate().map_err(ConfigError::ValidationError)?;
+        self.broadcast.validate().map_err(ConfigError::ValidationError)?;
+        
         // Validate network configuration
         if self.network.drop_prob < 0.0 || self.network.drop_prob > 1.0 {
             return Err(ConfigError::ValidationError("Drop probability must be between 0.0 and 1.0".to_string()));
@@ -286,30 +835,173 @@ impl Config {
             return Err(ConfigError::ValidationError("Reorder probability must be between 0.0 and 1.0".to_string()));
         }
         
-        // Validate market maker configuration
-        if self.market_maker.target_spread == 0 {
-            return Err(ConfigError::ValidationError("Target spread cannot be 0".to_string()));
+        for outage in &self.network.outages {
+            outage.validate().map_err(ConfigError::ValidationError)?;
+        }
+        
+        if self.network.busy.enabled {
+            self.network.busy.validate().map_err(ConfigError::ValidationError)?;
+        }
+        
+        // Validate market maker and order generation configuration
+        self.market_maker.validate().map_err(ConfigError::ValidationError)?;
+        self.order_generation.validate().map_err(ConfigError::ValidationError)?;
+        
+        // Validate hedging configuration
+        if self.hedging.enabled && self.hedging.interval_steps == 0 {
+            return Err(ConfigError::ValidationError("Hedge interval steps cannot be 0".to_string()));
+        }
+        
+        if self.hedging.inventory_threshold < 0 {
+            return Err(ConfigError::ValidationError("Hedge inventory threshold cannot be negative".to_string()));
+        }
+        
+        if self.hedging.cost_bps < 0.0 || self.hedging.fixed_cost < 0 {
+            return Err(ConfigError::ValidationError("Hedge costs cannot be negative".to_string()));
+        }
+        
+        // Validate liquidity expiry
+        if self.liquidity_ttl.enabled {
+            self.liquidity_ttl.validate().map_err(ConfigError::ValidationError)?;
+        }
+        
+        // Validate self-match prevention groups
+        if self.self_match.enabled {
+            self.self_match.validate().map_err(ConfigError::ValidationError)?;
+        }
+        
+        // Validate margin configuration
+        if self.margin.enabled {
+            self.margin.validate().map_err(ConfigError::ValidationError)?;
+        }
+        
+        // Validate velocity configuration
+        if self.velocity.enabled {
+            self.velocity.validate().map_err(ConfigError::ValidationError)?;
+        }
+        
+        // Validate funding schedule
+        if self.funding.enabled {
+            self.funding.validate().map_err(ConfigError::ValidationError)?;
+        }
+        
+        // Validate market-maker obligations
+        if self.obligations.enabled {
+            self.obligations.validate().map_err(ConfigError::ValidationError)?;
+        }
+        
+        // Validate settlement cycles
+        if self.settlement.enabled {
+            self.settlement.validate().map_err(ConfigError::ValidationError)?;
+        }
+        
+        // Validate price bands
+        if self.price_bands.enabled {
+            self.price_bands.validate().map_err(ConfigError::ValidationError)?;
+        }
+        
+        // Validate latency accounting
+        if self.latency.enabled {
+            self.latency.validate().map_err(ConfigError::ValidationError)?;
+        }
+        
+        // Validate warm-up criteria
+        if self.warmup.enabled {
+            self.warmup.validate().map_err(ConfigError::ValidationError)?;
         }
         
-        if self.market_maker.order_size == 0 {
-            return Err(ConfigError::ValidationError("Market maker order size cannot be 0".to_string()));
+        // Validate flow throttling
+        if self.throttle.enabled {
+            self.throttle.validate().map_err(ConfigError::ValidationError)?;
         }
         
-        if self.market_maker.mm_probability < 0.0 || self.market_maker.mm_probability > 1.0 {
-            return Err(ConfigError::ValidationError("Market maker probability must be between 0.0 and 1.0".to_string()));
+        // Validate the pre-open auction
+        if self.auction.enabled {
+            self.auction.validate().map_err(ConfigError::ValidationError)?;
         }
         
-        // Validate order generation configuration
-        if self.order_generation.market_order_prob < 0.0 || self.order_generation.market_order_prob > 1.0 {
-            return Err(ConfigError::ValidationError("Market order probability must be between 0.0 and 1.0".to_string()));
+        // Validate the shadow book ladder
+        if self.shadow_book.enabled {
+            self.shadow_book.validate().map_err(ConfigError::ValidationError)?;
         }
         
-        if self.order_generation.min_order_size == 0 {
-            return Err(ConfigError::ValidationError("Minimum order size cannot be 0".to_string()));
+        // Validate the A/B shadow book
+        if self.ab_shadow.enabled {
+            self.ab_shadow.validate().map_err(ConfigError::ValidationError)?;
         }
         
-        if self.order_generation.max_order_size < self.order_generation.min_order_size {
-            return Err(ConfigError::ValidationError("Maximum order size cannot be less than minimum order size".to_string()));
+        // Validate agent population
+        for agent in &self.agents {
+            agent.validate().map_err(ConfigError::ValidationError)?;
+        }
+        
+        // Validate analytics configuration
+        self.analytics.validate().map_err(ConfigError::ValidationError)?;
+        
+        // Validate memory budgets
+        if self.simulation.max_spread_history == 0 {
+            return Err(ConfigError::ValidationError("Max spread history cannot be 0".to_string()));
+        }
+        
+        self.memory.validate().map_err(ConfigError::ValidationError)?;
+        
+        // Validate metrics persistence
+        if self.metrics_store.enabled {
+            self.metrics_store.validate().map_err(ConfigError::ValidationError)?;
+        }
+        
+        // Validate feature export
+        if self.features.enabled {
+            self.features.validate().map_err(ConfigError::ValidationError)?;
+        }
+        
+        // Validate depth tensor recording
+        if self.depth_tensor.enabled {
+            self.depth_tensor.validate().map_err(ConfigError::ValidationError)?;
+        }
+        
+        // Validate saved recordings
+        if self.recordings.enabled {
+            self.recordings.validate().map_err(ConfigError::ValidationError)?;
+        }
+        
+        // Validate streaming publisher
+        if self.publisher.enabled {
+            self.publisher.validate().map_err(ConfigError::ValidationError)?;
+        }
+        
+        // Validate Redis snapshot bridge
+        if self.redis.enabled {
+            self.redis.validate().map_err(ConfigError::ValidationError)?;
+        }
+        
+        // Validate feed relay
+        self.relay.validate().map_err(ConfigError::ValidationError)?;
+        
+        // Validate activity timeline
+        self.events.validate().map_err(ConfigError::ValidationError)?;
+        
+        // Validate health alarms
+        if self.health_alarms.enabled {
+            self.health_alarms.validate().map_err(ConfigError::ValidationError)?;
+        }
+        
+        // Validate webhooks
+        if self.webhooks.enabled {
+            self.webhooks.validate().map_err(ConfigError::ValidationError)?;
+        }
+        
+        // Validate block trade rules
+        if self.block_trades.enabled {
+            self.block_trades.validate().map_err(ConfigError::ValidationError)?;
+        }
+        
+        self.masking.validate().map_err(ConfigError::ValidationError)?;
+        if self.entitlements.enabled {
+            self.entitlements.validate().map_err(ConfigError::ValidationError)?;
+        }
+        if self.degradation.enabled {
+            self.degradation.validate().map_err(ConfigError::ValidationError)?;
         }
         
         // Validate data source configuration
@@ -321,15 +1013,68 @@ impl Config {
             return Err(ConfigError::ValidationError("Default playback speed must be positive".to_string()));
         }
         
+        self.data_source.timestamps.validate().map_err(ConfigError::ValidationError)?;
+        
+        if self.data_source.cache.enabled {
+            self.data_source.cache.validate().map_err(ConfigError::ValidationError)?;
+        }
+        
+        if self.data_source.transform.enabled {
+            self.data_source.transform.validate().map_err(ConfigError::ValidationError)?;
+        }
+        
+        if self.data_source.heartbeat_interval_ms == Some(0) {
+            return Err(ConfigError::ValidationError("Heartbeat interval must be greater than 0".to_string()));
+        }
+        
+        if let Some(stale_ms) = self.data_source.stale_feed_ms {
+            // A quiet but healthy feed must not look stale between heartbeats
+            if self.data_source.heartbeat_interval_ms.is_some_and(|interval| stale_ms <= interval) {
+                return Err(ConfigError::ValidationError("Stale feed window must be longer than the heartbeat interval".to_string()));
+            }
+            if stale_ms == 0 {
+                return Err(ConfigError::ValidationError("Stale feed window must be greater than 0".to_string()));
+            }
+        }
+        
         // Validate logging configuration
         let valid_levels = ["error", "warn", "info", "debug", "trace", "off"];
         if !valid_levels.contains(&self.logging.level.as_str()) {
             return Err(ConfigError::ValidationError(format!("Invalid log level: {}", self.logging.level)));
         }
+        if self.logging.profile_interval_steps == 0 {
+            return Err(ConfigError::ValidationError("Profile log interval must be greater than 0 steps".to_string()));
+        }
+        
+        self.bookmarks.validate().map_err(ConfigError::ValidationError)?;
+        
+        // Validate tenants, each against its own resolved settings
+        let mut tenant_names = std::collections::HashSet::new();
+        for tenant in &self.tenants {
+            if !tenant_names.insert(tenant.name.as_str()) {
+                return Err(ConfigError::ValidationError(format!("Tenant '{}' is defined twice", tenant.name)));
+            }
+            self.tenant_config(tenant)?;
+        }
         
         Ok(())
     }
     
+    /// The settings a tenant runs with: these, with the tenant's overrides
+    /// merged over them table by table
+    pub fn tenant_config(&self, tenant: &TenantConfig) -> Result<Self, ConfigError> {
+        tenant.validate().map_err(ConfigError::ValidationError)?;
+        let mut base = toml::Table::try_from(self)
+            .map_err(|e| ConfigError::SerializeError(format!("Failed to serialize config: {}", e)))?;
+        base.remove("tenants");
+        merge_table(&mut base, tenant.overrides.clone());
+        let config: Self = toml::Value::Table(base).try_into()
+            .map_err(|e| ConfigError::ParseError(format!("Failed to parse settings of tenant '{}': {}", tenant.name, e)))?;
+        config.validate()?;
+        tenant.check_quota(&config).map_err(ConfigError::ValidationError)?;
+        Ok(config)
+    }
+    
     /// Save configuration to file
     pub fn save_to_file<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), ConfigError> {
         let content = toml::to_string_pretty(self)
@@ -350,6 +1095,179 @@ impl Config {
     }
 }
 
+/// A config file's base section with a named profile applied
+fn merged_table(content: &str, profile: Option<&str>) -> Result<toml::Table, ConfigError> {
+    let mut base: toml::Table = toml::from_str(content)
+        .map_err(|e| ConfigError::ParseError(format!("Failed to parse config file: {}", e)))?;
+    let profiles = match base.remove(PROFILE_TABLE) {
+        Some(toml::Value::Table(profiles)) => profiles,
+        Some(_) => return Err(ConfigError::ParseError(format!("'{}' must be a table of named profiles", PROFILE_TABLE))),
+        None => toml::Table::new(),
+    };
+    
+    if let Some(name) = profile {
+        for layer in profile_chain(&profiles, name)? {
+            merge_table(&mut base, layer);
+        }
+    }
+    Ok(base)
+}
+
+/// Keys of a config table that no setting reads, in order
+///
+/// Deserializing through `serde_ignored` finds most of them, but a struct
+/// with a flattened field such as [`NetModel::order_path`] hands every key it
+/// does not know itself to that field, so nothing reports them as ignored.
+/// The schema still lists every key such a struct reads, so the table is
+/// checked against it as well.
+fn unread_keys(table: toml::Table) -> Result<Vec<String>, ConfigError> {
+    let mut unknown = Vec::new();
+    let value = toml::Value::Table(table);
+    let schema = serde_json::to_value(schemars::schema_for!(Config))
+        .expect("config schema serializes to JSON");
+    let definitions = schema.get("definitions").cloned().unwrap_or_default();
+    keys_outside_schema(&value, &schema, &definitions, "", &mut unknown);
+    serde_ignored::deserialize(value, |path| unknown.push(path.to_string()))
+        .map(|_: Config| ())
+        .map_err(|e| ConfigError::ParseError(format!("Failed to parse config file: {}", e)))?;
+    unknown.sort();
+    unknown.dedup();
+    Ok(unknown)
+}
+
+/// Collect the keys of `value` that `schema` has no property for, descending
+/// into the definitions it refers to
+///
+/// Only objects whose schema lists its properties and allows no others are
+/// checked; maps and enums are left to `serde_ignored`.
+fn keys_outside_schema(
+    value: &toml::Value,
+    schema: &serde_json::Value,
+    definitions: &serde_json::Value,
+    path: &str,
+    unknown: &mut Vec<String>,
+) {
+    let Some(table) = value.as_table() else {
+        return;
+    };
+    // Fields refer to their type directly, through allOf with a description,
+    // or through anyOf alongside null when optional
+    let mut schema = schema;
+    loop {
+        let next = schema.get("$ref")
+            .and_then(serde_json::Value::as_str)
+            .and_then(|reference| reference.strip_prefix("#/definitions/"))
+            .and_then(|name| definitions.get(name))
+            .or_else(|| match schema.get("allOf").and_then(serde_json::Value::as_array) {
+                Some(all) if all.len() == 1 => all.first(),
+                _ => None,
+            })
+            .or_else(|| {
+                let any = schema.get("anyOf").and_then(serde_json::Value::as_array)?;
+                let mut present = any.iter().filter(|option| option.get("type").and_then(serde_json::Value::as_str) != Some("null"));
+                match (present.next(), present.next()) {
+                    (Some(only), None) => Some(only),
+                    _ => None,
+                }
+            });
+        match next {
+            Some(next) => schema = next,
+            None => break,
+        }
+    }
+    let Some(properties) = schema.get("properties").and_then(serde_json::Value::as_object) else {
+        return;
+    };
+    let open = schema.get("additionalProperties").is_some_and(|extra| extra != &serde_json::Value::Bool(false));
+    for (key, value) in table {
+        let key_path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+        match properties.get(key) {
+            Some(property) => keys_outside_schema(value, property, definitions, &key_path, unknown),
+            None if !open => unknown.push(key_path),
+            None => {}
+        }
+    }
+}
+
+/// Give each property of `schema` that has no default the matching value
+/// of `defaults`, descending into the definitions it refers to
+///
+/// A definition takes its defaults from the first place that refers to it.
+fn fill_schema_defaults(
+    schema: &mut serde_json::Value,
+    defaults: &serde_json::Value,
+    definitions: &mut serde_json::Value,
+    visited: &mut std::collections::HashSet<String>,
+) {
+    let Some(properties) = schema.get_mut("properties").and_then(serde_json::Value::as_object_mut) else {
+        return;
+    };
+    for (key, property) in properties.iter_mut() {
+        let Some(default) = defaults.get(key) else {
+            continue;
+        };
+        let Some(property) = property.as_object_mut() else {
+            continue;
+        };
+        property.entry("default").or_insert_with(|| default.clone());
+        
+        // Fields refer to their type directly or, with a description, through allOf
+        let reference = property.get("$ref")
+            .or_else(|| property.get("allOf").and_then(|all| all.get(0)).and_then(|first| first.get("$ref")))
+            .and_then(serde_json::Value::as_str)
+            .and_then(|reference| reference.strip_prefix("#/definitions/"))
+            .map(str::to_string);
+        let Some(name) = reference.filter(|name| visited.insert(name.clone())) else {
+            continue;
+        };
+        if let Some(mut definition) = definitions.get_mut(&name).map(serde_json::Value::take) {
+            fill_schema_defaults(&mut definition, default, definitions, visited);
+            definitions[&name] = definition;
+        }
+    }
+}
+
+/// Layers of a profile to apply over the base section, outermost ancestor first
+fn profile_chain<'a>(profiles: &'a toml::Table, name: &str) -> Result<Vec<toml::Table>, ConfigError> {
+    let mut chain: Vec<(&'a str, toml::Table)> = Vec::new();
+    let mut next = Some(name.to_string());
+    while let Some(name) = next {
+        let Some((key, value)) = profiles.get_key_value(name.as_str()) else {
+            let known: Vec<&str> = profiles.keys().map(String::as_str).collect();
+            return Err(ConfigError::ValidationError(format!(
+                "Unknown profile '{}' (defined: {})", name, if known.is_empty() { "none".to_string() } else { known.join(", ") }
+            )));
+        };
+        if chain.iter().any(|(seen, _)| *seen == key.as_str()) {
+            return Err(ConfigError::ValidationError(format!("Profile '{}' inherits from itself", key)));
+        }
+        let Some(layer) = value.as_table() else {
+            return Err(ConfigError::ParseError(format!("Profile '{}' must be a table", key)));
+        };
+        let mut layer = layer.clone();
+        next = match layer.remove(PROFILE_PARENT_KEY) {
+            Some(toml::Value::String(parent)) => Some(parent),
+            Some(_) => return Err(ConfigError::ParseError(format!("'{}' of profile '{}' must be a profile name", PROFILE_PARENT_KEY, key))),
+            None => None,
+        };
+        chain.push((key.as_str(), layer));
+    }
+    Ok(chain.into_iter().rev().map(|(_, layer)| layer).collect())
+}
+
+/// Override `base` with `layer`, merging nested tables and replacing
+/// everything else, arrays included
+fn merge_table(base: &mut toml::Table, layer: toml::Table) {
+    for (key, value) in layer {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(existing)), toml::Value::Table(overrides)) => merge_table(existing, overrides),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
 /// Configuration error types
 #[derive(Debug, thiserror::Error)]
 pub enum ConfigError {
@@ -369,8 +1287,8 @@ pub enum ConfigError {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::sim::PathLatency;
     use tempfile::NamedTempFile;
-    use std::io::Write;
 
     #[test]
     fn test_default_config() {
@@ -397,12 +1315,47 @@ mod tests {
         config.simulation.step_interval_ms = 100;
         config.network.drop_prob = 1.5;
         assert!(config.validate().is_err());
+        
+        // Test invalid memory budgets
+        config.network.drop_prob = 0.1;
+        config.memory.trade_tape = 0;
+        assert!(config.validate().is_err());
+        
+        // Test wall-clock isolation with trades stamped from the wall clock
+        config.memory.trade_tape = MemoryConfig::default().trade_tape;
+        config.simulation.isolate_wall_clock = true;
+        config.simulation.trade_timestamps = TradeTimestamps::WallClock;
+        assert!(config.validate().is_err());
+        config.simulation.trade_timestamps = TradeTimestamps::TakerOrder;
+        assert!(config.validate().is_ok());
+        config.simulation.isolate_wall_clock = false;
+        
+        // Test invalid agent population
+        config.agents = vec![crate::agents::AgentSpec::new("taker").with_count(0)];
+        assert!(config.validate().is_err());
+        
+        // Test a stale-feed window that heartbeats cannot satisfy
+        config.agents = Vec::new();
+        config.data_source.heartbeat_interval_ms = Some(1000);
+        config.data_source.stale_feed_ms = Some(500);
+        assert!(config.validate().is_err());
+        config.data_source.stale_feed_ms = Some(3000);
+        assert!(config.validate().is_ok());
+        
+        // Test a snapshot cadence slower than the limit
+        config.broadcast.snapshot_interval_ms = Some(120_000);
+        assert!(config.validate().is_err());
+        config.broadcast.snapshot_interval_ms = Some(100);
+        assert!(config.validate().is_ok());
     }
 
     #[test]
     fn test_config_file_operations() {
-        let config = Config::default();
-        let mut temp_file = NamedTempFile::new().unwrap();
+        let config = Config {
+            agents: vec![crate::agents::AgentSpec::new("market_maker").with_count(2).with_param("order_size", 50)],
+            ..Default::default()
+        };
+        let temp_file = NamedTempFile::new().unwrap();
         
         // Test saving
         config.save_to_file(temp_file.path()).unwrap();
@@ -411,6 +1364,7 @@ mod tests {
         let loaded_config = Config::load_from_file(temp_file.path()).unwrap();
         assert_eq!(config.server.port, loaded_config.server.port);
         assert_eq!(config.simulation.step_interval_ms, loaded_config.simulation.step_interval_ms);
+        assert_eq!(config.agents, loaded_config.agents);
     }
 
     #[test]
@@ -428,4 +1382,133 @@ mod tests {
         env::remove_var("ORDERBOOK_PORT");
         env::remove_var("ORDERBOOK_SIMULATION_INTERVAL");
     }
+
+    #[test]
+    fn test_resolve_data_path_stays_in_data_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("ticks.csv"), "type,timestamp\n").unwrap();
+        
+        let data_config = DataSourceConfig {
+            data_directory: dir.path().to_path_buf(),
+            ..DataSourceConfig::default()
+        };
+        
+        let resolved = data_config.resolve_data_path("ticks.csv").unwrap();
+        assert!(resolved.ends_with("ticks.csv"));
+        
+        assert!(data_config.resolve_data_path("missing.csv").is_err());
+        assert!(data_config.resolve_data_path("../../etc/passwd").is_err());
+        
+        let outside = NamedTempFile::new().unwrap();
+        assert!(data_config.resolve_data_path(outside.path()).is_err());
+    }
+
+    #[test]
+    fn test_tls_config_validation() {
+        let mut config = Config::default();
+        config.server.tls = Some(TlsConfig {
+            cert_path: PathBuf::from("/nonexistent/cert.pem"),
+            key_path: PathBuf::from("/nonexistent/key.pem"),
+        });
+        assert!(config.validate().is_err());
+        
+        let cert = NamedTempFile::new().unwrap();
+        let key = NamedTempFile::new().unwrap();
+        config.server.tls = Some(TlsConfig {
+            cert_path: cert.path().to_path_buf(),
+            key_path: key.path().to_path_buf(),
+        });
+        assert!(config.validate().is_ok());
+        
+        // Configs without a tls section still load
+        let toml_str = toml::to_string_pretty(&Config::default()).unwrap();
+        let parsed: Config = toml::from_str(&toml_str).unwrap();
+        assert!(parsed.server.tls.is_none());
+    }
+
+    #[test]
+    fn test_profiles_layer_over_the_base() {
+        let content = toml::to_string_pretty(&Config::default()).unwrap() + r#"
+            [profile.demo.server]
+            port = 9000
+
+            [profile.demo.simulation]
+            step_interval_ms = 50
+
+            [profile.stress]
+            inherits = "demo"
+
+            [profile.stress.server]
+            max_connections = 5000
+        "#;
+        assert_eq!(Config::profile_names(&content).unwrap(), vec!["demo", "stress"]);
+        
+        let base = Config::from_toml_profile(&content, None).unwrap();
+        assert_eq!(base.server.port, Config::default().server.port);
+        
+        // A profile changes only what it names
+        let demo = Config::from_toml_profile(&content, Some("demo")).unwrap();
+        assert_eq!((demo.server.port, demo.simulation.step_interval_ms), (9000, 50));
+        assert_eq!(demo.server.host, base.server.host);
+        assert_eq!(demo.server.max_connections, base.server.max_connections);
+        
+        // Inherited profiles apply their parent's settings first
+        let stress = Config::from_toml_profile(&content, Some("stress")).unwrap();
+        assert_eq!((stress.server.port, stress.server.max_connections), (9000, 5000));
+        
+        let unknown = Config::from_toml_profile(&content, Some("backtest")).unwrap_err();
+        assert!(unknown.to_string().contains("defined: demo, stress"));
+        let looped = content.replace("[profile.demo.server]", "[profile.demo]\ninherits = \"stress\"\n\n[profile.demo.server]");
+        assert!(Config::from_toml_profile(&looped, Some("stress")).is_err());
+    }
+
+    #[test]
+    fn test_unknown_keys_and_schema() {
+        let content = toml::to_string_pretty(&Config::default()).unwrap();
+        assert!(Config::unknown_keys(&content).unwrap().is_empty());
+        
+        let typos = content
+            .replacen("[server]\n", "[server]\nprot = 3000\n", 1)
+            .replacen("[network]\n", "[network]\njiter_ns = 5\n", 1)
+            + "\n[profile.demo.logging]\nlevl = \"debug\"\n";
+        assert_eq!(Config::unknown_keys(&typos).unwrap(), vec!["network.jiter_ns", "server.prot", "profile.demo.logging.levl"]);
+        
+        // Profiles are checked whether or not they are selected, inherited
+        // settings are not blamed on the child
+        let profiles = content.clone()
+            + "\n[profile.base.server]\nprot = 1\n[profile.child]\ninherits = \"base\"\n[profile.child.network.ack_path]\nbase_latncy_ns = 1\n";
+        assert_eq!(Config::unknown_keys(&profiles).unwrap(), vec!["profile.base.server.prot", "profile.child.network.ack_path.base_latncy_ns"]);
+        
+        // Tenant overrides are checked as if they were the whole file
+        let tenants = content
+            + "
+[[tenants]]
+name = \"class-a\"
+overrides = { simulation = { sed = 7 }, network = { jitter_ns = 5 } }
+";
+        assert_eq!(Config::unknown_keys(&tenants).unwrap(), vec!["tenants.class-a.simulation.sed"]);
+        
+        let schema = Config::json_schema();
+        let server = &schema["definitions"]["ServerConfig"]["properties"]["port"];
+        assert_eq!(server["default"], Config::default().server.port);
+        assert!(server["description"].as_str().is_some_and(|doc| !doc.is_empty()));
+        assert_eq!(schema["properties"]["network"]["default"]["base_latency_ns"], 100_000);
+        assert!(schema["definitions"]["NetModel"]["properties"]["data_path"].is_object());
+    }
+
+    #[test]
+    fn test_network_paths_from_toml() {
+        let mut config = Config::default();
+        config.network.data_path = PathLatency::new(2_000_000, 500_000);
+        let toml_str = toml::to_string_pretty(&config).unwrap();
+        let parsed: Config = toml::from_str(&toml_str).unwrap();
+        assert_eq!(parsed.network.order_path, config.network.order_path);
+        assert_eq!(parsed.network.data_path, PathLatency::new(2_000_000, 500_000));
+        assert!(parsed.network.ack_path.is_instant());
+
+        // The order path keeps the top-level latency keys
+        let network: NetModel = toml::from_str("base_latency_ns = 80000\njitter_ns = 0\ndrop_prob = 0.0\nreorder_prob = 0.0\n[ack_path]\nbase_latency_ns = 1000").unwrap();
+        assert_eq!(network.order_path, PathLatency::new(80_000, 0));
+        assert_eq!(network.ack_path, PathLatency::new(1_000, 0));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,588 @@
+//! Protocol conformance suite
+//!
+//! Scripted interactions with a running server's public WebSocket and REST
+//! endpoints, each asserting a documented behavior: version negotiation,
+//! acks that echo their `request_id`, the error code and field of refused
+//! commands, snapshot streaming, and the sequenced feed's resume and reset
+//! rules. [`run`] plays every check against a server and returns a
+//! [`ConformanceReport`], so tools built on the protocol can pin it in CI.
+//!
+//! The suite places a few one-lot manual orders at the touch and asks for
+//! them to be cancelled on disconnect, so it can be pointed at a live
+//! server without leaving orders behind.
+
+use std::fmt;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use futures_util::{SinkExt, StreamExt};
+use serde::Serialize;
+use serde_json::json;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use crate::engine::DepthSnapshot;
+use crate::protocol::{Capability, CommandAck, ErrorCode, ServerMessage, ServerReply, PROTOCOL_VERSION};
+use crate::relay::SequencedSnapshot;
+use crate::types::{Price, Side};
+
+/// Where the server is and how long to wait for it
+#[derive(Debug, Clone)]
+pub struct ConformanceConfig {
+    /// Base URL of the server, e.g. `http://localhost:3000`
+    pub url: String,
+    /// Longest wait for any one reply or frame
+    pub timeout: Duration,
+}
+
+impl ConformanceConfig {
+    pub fn new<S: Into<String>>(url: S) -> Self {
+        Self { url: url.into().trim_end_matches('/').to_string(), timeout: Duration::from_secs(5) }
+    }
+
+    /// WebSocket URL of `path`, taking the scheme from the base URL
+    fn ws_url(&self, path: &str) -> String {
+        let base = match self.url.split_once("://") {
+            Some(("https" | "wss", rest)) => format!("wss://{}", rest),
+            Some((_, rest)) => format!("ws://{}", rest),
+            None => format!("ws://{}", self.url),
+        };
+        format!("{}{}", base, path)
+    }
+
+    /// HTTP URL of `path`
+    fn http_url(&self, path: &str) -> String {
+        let base = match self.url.split_once("://") {
+            Some(("wss" | "https", rest)) => format!("https://{}", rest),
+            Some((_, rest)) => format!("http://{}", rest),
+            None => format!("http://{}", self.url),
+        };
+        format!("{}{}", base, path)
+    }
+}
+
+/// How a check went
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", content = "reason", rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pass,
+    Fail(String),
+    /// The server does not offer what the check needs
+    Skip(String),
+}
+
+/// Outcome of one scripted interaction
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    pub name: &'static str,
+    /// The documented behavior checked
+    pub behavior: &'static str,
+    #[serde(flatten)]
+    pub status: CheckStatus,
+    pub elapsed_ms: u64,
+}
+
+/// Pass/fail report of a conformance run
+#[derive(Debug, Clone, Serialize)]
+pub struct ConformanceReport {
+    pub url: String,
+    /// Protocol version this suite checks
+    pub protocol_version: u32,
+    pub checks: Vec<CheckResult>,
+}
+
+impl ConformanceReport {
+    /// Checks that passed
+    pub fn passed(&self) -> usize {
+        self.count(|status| matches!(status, CheckStatus::Pass))
+    }
+
+    /// Checks that failed
+    pub fn failed(&self) -> usize {
+        self.count(|status| matches!(status, CheckStatus::Fail(_)))
+    }
+
+    /// Checks the server could not be put through
+    pub fn skipped(&self) -> usize {
+        self.count(|status| matches!(status, CheckStatus::Skip(_)))
+    }
+
+    /// Whether no check failed
+    pub fn conforms(&self) -> bool {
+        self.failed() == 0
+    }
+
+    fn count(&self, matches: impl Fn(&CheckStatus) -> bool) -> usize {
+        self.checks.iter().filter(|check| matches(&check.status)).count()
+    }
+
+    /// Run one check, failing it if it outlasts `timeout` overall
+    async fn record<F>(&mut self, name: &'static str, behavior: &'static str, timeout: Duration, check: F)
+    where
+        F: Future<Output = CheckStatus>,
+    {
+        let started = Instant::now();
+        let status = tokio::time::timeout(timeout, check).await
+            .unwrap_or_else(|_| CheckStatus::Fail(format!("No result within {:?}", timeout)));
+        self.checks.push(CheckResult { name, behavior, status, elapsed_ms: started.elapsed().as_millis() as u64 });
+    }
+}
+
+impl fmt::Display for ConformanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Protocol v{} conformance of {}", self.protocol_version, self.url)?;
+        for check in &self.checks {
+            match &check.status {
+                CheckStatus::Pass => writeln!(f, "  PASS {:<28} {}", check.name, check.behavior)?,
+                CheckStatus::Fail(reason) => writeln!(f, "  FAIL {:<28} {}: {}", check.name, check.behavior, reason)?,
+                CheckStatus::Skip(reason) => writeln!(f, "  SKIP {:<28} {}", check.name, reason)?,
+            }
+        }
+        write!(f, "{} passed, {} failed, {} skipped", self.passed(), self.failed(), self.skipped())
+    }
+}
+
+/// Put a server through every check
+pub async fn run(config: &ConformanceConfig) -> ConformanceReport {
+    let mut report = ConformanceReport { url: config.url.clone(), protocol_version: PROTOCOL_VERSION, checks: Vec::new() };
+    // Generous enough for a check that waits on several replies or frames
+    let budget = config.timeout * 4;
+
+    report.record("hello_negotiation", "hello is answered with this protocol version and its default capabilities",
+                  budget, status_of(check_hello(config))).await;
+    report.record("hello_unsupported_version", "a version older than the server serves is refused with VERSION_UNSUPPORTED",
+                  budget, status_of(check_old_version(config))).await;
+    report.record("request_id_echo", "every reply carries its command's request_id, and none when the command had none",
+                  budget, status_of(check_request_ids(config))).await;
+    report.record("error_codes", "refused commands name their error code and the field at fault",
+                  budget, status_of(check_error_codes(config))).await;
+    report.record("snapshot_stream", "depth snapshots stream to /ws connections in timestamp order",
+                  budget, status_of(check_snapshots(config))).await;
+    report.record("order_acks", "placed orders are acknowledged with distinct order ids",
+                  budget, status_of(check_order_acks(config))).await;
+    report.record("execution_reports", "a taking order's ack lists its trades and an execution summary that adds up",
+                  budget, status_of(check_executions(config))).await;
+    report.record("feed_sequencing", "/feed numbers frames contiguously",
+                  budget, status_of(check_feed_sequence(config))).await;
+    report.record("feed_resume", "/feed?from= resumes at the requested sequence number",
+                  budget, status_of(check_feed_resume(config))).await;
+    report.record("feed_reset", "/feed?from= past the kept history starts over with a reset frame",
+                  budget, status_of(check_feed_reset(config))).await;
+    report.record("delta_resync", "/feed/resync refuses an empty range with FIELD_INVALID on min_price",
+                  budget, check_resync(config)).await;
+    report.record("rest_health", "/health answers with a status",
+                  budget, status_of(check_health(config))).await;
+    report.record("rest_error_codes", "refused REST order entry carries the same error code and field",
+                  budget, status_of(check_preview_errors(config))).await;
+    report
+}
+
+async fn status_of(check: impl Future<Output = Result<(), String>>) -> CheckStatus {
+    match check.await {
+        Ok(()) => CheckStatus::Pass,
+        Err(reason) => CheckStatus::Fail(reason),
+    }
+}
+
+/// A raw `/ws` connection, so the suite sees exactly what the server sends
+struct Probe {
+    stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    timeout: Duration,
+}
+
+impl Probe {
+    async fn connect(config: &ConformanceConfig) -> Result<Self, String> {
+        let url = config.ws_url("/ws");
+        let (stream, _) = tokio_tungstenite::connect_async(&url).await
+            .map_err(|e| format!("Cannot connect to {}: {}", url, e))?;
+        Ok(Self { stream, timeout: config.timeout })
+    }
+
+    /// Send a text frame and wait for the reply to it
+    async fn request(&mut self, text: impl Into<String>) -> Result<ServerReply, String> {
+        self.stream.send(Message::Text(text.into())).await.map_err(|e| format!("Send failed: {}", e))?;
+        loop {
+            if let ServerMessage::Reply(reply) = self.next_message().await? {
+                return Ok(*reply);
+            }
+        }
+    }
+
+    /// Send a command and expect it acknowledged
+    async fn ack(&mut self, command: serde_json::Value) -> Result<CommandAck, String> {
+        match self.request(command.to_string()).await? {
+            ServerReply::Ack { ack, .. } => Ok(ack),
+            reply => Err(format!("Expected an ack to {}, got {:?}", command, reply)),
+        }
+    }
+
+    /// Next depth snapshot, skipping other messages
+    async fn snapshot(&mut self) -> Result<DepthSnapshot, String> {
+        loop {
+            if let ServerMessage::Snapshot(snapshot) = self.next_message().await? {
+                return Ok(*snapshot);
+            }
+        }
+    }
+
+    async fn next_message(&mut self) -> Result<ServerMessage, String> {
+        loop {
+            let frame = tokio::time::timeout(self.timeout, self.stream.next()).await
+                .map_err(|_| format!("Nothing received within {:?}", self.timeout))?;
+            match frame {
+                Some(Ok(Message::Text(text))) => {
+                    return serde_json::from_str(&text).map_err(|e| format!("Undecodable message {}: {}", text, e));
+                }
+                Some(Ok(Message::Close(_))) | None => return Err("Connection closed".to_string()),
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => return Err(format!("Connection error: {}", e)),
+            }
+        }
+    }
+
+    async fn close(mut self) {
+        let _ = self.stream.close(None).await;
+    }
+}
+
+/// A `/feed` connection
+struct FeedProbe {
+    stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    timeout: Duration,
+}
+
+impl FeedProbe {
+    async fn connect(config: &ConformanceConfig, from: Option<u64>) -> Result<Self, String> {
+        let path = match from {
+            Some(from) => format!("/feed?from={}", from),
+            None => "/feed".to_string(),
+        };
+        let url = config.ws_url(&path);
+        let (stream, _) = tokio_tungstenite::connect_async(&url).await
+            .map_err(|e| format!("Cannot connect to {}: {}", url, e))?;
+        Ok(Self { stream, timeout: config.timeout })
+    }
+
+    async fn frame(&mut self) -> Result<SequencedSnapshot, String> {
+        loop {
+            let frame = tokio::time::timeout(self.timeout, self.stream.next()).await
+                .map_err(|_| format!("No feed frame within {:?}", self.timeout))?;
+            match frame {
+                Some(Ok(Message::Text(text))) => {
+                    return serde_json::from_str(&text).map_err(|e| format!("Undecodable feed frame: {}", e));
+                }
+                Some(Ok(Message::Close(_))) | None => return Err("Feed closed".to_string()),
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => return Err(format!("Feed error: {}", e)),
+            }
+        }
+    }
+
+    async fn close(mut self) {
+        let _ = self.stream.close(None).await;
+    }
+}
+
+fn expect_error(reply: ServerReply, code: ErrorCode, field: Option<&str>) -> Result<(), String> {
+    match reply {
+        ServerReply::Error { error, .. } if error.code == code && error.field.as_deref() == field => Ok(()),
+        ServerReply::Error { error, .. } => Err(format!(
+            "Expected {:?} on {:?}, got {:?} on {:?} ({})", code, field, error.code, error.field, error.message
+        )),
+        reply => Err(format!("Expected a {:?} error, got {:?}", code, reply)),
+    }
+}
+
+async fn check_hello(config: &ConformanceConfig) -> Result<(), String> {
+    let mut probe = Probe::connect(config).await?;
+    let reply = probe.request(json!({ "command": "hello", "version": PROTOCOL_VERSION, "request_id": 1 }).to_string()).await?;
+    probe.close().await;
+    let ServerReply::Hello { request_id, handshake } = reply else {
+        return Err(format!("Expected a hello reply, got {:?}", reply));
+    };
+    if request_id != Some(1) {
+        return Err(format!("Hello reply carried request_id {:?}, not 1", request_id));
+    }
+    if handshake.version != PROTOCOL_VERSION {
+        return Err(format!("Server agreed to version {}, not {}", handshake.version, PROTOCOL_VERSION));
+    }
+    let missing: Vec<Capability> = Capability::ALL.into_iter()
+        .filter(|capability| !capability.opt_in() && !handshake.capabilities.contains(capability))
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("Capabilities {:?} were not granted", missing));
+    }
+    if handshake.capabilities.iter().any(Capability::opt_in) {
+        return Err(format!("Opt-in capabilities were granted unasked: {:?}", handshake.capabilities));
+    }
+    Ok(())
+}
+
+async fn check_old_version(config: &ConformanceConfig) -> Result<(), String> {
+    let mut probe = Probe::connect(config).await?;
+    let reply = probe.request(json!({ "command": "hello", "version": 0, "request_id": 2 }).to_string()).await?;
+    probe.close().await;
+    expect_error(reply, ErrorCode::VersionUnsupported, Some("version"))
+}
+
+async fn check_request_ids(config: &ConformanceConfig) -> Result<(), String> {
+    let mut probe = Probe::connect(config).await?;
+    let tagged = probe.request(json!({ "command": "get_health", "request_id": 42 }).to_string()).await?;
+    let untagged = probe.request(json!({ "command": "get_health" }).to_string()).await?;
+    let refused = probe.request(json!({ "command": "no_such_command", "request_id": 43 }).to_string()).await?;
+    probe.close().await;
+    if !matches!(tagged, ServerReply::Ack { request_id: Some(42), .. }) {
+        return Err(format!("Expected an ack with request_id 42, got {:?}", tagged));
+    }
+    if untagged.request_id().is_some() {
+        return Err(format!("A command without a request_id got one back: {:?}", untagged));
+    }
+    if !matches!(refused, ServerReply::Error { request_id: Some(43), .. }) {
+        return Err(format!("Expected an error with request_id 43, got {:?}", refused));
+    }
+    Ok(())
+}
+
+async fn check_error_codes(config: &ConformanceConfig) -> Result<(), String> {
+    let cases = [
+        (json!({ "request_id": 1 }).to_string(), ErrorCode::CommandMissing, Some("command")),
+        (json!({ "command": "no_such_command" }).to_string(), ErrorCode::CommandUnknown, Some("command")),
+        (json!({ "command": "place_test_order", "qty": 1 }).to_string(), ErrorCode::FieldMissing, Some("side")),
+        (json!({ "command": "place_test_order", "side": "Sideways", "qty": 1 }).to_string(), ErrorCode::SideInvalid, Some("side")),
+        (json!({ "command": "place_test_order", "side": "Buy", "qty": 0 }).to_string(), ErrorCode::QtyZero, Some("qty")),
+        (json!({ "command": "set_cancel_on_disconnect", "enabled": "yes" }).to_string(), ErrorCode::FieldInvalid, Some("enabled")),
+        (json!({ "command": "get_health", "padding": "x".repeat(10_000) }).to_string(), ErrorCode::MessageTooLarge, None),
+    ];
+    let mut probe = Probe::connect(config).await?;
+    let mut failures = Vec::new();
+    for (message, code, field) in cases {
+        if let Err(reason) = expect_error(probe.request(message).await?, code, field) {
+            failures.push(reason);
+        }
+    }
+    probe.close().await;
+    match failures.is_empty() {
+        true => Ok(()),
+        false => Err(failures.join("; ")),
+    }
+}
+
+async fn check_snapshots(config: &ConformanceConfig) -> Result<(), String> {
+    let mut probe = Probe::connect(config).await?;
+    let first = probe.snapshot().await?;
+    let second = probe.snapshot().await?;
+    probe.close().await;
+    if second.ts < first.ts {
+        return Err(format!("Snapshot at {} followed one at {}", second.ts, first.ts));
+    }
+    for snapshot in [&first, &second] {
+        if snapshot.best_bid != snapshot.bids.first().map(|level| level.price)
+            || snapshot.best_ask != snapshot.asks.first().map(|level| level.price) {
+            return Err(format!("Snapshot at {} has a best bid or ask that is not its top level", snapshot.ts));
+        }
+    }
+    Ok(())
+}
+
+/// Size of the order [`check_executions`] rests for its market order to take
+const RESTING_QTY: u64 = 10_000;
+
+/// A price for a one-lot order on `side` that joins the touch rather than
+/// crossing it
+fn joining_price(snapshot: &DepthSnapshot, side: Side) -> Price {
+    const FALLBACK: Price = 100_0000;
+    match side {
+        Side::Buy => snapshot.best_bid.or(snapshot.best_ask.map(|ask| ask.saturating_sub(1).max(1))).unwrap_or(FALLBACK),
+        Side::Sell => snapshot.best_ask.or(snapshot.best_bid.map(|bid| bid + 1)).unwrap_or(FALLBACK + 1),
+    }
+}
+
+/// Connect, have orders pulled on disconnect and read the book
+async fn trading_probe(config: &ConformanceConfig) -> Result<(Probe, DepthSnapshot), String> {
+    let mut probe = Probe::connect(config).await?;
+    probe.ack(json!({ "command": "set_cancel_on_disconnect", "enabled": true })).await?;
+    let snapshot = probe.snapshot().await?;
+    Ok((probe, snapshot))
+}
+
+async fn check_order_acks(config: &ConformanceConfig) -> Result<(), String> {
+    let (mut probe, snapshot) = trading_probe(config).await?;
+    let price = joining_price(&snapshot, Side::Buy);
+    let mut order_ids = Vec::new();
+    for request_id in [10, 11] {
+        let command = json!({ "command": "place_test_order", "side": "Buy", "qty": 1, "price": price, "request_id": request_id });
+        let reply = probe.request(command.to_string()).await?;
+        match reply {
+            ServerReply::Ack { request_id: Some(id), ack } if id == request_id => match ack.order_id {
+                Some(order_id) => order_ids.push(order_id),
+                None => return Err(format!("Ack to order {} has no order_id", request_id)),
+            },
+            reply => return Err(format!("Expected an ack with request_id {}, got {:?}", request_id, reply)),
+        }
+    }
+    probe.close().await;
+    match order_ids[1] != order_ids[0] {
+        true => Ok(()),
+        false => Err(format!("Both orders were acknowledged as order {}", order_ids[0])),
+    }
+}
+
+async fn check_executions(config: &ConformanceConfig) -> Result<(), String> {
+    let (mut probe, snapshot) = trading_probe(config).await?;
+    // Make sure there is something to take. The book may have moved since
+    // the snapshot, in which case the sell takes liquidity itself. It rests
+    // deeper than other flow is likely to take before the buy arrives, and
+    // is pulled when the probe disconnects
+    let price = joining_price(&snapshot, Side::Sell);
+    let sell = probe.ack(json!({ "command": "place_test_order", "side": "Sell", "qty": RESTING_QTY, "price": price })).await?;
+    let (ack, aggressor) = match sell.trades.is_empty() {
+        true => (probe.ack(json!({ "command": "place_test_order", "side": "Buy", "qty": 1 })).await?, Side::Buy),
+        false => (sell, Side::Sell),
+    };
+    probe.close().await;
+
+    if ack.trades.is_empty() {
+        return Err("Market order ack lists no trades".to_string());
+    }
+    if let Some(trade) = ack.trades.iter().find(|trade| trade.aggressor != aggressor) {
+        return Err(format!("Trade {:?} does not name the {:?} side as aggressor", trade, aggressor));
+    }
+    let execution = ack.execution.ok_or("Taking order ack has no execution summary")?;
+    let filled: u64 = ack.trades.iter().map(|trade| trade.qty).sum();
+    if execution.qty != filled {
+        return Err(format!("Execution summary reports {} filled, trades add up to {}", execution.qty, filled));
+    }
+    Ok(())
+}
+
+async fn check_feed_sequence(config: &ConformanceConfig) -> Result<(), String> {
+    let mut feed = FeedProbe::connect(config, None).await?;
+    let mut last = feed.frame().await?.seq;
+    for _ in 0..3 {
+        let frame = feed.frame().await?;
+        if frame.seq != last + 1 {
+            feed.close().await;
+            return Err(format!("Frame {} followed frame {}", frame.seq, last));
+        }
+        last = frame.seq;
+    }
+    feed.close().await;
+    Ok(())
+}
+
+async fn check_feed_resume(config: &ConformanceConfig) -> Result<(), String> {
+    let mut feed = FeedProbe::connect(config, None).await?;
+    let last = feed.frame().await?.seq;
+    feed.close().await;
+
+    let mut feed = FeedProbe::connect(config, Some(last + 1)).await?;
+    let frame = feed.frame().await?;
+    feed.close().await;
+    match (frame.seq, frame.reset) {
+        (seq, false) if seq == last + 1 => Ok(()),
+        (seq, reset) => Err(format!("Resuming from {} started at {} (reset {})", last + 1, seq, reset)),
+    }
+}
+
+async fn check_feed_reset(config: &ConformanceConfig) -> Result<(), String> {
+    let mut feed = FeedProbe::connect(config, None).await?;
+    let last = feed.frame().await?.seq;
+    feed.close().await;
+
+    // Far past anything the server has numbered
+    let mut feed = FeedProbe::connect(config, Some(last + 1_000_000)).await?;
+    let frame = feed.frame().await?;
+    feed.close().await;
+    match frame.reset {
+        true => Ok(()),
+        false => Err(format!("Resuming past the history started at {} without a reset", frame.seq)),
+    }
+}
+
+async fn check_resync(config: &ConformanceConfig) -> CheckStatus {
+    let client = reqwest::Client::new();
+    let response = client.post(config.http_url("/feed/resync"))
+        .json(&json!({ "min_price": 2, "max_price": 1 }))
+        .send()
+        .await;
+    match response {
+        Ok(response) if response.status() == reqwest::StatusCode::NOT_FOUND => {
+            CheckStatus::Skip("The streaming publisher is not enabled".to_string())
+        }
+        Ok(response) => status_of(expect_rest_error(response, ErrorCode::FieldInvalid, "min_price")).await,
+        Err(e) => CheckStatus::Fail(format!("Request failed: {}", e)),
+    }
+}
+
+async fn check_health(config: &ConformanceConfig) -> Result<(), String> {
+    let response = reqwest::get(config.http_url("/health")).await.map_err(|e| format!("Request failed: {}", e))?;
+    let body: serde_json::Value = response.json().await.map_err(|e| format!("Health is not JSON: {}", e))?;
+    match body.get("status").and_then(|status| status.as_str()) {
+        Some(_) => Ok(()),
+        None => Err(format!("Health has no status: {}", body)),
+    }
+}
+
+async fn check_preview_errors(config: &ConformanceConfig) -> Result<(), String> {
+    let response = reqwest::Client::new().post(config.http_url("/orders/preview"))
+        .json(&json!({ "side": "Buy", "qty": 0, "price": 100_0000 }))
+        .send()
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?;
+    expect_rest_error(response, ErrorCode::QtyZero, "qty").await
+}
+
+async fn expect_rest_error(response: reqwest::Response, code: ErrorCode, field: &str) -> Result<(), String> {
+    let status = response.status();
+    if !status.is_client_error() {
+        return Err(format!("Expected a client error, got {}", status));
+    }
+    let body: serde_json::Value = response.json().await.map_err(|e| format!("Error body is not JSON: {}", e))?;
+    let got_code = body.get("code").cloned().map(serde_json::from_value::<ErrorCode>);
+    match (got_code, body.get("field").and_then(|field| field.as_str())) {
+        (Some(Ok(got)), Some(got_field)) if got == code && got_field == field => Ok(()),
+        _ => Err(format!("Expected {:?} on {}, got {}", code, field, body)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+    use crate::engine::OrderBook;
+    use crate::queue_fifo::FifoLevel;
+    use crate::server::{create_router, start_simulation_loop, AppState};
+    use crate::sim::Simulator;
+
+    #[tokio::test]
+    async fn test_server_conforms() {
+        let state = AppState::new(Simulator::with_seed(OrderBook::<FifoLevel>::new(), 42));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = create_router(state.clone());
+        tokio::spawn(async move {
+            axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
+        });
+        let simulation = tokio::spawn(start_simulation_loop(state, 10));
+
+        let report = run(&ConformanceConfig::new(format!("http://{}", addr))).await;
+        assert!(report.conforms(), "{}", report);
+        assert_eq!(report.skipped(), 1);
+        assert_eq!(report.passed() + report.skipped(), report.checks.len());
+        simulation.abort();
+
+        // Nothing listening fails every check rather than hanging
+        let config = ConformanceConfig { timeout: Duration::from_millis(200), ..ConformanceConfig::new("http://127.0.0.1:1") };
+        let report = run(&config).await;
+        assert_eq!(report.failed(), report.checks.len());
+    }
+
+    #[test]
+    fn test_urls_follow_the_base_scheme() {
+        let config = ConformanceConfig::new("https://engine.example.com/");
+        assert_eq!(config.ws_url("/ws"), "wss://engine.example.com/ws");
+        assert_eq!(config.http_url("/health"), "https://engine.example.com/health");
+        let config = ConformanceConfig::new("localhost:3000");
+        assert_eq!(config.ws_url("/feed?from=3"), "ws://localhost:3000/feed?from=3");
+        assert_eq!(config.http_url("/health"), "http://localhost:3000/health");
+    }
+}
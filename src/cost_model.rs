@@ -0,0 +1,220 @@
+//! Expected execution cost against a depth snapshot
+//!
+//! A [`CostModel`] walks the visible levels of a [`DepthSnapshot`] the way an
+//! incoming order would, without touching the book. It reports what a market
+//! order of a given size would cost to sweep, what a limit order would fill
+//! immediately, and the slippage of either against the mid. Hidden liquidity
+//! and queue dynamics are ignored, so the figures are what the book shows at
+//! the moment the snapshot was taken.
+
+use serde::{Deserialize, Serialize};
+use crate::engine::{BookLevelPoint, DepthSnapshot};
+use crate::types::{Price, Qty, Side};
+
+/// What an order would pay to execute against the visible book
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionCost {
+    pub side: Side,
+    /// Quantity asked for
+    pub requested_qty: Qty,
+    /// Quantity the visible book can fill
+    pub filled_qty: Qty,
+    /// Sum of price times quantity over the fills, in ticks
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub sweep_cost: u128,
+    /// Volume-weighted fill price in ticks
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub avg_price: Option<f64>,
+    /// Least favourable price reached
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub worst_price: Option<Price>,
+    /// Distinct prices filled at
+    pub levels_swept: usize,
+    /// Average fill price worse than the mid, in ticks
+    pub slippage_ticks: Option<f64>,
+    /// Slippage in basis points of the mid
+    pub slippage_bps: Option<f64>,
+}
+
+impl ExecutionCost {
+    /// Whether the whole requested quantity would fill
+    pub fn is_complete(&self) -> bool {
+        self.filled_qty == self.requested_qty
+    }
+}
+
+/// Execution cost queries against one snapshot
+#[derive(Debug, Clone, Default)]
+pub struct CostModel {
+    /// Bid levels, best first
+    bids: Vec<(Price, Qty)>,
+    /// Ask levels, best first
+    asks: Vec<(Price, Qty)>,
+    /// Mid in ticks, if both sides are quoted
+    mid: Option<f64>,
+}
+
+impl CostModel {
+    /// Model the visible book of a snapshot
+    pub fn from_snapshot(snapshot: &DepthSnapshot) -> Self {
+        let levels = |levels: &[BookLevelPoint]| levels.iter()
+            .filter(|level| level.qty > 0)
+            .map(|level| (level.price, level.qty))
+            .collect();
+        let mid = match (snapshot.best_bid, snapshot.best_ask) {
+            (Some(bid), Some(ask)) => Some((bid as f64 + ask as f64) / 2.0),
+            _ => None,
+        };
+        Self { bids: levels(&snapshot.bids), asks: levels(&snapshot.asks), mid }
+    }
+
+    /// Mid in ticks, if both sides are quoted
+    pub fn mid(&self) -> Option<f64> {
+        self.mid
+    }
+
+    /// Cost of a market order sweeping `qty` from the opposite side
+    pub fn sweep(&self, side: Side, qty: Qty) -> ExecutionCost {
+        self.walk(side, qty, None)
+    }
+
+    /// What a limit order would fill on arrival, trading only at `price` or better
+    pub fn expected_fill(&self, side: Side, price: Price, qty: Qty) -> ExecutionCost {
+        self.walk(side, qty, Some(price))
+    }
+
+    /// Largest market order whose slippage stays within `max_slippage_ticks`,
+    /// capped by the visible depth
+    pub fn max_qty_within(&self, side: Side, max_slippage_ticks: f64) -> Qty {
+        let Some(mid) = self.mid else {
+            return 0;
+        };
+
+        // Slippage only grows as the order walks the book, so take each level
+        // whole while the running average stays in budget, then the part of
+        // the first level that would break it
+        let (mut qty, mut adverse) = (0u64, 0.0f64);
+        for &(price, available) in self.levels_against(side) {
+            let level_adverse = Self::adverse(side, price as f64, mid);
+            let total = adverse + level_adverse * available as f64;
+            if total <= max_slippage_ticks * (qty + available) as f64 {
+                qty += available;
+                adverse = total;
+                continue;
+            }
+            if level_adverse > max_slippage_ticks {
+                let room = max_slippage_ticks * qty as f64 - adverse;
+                qty += (room / (level_adverse - max_slippage_ticks)).floor().max(0.0) as Qty;
+            }
+            break;
+        }
+        qty
+    }
+
+    /// Opposite side levels an order on `side` trades against, best first
+    fn levels_against(&self, side: Side) -> &[(Price, Qty)] {
+        match side {
+            Side::Buy => &self.asks,
+            Side::Sell => &self.bids,
+        }
+    }
+
+    /// How much worse than the mid `price` is for an order on `side`
+    fn adverse(side: Side, price: f64, mid: f64) -> f64 {
+        match side {
+            Side::Buy => price - mid,
+            Side::Sell => mid - price,
+        }
+    }
+
+    fn walk(&self, side: Side, qty: Qty, limit: Option<Price>) -> ExecutionCost {
+        let tradeable = |price: Price| match (side, limit) {
+            (_, None) => true,
+            (Side::Buy, Some(limit)) => price <= limit,
+            (Side::Sell, Some(limit)) => price >= limit,
+        };
+
+        let (mut filled, mut sweep_cost, mut levels_swept, mut worst_price) = (0, 0u128, 0, None);
+        for &(price, available) in self.levels_against(side) {
+            if filled == qty || !tradeable(price) {
+                break;
+            }
+            let take = available.min(qty - filled);
+            filled += take;
+            sweep_cost += price as u128 * take as u128;
+            levels_swept += 1;
+            worst_price = Some(price);
+        }
+
+        let avg_price = (filled > 0).then(|| sweep_cost as f64 / filled as f64);
+        let slippage_ticks = avg_price.zip(self.mid).map(|(avg, mid)| Self::adverse(side, avg, mid));
+        ExecutionCost {
+            side,
+            requested_qty: qty,
+            filled_qty: filled,
+            sweep_cost,
+            avg_price,
+            worst_price,
+            levels_swept,
+            slippage_ticks,
+            slippage_bps: slippage_ticks.zip(self.mid).map(|(slippage, mid)| slippage / mid * 10_000.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{OrderBook, OrderBookEngine};
+    use crate::queue_fifo::FifoLevel;
+    use crate::types::Order;
+
+    fn model() -> CostModel {
+        let mut book = OrderBook::<FifoLevel>::new();
+        let ts = crate::time::now_ns();
+        book.place(Order::new_limit(1, Side::Buy, 100, 99_0000, ts)).unwrap();
+        book.place(Order::new_limit(2, Side::Sell, 100, 101_0000, ts)).unwrap();
+        book.place(Order::new_limit(3, Side::Sell, 200, 102_0000, ts)).unwrap();
+        book.place(Order::new_limit(4, Side::Sell, 300, 104_0000, ts)).unwrap();
+        CostModel::from_snapshot(&book.snapshot())
+    }
+
+    #[test]
+    fn test_sweep_and_expected_fill() {
+        let model = model();
+        assert_eq!(model.mid(), Some(100_0000.0));
+
+        let cost = model.sweep(Side::Buy, 200);
+        assert!(cost.is_complete());
+        assert_eq!(cost.sweep_cost, 101_0000 * 100 + 102_0000 * 100);
+        assert_eq!(cost.avg_price, Some(101_5000.0));
+        assert_eq!((cost.levels_swept, cost.worst_price), (2, Some(102_0000)));
+        assert_eq!(cost.slippage_ticks, Some(1_5000.0));
+        assert!((cost.slippage_bps.unwrap() - 150.0).abs() < 1e-9);
+
+        // Beyond the visible depth only part fills
+        let cost = model.sweep(Side::Buy, 1000);
+        assert_eq!((cost.filled_qty, cost.levels_swept), (600, 3));
+        assert!(!cost.is_complete());
+
+        // A limit order stops at its price
+        let cost = model.expected_fill(Side::Buy, 102_0000, 500);
+        assert_eq!((cost.filled_qty, cost.worst_price), (300, Some(102_0000)));
+        assert_eq!(model.expected_fill(Side::Sell, 99_5000, 50).filled_qty, 0);
+        assert_eq!(model.expected_fill(Side::Sell, 99_5000, 50).avg_price, None);
+    }
+
+    #[test]
+    fn test_max_qty_within_slippage_budget() {
+        let model = model();
+        // Best ask alone costs 1_0000 ticks of slippage
+        assert_eq!(model.max_qty_within(Side::Buy, 9999.0), 0);
+        assert_eq!(model.max_qty_within(Side::Buy, 1_0000.0), 100);
+        // 100 @ +1_0000 plus x @ +2_0000 averages +1_5000 at x = 100
+        assert_eq!(model.max_qty_within(Side::Buy, 1_5000.0), 200);
+        assert_eq!(model.max_qty_within(Side::Buy, 1_000_000.0), 600);
+        let qty = model.max_qty_within(Side::Buy, 1_5000.0);
+        assert!(model.sweep(Side::Buy, qty).slippage_ticks.unwrap() <= 1_5000.0);
+        assert_eq!(CostModel::default().max_qty_within(Side::Sell, 1_0000.0), 0);
+    }
+}
@@ -1,6 +1,11 @@
-use crate::types::{Order, OrderId, Price, Qty, Side, price_utils};
+use crate::types::{Order, OrderId, OrderType, Price, Qty, Side, price_utils};
+use crate::time::{date_to_ns, NANOS_PER_DAY};
 use crate::metrics::PerformanceMetrics;
+use crate::analytics::TradeClassifier;
+use crate::order_refs::UnmatchedOrderReport;
+use crate::flow_transform::AppliedTransform;
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 use thiserror::Error;
 use std::fs::File;
 use std::path::{Path, PathBuf};
@@ -8,6 +13,7 @@ use std::time::{Duration, Instant};
 use std::io::BufRead;
 use std::sync::Arc;
 use csv::{Reader, StringRecord};
+use metrics::{counter, gauge};
 
 /// Errors that can occur during data ingestion
 #[derive(Error, Debug, Clone, PartialEq)]
@@ -179,6 +185,11 @@ pub enum MarketEvent {
         ask_qty: Option<Qty>,
         timestamp: u128,
     },
+
+    /// Liveness signal from a feed with nothing else to report
+    Heartbeat {
+        timestamp: u128,
+    },
 }
 
 /// Market status types
@@ -209,6 +220,21 @@ impl MarketEvent {
             Self::OrderModification { timestamp, .. } => *timestamp,
             Self::MarketStatus { timestamp, .. } => *timestamp,
             Self::BestBidOffer { timestamp, .. } => *timestamp,
+            Self::Heartbeat { timestamp } => *timestamp,
+        }
+    }
+
+    /// Replace the timestamp of this event
+    pub fn set_timestamp(&mut self, ts: u128) {
+        match self {
+            Self::OrderPlacement(order) => order.ts = ts,
+            Self::Trade { timestamp, .. }
+            | Self::Quote { timestamp, .. }
+            | Self::OrderCancellation { timestamp, .. }
+            | Self::OrderModification { timestamp, .. }
+            | Self::MarketStatus { timestamp, .. }
+            | Self::BestBidOffer { timestamp, .. }
+            | Self::Heartbeat { timestamp } => *timestamp = ts,
         }
     }
 
@@ -343,6 +369,328 @@ pub trait DataSource: Send + Sync {
 
     /// Check if playback is currently paused
     fn is_paused(&self) -> bool;
+
+    /// Symbol of the event most recently returned by `next_event`, if the
+    /// data carries one
+    fn last_symbol(&self) -> Option<&str> {
+        None
+    }
+
+    /// Line of the file the event most recently returned by `next_event`
+    /// was read from, if the data is line-based
+    fn last_line(&self) -> Option<usize> {
+        None
+    }
+
+    /// Only return events for the given symbols; an empty list clears the filter.
+    /// Events without a symbol are dropped while a filter is active.
+    fn filter_symbols(&mut self, symbols: Vec<String>) -> DataResult<()> {
+        let _ = symbols;
+        Err(DataError::unsupported("Symbol filtering is not supported by this data source"))
+    }
+
+    /// Restrict playback to events with `start <= timestamp <= end`.
+    /// The source seeks to `start` and reports the end of data after `end`;
+    /// `reset` returns to the start of the window rather than the file.
+    fn set_time_window(&mut self, start: u128, end: u128) -> DataResult<()> {
+        let _ = (start, end);
+        Err(DataError::unsupported("Time windows are not supported by this data source"))
+    }
+
+    /// Choose what happens to records that fail to parse or validate
+    fn set_error_policy(&mut self, policy: ErrorPolicy) -> DataResult<()> {
+        let _ = policy;
+        Err(DataError::unsupported("Error policies are not supported by this data source"))
+    }
+}
+
+/// Number of events between checkpoints in a [`SeekIndex`]
+const SEEK_INDEX_INTERVAL: u64 = 1024;
+
+/// Sparse timestamp index into a data file, filled in as the file is read
+/// and extended ahead of a seek by skimming the file, so seeks can jump to a
+/// nearby offset instead of re-parsing from the beginning. `P` is whatever
+/// the source needs to reposition its reader.
+#[derive(Debug, Clone)]
+struct SeekIndex<P> {
+    /// (timestamp, event ordinal, position) in file order
+    checkpoints: Vec<(u128, u64, P)>,
+    /// The checkpoints run to the end of the file
+    complete: bool,
+}
+
+impl<P: Clone> SeekIndex<P> {
+    fn new() -> Self {
+        Self { checkpoints: Vec::new(), complete: false }
+    }
+
+    /// Whether the checkpoints cover `timestamp`, so a seek to it only has
+    /// to scan from the checkpoint before it
+    fn reaches(&self, timestamp: u128) -> bool {
+        self.complete || self.checkpoints.last().is_some_and(|(ts, _, _)| *ts >= timestamp)
+    }
+
+    /// Last checkpoint, where extending the index resumes
+    fn last(&self) -> Option<(u64, P)> {
+        self.checkpoints.last().map(|(_, ordinal, position)| (*ordinal, position.clone()))
+    }
+
+    /// Record the position of the `ordinal`th record if it falls on a
+    /// checkpoint boundary past the indexed prefix
+    fn record(&mut self, timestamp: u128, ordinal: u64, position: impl FnOnce() -> P) {
+        let extends = self.checkpoints.last().is_none_or(|(_, last, _)| ordinal > *last);
+        if extends && ordinal.is_multiple_of(SEEK_INDEX_INTERVAL) {
+            self.checkpoints.push((timestamp, ordinal, position()));
+        }
+    }
+
+    /// Latest checkpoint strictly before `timestamp`, from which a forward
+    /// scan is guaranteed not to miss the first event at or after it
+    fn checkpoint_before(&self, timestamp: u128) -> Option<(u64, P)> {
+        let index = self.checkpoints.partition_point(|(ts, _, _)| *ts < timestamp);
+        index
+            .checked_sub(1)
+            .map(|i| (self.checkpoints[i].1, self.checkpoints[i].2.clone()))
+    }
+}
+
+/// Where a timestamp falls relative to an optional time window
+fn window_cmp(window: Option<(u128, u128)>, timestamp: u128) -> std::cmp::Ordering {
+    match window {
+        Some((start, _)) if timestamp < start => std::cmp::Ordering::Less,
+        Some((_, end)) if timestamp > end => std::cmp::Ordering::Greater,
+        _ => std::cmp::Ordering::Equal,
+    }
+}
+
+fn validate_time_window(start: u128, end: u128) -> DataResult<()> {
+    if start > end {
+        return Err(DataError::validation(format!(
+            "Time window start {} is after end {}", start, end
+        )));
+    }
+    Ok(())
+}
+
+/// Seek a source to the start of its time window, treating a window with no
+/// events as an exhausted source
+fn seek_to_window_start<S: DataSource + ?Sized>(source: &mut S, start: u128) -> DataResult<bool> {
+    match source.seek_to_time(start) {
+        Ok(()) => Ok(true),
+        Err(DataError::SeekFailed { .. }) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Per-symbol bookkeeping shared by the file-based data sources
+#[derive(Debug, Clone, Default)]
+struct SymbolTracker {
+    /// Symbols to keep; empty means every event passes
+    filter: std::collections::HashSet<String>,
+    /// Symbol of the last event that passed the filter
+    last_symbol: Option<String>,
+}
+
+impl SymbolTracker {
+    fn set_filter(&mut self, symbols: Vec<String>) {
+        self.filter = symbols.into_iter().collect();
+    }
+
+    /// Count an event's symbol in the metadata and report whether it passes the filter
+    fn observe(&mut self, symbol: Option<String>, metadata: &mut DataSourceMetadata) -> bool {
+        if let Some(ref symbol) = symbol {
+            *metadata.symbol_counts.entry(symbol.clone()).or_insert(0) += 1;
+        }
+
+        let keep = self.filter.is_empty()
+            || symbol.as_ref().is_some_and(|symbol| self.filter.contains(symbol));
+        if keep {
+            self.last_symbol = symbol;
+        }
+        keep
+    }
+
+    fn reset(&mut self, metadata: &mut DataSourceMetadata) {
+        self.last_symbol = None;
+        metadata.symbol_counts.clear();
+    }
+}
+
+/// Infers the aggressor of trade prints that arrive without one
+///
+/// Each symbol gets its own Lee-Ready classifier fed by every quote and
+/// signed trade, so unsigned prints are judged against that symbol's market.
+#[derive(Debug, Clone, Default)]
+struct TradeSigner {
+    classifiers: std::collections::HashMap<Option<String>, TradeClassifier>,
+    classified: u64,
+    unclassified: u64,
+}
+
+impl TradeSigner {
+    /// Update market state from an event, signing it first if `unsigned`
+    fn observe(&mut self, symbol: Option<&str>, event: &mut MarketEvent, unsigned: bool, metadata: &mut DataSourceMetadata) {
+        let classifier = self.classifiers.entry(symbol.map(str::to_string)).or_default();
+        match event {
+            MarketEvent::Trade { price, side, .. } if unsigned => {
+                match classifier.classify(*price) {
+                    Some(inferred) => {
+                        *side = inferred;
+                        self.classified += 1;
+                    }
+                    None => self.unclassified += 1,
+                }
+                metadata.properties.insert("classified_trades".to_string(), self.classified.to_string());
+                metadata.properties.insert("unclassified_trades".to_string(), self.unclassified.to_string());
+            }
+            MarketEvent::Trade { price, side, .. } => classifier.observe_trade(*price, *side),
+            MarketEvent::Quote { bid, ask, .. } => classifier.observe_quote(*bid, *ask),
+            MarketEvent::BestBidOffer { best_bid, best_ask, .. } => classifier.observe_quote(*best_bid, *best_ask),
+            _ => {}
+        }
+    }
+
+    fn reset(&mut self, metadata: &mut DataSourceMetadata) {
+        *self = Self::default();
+        metadata.properties.remove("classified_trades");
+        metadata.properties.remove("unclassified_trades");
+    }
+}
+
+/// What a data source does with a record that fails to parse or validate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorPolicy {
+    /// Return the error from `next_event`
+    #[default]
+    FailFast,
+    /// Drop the record and count it
+    Skip,
+    /// Drop the record, count it and append it to a sidecar file next to
+    /// the data file (see [`ErrorPolicy::quarantine_path`])
+    Quarantine,
+}
+
+impl ErrorPolicy {
+    /// Sidecar file that quarantined records from `data_path` are written to
+    pub fn quarantine_path<P: AsRef<Path>>(data_path: P) -> PathBuf {
+        let mut path = data_path.as_ref().as_os_str().to_owned();
+        path.push(".rejected");
+        PathBuf::from(path)
+    }
+}
+
+impl std::str::FromStr for ErrorPolicy {
+    type Err = DataError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('_', "-").as_str() {
+            "fail-fast" => Ok(Self::FailFast),
+            "skip" => Ok(Self::Skip),
+            "quarantine" => Ok(Self::Quarantine),
+            _ => Err(DataError::validation(format!(
+                "Unknown error policy: {} (expected fail-fast, skip or quarantine)", s
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::FailFast => write!(f, "fail-fast"),
+            Self::Skip => write!(f, "skip"),
+            Self::Quarantine => write!(f, "quarantine"),
+        }
+    }
+}
+
+/// Applies an [`ErrorPolicy`] to the bad records of one data file
+#[derive(Debug)]
+struct RecordErrorHandler {
+    policy: ErrorPolicy,
+    /// Sidecar for quarantined records
+    quarantine_path: PathBuf,
+    /// Opened on the first quarantined record
+    quarantine: Option<std::io::BufWriter<File>>,
+    /// Ordinal of the furthest record already counted, so rereading a bad
+    /// record after a seek or reset does not count or quarantine it twice
+    furthest_counted: Option<u64>,
+}
+
+impl RecordErrorHandler {
+    fn new(data_path: &Path) -> Self {
+        Self {
+            policy: ErrorPolicy::default(),
+            quarantine_path: ErrorPolicy::quarantine_path(data_path),
+            quarantine: None,
+            furthest_counted: None,
+        }
+    }
+
+    fn set_policy(&mut self, policy: ErrorPolicy) {
+        self.policy = policy;
+    }
+
+    /// Handle a bad record at position `ordinal` in the file. Returns the
+    /// error under fail-fast; otherwise counts the record (and quarantines
+    /// `raw` verbatim) so the caller can move on to the next one.
+    fn handle(
+        &mut self,
+        error: DataError,
+        ordinal: u64,
+        raw: &[u8],
+        metadata: &mut DataSourceMetadata,
+        perf_metrics: Option<&PerformanceMetrics>,
+    ) -> DataResult<()> {
+        use std::io::Write;
+
+        if self.policy == ErrorPolicy::FailFast {
+            return Err(error);
+        }
+        if self.furthest_counted.is_some_and(|furthest| ordinal <= furthest) {
+            return Ok(());
+        }
+        self.furthest_counted = Some(ordinal);
+
+        let quarantined = self.policy == ErrorPolicy::Quarantine;
+        if quarantined {
+            if self.quarantine.is_none() {
+                let file = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.quarantine_path)?;
+                self.quarantine = Some(std::io::BufWriter::new(file));
+            }
+            if let Some(ref mut writer) = self.quarantine {
+                writer.write_all(raw)?;
+                writer.flush()?;
+            }
+            metadata.quarantined_records += 1;
+        } else {
+            metadata.skipped_records += 1;
+        }
+
+        if let Some(perf_metrics) = perf_metrics {
+            perf_metrics.record_corrupt_record(quarantined);
+        }
+        tracing::debug!("Dropped bad record {} ({}): {}", ordinal, self.policy, error);
+        Ok(())
+    }
+}
+
+/// Record one `next_event` call of a monitored source: the records it read
+/// and how many of them, or the call itself, failed
+fn record_ingestion<T>(
+    perf_metrics: Option<&PerformanceMetrics>,
+    start_time: Instant,
+    records: usize,
+    dropped: usize,
+    result: &DataResult<T>,
+) {
+    if let Some(perf_metrics) = perf_metrics {
+        perf_metrics.record_data_ingestion(start_time.elapsed(), records, dropped + usize::from(result.is_err()));
+    }
 }
 
 /// Metadata about a data source
@@ -360,6 +708,22 @@ pub struct DataSourceMetadata {
     pub file_size: Option<u64>,
     /// Additional properties
     pub properties: std::collections::HashMap<String, String>,
+    /// Events read so far per symbol, counted before any symbol filter
+    #[serde(default)]
+    pub symbol_counts: std::collections::HashMap<String, usize>,
+    /// Bad records dropped under [`ErrorPolicy::Skip`]
+    #[serde(default)]
+    pub skipped_records: u64,
+    /// Bad records written to the sidecar under [`ErrorPolicy::Quarantine`]
+    #[serde(default)]
+    pub quarantined_records: u64,
+    /// Cancels and modifications naming orders the book never saw, counted
+    /// by the simulator replaying the data
+    #[serde(default)]
+    pub unmatched_orders: UnmatchedOrderReport,
+    /// Stress transform applied to the replayed flow, and what it changed
+    #[serde(default)]
+    pub flow_transform: Option<AppliedTransform>,
 }
 
 impl DataSourceMetadata {
@@ -372,6 +736,11 @@ impl DataSourceMetadata {
             time_range: None,
             file_size: None,
             properties: std::collections::HashMap::new(),
+            symbol_counts: std::collections::HashMap::new(),
+            skipped_records: 0,
+            quarantined_records: 0,
+            unmatched_orders: UnmatchedOrderReport::default(),
+            flow_transform: None,
         }
     }
 
@@ -398,6 +767,22 @@ impl DataSourceMetadata {
         self.file_size = Some(size);
         self
     }
+
+    /// Record the file the data is read from, canonicalized when it can be
+    pub fn with_path(self, path: &Path) -> Self {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.with_property("path", path.display().to_string())
+    }
+
+    /// Canonical path of the file the data is read from, for file sources
+    pub fn path(&self) -> Option<&str> {
+        self.properties.get("path").map(String::as_str)
+    }
+
+    /// Bad records dropped under either the skip or quarantine policy
+    pub fn dropped_records(&self) -> usize {
+        (self.skipped_records + self.quarantined_records) as usize
+    }
 }
 
 // Conversion from std::io::Error to DataError
@@ -456,6 +841,20 @@ pub struct CsvDataSource {
     record_buffer: StringRecord,
     /// Performance metrics (optional)
     perf_metrics: Option<Arc<PerformanceMetrics>>,
+    /// Index of the `symbol` column if the header has one
+    symbol_column: Option<usize>,
+    /// Symbol counting and filtering
+    symbols: SymbolTracker,
+    /// Record positions by timestamp for seeking
+    seek_index: SeekIndex<csv::Position>,
+    /// What to do with records that fail to parse or validate
+    errors: RecordErrorHandler,
+    /// Inclusive time window to play back, if restricted
+    time_window: Option<(u128, u128)>,
+    /// Set by the trade parser when the record had no usable side
+    unsigned_trade: std::sync::atomic::AtomicBool,
+    /// Classifies trades with no side column value
+    signer: TradeSigner,
 }
 
 impl CsvDataSource {
@@ -464,17 +863,25 @@ impl CsvDataSource {
         let path = file_path.as_ref().to_path_buf();
         let file = File::open(&path).map_err(|_| DataError::file_not_found(path.display().to_string()))?;
         
-        let reader = csv::ReaderBuilder::new()
+        let mut reader = csv::ReaderBuilder::new()
             .has_headers(true)
             .flexible(true) // Allow records with different numbers of fields
             .from_reader(file);
 
+        // The symbol column may sit anywhere in the header; it is stripped
+        // from each record before the positional parsers see it
+        let symbol_column = reader
+            .headers()?
+            .iter()
+            .position(|column| column.trim().eq_ignore_ascii_case("symbol"));
+
         // Get file metadata
         let file_size = std::fs::metadata(&path)?.len();
         let metadata = DataSourceMetadata::new(
             path.file_name().unwrap_or_default().to_string_lossy(),
             "CSV"
-        ).with_file_size(file_size);
+        ).with_file_size(file_size).with_path(&path);
+        let errors = RecordErrorHandler::new(&path);
 
         Ok(Self {
             reader,
@@ -489,6 +896,13 @@ impl CsvDataSource {
             finished: false,
             record_buffer: StringRecord::new(),
             perf_metrics: None,
+            symbol_column,
+            symbols: SymbolTracker::default(),
+            seek_index: SeekIndex::new(),
+            errors,
+            time_window: None,
+            unsigned_trade: std::sync::atomic::AtomicBool::new(false),
+            signer: TradeSigner::default(),
         })
     }
 
@@ -498,11 +912,202 @@ impl CsvDataSource {
         self
     }
 
+    /// Only play back events between `start` and `end` (inclusive)
+    pub fn with_time_window(mut self, start: u128, end: u128) -> DataResult<Self> {
+        self.set_time_window(start, end)?;
+        Ok(self)
+    }
+
+    /// Write a CSV data file from a collection of events
+    pub fn write_csv_file<P: AsRef<Path>>(file_path: P, events: &[MarketEvent]) -> DataResult<()> {
+        let mut writer = csv::WriterBuilder::new()
+            .flexible(true)
+            .from_path(file_path.as_ref())?;
+
+        writer.write_record(["type", "timestamp", "price", "qty", "side", "trade_id"])?;
+        for event in events {
+            writer.write_record(Self::event_record(event))?;
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Format an event as a CSV record, the inverse of `parse_record`
+    fn event_record(event: &MarketEvent) -> Vec<String> {
+        let optional_price = |price: &Option<Price>| price.map(price_utils::format).unwrap_or_default();
+        let optional_qty = |qty: &Option<Qty>| qty.map(|qty| qty.to_string()).unwrap_or_default();
+        let side_name = |side: &Side| match side {
+            Side::Buy => "buy".to_string(),
+            Side::Sell => "sell".to_string(),
+        };
+
+        match event {
+            MarketEvent::Trade { price, qty, side, timestamp, trade_id } => vec![
+                "trade".to_string(),
+                timestamp.to_string(),
+                price_utils::format(*price),
+                qty.to_string(),
+                side_name(side),
+                trade_id.clone().unwrap_or_default(),
+            ],
+            MarketEvent::Quote { bid, ask, bid_qty, ask_qty, timestamp } => vec![
+                "quote".to_string(),
+                timestamp.to_string(),
+                optional_price(bid),
+                optional_price(ask),
+                optional_qty(bid_qty),
+                optional_qty(ask_qty),
+            ],
+            MarketEvent::OrderPlacement(order) => vec![
+                "order".to_string(),
+                order.ts.to_string(),
+                order.id.to_string(),
+                side_name(&order.side),
+                order.qty.to_string(),
+                optional_price(&order.price()),
+                match order.order_type {
+                    OrderType::Limit { .. } => "limit".to_string(),
+                    OrderType::Market => "market".to_string(),
+                },
+            ],
+            MarketEvent::OrderCancellation { order_id, timestamp, reason } => vec![
+                "cancel".to_string(),
+                timestamp.to_string(),
+                order_id.to_string(),
+                reason.clone().unwrap_or_default(),
+            ],
+            MarketEvent::OrderModification { order_id, new_qty, new_price, timestamp } => vec![
+                "modify".to_string(),
+                timestamp.to_string(),
+                order_id.to_string(),
+                optional_qty(new_qty),
+                optional_price(new_price),
+            ],
+            MarketEvent::MarketStatus { status, timestamp, message } => vec![
+                "status".to_string(),
+                timestamp.to_string(),
+                match status {
+                    MarketStatusType::Open => "open",
+                    MarketStatusType::Closed => "closed",
+                    MarketStatusType::Halted => "halted",
+                    MarketStatusType::PreMarket => "premarket",
+                    MarketStatusType::AfterHours => "afterhours",
+                    MarketStatusType::Auction => "auction",
+                }
+                .to_string(),
+                message.clone().unwrap_or_default(),
+            ],
+            MarketEvent::BestBidOffer { best_bid, best_ask, bid_qty, ask_qty, timestamp } => vec![
+                "bbo".to_string(),
+                timestamp.to_string(),
+                optional_price(best_bid),
+                optional_price(best_ask),
+                optional_qty(bid_qty),
+                optional_qty(ask_qty),
+            ],
+            MarketEvent::Heartbeat { timestamp } => vec![
+                "heartbeat".to_string(),
+                timestamp.to_string(),
+                String::new(),
+            ],
+        }
+    }
+
+    /// Reopen the file at its first record, ignoring any time window
+    fn rewind(&mut self) -> DataResult<()> {
+        let file = File::open(&self.file_path)
+            .map_err(|_| DataError::file_not_found(self.file_path.display().to_string()))?;
+        
+        self.reader = csv::ReaderBuilder::new()
+            .has_headers(true)
+            .flexible(true) // Allow records with different numbers of fields
+            .from_reader(file);
+        // Read the header now, so the position before the first record
+        // points past it and seeking back there doesn't replay the header
+        self.reader.byte_headers()?;
+        
+        self.current_line = 1;
+        self.finished = false;
+        self.last_timestamp = None;
+        self.playback_start = None;
+        self.current_position = None;
+        self.symbols.reset(&mut self.metadata);
+        self.signer.reset(&mut self.metadata);
+        
+        Ok(())
+    }
+
+    /// Extend the seek index until it reaches `timestamp` or the end of the
+    /// file, parsing only the records that become checkpoints
+    fn index_through(&mut self, timestamp: u128) -> DataResult<()> {
+        if self.seek_index.reaches(timestamp) {
+            return Ok(());
+        }
+        match self.seek_index.last() {
+            Some((_, position)) => self.reader.seek(position)?,
+            None => self.rewind()?,
+        }
+
+        let mut record = csv::ByteRecord::new();
+        loop {
+            let position = self.reader.position().clone();
+            if !self.reader.read_byte_record(&mut record)? {
+                break;
+            }
+            if !position.record().is_multiple_of(SEEK_INDEX_INTERVAL) {
+                continue;
+            }
+            let Ok(record) = StringRecord::from_byte_record(record.clone()) else {
+                continue;
+            };
+            if let Ok((_, event)) = self.parse_symbol_record(&record) {
+                self.seek_index.record(event.timestamp(), position.record(), || position);
+                if event.timestamp() >= timestamp {
+                    return Ok(());
+                }
+            }
+        }
+        self.seek_index.complete = true;
+        Ok(())
+    }
+
+    /// Split the symbol column out of a record, returning the symbol and the
+    /// remaining fields
+    fn split_symbol(&self, record: &StringRecord) -> (Option<String>, StringRecord) {
+        match self.symbol_column {
+            Some(column) => {
+                let symbol = record
+                    .get(column)
+                    .map(str::trim)
+                    .filter(|symbol| !symbol.is_empty())
+                    .map(str::to_string);
+                let fields = record
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| *i != column)
+                    .map(|(_, field)| field)
+                    .collect();
+                (symbol, fields)
+            }
+            None => (None, record.clone()),
+        }
+    }
+
+    /// Parse a CSV record into a MarketEvent and its symbol
+    fn parse_symbol_record(&self, record: &StringRecord) -> DataResult<(Option<String>, MarketEvent)> {
+        if self.symbol_column.is_none() {
+            return Ok((None, self.parse_record(record)?));
+        }
+        let (symbol, fields) = self.split_symbol(record);
+        Ok((symbol, self.parse_record(&fields)?))
+    }
+
     /// Parse a CSV record into a MarketEvent
     fn parse_record(&self, record: &StringRecord) -> DataResult<MarketEvent> {
         if record.len() < 3 {
             return Err(DataError::parse_error(
-                &self.file_path.display().to_string(),
+                self.file_path.display().to_string(),
                 self.current_line,
                 "Insufficient columns in CSV record"
             ));
@@ -511,7 +1116,7 @@ impl CsvDataSource {
         // First column should be event type
         let event_type = record.get(0).ok_or_else(|| {
             DataError::parse_error(
-                &self.file_path.display().to_string(),
+                self.file_path.display().to_string(),
                 self.current_line,
                 "Missing event type column"
             )
@@ -525,8 +1130,11 @@ impl CsvDataSource {
             "modify" => self.parse_modify_record(record),
             "status" => self.parse_status_record(record),
             "bbo" => self.parse_bbo_record(record),
+            "heartbeat" => Ok(MarketEvent::Heartbeat {
+                timestamp: self.parse_timestamp(record.get(1).unwrap())?,
+            }),
             _ => Err(DataError::parse_error(
-                &self.file_path.display().to_string(),
+                self.file_path.display().to_string(),
                 self.current_line,
                 format!("Unknown event type: {}", event_type)
             ))
@@ -534,10 +1142,13 @@ impl CsvDataSource {
     }
 
     /// Parse a trade record: trade,timestamp,price,qty,side[,trade_id]
+    ///
+    /// A blank or `unknown` side is accepted as a trade-only print; it is
+    /// parsed as a buy and flagged so `next_event` can classify it.
     fn parse_trade_record(&self, record: &StringRecord) -> DataResult<MarketEvent> {
         if record.len() < 5 {
             return Err(DataError::parse_error(
-                &self.file_path.display().to_string(),
+                self.file_path.display().to_string(),
                 self.current_line,
                 "Trade record requires at least 5 columns: type,timestamp,price,qty,side"
             ));
@@ -546,7 +1157,10 @@ impl CsvDataSource {
         let timestamp = self.parse_timestamp(record.get(1).unwrap())?;
         let price = self.parse_price(record.get(2).unwrap())?;
         let qty = self.parse_qty(record.get(3).unwrap())?;
-        let side = self.parse_side(record.get(4).unwrap())?;
+        let side_str = record.get(4).unwrap().trim();
+        let unsigned = side_str.is_empty() || side_str.eq_ignore_ascii_case("unknown") || side_str == "?";
+        self.unsigned_trade.store(unsigned, std::sync::atomic::Ordering::Relaxed);
+        let side = if unsigned { Side::Buy } else { self.parse_side(side_str)? };
         let trade_id = record.get(5).map(|s| s.to_string()).filter(|s| !s.is_empty());
 
         Ok(MarketEvent::Trade {
@@ -562,7 +1176,7 @@ impl CsvDataSource {
     fn parse_quote_record(&self, record: &StringRecord) -> DataResult<MarketEvent> {
         if record.len() < 6 {
             return Err(DataError::parse_error(
-                &self.file_path.display().to_string(),
+                self.file_path.display().to_string(),
                 self.current_line,
                 "Quote record requires 6 columns: type,timestamp,bid,ask,bid_qty,ask_qty"
             ));
@@ -587,7 +1201,7 @@ impl CsvDataSource {
     fn parse_order_record(&self, record: &StringRecord) -> DataResult<MarketEvent> {
         if record.len() < 7 {
             return Err(DataError::parse_error(
-                &self.file_path.display().to_string(),
+                self.file_path.display().to_string(),
                 self.current_line,
                 "Order record requires 7 columns: type,timestamp,order_id,side,qty,price,order_type"
             ));
@@ -607,7 +1221,7 @@ impl CsvDataSource {
             }
             "market" => Order::new_market(order_id, side, qty, timestamp),
             _ => return Err(DataError::parse_error(
-                &self.file_path.display().to_string(),
+                self.file_path.display().to_string(),
                 self.current_line,
                 format!("Unknown order type: {}", order_type_str)
             ))
@@ -620,7 +1234,7 @@ impl CsvDataSource {
     fn parse_cancel_record(&self, record: &StringRecord) -> DataResult<MarketEvent> {
         if record.len() < 3 {
             return Err(DataError::parse_error(
-                &self.file_path.display().to_string(),
+                self.file_path.display().to_string(),
                 self.current_line,
                 "Cancel record requires at least 3 columns: type,timestamp,order_id"
             ));
@@ -641,7 +1255,7 @@ impl CsvDataSource {
     fn parse_modify_record(&self, record: &StringRecord) -> DataResult<MarketEvent> {
         if record.len() < 5 {
             return Err(DataError::parse_error(
-                &self.file_path.display().to_string(),
+                self.file_path.display().to_string(),
                 self.current_line,
                 "Modify record requires 5 columns: type,timestamp,order_id,new_qty,new_price"
             ));
@@ -664,7 +1278,7 @@ impl CsvDataSource {
     fn parse_status_record(&self, record: &StringRecord) -> DataResult<MarketEvent> {
         if record.len() < 3 {
             return Err(DataError::parse_error(
-                &self.file_path.display().to_string(),
+                self.file_path.display().to_string(),
                 self.current_line,
                 "Status record requires at least 3 columns: type,timestamp,status"
             ));
@@ -682,7 +1296,7 @@ impl CsvDataSource {
             "afterhours" => MarketStatusType::AfterHours,
             "auction" => MarketStatusType::Auction,
             _ => return Err(DataError::parse_error(
-                &self.file_path.display().to_string(),
+                self.file_path.display().to_string(),
                 self.current_line,
                 format!("Unknown market status: {}", status_str)
             ))
@@ -699,7 +1313,7 @@ impl CsvDataSource {
     fn parse_bbo_record(&self, record: &StringRecord) -> DataResult<MarketEvent> {
         if record.len() < 6 {
             return Err(DataError::parse_error(
-                &self.file_path.display().to_string(),
+                self.file_path.display().to_string(),
                 self.current_line,
                 "BBO record requires 6 columns: type,timestamp,best_bid,best_ask,bid_qty,ask_qty"
             ));
@@ -724,7 +1338,7 @@ impl CsvDataSource {
     fn parse_timestamp(&self, s: &str) -> DataResult<u128> {
         s.parse::<u128>().map_err(|_| {
             DataError::parse_error(
-                &self.file_path.display().to_string(),
+                self.file_path.display().to_string(),
                 self.current_line,
                 format!("Invalid timestamp: {}", s)
             )
@@ -735,7 +1349,7 @@ impl CsvDataSource {
     fn parse_price(&self, s: &str) -> DataResult<Price> {
         if s.is_empty() {
             return Err(DataError::parse_error(
-                &self.file_path.display().to_string(),
+                self.file_path.display().to_string(),
                 self.current_line,
                 "Empty price field"
             ));
@@ -745,7 +1359,7 @@ impl CsvDataSource {
             .map(price_utils::from_f64)
             .map_err(|_| {
                 DataError::parse_error(
-                    &self.file_path.display().to_string(),
+                    self.file_path.display().to_string(),
                     self.current_line,
                     format!("Invalid price: {}", s)
                 )
@@ -765,7 +1379,7 @@ impl CsvDataSource {
     fn parse_qty(&self, s: &str) -> DataResult<Qty> {
         s.parse::<Qty>().map_err(|_| {
             DataError::parse_error(
-                &self.file_path.display().to_string(),
+                self.file_path.display().to_string(),
                 self.current_line,
                 format!("Invalid quantity: {}", s)
             )
@@ -787,7 +1401,7 @@ impl CsvDataSource {
             "buy" | "b" => Ok(Side::Buy),
             "sell" | "s" => Ok(Side::Sell),
             _ => Err(DataError::parse_error(
-                &self.file_path.display().to_string(),
+                self.file_path.display().to_string(),
                 self.current_line,
                 format!("Invalid side: {}", s)
             ))
@@ -798,7 +1412,7 @@ impl CsvDataSource {
     fn parse_order_id(&self, s: &str) -> DataResult<OrderId> {
         s.parse::<OrderId>().map_err(|_| {
             DataError::parse_error(
-                &self.file_path.display().to_string(),
+                self.file_path.display().to_string(),
                 self.current_line,
                 format!("Invalid order ID: {}", s)
             )
@@ -838,51 +1452,81 @@ impl CsvDataSource {
     }
 }
 
+/// Re-encode a CSV record as a line of the file, for quarantining
+fn csv_record_line(record: &StringRecord) -> Vec<u8> {
+    let mut writer = csv::WriterBuilder::new().flexible(true).from_writer(Vec::new());
+    match writer.write_record(record) {
+        Ok(()) => writer.into_inner().unwrap_or_default(),
+        Err(_) => Vec::new(),
+    }
+}
+
 impl DataSource for CsvDataSource {
     fn next_event(&mut self) -> DataResult<Option<MarketEvent>> {
         let start_time = Instant::now();
-        let events_processed;
         let mut errors_encountered = 0;
         
         if self.finished {
             return Ok(None);
         }
 
-        // Read next record
-        if !self.reader.read_record(&mut self.record_buffer)? {
-            self.finished = true;
-            return Ok(None);
-        }
+        let mut events_processed = 0;
+        let event = loop {
+            // Read next record
+            let position = self.reader.position().clone();
+            if !self.reader.read_record(&mut self.record_buffer)? {
+                self.finished = true;
+                return Ok(None);
+            }
 
-        self.current_line += 1;
-        events_processed = 1;
+            self.current_line += 1;
+            events_processed += 1;
+
+            // Parse and validate the record
+            self.unsigned_trade.store(false, std::sync::atomic::Ordering::Relaxed);
+            let parsed = self
+                .parse_symbol_record(&self.record_buffer)
+                .and_then(|(symbol, event)| event.validate().map(|_| (symbol, event)));
+            let (symbol, mut event) = match parsed {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    errors_encountered += 1;
+                    let raw = csv_record_line(&self.record_buffer);
+                    let handled = self.errors.handle(
+                        e,
+                        self.current_line as u64,
+                        &raw,
+                        &mut self.metadata,
+                        self.perf_metrics.as_deref(),
+                    );
+
+                    if let Err(e) = handled {
+                        // Record performance metrics
+                        if let Some(ref perf_metrics) = self.perf_metrics {
+                            perf_metrics.record_data_ingestion(start_time.elapsed(), events_processed, errors_encountered);
+                        }
 
-        // Parse the record
-        let event = match self.parse_record(&self.record_buffer) {
-            Ok(event) => event,
-            Err(e) => {
-                errors_encountered = 1;
-                
-                // Record performance metrics
-                if let Some(ref perf_metrics) = self.perf_metrics {
-                    perf_metrics.record_data_ingestion(start_time.elapsed(), events_processed, errors_encountered);
+                        return Err(e);
+                    }
+                    continue;
                 }
-                
-                return Err(e);
+            };
+
+            self.signer.observe(symbol.as_deref(), &mut event, self.unsigned_trade.load(std::sync::atomic::Ordering::Relaxed), &mut self.metadata);
+            self.seek_index.record(event.timestamp(), position.record(), || position);
+            match window_cmp(self.time_window, event.timestamp()) {
+                std::cmp::Ordering::Less => continue,
+                std::cmp::Ordering::Greater => {
+                    self.finished = true;
+                    return Ok(None);
+                }
+                std::cmp::Ordering::Equal => {}
             }
-        };
-        
-        // Validate the event
-        if let Err(e) = event.validate() {
-            errors_encountered = 1;
-            
-            // Record performance metrics
-            if let Some(ref perf_metrics) = self.perf_metrics {
-                perf_metrics.record_data_ingestion(start_time.elapsed(), events_processed, errors_encountered);
+
+            if self.symbols.observe(symbol, &mut self.metadata) {
+                break event;
             }
-            
-            return Err(e);
-        }
+        };
 
         // Update current position
         self.current_position = Some(event.timestamp());
@@ -908,8 +1552,15 @@ impl DataSource for CsvDataSource {
     }
 
     fn seek_to_time(&mut self, timestamp: u128) -> DataResult<()> {
-        // Reset to beginning and scan for the target timestamp
-        self.reset()?;
+        let timestamp = self.time_window.map_or(timestamp, |(start, _)| timestamp.max(start));
+        self.index_through(timestamp)?;
+        self.rewind()?;
+
+        // Jump to the closest indexed record, then scan for the target timestamp
+        if let Some((_, position)) = self.seek_index.checkpoint_before(timestamp) {
+            self.current_line = position.line() as usize - 1;
+            self.reader.seek(position)?;
+        }
         
         loop {
             let position = self.reader.position().clone();
@@ -922,7 +1573,8 @@ impl DataSource for CsvDataSource {
             self.current_line += 1;
             
             // Parse just to get the timestamp
-            if let Ok(event) = self.parse_record(&self.record_buffer) {
+            if let Ok((_, event)) = self.parse_symbol_record(&self.record_buffer) {
+                self.seek_index.record(event.timestamp(), position.record(), || position.clone());
                 if event.timestamp() >= timestamp {
                     // Found target, seek back to this position
                     self.reader.seek(position)?;
@@ -959,22 +1611,13 @@ impl DataSource for CsvDataSource {
     }
 
     fn reset(&mut self) -> DataResult<()> {
-        // Reopen the file and create a new reader
-        let file = File::open(&self.file_path)
-            .map_err(|_| DataError::file_not_found(self.file_path.display().to_string()))?;
-        
-        self.reader = csv::ReaderBuilder::new()
-            .has_headers(true)
-            .flexible(true) // Allow records with different numbers of fields
-            .from_reader(file);
-        
-        self.current_line = 1;
-        self.finished = false;
-        self.last_timestamp = None;
-        self.playback_start = None;
-        self.current_position = None;
-        
-        Ok(())
+        match self.time_window {
+            Some((start, _)) => {
+                self.finished = !seek_to_window_start(self, start)?;
+                Ok(())
+            }
+            None => self.rewind(),
+        }
     }
 
     fn metadata(&self) -> DataSourceMetadata {
@@ -993,6 +1636,30 @@ impl DataSource for CsvDataSource {
     fn is_paused(&self) -> bool {
         self.paused
     }
+
+    fn last_symbol(&self) -> Option<&str> {
+        self.symbols.last_symbol.as_deref()
+    }
+
+    fn last_line(&self) -> Option<usize> {
+        Some(self.current_line).filter(|&line| line > 0)
+    }
+
+    fn filter_symbols(&mut self, symbols: Vec<String>) -> DataResult<()> {
+        self.symbols.set_filter(symbols);
+        Ok(())
+    }
+
+    fn set_time_window(&mut self, start: u128, end: u128) -> DataResult<()> {
+        validate_time_window(start, end)?;
+        self.time_window = Some((start, end));
+        self.reset()
+    }
+
+    fn set_error_policy(&mut self, policy: ErrorPolicy) -> DataResult<()> {
+        self.errors.set_policy(policy);
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -1188,6 +1855,38 @@ mod tests {
         assert!(csv_source.is_finished());
     }
 
+    #[test]
+    fn test_csv_classifies_unsigned_trades() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "type,timestamp,price,qty,side,trade_id").unwrap();
+        writeln!(temp_file, "trade,1000000000,100.00,10,,").unwrap();
+        writeln!(temp_file, "quote,1000000001,99.98,100.02,100,100").unwrap();
+        writeln!(temp_file, "trade,1000000002,100.02,10,,").unwrap();
+        writeln!(temp_file, "trade,1000000003,99.99,10,unknown,").unwrap();
+        writeln!(temp_file, "trade,1000000004,100.00,10,sell,").unwrap();
+        temp_file.flush().unwrap();
+
+        let mut csv_source = CsvDataSource::new(temp_file.path()).unwrap();
+        let mut sides = Vec::new();
+        while let Some(event) = csv_source.next_event().unwrap() {
+            if let MarketEvent::Trade { side, .. } = event {
+                sides.push(side);
+            }
+        }
+        // The first print has nothing to compare against and keeps the default
+        assert_eq!(sides, vec![Side::Buy, Side::Buy, Side::Sell, Side::Sell]);
+
+        let metadata = csv_source.metadata();
+        assert_eq!(metadata.properties.get("classified_trades").map(String::as_str), Some("2"));
+        assert_eq!(metadata.properties.get("unclassified_trades").map(String::as_str), Some("1"));
+
+        csv_source.reset().unwrap();
+        assert!(!csv_source.metadata().properties.contains_key("classified_trades"));
+    }
+
     #[test]
     fn test_csv_parsing_quote_record() {
         use std::io::Write;
@@ -1379,17 +2078,75 @@ mod tests {
     }
 
     #[test]
-    fn test_csv_playback_speed() {
+    fn test_csv_skip_policy_counts_bad_records() {
         use std::io::Write;
         use tempfile::NamedTempFile;
 
         let mut temp_file = NamedTempFile::new().unwrap();
         writeln!(temp_file, "type,timestamp,price,qty,side").unwrap();
         writeln!(temp_file, "trade,1000000000,100.25,500,buy").unwrap();
+        writeln!(temp_file, "trade,invalid_timestamp,100.25,500,buy").unwrap();
+        writeln!(temp_file, "trade,1000000200,100.25,0,buy").unwrap();
+        writeln!(temp_file, "trade,1000000300,100.30,200,sell").unwrap();
         temp_file.flush().unwrap();
 
-        let mut csv_source = CsvDataSource::new(temp_file.path()).unwrap();
-        
+        let perf_metrics = Arc::new(PerformanceMetrics::new());
+        let mut csv_source = CsvDataSource::new(temp_file.path())
+            .unwrap()
+            .with_performance_monitoring(perf_metrics.clone());
+        csv_source.set_paused(true).unwrap();
+        csv_source.set_error_policy(ErrorPolicy::Skip).unwrap();
+
+        let mut timestamps = Vec::new();
+        while let Some(event) = csv_source.next_event().unwrap() {
+            timestamps.push(event.timestamp());
+        }
+        assert_eq!(timestamps, vec![1000000000, 1000000300]);
+        assert_eq!(csv_source.metadata().skipped_records, 2);
+        assert_eq!(perf_metrics.get_snapshot().records_skipped, 2);
+
+        // Rereading the file does not count the same records again
+        csv_source.reset().unwrap();
+        while csv_source.next_event().unwrap().is_some() {}
+        assert_eq!(csv_source.metadata().skipped_records, 2);
+        assert!(!ErrorPolicy::quarantine_path(temp_file.path()).exists());
+    }
+
+    #[test]
+    fn test_csv_quarantine_policy_writes_sidecar() {
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trades.csv");
+        let mut file = File::create(&path).unwrap();
+        writeln!(file, "type,timestamp,price,qty,side").unwrap();
+        writeln!(file, "trade,invalid_timestamp,100.25,500,buy").unwrap();
+        writeln!(file, "trade,1000000100,100.25,500,buy").unwrap();
+        drop(file);
+
+        let mut csv_source = CsvDataSource::new(&path).unwrap();
+        csv_source.set_paused(true).unwrap();
+        csv_source.set_error_policy(ErrorPolicy::Quarantine).unwrap();
+
+        assert_eq!(csv_source.next_event().unwrap().unwrap().timestamp(), 1000000100);
+        assert_eq!(csv_source.metadata().quarantined_records, 1);
+
+        let sidecar = std::fs::read_to_string(dir.path().join("trades.csv.rejected")).unwrap();
+        assert_eq!(sidecar, "trade,invalid_timestamp,100.25,500,buy\n");
+    }
+
+    #[test]
+    fn test_csv_playback_speed() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "type,timestamp,price,qty,side").unwrap();
+        writeln!(temp_file, "trade,1000000000,100.25,500,buy").unwrap();
+        temp_file.flush().unwrap();
+
+        let mut csv_source = CsvDataSource::new(temp_file.path()).unwrap();
+        
         // Test setting playback speed
         assert!(csv_source.set_playback_speed(2.0).is_ok());
         assert!(csv_source.set_playback_speed(0.5).is_ok());
@@ -1461,6 +2218,127 @@ mod tests {
         assert!(metadata.file_size.unwrap() > 0);
     }
 
+    #[test]
+    fn test_csv_symbol_column_and_filter() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "type,symbol,timestamp,price,qty,side").unwrap();
+        writeln!(temp_file, "trade,AAPL,1000000000,100.25,500,buy").unwrap();
+        writeln!(temp_file, "trade,MSFT,1000000100,300.10,200,sell").unwrap();
+        writeln!(temp_file, "trade,AAPL,1000000200,100.30,100,buy").unwrap();
+        temp_file.flush().unwrap();
+
+        let mut csv_source = CsvDataSource::new(temp_file.path()).unwrap();
+        csv_source.set_paused(true).unwrap();
+        csv_source.filter_symbols(vec!["MSFT".to_string()]).unwrap();
+
+        let event = csv_source.next_event().unwrap().unwrap();
+        match event {
+            MarketEvent::Trade { price, .. } => assert_eq!(price, 3001000),
+            _ => panic!("Expected Trade event"),
+        }
+        assert_eq!(csv_source.last_symbol(), Some("MSFT"));
+        assert!(csv_source.next_event().unwrap().is_none());
+
+        // Counts cover every parsed event, not just the filtered ones
+        let counts = csv_source.metadata().symbol_counts;
+        assert_eq!(counts.get("AAPL"), Some(&2));
+        assert_eq!(counts.get("MSFT"), Some(&1));
+
+        // An empty filter lets everything through again
+        csv_source.filter_symbols(Vec::new()).unwrap();
+        csv_source.reset().unwrap();
+        let mut count = 0;
+        while csv_source.next_event().unwrap().is_some() {
+            count += 1;
+        }
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn test_csv_time_window_uses_seek_index() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "type,timestamp,price,qty,side").unwrap();
+        for i in 0..3000u128 {
+            writeln!(temp_file, "trade,{},100.25,10,buy", 1000000000 + i * 1000).unwrap();
+        }
+        temp_file.flush().unwrap();
+
+        let mut csv_source = CsvDataSource::new(temp_file.path())
+            .unwrap()
+            .with_time_window(1000000000 + 2500 * 1000, 1000000000 + 2599 * 1000)
+            .unwrap();
+        csv_source.set_paused(true).unwrap();
+        assert!(!csv_source.seek_index.checkpoints.is_empty());
+
+        let mut timestamps = Vec::new();
+        while let Some(event) = csv_source.next_event().unwrap() {
+            timestamps.push(event.timestamp());
+        }
+        assert_eq!(timestamps.len(), 100);
+        assert_eq!(timestamps[0], 1000000000 + 2500 * 1000);
+        assert!(csv_source.is_finished());
+
+        // Reset returns to the window start, jumping through the index
+        csv_source.reset().unwrap();
+        assert_eq!(csv_source.next_event().unwrap().unwrap().timestamp(), 1000000000 + 2500 * 1000);
+
+        // Seeks are clamped to the window
+        csv_source.seek_to_time(0).unwrap();
+        assert_eq!(csv_source.next_event().unwrap().unwrap().timestamp(), 1000000000 + 2500 * 1000);
+        csv_source.seek_to_time(1000000000 + 2550 * 1000).unwrap();
+        assert_eq!(csv_source.next_event().unwrap().unwrap().timestamp(), 1000000000 + 2550 * 1000);
+
+        assert!(csv_source.set_time_window(10, 5).is_err());
+    }
+
+    #[test]
+    fn test_seeks_jump_through_the_index_instead_of_parsing_from_the_start() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        // Early records carry a late timestamp, so a seek that parsed from
+        // the start would stop on one of them
+        let timestamp = |i: u128| if (10..1000).contains(&i) { 9000000000 } else { 1000000000 + i * 1000 };
+        let target = 1000000000 + 2500 * 1000;
+
+        let mut csv_file = NamedTempFile::new().unwrap();
+        writeln!(csv_file, "type,timestamp,price,qty,side").unwrap();
+        let mut json_file = NamedTempFile::new().unwrap();
+        for i in 0..3000u128 {
+            writeln!(csv_file, "trade,{},100.25,10,buy", timestamp(i)).unwrap();
+            writeln!(json_file, r#"{{"Trade": {{"price": 10025, "qty": 10, "side": "Buy", "timestamp": {}, "trade_id": null}}}}"#, timestamp(i)).unwrap();
+        }
+        csv_file.flush().unwrap();
+        json_file.flush().unwrap();
+
+        let mut csv_source = CsvDataSource::new(csv_file.path()).unwrap();
+        csv_source.set_paused(true).unwrap();
+        csv_source.seek_to_time(target).unwrap();
+        assert_eq!(csv_source.next_event().unwrap().unwrap().timestamp(), target);
+        assert_eq!(csv_source.last_line(), Some(2502));
+        assert!(csv_source.seek_index.complete);
+
+        // The index stops at the first checkpoint past the target and
+        // resumes from there on a later seek
+        let mut json_source = JsonDataSource::new(json_file.path()).unwrap();
+        json_source.set_paused(true).unwrap();
+        json_source.seek_to_time(1000000000 + 1500 * 1000).unwrap();
+        assert_eq!(json_source.next_event().unwrap().unwrap().timestamp(), 1000000000 + 1500 * 1000);
+        assert_eq!(json_source.last_line(), Some(1501));
+        assert!(!json_source.seek_index.complete);
+
+        json_source.seek_to_time(target).unwrap();
+        assert_eq!(json_source.next_event().unwrap().unwrap().timestamp(), target);
+        assert_eq!(json_source.last_line(), Some(2501));
+        assert!(json_source.seek_index.complete);
+    }
+
     #[test]
     fn test_csv_integration_with_sample_file() {
         // Test with the sample CSV file if it exists
@@ -1543,6 +2421,20 @@ pub struct JsonDataSource {
     finished: bool,
     /// Buffer for reading lines
     line_buffer: String,
+    /// Symbol counting and filtering
+    symbols: SymbolTracker,
+    /// Byte offset of the next line to read
+    byte_offset: u64,
+    /// (byte offset, preceding line number) of lines by timestamp for seeking
+    seek_index: SeekIndex<(u64, usize)>,
+    /// What to do with records that fail to parse or validate
+    errors: RecordErrorHandler,
+    /// Inclusive time window to play back, if restricted
+    time_window: Option<(u128, u128)>,
+    /// Performance metrics (optional)
+    perf_metrics: Option<Arc<PerformanceMetrics>>,
+    /// Whether the file is deleted when the source is dropped
+    remove_on_drop: bool,
 }
 
 impl JsonDataSource {
@@ -1558,7 +2450,8 @@ impl JsonDataSource {
         let metadata = DataSourceMetadata::new(
             path.file_name().unwrap_or_default().to_string_lossy(),
             "JSON"
-        ).with_file_size(file_size);
+        ).with_file_size(file_size).with_path(&path);
+        let errors = RecordErrorHandler::new(&path);
 
         Ok(Self {
             reader,
@@ -1572,22 +2465,143 @@ impl JsonDataSource {
             metadata,
             finished: false,
             line_buffer: String::new(),
+            symbols: SymbolTracker::default(),
+            byte_offset: 0,
+            seek_index: SeekIndex::new(),
+            errors,
+            time_window: None,
+            perf_metrics: None,
+            remove_on_drop: false,
         })
     }
 
-    /// Parse a JSON line into a MarketEvent
-    fn parse_json_line(&self, line: &str) -> DataResult<MarketEvent> {
-        let event: MarketEvent = serde_json::from_str(line.trim()).map_err(|e| {
+    /// Set performance metrics for monitoring
+    pub fn with_performance_monitoring(mut self, perf_metrics: Arc<PerformanceMetrics>) -> Self {
+        self.perf_metrics = Some(perf_metrics);
+        self
+    }
+
+    /// Only play back events between `start` and `end` (inclusive)
+    pub fn with_time_window(mut self, start: u128, end: u128) -> DataResult<Self> {
+        self.set_time_window(start, end)?;
+        Ok(self)
+    }
+
+    /// Delete the file when the source is dropped, for temporary files such
+    /// as the output of an [`ExternalSorter`]
+    pub fn removing_file_on_drop(mut self) -> Self {
+        self.remove_on_drop = true;
+        self
+    }
+
+    /// Write a JSON Lines data file from a collection of events
+    pub fn write_json_file<P: AsRef<Path>>(file_path: P, events: &[MarketEvent]) -> DataResult<()> {
+        use std::io::Write;
+
+        let mut writer = std::io::BufWriter::new(File::create(file_path.as_ref())?);
+        for event in events {
+            write_json_record(&mut writer, None, event)?;
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reopen the file at its first line, ignoring any time window
+    fn rewind(&mut self) -> DataResult<()> {
+        let file = File::open(&self.file_path)?;
+        self.reader = std::io::BufReader::new(file);
+        self.current_line = 0;
+        self.byte_offset = 0;
+        self.finished = false;
+        self.last_timestamp = None;
+        self.playback_start = None;
+        self.current_position = None;
+        self.symbols.reset(&mut self.metadata);
+        Ok(())
+    }
+
+    /// Read the next line into the buffer, returning its starting byte offset,
+    /// or `None` at the end of the file
+    fn read_next_line(&mut self) -> DataResult<Option<u64>> {
+        self.line_buffer.clear();
+        let offset = self.byte_offset;
+        let read = self.reader.read_line(&mut self.line_buffer).map_err(|e| DataError::IoError {
+            message: format!("Failed to read line: {}", e),
+        })?;
+        if read == 0 {
+            return Ok(None);
+        }
+
+        self.byte_offset += read as u64;
+        self.current_line += 1;
+        Ok(Some(offset))
+    }
+
+    /// Extend the seek index until it reaches `timestamp` or the end of the
+    /// file, parsing only the lines that become checkpoints
+    fn index_through(&mut self, timestamp: u128) -> DataResult<()> {
+        use std::io::{Seek, SeekFrom};
+
+        if self.seek_index.reaches(timestamp) {
+            return Ok(());
+        }
+        self.rewind()?;
+        if let Some((_, (offset, line))) = self.seek_index.last() {
+            self.reader.seek(SeekFrom::Start(offset))?;
+            self.byte_offset = offset;
+            self.current_line = line;
+        }
+
+        while let Some(offset) = self.read_next_line()? {
+            let line = self.current_line;
+            if !(line as u64).is_multiple_of(SEEK_INDEX_INTERVAL) || self.line_buffer.trim().is_empty() {
+                continue;
+            }
+            if let Ok((_, event)) = self.parse_json_line(&self.line_buffer) {
+                self.seek_index.record(event.timestamp(), line as u64, || (offset, line - 1));
+                if event.timestamp() >= timestamp {
+                    return Ok(());
+                }
+            }
+        }
+        self.seek_index.complete = true;
+        Ok(())
+    }
+
+    /// Parse a JSON line into a MarketEvent and its optional top-level
+    /// `"symbol"` field, e.g. `{"symbol": "AAPL", "Trade": {...}}`
+    fn parse_json_line(&self, line: &str) -> DataResult<(Option<String>, MarketEvent)> {
+        let parse_error = |e: serde_json::Error| {
             DataError::parse_error(
-                &self.file_path.display().to_string(),
+                self.file_path.display().to_string(),
                 self.current_line,
                 format!("JSON parse error: {}", e)
             )
-        })?;
+        };
+
+        // Only lines that mention a symbol pay for the intermediate Value
+        let (symbol, event) = if line.contains("\"symbol\"") {
+            let mut value: serde_json::Value = serde_json::from_str(line.trim()).map_err(parse_error)?;
+            let symbol = match value.as_object_mut().and_then(|object| object.remove("symbol")) {
+                Some(serde_json::Value::String(symbol)) => Some(symbol),
+                Some(serde_json::Value::Null) | None => None,
+                Some(other) => {
+                    return Err(DataError::parse_error(
+                        self.file_path.display().to_string(),
+                        self.current_line,
+                        format!("Symbol must be a string, got {}", other)
+                    ));
+                }
+            };
+            (symbol, serde_json::from_value(value).map_err(parse_error)?)
+        } else {
+            (None, serde_json::from_str::<MarketEvent>(line.trim()).map_err(parse_error)?)
+        };
 
         // Validate the event
         event.validate()?;
-        Ok(event)
+        Ok((symbol, event))
     }
 
     /// Handle timing for playback speed control
@@ -1621,90 +2635,115 @@ impl JsonDataSource {
         self.last_timestamp = Some(event_timestamp);
         Ok(())
     }
-}
-
-impl DataSource for JsonDataSource {
-    fn next_event(&mut self) -> DataResult<Option<MarketEvent>> {
-        use std::io::BufRead;
 
+    /// Read the next event that passes the window and symbol filter
+    fn read_event(&mut self) -> DataResult<Option<MarketEvent>> {
         if self.finished {
             return Ok(None);
         }
 
-        self.line_buffer.clear();
-        
-        // Read next line
-        match self.reader.read_line(&mut self.line_buffer) {
-            Ok(0) => {
+        loop {
+            // Read next line
+            let Some(offset) = self.read_next_line()? else {
                 // End of file
                 self.finished = true;
                 return Ok(None);
+            };
+
+            // Skip empty lines
+            if self.line_buffer.trim().is_empty() {
+                continue;
             }
-            Ok(_) => {
-                self.current_line += 1;
-                
-                // Skip empty lines
-                if self.line_buffer.trim().is_empty() {
-                    return self.next_event(); // Recursively try next line
+
+            // Parse the JSON line
+            let (symbol, event) = match self.parse_json_line(&self.line_buffer) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    let mut raw = self.line_buffer.trim_end_matches(['\r', '\n']).as_bytes().to_vec();
+                    raw.push(b'\n');
+                    self.errors.handle(e, self.current_line as u64, &raw, &mut self.metadata, self.perf_metrics.as_deref())?;
+                    continue;
                 }
+            };
+            let line = self.current_line;
+            self.seek_index.record(event.timestamp(), line as u64, || (offset, line - 1));
+
+            // Skip events outside the time window or symbol filter
+            match window_cmp(self.time_window, event.timestamp()) {
+                std::cmp::Ordering::Less => continue,
+                std::cmp::Ordering::Greater => {
+                    self.finished = true;
+                    return Ok(None);
+                }
+                std::cmp::Ordering::Equal => {}
+            }
+            if !self.symbols.observe(symbol, &mut self.metadata) {
+                continue;
+            }
 
-                // Parse the JSON line
-                let event = self.parse_json_line(&self.line_buffer)?;
-                
-                // Update current position
-                self.current_position = Some(event.timestamp());
+            // Update current position
+            self.current_position = Some(event.timestamp());
 
-                // Handle timing for playback speed
-                self.handle_timing(event.timestamp())?;
+            // Handle timing for playback speed
+            self.handle_timing(event.timestamp())?;
+
+            return Ok(Some(event));
+        }
+    }
+}
 
-                Ok(Some(event))
+impl Drop for JsonDataSource {
+    fn drop(&mut self) {
+        if self.remove_on_drop {
+            if let Err(e) = std::fs::remove_file(&self.file_path) {
+                tracing::warn!("Failed to remove {}: {}", self.file_path.display(), e);
             }
-            Err(e) => Err(DataError::IoError {
-                message: format!("Failed to read line: {}", e),
-            }),
         }
     }
+}
+
+impl DataSource for JsonDataSource {
+    fn next_event(&mut self) -> DataResult<Option<MarketEvent>> {
+        let start_time = Instant::now();
+        let (line, dropped) = (self.current_line, self.metadata.dropped_records());
+        let result = self.read_event();
+        record_ingestion(self.perf_metrics.as_deref(), start_time, self.current_line - line, self.metadata.dropped_records() - dropped, &result);
+        result
+    }
 
     fn seek_to_time(&mut self, timestamp: u128) -> DataResult<()> {
-        // Reset to beginning and scan for the target timestamp
-        self.reset()?;
+        use std::io::{Seek, SeekFrom};
+
+        let timestamp = self.time_window.map_or(timestamp, |(start, _)| timestamp.max(start));
+        self.index_through(timestamp)?;
+        self.rewind()?;
+
+        // Jump to the closest indexed line, then scan for the target timestamp
+        if let Some((_, (offset, line))) = self.seek_index.checkpoint_before(timestamp) {
+            self.reader.seek(SeekFrom::Start(offset))?;
+            self.byte_offset = offset;
+            self.current_line = line;
+        }
         
-        loop {
-            self.line_buffer.clear();
-            
-            match self.reader.read_line(&mut self.line_buffer) {
-                Ok(0) => break, // End of file
-                Ok(_) => {
-                    self.current_line += 1;
-                    
-                    // Skip empty lines
-                    if self.line_buffer.trim().is_empty() {
-                        continue;
-                    }
+        while let Some(offset) = self.read_next_line()? {
+            // Skip empty lines
+            if self.line_buffer.trim().is_empty() {
+                continue;
+            }
 
-                    // Parse just to get the timestamp
-                    if let Ok(event) = self.parse_json_line(&self.line_buffer) {
-                        if event.timestamp() >= timestamp {
-                            // Found target - we can't seek back in a BufReader easily,
-                            // so we'll need to recreate the reader
-                            let file = File::open(&self.file_path)?;
-                            self.reader = std::io::BufReader::new(file);
-                            self.current_line = 0;
-                            
-                            // Read up to the target line again
-                            for _ in 0..(self.current_line - 1) {
-                                self.line_buffer.clear();
-                                self.reader.read_line(&mut self.line_buffer)?;
-                            }
-                            
-                            self.current_position = Some(event.timestamp());
-                            return Ok(());
-                        }
-                    }
+            // Parse just to get the timestamp
+            if let Ok((_, event)) = self.parse_json_line(&self.line_buffer) {
+                let line = self.current_line;
+                self.seek_index.record(event.timestamp(), line as u64, || (offset, line - 1));
+
+                if event.timestamp() >= timestamp {
+                    // Found target, seek back to the start of this line
+                    self.reader.seek(SeekFrom::Start(offset))?;
+                    self.byte_offset = offset;
+                    self.current_line = line - 1;
+                    self.current_position = Some(event.timestamp());
+                    return Ok(());
                 }
-                Err(e) => return Err(DataError::IoError {
-                    message: format!("Failed to read during seek: {}", e),
-                }),
             }
         }
 
@@ -1734,14 +2773,13 @@ impl DataSource for JsonDataSource {
     }
 
     fn reset(&mut self) -> DataResult<()> {
-        let file = File::open(&self.file_path)?;
-        self.reader = std::io::BufReader::new(file);
-        self.current_line = 0;
-        self.finished = false;
-        self.last_timestamp = None;
-        self.playback_start = None;
-        self.current_position = None;
-        Ok(())
+        match self.time_window {
+            Some((start, _)) => {
+                self.finished = !seek_to_window_start(self, start)?;
+                Ok(())
+            }
+            None => self.rewind(),
+        }
     }
 
     fn metadata(&self) -> DataSourceMetadata {
@@ -1756,6 +2794,30 @@ impl DataSource for JsonDataSource {
     fn is_paused(&self) -> bool {
         self.paused
     }
+
+    fn last_symbol(&self) -> Option<&str> {
+        self.symbols.last_symbol.as_deref()
+    }
+
+    fn last_line(&self) -> Option<usize> {
+        Some(self.current_line).filter(|&line| line > 0)
+    }
+
+    fn filter_symbols(&mut self, symbols: Vec<String>) -> DataResult<()> {
+        self.symbols.set_filter(symbols);
+        Ok(())
+    }
+
+    fn set_time_window(&mut self, start: u128, end: u128) -> DataResult<()> {
+        validate_time_window(start, end)?;
+        self.time_window = Some((start, end));
+        self.reset()
+    }
+
+    fn set_error_policy(&mut self, policy: ErrorPolicy) -> DataResult<()> {
+        self.errors.set_policy(policy);
+        Ok(())
+    }
 }
 
 /// Binary data format specification and header
@@ -1784,6 +2846,9 @@ impl BinaryDataHeader {
     /// Current version of the binary format
     pub const VERSION: u16 = 1;
 
+    /// Flag set when every record is a `(symbol, event)` pair
+    pub const FLAG_SYMBOLS: u16 = 0x0001;
+
     /// Create a new header with default values
     pub fn new() -> Self {
         Self {
@@ -1860,6 +2925,18 @@ pub struct BinaryDataSource {
     metadata: DataSourceMetadata,
     /// Whether we've reached the end of the file
     finished: bool,
+    /// Symbol counting and filtering
+    symbols: SymbolTracker,
+    /// Byte offset of the next record
+    next_offset: u64,
+    /// Record offsets by timestamp for seeking
+    seek_index: SeekIndex<u64>,
+    /// What to do with records that fail to parse or validate
+    errors: RecordErrorHandler,
+    /// Inclusive time window to play back, if restricted
+    time_window: Option<(u128, u128)>,
+    /// Performance metrics (optional)
+    perf_metrics: Option<Arc<PerformanceMetrics>>,
 }
 
 impl BinaryDataSource {
@@ -1879,10 +2956,12 @@ impl BinaryDataSource {
             "Binary"
         )
         .with_file_size(file_size)
+        .with_path(&path)
         .with_event_count(header.event_count as usize)
         .with_time_range(header.start_timestamp, header.end_timestamp);
 
         let start_timestamp = header.start_timestamp;
+        let errors = RecordErrorHandler::new(&path);
         
         Ok(Self {
             file,
@@ -1896,9 +2975,90 @@ impl BinaryDataSource {
             current_position: Some(start_timestamp),
             metadata,
             finished: false,
+            symbols: SymbolTracker::default(),
+            next_offset: BinaryDataHeader::size() as u64,
+            seek_index: SeekIndex::new(),
+            errors,
+            time_window: None,
+            perf_metrics: None,
         })
     }
 
+    /// Set performance metrics for monitoring
+    pub fn with_performance_monitoring(mut self, perf_metrics: Arc<PerformanceMetrics>) -> Self {
+        self.perf_metrics = Some(perf_metrics);
+        self
+    }
+
+    /// Only play back events between `start` and `end` (inclusive)
+    pub fn with_time_window(mut self, start: u128, end: u128) -> DataResult<Self> {
+        self.set_time_window(start, end)?;
+        Ok(self)
+    }
+
+    /// Move back to the first record, ignoring any time window
+    fn rewind(&mut self) -> DataResult<()> {
+        use std::io::{Seek, SeekFrom};
+
+        self.next_offset = BinaryDataHeader::size() as u64;
+        self.file.seek(SeekFrom::Start(self.next_offset))?;
+        self.current_event = 0;
+        self.finished = false;
+        self.last_timestamp = None;
+        self.playback_start = None;
+        self.current_position = Some(self.header.start_timestamp);
+        self.symbols.reset(&mut self.metadata);
+        Ok(())
+    }
+
+    /// Position the reader at a record whose index and offset are known
+    fn seek_to_record(&mut self, event_index: u64, offset: u64) -> DataResult<()> {
+        use std::io::{Seek, SeekFrom};
+
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.next_offset = offset;
+        self.current_event = event_index;
+        Ok(())
+    }
+
+    /// Extend the seek index until it reaches `timestamp` or the end of the
+    /// file, decoding only the records that become checkpoints and skipping
+    /// the rest by their length prefix
+    fn index_through(&mut self, timestamp: u128) -> DataResult<()> {
+        use std::io::{Read, Seek, SeekFrom};
+        use byteorder::{LittleEndian, ReadBytesExt};
+
+        if self.seek_index.reaches(timestamp) {
+            return Ok(());
+        }
+        match self.seek_index.last() {
+            Some((event_index, offset)) => self.seek_to_record(event_index, offset)?,
+            None => self.rewind()?,
+        }
+
+        while self.current_event < self.header.event_count {
+            let (event_index, offset) = (self.current_event, self.next_offset);
+            let event_length = self.file.read_u32::<LittleEndian>()?;
+            self.next_offset += 4 + event_length as u64;
+            self.current_event += 1;
+            if !event_index.is_multiple_of(SEEK_INDEX_INTERVAL) {
+                self.file.seek(SeekFrom::Start(self.next_offset))?;
+                continue;
+            }
+
+            let mut event_data = vec![0u8; event_length as usize];
+            self.file.read_exact(&mut event_data)?;
+            if let Ok((_, event)) = self.decode_record(&event_data) {
+                self.seek_index.record(event.timestamp(), event_index, || offset);
+                if event.timestamp() >= timestamp {
+                    return Ok(());
+                }
+            }
+        }
+        self.seek_index.complete = true;
+        Ok(())
+    }
+
     /// Read the binary header from the file
     fn read_header(file: &mut File) -> DataResult<BinaryDataHeader> {
         use std::io::{Read, Seek, SeekFrom};
@@ -1927,229 +3087,2221 @@ impl BinaryDataSource {
         })
     }
 
-    /// Read the next event from the binary file
-    fn read_next_event(&mut self) -> DataResult<Option<MarketEvent>> {
+    /// Read the next event and its symbol from the binary file
+    fn read_next_event(&mut self) -> DataResult<Option<(Option<String>, MarketEvent)>> {
         use std::io::Read;
         use byteorder::{LittleEndian, ReadBytesExt};
 
-        if self.current_event >= self.header.event_count {
-            self.finished = true;
-            return Ok(None);
-        }
+        loop {
+            if self.current_event >= self.header.event_count {
+                self.finished = true;
+                return Ok(None);
+            }
 
-        // Read event length
-        let event_length = self.file.read_u32::<LittleEndian>()?;
-        
-        // Read event data
-        let mut event_data = vec![0u8; event_length as usize];
-        self.file.read_exact(&mut event_data)?;
+            // Read event length
+            let offset = self.next_offset;
+            let event_length = self.file.read_u32::<LittleEndian>()?;
+            self.next_offset += 4 + event_length as u64;
+
+            // Read event data
+            let mut event_data = vec![0u8; event_length as usize];
+            self.file.read_exact(&mut event_data)?;
+
+            // The length prefix frames each record, so a record that fails to
+            // decode can be dropped without losing our place in the file
+            let (symbol, event) = match self.decode_record(&event_data) {
+                Ok(decoded) => decoded,
+                Err(e) => {
+                    let ordinal = self.current_event;
+                    self.current_event += 1;
+
+                    let mut raw = event_length.to_le_bytes().to_vec();
+                    raw.extend_from_slice(&event_data);
+                    self.errors.handle(e, ordinal, &raw, &mut self.metadata, self.perf_metrics.as_deref())?;
+                    continue;
+                }
+            };
 
-        // Deserialize the event using bincode
-        let event: MarketEvent = bincode::deserialize(&event_data).map_err(|e| {
-            DataError::InvalidFormat {
-                file: self.file_path.display().to_string(),
-                details: format!("Failed to deserialize event {}: {}", self.current_event, e),
-            }
-        })?;
+            self.seek_index.record(event.timestamp(), self.current_event, || offset);
+            self.current_event += 1;
+            self.current_position = Some(event.timestamp());
+
+            return Ok(Some((symbol, event)));
+        }
+    }
+
+    /// Deserialize and validate one record's bytes
+    fn decode_record(&self, event_data: &[u8]) -> DataResult<(Option<String>, MarketEvent)> {
+        // Deserialize the event using bincode
+        let deserialize_error = |e: bincode::Error| DataError::InvalidFormat {
+            file: self.file_path.display().to_string(),
+            details: format!("Failed to deserialize event {}: {}", self.current_event, e),
+        };
+        let (symbol, event) = if self.header.flags & BinaryDataHeader::FLAG_SYMBOLS != 0 {
+            bincode::deserialize::<(Option<String>, MarketEvent)>(event_data).map_err(deserialize_error)?
+        } else {
+            (None, bincode::deserialize::<MarketEvent>(event_data).map_err(deserialize_error)?)
+        };
 
         // Validate the event
         event.validate()?;
+        Ok((symbol, event))
+    }
+
+    /// Handle timing for playback speed control
+    fn handle_timing(&mut self, event_timestamp: u128) -> DataResult<()> {
+        if self.paused {
+            return Ok(());
+        }
+
+        if let Some(last_ts) = self.last_timestamp {
+            if event_timestamp > last_ts {
+                let time_diff_ns = event_timestamp - last_ts;
+                let real_time_diff = Duration::from_nanos(time_diff_ns as u64);
+                let adjusted_diff = real_time_diff.div_f64(self.playback_speed);
+
+                if let Some(start_time) = self.playback_start {
+                    let elapsed = start_time.elapsed();
+                    let target_elapsed = Duration::from_nanos((event_timestamp - self.last_timestamp.unwrap_or(0)) as u64);
+                    
+                    if elapsed < target_elapsed {
+                        std::thread::sleep(target_elapsed - elapsed);
+                    }
+                } else {
+                    std::thread::sleep(adjusted_diff);
+                }
+            }
+        } else {
+            // First event, record start time
+            self.playback_start = Some(Instant::now());
+        }
+
+        self.last_timestamp = Some(event_timestamp);
+        Ok(())
+    }
+
+    /// Write a binary data file from a collection of events
+    pub fn write_binary_file<P: AsRef<Path>>(
+        file_path: P,
+        events: &[MarketEvent],
+    ) -> DataResult<()> {
+        Self::write_records(file_path.as_ref(), events, 0, |event| event.timestamp(), bincode::serialize)
+    }
+
+    /// Write a binary data file where each event carries an optional symbol
+    pub fn write_binary_file_with_symbols<P: AsRef<Path>>(
+        file_path: P,
+        events: &[(Option<String>, MarketEvent)],
+    ) -> DataResult<()> {
+        Self::write_records(
+            file_path.as_ref(),
+            events,
+            BinaryDataHeader::FLAG_SYMBOLS,
+            |(_, event)| event.timestamp(),
+            bincode::serialize,
+        )
+    }
+
+    fn write_records<T>(
+        path: &Path,
+        records: &[T],
+        flags: u16,
+        timestamp: impl Fn(&T) -> u128,
+        serialize: impl Fn(&T) -> bincode::Result<Vec<u8>>,
+    ) -> DataResult<()> {
+        use std::io::Write;
+        use byteorder::{LittleEndian, WriteBytesExt};
+
+        let mut file = File::create(path)?;
+
+        // Calculate header information
+        let start_timestamp = records.first().map(&timestamp).unwrap_or(0);
+        let end_timestamp = records.last().map(&timestamp).unwrap_or(0);
+
+        let mut header = BinaryDataHeader::new();
+        header.flags = flags;
+        header.event_count = records.len() as u64;
+        header.start_timestamp = start_timestamp;
+        header.end_timestamp = end_timestamp;
+
+        // Write header
+        Self::write_header(&mut file, &header)?;
+
+        // Write events
+        for record in records {
+            // Serialize event using bincode
+            let event_data = serialize(record).map_err(|e| {
+                DataError::InvalidFormat {
+                    file: path.display().to_string(),
+                    details: format!("Failed to serialize event: {}", e),
+                }
+            })?;
+
+            // Write event length and data
+            file.write_u32::<LittleEndian>(event_data.len() as u32)?;
+            file.write_all(&event_data)?;
+        }
+
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Write the binary header to a file
+    fn write_header(file: &mut File, header: &BinaryDataHeader) -> DataResult<()> {
+        use std::io::Write;
+        use byteorder::{LittleEndian, WriteBytesExt};
+
+        file.write_u32::<LittleEndian>(header.magic)?;
+        file.write_u16::<LittleEndian>(header.version)?;
+        file.write_u16::<LittleEndian>(header.flags)?;
+        file.write_u64::<LittleEndian>(header.event_count)?;
+        file.write_u128::<LittleEndian>(header.start_timestamp)?;
+        file.write_u128::<LittleEndian>(header.end_timestamp)?;
+        file.write_all(&header.reserved)?;
+
+        Ok(())
+    }
+
+    /// Read the next event that passes the window and symbol filter
+    fn read_event(&mut self) -> DataResult<Option<MarketEvent>> {
+        if self.finished {
+            return Ok(None);
+        }
+
+        // Skip events outside the time window or symbol filter
+        while let Some((symbol, event)) = self.read_next_event()? {
+            match window_cmp(self.time_window, event.timestamp()) {
+                std::cmp::Ordering::Less => continue,
+                std::cmp::Ordering::Greater => {
+                    self.finished = true;
+                    return Ok(None);
+                }
+                std::cmp::Ordering::Equal => {}
+            }
+
+            if self.symbols.observe(symbol, &mut self.metadata) {
+                // Handle timing for playback speed
+                self.handle_timing(event.timestamp())?;
+                return Ok(Some(event));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl DataSource for BinaryDataSource {
+    fn next_event(&mut self) -> DataResult<Option<MarketEvent>> {
+        let start_time = Instant::now();
+        let (record, dropped) = (self.current_event, self.metadata.dropped_records());
+        let result = self.read_event();
+        let records = (self.current_event - record) as usize;
+        record_ingestion(self.perf_metrics.as_deref(), start_time, records, self.metadata.dropped_records() - dropped, &result);
+        result
+    }
+
+    fn seek_to_time(&mut self, timestamp: u128) -> DataResult<()> {
+        let timestamp = self.time_window.map_or(timestamp, |(start, _)| timestamp.max(start));
+        self.index_through(timestamp)?;
+        self.rewind()?;
+
+        // Jump to the closest indexed record, then scan for the target timestamp
+        if let Some((event_index, offset)) = self.seek_index.checkpoint_before(timestamp) {
+            self.seek_to_record(event_index, offset)?;
+        }
+        
+        while self.current_event < self.header.event_count {
+            // Save before reading
+            let (event_index, offset) = (self.current_event, self.next_offset);
+            
+            if let Some((_, event)) = self.read_next_event()? {
+                if event.timestamp() >= timestamp {
+                    self.seek_to_record(event_index, offset)?;
+                    self.current_position = Some(event.timestamp());
+                    return Ok(());
+                }
+            }
+        }
+
+        Err(DataError::seek_failed(format!("Timestamp {} not found in data", timestamp)))
+    }
+
+    fn set_playback_speed(&mut self, multiplier: f64) -> DataResult<()> {
+        if multiplier <= 0.0 {
+            return Err(DataError::validation("Playback speed must be positive"));
+        }
+        self.playback_speed = multiplier;
+        Ok(())
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    fn current_position(&self) -> Option<u128> {
+        self.current_position
+    }
+
+    fn duration(&self) -> Option<(u128, u128)> {
+        let (start, end) = (self.header.start_timestamp, self.header.end_timestamp);
+        match self.time_window {
+            Some((window_start, window_end)) => Some((start.max(window_start), end.min(window_end))),
+            None => Some((start, end)),
+        }
+    }
+
+    fn reset(&mut self) -> DataResult<()> {
+        match self.time_window {
+            Some((start, _)) => {
+                self.finished = !seek_to_window_start(self, start)?;
+                Ok(())
+            }
+            None => self.rewind(),
+        }
+    }
+
+    fn metadata(&self) -> DataSourceMetadata {
+        self.metadata.clone()
+    }
+
+    fn set_paused(&mut self, paused: bool) -> DataResult<()> {
+        self.paused = paused;
+        Ok(())
+    }
+
+    fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    fn last_symbol(&self) -> Option<&str> {
+        self.symbols.last_symbol.as_deref()
+    }
+
+    fn filter_symbols(&mut self, symbols: Vec<String>) -> DataResult<()> {
+        self.symbols.set_filter(symbols);
+        Ok(())
+    }
+
+    fn set_time_window(&mut self, start: u128, end: u128) -> DataResult<()> {
+        validate_time_window(start, end)?;
+        self.time_window = Some((start, end));
+        self.reset()
+    }
+
+    fn set_error_policy(&mut self, policy: ErrorPolicy) -> DataResult<()> {
+        self.errors.set_policy(policy);
+        Ok(())
+    }
+}
+
+/// How a [`ThinningDataSource`] decides which quote updates to keep
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThinningPolicy {
+    /// Keep every Nth quote update
+    EveryNth(usize),
+    /// Keep at most this many quote updates per second of data time
+    MaxRate(f64),
+}
+
+impl ThinningPolicy {
+    fn validate(&self) -> DataResult<()> {
+        match *self {
+            Self::EveryNth(0) => Err(DataError::validation("Thinning interval must be at least 1")),
+            Self::MaxRate(rate) if !(rate > 0.0 && rate.is_finite()) => {
+                Err(DataError::validation("Maximum event rate must be positive"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl std::fmt::Display for ThinningPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EveryNth(n) => write!(f, "every {} quotes", n),
+            Self::MaxRate(rate) => write!(f, "max {} quotes/s", rate),
+        }
+    }
+}
+
+/// Wrapper that down-samples a dense data source for lightweight replays.
+/// Only quote and BBO updates are thinned; trades, order events and status
+/// changes always pass through so book reconstruction stays intact.
+pub struct ThinningDataSource {
+    /// Wrapped data source
+    inner: Box<dyn DataSource>,
+    /// Thinning policy
+    policy: ThinningPolicy,
+    /// Quote updates seen since the last reset
+    quotes_seen: u64,
+    /// Timestamp of the last quote update kept
+    last_kept_quote: Option<u128>,
+    /// Quote updates dropped since the last reset
+    dropped: u64,
+}
+
+impl ThinningDataSource {
+    /// Wrap a data source with the given thinning policy
+    pub fn new(inner: Box<dyn DataSource>, policy: ThinningPolicy) -> DataResult<Self> {
+        policy.validate()?;
+        Ok(Self {
+            inner,
+            policy,
+            quotes_seen: 0,
+            last_kept_quote: None,
+            dropped: 0,
+        })
+    }
+
+    /// Number of quote updates dropped since the last reset
+    pub fn dropped_events(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Unwrap the inner data source
+    pub fn into_inner(self) -> Box<dyn DataSource> {
+        self.inner
+    }
+
+    /// Decide whether to keep an event, updating the thinning state
+    fn keep(&mut self, event: &MarketEvent) -> bool {
+        if !matches!(event, MarketEvent::Quote { .. } | MarketEvent::BestBidOffer { .. }) {
+            return true;
+        }
+
+        let keep = match self.policy {
+            ThinningPolicy::EveryNth(n) => self.quotes_seen.is_multiple_of(n as u64),
+            ThinningPolicy::MaxRate(rate) => {
+                let min_interval_ns = (1_000_000_000.0 / rate) as u128;
+                self.last_kept_quote
+                    .is_none_or(|last| event.timestamp().saturating_sub(last) >= min_interval_ns)
+            }
+        };
+
+        self.quotes_seen += 1;
+        if keep {
+            self.last_kept_quote = Some(event.timestamp());
+        } else {
+            self.dropped += 1;
+        }
+        keep
+    }
+
+    fn reset_thinning(&mut self) {
+        self.quotes_seen = 0;
+        self.last_kept_quote = None;
+        self.dropped = 0;
+    }
+}
+
+impl DataSource for ThinningDataSource {
+    fn next_event(&mut self) -> DataResult<Option<MarketEvent>> {
+        while let Some(event) = self.inner.next_event()? {
+            if self.keep(&event) {
+                return Ok(Some(event));
+            }
+        }
+        Ok(None)
+    }
+
+    fn seek_to_time(&mut self, timestamp: u128) -> DataResult<()> {
+        self.reset_thinning();
+        self.inner.seek_to_time(timestamp)
+    }
+
+    fn set_playback_speed(&mut self, multiplier: f64) -> DataResult<()> {
+        self.inner.set_playback_speed(multiplier)
+    }
+
+    fn is_finished(&self) -> bool {
+        self.inner.is_finished()
+    }
+
+    fn current_position(&self) -> Option<u128> {
+        self.inner.current_position()
+    }
+
+    fn duration(&self) -> Option<(u128, u128)> {
+        self.inner.duration()
+    }
+
+    fn reset(&mut self) -> DataResult<()> {
+        self.reset_thinning();
+        self.inner.reset()
+    }
+
+    fn metadata(&self) -> DataSourceMetadata {
+        self.inner
+            .metadata()
+            .with_property("thinning", self.policy.to_string())
+            .with_property("dropped_events", self.dropped.to_string())
+    }
+
+    fn set_paused(&mut self, paused: bool) -> DataResult<()> {
+        self.inner.set_paused(paused)
+    }
+
+    fn is_paused(&self) -> bool {
+        self.inner.is_paused()
+    }
+
+    fn last_symbol(&self) -> Option<&str> {
+        self.inner.last_symbol()
+    }
+
+    fn last_line(&self) -> Option<usize> {
+        self.inner.last_line()
+    }
+
+    fn filter_symbols(&mut self, symbols: Vec<String>) -> DataResult<()> {
+        self.inner.filter_symbols(symbols)
+    }
+
+    fn set_time_window(&mut self, start: u128, end: u128) -> DataResult<()> {
+        self.reset_thinning();
+        self.inner.set_time_window(start, end)
+    }
+
+    fn set_error_policy(&mut self, policy: ErrorPolicy) -> DataResult<()> {
+        self.inner.set_error_policy(policy)
+    }
+}
+
+/// How a [`MonotonicDataSource`] enforces timestamp order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderingPolicy {
+    /// Report every event older than the one before it as an error
+    Strict,
+    /// Hold up to N events and release them in timestamp order. Events that
+    /// arrive more than N positions late are still reported as errors.
+    SortWindow(usize),
+}
+
+impl OrderingPolicy {
+    fn validate(&self) -> DataResult<()> {
+        match *self {
+            Self::SortWindow(0) => Err(DataError::validation("Sorting window must hold at least 1 event")),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl std::fmt::Display for OrderingPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Strict => write!(f, "strict"),
+            Self::SortWindow(size) => write!(f, "sort window of {} events", size),
+        }
+    }
+}
+
+/// An event waiting in a sorting window or merge, ordered so that a
+/// `BinaryHeap` pops the earliest timestamp first and breaks ties by `seq`
+#[derive(Debug)]
+struct PendingEvent {
+    timestamp: u128,
+    seq: u64,
+    symbol: Option<String>,
+    /// Line of the source file it was read from, if known
+    line: Option<usize>,
+    event: MarketEvent,
+}
+
+impl PartialEq for PendingEvent {
+    fn eq(&self, other: &Self) -> bool {
+        (self.timestamp, self.seq) == (other.timestamp, other.seq)
+    }
+}
+
+impl Eq for PendingEvent {}
+
+impl PartialOrd for PendingEvent {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PendingEvent {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (other.timestamp, other.seq).cmp(&(self.timestamp, self.seq))
+    }
+}
+
+/// Wrapper that guarantees the events it returns never go back in time.
+/// The engine's behavior with time-travel events is undefined, so anything
+/// that cannot be put in order is surfaced as `DataError::InvalidTimestamp`
+/// and dropped rather than passed through.
+pub struct MonotonicDataSource {
+    /// Wrapped data source
+    inner: Box<dyn DataSource>,
+    /// Ordering policy
+    policy: OrderingPolicy,
+    /// Events held back by a sorting window
+    window: std::collections::BinaryHeap<PendingEvent>,
+    /// Events read from the inner source since the last reset
+    events_read: u64,
+    /// Latest timestamp read from the inner source
+    latest_read: Option<u128>,
+    /// Timestamp of the last event returned
+    last_timestamp: Option<u128>,
+    /// Symbol of the last event returned
+    last_symbol: Option<String>,
+    /// Source line of the last event returned
+    last_line: Option<usize>,
+    /// Events that arrived after a later-stamped event
+    out_of_order: u64,
+    /// Out-of-order events that could not be repaired
+    rejected: u64,
+}
+
+impl MonotonicDataSource {
+    /// Wrap a data source with the given ordering policy
+    pub fn new(inner: Box<dyn DataSource>, policy: OrderingPolicy) -> DataResult<Self> {
+        policy.validate()?;
+        Ok(Self {
+            inner,
+            policy,
+            window: std::collections::BinaryHeap::new(),
+            events_read: 0,
+            latest_read: None,
+            last_timestamp: None,
+            last_symbol: None,
+            last_line: None,
+            out_of_order: 0,
+            rejected: 0,
+        })
+    }
+
+    /// Number of events read out of order since the last reset
+    pub fn out_of_order_events(&self) -> u64 {
+        self.out_of_order
+    }
+
+    /// Number of out-of-order events reported as errors since the last reset
+    pub fn rejected_events(&self) -> u64 {
+        self.rejected
+    }
+
+    /// Unwrap the inner data source
+    pub fn into_inner(self) -> Box<dyn DataSource> {
+        self.inner
+    }
+
+    /// Read the next event from the inner source, noting whether it is out of order
+    fn read(&mut self) -> DataResult<Option<PendingEvent>> {
+        let Some(event) = self.inner.next_event()? else {
+            return Ok(None);
+        };
+
+        let timestamp = event.timestamp();
+        if self.latest_read.is_some_and(|latest| timestamp < latest) {
+            self.out_of_order += 1;
+        }
+        self.latest_read = Some(self.latest_read.map_or(timestamp, |latest| latest.max(timestamp)));
+        self.events_read += 1;
+
+        Ok(Some(PendingEvent {
+            timestamp,
+            seq: self.events_read,
+            symbol: self.inner.last_symbol().map(str::to_string),
+            line: self.inner.last_line(),
+            event,
+        }))
+    }
+
+    /// Return an event unless it would move time backwards
+    fn emit(&mut self, pending: PendingEvent) -> DataResult<Option<MarketEvent>> {
+        if let Some(last) = self.last_timestamp.filter(|last| pending.timestamp < *last) {
+            self.rejected += 1;
+            return Err(DataError::invalid_timestamp(
+                pending.timestamp,
+                pending.line.unwrap_or(0),
+                format!("Event is earlier than the preceding event at {}", last),
+            ));
+        }
+
+        self.last_timestamp = Some(pending.timestamp);
+        self.last_symbol = pending.symbol;
+        self.last_line = pending.line;
+        Ok(Some(pending.event))
+    }
+
+    fn reset_ordering(&mut self) {
+        self.window.clear();
+        self.events_read = 0;
+        self.latest_read = None;
+        self.last_timestamp = None;
+        self.last_symbol = None;
+        self.last_line = None;
+        self.out_of_order = 0;
+        self.rejected = 0;
+    }
+}
+
+impl DataSource for MonotonicDataSource {
+    fn next_event(&mut self) -> DataResult<Option<MarketEvent>> {
+        match self.policy {
+            OrderingPolicy::Strict => match self.read()? {
+                Some(pending) => self.emit(pending),
+                None => Ok(None),
+            },
+            OrderingPolicy::SortWindow(size) => {
+                while self.window.len() < size {
+                    match self.read()? {
+                        Some(pending) => self.window.push(pending),
+                        None => break,
+                    }
+                }
+                match self.window.pop() {
+                    Some(pending) => self.emit(pending),
+                    None => Ok(None),
+                }
+            }
+        }
+    }
+
+    fn seek_to_time(&mut self, timestamp: u128) -> DataResult<()> {
+        self.reset_ordering();
+        self.inner.seek_to_time(timestamp)
+    }
+
+    fn set_playback_speed(&mut self, multiplier: f64) -> DataResult<()> {
+        self.inner.set_playback_speed(multiplier)
+    }
+
+    fn is_finished(&self) -> bool {
+        self.window.is_empty() && self.inner.is_finished()
+    }
+
+    fn current_position(&self) -> Option<u128> {
+        self.last_timestamp.or_else(|| self.inner.current_position())
+    }
+
+    fn duration(&self) -> Option<(u128, u128)> {
+        self.inner.duration()
+    }
+
+    fn reset(&mut self) -> DataResult<()> {
+        self.reset_ordering();
+        self.inner.reset()
+    }
+
+    fn metadata(&self) -> DataSourceMetadata {
+        self.inner
+            .metadata()
+            .with_property("ordering", self.policy.to_string())
+            .with_property("out_of_order_events", self.out_of_order.to_string())
+            .with_property("rejected_events", self.rejected.to_string())
+    }
+
+    fn set_paused(&mut self, paused: bool) -> DataResult<()> {
+        self.inner.set_paused(paused)
+    }
+
+    fn is_paused(&self) -> bool {
+        self.inner.is_paused()
+    }
+
+    fn last_symbol(&self) -> Option<&str> {
+        self.last_symbol.as_deref()
+    }
+
+    fn last_line(&self) -> Option<usize> {
+        self.last_line
+    }
+
+    fn filter_symbols(&mut self, symbols: Vec<String>) -> DataResult<()> {
+        self.inner.filter_symbols(symbols)
+    }
+
+    fn set_time_window(&mut self, start: u128, end: u128) -> DataResult<()> {
+        self.reset_ordering();
+        self.inner.set_time_window(start, end)
+    }
+
+    fn set_error_policy(&mut self, policy: ErrorPolicy) -> DataResult<()> {
+        self.inner.set_error_policy(policy)
+    }
+}
+
+/// Real-time pacing for wrappers that read their inner source unpaced
+#[derive(Debug, Clone)]
+struct Pacer {
+    paused: bool,
+    playback_speed: f64,
+    /// Latest timestamp waited for
+    last_timestamp: Option<u128>,
+}
+
+impl Pacer {
+    fn new(paused: bool) -> Self {
+        Self {
+            paused,
+            playback_speed: 1.0,
+            last_timestamp: None,
+        }
+    }
+
+    fn set_playback_speed(&mut self, multiplier: f64) -> DataResult<()> {
+        if multiplier <= 0.0 {
+            return Err(DataError::validation("Playback speed must be positive"));
+        }
+        self.playback_speed = multiplier;
+        Ok(())
+    }
+
+    /// Sleep until `timestamp` is due relative to the last one, then record it
+    fn wait_until(&mut self, timestamp: u128) {
+        if let Some(last) = self.last_timestamp.filter(|last| timestamp > *last) {
+            if !self.paused {
+                let gap = Duration::from_nanos((timestamp - last).min(u64::MAX as u128) as u64);
+                std::thread::sleep(gap.div_f64(self.playback_speed));
+            }
+        }
+        self.last_timestamp = Some(self.last_timestamp.map_or(timestamp, |last| last.max(timestamp)));
+    }
+
+    fn reset(&mut self) {
+        self.last_timestamp = None;
+    }
+}
+
+/// Wrapper that keeps a quiet feed visibly alive. Whenever the wrapped
+/// source goes longer than the heartbeat interval without an event, a
+/// [`MarketEvent::Heartbeat`] is returned in its place, so consumers can
+/// tell an idle market from a dead feed. The wrapper does the real-time
+/// pacing itself, so heartbeats go out while it waits for the next event.
+pub struct HeartbeatDataSource {
+    /// Wrapped data source, always read without pacing
+    inner: Box<dyn DataSource>,
+    /// Data time between heartbeats in a quiet period (nanoseconds)
+    interval_ns: u128,
+    /// Next event from the inner source, read ahead to find quiet periods
+    pending: Option<(MarketEvent, Option<String>)>,
+    /// Paces the events and heartbeats returned
+    pacer: Pacer,
+    /// Symbol of the last event returned
+    last_symbol: Option<String>,
+    /// Heartbeats returned since the last reset
+    heartbeats: u64,
+}
+
+impl HeartbeatDataSource {
+    /// Wrap a data source, sending a heartbeat after every `interval` of quiet
+    pub fn new(mut inner: Box<dyn DataSource>, interval: Duration) -> DataResult<Self> {
+        if interval.is_zero() {
+            return Err(DataError::validation("Heartbeat interval must be greater than 0"));
+        }
+        let paused = inner.is_paused();
+        inner.set_paused(true)?;
+        Ok(Self {
+            inner,
+            interval_ns: interval.as_nanos(),
+            pending: None,
+            pacer: Pacer::new(paused),
+            last_symbol: None,
+            heartbeats: 0,
+        })
+    }
+
+    /// Number of heartbeats sent since the last reset
+    pub fn heartbeats_sent(&self) -> u64 {
+        self.heartbeats
+    }
+
+    /// Unwrap the inner data source, restoring its pacing
+    pub fn into_inner(mut self) -> Box<dyn DataSource> {
+        let _ = self.inner.set_paused(self.pacer.paused);
+        let _ = self.inner.set_playback_speed(self.pacer.playback_speed);
+        self.inner
+    }
+
+    fn reset_heartbeats(&mut self) {
+        self.pending = None;
+        self.pacer.reset();
+        self.last_symbol = None;
+        self.heartbeats = 0;
+    }
+}
+
+impl DataSource for HeartbeatDataSource {
+    fn next_event(&mut self) -> DataResult<Option<MarketEvent>> {
+        let (event, symbol) = match self.pending.take() {
+            Some(pending) => pending,
+            None => match self.inner.next_event()? {
+                Some(event) => (event, self.inner.last_symbol().map(str::to_string)),
+                None => return Ok(None),
+            },
+        };
+
+        let timestamp = event.timestamp();
+        let heartbeat_due = self.pacer.last_timestamp
+            .map(|last| last + self.interval_ns)
+            .filter(|due| *due < timestamp);
+        if let Some(due) = heartbeat_due {
+            self.pending = Some((event, symbol));
+            self.pacer.wait_until(due);
+            self.last_symbol = None;
+            self.heartbeats += 1;
+            return Ok(Some(MarketEvent::Heartbeat { timestamp: due }));
+        }
+
+        self.pacer.wait_until(timestamp);
+        self.last_symbol = symbol;
+        Ok(Some(event))
+    }
+
+    fn seek_to_time(&mut self, timestamp: u128) -> DataResult<()> {
+        self.reset_heartbeats();
+        self.inner.seek_to_time(timestamp)
+    }
+
+    fn set_playback_speed(&mut self, multiplier: f64) -> DataResult<()> {
+        self.pacer.set_playback_speed(multiplier)
+    }
+
+    fn is_finished(&self) -> bool {
+        self.pending.is_none() && self.inner.is_finished()
+    }
+
+    fn current_position(&self) -> Option<u128> {
+        self.pacer.last_timestamp.or_else(|| self.inner.current_position())
+    }
+
+    fn duration(&self) -> Option<(u128, u128)> {
+        self.inner.duration()
+    }
+
+    fn reset(&mut self) -> DataResult<()> {
+        self.reset_heartbeats();
+        self.inner.reset()
+    }
+
+    fn metadata(&self) -> DataSourceMetadata {
+        self.inner
+            .metadata()
+            .with_property("heartbeat_interval_ns", self.interval_ns.to_string())
+            .with_property("heartbeats", self.heartbeats.to_string())
+    }
+
+    fn set_paused(&mut self, paused: bool) -> DataResult<()> {
+        self.pacer.paused = paused;
+        Ok(())
+    }
+
+    fn is_paused(&self) -> bool {
+        self.pacer.paused
+    }
+
+    fn last_symbol(&self) -> Option<&str> {
+        self.last_symbol.as_deref()
+    }
+
+    fn filter_symbols(&mut self, symbols: Vec<String>) -> DataResult<()> {
+        self.inner.filter_symbols(symbols)
+    }
+
+    fn set_time_window(&mut self, start: u128, end: u128) -> DataResult<()> {
+        self.reset_heartbeats();
+        self.inner.set_time_window(start, end)
+    }
+
+    fn set_error_policy(&mut self, policy: ErrorPolicy) -> DataResult<()> {
+        self.inner.set_error_policy(policy)
+    }
+}
+
+/// Settings for [`CachedDataSource`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct BlockCacheConfig {
+    /// Keep parsed blocks of events in memory for repeated seeks and replays
+    pub enabled: bool,
+    /// Events per cached block
+    pub block_events: usize,
+    /// Memory the cached blocks may hold (bytes); least recently used
+    /// blocks are dropped past it
+    pub budget_bytes: usize,
+}
+
+impl Default for BlockCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            block_events: 1024,
+            budget_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+impl BlockCacheConfig {
+    /// Check the settings
+    pub fn validate(&self) -> Result<(), String> {
+        if self.block_events == 0 {
+            return Err("Cache block size must be at least one event".to_string());
+        }
+        if self.budget_bytes == 0 {
+            return Err("Cache memory budget must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Hit rate and memory use of a [`CachedDataSource`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct BlockCacheStats {
+    /// Block lookups served from memory
+    pub hits: u64,
+    /// Block lookups that re-read an evicted block from the wrapped source
+    pub misses: u64,
+    /// Blocks dropped to stay within the memory budget
+    pub evictions: u64,
+    /// Blocks held
+    pub blocks: usize,
+    /// Estimated memory held by the blocks (bytes)
+    pub bytes: usize,
+    /// Events parsed by the wrapped source, first reads and re-reads alike
+    pub events_read: u64,
+}
+
+impl BlockCacheStats {
+    /// Share of block lookups served from memory
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / lookups as f64
+        }
+    }
+}
+
+/// Where one block of a [`CachedDataSource`] sits in the stream
+#[derive(Debug, Clone, Copy)]
+struct BlockInfo {
+    /// Ordinal of the block's first event in the stream
+    start: u64,
+    /// Events in the block
+    len: usize,
+    /// Timestamp of the block's first event
+    first_ts: u128,
+    /// Latest timestamp in the block
+    max_ts: u128,
+    /// Events right before the block with the same timestamp as its first,
+    /// which a seek to that timestamp lands in front of
+    lead_ties: u64,
+}
+
+/// A parsed event and its symbol
+type CachedEvent = (MarketEvent, Option<String>);
+
+#[derive(Debug)]
+struct CachedBlock {
+    events: Vec<CachedEvent>,
+    /// Estimated memory held (bytes)
+    bytes: usize,
+    /// Cache clock at the last lookup
+    last_used: u64,
+}
+
+/// Wrapper that keeps parsed blocks of events in memory, so scrubbing back
+/// and forth over the same part of a file (seeks, rewinds and replay seeks)
+/// doesn't read and parse it again.
+///
+/// The stream is split into blocks of `block_events` events as it is first
+/// read. Blocks are kept up to the memory budget and the least recently used
+/// ones are dropped past it. An evicted block that is needed again is
+/// re-read from the wrapped source: by seeking to it when timestamps have
+/// never gone backwards, and by reading from the start otherwise. The
+/// wrapper does the real-time pacing itself, since cached events never reach
+/// the wrapped source.
+pub struct CachedDataSource {
+    /// Wrapped data source, always read without pacing
+    inner: Box<dyn DataSource>,
+    config: BlockCacheConfig,
+    /// Every full block read so far, in stream order
+    directory: Vec<BlockInfo>,
+    /// Cached events of full blocks, by block index
+    blocks: std::collections::HashMap<usize, CachedBlock>,
+    /// Events after the last full block, whose block is still being read
+    open: Vec<CachedEvent>,
+    /// Extent of the open block, once it has an event
+    open_info: Option<BlockInfo>,
+    /// The wrapped source has nothing after the open block
+    complete: bool,
+    /// Ordinal of the event the wrapped source returns next, if known
+    inner_ordinal: Option<u64>,
+    /// Timestamps so far never go backwards, so blocks can be found by seeking
+    ordered: bool,
+    /// Timestamp of the last event read and how many in a row had it
+    tail_run: Option<(u128, u64)>,
+    /// Errors the wrapped source reported while the stream was first read
+    known_errors: u64,
+    /// Block and offset within it of the next event to return
+    cursor: (usize, usize),
+    /// Full block the cursor last took an event from
+    current: Option<usize>,
+    /// Advances on every block lookup, for least-recently-used eviction
+    clock: u64,
+    stats: BlockCacheStats,
+    /// Paces the events returned
+    pacer: Pacer,
+    /// Timestamp of the last event returned or sought to
+    position: Option<u128>,
+    /// Symbol of the last event returned
+    last_symbol: Option<String>,
+}
+
+impl CachedDataSource {
+    /// Wrap a data source that is at the start of its stream
+    pub fn new(mut inner: Box<dyn DataSource>, config: BlockCacheConfig) -> DataResult<Self> {
+        config.validate().map_err(DataError::validation)?;
+        let paused = inner.is_paused();
+        inner.set_paused(true)?;
+        Ok(Self {
+            inner,
+            config,
+            directory: Vec::new(),
+            blocks: std::collections::HashMap::new(),
+            open: Vec::new(),
+            open_info: None,
+            complete: false,
+            inner_ordinal: Some(0),
+            ordered: true,
+            tail_run: None,
+            known_errors: 0,
+            cursor: (0, 0),
+            current: None,
+            clock: 0,
+            stats: BlockCacheStats::default(),
+            pacer: Pacer::new(paused),
+            position: None,
+            last_symbol: None,
+        })
+    }
+
+    /// Hit rate and memory use so far
+    pub fn stats(&self) -> BlockCacheStats {
+        BlockCacheStats { blocks: self.blocks.len(), ..self.stats }
+    }
+
+    /// Unwrap the inner data source, restoring its pacing
+    pub fn into_inner(mut self) -> Box<dyn DataSource> {
+        let _ = self.inner.set_paused(self.pacer.paused);
+        let _ = self.inner.set_playback_speed(self.pacer.playback_speed);
+        self.inner
+    }
+
+    /// Ordinal of the open block's first event
+    fn open_start(&self) -> u64 {
+        self.directory.last().map_or(0, |info| info.start + info.len as u64)
+    }
+
+    /// Read the stream's next event from the wrapped source into the open block
+    fn read_next(&mut self) -> DataResult<Option<CachedEvent>> {
+        if self.complete {
+            return Ok(None);
+        }
+        self.position_inner(self.open_start() + self.open.len() as u64)?;
+        let event = match self.inner.next_event() {
+            Ok(Some(event)) => event,
+            Ok(None) => {
+                self.complete = true;
+                return Ok(None);
+            }
+            Err(e) => {
+                self.known_errors += 1;
+                return Err(e);
+            }
+        };
+        self.inner_ordinal = self.inner_ordinal.map(|ordinal| ordinal + 1);
+        self.stats.events_read += 1;
+        let read = (event, self.inner.last_symbol().map(str::to_string));
+        self.append(read.clone());
+        Ok(Some(read))
+    }
+
+    /// Add a newly read event to the open block, filing the block once full
+    fn append(&mut self, read: CachedEvent) {
+        let timestamp = read.0.timestamp();
+        let ties = match self.tail_run {
+            Some((last, count)) if last == timestamp => count,
+            _ => 0,
+        };
+        if self.tail_run.is_some_and(|(last, _)| timestamp < last) {
+            self.ordered = false;
+        }
+        self.tail_run = Some((timestamp, ties + 1));
+
+        let start = self.open_start();
+        let info = self.open_info.get_or_insert(BlockInfo {
+            start,
+            len: 0,
+            first_ts: timestamp,
+            max_ts: timestamp,
+            lead_ties: ties,
+        });
+        info.len += 1;
+        info.max_ts = info.max_ts.max(timestamp);
+        self.open.push(read);
+
+        if self.open.len() >= self.config.block_events {
+            if let Some(info) = self.open_info.take() {
+                self.directory.push(info);
+                let events = std::mem::take(&mut self.open);
+                self.insert(self.directory.len() - 1, events);
+            }
+        }
+    }
+
+    /// Next event from the wrapped source when re-reading, passing over as
+    /// many errors as it reported when the stream was first read
+    fn reread(&mut self) -> DataResult<Option<CachedEvent>> {
+        let mut errors = 0;
+        loop {
+            match self.inner.next_event() {
+                Ok(event) => {
+                    return Ok(event.map(|event| (event, self.inner.last_symbol().map(str::to_string))));
+                }
+                Err(e) if errors >= self.known_errors => return Err(e),
+                Err(_) => errors += 1,
+            }
+        }
+    }
+
+    /// Put the wrapped source where its next event is the `target`th of the stream
+    fn position_inner(&mut self, target: u64) -> DataResult<()> {
+        let skip = match self.inner_ordinal {
+            Some(ordinal) if ordinal == target => return Ok(()),
+            // Close ahead: reading on is cheaper than repositioning
+            Some(ordinal) if ordinal < target && target - ordinal <= self.config.block_events as u64 => target - ordinal,
+            _ => {
+                self.inner_ordinal = None;
+                let anchor = self.open_info
+                    .filter(|info| info.start <= target)
+                    .or_else(|| {
+                        let index = self.directory.partition_point(|info| info.start <= target);
+                        index.checked_sub(1).map(|index| self.directory[index])
+                    })
+                    .filter(|_| self.ordered && target > 0);
+                match anchor {
+                    Some(anchor) => {
+                        self.inner.seek_to_time(anchor.first_ts)?;
+                        anchor.lead_ties + (target - anchor.start)
+                    }
+                    None => {
+                        self.inner.reset()?;
+                        target
+                    }
+                }
+            }
+        };
+
+        for _ in 0..skip {
+            if self.reread()?.is_none() {
+                return Err(DataError::seek_failed("Data ended before a cached position; the file may have changed"));
+            }
+        }
+        self.inner_ordinal = Some(target);
+        Ok(())
+    }
+
+    /// Look up a full block, re-reading it from the wrapped source if it was evicted
+    fn lookup(&mut self, block: usize) -> DataResult<()> {
+        self.clock += 1;
+        if let Some(cached) = self.blocks.get_mut(&block) {
+            cached.last_used = self.clock;
+            self.stats.hits += 1;
+            counter!("data_cache_hits_total", 1);
+            return Ok(());
+        }
+        self.stats.misses += 1;
+        counter!("data_cache_misses_total", 1);
+
+        let info = self.directory[block];
+        self.position_inner(info.start)?;
+        let mut events = Vec::with_capacity(info.len);
+        while events.len() < info.len {
+            match self.reread()? {
+                Some(read) => events.push(read),
+                None => {
+                    self.inner_ordinal = None;
+                    return Err(DataError::seek_failed(format!(
+                        "Data ended inside cached block {}; the file may have changed", block
+                    )));
+                }
+            }
+        }
+        self.stats.events_read += info.len as u64;
+        self.inner_ordinal = Some(info.start + info.len as u64);
+        self.insert(block, events);
+        Ok(())
+    }
+
+    /// Cache a full block, evicting the least recently used ones past the budget
+    fn insert(&mut self, block: usize, events: Vec<CachedEvent>) {
+        let bytes = events
+            .iter()
+            .map(|(_, symbol)| std::mem::size_of::<CachedEvent>() + symbol.as_ref().map_or(0, String::capacity))
+            .sum();
+        self.clock += 1;
+        self.blocks.insert(block, CachedBlock { events, bytes, last_used: self.clock });
+        self.stats.bytes += bytes;
+
+        // The newest block stays even if it alone is over budget
+        while self.stats.bytes > self.config.budget_bytes && self.blocks.len() > 1 {
+            let Some(oldest) = self.blocks.iter().min_by_key(|(_, cached)| cached.last_used).map(|(block, _)| *block) else {
+                break;
+            };
+            if let Some(evicted) = self.blocks.remove(&oldest) {
+                self.stats.bytes -= evicted.bytes;
+                self.stats.evictions += 1;
+            }
+        }
+        gauge!("data_cache_bytes", self.stats.bytes as f64);
+    }
+
+    /// Forget everything read, after a change to which events the wrapped
+    /// source returns; playback starts over from the beginning
+    fn invalidate(&mut self) {
+        self.directory.clear();
+        self.blocks.clear();
+        self.open.clear();
+        self.open_info = None;
+        self.complete = false;
+        self.inner_ordinal = None;
+        self.ordered = true;
+        self.tail_run = None;
+        self.known_errors = 0;
+        self.stats.bytes = 0;
+        self.rewind();
+    }
+
+    fn rewind(&mut self) {
+        self.cursor = (0, 0);
+        self.current = None;
+        self.pacer.reset();
+        self.position = None;
+        self.last_symbol = None;
+    }
+}
+
+impl DataSource for CachedDataSource {
+    fn next_event(&mut self) -> DataResult<Option<MarketEvent>> {
+        let (event, symbol) = loop {
+            let (block, offset) = self.cursor;
+            if let Some(info) = self.directory.get(block) {
+                if offset >= info.len {
+                    self.cursor = (block + 1, 0);
+                    continue;
+                }
+                if self.current != Some(block) {
+                    self.lookup(block)?;
+                    self.current = Some(block);
+                }
+                break self.blocks[&block].events[offset].clone();
+            }
+            if let Some(read) = self.open.get(offset) {
+                break read.clone();
+            }
+            match self.read_next()? {
+                Some(read) => break read,
+                None => return Ok(None),
+            }
+        };
+
+        self.cursor.1 += 1;
+        self.pacer.wait_until(event.timestamp());
+        self.position = Some(event.timestamp());
+        self.last_symbol = symbol;
+        Ok(Some(event))
+    }
+
+    fn seek_to_time(&mut self, timestamp: u128) -> DataResult<()> {
+        // The first event at or after the target is in the first block that
+        // reaches it, reading further into the stream if none does yet
+        loop {
+            if let Some(block) = self.directory.iter().position(|info| info.max_ts >= timestamp) {
+                self.lookup(block)?;
+                let offset = self.blocks[&block].events.iter().position(|(event, _)| event.timestamp() >= timestamp);
+                self.rewind();
+                self.cursor = (block, offset.unwrap_or(0));
+                self.current = Some(block);
+                self.position = Some(timestamp);
+                return Ok(());
+            }
+            if self.open_info.is_some_and(|info| info.max_ts >= timestamp) {
+                let offset = self.open.iter().position(|(event, _)| event.timestamp() >= timestamp);
+                self.rewind();
+                self.cursor = (self.directory.len(), offset.unwrap_or(0));
+                self.position = Some(timestamp);
+                return Ok(());
+            }
+            if self.read_next()?.is_none() {
+                return Err(DataError::seek_failed(format!("Timestamp {} not found in data", timestamp)));
+            }
+        }
+    }
+
+    fn set_playback_speed(&mut self, multiplier: f64) -> DataResult<()> {
+        self.pacer.set_playback_speed(multiplier)
+    }
+
+    fn is_finished(&self) -> bool {
+        let (block, offset) = self.cursor;
+        let ordinal = self.directory.get(block).map_or(self.open_start() + offset as u64, |info| info.start + offset as u64);
+        self.complete && ordinal >= self.open_start() + self.open.len() as u64
+    }
+
+    fn current_position(&self) -> Option<u128> {
+        self.position
+    }
+
+    fn duration(&self) -> Option<(u128, u128)> {
+        self.inner.duration()
+    }
+
+    fn reset(&mut self) -> DataResult<()> {
+        self.rewind();
+        Ok(())
+    }
+
+    fn metadata(&self) -> DataSourceMetadata {
+        let stats = self.stats();
+        self.inner
+            .metadata()
+            .with_property("cache_hits", stats.hits.to_string())
+            .with_property("cache_misses", stats.misses.to_string())
+            .with_property("cache_hit_rate", format!("{:.3}", stats.hit_rate()))
+            .with_property("cache_bytes", stats.bytes.to_string())
+    }
+
+    fn set_paused(&mut self, paused: bool) -> DataResult<()> {
+        self.pacer.paused = paused;
+        Ok(())
+    }
+
+    fn is_paused(&self) -> bool {
+        self.pacer.paused
+    }
+
+    fn last_symbol(&self) -> Option<&str> {
+        self.last_symbol.as_deref()
+    }
+
+    fn filter_symbols(&mut self, symbols: Vec<String>) -> DataResult<()> {
+        self.inner.filter_symbols(symbols)?;
+        self.invalidate();
+        Ok(())
+    }
+
+    fn set_time_window(&mut self, start: u128, end: u128) -> DataResult<()> {
+        self.inner.set_time_window(start, end)?;
+        self.invalidate();
+        Ok(())
+    }
+
+    fn set_error_policy(&mut self, policy: ErrorPolicy) -> DataResult<()> {
+        self.inner.set_error_policy(policy)?;
+        self.invalidate();
+        Ok(())
+    }
+}
+
+/// Playback statistics for one file of a [`ChainedDataSource`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainedFile {
+    /// Name of the file's data source
+    pub name: String,
+    /// Events returned from this file since the last reset
+    pub events: u64,
+    /// First and last timestamps returned from this file
+    pub first_timestamp: Option<u128>,
+    pub last_timestamp: Option<u128>,
+    /// Events dropped for starting before the previous file ended
+    pub overlapping: u64,
+}
+
+/// Plays several data sources back to back, e.g. one file per trading day,
+/// as if they were one file. Each file must pick up where the previous one
+/// left off: an event earlier than the last event of an earlier file is
+/// surfaced as `DataError::InvalidTimestamp` and dropped. Gaps between files
+/// (overnight, weekends) are expected and are not paced in real time.
+pub struct ChainedDataSource {
+    /// Sources in playback order
+    sources: Vec<Box<dyn DataSource>>,
+    /// Statistics per source, in the same order
+    files: Vec<ChainedFile>,
+    /// Index of the source being played
+    current: usize,
+    /// Latest timestamp of the files before the current one
+    previous_end: Option<u128>,
+}
+
+impl ChainedDataSource {
+    /// Chain data sources in playback order
+    pub fn new(sources: Vec<Box<dyn DataSource>>) -> DataResult<Self> {
+        if sources.is_empty() {
+            return Err(DataError::validation("A chained data source needs at least one source"));
+        }
+        let files = sources
+            .iter()
+            .map(|source| ChainedFile { name: source.metadata().name, ..ChainedFile::default() })
+            .collect();
+        Ok(Self {
+            sources,
+            files,
+            current: 0,
+            previous_end: None,
+        })
+    }
+
+    /// Open data files in playback order, detecting each file's format
+    pub fn from_files<P: AsRef<Path>>(paths: &[P]) -> DataResult<Self> {
+        let sources = paths
+            .iter()
+            .map(DataFormatDetector::create_data_source)
+            .collect::<DataResult<Vec<_>>>()?;
+        Self::new(sources)
+    }
+
+    /// Playback statistics per file
+    pub fn files(&self) -> &[ChainedFile] {
+        &self.files
+    }
+
+    /// Metadata of each file's data source
+    pub fn file_metadata(&self) -> Vec<DataSourceMetadata> {
+        self.sources.iter().map(|source| source.metadata()).collect()
+    }
+
+    /// Index of the file being played
+    pub fn current_file(&self) -> usize {
+        self.current
+    }
+
+    /// Unwrap the chained data sources
+    pub fn into_inner(self) -> Vec<Box<dyn DataSource>> {
+        self.sources
+    }
+
+    /// Start playback over from the first file
+    fn reset_chain(&mut self) {
+        self.current = 0;
+        self.previous_end = None;
+        for file in &mut self.files {
+            *file = ChainedFile { name: std::mem::take(&mut file.name), ..ChainedFile::default() };
+        }
+    }
+
+    /// Move on to the next file, returning false after the last one
+    fn advance(&mut self) -> bool {
+        if self.current + 1 >= self.sources.len() {
+            return false;
+        }
+        let end = self.files[self.current].last_timestamp;
+        self.previous_end = self.previous_end.max(end);
+        self.current += 1;
+        true
+    }
+
+    /// Apply a setting to every source
+    fn for_each_source(&mut self, mut apply: impl FnMut(&mut dyn DataSource) -> DataResult<()>) -> DataResult<()> {
+        self.sources.iter_mut().try_for_each(|source| apply(source.as_mut()))
+    }
+}
+
+impl DataSource for ChainedDataSource {
+    fn next_event(&mut self) -> DataResult<Option<MarketEvent>> {
+        loop {
+            let Some(event) = self.sources[self.current].next_event()? else {
+                if self.advance() {
+                    continue;
+                }
+                return Ok(None);
+            };
+
+            let timestamp = event.timestamp();
+            let file = &mut self.files[self.current];
+            if let Some(end) = self.previous_end.filter(|end| timestamp < *end) {
+                file.overlapping += 1;
+                return Err(DataError::invalid_timestamp(
+                    timestamp,
+                    self.sources[self.current].last_line().unwrap_or(0),
+                    format!("{} starts before the previous file ended at {}", file.name, end),
+                ));
+            }
+
+            file.events += 1;
+            file.first_timestamp.get_or_insert(timestamp);
+            file.last_timestamp = Some(file.last_timestamp.map_or(timestamp, |last| last.max(timestamp)));
+            return Ok(Some(event));
+        }
+    }
+
+    fn seek_to_time(&mut self, timestamp: u128) -> DataResult<()> {
+        self.reset()?;
+        // Files that end before the target cannot seek to it; move past them,
+        // remembering where they end so the next file is still checked
+        // against it. A file that cannot say ends before the target.
+        loop {
+            match self.sources[self.current].seek_to_time(timestamp) {
+                Err(DataError::SeekFailed { .. }) if self.current + 1 < self.sources.len() => {
+                    let end = self.sources[self.current].duration().map(|(_, end)| end);
+                    self.previous_end = self.previous_end.max(end.or(timestamp.checked_sub(1)));
+                    self.advance();
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn set_playback_speed(&mut self, multiplier: f64) -> DataResult<()> {
+        self.for_each_source(|source| source.set_playback_speed(multiplier))
+    }
+
+    fn is_finished(&self) -> bool {
+        self.current + 1 == self.sources.len() && self.sources[self.current].is_finished()
+    }
+
+    fn current_position(&self) -> Option<u128> {
+        self.sources[self.current].current_position().or(self.previous_end)
+    }
+
+    fn duration(&self) -> Option<(u128, u128)> {
+        let (start, _) = self.sources.first()?.duration()?;
+        let (_, end) = self.sources.last()?.duration()?;
+        Some((start, end))
+    }
+
+    fn reset(&mut self) -> DataResult<()> {
+        self.reset_chain();
+        self.for_each_source(|source| source.reset())
+    }
+
+    fn metadata(&self) -> DataSourceMetadata {
+        let files = self.file_metadata();
+        let names: Vec<&str> = files.iter().map(|file| file.name.as_str()).collect();
+        let mut metadata = DataSourceMetadata::new(names.join(" + "), "Chain")
+            .with_property("files", files.len().to_string())
+            .with_property("current_file", self.files[self.current].name.clone())
+            .with_property(
+                "overlapping_events",
+                self.files.iter().map(|file| file.overlapping).sum::<u64>().to_string(),
+            );
+
+        metadata.event_count = files.iter().map(|file| file.event_count).sum();
+        metadata.time_range = self.duration();
+        metadata.file_size = files.iter().map(|file| file.file_size).sum();
+        for file in files {
+            for (symbol, count) in file.symbol_counts {
+                *metadata.symbol_counts.entry(symbol).or_default() += count;
+            }
+            metadata.skipped_records += file.skipped_records;
+            metadata.quarantined_records += file.quarantined_records;
+        }
+        metadata
+    }
+
+    fn set_paused(&mut self, paused: bool) -> DataResult<()> {
+        self.for_each_source(|source| source.set_paused(paused))
+    }
+
+    fn is_paused(&self) -> bool {
+        self.sources[self.current].is_paused()
+    }
+
+    fn last_symbol(&self) -> Option<&str> {
+        self.sources[self.current].last_symbol()
+    }
+
+    fn last_line(&self) -> Option<usize> {
+        self.sources[self.current].last_line()
+    }
+
+    fn filter_symbols(&mut self, symbols: Vec<String>) -> DataResult<()> {
+        self.for_each_source(|source| source.filter_symbols(symbols.clone()))
+    }
+
+    fn set_time_window(&mut self, start: u128, end: u128) -> DataResult<()> {
+        validate_time_window(start, end)?;
+        self.reset_chain();
+        self.for_each_source(|source| source.set_time_window(start, end))
+    }
+
+    fn set_error_policy(&mut self, policy: ErrorPolicy) -> DataResult<()> {
+        self.for_each_source(|source| source.set_error_policy(policy))
+    }
+}
 
-        self.current_event += 1;
-        self.current_position = Some(event.timestamp());
+/// Unit of the raw timestamps in a data file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampUnit {
+    /// Guess from the magnitude of the first timestamp
+    Auto,
+    Seconds,
+    Milliseconds,
+    Microseconds,
+    #[default]
+    Nanoseconds,
+}
 
-        Ok(Some(event))
+impl TimestampUnit {
+    /// Nanoseconds per unit; `Auto` must be resolved first
+    fn nanos(self) -> u128 {
+        match self {
+            Self::Seconds => 1_000_000_000,
+            Self::Milliseconds => 1_000_000,
+            Self::Microseconds => 1_000,
+            Self::Auto | Self::Nanoseconds => 1,
+        }
     }
 
-    /// Handle timing for playback speed control
-    fn handle_timing(&mut self, event_timestamp: u128) -> DataResult<()> {
-        if self.paused {
-            return Ok(());
+    /// The unit a raw timestamp is most plausibly in. Epoch timestamps from
+    /// 1973 onwards are unambiguous; a time of day early in the morning can be
+    /// mistaken for a coarser unit, so set the unit explicitly for those.
+    fn detect(raw: u128, time_of_day: bool) -> Self {
+        let seconds_limit: u128 = if time_of_day { 86_400 } else { 100_000_000_000 };
+        if raw < seconds_limit {
+            Self::Seconds
+        } else if raw < seconds_limit * 1_000 {
+            Self::Milliseconds
+        } else if raw < seconds_limit * 1_000_000 {
+            Self::Microseconds
+        } else {
+            Self::Nanoseconds
         }
+    }
+}
 
-        if let Some(last_ts) = self.last_timestamp {
-            if event_timestamp > last_ts {
-                let time_diff_ns = event_timestamp - last_ts;
-                let real_time_diff = Duration::from_nanos(time_diff_ns as u64);
-                let adjusted_diff = real_time_diff.div_f64(self.playback_speed);
+/// How a data file's raw timestamps map onto nanoseconds since the Unix
+/// epoch in UTC, which is what the rest of the system expects
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct TimestampConfig {
+    /// Unit of the raw timestamps
+    pub unit: TimestampUnit,
+    /// Local time at this offset east of UTC (minutes); converted to UTC
+    pub utc_offset_minutes: i32,
+    /// Timestamps count from midnight of this local date (`YYYY-MM-DD`)
+    /// rather than from the epoch. When the time of day wraps past midnight
+    /// the following day is assumed.
+    pub session_date: Option<String>,
+    /// Correction added after conversion, e.g. a vendor's known clock skew
+    /// (nanoseconds, may be negative)
+    pub offset_ns: i64,
+}
 
-                if let Some(start_time) = self.playback_start {
-                    let elapsed = start_time.elapsed();
-                    let target_elapsed = Duration::from_nanos((event_timestamp - self.last_timestamp.unwrap_or(0)) as u64);
-                    
-                    if elapsed < target_elapsed {
-                        std::thread::sleep(target_elapsed - elapsed);
-                    }
-                } else {
-                    std::thread::sleep(adjusted_diff);
-                }
-            }
-        } else {
-            // First event, record start time
-            self.playback_start = Some(Instant::now());
+impl Default for TimestampConfig {
+    fn default() -> Self {
+        Self {
+            unit: TimestampUnit::Nanoseconds,
+            utc_offset_minutes: 0,
+            session_date: None,
+            offset_ns: 0,
         }
+    }
+}
 
-        self.last_timestamp = Some(event_timestamp);
+impl TimestampConfig {
+    /// Check the settings
+    pub fn validate(&self) -> Result<(), String> {
+        if self.utc_offset_minutes.abs() > 14 * 60 {
+            return Err("UTC offset must be within 14 hours".to_string());
+        }
+        if let Some(date) = &self.session_date {
+            if date_to_ns(date).is_none() {
+                return Err(format!("Invalid session date: {} (expected YYYY-MM-DD from 1970)", date));
+            }
+        }
         Ok(())
     }
 
-    /// Write a binary data file from a collection of events
-    pub fn write_binary_file<P: AsRef<Path>>(
-        file_path: P,
-        events: &[MarketEvent],
-    ) -> DataResult<()> {
-        use std::io::Write;
-        use byteorder::{LittleEndian, WriteBytesExt};
-
-        let path = file_path.as_ref();
-        let mut file = File::create(path)?;
-
-        // Calculate header information
-        let start_timestamp = events.first().map(|e| e.timestamp()).unwrap_or(0);
-        let end_timestamp = events.last().map(|e| e.timestamp()).unwrap_or(0);
+    /// Whether timestamps are already nanoseconds since the epoch in UTC
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+}
 
-        let mut header = BinaryDataHeader::new();
-        header.event_count = events.len() as u64;
-        header.start_timestamp = start_timestamp;
-        header.end_timestamp = end_timestamp;
+/// Converts raw timestamps according to a [`TimestampConfig`], tracking the
+/// detected unit and midnight wraps along the way
+#[derive(Debug, Clone)]
+pub struct TimestampNormalizer {
+    config: TimestampConfig,
+    /// Unit in use, once detected
+    unit: Option<TimestampUnit>,
+    /// Midnight of the session date, when timestamps are times of day
+    session_start: Option<u128>,
+    /// Midnight wraps seen so far
+    days_elapsed: u128,
+    /// Last time of day converted (nanoseconds)
+    last_time_of_day: Option<u128>,
+}
 
-        // Write header
-        Self::write_header(&mut file, &header)?;
+impl TimestampNormalizer {
+    /// Create a normalizer for the given settings
+    pub fn new(config: TimestampConfig) -> DataResult<Self> {
+        config.validate().map_err(DataError::validation)?;
+        let session_start = config.session_date.as_deref().and_then(date_to_ns);
+        Ok(Self {
+            unit: (config.unit != TimestampUnit::Auto).then_some(config.unit),
+            config,
+            session_start,
+            days_elapsed: 0,
+            last_time_of_day: None,
+        })
+    }
 
-        // Write events
-        for event in events {
-            // Serialize event using bincode
-            let event_data = bincode::serialize(event).map_err(|e| {
-                DataError::InvalidFormat {
-                    file: path.display().to_string(),
-                    details: format!("Failed to serialize event: {}", e),
-                }
-            })?;
+    /// Unit in use, if it has been detected or was configured
+    pub fn unit(&self) -> Option<TimestampUnit> {
+        self.unit
+    }
 
-            // Write event length and data
-            file.write_u32::<LittleEndian>(event_data.len() as u32)?;
-            file.write_all(&event_data)?;
+    /// Convert a raw timestamp to nanoseconds since the epoch (UTC)
+    pub fn normalize(&mut self, raw: u128) -> DataResult<u128> {
+        let time_of_day = self.session_start.is_some();
+        let unit = *self.unit.get_or_insert_with(|| TimestampUnit::detect(raw, time_of_day));
+        let out_of_range = || DataError::invalid_timestamp(raw, 0, "Timestamp is out of range after normalization");
+
+        let mut ns = raw.checked_mul(unit.nanos()).ok_or_else(out_of_range)?;
+        if let Some(session_start) = self.session_start {
+            // Going back more than half a day means midnight has passed
+            if self.last_time_of_day.is_some_and(|last| last > ns + NANOS_PER_DAY / 2) {
+                self.days_elapsed += 1;
+            }
+            self.last_time_of_day = Some(ns);
+            ns += session_start + self.days_elapsed * NANOS_PER_DAY;
         }
 
-        file.flush()?;
-        Ok(())
+        let ns = i128::try_from(ns).map_err(|_| out_of_range())? + self.correction();
+        u128::try_from(ns).map_err(|_| out_of_range())
     }
 
-    /// Write the binary header to a file
-    fn write_header(file: &mut File, header: &BinaryDataHeader) -> DataResult<()> {
-        use std::io::Write;
-        use byteorder::{LittleEndian, WriteBytesExt};
+    /// Offset and time zone correction added after unit conversion
+    fn correction(&self) -> i128 {
+        self.config.offset_ns as i128 - self.config.utc_offset_minutes as i128 * 60_000_000_000
+    }
 
-        file.write_u32::<LittleEndian>(header.magic)?;
-        file.write_u16::<LittleEndian>(header.version)?;
-        file.write_u16::<LittleEndian>(header.flags)?;
-        file.write_u64::<LittleEndian>(header.event_count)?;
-        file.write_u128::<LittleEndian>(header.start_timestamp)?;
-        file.write_u128::<LittleEndian>(header.end_timestamp)?;
-        file.write_all(&header.reserved)?;
+    /// Smallest raw timestamp that normalizes to `ns` or later. Only known
+    /// for epoch timestamps once the unit is, since times of day wrap.
+    fn raw_at_or_after(&self, ns: u128) -> Option<u128> {
+        if self.session_start.is_some() {
+            return None;
+        }
+        let unit = self.unit?;
+        let ns = i128::try_from(ns).ok()?.checked_sub(self.correction())?;
+        Some(u128::try_from(ns).unwrap_or(0).div_ceil(unit.nanos()))
+    }
 
-        Ok(())
+    /// Forget midnight wraps and, under `Auto`, the detected unit
+    pub fn reset(&mut self) {
+        if self.config.unit == TimestampUnit::Auto {
+            self.unit = None;
+        }
+        self.days_elapsed = 0;
+        self.last_time_of_day = None;
     }
 }
 
-impl DataSource for BinaryDataSource {
-    fn next_event(&mut self) -> DataResult<Option<MarketEvent>> {
-        if self.finished {
-            return Ok(None);
-        }
+/// Wrapper that rewrites a data source's timestamps into nanoseconds since
+/// the epoch in UTC, for files that use another unit, a local clock or a
+/// time of day. Time windows and seeks are in normalized time, and the
+/// wrapper paces playback itself since the inner source's pacing assumes
+/// raw nanoseconds.
+pub struct NormalizedDataSource {
+    /// Wrapped data source, always read without pacing
+    inner: Box<dyn DataSource>,
+    normalizer: TimestampNormalizer,
+    /// Event found by a seek, returned next
+    pending: Option<MarketEvent>,
+    /// Normalized playback window
+    time_window: Option<(u128, u128)>,
+    /// The window's end has been passed or a seek found nothing
+    finished: bool,
+    /// Paces the events returned
+    pacer: Pacer,
+}
 
-        let event = self.read_next_event()?;
-        
-        if let Some(ref event) = event {
-            // Handle timing for playback speed
-            self.handle_timing(event.timestamp())?;
-        }
+impl NormalizedDataSource {
+    /// Wrap a data source with the given timestamp settings
+    pub fn new(mut inner: Box<dyn DataSource>, config: TimestampConfig) -> DataResult<Self> {
+        let normalizer = TimestampNormalizer::new(config)?;
+        let paused = inner.is_paused();
+        inner.set_paused(true)?;
+        Ok(Self {
+            inner,
+            normalizer,
+            pending: None,
+            time_window: None,
+            finished: false,
+            pacer: Pacer::new(paused),
+        })
+    }
 
-        Ok(event)
+    /// Unit the raw timestamps are read in, once known
+    pub fn unit(&self) -> Option<TimestampUnit> {
+        self.normalizer.unit()
     }
 
-    fn seek_to_time(&mut self, timestamp: u128) -> DataResult<()> {
-        use std::io::{Seek, SeekFrom};
+    /// Unwrap the inner data source, restoring its pacing
+    pub fn into_inner(mut self) -> Box<dyn DataSource> {
+        let _ = self.inner.set_paused(self.pacer.paused);
+        let _ = self.inner.set_playback_speed(self.pacer.playback_speed);
+        self.inner
+    }
 
-        // Reset to beginning of events (after header)
-        self.file.seek(SeekFrom::Start(BinaryDataHeader::size() as u64))?;
-        self.current_event = 0;
+    /// Read and normalize the next event from the inner source
+    fn read(&mut self) -> DataResult<Option<MarketEvent>> {
+        let Some(mut event) = self.inner.next_event()? else {
+            return Ok(None);
+        };
+        let timestamp = self.normalizer.normalize(event.timestamp())?;
+        event.set_timestamp(timestamp);
+        Ok(Some(event))
+    }
+
+    /// Rewind to the first event at or after `timestamp`, holding it back
+    /// for the next read. Returns false if there is none.
+    fn rewind_to(&mut self, timestamp: u128) -> DataResult<bool> {
+        self.pacer.reset();
+        self.pending = None;
         self.finished = false;
 
-        // Scan for the target timestamp
-        let mut target_event_index = None;
-        
-        while self.current_event < self.header.event_count {
-            let event_index = self.current_event; // Save before reading
-            let event = self.read_next_event()?;
-            
-            if let Some(event) = event {
-                if event.timestamp() >= timestamp {
-                    target_event_index = Some(event_index);
-                    break;
+        // Epoch timestamps keep their order when normalized, so the inner
+        // source can seek to the raw equivalent through its seek index.
+        // The unit has to be known first, which may take the first event.
+        if self.normalizer.session_start.is_none() {
+            if self.normalizer.unit().is_none() {
+                self.inner.reset()?;
+                match self.read()? {
+                    None => return Ok(false),
+                    Some(event) if event.timestamp() >= timestamp => {
+                        self.pending = Some(event);
+                        return Ok(true);
+                    }
+                    Some(_) => {}
+                }
+            }
+            if let Some(raw) = self.normalizer.raw_at_or_after(timestamp) {
+                match self.inner.seek_to_time(raw) {
+                    Ok(()) => {
+                        self.pending = self.read()?;
+                        return Ok(self.pending.is_some());
+                    }
+                    Err(DataError::SeekFailed { .. }) => return Ok(false),
+                    Err(DataError::UnsupportedOperation { .. }) => {}
+                    Err(e) => return Err(e),
                 }
             }
         }
 
-        if let Some(target_index) = target_event_index {
-            // Reset and skip to the target event
-            self.reset()?;
-            
-            // Skip events until we reach the target
-            for _ in 0..target_index {
-                self.read_next_event()?;
+        // Times of day are only placed by reading from the start
+        self.inner.reset()?;
+        self.normalizer.reset();
+        while let Some(event) = self.read()? {
+            if event.timestamp() >= timestamp {
+                self.pending = Some(event);
+                return Ok(true);
             }
-            
-            return Ok(());
         }
+        Ok(false)
+    }
+}
 
-        Err(DataError::seek_failed(format!("Timestamp {} not found in data", timestamp)))
+impl DataSource for NormalizedDataSource {
+    fn next_event(&mut self) -> DataResult<Option<MarketEvent>> {
+        if self.finished {
+            return Ok(None);
+        }
+        let event = match self.pending.take() {
+            Some(event) => event,
+            None => match self.read()? {
+                Some(event) => event,
+                None => return Ok(None),
+            },
+        };
+
+        let timestamp = event.timestamp();
+        if window_cmp(self.time_window, timestamp) == std::cmp::Ordering::Greater {
+            self.finished = true;
+            return Ok(None);
+        }
+        self.pacer.wait_until(timestamp);
+        Ok(Some(event))
     }
 
-    fn set_playback_speed(&mut self, multiplier: f64) -> DataResult<()> {
-        if multiplier <= 0.0 {
-            return Err(DataError::validation("Playback speed must be positive"));
+    fn seek_to_time(&mut self, timestamp: u128) -> DataResult<()> {
+        let timestamp = self.time_window.map_or(timestamp, |(start, _)| timestamp.max(start));
+        if self.rewind_to(timestamp)? {
+            Ok(())
+        } else {
+            Err(DataError::seek_failed(format!("Timestamp {} not found in data", timestamp)))
         }
-        self.playback_speed = multiplier;
-        Ok(())
+    }
+
+    fn set_playback_speed(&mut self, multiplier: f64) -> DataResult<()> {
+        self.pacer.set_playback_speed(multiplier)
     }
 
     fn is_finished(&self) -> bool {
-        self.finished
+        self.finished || (self.pending.is_none() && self.inner.is_finished())
     }
 
     fn current_position(&self) -> Option<u128> {
-        self.current_position
+        self.pacer.last_timestamp
     }
 
     fn duration(&self) -> Option<(u128, u128)> {
-        Some((self.header.start_timestamp, self.header.end_timestamp))
+        // Midnight wraps can only be followed by reading the whole file
+        if self.normalizer.session_start.is_some() {
+            return None;
+        }
+        let (start, end) = self.inner.duration()?;
+        let mut normalizer = self.normalizer.clone();
+        Some((normalizer.normalize(start).ok()?, normalizer.normalize(end).ok()?))
     }
 
     fn reset(&mut self) -> DataResult<()> {
-        use std::io::{Seek, SeekFrom};
-
-        self.file.seek(SeekFrom::Start(BinaryDataHeader::size() as u64))?;
-        self.current_event = 0;
-        self.finished = false;
-        self.last_timestamp = None;
-        self.playback_start = None;
-        self.current_position = Some(self.header.start_timestamp);
+        let start = self.time_window.map_or(0, |(start, _)| start);
+        self.finished = !self.rewind_to(start)?;
         Ok(())
     }
 
     fn metadata(&self) -> DataSourceMetadata {
-        self.metadata.clone()
+        let unit = self.unit().map_or("auto".to_string(), |unit| format!("{:?}", unit).to_lowercase());
+        let mut metadata = self.inner.metadata()
+            .with_property("timestamp_unit", unit)
+            .with_property("utc_offset_minutes", self.normalizer.config.utc_offset_minutes.to_string())
+            .with_property("timestamp_offset_ns", self.normalizer.config.offset_ns.to_string());
+        metadata.time_range = self.duration();
+        metadata
     }
 
     fn set_paused(&mut self, paused: bool) -> DataResult<()> {
-        self.paused = paused;
+        self.pacer.paused = paused;
         Ok(())
     }
 
     fn is_paused(&self) -> bool {
-        self.paused
+        self.pacer.paused
+    }
+
+    fn last_symbol(&self) -> Option<&str> {
+        self.inner.last_symbol()
+    }
+
+    fn last_line(&self) -> Option<usize> {
+        self.inner.last_line()
+    }
+
+    fn filter_symbols(&mut self, symbols: Vec<String>) -> DataResult<()> {
+        self.inner.filter_symbols(symbols)
+    }
+
+    fn set_time_window(&mut self, start: u128, end: u128) -> DataResult<()> {
+        validate_time_window(start, end)?;
+        self.time_window = Some((start, end));
+        self.reset()
+    }
+
+    fn set_error_policy(&mut self, policy: ErrorPolicy) -> DataResult<()> {
+        self.inner.set_error_policy(policy)
+    }
+}
+
+/// Summary of an [`ExternalSorter`] pass
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SortReport {
+    /// Events written to the sorted file
+    pub events: usize,
+    /// Events that were read after a later-stamped event
+    pub out_of_order: usize,
+    /// Sorted runs the input was split into
+    pub chunks: usize,
+}
+
+/// Sorts a data file of any supported format by timestamp with bounded
+/// memory: the input is split into sorted runs of `chunk_events` events on
+/// disk, which are then merged. The output is always JSON Lines, with each
+/// event's symbol preserved. Events with equal timestamps keep file order.
+#[derive(Debug, Clone)]
+pub struct ExternalSorter {
+    /// Maximum events held in memory at once
+    chunk_events: usize,
+    /// Directory the sorted runs are written to
+    temp_dir: PathBuf,
+    /// Policy for malformed records in the input
+    error_policy: ErrorPolicy,
+}
+
+impl Default for ExternalSorter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExternalSorter {
+    /// Default number of events per sorted run
+    pub const DEFAULT_CHUNK_EVENTS: usize = 1_000_000;
+
+    /// Create a sorter using the system temp directory
+    pub fn new() -> Self {
+        Self {
+            chunk_events: Self::DEFAULT_CHUNK_EVENTS,
+            temp_dir: std::env::temp_dir(),
+            error_policy: ErrorPolicy::default(),
+        }
+    }
+
+    /// Set the number of events sorted in memory per run
+    pub fn with_chunk_events(mut self, chunk_events: usize) -> DataResult<Self> {
+        if chunk_events == 0 {
+            return Err(DataError::validation("Sort chunk size must be at least 1 event"));
+        }
+        self.chunk_events = chunk_events;
+        Ok(self)
+    }
+
+    /// Set the directory sorted runs are written to
+    pub fn with_temp_dir<P: AsRef<Path>>(mut self, temp_dir: P) -> Self {
+        self.temp_dir = temp_dir.as_ref().to_path_buf();
+        self
+    }
+
+    /// Set the policy for malformed records in the input
+    pub fn with_error_policy(mut self, error_policy: ErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        self
+    }
+
+    /// Sort `input` into a JSON Lines file at `output`
+    pub fn sort_file<P1: AsRef<Path>, P2: AsRef<Path>>(&self, input: P1, output: P2) -> DataResult<SortReport> {
+        let mut source = DataFormatDetector::create_data_source(input)?;
+        source.set_paused(true)?;
+        if self.error_policy != ErrorPolicy::FailFast {
+            source.set_error_policy(self.error_policy)?;
+        }
+
+        let work_dir = self.temp_dir.join(format!(
+            "orderbook-sort-{}-{}",
+            std::process::id(),
+            crate::time::now_ns()
+        ));
+        std::fs::create_dir_all(&work_dir)?;
+
+        let result = self
+            .write_runs(source.as_mut(), &work_dir)
+            .and_then(|(runs, report)| Self::merge_runs(&runs, output.as_ref()).map(|_| report));
+
+        if let Err(e) = std::fs::remove_dir_all(&work_dir) {
+            tracing::warn!("Failed to remove sort directory {}: {}", work_dir.display(), e);
+        }
+        result
+    }
+
+    /// Split the source into sorted runs on disk
+    fn write_runs(&self, source: &mut dyn DataSource, work_dir: &Path) -> DataResult<(Vec<PathBuf>, SortReport)> {
+        let mut report = SortReport::default();
+        let mut runs = Vec::new();
+        let mut latest: Option<u128> = None;
+        let mut chunk: Vec<(Option<String>, MarketEvent)> = Vec::new();
+
+        loop {
+            let event = source.next_event()?;
+            if let Some(event) = event.as_ref() {
+                let timestamp = event.timestamp();
+                if latest.is_some_and(|latest| timestamp < latest) {
+                    report.out_of_order += 1;
+                }
+                latest = Some(latest.map_or(timestamp, |latest| latest.max(timestamp)));
+                report.events += 1;
+            }
+
+            let finished = event.is_none();
+            if let Some(event) = event {
+                chunk.push((source.last_symbol().map(str::to_string), event));
+            }
+
+            if chunk.len() >= self.chunk_events || (finished && !chunk.is_empty()) {
+                // Stable, so equal timestamps keep file order
+                chunk.sort_by_key(|(_, event)| event.timestamp());
+
+                let path = work_dir.join(format!("run-{}.jsonl", runs.len()));
+                let mut writer = std::io::BufWriter::new(File::create(&path)?);
+                for (symbol, event) in chunk.drain(..) {
+                    write_json_record(&mut writer, symbol.as_deref(), &event)?;
+                }
+                std::io::Write::flush(&mut writer)?;
+                runs.push(path);
+            }
+
+            if finished {
+                break;
+            }
+        }
+
+        report.chunks = runs.len();
+        Ok((runs, report))
+    }
+
+    /// K-way merge sorted runs into the output file
+    fn merge_runs(runs: &[PathBuf], output: &Path) -> DataResult<()> {
+        let mut sources = Vec::with_capacity(runs.len());
+        for path in runs {
+            let mut source = JsonDataSource::new(path)?;
+            source.set_paused(true)?;
+            sources.push(source);
+        }
+
+        let mut heap = std::collections::BinaryHeap::new();
+        for (run, source) in sources.iter_mut().enumerate() {
+            Self::push_next(&mut heap, source, run)?;
+        }
+
+        let mut writer = std::io::BufWriter::new(File::create(output)?);
+        while let Some(pending) = heap.pop() {
+            write_json_record(&mut writer, pending.symbol.as_deref(), &pending.event)?;
+            let run = pending.seq as usize;
+            Self::push_next(&mut heap, &mut sources[run], run)?;
+        }
+
+        std::io::Write::flush(&mut writer)?;
+        Ok(())
+    }
+
+    fn push_next(
+        heap: &mut std::collections::BinaryHeap<PendingEvent>,
+        source: &mut JsonDataSource,
+        run: usize,
+    ) -> DataResult<()> {
+        if let Some(event) = source.next_event()? {
+            heap.push(PendingEvent {
+                timestamp: event.timestamp(),
+                seq: run as u64,
+                symbol: source.last_symbol().map(str::to_string),
+                line: source.last_line(),
+                event,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Write one JSON Lines record, with the symbol as a top-level field
+fn write_json_record<W: std::io::Write>(writer: &mut W, symbol: Option<&str>, event: &MarketEvent) -> DataResult<()> {
+    match symbol {
+        Some(symbol) => {
+            let mut value = serde_json::to_value(event)?;
+            if let Some(object) = value.as_object_mut() {
+                object.insert("symbol".to_string(), serde_json::Value::String(symbol.to_string()));
+            }
+            serde_json::to_writer(&mut *writer, &value)?;
+        }
+        None => serde_json::to_writer(&mut *writer, event)?,
     }
+    writer.write_all(b"\n")?;
+    Ok(())
 }
 
 /// Data format detection utilities
@@ -2229,6 +5381,19 @@ impl DataFormatDetector {
             DataFormat::Binary => Ok(Box::new(BinaryDataSource::new(path)?)),
         }
     }
+
+    /// Create a data source that records its ingestion in `perf_metrics`
+    pub fn create_monitored_data_source<P: AsRef<Path>>(
+        file_path: P,
+        perf_metrics: Arc<PerformanceMetrics>,
+    ) -> DataResult<Box<dyn DataSource>> {
+        let path = file_path.as_ref();
+        match Self::detect_format(path)? {
+            DataFormat::Csv => Ok(Box::new(CsvDataSource::new(path)?.with_performance_monitoring(perf_metrics))),
+            DataFormat::Json => Ok(Box::new(JsonDataSource::new(path)?.with_performance_monitoring(perf_metrics))),
+            DataFormat::Binary => Ok(Box::new(BinaryDataSource::new(path)?.with_performance_monitoring(perf_metrics))),
+        }
+    }
 }
 
 /// Supported data formats
@@ -2260,6 +5425,37 @@ impl DataFormat {
             Self::Binary => "Binary Order Book Format",
         }
     }
+
+    /// Determine the format from a file's extension alone
+    pub fn from_path<P: AsRef<Path>>(file_path: P) -> Option<Self> {
+        file_path
+            .as_ref()
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| ext.parse().ok())
+    }
+
+    /// Write events to a file in this format
+    pub fn write_file<P: AsRef<Path>>(&self, file_path: P, events: &[MarketEvent]) -> DataResult<()> {
+        match self {
+            Self::Csv => CsvDataSource::write_csv_file(file_path, events),
+            Self::Json => JsonDataSource::write_json_file(file_path, events),
+            Self::Binary => BinaryDataSource::write_binary_file(file_path, events),
+        }
+    }
+}
+
+impl std::str::FromStr for DataFormat {
+    type Err = DataError;
+
+    /// Parse a format name or file extension (e.g. `csv`, `jsonl`, `bin`)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.to_lowercase();
+        [Self::Csv, Self::Json, Self::Binary]
+            .into_iter()
+            .find(|format| format.extensions().contains(&name.as_str()))
+            .ok_or_else(|| DataError::validation(format!("Unknown data format: {}", s)))
+    }
 }
 
 impl std::fmt::Display for DataFormat {
@@ -2380,6 +5576,115 @@ mod json_tests {
             _ => panic!("Expected Trade event"),
         }
     }
+
+    #[test]
+    fn test_json_seek_and_time_window() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        for i in 0..2000u128 {
+            writeln!(temp_file, r#"{{"Trade": {{"price": 10025, "qty": 10, "side": "Buy", "timestamp": {}, "trade_id": null}}}}"#, 1000000000 + i * 1000).unwrap();
+        }
+        temp_file.flush().unwrap();
+
+        let mut json_source = JsonDataSource::new(temp_file.path()).unwrap();
+        json_source.set_paused(true).unwrap();
+
+        // Seeking twice gives the same answer once the index is populated
+        for _ in 0..2 {
+            json_source.seek_to_time(1000000000 + 1500 * 1000).unwrap();
+            assert_eq!(json_source.next_event().unwrap().unwrap().timestamp(), 1000000000 + 1500 * 1000);
+            assert_eq!(json_source.current_line, 1501);
+        }
+        assert!(json_source.seek_to_time(u64::MAX as u128).is_err());
+
+        json_source.set_time_window(1000000000 + 1998 * 1000, u128::MAX).unwrap();
+        let mut count = 0;
+        while json_source.next_event().unwrap().is_some() {
+            count += 1;
+        }
+        assert_eq!(count, 2);
+
+        // A window past the end of the data is simply empty
+        json_source.set_time_window(u64::MAX as u128, u128::MAX).unwrap();
+        assert!(json_source.is_finished());
+        assert!(json_source.next_event().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_json_error_policies() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, r#"{{"Trade": {{"price": 10025, "qty": 500, "side": "Buy", "timestamp": 1000000000, "trade_id": null}}}}"#).unwrap();
+        writeln!(temp_file, r#"{{"Trade": {{"price": 10025, "#).unwrap();
+        writeln!(temp_file, r#"{{"Trade": {{"price": 10030, "qty": 100, "side": "Buy", "timestamp": 1000000200, "trade_id": null}}}}"#).unwrap();
+        temp_file.flush().unwrap();
+
+        // Fail-fast surfaces the bad line but the next call carries on past it
+        let mut json_source = JsonDataSource::new(temp_file.path()).unwrap();
+        json_source.set_paused(true).unwrap();
+        assert!(json_source.next_event().unwrap().is_some());
+        assert!(matches!(json_source.next_event(), Err(DataError::ParseError { line: 2, .. })));
+        assert_eq!(json_source.next_event().unwrap().unwrap().timestamp(), 1000000200);
+
+        let quarantine_path = ErrorPolicy::quarantine_path(temp_file.path());
+        let mut json_source = JsonDataSource::new(temp_file.path()).unwrap();
+        json_source.set_paused(true).unwrap();
+        json_source.set_error_policy(ErrorPolicy::Quarantine).unwrap();
+        let mut count = 0;
+        while json_source.next_event().unwrap().is_some() {
+            count += 1;
+        }
+        assert_eq!(count, 2);
+        assert_eq!(json_source.metadata().quarantined_records, 1);
+        assert_eq!(std::fs::read_to_string(&quarantine_path).unwrap(), "{\"Trade\": {\"price\": 10025, \n");
+        std::fs::remove_file(quarantine_path).unwrap();
+    }
+
+    #[test]
+    fn test_json_symbol_field_and_filter() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, r#"{{"symbol": "AAPL", "Trade": {{"price": 10025, "qty": 500, "side": "Buy", "timestamp": 1000000000, "trade_id": null}}}}"#).unwrap();
+        writeln!(temp_file, r#"{{"symbol": "MSFT", "Trade": {{"price": 30010, "qty": 200, "side": "Sell", "timestamp": 1000000100, "trade_id": null}}}}"#).unwrap();
+        writeln!(temp_file, r#"{{"Trade": {{"price": 10030, "qty": 100, "side": "Buy", "timestamp": 1000000200, "trade_id": null}}}}"#).unwrap();
+        temp_file.flush().unwrap();
+
+        let mut json_source = JsonDataSource::new(temp_file.path()).unwrap();
+        json_source.set_paused(true).unwrap();
+        json_source.filter_symbols(vec!["AAPL".to_string()]).unwrap();
+
+        let event = json_source.next_event().unwrap().unwrap();
+        assert_eq!(event.timestamp(), 1000000000);
+        assert_eq!(json_source.last_symbol(), Some("AAPL"));
+
+        // Neither the other symbol nor the unlabelled event pass the filter
+        assert!(json_source.next_event().unwrap().is_none());
+        let counts = json_source.metadata().symbol_counts;
+        assert_eq!(counts.get("AAPL"), Some(&1));
+        assert_eq!(counts.get("MSFT"), Some(&1));
+    }
+
+    #[test]
+    fn test_json_source_records_ingestion() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, r#"{{"Trade":{{"price":10025,"qty":100,"side":"Buy","timestamp":1000000000,"trade_id":null}}}}"#).unwrap();
+        writeln!(temp_file, "not json").unwrap();
+        writeln!(temp_file, r#"{{"Trade":{{"price":10030,"qty":200,"side":"Sell","timestamp":1000000001,"trade_id":null}}}}"#).unwrap();
+        temp_file.flush().unwrap();
+
+        let perf_metrics = Arc::new(PerformanceMetrics::new());
+        let mut json_source = JsonDataSource::new(temp_file.path())
+            .unwrap()
+            .with_performance_monitoring(perf_metrics.clone());
+        json_source.set_paused(true).unwrap();
+        json_source.set_error_policy(ErrorPolicy::Quarantine).unwrap();
+
+        let mut events = 0;
+        while json_source.next_event().unwrap().is_some() {
+            events += 1;
+        }
+        assert_eq!(events, 2);
+        let snapshot = perf_metrics.get_snapshot();
+        assert_eq!((snapshot.events_ingested, snapshot.ingestion_errors, snapshot.records_quarantined), (3, 1, 1));
+        let _ = std::fs::remove_file(ErrorPolicy::quarantine_path(temp_file.path()));
+    }
 }
 
 #[cfg(test)]
@@ -2454,6 +5759,47 @@ mod binary_tests {
         assert!(binary_source.is_finished());
     }
 
+    #[test]
+    fn test_binary_skip_policy_drops_undecodable_record() {
+        use std::io::{Seek, SeekFrom, Write};
+
+        let temp_file = NamedTempFile::new().unwrap();
+        let events: Vec<MarketEvent> = (0..3u128)
+            .map(|i| MarketEvent::Trade {
+                price: 10025,
+                qty: 100,
+                side: Side::Buy,
+                timestamp: 1000000000 + i,
+                trade_id: None,
+            })
+            .collect();
+        BinaryDataSource::write_binary_file(temp_file.path(), &events).unwrap();
+
+        // Overwrite the second record's enum tag with an unknown variant
+        let first_len = bincode::serialize(&events[0]).unwrap().len() as u64;
+        let mut file = std::fs::OpenOptions::new().write(true).open(temp_file.path()).unwrap();
+        file.seek(SeekFrom::Start(BinaryDataHeader::size() as u64 + 4 + first_len + 4)).unwrap();
+        file.write_all(&[0xff; 4]).unwrap();
+        drop(file);
+
+        let perf_metrics = Arc::new(PerformanceMetrics::new());
+        let mut binary_source = BinaryDataSource::new(temp_file.path())
+            .unwrap()
+            .with_performance_monitoring(perf_metrics.clone());
+        binary_source.set_paused(true).unwrap();
+        binary_source.set_error_policy(ErrorPolicy::Skip).unwrap();
+
+        let mut timestamps = Vec::new();
+        while let Some(event) = binary_source.next_event().unwrap() {
+            timestamps.push(event.timestamp());
+        }
+        assert_eq!(timestamps, vec![1000000000, 1000000002]);
+        assert_eq!(binary_source.metadata().skipped_records, 1);
+        let snapshot = perf_metrics.get_snapshot();
+        assert_eq!((snapshot.events_ingested, snapshot.ingestion_errors, snapshot.records_skipped), (3, 1, 1));
+    }
+
+
     #[test]
     fn test_binary_seek() {
         let temp_file = NamedTempFile::new().unwrap();
@@ -2526,6 +5872,77 @@ mod binary_tests {
         assert!(binary_source.set_playback_speed(0.0).is_err());
         assert!(binary_source.set_playback_speed(-1.0).is_err());
     }
+
+    #[test]
+    fn test_binary_time_window() {
+        let temp_file = NamedTempFile::new().unwrap();
+
+        let events: Vec<MarketEvent> = (0..3000u128)
+            .map(|i| MarketEvent::Trade {
+                price: 10025,
+                qty: 10,
+                side: Side::Buy,
+                timestamp: 1000000000 + i * 1000,
+                trade_id: None,
+            })
+            .collect();
+        BinaryDataSource::write_binary_file(temp_file.path(), &events).unwrap();
+
+        let mut binary_source = BinaryDataSource::new(temp_file.path())
+            .unwrap()
+            .with_time_window(1000000000 + 2048 * 1000, 1000000000 + 2050 * 1000)
+            .unwrap();
+        binary_source.set_paused(true).unwrap();
+        assert_eq!(
+            binary_source.duration(),
+            Some((1000000000 + 2048 * 1000, 1000000000 + 2050 * 1000))
+        );
+
+        let mut read_events = Vec::new();
+        while let Some(event) = binary_source.next_event().unwrap() {
+            read_events.push(event);
+        }
+        assert_eq!(read_events, events[2048..=2050].to_vec());
+
+        // The second pass starts from an index checkpoint
+        binary_source.reset().unwrap();
+        assert_eq!(binary_source.current_event, 2048);
+        assert_eq!(binary_source.next_event().unwrap(), Some(events[2048].clone()));
+    }
+
+    #[test]
+    fn test_binary_symbols_round_trip() {
+        let temp_file = NamedTempFile::new().unwrap();
+
+        let trade = |price, timestamp| MarketEvent::Trade {
+            price,
+            qty: 100,
+            side: Side::Buy,
+            timestamp,
+            trade_id: None,
+        };
+        let events = vec![
+            (Some("AAPL".to_string()), trade(10025, 1000000000)),
+            (Some("MSFT".to_string()), trade(30010, 1000000100)),
+            (Some("AAPL".to_string()), trade(10030, 1000000200)),
+        ];
+
+        BinaryDataSource::write_binary_file_with_symbols(temp_file.path(), &events).unwrap();
+        let mut binary_source = BinaryDataSource::new(temp_file.path()).unwrap();
+        binary_source.set_paused(true).unwrap();
+        binary_source.filter_symbols(vec!["AAPL".to_string()]).unwrap();
+
+        let mut read_events = Vec::new();
+        while let Some(event) = binary_source.next_event().unwrap() {
+            assert_eq!(binary_source.last_symbol(), Some("AAPL"));
+            read_events.push(event);
+        }
+        assert_eq!(read_events, vec![events[0].1.clone(), events[2].1.clone()]);
+
+        let counts = binary_source.metadata().symbol_counts;
+        assert_eq!(counts.get("AAPL"), Some(&2));
+        assert_eq!(counts.get("MSFT"), Some(&1));
+    }
 }
 
 #[cfg(test)]
@@ -2616,4 +6033,518 @@ mod format_detection_tests {
         let binary_source = DataFormatDetector::create_data_source(temp_file.path()).unwrap();
         assert_eq!(binary_source.metadata().source_type, "Binary");
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod thinning_tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+    use std::io::Write;
+
+    /// Ten quotes 100ms apart with a trade after every fifth one
+    fn dense_source() -> (NamedTempFile, Box<dyn DataSource>) {
+        let mut temp_file = NamedTempFile::with_suffix(".csv").unwrap();
+        writeln!(temp_file, "type,timestamp,price,qty,side").unwrap();
+        for i in 0..10u128 {
+            let ts = 1000000000 + i * 100_000_000;
+            writeln!(temp_file, "quote,{},100.20,100.30,100,100", ts).unwrap();
+            if i % 5 == 4 {
+                writeln!(temp_file, "trade,{},100.25,10,buy", ts + 1).unwrap();
+            }
+        }
+        temp_file.flush().unwrap();
+
+        let mut source = DataFormatDetector::create_data_source(temp_file.path()).unwrap();
+        source.set_paused(true).unwrap();
+        (temp_file, source)
+    }
+
+    fn drain(source: &mut dyn DataSource) -> (usize, usize) {
+        let (mut quotes, mut trades) = (0, 0);
+        while let Some(event) = source.next_event().unwrap() {
+            match event {
+                MarketEvent::Quote { .. } => quotes += 1,
+                MarketEvent::Trade { .. } => trades += 1,
+                _ => {}
+            }
+        }
+        (quotes, trades)
+    }
+
+    #[test]
+    fn test_every_nth_keeps_all_trades() {
+        let (_file, inner) = dense_source();
+        let mut thinned = ThinningDataSource::new(inner, ThinningPolicy::EveryNth(3)).unwrap();
+
+        assert_eq!(drain(&mut thinned), (4, 2));
+        assert_eq!(thinned.dropped_events(), 6);
+        assert_eq!(thinned.metadata().properties.get("dropped_events").map(String::as_str), Some("6"));
+
+        // Reset restarts the thinning pattern
+        thinned.reset().unwrap();
+        assert_eq!(thinned.dropped_events(), 0);
+        assert_eq!(drain(&mut thinned), (4, 2));
+    }
+
+    #[test]
+    fn test_max_rate_uses_data_time() {
+        let (_file, inner) = dense_source();
+        // At most 4 quotes/s means one every 250ms of data time: 0, 300, 600, 900ms
+        let mut thinned = ThinningDataSource::new(inner, ThinningPolicy::MaxRate(4.0)).unwrap();
+
+        assert_eq!(drain(&mut thinned), (4, 2));
+    }
+
+    #[test]
+    fn test_invalid_policies_rejected() {
+        let (_file, inner) = dense_source();
+        assert!(ThinningDataSource::new(inner, ThinningPolicy::EveryNth(0)).is_err());
+        let (_file, inner) = dense_source();
+        assert!(ThinningDataSource::new(inner, ThinningPolicy::MaxRate(0.0)).is_err());
+    }
+}
+
+#[cfg(test)]
+mod ordering_tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+    use std::io::Write;
+
+    /// Trades with the given timestamps, in file order
+    fn unordered_source(timestamps: &[u128]) -> (NamedTempFile, Box<dyn DataSource>) {
+        let mut temp_file = NamedTempFile::with_suffix(".csv").unwrap();
+        writeln!(temp_file, "type,symbol,timestamp,price,qty,side").unwrap();
+        for (i, ts) in timestamps.iter().enumerate() {
+            let symbol = if i % 2 == 0 { "AAPL" } else { "MSFT" };
+            writeln!(temp_file, "trade,{},{},100.25,{},buy", symbol, ts, i + 1).unwrap();
+        }
+        temp_file.flush().unwrap();
+
+        let mut source = DataFormatDetector::create_data_source(temp_file.path()).unwrap();
+        source.set_paused(true).unwrap();
+        (temp_file, source)
+    }
+
+    #[test]
+    fn test_strict_rejects_time_travel() {
+        let (_file, inner) = unordered_source(&[100, 200, 150, 300]);
+        let mut ordered = MonotonicDataSource::new(inner, OrderingPolicy::Strict).unwrap();
+
+        assert_eq!(ordered.next_event().unwrap().unwrap().timestamp(), 100);
+        assert_eq!(ordered.next_event().unwrap().unwrap().timestamp(), 200);
+        assert!(matches!(
+            ordered.next_event(),
+            Err(DataError::InvalidTimestamp { timestamp: 150, line: 4, .. })
+        ));
+        assert_eq!(ordered.next_event().unwrap().unwrap().timestamp(), 300);
+        assert!(ordered.next_event().unwrap().is_none());
+        assert_eq!(ordered.out_of_order_events(), 1);
+        assert_eq!(ordered.rejected_events(), 1);
+    }
+
+    #[test]
+    fn test_sort_window_repairs_local_disorder() {
+        let (_file, inner) = unordered_source(&[100, 300, 200, 400, 350, 500, 50]);
+        let mut ordered = MonotonicDataSource::new(inner, OrderingPolicy::SortWindow(2)).unwrap();
+
+        let mut timestamps = Vec::new();
+        let mut symbols = Vec::new();
+        loop {
+            match ordered.next_event() {
+                Ok(Some(event)) => {
+                    timestamps.push(event.timestamp());
+                    symbols.push(ordered.last_symbol().unwrap().to_string());
+                }
+                Ok(None) => break,
+                // The last event is too late for a two-event window
+                Err(e) => assert!(matches!(e, DataError::InvalidTimestamp { timestamp: 50, .. })),
+            }
+        }
+        assert_eq!(timestamps, vec![100, 200, 300, 350, 400, 500]);
+        assert_eq!(symbols, vec!["AAPL", "AAPL", "MSFT", "AAPL", "MSFT", "MSFT"]);
+        assert_eq!(ordered.out_of_order_events(), 3);
+        assert_eq!(ordered.rejected_events(), 1);
+        assert!(ordered.is_finished());
+
+        assert!(MonotonicDataSource::new(ordered.into_inner(), OrderingPolicy::SortWindow(0)).is_err());
+    }
+
+    #[test]
+    fn test_external_sort_merges_runs() {
+        let timestamps: Vec<u128> = (0..50u128).map(|i| 1000 + (i * 37) % 50).chain([1010, 1010]).collect();
+        let (file, _) = unordered_source(&timestamps);
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("sorted.jsonl");
+
+        let report = ExternalSorter::new()
+            .with_temp_dir(dir.path())
+            .with_chunk_events(8)
+            .unwrap()
+            .sort_file(file.path(), &output)
+            .unwrap();
+        assert_eq!(report.events, 52);
+        assert_eq!(report.chunks, 7);
+        assert!(report.out_of_order > 0);
+        // Only the output is left behind
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        let mut sorted = JsonDataSource::new(&output).unwrap();
+        sorted.set_paused(true).unwrap();
+        let mut read_back = Vec::new();
+        while let Some(event) = sorted.next_event().unwrap() {
+            let qty = match event {
+                MarketEvent::Trade { qty, .. } => qty,
+                _ => unreachable!(),
+            };
+            read_back.push((event.timestamp(), qty, sorted.last_symbol().unwrap().to_string()));
+        }
+        assert_eq!(read_back.len(), 52);
+        assert!(read_back.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+
+        // Ties keep file order, and symbols survive the round trip
+        let ties: Vec<_> = read_back.iter().filter(|(ts, _, _)| *ts == 1010).collect();
+        assert_eq!(ties.iter().map(|(_, qty, _)| *qty).collect::<Vec<_>>(), vec![31, 51, 52]);
+        assert_eq!(ties[1].2, "AAPL");
+        assert_eq!(ties[2].2, "MSFT");
+
+        // A temporary sorted copy goes away with its source
+        drop(sorted.removing_file_on_drop());
+        assert!(!output.exists());
+    }
+}
+
+#[cfg(test)]
+mod heartbeat_tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+    use std::io::Write;
+
+    /// Trades at the given timestamps (nanoseconds)
+    fn sparse_source(timestamps: &[u128]) -> (NamedTempFile, Box<dyn DataSource>) {
+        let mut temp_file = NamedTempFile::with_suffix(".csv").unwrap();
+        writeln!(temp_file, "type,timestamp,price,qty,side").unwrap();
+        for ts in timestamps {
+            writeln!(temp_file, "trade,{},100.25,10,buy", ts).unwrap();
+        }
+        temp_file.flush().unwrap();
+
+        let mut source = DataFormatDetector::create_data_source(temp_file.path()).unwrap();
+        source.set_paused(true).unwrap();
+        (temp_file, source)
+    }
+
+    fn drain(source: &mut dyn DataSource) -> Vec<(bool, u128)> {
+        let mut events = Vec::new();
+        while let Some(event) = source.next_event().unwrap() {
+            events.push((matches!(event, MarketEvent::Heartbeat { .. }), event.timestamp()));
+        }
+        events
+    }
+
+    #[test]
+    fn test_heartbeats_fill_quiet_periods() {
+        let (_file, inner) = sparse_source(&[1_000, 1_050, 1_400]);
+        let mut source = HeartbeatDataSource::new(inner, Duration::from_nanos(100)).unwrap();
+        assert!(source.is_paused());
+
+        assert_eq!(
+            drain(&mut source),
+            vec![(false, 1_000), (false, 1_050), (true, 1_150), (true, 1_250), (true, 1_350), (false, 1_400)]
+        );
+        assert_eq!(source.heartbeats_sent(), 3);
+        assert!(source.is_finished());
+
+        // Reset replays the same stream
+        source.reset().unwrap();
+        assert_eq!(source.heartbeats_sent(), 0);
+        assert_eq!(drain(&mut source).len(), 6);
+    }
+
+    #[test]
+    fn test_heartbeat_round_trips_through_csv() {
+        let temp_file = NamedTempFile::with_suffix(".csv").unwrap();
+        let events = vec![
+            MarketEvent::Heartbeat { timestamp: 5_000 },
+            MarketEvent::Trade { price: 1_002_500, qty: 10, side: Side::Buy, timestamp: 6_000, trade_id: None },
+        ];
+        CsvDataSource::write_csv_file(temp_file.path(), &events).unwrap();
+
+        let mut source = CsvDataSource::new(temp_file.path()).unwrap();
+        source.set_paused(true).unwrap();
+        assert_eq!(source.next_event().unwrap(), Some(MarketEvent::Heartbeat { timestamp: 5_000 }));
+        assert!(!MarketEvent::Heartbeat { timestamp: 5_000 }.affects_book());
+
+        let (_file, inner) = sparse_source(&[1_000]);
+        assert!(HeartbeatDataSource::new(inner, Duration::ZERO).is_err());
+    }
+}
+
+#[cfg(test)]
+mod cache_tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+    use std::io::Write;
+
+    /// Trades at the given timestamps, cached in blocks of `block_events`
+    fn cached_source(timestamps: &[u128], block_events: usize, budget_blocks: usize) -> (NamedTempFile, CachedDataSource) {
+        let mut temp_file = NamedTempFile::with_suffix(".csv").unwrap();
+        writeln!(temp_file, "type,timestamp,price,qty,side").unwrap();
+        for (i, ts) in timestamps.iter().enumerate() {
+            writeln!(temp_file, "trade,{},100.25,{},buy", ts, i + 1).unwrap();
+        }
+        temp_file.flush().unwrap();
+
+        let inner = DataFormatDetector::create_data_source(temp_file.path()).unwrap();
+        let budget_bytes = budget_blocks * block_events * std::mem::size_of::<CachedEvent>();
+        let config = BlockCacheConfig { enabled: true, block_events, budget_bytes };
+        let mut source = CachedDataSource::new(inner, config).unwrap();
+        source.set_paused(true).unwrap();
+        (temp_file, source)
+    }
+
+    /// Quantity of each trade, which numbers them in file order
+    fn drain(source: &mut dyn DataSource) -> Vec<Qty> {
+        let mut trades = Vec::new();
+        while let Some(event) = source.next_event().unwrap() {
+            if let MarketEvent::Trade { qty, .. } = event {
+                trades.push(qty);
+            }
+        }
+        trades
+    }
+
+    #[test]
+    fn test_replays_come_from_memory() {
+        let timestamps: Vec<u128> = (0..10).map(|i| 1_000 + i * 10).collect();
+        let (_file, mut source) = cached_source(&timestamps, 4, 8);
+        assert_eq!(drain(&mut source), (1..=10).collect::<Vec<_>>());
+        assert!(source.is_finished());
+        assert_eq!(source.stats().events_read, 10);
+
+        // Rewinding and seeking replay the same events without parsing any
+        source.reset().unwrap();
+        assert!(!source.is_finished());
+        assert_eq!(drain(&mut source), (1..=10).collect::<Vec<_>>());
+        source.seek_to_time(1_045).unwrap();
+        assert_eq!(source.current_position(), Some(1_045));
+        assert_eq!(drain(&mut source), (6..=10).collect::<Vec<_>>());
+        assert!(source.seek_to_time(5_000).is_err());
+
+        let stats = source.stats();
+        assert_eq!(stats.events_read, 10);
+        assert_eq!((stats.blocks, stats.misses), (2, 0));
+        assert_eq!(stats.hits, 3);
+        assert_eq!(stats.hit_rate(), 1.0);
+        assert_eq!(source.metadata().properties["cache_hit_rate"], "1.000");
+    }
+
+    #[test]
+    fn test_evicted_blocks_are_reread_in_place() {
+        // Runs of equal timestamps straddle the block boundaries
+        let timestamps = [100, 100, 100, 100, 100, 200, 200, 300, 300, 300, 300, 400];
+        let (_file, mut source) = cached_source(&timestamps, 3, 1);
+        assert_eq!(drain(&mut source), (1..=12).collect::<Vec<_>>());
+        let stats = source.stats();
+        assert_eq!((stats.blocks, stats.evictions), (1, 3));
+
+        source.reset().unwrap();
+        assert_eq!(drain(&mut source), (1..=12).collect::<Vec<_>>());
+        source.seek_to_time(300).unwrap();
+        assert_eq!(drain(&mut source), (8..=12).collect::<Vec<_>>());
+
+        let stats = source.stats();
+        assert!(stats.misses >= 4);
+        assert!(stats.bytes <= 3 * std::mem::size_of::<CachedEvent>());
+    }
+
+    #[test]
+    fn test_unordered_data_rereads_from_the_start() {
+        let timestamps = [500, 400, 300, 200, 100, 600, 50];
+        let (_file, mut source) = cached_source(&timestamps, 2, 1);
+        assert_eq!(drain(&mut source), (1..=7).collect::<Vec<_>>());
+
+        // The first event at or after the target in file order
+        source.seek_to_time(350).unwrap();
+        assert_eq!(drain(&mut source), vec![1, 2, 3, 4, 5, 6, 7]);
+        source.seek_to_time(550).unwrap();
+        assert_eq!(drain(&mut source), vec![6, 7]);
+        source.reset().unwrap();
+        assert_eq!(drain(&mut source), (1..=7).collect::<Vec<_>>());
+
+        // Changing which events the source returns starts over
+        source.set_time_window(200, 500).unwrap();
+        assert_eq!(source.stats().blocks, 0);
+        assert_eq!(drain(&mut source), vec![1, 2, 3, 4]);
+        assert!(CachedDataSource::new(Box::new(source), BlockCacheConfig { block_events: 0, ..BlockCacheConfig::default() }).is_err());
+    }
+}
+
+#[cfg(test)]
+mod chain_tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+    use std::io::Write;
+
+    /// A day file of trades at the given timestamps
+    fn day_file(timestamps: &[u128]) -> NamedTempFile {
+        let mut temp_file = NamedTempFile::with_suffix(".csv").unwrap();
+        writeln!(temp_file, "type,timestamp,price,qty,side").unwrap();
+        for ts in timestamps {
+            writeln!(temp_file, "trade,{},100.25,10,buy", ts).unwrap();
+        }
+        temp_file.flush().unwrap();
+        temp_file
+    }
+
+    #[test]
+    fn test_chain_plays_files_in_order() {
+        let days = [day_file(&[100, 200]), day_file(&[1_000, 1_100]), day_file(&[2_000])];
+        let paths: Vec<&Path> = days.iter().map(|day| day.path()).collect();
+        let mut chain = ChainedDataSource::from_files(&paths).unwrap();
+        chain.set_paused(true).unwrap();
+
+        let mut timestamps = Vec::new();
+        while let Some(event) = chain.next_event().unwrap() {
+            timestamps.push(event.timestamp());
+        }
+        assert_eq!(timestamps, vec![100, 200, 1_000, 1_100, 2_000]);
+        assert!(chain.is_finished());
+        assert_eq!(chain.current_file(), 2);
+        assert_eq!(chain.files()[1].events, 2);
+        assert_eq!(chain.files()[1].first_timestamp, Some(1_000));
+        assert_eq!(chain.file_metadata().len(), 3);
+        assert_eq!(chain.metadata().properties.get("files").map(String::as_str), Some("3"));
+
+        // Seeking moves past the files that end before the target
+        chain.seek_to_time(1_050).unwrap();
+        assert_eq!(chain.current_file(), 1);
+        assert!(chain.previous_end.is_some_and(|end| (200..1_050).contains(&end)));
+        assert_eq!(chain.next_event().unwrap().unwrap().timestamp(), 1_100);
+    }
+
+    #[test]
+    fn test_chain_rejects_overlapping_files() {
+        let days = [day_file(&[100, 500]), day_file(&[400, 600])];
+        let paths: Vec<&Path> = days.iter().map(|day| day.path()).collect();
+        let mut chain = ChainedDataSource::from_files(&paths).unwrap();
+        chain.set_paused(true).unwrap();
+
+        assert_eq!(chain.next_event().unwrap().unwrap().timestamp(), 100);
+        assert_eq!(chain.next_event().unwrap().unwrap().timestamp(), 500);
+        assert!(matches!(chain.next_event(), Err(DataError::InvalidTimestamp { timestamp: 400, line: 2, .. })));
+        assert_eq!(chain.next_event().unwrap().unwrap().timestamp(), 600);
+        assert_eq!(chain.files()[1].overlapping, 1);
+
+        // Reset forgets the overlap
+        chain.reset().unwrap();
+        assert_eq!(chain.files()[1].overlapping, 0);
+        assert!(ChainedDataSource::new(Vec::new()).is_err());
+    }
+}
+
+#[cfg(test)]
+mod timestamp_tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+    use std::io::Write;
+
+    fn trades(timestamps: &[u128]) -> (NamedTempFile, Box<dyn DataSource>) {
+        let mut temp_file = NamedTempFile::with_suffix(".csv").unwrap();
+        writeln!(temp_file, "type,timestamp,price,qty,side").unwrap();
+        for ts in timestamps {
+            writeln!(temp_file, "trade,{},100.25,10,buy", ts).unwrap();
+        }
+        temp_file.flush().unwrap();
+        let source = DataFormatDetector::create_data_source(temp_file.path()).unwrap();
+        (temp_file, source)
+    }
+
+    fn drain(source: &mut dyn DataSource) -> Vec<u128> {
+        let mut timestamps = Vec::new();
+        while let Some(event) = source.next_event().unwrap() {
+            timestamps.push(event.timestamp());
+        }
+        timestamps
+    }
+
+    #[test]
+    fn test_units_are_detected_from_magnitude() {
+        let mut normalizer = TimestampNormalizer::new(TimestampConfig {
+            unit: TimestampUnit::Auto,
+            ..TimestampConfig::default()
+        }).unwrap();
+        // 2024-03-01 in milliseconds
+        assert_eq!(normalizer.normalize(1_709_251_200_000).unwrap(), 1_709_251_200_000_000_000);
+        assert_eq!(normalizer.unit(), Some(TimestampUnit::Milliseconds));
+
+        assert_eq!(TimestampUnit::detect(1_709_251_200, false), TimestampUnit::Seconds);
+        assert_eq!(TimestampUnit::detect(1_709_251_200_000_000, false), TimestampUnit::Microseconds);
+        assert_eq!(TimestampUnit::detect(1_709_251_200_000_000_000, false), TimestampUnit::Nanoseconds);
+        // 09:30 as microseconds since midnight
+        assert_eq!(TimestampUnit::detect(34_200_000_000, true), TimestampUnit::Microseconds);
+    }
+
+    #[test]
+    fn test_local_time_of_day_wraps_at_midnight() {
+        let mut normalizer = TimestampNormalizer::new(TimestampConfig {
+            unit: TimestampUnit::Seconds,
+            utc_offset_minutes: -300,
+            session_date: Some("2024-03-01".to_string()),
+            offset_ns: -500,
+        }).unwrap();
+        let midnight = date_to_ns("2024-03-01").unwrap();
+        let five_hours = 5 * 3_600 * 1_000_000_000;
+
+        // 23:00 New York is 04:00 UTC the next day
+        assert_eq!(normalizer.normalize(82_800).unwrap(), midnight + 82_800_000_000_000 + five_hours - 500);
+        // 00:30 after midnight belongs to the next day
+        assert_eq!(normalizer.normalize(1_800).unwrap(), midnight + NANOS_PER_DAY + 1_800_000_000_000 + five_hours - 500);
+
+        normalizer.reset();
+        assert_eq!(normalizer.normalize(1_800).unwrap(), midnight + 1_800_000_000_000 + five_hours - 500);
+
+        assert!(TimestampConfig { utc_offset_minutes: 900, ..TimestampConfig::default() }.validate().is_err());
+        assert!(TimestampConfig { session_date: Some("03/01/2024".to_string()), ..TimestampConfig::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_normalized_source_windows_in_normalized_time() {
+        let (_file, inner) = trades(&[1, 2, 3, 4]);
+        let config = TimestampConfig { unit: TimestampUnit::Seconds, ..TimestampConfig::default() };
+        let mut source = NormalizedDataSource::new(inner, config).unwrap();
+        source.set_paused(true).unwrap();
+
+        assert_eq!(drain(&mut source), vec![1_000_000_000, 2_000_000_000, 3_000_000_000, 4_000_000_000]);
+
+        source.set_time_window(2_000_000_000, 3_000_000_000).unwrap();
+        assert_eq!(drain(&mut source), vec![2_000_000_000, 3_000_000_000]);
+        assert!(source.is_finished());
+
+        source.seek_to_time(2_500_000_000).unwrap();
+        assert_eq!(source.next_event().unwrap().unwrap().timestamp(), 3_000_000_000);
+        assert!(source.seek_to_time(5_000_000_000).is_err());
+        assert_eq!(source.metadata().properties.get("timestamp_unit").map(String::as_str), Some("seconds"));
+    }
+
+    #[test]
+    fn test_normalized_source_seeks_through_the_inner_index() {
+        // Millisecond epoch timestamps, with early records stamped late so a
+        // seek that read from the start would stop on one of them
+        let start = 1_709_251_200_000u128;
+        let raw: Vec<u128> = (0..3000).map(|i| if (10..1000).contains(&i) { start + 10_000_000 } else { start + i }).collect();
+        let (_file, inner) = trades(&raw);
+        let config = TimestampConfig { unit: TimestampUnit::Auto, offset_ns: 250, ..TimestampConfig::default() };
+        let mut source = NormalizedDataSource::new(inner, config).unwrap();
+        source.set_paused(true).unwrap();
+
+        // Between two records, so the seek lands on the later one
+        let target = (start + 2499) * 1_000_000 + 500;
+        source.seek_to_time(target).unwrap();
+        assert_eq!(source.unit(), Some(TimestampUnit::Milliseconds));
+        assert_eq!(source.next_event().unwrap().unwrap().timestamp(), (start + 2500) * 1_000_000 + 250);
+        assert_eq!(source.last_line(), Some(2502));
+
+        source.seek_to_time(0).unwrap();
+        assert_eq!(source.next_event().unwrap().unwrap().timestamp(), start * 1_000_000 + 250);
+        assert!(source.seek_to_time((start + 3000) * 1_000_000).is_err());
+    }
+}
@@ -0,0 +1,390 @@
+//! Command-level engine debugger
+//!
+//! A [`JournalingEngine`] records every command the simulator sends the
+//! engine. An [`EngineDebugger`] replays such a journal into a fresh book one
+//! command at a time, so the depth and each order's place in the queue can be
+//! inspected at any point, and moves backwards by replaying from the start.
+//! [`bisect`] replays one journal under two engine settings in lockstep and
+//! finds the first command after which the books differ, comparing
+//! [`OrderBook::state_hash`] rather than whole books.
+
+use serde::{Deserialize, Serialize};
+use crate::config::SimulationConfig;
+use crate::data::DataSource;
+use crate::engine::{
+    CommandEngine, CrossedBookPolicy, DepthLimit, DepthSnapshot, EngineCommand, EngineEvent, OrderBook, OrderBookEngine,
+    OrderPreview, OrderState, StpGroup,
+};
+use crate::error::EngineResult;
+use crate::order_age::OrderAges;
+use crate::queue_fifo::FifoLevel;
+use crate::sim::Simulator;
+use crate::time::now_ns;
+use crate::types::{LotSize, Order, OrderId, Price, Qty};
+
+/// Wraps an engine and records every command sent to it
+#[derive(Debug)]
+pub struct JournalingEngine<E> {
+    inner: E,
+    journal: Vec<EngineCommand>,
+}
+
+impl<E: CommandEngine> JournalingEngine<E> {
+    /// Record the commands sent to `inner`
+    pub fn new(inner: E) -> Self {
+        Self { inner, journal: Vec::new() }
+    }
+
+    /// Commands recorded so far, oldest first
+    pub fn journal(&self) -> &[EngineCommand] {
+        &self.journal
+    }
+
+    /// Take the recorded commands
+    pub fn into_journal(self) -> Vec<EngineCommand> {
+        self.journal
+    }
+}
+
+impl<E: CommandEngine> CommandEngine for JournalingEngine<E> {
+    fn execute(&mut self, command: EngineCommand) -> EngineResult<EngineEvent> {
+        self.journal.push(command.clone());
+        self.inner.execute(command)
+    }
+
+    fn best_bid_ask(&self) -> (Option<Price>, Option<Price>) {
+        self.inner.best_bid_ask()
+    }
+
+    fn depth_snapshot(&self) -> DepthSnapshot {
+        self.inner.depth_snapshot()
+    }
+
+    fn depth_snapshot_top(&self, max_levels: usize) -> DepthSnapshot {
+        self.inner.depth_snapshot_top(max_levels)
+    }
+
+    fn open_orders(&self) -> Vec<Order> {
+        self.inner.open_orders()
+    }
+
+    fn order_priority_fee(&self, order_id: OrderId) -> u64 {
+        self.inner.order_priority_fee(order_id)
+    }
+
+    fn order_resting_qty(&self, order_id: OrderId) -> Option<Qty> {
+        self.inner.order_resting_qty(order_id)
+    }
+
+    fn open_order_ages(&self, now: u128) -> OrderAges {
+        self.inner.open_order_ages(now)
+    }
+
+    fn preview_order(&self, order: Order, stp_group: Option<StpGroup>) -> EngineResult<OrderPreview> {
+        self.inner.preview_order(order, stp_group)
+    }
+}
+
+/// Record the engine commands a replay of `source` produces
+pub fn journal_replay(source: Box<dyn DataSource>) -> EngineResult<Vec<EngineCommand>> {
+    let engine = JournalingEngine::new(OrderBook::<FifoLevel>::new());
+    let mut simulator = Simulator::new(engine).with_replay_source(source);
+    simulator.advance_replay(u128::MAX)?;
+    Ok(simulator.engine.into_journal())
+}
+
+/// Engine settings a journal is replayed under
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EngineSettings {
+    pub lot_size: LotSize,
+    pub depth_limit: DepthLimit,
+    pub crossed_book_policy: CrossedBookPolicy,
+}
+
+impl EngineSettings {
+    /// The engine settings of a simulation configuration
+    pub fn from_simulation(config: &SimulationConfig) -> Self {
+        Self {
+            lot_size: config.lot_size,
+            depth_limit: config.depth_limit,
+            crossed_book_policy: config.crossed_book_policy,
+        }
+    }
+
+    /// A fresh book with the settings applied
+    pub fn book(&self) -> OrderBook<FifoLevel> {
+        let mut book = OrderBook::new();
+        for command in self.commands() {
+            let _ = book.execute(command);
+        }
+        book
+    }
+
+    fn commands(&self) -> [EngineCommand; 3] {
+        [
+            EngineCommand::SetLotSize(self.lot_size),
+            EngineCommand::SetDepthLimit(self.depth_limit),
+            EngineCommand::SetCrossedBookPolicy(self.crossed_book_policy),
+        ]
+    }
+}
+
+/// One command applied by the debugger
+#[derive(Debug, Clone)]
+pub struct DebugStep {
+    /// Position of the command in the journal
+    pub index: usize,
+    pub command: EngineCommand,
+    /// What the engine reported, or why it refused the command
+    pub outcome: Result<EngineEvent, String>,
+    /// Book state hash after the command
+    pub state_hash: u64,
+}
+
+/// Replays a journal into a fresh book one command at a time
+pub struct EngineDebugger {
+    journal: Vec<EngineCommand>,
+    settings: EngineSettings,
+    book: OrderBook<FifoLevel>,
+    position: usize,
+}
+
+impl EngineDebugger {
+    /// Start before the first command, with `settings` applied
+    pub fn new(journal: Vec<EngineCommand>, settings: EngineSettings) -> Self {
+        let mut debugger = Self {
+            journal,
+            settings,
+            book: OrderBook::new(),
+            position: 0,
+        };
+        debugger.restart();
+        debugger
+    }
+
+    /// Start at a checkpoint: after the first `checkpoint` commands
+    pub fn at(journal: Vec<EngineCommand>, settings: EngineSettings, checkpoint: usize) -> Self {
+        let mut debugger = Self::new(journal, settings);
+        debugger.seek(checkpoint);
+        debugger
+    }
+
+    /// Carry on from a book restored some other way, taken to stand after
+    /// the first `position` commands
+    pub fn resume(journal: Vec<EngineCommand>, settings: EngineSettings, book: OrderBook<FifoLevel>, position: usize) -> Self {
+        let position = position.min(journal.len());
+        Self { journal, settings, book, position }
+    }
+
+    /// Give up the book as it stands
+    pub fn into_book(self) -> OrderBook<FifoLevel> {
+        self.book
+    }
+
+    /// Commands applied so far
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Commands in the journal
+    pub fn len(&self) -> usize {
+        self.journal.len()
+    }
+
+    /// Whether the journal has no commands
+    pub fn is_empty(&self) -> bool {
+        self.journal.is_empty()
+    }
+
+    /// Whether every command has been applied
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.journal.len()
+    }
+
+    /// The command the next step applies
+    pub fn next_command(&self) -> Option<&EngineCommand> {
+        self.journal.get(self.position)
+    }
+
+    /// Apply the next command
+    pub fn step(&mut self) -> Option<DebugStep> {
+        let command = self.journal.get(self.position)?.clone();
+        let outcome = self.apply(command.clone()).map_err(|e| e.to_string());
+        let step = DebugStep { index: self.position, command, outcome, state_hash: self.state_hash() };
+        self.position += 1;
+        Some(step)
+    }
+
+    /// Undo the last command by replaying everything before it
+    pub fn step_back(&mut self) -> bool {
+        if self.position == 0 {
+            return false;
+        }
+        self.seek(self.position - 1);
+        true
+    }
+
+    /// Move to just after the first `position` commands (clamped to the
+    /// journal), replaying from the start to go backwards
+    pub fn seek(&mut self, position: usize) {
+        let position = position.min(self.journal.len());
+        if position < self.position {
+            self.restart();
+        }
+        while self.position < position {
+            let command = self.journal[self.position].clone();
+            let _ = self.apply(command);
+            self.position += 1;
+        }
+    }
+
+    /// Book as it stands
+    pub fn book(&self) -> &OrderBook<FifoLevel> {
+        &self.book
+    }
+
+    /// Depth as it stands
+    pub fn depth(&self) -> DepthSnapshot {
+        self.book.depth_snapshot()
+    }
+
+    /// Where an order stands, if it is resting
+    pub fn order(&self, order_id: OrderId) -> Option<OrderState> {
+        self.book.order_state(order_id)
+    }
+
+    /// Book state hash as it stands
+    pub fn state_hash(&self) -> u64 {
+        self.book.state_hash()
+    }
+
+    fn restart(&mut self) {
+        self.book = self.settings.book();
+        self.position = 0;
+    }
+
+    /// Apply a command, restamping orders and moving the book's clock with
+    /// them, since the engine checks their timestamps against its clock or
+    /// the wall clock and the journal's may be far from either
+    fn apply(&mut self, command: EngineCommand) -> EngineResult<EngineEvent> {
+        let command = match command {
+            EngineCommand::Place(mut order) => {
+                order.ts = now_ns();
+                self.book.set_clock(order.ts);
+                EngineCommand::Place(order)
+            }
+            EngineCommand::Quote(mut quote) => {
+                quote.ts = now_ns();
+                self.book.set_clock(quote.ts);
+                EngineCommand::Quote(quote)
+            }
+            command => command,
+        };
+        self.book.execute(command)
+    }
+}
+
+/// First command after which two replays of a journal differ
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    /// Position of the command in the journal
+    pub index: usize,
+    pub command: EngineCommand,
+    /// State hashes after the command under each setting
+    pub left_hash: u64,
+    pub right_hash: u64,
+}
+
+/// Find the first command after which replaying `journal` under `left` and
+/// `right` leaves different books
+///
+/// Both books step through the journal together and are compared after every
+/// command, so this is a single replay, and books that diverge and later
+/// converge again are still caught. `None` means they match throughout.
+pub fn bisect(journal: &[EngineCommand], left: EngineSettings, right: EngineSettings) -> Option<Divergence> {
+    let mut left_book = EngineDebugger::new(journal.to_vec(), left);
+    let mut right_book = EngineDebugger::new(journal.to_vec(), right);
+    while let (Some(left_step), Some(right_step)) = (left_book.step(), right_book.step()) {
+        if left_step.state_hash != right_step.state_hash {
+            return Some(Divergence {
+                index: left_step.index,
+                command: left_step.command,
+                left_hash: left_step.state_hash,
+                right_hash: right_step.state_hash,
+            });
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Order, Side};
+
+    fn journal() -> Vec<EngineCommand> {
+        let ts = now_ns();
+        vec![
+            EngineCommand::Place(Order::new_limit(1, Side::Sell, 100, 101_0000, ts)),
+            EngineCommand::Place(Order::new_limit(2, Side::Sell, 50, 101_0000, ts)),
+            EngineCommand::Place(Order::new_limit(3, Side::Buy, 30, 100_0000, ts)),
+            EngineCommand::Place(Order::new_limit(4, Side::Sell, 70, 103_0000, ts)),
+            EngineCommand::Place(Order::new_market(5, Side::Buy, 120, ts)),
+            EngineCommand::Cancel(3),
+        ]
+    }
+
+    #[test]
+    fn test_steps_forward_and_back_through_the_journal() {
+        let mut debugger = EngineDebugger::at(journal(), EngineSettings::default(), 2);
+        assert_eq!(debugger.position(), 2);
+        let order = debugger.order(2).unwrap();
+        assert_eq!((order.qty, order.queue_position), (50, 1));
+
+        let checkpoint_hash = debugger.state_hash();
+        debugger.seek(4);
+        let step = debugger.step().unwrap();
+        assert_eq!(step.index, 4);
+        assert!(matches!(step.outcome, Ok(EngineEvent::Placed(ref trades)) if trades.len() == 2));
+        // The market order took order 1 and part of order 2, which moves up
+        assert_eq!(debugger.order(1), None);
+        assert_eq!(debugger.order(2).map(|order| (order.qty, order.queue_position)), Some((30, 0)));
+
+        // Going back replays the same commands to the same state
+        debugger.seek(2);
+        assert_eq!(debugger.state_hash(), checkpoint_hash);
+        assert!(debugger.step_back());
+        assert_eq!(debugger.depth().asks.len(), 1);
+        debugger.seek(usize::MAX);
+        assert!(debugger.is_finished());
+        assert!(debugger.step().is_none());
+    }
+
+    #[test]
+    fn test_bisect_finds_first_divergent_command() {
+        let journal = journal();
+        assert!(bisect(&journal, EngineSettings::default(), EngineSettings::default()).is_none());
+
+        // Keeping one ask level turns away the order two levels out
+        let shallow = EngineSettings { depth_limit: DepthLimit::levels(1), ..EngineSettings::default() };
+        let divergence = bisect(&journal, EngineSettings::default(), shallow).unwrap();
+        assert_eq!(divergence.index, 3);
+        assert!(matches!(divergence.command, EngineCommand::Place(ref order) if order.id == 4));
+        assert_ne!(divergence.left_hash, divergence.right_hash);
+
+        // Cancelling the turned-away order brings the books back together,
+        // which still counts as having diverged
+        let mut converging = journal.clone();
+        converging.push(EngineCommand::Cancel(4));
+        assert_eq!(bisect(&converging, EngineSettings::default(), shallow).map(|divergence| divergence.index), Some(3));
+    }
+
+    #[test]
+    fn test_journaling_engine_records_commands() {
+        let mut engine = JournalingEngine::new(OrderBook::<FifoLevel>::new());
+        engine.submit(Order::new_limit(1, Side::Buy, 10, 100_0000, now_ns())).unwrap();
+        engine.withdraw(1).unwrap();
+        assert!(matches!(engine.journal(), [EngineCommand::Place(_), EngineCommand::Cancel(1)]));
+        assert_eq!(engine.depth_snapshot().bids.len(), 0);
+    }
+}
@@ -0,0 +1,193 @@
+//! Graceful degradation when steps overrun their budget
+//!
+//! Each simulation step has the step interval to finish in. When steps keep
+//! taking longer than that, the server falls behind however much it warns
+//! about it. With degradation enabled, a run of over-budget steps puts the
+//! server in degraded mode: the simulator builds snapshots of fewer levels
+//! and without order ages, which shortens the steps themselves, the server
+//! pauses feature and depth tensor export, and `/health` reports `DEGRADED`. A
+//! longer run of steps well inside the budget restores full service, so a
+//! server near its limit does not flap between the two.
+
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+
+/// When to degrade, when to recover and what to shed
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DegradationConfig {
+    /// Degrade under sustained overload
+    pub enabled: bool,
+    /// Consecutive steps over the interval that put the server in degraded mode
+    pub trigger_steps: u32,
+    /// Consecutive steps under `recover_below` that restore full service
+    pub recover_steps: u32,
+    /// Share of the interval a step must finish within to count towards
+    /// recovery
+    pub recover_below: f64,
+    /// Levels a side published while degraded
+    pub max_levels: usize,
+    /// Pause feature and depth tensor export while degraded
+    pub pause_analytics: bool,
+}
+
+impl Default for DegradationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            trigger_steps: 5,
+            recover_steps: 50,
+            recover_below: 0.5,
+            max_levels: 10,
+            pause_analytics: true,
+        }
+    }
+}
+
+impl DegradationConfig {
+    /// Check the step counts, recovery share and level cap
+    pub fn validate(&self) -> Result<(), String> {
+        if self.trigger_steps == 0 || self.recover_steps == 0 {
+            return Err("Degradation trigger and recovery steps must be greater than 0".to_string());
+        }
+        if !(self.recover_below > 0.0 && self.recover_below <= 1.0) {
+            return Err("Degradation recovery share must be above 0 and at most 1".to_string());
+        }
+        if self.max_levels == 0 {
+            return Err("Degraded depth must be at least 1 level".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The server entering or leaving degraded mode
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DegradationChange {
+    /// Whether the server is degraded after the change
+    pub degraded: bool,
+    /// Steps in the run that caused it
+    pub steps: u32,
+    /// Last step's time as a share of the interval
+    pub load: f64,
+}
+
+impl std::fmt::Display for DegradationChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.degraded {
+            true => write!(
+                f,
+                "Degraded after {} steps over the interval (last at {:.0}%): shedding depth and analytics",
+                self.steps, self.load * 100.0,
+            ),
+            false => write!(
+                f,
+                "Recovered after {} steps inside the budget (last at {:.0}%): full depth and analytics restored",
+                self.steps, self.load * 100.0,
+            ),
+        }
+    }
+}
+
+/// Where the server stands against its step budget
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct DegradationStatus {
+    pub degraded: bool,
+    /// Consecutive steps over the interval
+    pub overrun_streak: u32,
+    /// Consecutive steps inside the recovery share, while degraded
+    pub recovery_streak: u32,
+    /// Times the server has entered degraded mode
+    pub episodes: u64,
+    /// Steps spent degraded
+    pub degraded_steps: u64,
+}
+
+/// Watches step times against the interval and decides when to degrade
+#[derive(Debug, Clone)]
+pub struct StepBudget {
+    config: DegradationConfig,
+    status: DegradationStatus,
+}
+
+impl StepBudget {
+    pub fn new(config: DegradationConfig) -> Self {
+        Self { config, status: DegradationStatus::default() }
+    }
+
+    /// Get the thresholds and what is shed
+    pub fn config(&self) -> &DegradationConfig {
+        &self.config
+    }
+
+    pub fn is_degraded(&self) -> bool {
+        self.status.degraded
+    }
+
+    pub fn status(&self) -> DegradationStatus {
+        self.status
+    }
+
+    /// Record how long a step took against its interval, returning the
+    /// change if the server enters or leaves degraded mode
+    pub fn observe(&mut self, step: Duration, interval: Duration) -> Option<DegradationChange> {
+        let load = step.as_secs_f64() / interval.as_secs_f64().max(f64::MIN_POSITIVE);
+        let status = &mut self.status;
+        status.overrun_streak = if load > 1.0 { status.overrun_streak + 1 } else { 0 };
+        if !status.degraded {
+            if status.overrun_streak < self.config.trigger_steps {
+                return None;
+            }
+            status.degraded = true;
+            status.episodes += 1;
+            status.degraded_steps += 1;
+            status.recovery_streak = 0;
+            return Some(DegradationChange { degraded: true, steps: status.overrun_streak, load });
+        }
+
+        status.degraded_steps += 1;
+        status.recovery_streak = if load < self.config.recover_below { status.recovery_streak + 1 } else { 0 };
+        if status.recovery_streak < self.config.recover_steps {
+            return None;
+        }
+        status.degraded = false;
+        let steps = std::mem::take(&mut status.recovery_streak);
+        Some(DegradationChange { degraded: false, steps, load })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sustained_overruns_degrade_until_load_drops() {
+        let config = DegradationConfig { enabled: true, trigger_steps: 3, recover_steps: 4, ..DegradationConfig::default() };
+        let mut budget = StepBudget::new(config);
+        let interval = Duration::from_millis(10);
+        let slow = Duration::from_millis(15);
+        let fast = Duration::from_millis(2);
+
+        // A single slow step is not overload
+        assert!(budget.observe(slow, interval).is_none());
+        assert!(budget.observe(fast, interval).is_none());
+        assert!(budget.observe(slow, interval).is_none());
+        assert!(budget.observe(slow, interval).is_none());
+        let change = budget.observe(slow, interval).unwrap();
+        assert!(change.degraded && budget.is_degraded());
+        assert_eq!(change.steps, 3);
+
+        // Steps inside the interval but above the recovery share keep it degraded
+        for _ in 0..10 {
+            assert!(budget.observe(Duration::from_millis(8), interval).is_none());
+        }
+        for _ in 0..3 {
+            assert!(budget.observe(fast, interval).is_none());
+        }
+        let change = budget.observe(fast, interval).unwrap();
+        assert!(!change.degraded && !budget.is_degraded());
+        assert_eq!(budget.status().episodes, 1);
+        assert_eq!(budget.status().degraded_steps, 15);
+
+        assert!(DegradationConfig { recover_below: 0.0, ..DegradationConfig::default() }.validate().is_err());
+    }
+}
@@ -0,0 +1,377 @@
+//! Depth-over-time tensor for 3D book visualizations
+//!
+//! A [`DepthTensorRecorder`] samples the book on a fixed grid of simulation
+//! time, the way the feature exporter does, and keeps the latest `window`
+//! frames. On request it lays them out as a dense time × price grid of
+//! resting quantity per side, a [`DepthTensor`], which 3D frontends can draw
+//! as a surface without any further bucketing.
+//!
+//! The price axis has a fixed number of buckets centred on the latest mid, so
+//! the tensor's size depends only on the configuration. Depth outside the
+//! axis is left out.
+//!
+//! [`DepthTensor::to_bytes`] encodes it as a little-endian binary blob:
+//!
+//! | Field          | Type               |
+//! |----------------|--------------------|
+//! | magic `ODT1`   | 4 bytes            |
+//! | frames         | u32                |
+//! | buckets        | u32                |
+//! | bucket ticks   | u64                |
+//! | lowest price   | u64                |
+//! | timestamps     | u64 × frames       |
+//! | bid quantities | u64 × frames × buckets, frame-major |
+//! | ask quantities | u64 × frames × buckets, frame-major |
+
+use std::collections::VecDeque;
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::engine::{BookLevelPoint, DepthSnapshot};
+use crate::time::ms_to_ns;
+use crate::types::{Price, Qty, Side};
+
+/// Magic bytes opening an encoded tensor
+pub const DEPTH_TENSOR_MAGIC: &[u8; 4] = b"ODT1";
+
+/// Largest tensor, in cells per side, a configuration may ask for
+const MAX_CELLS: usize = 4_000_000;
+
+/// Sampling grid and bounds of the depth tensor
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DepthTensorConfig {
+    /// Record the tensor while the server runs
+    pub enabled: bool,
+    /// Milliseconds of simulation time between frames
+    pub interval_ms: u64,
+    /// Frames kept; older ones are dropped
+    pub window: usize,
+    /// Price buckets on the price axis
+    pub price_buckets: usize,
+    /// Width of a price bucket in ticks
+    pub bucket_ticks: Price,
+}
+
+impl Default for DepthTensorConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_ms: 100,
+            window: 600,
+            price_buckets: 200,
+            bucket_ticks: 100,
+        }
+    }
+}
+
+impl DepthTensorConfig {
+    /// Check the grid is non-empty and the tensor bounded
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval_ms == 0 {
+            return Err("Depth tensor interval must be greater than 0".to_string());
+        }
+        if self.window == 0 {
+            return Err("Depth tensor window must keep at least 1 frame".to_string());
+        }
+        if self.price_buckets == 0 || self.bucket_ticks == 0 {
+            return Err("Depth tensor price buckets and bucket ticks must be greater than 0".to_string());
+        }
+        if self.window.saturating_mul(self.price_buckets) > MAX_CELLS {
+            return Err(format!(
+                "Depth tensor window × price buckets must be at most {}, got {} × {}",
+                MAX_CELLS, self.window, self.price_buckets
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The book at one grid point
+#[derive(Debug, Clone)]
+struct Frame {
+    ts: u128,
+    mid: Option<Price>,
+    bids: Vec<(Price, Qty)>,
+    asks: Vec<(Price, Qty)>,
+}
+
+impl Frame {
+    fn at(ts: u128, snapshot: &DepthSnapshot) -> Self {
+        let levels = |levels: &[BookLevelPoint]| levels.iter().map(|level| (level.price, level.qty)).collect();
+        let mid = match (snapshot.best_bid, snapshot.best_ask) {
+            (Some(bid), Some(ask)) => Some((bid + ask) / 2),
+            (bid, ask) => bid.or(ask),
+        };
+        Self { ts, mid, bids: levels(&snapshot.bids), asks: levels(&snapshot.asks) }
+    }
+}
+
+/// Resting quantity over a time × price grid
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DepthTensor {
+    /// Grid time of each frame (simulation nanoseconds), oldest first
+    pub timestamps: Vec<u128>,
+    pub buckets: usize,
+    pub bucket_ticks: Price,
+    /// Lowest price of the first bucket
+    pub price_origin: Price,
+    /// Bid quantity per frame and bucket, frame-major
+    pub bids: Vec<Qty>,
+    /// Ask quantity per frame and bucket, frame-major
+    pub asks: Vec<Qty>,
+}
+
+impl DepthTensor {
+    /// Number of frames
+    pub fn frames(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    /// Lowest price of bucket `bucket`
+    pub fn bucket_price(&self, bucket: usize) -> Price {
+        self.price_origin + bucket as Price * self.bucket_ticks
+    }
+
+    /// Quantity on `side` in one cell
+    pub fn qty(&self, side: Side, frame: usize, bucket: usize) -> Qty {
+        let cells = match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks,
+        };
+        cells[frame * self.buckets + bucket]
+    }
+
+    /// Encode as the binary blob described in the module docs
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(28 + 8 * (self.frames() + self.bids.len() + self.asks.len()));
+        bytes.extend_from_slice(DEPTH_TENSOR_MAGIC);
+        bytes.extend_from_slice(&(self.frames() as u32).to_le_bytes());
+        bytes.extend_from_slice(&(self.buckets as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.bucket_ticks.to_le_bytes());
+        bytes.extend_from_slice(&self.price_origin.to_le_bytes());
+        for &ts in &self.timestamps {
+            bytes.extend_from_slice(&(ts as u64).to_le_bytes());
+        }
+        for &qty in self.bids.iter().chain(&self.asks) {
+            bytes.extend_from_slice(&qty.to_le_bytes());
+        }
+        bytes
+    }
+}
+
+/// Keeps a bounded window of book frames on a simulation-time grid
+#[derive(Debug, Clone)]
+pub struct DepthTensorRecorder {
+    config: DepthTensorConfig,
+    interval_ns: u128,
+    frames: VecDeque<Frame>,
+    /// Next grid time to record
+    next_ts: Option<u128>,
+    /// Book as of the latest snapshot
+    latest: Option<Frame>,
+    /// Simulation time of the latest snapshot
+    latest_ts: Option<u128>,
+}
+
+impl DepthTensorRecorder {
+    /// An empty recorder; `config` should pass [`DepthTensorConfig::validate`]
+    pub fn new(config: DepthTensorConfig) -> Self {
+        Self {
+            interval_ns: ms_to_ns(config.interval_ms.max(1)),
+            frames: VecDeque::with_capacity(config.window),
+            next_ts: None,
+            latest: None,
+            latest_ts: None,
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &DepthTensorConfig {
+        &self.config
+    }
+
+    /// Take in a snapshot, recording a frame for every grid point up to its
+    /// time, and returning how many were recorded
+    ///
+    /// Grid points before the snapshot carry the previous snapshot's book;
+    /// a grid point at the snapshot's own time uses the new one. After a
+    /// jump only the last window's worth of grid points is filled, and a
+    /// snapshot from before the latest one, as after a seek back, starts
+    /// the recorder over.
+    pub fn record(&mut self, snapshot: &DepthSnapshot) -> usize {
+        if self.latest_ts.is_some_and(|latest_ts| snapshot.ts < latest_ts) {
+            self.clear();
+        }
+        self.latest_ts = Some(snapshot.ts);
+        let interval_ns = self.interval_ns;
+        let mut ts = *self.next_ts.get_or_insert_with(|| snapshot.ts.div_ceil(interval_ns) * interval_ns);
+        // Grid points before the snapshot, of which only those that still
+        // fit in the window next to its own frame are worth filling
+        let gap = snapshot.ts.saturating_sub(ts);
+        let behind = gap.div_ceil(interval_ns);
+        let room = self.config.window as u128 - u128::from(gap.is_multiple_of(interval_ns));
+        if behind > room {
+            ts += (behind - room) * interval_ns;
+        }
+        let mut recorded = 0;
+        while ts < snapshot.ts {
+            if let Some(latest) = &self.latest {
+                let frame = Frame { ts, ..latest.clone() };
+                self.push(frame);
+                recorded += 1;
+            }
+            ts += interval_ns;
+        }
+
+        let frame = Frame::at(ts, snapshot);
+        if ts == snapshot.ts {
+            self.push(frame.clone());
+            recorded += 1;
+            ts += interval_ns;
+        }
+        self.latest = Some(frame);
+        self.next_ts = Some(ts);
+        recorded
+    }
+
+    fn push(&mut self, frame: Frame) {
+        if self.frames.len() == self.config.window {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(frame);
+    }
+
+    /// Number of frames kept
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Whether no frame has been recorded
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Drop every frame and start the grid again at the next snapshot
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.next_ts = None;
+        self.latest = None;
+        self.latest_ts = None;
+    }
+
+    /// Lay the kept frames out on a price axis centred on the latest mid
+    pub fn tensor(&self) -> DepthTensor {
+        let buckets = self.config.price_buckets;
+        let bucket_ticks = self.config.bucket_ticks;
+        let center = self.frames.iter().rev().find_map(|frame| frame.mid).unwrap_or(0);
+        let price_origin = (center / bucket_ticks * bucket_ticks).saturating_sub(buckets as Price / 2 * bucket_ticks);
+
+        let mut tensor = DepthTensor {
+            timestamps: self.frames.iter().map(|frame| frame.ts).collect(),
+            buckets,
+            bucket_ticks,
+            price_origin,
+            bids: vec![0; self.frames.len() * buckets],
+            asks: vec![0; self.frames.len() * buckets],
+        };
+        let bucket_of = |price: Price| {
+            let bucket = (price.checked_sub(price_origin)? / bucket_ticks) as usize;
+            (bucket < buckets).then_some(bucket)
+        };
+        for (index, frame) in self.frames.iter().enumerate() {
+            let row = index * buckets;
+            for (levels, cells) in [(&frame.bids, &mut tensor.bids), (&frame.asks, &mut tensor.asks)] {
+                for &(price, qty) in levels {
+                    if let Some(bucket) = bucket_of(price) {
+                        cells[row + bucket] += qty;
+                    }
+                }
+            }
+        }
+        tensor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{OrderBook, OrderBookEngine};
+    use crate::queue_fifo::FifoLevel;
+    use crate::types::Order;
+
+    const MS: u128 = 1_000_000;
+
+    fn snapshot(ts: u128, bids: &[(Price, Qty)], asks: &[(Price, Qty)]) -> DepthSnapshot {
+        let mut book = OrderBook::<FifoLevel>::new();
+        book.set_clock(0);
+        let orders = bids.iter().map(|&level| (Side::Buy, level)).chain(asks.iter().map(|&level| (Side::Sell, level)));
+        for (id, (side, (price, qty))) in orders.enumerate() {
+            book.place(Order::new_limit(id as u64 + 1, side, qty, price, 0)).unwrap();
+        }
+        let mut snapshot = book.snapshot();
+        snapshot.ts = ts;
+        snapshot
+    }
+
+    fn config(window: usize, price_buckets: usize) -> DepthTensorConfig {
+        DepthTensorConfig { enabled: true, interval_ms: 100, window, price_buckets, bucket_ticks: 100 }
+    }
+
+    #[test]
+    fn test_frames_fill_the_grid_and_roll_off() {
+        let mut recorder = DepthTensorRecorder::new(config(3, 4));
+        assert_eq!(recorder.record(&snapshot(100 * MS, &[(99_900, 10)], &[(100_100, 20)])), 1);
+        // Three grid points elapse; the first two carry the earlier book
+        assert_eq!(recorder.record(&snapshot(400 * MS, &[(99_900, 15), (99_850, 5)], &[(100_100, 20)])), 3);
+        assert_eq!(recorder.len(), 3);
+
+        // Mid 100_000 centres four buckets from 99_800
+        let tensor = recorder.tensor();
+        assert_eq!(tensor.timestamps, vec![200 * MS, 300 * MS, 400 * MS]);
+        assert_eq!((tensor.price_origin, tensor.bucket_price(1)), (99_800, 99_900));
+        assert_eq!(tensor.qty(Side::Buy, 0, 1), 10);
+        assert_eq!(tensor.qty(Side::Buy, 2, 1), 15);
+        // 99_850 falls in the 99_800 bucket
+        assert_eq!(tensor.qty(Side::Buy, 2, 0), 5);
+        assert_eq!(tensor.qty(Side::Sell, 2, 3), 20);
+        assert_eq!(tensor.bids.len(), 12);
+
+        // A jump of a day fills no more than the window
+        assert_eq!(recorder.record(&snapshot(86_400_000 * MS, &[(99_900, 1)], &[(100_100, 1)])), 3);
+        assert_eq!(recorder.tensor().timestamps, vec![86_399_800 * MS, 86_399_900 * MS, 86_400_000 * MS]);
+        assert_eq!(recorder.tensor().qty(Side::Buy, 0, 1), 15);
+
+        // Going back in time starts over from the earlier snapshot
+        assert_eq!(recorder.record(&snapshot(200 * MS, &[(99_900, 4)], &[(100_100, 4)])), 1);
+        assert_eq!(recorder.tensor().timestamps, vec![200 * MS]);
+        assert_eq!(recorder.record(&snapshot(300 * MS, &[(99_900, 4)], &[(100_100, 4)])), 1);
+
+        recorder.clear();
+        assert!(recorder.tensor().timestamps.is_empty());
+    }
+
+    #[test]
+    fn test_binary_layout() {
+        let mut recorder = DepthTensorRecorder::new(config(10, 3));
+        recorder.record(&snapshot(100 * MS, &[(99_900, 7)], &[(100_100, 9), (250_000, 1)]));
+        let bytes = recorder.tensor().to_bytes();
+
+        assert_eq!(&bytes[..4], DEPTH_TENSOR_MAGIC);
+        let u32_at = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        let u64_at = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+        assert_eq!((u32_at(4), u32_at(8)), (1, 3));
+        assert_eq!((u64_at(12), u64_at(20)), (100, 99_900));
+        assert_eq!(u64_at(28), 100_000_000);
+        // Bids then asks; the far ask is off the axis
+        let cells: Vec<u64> = (0..6).map(|cell| u64_at(36 + 8 * cell)).collect();
+        assert_eq!(cells, vec![7, 0, 0, 0, 0, 9]);
+        assert_eq!(bytes.len(), 84);
+    }
+
+    #[test]
+    fn test_config_bounds() {
+        assert!(DepthTensorConfig::default().validate().is_ok());
+        assert!(config(0, 10).validate().is_err());
+        assert!(config(10, 0).validate().is_err());
+        assert!(config(100_000, 1_000).validate().is_err());
+    }
+}
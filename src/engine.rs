@@ -1,35 +1,364 @@
-use std::collections::{BTreeMap, HashMap};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::cmp::Reverse;
 use std::sync::Arc;
 use std::time::Instant;
-use crate::types::{Order, OrderId, OrderType, Price, Qty, Side, Trade};
+use crate::types::{order_ids, price_utils, ExecutionSummary, LotSize, Order, OrderId, OrderType, Price, Qty, Side, Trade};
 use crate::error::{EngineError, EngineResult};
-use crate::queue::QueueDiscipline;
-use crate::time::now_ns;
-use crate::metrics::PerformanceMetrics;
-use crate::memory::CircularBuffer;
+use crate::queue::{MatchFilter, QueueDiscipline};
+use crate::queue_fifo::FifoLevel;
+use crate::time::{now_ns, WallClockIsolation};
+use crate::metrics::{PerformanceMetrics, PipelineWatermark};
+use crate::memory::{shrink_map, shrink_vec, CircularBuffer, Compaction};
+use crate::agents::AgentStats;
+use crate::auction::AuctionImbalance;
+use crate::order_age::{OrderAges, SideAges};
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+
+/// Largest quantity a single order may carry
+pub const MAX_QTY: Qty = 1_000_000_000;
+
+/// Lowest limit price an order may carry, in ticks
+pub const MIN_PRICE: Price = 1;
+
+/// Highest limit price an order may carry, in ticks ($10,000)
+pub const MAX_PRICE: Price = 100_000_000;
 
 /// Market data snapshot for visualization and analysis
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BookLevelPoint {
+    #[serde(serialize_with = "crate::price_format::ticks")]
     pub price: Price,
     pub qty: Qty,
     pub latency_ms: u64,  // For heatmap visualization
+    /// Orders resting at the level
+    #[serde(default)]
+    pub orders: usize,
+    /// Price times quantity, in ticks
+    #[serde(default, serialize_with = "crate::price_format::ticks")]
+    pub notional: u128,
+}
+
+/// Resting depth on one side of the book
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SideTotals {
+    pub levels: usize,
+    pub orders: usize,
+    pub qty: Qty,
+    /// Price times quantity summed over the levels, in ticks
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub notional: u128,
+}
+
+impl SideTotals {
+    /// Totals of a side's levels
+    pub fn of(levels: &[BookLevelPoint]) -> Self {
+        levels.iter().fold(Self { levels: levels.len(), ..Self::default() }, |totals, level| Self {
+            orders: totals.orders + level.orders,
+            qty: totals.qty + level.qty,
+            notional: totals.notional + level.notional,
+            ..totals
+        })
+    }
 }
 
 /// Comprehensive market data snapshot
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DepthSnapshot {
     pub ts: u128,
+    #[serde(serialize_with = "crate::price_format::ticks")]
     pub best_bid: Option<Price>,
+    #[serde(serialize_with = "crate::price_format::ticks")]
     pub best_ask: Option<Price>,
+    #[serde(serialize_with = "crate::price_format::ticks")]
     pub spread: Option<i64>,
+    #[serde(serialize_with = "crate::price_format::units")]
     pub mid: Option<f64>,
     pub bids: Vec<BookLevelPoint>,
     pub asks: Vec<BookLevelPoint>,
+    /// Every bid level taken together
+    #[serde(default)]
+    pub bid_totals: SideTotals,
+    /// Every ask level taken together
+    #[serde(default)]
+    pub ask_totals: SideTotals,
+    #[serde(serialize_with = "crate::price_format::timed_ticks")]
     pub recent_spreads: Vec<(u128, i64)>,
     pub metrics: crate::types::Metrics,
+    /// Trades executed since the previous snapshot (for trade tape display)
+    #[serde(default)]
+    pub trades: Vec<Trade>,
+    /// `trades` grouped by the order that took liquidity
+    #[serde(default)]
+    pub executions: Vec<ExecutionSummary>,
+    /// Block trades among `trades`, printed after the reporting delay
+    #[serde(default)]
+    pub block_trades: Vec<Trade>,
+    /// Whether the best bid and ask are locked or crossed
+    #[serde(default)]
+    pub book_state: BookState,
+    /// How often orders have locked or crossed the book, and what was done
+    #[serde(default)]
+    pub crossings: CrossingCounters,
+    /// Activity and fills of each configured synthetic agent
+    #[serde(default)]
+    pub agents: Vec<AgentStats>,
+    /// Latest pre-open auction imbalance, while the market is pre-open
+    #[serde(default)]
+    pub auction: Option<AuctionImbalance>,
+    /// Ages of resting orders, measured by the simulator against its clock
+    /// when `analytics.order_ages` is on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order_ages: Option<OrderAges>,
+    /// When the data source events behind it were ingested, matched and
+    /// published; absent when it carries none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watermark: Option<PipelineWatermark>,
+    /// Number of the market-by-order updates that lead to this snapshot;
+    /// absent when the server runs no order feed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order_seq: Option<u64>,
+    /// Those updates, when any order changed since the previous snapshot
+    #[serde(skip)]
+    pub orders: Option<Arc<crate::mbo::OrderUpdates>>,
+}
+
+impl DepthSnapshot {
+    /// Keep only the best `max_levels` levels a side, with totals over those
+    pub fn truncated(mut self, max_levels: usize) -> Self {
+        self.bids.truncate(max_levels);
+        self.asks.truncate(max_levels);
+        self.bid_totals = SideTotals::of(&self.bids);
+        self.ask_totals = SideTotals::of(&self.asks);
+        self
+    }
+}
+
+/// Top of the book, published on change between snapshots
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BboUpdate {
+    pub ts: u128,
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub best_bid: Option<Price>,
+    pub bid_qty: Qty,
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub best_ask: Option<Price>,
+    pub ask_qty: Qty,
+}
+
+impl BboUpdate {
+    /// Top of the book in a snapshot
+    pub fn of(snapshot: &DepthSnapshot) -> Self {
+        Self {
+            ts: snapshot.ts,
+            best_bid: snapshot.best_bid,
+            bid_qty: snapshot.bids.first().map_or(0, |level| level.qty),
+            best_ask: snapshot.best_ask,
+            ask_qty: snapshot.asks.first().map_or(0, |level| level.qty),
+        }
+    }
+
+    /// Whether both quotes match, whatever the time
+    pub fn same_quote(&self, other: &Self) -> bool {
+        (self.best_bid, self.bid_qty, self.best_ask, self.ask_qty)
+            == (other.best_bid, other.bid_qty, other.best_ask, other.ask_qty)
+    }
+}
+
+/// Relation between the best bid and the best ask
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BookState {
+    /// Best bid below best ask, or a side is empty
+    #[default]
+    Normal,
+    /// Best bid equals best ask
+    Locked,
+    /// Best bid above best ask
+    Crossed,
+}
+
+impl BookState {
+    /// State of a book with the given touch
+    pub fn of(best_bid: Option<Price>, best_ask: Option<Price>) -> Self {
+        match (best_bid, best_ask) {
+            (Some(bid), Some(ask)) if bid == ask => Self::Locked,
+            (Some(bid), Some(ask)) if bid > ask => Self::Crossed,
+            _ => Self::Normal,
+        }
+    }
+}
+
+impl std::fmt::Display for BookState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Normal => "normal",
+            Self::Locked => "locked",
+            Self::Crossed => "crossed",
+        })
+    }
+}
+
+/// What to do with an order that would rest locking or crossing the book
+/// 
+/// This only happens when the match filter keeps an order from executing
+/// against liquidity at or through its limit price, e.g. synthetic orders
+/// meeting historical ones in shadow mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CrossedBookPolicy {
+    /// Let the order rest and count it
+    #[default]
+    FlagAndContinue,
+    /// Match the order against the liquidity it locks or crosses, ignoring
+    /// the match filter
+    AutoMatch,
+    /// Refuse the part of the order that would rest; fills it already made stand
+    RejectIncoming,
+}
+
+/// Identifier of a self-match prevention group
+pub type StpGroup = u32;
+
+/// Which order self-match prevention cancels when an incoming order reaches
+/// a resting order of its own group
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StpMode {
+    /// Cancel the rest of the incoming order; fills it already made stand
+    #[default]
+    CancelTaker,
+    /// Cancel the resting order and keep matching past it
+    CancelResting,
+}
+
+/// Where the timestamps of trades come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TradeTimestamps {
+    /// The incoming order's timestamp: the simulation clock for synthetic
+    /// orders, the recorded time for replayed ones
+    #[default]
+    TakerOrder,
+    /// Host time when the match happens
+    WallClock,
+}
+
+/// Orders that would have locked or crossed the book
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct CrossingCounters {
+    pub locked: u64,
+    pub crossed: u64,
+    /// Resolved by matching through the filter
+    pub auto_matched: u64,
+    /// Resolved by refusing the resting remainder
+    pub rejected: u64,
+}
+
+/// Book composition and running totals of its churn
+///
+/// The totals only grow, so each reader keeps its previous reading and
+/// diffs the next against it with [`EngineStats::since`], leaving the
+/// engine's counts as they are for everyone else.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct EngineStats {
+    pub resting_orders: usize,
+    pub bid_levels: usize,
+    pub ask_levels: usize,
+    /// Orders that have come to rest
+    pub adds: u64,
+    /// Resting orders cancelled
+    pub cancels: u64,
+    /// Trades executed
+    pub trades: u64,
+}
+
+impl EngineStats {
+    /// Composition of a book holding `orders`, with no churn counted
+    pub fn of_orders(orders: &[Order]) -> Self {
+        let levels = |side| orders.iter()
+            .filter(|order| order.side == side)
+            .filter_map(Order::price)
+            .collect::<HashSet<_>>()
+            .len();
+        Self { resting_orders: orders.len(), bid_levels: levels(Side::Buy), ask_levels: levels(Side::Sell), ..Self::default() }
+    }
+
+    /// Churn between an `earlier` reading and this one, taken `interval_ns`
+    /// of simulation time later
+    ///
+    /// Totals that went down, as when the engine was replaced, count from 0.
+    pub fn since(&self, earlier: &EngineStats, interval_ns: u64) -> EngineActivity {
+        let change = |now: usize, then: usize| now as i64 - then as i64;
+        let per_sec = |count: f64| if interval_ns == 0 { 0.0 } else { count * 1e9 / interval_ns as f64 };
+        let count = |now: u64, then: u64| if now < then { now } else { now - then };
+        let (adds, cancels, trades) = (count(self.adds, earlier.adds), count(self.cancels, earlier.cancels), count(self.trades, earlier.trades));
+        let resting_orders_change = change(self.resting_orders, earlier.resting_orders);
+        EngineActivity {
+            adds,
+            cancels,
+            trades,
+            interval_ns,
+            resting_orders_change,
+            bid_levels_change: change(self.bid_levels, earlier.bid_levels),
+            ask_levels_change: change(self.ask_levels, earlier.ask_levels),
+            adds_per_sec: per_sec(adds as f64),
+            cancels_per_sec: per_sec(cancels as f64),
+            trades_per_sec: per_sec(trades as f64),
+            resting_orders_per_sec: per_sec(resting_orders_change as f64),
+        }
+    }
+}
+
+/// Book churn between two [`EngineStats`] readings
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct EngineActivity {
+    /// Orders that came to rest between the readings
+    pub adds: u64,
+    /// Resting orders cancelled between the readings
+    pub cancels: u64,
+    /// Trades executed between the readings
+    pub trades: u64,
+    /// Simulation time between the readings in nanoseconds
+    pub interval_ns: u64,
+    /// Net change in resting orders and levels between the readings
+    pub resting_orders_change: i64,
+    pub bid_levels_change: i64,
+    pub ask_levels_change: i64,
+    /// Per-second rates over the interval, 0 for an empty interval
+    pub adds_per_sec: f64,
+    pub cancels_per_sec: f64,
+    pub trades_per_sec: f64,
+    pub resting_orders_per_sec: f64,
+}
+
+/// Engine statistics as one reader sees them
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EngineStatsReport {
+    #[serde(flatten)]
+    pub totals: EngineStats,
+    /// Churn since the reader's previous reading
+    pub activity: EngineActivity,
+}
+
+/// One reader's previous [`EngineStats`] reading, to diff the next against
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StatsReader {
+    previous: Option<(u128, EngineStats)>,
+}
+
+impl StatsReader {
+    /// Report a reading taken at simulation time `ts` against the previous
+    /// one, which the first reading and one taken after the clock went
+    /// back are measured from an empty book at the same time
+    pub fn read(&mut self, ts: u128, totals: EngineStats) -> EngineStatsReport {
+        let (then, earlier) = self.previous
+            .filter(|&(then, _)| then <= ts)
+            .unwrap_or((ts, EngineStats::default()));
+        self.previous = Some((ts, totals));
+        EngineStatsReport { totals, activity: totals.since(&earlier, (ts - then) as u64) }
+    }
 }
 
 /// Trait defining the core order book engine interface
@@ -49,7 +378,8 @@ pub trait OrderBookEngine {
     /// * `InvalidPrice` - For limit orders with invalid prices
     /// * `InvalidQty` - For orders with zero or negative quantity
     /// * `NoLiquidity` - For market orders when no opposite side exists
-    /// * `Reject` - For business logic rejections
+    /// * `DuplicateOrder` - If an order with the same id is already resting
+    /// * `Reject` - For other business logic rejections
     fn place(&mut self, order: Order) -> EngineResult<Vec<Trade>>;
 
     /// Cancel an existing order
@@ -92,6 +422,187 @@ pub trait OrderBookEngine {
     /// * DepthSnapshot containing current market state
     fn snapshot(&self) -> DepthSnapshot;
 
+    /// Market data snapshot of the best `max_levels` levels a side, with
+    /// totals over those levels
+    ///
+    /// Engines that cannot stop early build the complete snapshot and cut it.
+    fn snapshot_top(&self, max_levels: usize) -> DepthSnapshot {
+        self.snapshot().truncated(max_levels)
+    }
+
+    /// Remove all resting orders from the book
+    /// 
+    /// Used when a replay needs to rebuild the book from scratch (e.g. on seek).
+    /// Engines that keep no faster way cancel each of their resting orders.
+    fn clear(&mut self) {
+        for order in self.resting_orders() {
+            let _ = self.cancel(order.id);
+        }
+    }
+
+    /// Restrict which resting orders an incoming order may execute against
+    /// 
+    /// Orders the filter rules out stay in the book and keep their queue
+    /// priority, so the book may end up crossed unless the crossed book
+    /// policy resolves it. `None` lets everything match. Ignored by engines
+    /// without match filtering.
+    fn set_match_filter(&mut self, _filter: Option<MatchFilter>) {}
+
+    /// Choose what happens to an order that would rest locking or crossing the book
+    ///
+    /// Ignored by engines that cannot rest a crossing order.
+    fn set_crossed_book_policy(&mut self, _policy: CrossedBookPolicy) {}
+
+    /// Put the orders placed from now on in a self-match prevention group
+    ///
+    /// An order never executes against a resting order of its own group;
+    /// the STP mode decides which of the two is cancelled instead. `None`
+    /// places orders outside any group. Ignored by engines without
+    /// self-match prevention.
+    fn set_stp_group(&mut self, _group: Option<StpGroup>) {}
+
+    /// Choose which order self-match prevention cancels
+    fn set_stp_mode(&mut self, _mode: StpMode) {}
+
+    /// Choose where trades take their timestamps from
+    ///
+    /// Ignored by engines that always stamp trades the same way.
+    fn set_trade_timestamps(&mut self, _timestamps: TradeTimestamps) {}
+
+    /// Set the time order timestamps are checked against while trades take
+    /// the incoming order's timestamp
+    ///
+    /// The wall clock stands in until it is set. Ignored by engines that do
+    /// not check timestamps.
+    fn set_clock(&mut self, _now: u128) {}
+
+    /// Run every call through [`CommandEngine`] under a
+    /// [`WallClockIsolation`] guard, so any wall-clock read inside the
+    /// engine panics and its timestamps must come from its clock
+    ///
+    /// Ignored by engines that do not support it.
+    fn set_wall_clock_isolation(&mut self, _isolated: bool) {}
+
+    /// Whether calls through [`CommandEngine`] run under wall-clock isolation
+    fn isolates_wall_clock(&self) -> bool {
+        false
+    }
+
+    /// Require order quantities to be whole lots
+    /// 
+    /// Orders in the historical id namespace are exempt, since replayed data
+    /// records what actually traded. Ignored by engines without lot sizes.
+    fn set_lot_size(&mut self, _lot_size: LotSize) {}
+
+    /// Bound how many price levels, and how far from the touch, each side keeps
+    ///
+    /// Ignored by engines without a depth limit.
+    fn set_depth_limit(&mut self, _limit: DepthLimit) {}
+
+    /// Take the orders removed by the depth limit or by self-match
+    /// prevention since the last call
+    ///
+    /// Pruned orders leave the book without a cancel, so their owners learn
+    /// about them here.
+    fn take_pruned(&mut self) -> Vec<PrunedOrder> {
+        Vec::new()
+    }
+
+    /// Resting orders, levels per side, and the adds, cancels and trades
+    /// so far
+    ///
+    /// Lets operators watch the book's composition without a full snapshot.
+    /// Engines that keep no counts report the composition of their resting
+    /// orders with no churn.
+    fn stats(&self) -> EngineStats {
+        EngineStats::of_orders(&self.resting_orders())
+    }
+
+    /// Release spare capacity in the book's indexes
+    ///
+    /// Filled orders leave the indexes as they fill, so this only shrinks
+    /// what they grew to. Engines with nothing to release keep the default.
+    fn compact(&mut self) -> Compaction {
+        Compaction::default()
+    }
+
+    /// Pay a priority fee to move a resting order ahead at its price level
+    /// 
+    /// # Returns
+    /// * Fee charged, which is 0 under queue disciplines without fee priority
+    /// 
+    /// # Errors
+    /// * `UnknownOrder` - If the order ID is not found
+    fn pay_priority_fee(&mut self, order_id: OrderId, _fee: u64) -> EngineResult<u64> {
+        if self.resting_orders().iter().any(|order| order.id == order_id) {
+            Ok(0)
+        } else {
+            Err(EngineError::UnknownOrder { order_id })
+        }
+    }
+
+    /// Every resting order: bids best price first, then asks best price
+    /// first, each level in queue priority order
+    ///
+    /// Placing them in this order into an empty book rebuilds it with the
+    /// same queue priority. Engines that cannot list their orders return
+    /// none, so they save as an empty book.
+    fn resting_orders(&self) -> Vec<Order> {
+        Vec::new()
+    }
+
+    /// Priority fees a resting order has paid, 0 under queue disciplines
+    /// without fee priority or for an unknown order
+    fn priority_fee_paid(&self, _order_id: OrderId) -> u64 {
+        0
+    }
+
+    /// Quantity an order has resting, if it is in the book
+    ///
+    /// Engines without an order index search their resting orders.
+    fn resting_qty(&self, order_id: OrderId) -> Option<Qty> {
+        self.resting_orders().into_iter().find(|order| order.id == order_id).map(|order| order.qty)
+    }
+
+    /// Ages at `now` of the resting orders on each side and of each level's
+    /// oldest order
+    fn order_ages(&self, now: u128) -> OrderAges {
+        OrderAges::from_orders(now, &self.resting_orders())
+    }
+
+    /// Work out what an order would do if placed now, in the self-match
+    /// prevention group `stp_group`, leaving the book as it is
+    ///
+    /// Engines that cannot work it out themselves are previewed on a FIFO
+    /// book rebuilt from their resting orders, under default rules.
+    ///
+    /// # Errors
+    /// * Whatever placing the order would fail with
+    fn preview(&self, order: Order, stp_group: Option<StpGroup>) -> EngineResult<OrderPreview> {
+        let mut book = OrderBook::<FifoLevel>::new();
+        for resting in self.resting_orders() {
+            if let Some(price) = resting.price() {
+                book.add_to_book(resting, price)?;
+            }
+        }
+        book.preview(order, stp_group)
+    }
+
+    /// Cancel-replace an owner's two-sided quote in one call
+    ///
+    /// Both new legs are checked before anything changes, so a quote that
+    /// fails validation leaves the previous one resting. Otherwise the
+    /// previous legs are pulled and the new ones placed, bid first, with no
+    /// moment in between where only one side or a crossed pair is quoted.
+    ///
+    /// # Errors
+    /// * Whatever placing either leg would fail validation with
+    /// * `InvalidQuote` - If the bid is not below the ask or both legs share an id
+    /// * `Reject` - If the engine does not support quotes
+    fn quote(&mut self, _quote: TwoSidedQuote) -> EngineResult<QuoteOutcome> {
+        Err(EngineError::reject("Two-sided quotes are not supported by this engine"))
+    }
+
     /// Get the current spread (ask - bid)
     /// 
     /// # Returns
@@ -109,12 +620,513 @@ pub trait OrderBookEngine {
     /// * Some(mid_price) if both bid and ask exist, None otherwise
     fn mid_price(&self) -> Option<f64> {
         match (self.best_bid(), self.best_ask()) {
-            (Some(bid), Some(ask)) => Some((bid as f64 + ask as f64) / 2.0 / 10000.0),
+            (Some(bid), Some(ask)) => Some(price_utils::mid_price(bid, ask)),
+            _ => None,
+        }
+    }
+}
+
+/// What happens to an incoming order that would rest past the depth limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DepthPolicy {
+    /// Refuse the order
+    #[default]
+    Reject,
+    /// Accept the order, then prune whatever rests past the limit
+    Prune,
+}
+
+/// Bound on the depth each side of the book retains
+/// 
+/// Resting orders pushed past the limit by better-priced orders are pruned
+/// under either policy; the policy only decides what happens to an incoming
+/// order that would itself rest past it. Marketable orders are never refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+pub struct DepthLimit {
+    /// Price levels kept per side
+    #[serde(default)]
+    pub max_levels: Option<usize>,
+    /// Furthest a resting order may sit from its side's best price, in ticks
+    #[serde(default)]
+    pub max_ticks_from_touch: Option<Price>,
+    #[serde(default)]
+    pub policy: DepthPolicy,
+}
+
+impl DepthLimit {
+    /// Keep at most `max_levels` price levels per side
+    pub fn levels(max_levels: usize) -> Self {
+        Self { max_levels: Some(max_levels), ..Self::default() }
+    }
+
+    /// Keep orders within `ticks` of their side's best price
+    pub fn band(ticks: Price) -> Self {
+        Self { max_ticks_from_touch: Some(ticks), ..Self::default() }
+    }
+
+    /// Also keep orders within `ticks` of their side's best price
+    pub fn with_band(mut self, ticks: Price) -> Self {
+        self.max_ticks_from_touch = Some(ticks);
+        self
+    }
+
+    /// Set how orders past the limit are handled
+    pub fn with_policy(mut self, policy: DepthPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Whether any bound is set
+    pub fn is_bounded(&self) -> bool {
+        self.max_levels.is_some() || self.max_ticks_from_touch.is_some()
+    }
+
+    /// Check the limit settings
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_levels == Some(0) {
+            return Err("Depth limit max_levels must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether a level `index` levels behind the touch and `ticks` away from
+    /// it lies past the limit
+    fn excludes(&self, index: usize, ticks: Price) -> bool {
+        self.max_levels.is_some_and(|max| index >= max)
+            || self.max_ticks_from_touch.is_some_and(|max| ticks > max)
+    }
+}
+
+/// Where a resting order stands in the book
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderState {
+    pub order_id: OrderId,
+    pub side: Side,
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub price: Price,
+    /// Quantity still resting
+    pub qty: Qty,
+    /// Orders ahead of it at its price level
+    pub queue_position: usize,
+}
+
+/// Why an order left the book without a cancel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PruneReason {
+    /// It rested past the depth limit
+    #[default]
+    DepthLimit,
+    /// Self-match prevention cancelled it
+    SelfMatch,
+}
+
+/// An order the depth limit or self-match prevention removed from the book
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrunedOrder {
+    pub order_id: OrderId,
+    pub side: Side,
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub price: Price,
+    /// Quantity that was resting, or left to rest
+    pub qty: Qty,
+    #[serde(default)]
+    pub reason: PruneReason,
+}
+
+/// What an order would do if placed now, worked out without touching the book
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderPreview {
+    /// Trades the order would make, in matching order
+    pub trades: Vec<Trade>,
+    /// Quantity the trades fill
+    pub filled: Qty,
+    /// Quantity left once matching stops
+    pub remaining: Qty,
+    /// Whether the remainder would rest at the order's limit price
+    pub rests: bool,
+    /// Volume-weighted fill price in ticks, if anything fills
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub avg_price: Option<f64>,
+    /// Opposite price levels the order would empty
+    pub levels_consumed: usize,
+    /// Resting orders the depth limit or self-match prevention would remove
+    pub pruned: Vec<PrunedOrder>,
+    /// Top of the book as it is
+    pub before: BboUpdate,
+    /// Top of the book as the order would leave it
+    pub after: BboUpdate,
+}
+
+/// Who a two-sided quote belongs to; each owner has at most one quote in the book
+pub type QuoteOwner = u64;
+
+/// One side of a two-sided quote
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuoteLeg {
+    pub order_id: OrderId,
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub price: Price,
+    pub qty: Qty,
+}
+
+/// A bid and an ask that replace their owner's previous quote in one call
+///
+/// A missing leg pulls that side of the quote without replacing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TwoSidedQuote {
+    pub owner: QuoteOwner,
+    pub bid: Option<QuoteLeg>,
+    pub ask: Option<QuoteLeg>,
+    /// Time stamped on both legs
+    pub ts: u128,
+}
+
+impl TwoSidedQuote {
+    /// Quote both sides for `owner`
+    pub fn new(owner: QuoteOwner, bid: QuoteLeg, ask: QuoteLeg, ts: u128) -> Self {
+        Self { owner, bid: Some(bid), ask: Some(ask), ts }
+    }
+
+    /// The legs as limit orders, bid first
+    pub fn orders(&self) -> impl Iterator<Item = Order> + '_ {
+        [(Side::Buy, self.bid), (Side::Sell, self.ask)].into_iter()
+            .filter_map(|(side, leg)| leg.map(|leg| Order::new_limit(leg.order_id, side, leg.qty, leg.price, self.ts)))
+    }
+}
+
+/// What replacing a quote did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QuoteOutcome {
+    /// Legs of the previous quote that were still resting, with the
+    /// quantity pulled from each
+    pub cancelled: Vec<(OrderId, Qty)>,
+    /// Trades the new legs executed on arrival, bid first
+    pub trades: Vec<Trade>,
+    /// New legs left resting
+    pub resting: Vec<OrderId>,
+    /// New legs a book rule refused once the previous quote was pulled:
+    /// the depth limit, the crossed book policy or self-match prevention
+    pub refused: Vec<(OrderId, EngineError)>,
+}
+
+/// A state-changing request to an engine
+#[derive(Debug, Clone)]
+pub enum EngineCommand {
+    Place(Order),
+    Cancel(OrderId),
+    Clear,
+    SetMatchFilter(Option<MatchFilter>),
+    SetLotSize(LotSize),
+    SetDepthLimit(DepthLimit),
+    SetCrossedBookPolicy(CrossedBookPolicy),
+    SetTradeTimestamps(TradeTimestamps),
+    /// Move the clock order timestamps are checked against
+    SetClock(u128),
+    /// Forbid wall-clock reads inside the engine from now on, or allow them again
+    SetWallClockIsolation(bool),
+    /// Put the orders placed from now on in a self-match prevention group
+    SetStpGroup(Option<StpGroup>),
+    SetStpMode(StpMode),
+    /// Collect the orders pruned by the depth limit or self-match prevention
+    TakePruned,
+    /// Pay a priority fee for a resting order's place in its queue
+    PayPriorityFee { order_id: OrderId, fee: u64 },
+    /// Read book composition and the running totals of its churn
+    Stats,
+    /// Drop stale index entries and spare capacity
+    Compact,
+    /// Replace an owner's two-sided quote
+    Quote(TwoSidedQuote),
+}
+
+/// What an engine reports back for a command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EngineEvent {
+    /// Trades executed by a placed order (empty if it rested)
+    Placed(Vec<Trade>),
+    /// Quantity removed by a cancel
+    Cancelled(Qty),
+    /// The book was cleared or reconfigured
+    Applied,
+    /// Orders removed by the depth limit or self-match prevention
+    Pruned(Vec<PrunedOrder>),
+    /// Priority fee charged
+    PriorityFeePaid(u64),
+    /// Book composition and the running totals of its churn
+    Stats(EngineStats),
+    /// What compaction reclaimed
+    Compacted(Compaction),
+    /// What replacing a quote did
+    Quoted(QuoteOutcome),
+}
+
+/// Command/event interface the simulator drives an engine through
+///
+/// Unlike [`OrderBookEngine`] this needs no direct access to the book, so it
+/// can front a remote engine or a reference model. Every
+/// [`OrderBookEngine`] implements it.
+pub trait CommandEngine {
+    /// Apply a command
+    fn execute(&mut self, command: EngineCommand) -> EngineResult<EngineEvent>;
+
+    /// Best bid and best ask
+    fn best_bid_ask(&self) -> (Option<Price>, Option<Price>);
+
+    /// Complete market data snapshot
+    fn depth_snapshot(&self) -> DepthSnapshot;
+
+    /// Market data snapshot of the best `max_levels` levels a side
+    fn depth_snapshot_top(&self, max_levels: usize) -> DepthSnapshot {
+        self.depth_snapshot().truncated(max_levels)
+    }
+
+    /// Every resting order, in an order that rebuilds the book with the same
+    /// queue priority when placed into an empty one
+    fn open_orders(&self) -> Vec<Order> {
+        Vec::new()
+    }
+
+    /// Priority fees a resting order has paid
+    fn order_priority_fee(&self, _order_id: OrderId) -> u64 {
+        0
+    }
+
+    /// Quantity an order has resting, if it is in the book
+    fn order_resting_qty(&self, order_id: OrderId) -> Option<Qty> {
+        self.open_orders().into_iter().find(|order| order.id == order_id).map(|order| order.qty)
+    }
+
+    /// Ages at `now` of the resting orders, per side and per level
+    fn open_order_ages(&self, now: u128) -> OrderAges {
+        OrderAges::from_orders(now, &self.open_orders())
+    }
+
+    /// What an order would do if placed now in `stp_group`, without placing it
+    ///
+    /// Engines that cannot work it out themselves are previewed on a FIFO
+    /// book rebuilt from their open orders, under default rules.
+    fn preview_order(&self, order: Order, stp_group: Option<StpGroup>) -> EngineResult<OrderPreview> {
+        let mut book = OrderBook::<FifoLevel>::new();
+        for resting in self.open_orders() {
+            if let Some(price) = resting.price() {
+                book.add_to_book(resting, price)?;
+            }
+        }
+        book.preview(order, stp_group)
+    }
+
+    /// Place an order, returning its trades
+    fn submit(&mut self, order: Order) -> EngineResult<Vec<Trade>> {
+        match self.execute(EngineCommand::Place(order))? {
+            EngineEvent::Placed(trades) => Ok(trades),
+            event => Err(EngineError::internal(format!("Unexpected reply to place: {:?}", event))),
+        }
+    }
+
+    /// Cancel an order, returning the quantity removed
+    fn withdraw(&mut self, order_id: OrderId) -> EngineResult<Qty> {
+        match self.execute(EngineCommand::Cancel(order_id))? {
+            EngineEvent::Cancelled(qty) => Ok(qty),
+            event => Err(EngineError::internal(format!("Unexpected reply to cancel: {:?}", event))),
+        }
+    }
+
+    /// Collect the orders the depth limit removed since the last call
+    fn collect_pruned(&mut self) -> EngineResult<Vec<PrunedOrder>> {
+        match self.execute(EngineCommand::TakePruned)? {
+            EngineEvent::Pruned(orders) => Ok(orders),
+            event => Err(EngineError::internal(format!("Unexpected reply to take pruned: {:?}", event))),
+        }
+    }
+
+    /// Read book composition and the running totals of its churn
+    ///
+    /// Reading changes nothing; a [`StatsReader`] diffs the totals against
+    /// its own previous reading for the churn in between.
+    fn collect_stats(&mut self) -> EngineResult<EngineStats> {
+        match self.execute(EngineCommand::Stats)? {
+            EngineEvent::Stats(stats) => Ok(stats),
+            event => Err(EngineError::internal(format!("Unexpected reply to stats: {:?}", event))),
+        }
+    }
+
+    /// Compact the engine's indexes, returning what was reclaimed
+    fn compact_book(&mut self) -> EngineResult<Compaction> {
+        match self.execute(EngineCommand::Compact)? {
+            EngineEvent::Compacted(compaction) => Ok(compaction),
+            event => Err(EngineError::internal(format!("Unexpected reply to compact: {:?}", event))),
+        }
+    }
+
+    /// Pay a priority fee for a resting order, returning the fee charged
+    fn buy_priority(&mut self, order_id: OrderId, fee: u64) -> EngineResult<u64> {
+        match self.execute(EngineCommand::PayPriorityFee { order_id, fee })? {
+            EngineEvent::PriorityFeePaid(charged) => Ok(charged),
+            event => Err(EngineError::internal(format!("Unexpected reply to priority fee: {:?}", event))),
+        }
+    }
+
+    /// Replace an owner's two-sided quote, returning what changed
+    fn requote(&mut self, quote: TwoSidedQuote) -> EngineResult<QuoteOutcome> {
+        match self.execute(EngineCommand::Quote(quote))? {
+            EngineEvent::Quoted(outcome) => Ok(outcome),
+            event => Err(EngineError::internal(format!("Unexpected reply to quote: {:?}", event))),
+        }
+    }
+
+    /// Mid-price in dollars, if both sides are quoted
+    fn quote_mid(&self) -> Option<f64> {
+        match self.best_bid_ask() {
+            (Some(bid), Some(ask)) => Some(price_utils::mid_price(bid, ask)),
+            _ => None,
+        }
+    }
+
+    /// Spread in ticks, if both sides are quoted
+    fn quote_spread(&self) -> Option<i64> {
+        match self.best_bid_ask() {
+            (Some(bid), Some(ask)) => Some(ask as i64 - bid as i64),
             _ => None,
         }
     }
 }
 
+impl<E: OrderBookEngine> CommandEngine for E {
+    fn execute(&mut self, command: EngineCommand) -> EngineResult<EngineEvent> {
+        let _isolation = self.isolates_wall_clock().then(WallClockIsolation::enter);
+        match command {
+            EngineCommand::Place(order) => self.place(order).map(EngineEvent::Placed),
+            EngineCommand::Cancel(order_id) => self.cancel(order_id).map(EngineEvent::Cancelled),
+            EngineCommand::Clear => {
+                self.clear();
+                Ok(EngineEvent::Applied)
+            }
+            EngineCommand::SetMatchFilter(filter) => {
+                self.set_match_filter(filter);
+                Ok(EngineEvent::Applied)
+            }
+            EngineCommand::SetLotSize(lot_size) => {
+                self.set_lot_size(lot_size);
+                Ok(EngineEvent::Applied)
+            }
+            EngineCommand::SetDepthLimit(limit) => {
+                self.set_depth_limit(limit);
+                Ok(EngineEvent::Applied)
+            }
+            EngineCommand::SetCrossedBookPolicy(policy) => {
+                self.set_crossed_book_policy(policy);
+                Ok(EngineEvent::Applied)
+            }
+            EngineCommand::SetTradeTimestamps(timestamps) => {
+                self.set_trade_timestamps(timestamps);
+                Ok(EngineEvent::Applied)
+            }
+            EngineCommand::SetClock(now) => {
+                self.set_clock(now);
+                Ok(EngineEvent::Applied)
+            }
+            EngineCommand::SetWallClockIsolation(isolated) => {
+                self.set_wall_clock_isolation(isolated);
+                Ok(EngineEvent::Applied)
+            }
+            EngineCommand::SetStpGroup(group) => {
+                self.set_stp_group(group);
+                Ok(EngineEvent::Applied)
+            }
+            EngineCommand::SetStpMode(mode) => {
+                self.set_stp_mode(mode);
+                Ok(EngineEvent::Applied)
+            }
+            EngineCommand::TakePruned => Ok(EngineEvent::Pruned(self.take_pruned())),
+            EngineCommand::PayPriorityFee { order_id, fee } => {
+                self.pay_priority_fee(order_id, fee).map(EngineEvent::PriorityFeePaid)
+            }
+            EngineCommand::Stats => Ok(EngineEvent::Stats(self.stats())),
+            EngineCommand::Compact => Ok(EngineEvent::Compacted(self.compact())),
+            EngineCommand::Quote(quote) => self.quote(quote).map(EngineEvent::Quoted),
+        }
+    }
+
+    fn best_bid_ask(&self) -> (Option<Price>, Option<Price>) {
+        let _isolation = self.isolates_wall_clock().then(WallClockIsolation::enter);
+        (self.best_bid(), self.best_ask())
+    }
+
+    fn depth_snapshot(&self) -> DepthSnapshot {
+        let _isolation = self.isolates_wall_clock().then(WallClockIsolation::enter);
+        self.snapshot()
+    }
+
+    fn depth_snapshot_top(&self, max_levels: usize) -> DepthSnapshot {
+        let _isolation = self.isolates_wall_clock().then(WallClockIsolation::enter);
+        self.snapshot_top(max_levels)
+    }
+
+    fn open_orders(&self) -> Vec<Order> {
+        let _isolation = self.isolates_wall_clock().then(WallClockIsolation::enter);
+        self.resting_orders()
+    }
+
+    fn order_priority_fee(&self, order_id: OrderId) -> u64 {
+        let _isolation = self.isolates_wall_clock().then(WallClockIsolation::enter);
+        self.priority_fee_paid(order_id)
+    }
+
+    fn order_resting_qty(&self, order_id: OrderId) -> Option<Qty> {
+        let _isolation = self.isolates_wall_clock().then(WallClockIsolation::enter);
+        self.resting_qty(order_id)
+    }
+
+    fn open_order_ages(&self, now: u128) -> OrderAges {
+        let _isolation = self.isolates_wall_clock().then(WallClockIsolation::enter);
+        self.order_ages(now)
+    }
+
+    fn preview_order(&self, order: Order, stp_group: Option<StpGroup>) -> EngineResult<OrderPreview> {
+        let _isolation = self.isolates_wall_clock().then(WallClockIsolation::enter);
+        self.preview(order, stp_group)
+    }
+}
+
+/// Self-match prevention for one incoming order
+struct SelfMatchGuard<'a> {
+    groups: &'a HashMap<OrderId, StpGroup>,
+    group: StpGroup,
+    mode: StpMode,
+    /// Resting orders of the incoming order's group that it reached
+    reached: RefCell<Vec<OrderId>>,
+}
+
+impl<'a> SelfMatchGuard<'a> {
+    fn new(groups: &'a HashMap<OrderId, StpGroup>, group: StpGroup, mode: StpMode) -> Self {
+        Self { groups, group, mode, reached: RefCell::new(Vec::new()) }
+    }
+
+    /// Whether the incoming order may execute against a resting order,
+    /// noting the resting order if it belongs to the same group
+    fn allows(&self, maker_id: OrderId) -> bool {
+        if self.cancels_taker() {
+            return false;
+        }
+        if self.groups.get(&maker_id) == Some(&self.group) {
+            self.reached.borrow_mut().push(maker_id);
+            return false;
+        }
+        true
+    }
+
+    /// Whether the incoming order is to be cancelled, so nothing more may fill it
+    fn cancels_taker(&self) -> bool {
+        self.mode == StpMode::CancelTaker && !self.reached.borrow().is_empty()
+    }
+
+    fn into_reached(self) -> Vec<OrderId> {
+        self.reached.into_inner()
+    }
+}
+
 /// Generic order book implementation using pluggable queue disciplines
 /// 
 /// The order book maintains price levels using BTreeMap for efficient price-ordered
@@ -135,7 +1147,7 @@ pub struct OrderBook<D: QueueDiscipline> {
     order_index: HashMap<OrderId, (Side, Price)>,
     
     /// Function to create new queue discipline instances
-    level_factory: fn() -> D,
+    level_factory: Arc<dyn Fn() -> D + Send + Sync>,
     
     /// Rolling history of spread values for visualization
     /// Tuple format: (timestamp, spread_in_ticks)
@@ -146,21 +1158,64 @@ pub struct OrderBook<D: QueueDiscipline> {
     
     /// Performance monitoring (optional)
     perf_metrics: Option<Arc<PerformanceMetrics>>,
+    
+    /// Restricts which resting orders an incoming order may hit (optional)
+    match_filter: Option<MatchFilter>,
+    /// Lot-size rule applied to non-historical orders
+    lot_size: LotSize,
+    /// Bound on the levels each side retains
+    depth_limit: DepthLimit,
+    /// Orders pruned by the depth limit or self-match prevention and not yet taken
+    pruned: Vec<PrunedOrder>,
+    /// Self-match prevention group of incoming orders
+    stp_group: Option<StpGroup>,
+    /// Which order self-match prevention cancels
+    stp_mode: StpMode,
+    /// Groups of the resting orders placed in one
+    stp_groups: HashMap<OrderId, StpGroup>,
+    /// Handling of orders that would lock or cross the book
+    crossed_book_policy: CrossedBookPolicy,
+    /// Orders that would have locked or crossed the book
+    crossings: CrossingCounters,
+    /// Where trades take their timestamps from
+    trade_timestamps: TradeTimestamps,
+    /// Time order timestamps are checked against when trades take the
+    /// taker's; the wall clock until set
+    clock: Option<u128>,
+    /// Running totals of adds, cancels and trades, reported by `stats`
+    churn: EngineStats,
+    /// Legs of each owner's latest two-sided quote
+    quotes: HashMap<QuoteOwner, Vec<OrderId>>,
+    /// Log nothing, as a preview's scratch book whose trades never happen
+    quiet: bool,
+    /// Forbid wall-clock reads during calls through `CommandEngine`
+    isolate_wall_clock: bool,
 }
 
-impl<D: QueueDiscipline + Default> OrderBook<D> {
+impl<D: QueueDiscipline + Default + 'static> OrderBook<D> {
     /// Create a new order book with default level factory
     pub fn new() -> Self {
         Self::with_level_factory(D::default)
     }
 }
 
+impl<D: QueueDiscipline + Default + 'static> Default for OrderBook<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<D: QueueDiscipline> OrderBook<D> {
     /// Create a new order book with custom level factory
     /// 
     /// # Arguments
     /// * `level_factory` - Function to create new queue discipline instances
-    pub fn with_level_factory(level_factory: fn() -> D) -> Self {
+    pub fn with_level_factory(level_factory: impl Fn() -> D + Send + Sync + 'static) -> Self {
+        Self::with_shared_factory(Arc::new(level_factory))
+    }
+
+    /// Create a new order book making its levels with a factory it shares
+    fn with_shared_factory(level_factory: Arc<dyn Fn() -> D + Send + Sync>) -> Self {
         Self {
             bids: BTreeMap::new(),
             asks: BTreeMap::new(),
@@ -169,25 +1224,64 @@ impl<D: QueueDiscipline> OrderBook<D> {
             recent_spreads: CircularBuffer::new(400),
             metrics: crate::types::Metrics::new(),
             perf_metrics: None,
+            match_filter: None,
+            lot_size: LotSize::default(),
+            depth_limit: DepthLimit::default(),
+            pruned: Vec::new(),
+            stp_group: None,
+            stp_mode: StpMode::default(),
+            stp_groups: HashMap::new(),
+            crossed_book_policy: CrossedBookPolicy::default(),
+            crossings: CrossingCounters::default(),
+            trade_timestamps: TradeTimestamps::default(),
+            clock: None,
+            churn: EngineStats::default(),
+            quotes: HashMap::new(),
+            quiet: false,
+            isolate_wall_clock: false,
         }
     }
 
     /// Create a new order book with performance monitoring
-    pub fn with_performance_monitoring(level_factory: fn() -> D, perf_metrics: Arc<PerformanceMetrics>) -> Self {
+    pub fn with_performance_monitoring(level_factory: impl Fn() -> D + Send + Sync + 'static, perf_metrics: Arc<PerformanceMetrics>) -> Self {
         Self {
             bids: BTreeMap::new(),
             asks: BTreeMap::new(),
             order_index: HashMap::new(),
-            level_factory,
+            level_factory: Arc::new(level_factory),
             recent_spreads: CircularBuffer::new(400),
             metrics: crate::types::Metrics::new(),
             perf_metrics: Some(perf_metrics),
+            match_filter: None,
+            lot_size: LotSize::default(),
+            depth_limit: DepthLimit::default(),
+            pruned: Vec::new(),
+            stp_group: None,
+            stp_mode: StpMode::default(),
+            stp_groups: HashMap::new(),
+            crossed_book_policy: CrossedBookPolicy::default(),
+            crossings: CrossingCounters::default(),
+            trade_timestamps: TradeTimestamps::default(),
+            clock: None,
+            churn: EngineStats::default(),
+            quotes: HashMap::new(),
+            quiet: false,
+            isolate_wall_clock: false,
+        }
+    }
+
+    /// Order operation logger, silent on a preview's scratch book
+    fn operation_logger(&self) -> fn(&str, OrderId, Option<&str>) {
+        if self.quiet {
+            |_, _, _| {}
+        } else {
+            crate::logging::log_order_operation
         }
     }
 
     /// Validate an order before processing
     fn validate_order(&self, order: &Order) -> EngineResult<()> {
-        use crate::logging::log_order_operation;
+        let log_order_operation = self.operation_logger();
         
         // Check quantity bounds
         if order.qty == 0 {
@@ -196,7 +1290,6 @@ impl<D: QueueDiscipline> OrderBook<D> {
         }
 
         // Check maximum quantity limit (prevent overflow and unrealistic orders)
-        const MAX_QTY: Qty = 1_000_000_000; // 1 billion shares max
         if order.qty > MAX_QTY {
             log_order_operation("VALIDATION_FAILED", order.id, Some(&format!("Quantity {} exceeds maximum {}", order.qty, MAX_QTY)));
             return Err(EngineError::QtyTooLarge { 
@@ -213,10 +1306,7 @@ impl<D: QueueDiscipline> OrderBook<D> {
             }
 
             // Check price bounds (prevent overflow and unrealistic prices)
-            const MIN_PRICE: Price = 1; // Minimum 1 tick
-            const MAX_PRICE: Price = 100_000_000; // $10,000 per share max (in ticks)
-            
-            if price < MIN_PRICE || price > MAX_PRICE {
+            if !(MIN_PRICE..=MAX_PRICE).contains(&price) {
                 log_order_operation("VALIDATION_FAILED", order.id, Some(&format!("Price {} outside valid range [{}, {}]", price, MIN_PRICE, MAX_PRICE)));
                 return Err(EngineError::PriceOutOfRange {
                     price,
@@ -229,36 +1319,221 @@ impl<D: QueueDiscipline> OrderBook<D> {
         // Check for duplicate order ID
         if self.order_index.contains_key(&order.id) {
             log_order_operation("VALIDATION_FAILED", order.id, Some("Duplicate order ID"));
-            return Err(EngineError::reject(format!(
-                "Order ID {} already exists", order.id
-            )));
+            return Err(EngineError::DuplicateOrder { order_id: order.id });
         }
 
-        // Check timestamp is reasonable (not too far in the past or future)
-        let current_ts = crate::time::now_ns();
+        // Check timestamp is reasonable (not too far in the past or future).
+        // Replayed orders keep their recorded time when trades take the
+        // taker's, so only then are they exempt
+        let recorded = self.trade_timestamps == TradeTimestamps::TakerOrder && order_ids::is_historical(order.id);
+        if !recorded {
+            self.validate_order_ts(order)?;
+        }
+
+        log_order_operation("VALIDATION_PASSED", order.id, Some(&format!("{:?} {} @ {:?}", order.side, order.qty, order.order_type)));
+        Ok(())
+    }
+
+    /// Reject order timestamps more than an hour away from the engine's
+    /// time: the wall clock when it stamps trades, otherwise its clock
+    fn validate_order_ts(&self, order: &Order) -> EngineResult<()> {
+        let log_order_operation = self.operation_logger();
+
+        let current_ts = match self.trade_timestamps {
+            TradeTimestamps::TakerOrder => self.now(),
+            TradeTimestamps::WallClock => now_ns(),
+        };
         let one_hour_ns = 3_600_000_000_000u128; // 1 hour in nanoseconds
         
         if order.ts > current_ts + one_hour_ns {
             log_order_operation("VALIDATION_FAILED", order.id, Some("Timestamp too far in future"));
-            return Err(EngineError::reject(format!(
-                "Order timestamp {} is too far in the future", order.ts
-            )));
+            return Err(EngineError::TimestampOutOfRange { ts: order.ts, direction: "future" });
         }
         
         if current_ts > order.ts + one_hour_ns {
             log_order_operation("VALIDATION_FAILED", order.id, Some("Timestamp too far in past"));
-            return Err(EngineError::reject(format!(
-                "Order timestamp {} is too far in the past", order.ts
-            )));
+            return Err(EngineError::TimestampOutOfRange { ts: order.ts, direction: "past" });
         }
-
-        log_order_operation("VALIDATION_PASSED", order.id, Some(&format!("{:?} {} @ {:?}", order.side, order.qty, order.order_type)));
         Ok(())
     }
 
+    /// Timestamp for the trades of an incoming order
+    fn trade_ts(&self, taker: &Order) -> u128 {
+        match self.trade_timestamps {
+            TradeTimestamps::TakerOrder => taker.ts,
+            TradeTimestamps::WallClock => now_ns(),
+        }
+    }
+
+    /// Match an incoming order against one price level, honoring the match
+    /// filter and self-match prevention
+    fn match_level(
+        level: &mut D,
+        match_filter: Option<MatchFilter>,
+        guard: Option<&SelfMatchGuard>,
+        order: &Order,
+        price: Price,
+        trade_ts: u128,
+    ) -> (Qty, Vec<Trade>) {
+        match (match_filter, guard) {
+            (None, None) => level.match_against_at(order.id, order.side, order.qty, price, trade_ts),
+            (filter, guard) => level.match_against_filtered_at(order.id, order.side, order.qty, price, trade_ts, &|maker_id, taker_id| {
+                filter.is_none_or(|filter| filter(maker_id, taker_id)) && guard.is_none_or(|guard| guard.allows(maker_id))
+            }),
+        }
+    }
+
+    /// Note the makers of a level's trades that the level no longer lists,
+    /// which are the ones the trades filled
+    ///
+    /// Every discipline lists its orders, so a partly filled maker is still
+    /// listed and stays indexed.
+    fn collect_filled(level: &D, trades: &[Trade], filled: &mut Vec<OrderId>) {
+        if trades.is_empty() {
+            return;
+        }
+        if level.is_empty() {
+            filled.extend(trades.iter().map(|trade| trade.maker_id));
+            return;
+        }
+        let resting: HashSet<OrderId> = level.resting().into_iter().map(|(order_id, _)| order_id).collect();
+        filled.extend(trades.iter().map(|trade| trade.maker_id).filter(|maker_id| !resting.contains(maker_id)));
+    }
+
+    /// Drop filled orders from the indexes, so their ids can be used again
+    fn forget_filled(&mut self, filled: &[OrderId]) {
+        for order_id in filled {
+            self.order_index.remove(order_id);
+            self.stp_groups.remove(order_id);
+        }
+    }
+
+    /// Carry out self-match prevention for the resting orders an incoming
+    /// order reached: cancel them, or cancel what is left of the order,
+    /// refusing it outright if it filled nothing
+    fn prevent_self_match(&mut self, order: &mut Order, limit_price: Option<Price>, reached: Vec<OrderId>, traded: bool) -> EngineResult<()> {
+        let log_order_operation = self.operation_logger();
+        
+        if reached.is_empty() {
+            return Ok(());
+        }
+        match self.stp_mode {
+            StpMode::CancelResting => {
+                for order_id in reached {
+                    self.cancel_self_match(order_id);
+                }
+                Ok(())
+            }
+            StpMode::CancelTaker if !traded => Err(EngineError::SelfTrade { order_id: order.id }),
+            StpMode::CancelTaker => {
+                log_order_operation("SELF_MATCH_CANCEL", order.id, Some(&format!("{:?} {} left of incoming order", order.side, order.qty)));
+                // The simulator takes a partly filled limit order's remainder
+                // to be resting, so report it like a removed resting order
+                if let Some(price) = limit_price {
+                    self.pruned.push(PrunedOrder { order_id: order.id, side: order.side, price, qty: order.qty, reason: PruneReason::SelfMatch });
+                }
+                order.qty = 0;
+                Ok(())
+            }
+        }
+    }
+
+    /// Remove a resting order an incoming order of its own group reached,
+    /// queueing it as pruned
+    fn cancel_self_match(&mut self, order_id: OrderId) {
+        let log_order_operation = self.operation_logger();
+        
+        let Some((side, price)) = self.order_index.remove(&order_id) else {
+            return;
+        };
+        self.stp_groups.remove(&order_id);
+        let qty = match side {
+            Side::Buy => {
+                let Some(level) = self.bids.get_mut(&Reverse(price)) else { return };
+                let qty = level.cancel(order_id);
+                if level.is_empty() {
+                    self.bids.remove(&Reverse(price));
+                }
+                qty
+            }
+            Side::Sell => {
+                let Some(level) = self.asks.get_mut(&price) else { return };
+                let qty = level.cancel(order_id);
+                if level.is_empty() {
+                    self.asks.remove(&price);
+                }
+                qty
+            }
+        };
+        log_order_operation("SELF_MATCH_CANCEL", order_id, Some(&format!("{:?} {} @ {} resting", side, qty, price)));
+        self.pruned.push(PrunedOrder { order_id, side, price, qty, reason: PruneReason::SelfMatch });
+    }
+
     /// Process a limit order by crossing against opposite side
     fn process_limit_order(&mut self, mut order: Order, limit_price: Price) -> EngineResult<Vec<Trade>> {
+        let mut reached = Vec::new();
+        let mut trades = self.cross_limit(&mut order, limit_price, self.match_filter, &mut reached);
+        self.prevent_self_match(&mut order, Some(limit_price), reached, !trades.is_empty())?;
+
+        // Liquidity the match filter skipped may leave the remainder locking
+        // or crossing the book
+        let state = if order.qty > 0 { self.resting_state(order.side, limit_price) } else { BookState::Normal };
+        let mut rest = order.qty > 0;
+        match state {
+            BookState::Normal => {}
+            BookState::Locked => self.crossings.locked += 1,
+            BookState::Crossed => self.crossings.crossed += 1,
+        }
+        if state != BookState::Normal {
+            match self.crossed_book_policy {
+                CrossedBookPolicy::FlagAndContinue => {}
+                CrossedBookPolicy::AutoMatch => {
+                    self.crossings.auto_matched += 1;
+                    let mut reached = Vec::new();
+                    let auto_trades = self.cross_limit(&mut order, limit_price, None, &mut reached);
+                    let traded = !trades.is_empty() || !auto_trades.is_empty();
+                    self.prevent_self_match(&mut order, Some(limit_price), reached, traded)?;
+                    trades.extend(auto_trades);
+                }
+                CrossedBookPolicy::RejectIncoming => {
+                    self.crossings.rejected += 1;
+                    if trades.is_empty() {
+                        return Err(EngineError::CrossedBook { price: limit_price, state });
+                    }
+                    // The fills stand; the caller finds the order not resting
+                    self.operation_logger()("CROSSED_REJECT", order.id, Some(&format!(
+                        "{:?} {} left of incoming order refused at {}", order.side, order.qty, limit_price
+                    )));
+                    rest = false;
+                }
+            }
+        }
+
+        // Update metrics for each trade and spread history if trades occurred
+        if !trades.is_empty() {
+            for trade in &trades {
+                self.update_metrics_for_trade(trade, order.side);
+            }
+            // Update spread history after processing trades
+            self.update_spread_history();
+        }
+
+        // Add remaining quantity to our side if any
+        if rest && order.qty > 0 {
+            self.add_to_book(order, limit_price)?;
+        }
+
+        Ok(trades)
+    }
+
+    /// Match a limit order against the opposite side up to its limit price,
+    /// reducing its quantity by what filled and collecting the resting
+    /// orders of its own STP group that it reached into `reached`
+    fn cross_limit(&mut self, order: &mut Order, limit_price: Price, match_filter: Option<MatchFilter>, reached: &mut Vec<OrderId>) -> Vec<Trade> {
         let mut trades = Vec::new();
+        let mut filled = Vec::new();
+        let trade_ts = self.trade_ts(order);
+        let guard = self.stp_group.map(|group| SelfMatchGuard::new(&self.stp_groups, group, self.stp_mode));
         
         // Cross against opposite side levels based on order side
         match order.side {
@@ -273,13 +1548,16 @@ impl<D: QueueDiscipline> OrderBook<D> {
                     }
 
                     // Match against this level
-                    let (remaining_qty, level_trades) = level.match_against(
-                        order.id,
-                        order.side,
-                        order.qty,
+                    let (remaining_qty, level_trades) = Self::match_level(
+                        level,
+                        match_filter,
+                        guard.as_ref(),
+                        order,
                         *price,
+                        trade_ts,
                     );
 
+                    Self::collect_filled(level, &level_trades, &mut filled);
                     trades.extend(level_trades);
                     order.qty = remaining_qty;
 
@@ -288,8 +1566,8 @@ impl<D: QueueDiscipline> OrderBook<D> {
                         prices_to_remove.push(*price);
                     }
 
-                    // Stop if order is fully filled
-                    if order.qty == 0 {
+                    // Stop if order is fully filled or self-match prevention cancelled it
+                    if order.qty == 0 || guard.as_ref().is_some_and(SelfMatchGuard::cancels_taker) {
                         break;
                     }
                 }
@@ -312,13 +1590,16 @@ impl<D: QueueDiscipline> OrderBook<D> {
                     }
 
                     // Match against this level
-                    let (remaining_qty, level_trades) = level.match_against(
-                        order.id,
-                        order.side,
-                        order.qty,
+                    let (remaining_qty, level_trades) = Self::match_level(
+                        level,
+                        match_filter,
+                        guard.as_ref(),
+                        order,
                         price,
+                        trade_ts,
                     );
 
+                    Self::collect_filled(level, &level_trades, &mut filled);
                     trades.extend(level_trades);
                     order.qty = remaining_qty;
 
@@ -327,8 +1608,8 @@ impl<D: QueueDiscipline> OrderBook<D> {
                         prices_to_remove.push(*reverse_price);
                     }
 
-                    // Stop if order is fully filled
-                    if order.qty == 0 {
+                    // Stop if order is fully filled or self-match prevention cancelled it
+                    if order.qty == 0 || guard.as_ref().is_some_and(SelfMatchGuard::cancels_taker) {
                         break;
                     }
                 }
@@ -340,26 +1621,26 @@ impl<D: QueueDiscipline> OrderBook<D> {
             }
         }
 
-        // Update metrics for each trade and spread history if trades occurred
-        if !trades.is_empty() {
-            for trade in &trades {
-                self.update_metrics_for_trade(trade, order.side);
-            }
-            // Update spread history after processing trades
-            self.update_spread_history();
-        }
+        reached.extend(guard.map(SelfMatchGuard::into_reached).unwrap_or_default());
+        self.forget_filled(&filled);
+        trades
+    }
 
-        // Add remaining quantity to our side if any
-        if order.qty > 0 {
-            self.add_to_book(order, limit_price)?;
+    /// How the book would stand with an order resting at `price` on `side`
+    fn resting_state(&self, side: Side, price: Price) -> BookState {
+        match side {
+            Side::Buy => BookState::of(Some(price), self.best_ask()),
+            Side::Sell => BookState::of(self.best_bid(), Some(price)),
         }
-
-        Ok(trades)
     }
 
     /// Process a market order by crossing against opposite side at any price
     fn process_market_order(&mut self, mut order: Order) -> EngineResult<Vec<Trade>> {
         let mut trades = Vec::new();
+        let mut filled = Vec::new();
+        let match_filter = self.match_filter;
+        let trade_ts = self.trade_ts(&order);
+        let guard = self.stp_group.map(|group| SelfMatchGuard::new(&self.stp_groups, group, self.stp_mode));
         
         // Cross against opposite side levels based on order side
         match order.side {
@@ -373,13 +1654,16 @@ impl<D: QueueDiscipline> OrderBook<D> {
                 
                 for (price, level) in self.asks.iter_mut() {
                     // Match against this level
-                    let (remaining_qty, level_trades) = level.match_against(
-                        order.id,
-                        order.side,
-                        order.qty,
+                    let (remaining_qty, level_trades) = Self::match_level(
+                        level,
+                        match_filter,
+                        guard.as_ref(),
+                        &order,
                         *price,
+                        trade_ts,
                     );
 
+                    Self::collect_filled(level, &level_trades, &mut filled);
                     trades.extend(level_trades);
                     order.qty = remaining_qty;
 
@@ -388,8 +1672,8 @@ impl<D: QueueDiscipline> OrderBook<D> {
                         prices_to_remove.push(*price);
                     }
 
-                    // Stop if order is fully filled
-                    if order.qty == 0 {
+                    // Stop if order is fully filled or self-match prevention cancelled it
+                    if order.qty == 0 || guard.as_ref().is_some_and(SelfMatchGuard::cancels_taker) {
                         break;
                     }
                 }
@@ -411,13 +1695,16 @@ impl<D: QueueDiscipline> OrderBook<D> {
                     let price = reverse_price.0;
                     
                     // Match against this level
-                    let (remaining_qty, level_trades) = level.match_against(
-                        order.id,
-                        order.side,
-                        order.qty,
+                    let (remaining_qty, level_trades) = Self::match_level(
+                        level,
+                        match_filter,
+                        guard.as_ref(),
+                        &order,
                         price,
+                        trade_ts,
                     );
 
+                    Self::collect_filled(level, &level_trades, &mut filled);
                     trades.extend(level_trades);
                     order.qty = remaining_qty;
 
@@ -426,8 +1713,8 @@ impl<D: QueueDiscipline> OrderBook<D> {
                         prices_to_remove.push(*reverse_price);
                     }
 
-                    // Stop if order is fully filled
-                    if order.qty == 0 {
+                    // Stop if order is fully filled or self-match prevention cancelled it
+                    if order.qty == 0 || guard.as_ref().is_some_and(SelfMatchGuard::cancels_taker) {
                         break;
                     }
                 }
@@ -439,6 +1726,10 @@ impl<D: QueueDiscipline> OrderBook<D> {
             }
         }
 
+        let reached = guard.map(SelfMatchGuard::into_reached).unwrap_or_default();
+        self.forget_filled(&filled);
+        self.prevent_self_match(&mut order, None, reached, !trades.is_empty())?;
+
         // Update metrics for each trade and spread history if trades occurred
         if !trades.is_empty() {
             for trade in &trades {
@@ -448,29 +1739,124 @@ impl<D: QueueDiscipline> OrderBook<D> {
             self.update_spread_history();
         }
 
-        // Market orders don't rest in the book - any unfilled quantity is lost
-        if order.qty > 0 {
-            return Err(EngineError::reject(format!(
-                "Market order partially filled: {} shares unfilled", order.qty
-            )));
+        // Market orders don't rest in the book - any unfilled quantity is lost
+        if order.qty > 0 {
+            return Err(EngineError::PartialFill { unfilled: order.qty });
+        }
+
+        Ok(trades)
+    }
+
+    /// Refuse a non-marketable limit order that would rest past the depth
+    /// limit, when the limit's policy is to reject
+    fn check_depth_limit(&self, side: Side, price: Price) -> EngineResult<()> {
+        let limit = self.depth_limit;
+        if limit.policy != DepthPolicy::Reject || !limit.is_bounded() {
+            return Ok(());
+        }
+        
+        // A marketable order rests, if at all, at the new touch
+        let (touch, marketable) = match side {
+            Side::Buy => (self.best_bid(), self.best_ask().is_some_and(|ask| price >= ask)),
+            Side::Sell => (self.best_ask(), self.best_bid().is_some_and(|bid| price <= bid)),
+        };
+        let Some(touch) = touch.filter(|_| !marketable) else {
+            return Ok(());
+        };
+        
+        let (levels_ahead, exists) = match side {
+            Side::Buy => (self.bids.range(..Reverse(price)).count(), self.bids.contains_key(&Reverse(price))),
+            Side::Sell => (self.asks.range(..price).count(), self.asks.contains_key(&price)),
+        };
+        let ticks = match side {
+            Side::Buy => touch.saturating_sub(price),
+            Side::Sell => price.saturating_sub(touch),
+        };
+        // Joining an existing level adds no depth
+        let index = if exists { 0 } else { levels_ahead };
+        if limit.excludes(index, ticks) {
+            return Err(EngineError::BeyondDepthLimit {
+                price,
+                reason: format!("{} levels and {} ticks behind the touch", levels_ahead, ticks),
+            });
+        }
+        Ok(())
+    }
+
+    /// Prune the levels on one side that lie past the depth limit
+    fn enforce_depth_limit(&mut self, side: Side) {
+        let limit = self.depth_limit;
+        if !limit.is_bounded() {
+            return;
+        }
+        
+        // Levels are best first, so those past the limit form the tail
+        let touch = match side {
+            Side::Buy => self.best_bid(),
+            Side::Sell => self.best_ask(),
+        };
+        let Some(touch) = touch else {
+            return;
+        };
+        let past_limit = |&(index, price): &(usize, Price)| limit.excludes(index, price.abs_diff(touch));
+        let excluded: Vec<Price> = match side {
+            Side::Buy => self.bids.keys().map(|price| price.0).enumerate()
+                .skip_while(|level| !past_limit(level)).map(|(_, price)| price).collect(),
+            Side::Sell => self.asks.keys().copied().enumerate()
+                .skip_while(|level| !past_limit(level)).map(|(_, price)| price).collect(),
+        };
+        for price in excluded {
+            self.prune_level(side, price);
         }
+    }
 
-        Ok(trades)
+    /// Remove a whole price level, queueing its orders as pruned
+    fn prune_level(&mut self, side: Side, price: Price) {
+        let log_order_operation = self.operation_logger();
+        
+        let level = match side {
+            Side::Buy => self.bids.remove(&Reverse(price)),
+            Side::Sell => self.asks.remove(&price),
+        };
+        let Some(mut level) = level else {
+            return;
+        };
+        
+        // Not every discipline lists its orders, so find them through the
+        // index and prune them in id order; pruning is rare enough that the
+        // scan doesn't matter
+        let mut order_ids: Vec<OrderId> = self.order_index.iter()
+            .filter(|(_, &location)| location == (side, price))
+            .map(|(&order_id, _)| order_id)
+            .collect();
+        order_ids.sort_unstable();
+        
+        for order_id in order_ids {
+            self.order_index.remove(&order_id);
+            self.stp_groups.remove(&order_id);
+            let qty = level.cancel(order_id);
+            log_order_operation("PRUNED", order_id, Some(&format!("{:?} {} @ {} past depth limit", side, qty, price)));
+            self.pruned.push(PrunedOrder { order_id, side, price, qty, reason: PruneReason::DepthLimit });
+        }
     }
 
     /// Add an order to the appropriate side of the book
     fn add_to_book(&mut self, order: Order, price: Price) -> EngineResult<()> {
         // Add to order index
         self.order_index.insert(order.id, (order.side, price));
+        if let Some(group) = self.stp_group {
+            self.stp_groups.insert(order.id, group);
+        }
+        self.churn.adds += 1;
 
         // Add to appropriate side
         match order.side {
             Side::Buy => {
-                let level = self.bids.entry(Reverse(price)).or_insert_with(&self.level_factory);
+                let level = self.bids.entry(Reverse(price)).or_insert_with(|| (self.level_factory)());
                 level.enqueue(order);
             }
             Side::Sell => {
-                let level = self.asks.entry(price).or_insert_with(&self.level_factory);
+                let level = self.asks.entry(price).or_insert_with(|| (self.level_factory)());
                 level.enqueue(order);
             }
         }
@@ -478,9 +1864,68 @@ impl<D: QueueDiscipline> OrderBook<D> {
         Ok(())
     }
 
+    /// Copy, under the same rules, of the part of the book an order can
+    /// touch: the opposite levels within its limit and, for a limit order,
+    /// its own side, where the remainder would rest
+    fn scratch_for(&self, order: &Order) -> EngineResult<Self> {
+        let mut scratch = Self::with_shared_factory(self.level_factory.clone());
+        scratch.match_filter = self.match_filter;
+        scratch.lot_size = self.lot_size;
+        scratch.depth_limit = self.depth_limit;
+        scratch.stp_mode = self.stp_mode;
+        scratch.crossed_book_policy = self.crossed_book_policy;
+        scratch.trade_timestamps = self.trade_timestamps;
+        scratch.clock = self.clock;
+        scratch.quiet = true;
+
+        for (side, price, level) in self.levels() {
+            if !Self::touches(order, side, price) {
+                continue;
+            }
+            for resting in level.orders() {
+                if let Some(&group) = self.stp_groups.get(&resting.id) {
+                    scratch.stp_groups.insert(resting.id, group);
+                }
+                scratch.add_to_book(resting, price)?;
+            }
+        }
+        Ok(scratch)
+    }
+
+    /// Whether an order can change the level at `price` on `side`
+    fn touches(order: &Order, side: Side, price: Price) -> bool {
+        match order.price() {
+            None => side != order.side,
+            Some(_) if side == order.side => true,
+            Some(limit) => match order.side {
+                Side::Buy => price <= limit,
+                Side::Sell => price >= limit,
+            },
+        }
+    }
+
+    /// Every price level: bids best first, then asks best first
+    fn levels(&self) -> impl Iterator<Item = (Side, Price, &D)> {
+        self.bids.iter().map(|(price, level)| (Side::Buy, price.0, level))
+            .chain(self.asks.iter().map(|(&price, level)| (Side::Sell, price, level)))
+    }
+
+    /// Best price and its quantity on a side
+    fn touch(&self, side: Side) -> Option<(Price, Qty)> {
+        match side {
+            Side::Buy => self.bids.iter().next().map(|(price, level)| (price.0, level.total_qty())),
+            Side::Sell => self.asks.iter().next().map(|(&price, level)| (price, level.total_qty())),
+        }
+    }
+
+    /// The engine's time: its clock once set, the wall clock until then
+    fn now(&self) -> u128 {
+        self.clock.unwrap_or_else(now_ns)
+    }
+
     /// Calculate latency in milliseconds for visualization
     fn calculate_latency_ms(&self, last_activity_ts: u128) -> u64 {
-        let current_ts = now_ns();
+        let current_ts = self.now();
         let latency_ns = current_ts.saturating_sub(last_activity_ts);
         (latency_ns / 1_000_000).min(u64::MAX as u128) as u64
     }
@@ -488,7 +1933,7 @@ impl<D: QueueDiscipline> OrderBook<D> {
     /// Update spread history with current spread
     fn update_spread_history(&mut self) {
         if let Some(spread) = self.spread() {
-            let ts = now_ns();
+            let ts = self.now();
             self.recent_spreads.push((ts, spread));
         }
     }
@@ -499,7 +1944,7 @@ impl<D: QueueDiscipline> OrderBook<D> {
         self.metrics.update_trade(taker_side, trade.qty, trade.price);
         
         // Calculate PnL using current mid-price
-        let mid_price_ticks = self.mid_price().map(|mid| (mid * 10000.0) as Price);
+        let mid_price_ticks = self.best_bid().zip(self.best_ask()).map(|(bid, ask)| price_utils::mid_ticks(bid, ask));
         self.metrics.calculate_pnl(mid_price_ticks);
     }
 
@@ -517,18 +1962,66 @@ impl<D: QueueDiscipline> OrderBook<D> {
     pub fn reset_metrics(&mut self) {
         self.metrics = crate::types::Metrics::new();
     }
+
+    /// Where a resting order stands, if it is still in the book
+    pub fn order_state(&self, order_id: OrderId) -> Option<OrderState> {
+        let (side, price) = *self.order_index.get(&order_id)?;
+        let level = match side {
+            Side::Buy => self.bids.get(&Reverse(price)),
+            Side::Sell => self.asks.get(&price),
+        }?;
+        let resting = level.resting();
+        let queue_position = resting.iter().position(|(id, _)| *id == order_id)?;
+        Some(OrderState { order_id, side, price, qty: resting[queue_position].1, queue_position })
+    }
+
+    /// Hash of every resting order's id, side, price, remaining quantity and
+    /// queue position
+    ///
+    /// Timestamps are left out, so two books built by the same commands hash
+    /// alike whenever they ran. The digest is SHA-256 over fixed-width
+    /// little-endian fields, so it is the same across builds, toolchains and
+    /// platforms and can be stored next to a recording.
+    pub fn state_hash(&self) -> u64 {
+        use sha2::{Digest, Sha256};
+
+        let mut hasher = Sha256::new();
+        let levels = self.bids.iter().map(|(Reverse(price), level)| (Side::Buy, *price, level))
+            .chain(self.asks.iter().map(|(price, level)| (Side::Sell, *price, level)));
+        for (side, price, level) in levels {
+            let resting = level.resting();
+            hasher.update([side as u8]);
+            hasher.update(price.to_le_bytes());
+            hasher.update((resting.len() as u64).to_le_bytes());
+            for (order_id, qty) in resting {
+                hasher.update(order_id.to_le_bytes());
+                hasher.update(qty.to_le_bytes());
+            }
+        }
+        let digest = hasher.finalize();
+        u64::from_le_bytes(digest[..8].try_into().expect("SHA-256 digests are 32 bytes"))
+    }
 }
 
 impl<D: QueueDiscipline> OrderBookEngine for OrderBook<D> {
-    fn place(&mut self, order: Order) -> EngineResult<Vec<Trade>> {
-        use crate::logging::{log_order_operation, log_trade, log_engine_error};
+    fn place(&mut self, mut order: Order) -> EngineResult<Vec<Trade>> {
+        use crate::logging::{log_trade, log_engine_error};
         
+        let log_order_operation = self.operation_logger();
         let order_id = order.id;
         let start_time = Instant::now();
         
-        // Validate the order
-        if let Err(e) = self.validate_order(&order) {
-            log_engine_error(&e, Some(&format!("Order {} validation", order_id)));
+        // Normalize to whole lots, then validate the order
+        let validated = if order_ids::is_historical(order.id) {
+            Ok(())
+        } else {
+            self.lot_size.normalize(order.qty).map(|qty| order.qty = qty)
+        }
+        .and_then(|_| self.validate_order(&order));
+        if let Err(e) = validated {
+            if !self.quiet {
+                log_engine_error(&e, Some(&format!("Order {} validation", order_id)));
+            }
             
             // Record failed order in performance metrics
             if let Some(ref perf_metrics) = self.perf_metrics {
@@ -541,8 +2034,14 @@ impl<D: QueueDiscipline> OrderBookEngine for OrderBook<D> {
         // Process based on order type
         let result = match order.order_type {
             OrderType::Limit { price } => {
+                let side = order.side;
                 log_order_operation("PLACE_LIMIT", order_id, Some(&format!("{:?} {} @ {}", order.side, order.qty, price)));
-                self.process_limit_order(order, price)
+                let result = self.check_depth_limit(side, price)
+                    .and_then(|_| self.process_limit_order(order, price));
+                if result.is_ok() {
+                    self.enforce_depth_limit(side);
+                }
+                result
             },
             OrderType::Market => {
                 log_order_operation("PLACE_MARKET", order_id, Some(&format!("{:?} {}", order.side, order.qty)));
@@ -554,6 +2053,7 @@ impl<D: QueueDiscipline> OrderBookEngine for OrderBook<D> {
         
         match &result {
             Ok(trades) => {
+                self.churn.trades += trades.len() as u64;
                 // Record successful order in performance metrics
                 if let Some(ref perf_metrics) = self.perf_metrics {
                     perf_metrics.record_order_placement(processing_time, true);
@@ -565,9 +2065,11 @@ impl<D: QueueDiscipline> OrderBookEngine for OrderBook<D> {
                 } else {
                     log_order_operation("PLACED_WITH_FILLS", order_id, Some(&format!("{} trades, Processing time: {:?}", trades.len(), processing_time)));
                     
-                    // Log each trade
-                    for trade in trades {
-                        log_trade(trade.maker_id, trade.taker_id, trade.price, trade.qty, trade.ts);
+                    // Log each trade, unless it is a preview's
+                    if !self.quiet {
+                        for trade in trades {
+                            log_trade(trade.maker_id, trade.taker_id, trade.price, trade.qty, trade.ts);
+                        }
                     }
                 }
             }
@@ -577,7 +2079,9 @@ impl<D: QueueDiscipline> OrderBookEngine for OrderBook<D> {
                     perf_metrics.record_order_placement(processing_time, false);
                 }
                 
-                log_engine_error(e, Some(&format!("Order {} placement failed after {:?}", order_id, processing_time)));
+                if !self.quiet {
+                    log_engine_error(e, Some(&format!("Order {} placement failed after {:?}", order_id, processing_time)));
+                }
             }
         }
 
@@ -585,11 +2089,15 @@ impl<D: QueueDiscipline> OrderBookEngine for OrderBook<D> {
     }
 
     fn cancel(&mut self, order_id: OrderId) -> EngineResult<Qty> {
-        use crate::logging::{log_order_operation, log_engine_error};
+        use crate::logging::log_engine_error;
+        
+        let log_order_operation = self.operation_logger();
         
         let start_time = Instant::now();
+        let now = self.now();
         
         // Look up order in index
+        self.stp_groups.remove(&order_id);
         let (side, price) = match self.order_index.remove(&order_id) {
             Some(location) => {
                 log_order_operation("CANCEL_LOOKUP_SUCCESS", order_id, Some(&format!("{:?} @ {}", location.0, location.1)));
@@ -629,6 +2137,7 @@ impl<D: QueueDiscipline> OrderBookEngine for OrderBook<D> {
                 };
                 
                 let qty = level.cancel(order_id);
+                level.touch(now);
                 
                 // Remove level if empty
                 if level.is_empty() {
@@ -657,6 +2166,7 @@ impl<D: QueueDiscipline> OrderBookEngine for OrderBook<D> {
                 };
                 
                 let qty = level.cancel(order_id);
+                level.touch(now);
                 
                 // Remove level if empty
                 if level.is_empty() {
@@ -682,6 +2192,8 @@ impl<D: QueueDiscipline> OrderBookEngine for OrderBook<D> {
             return Err(error);
         }
 
+        self.churn.cancels += 1;
+
         // Record successful cancellation in performance metrics
         if let Some(ref perf_metrics) = self.perf_metrics {
             perf_metrics.record_order_cancellation(processing_time, true);
@@ -715,32 +2227,38 @@ impl<D: QueueDiscipline> OrderBookEngine for OrderBook<D> {
     }
 
     fn snapshot(&self) -> DepthSnapshot {
+        self.snapshot_top(usize::MAX)
+    }
+
+    fn snapshot_top(&self, max_levels: usize) -> DepthSnapshot {
         let start_time = Instant::now();
         
-        let ts = now_ns();
+        let ts = self.now();
         let best_bid = self.best_bid();
         let best_ask = self.best_ask();
         let spread = self.spread();
         let mid = self.mid_price();
 
+        let point = |price: Price, level: &D| BookLevelPoint {
+            price,
+            qty: level.total_qty(),
+            latency_ms: self.calculate_latency_ms(level.last_ts()),
+            orders: level.order_count(),
+            notional: price as u128 * level.total_qty() as u128,
+        };
+
         // Collect bid levels (already in descending price order)
         let bids: Vec<BookLevelPoint> = self.bids
             .iter()
-            .map(|(reverse_price, level)| BookLevelPoint {
-                price: reverse_price.0,
-                qty: level.total_qty(),
-                latency_ms: self.calculate_latency_ms(level.last_ts()),
-            })
+            .take(max_levels)
+            .map(|(reverse_price, level)| point(reverse_price.0, level))
             .collect();
 
         // Collect ask levels (already in ascending price order)
         let asks: Vec<BookLevelPoint> = self.asks
             .iter()
-            .map(|(price, level)| BookLevelPoint {
-                price: *price,
-                qty: level.total_qty(),
-                latency_ms: self.calculate_latency_ms(level.last_ts()),
-            })
+            .take(max_levels)
+            .map(|(price, level)| point(*price, level))
             .collect();
 
         let snapshot = DepthSnapshot {
@@ -749,10 +2267,23 @@ impl<D: QueueDiscipline> OrderBookEngine for OrderBook<D> {
             best_ask,
             spread,
             mid,
+            bid_totals: SideTotals::of(&bids),
+            ask_totals: SideTotals::of(&asks),
             bids,
             asks,
             recent_spreads: self.get_recent_spreads(),
             metrics: self.metrics.clone(),
+            trades: Vec::new(),
+            executions: Vec::new(),
+            block_trades: Vec::new(),
+            book_state: BookState::of(best_bid, best_ask),
+            crossings: self.crossings,
+            agents: Vec::new(),
+            auction: None,
+            order_ages: None,
+            watermark: None,
+            order_seq: None,
+            orders: None,
         };
 
         // Record snapshot generation time in performance metrics
@@ -762,6 +2293,233 @@ impl<D: QueueDiscipline> OrderBookEngine for OrderBook<D> {
 
         snapshot
     }
+
+    fn clear(&mut self) {
+        self.bids.clear();
+        self.asks.clear();
+        self.order_index.clear();
+        self.stp_groups.clear();
+        self.quotes.clear();
+        self.recent_spreads.clear();
+    }
+
+    fn set_match_filter(&mut self, filter: Option<MatchFilter>) {
+        self.match_filter = filter;
+    }
+
+    fn set_lot_size(&mut self, lot_size: LotSize) {
+        self.lot_size = lot_size;
+    }
+
+    fn set_crossed_book_policy(&mut self, policy: CrossedBookPolicy) {
+        self.crossed_book_policy = policy;
+    }
+
+    fn set_stp_group(&mut self, group: Option<StpGroup>) {
+        self.stp_group = group;
+    }
+
+    fn set_stp_mode(&mut self, mode: StpMode) {
+        self.stp_mode = mode;
+    }
+
+    fn set_trade_timestamps(&mut self, timestamps: TradeTimestamps) {
+        self.trade_timestamps = timestamps;
+    }
+
+    fn set_clock(&mut self, now: u128) {
+        self.clock = Some(now);
+    }
+
+    fn set_wall_clock_isolation(&mut self, isolated: bool) {
+        self.isolate_wall_clock = isolated;
+    }
+
+    fn isolates_wall_clock(&self) -> bool {
+        self.isolate_wall_clock
+    }
+
+    fn set_depth_limit(&mut self, limit: DepthLimit) {
+        self.depth_limit = limit;
+        self.enforce_depth_limit(Side::Buy);
+        self.enforce_depth_limit(Side::Sell);
+    }
+
+    fn take_pruned(&mut self) -> Vec<PrunedOrder> {
+        std::mem::take(&mut self.pruned)
+    }
+
+    fn compact(&mut self) -> Compaction {
+        // Quote legs are kept until their owner quotes again, so drop the
+        // ones that have since left the book
+        let quoted = self.quotes.values().map(Vec::len).sum::<usize>();
+        for legs in self.quotes.values_mut() {
+            legs.retain(|order_id| self.order_index.contains_key(order_id));
+        }
+        self.quotes.retain(|_, legs| !legs.is_empty());
+        let stale = quoted - self.quotes.values().map(Vec::len).sum::<usize>();
+        (shrink_map(&mut self.order_index) + shrink_map(&mut self.stp_groups) + shrink_map(&mut self.quotes)
+            + shrink_vec(&mut self.pruned) + self.recent_spreads.compact())
+            .with_entries(stale)
+    }
+
+    fn stats(&self) -> EngineStats {
+        let resting_orders = self.bids.values().chain(self.asks.values())
+            .map(|level| level.order_count())
+            .sum();
+        EngineStats { resting_orders, bid_levels: self.bids.len(), ask_levels: self.asks.len(), ..self.churn }
+    }
+
+    fn pay_priority_fee(&mut self, order_id: OrderId, fee: u64) -> EngineResult<u64> {
+        let (side, price) = *self.order_index.get(&order_id)
+            .ok_or(EngineError::UnknownOrder { order_id })?;
+        let now = self.now();
+        let level = match side {
+            Side::Buy => self.bids.get_mut(&Reverse(price)),
+            Side::Sell => self.asks.get_mut(&price),
+        }.ok_or_else(|| EngineError::internal("Order index inconsistency: level not found"))?;
+        let charged = level.pay_priority_fee(order_id, fee);
+        level.touch(now);
+        // The book's own metrics tally what its orders paid
+        self.metrics.priority_fees += charged;
+        Ok(charged)
+    }
+
+    fn preview(&self, order: Order, stp_group: Option<StpGroup>) -> EngineResult<OrderPreview> {
+        if self.order_index.contains_key(&order.id) {
+            return Err(EngineError::DuplicateOrder { order_id: order.id });
+        }
+
+        // Matching runs for real on a scratch copy of the levels the order
+        // reaches; the levels it cannot reach are read from this book
+        let mut scratch = self.scratch_for(&order)?;
+        scratch.stp_group = stp_group;
+        let (order_id, qty, ts) = (order.id, order.qty, order.ts);
+        let trades = scratch.place(order.clone())?;
+        let filled: Qty = trades.iter().map(|trade| trade.qty).sum();
+        let notional: u128 = trades.iter().map(|trade| trade.qty as u128 * trade.price as u128).sum();
+
+        let bbo = |bid: Option<(Price, Qty)>, ask: Option<(Price, Qty)>| BboUpdate {
+            ts,
+            best_bid: bid.map(|(price, _)| price),
+            bid_qty: bid.map_or(0, |(_, qty)| qty),
+            best_ask: ask.map(|(price, _)| price),
+            ask_qty: ask.map_or(0, |(_, qty)| qty),
+        };
+        let after_touch = |side: Side| {
+            scratch.touch(side).or_else(|| {
+                self.levels()
+                    .find(|&(level_side, price, _)| level_side == side && !Self::touches(&order, side, price))
+                    .map(|(_, price, level)| (price, level.total_qty()))
+            })
+        };
+        let levels_consumed = self.levels()
+            .filter(|&(side, price, _)| side != order.side && Self::touches(&order, side, price))
+            .filter(|&(side, price, _)| scratch.depth_at(side, price) == 0)
+            .count();
+        let after = bbo(after_touch(Side::Buy), after_touch(Side::Sell));
+
+        Ok(OrderPreview {
+            trades,
+            filled,
+            remaining: qty.saturating_sub(filled),
+            rests: scratch.order_index.contains_key(&order_id),
+            avg_price: (filled > 0).then(|| notional as f64 / filled as f64),
+            levels_consumed,
+            pruned: scratch.take_pruned(),
+            before: bbo(self.touch(Side::Buy), self.touch(Side::Sell)),
+            after,
+        })
+    }
+
+    fn resting_orders(&self) -> Vec<Order> {
+        self.bids.values()
+            .chain(self.asks.values())
+            .flat_map(|level| level.orders())
+            .collect()
+    }
+
+    fn resting_qty(&self, order_id: OrderId) -> Option<Qty> {
+        self.order_state(order_id).map(|state| state.qty)
+    }
+
+    fn priority_fee_paid(&self, order_id: OrderId) -> u64 {
+        let level = match self.order_index.get(&order_id) {
+            Some(&(Side::Buy, price)) => self.bids.get(&Reverse(price)),
+            Some(&(Side::Sell, price)) => self.asks.get(&price),
+            None => None,
+        };
+        level.map_or(0, |level| level.priority_fee_paid(order_id))
+    }
+
+    fn order_ages(&self, now: u128) -> OrderAges {
+        // Each level knows its oldest order; the rest come from its queue
+        let level_ages = |price: Price, level: &D| -> (Price, Option<u128>, Vec<u128>) {
+            (price, level.oldest_order_ts(), level.orders().iter().map(|order| order.ts).collect())
+        };
+        OrderAges {
+            bids: SideAges::measure(now, self.bids.iter().map(|(price, level)| level_ages(price.0, level))),
+            asks: SideAges::measure(now, self.asks.iter().map(|(&price, level)| level_ages(price, level))),
+        }
+    }
+
+    fn quote(&mut self, quote: TwoSidedQuote) -> EngineResult<QuoteOutcome> {
+        let log_order_operation = self.operation_logger();
+
+        // Check both legs before pulling anything
+        if let (Some(bid), Some(ask)) = (quote.bid, quote.ask) {
+            if bid.order_id == ask.order_id {
+                return Err(EngineError::InvalidQuote { reason: format!("legs share order ID {}", bid.order_id) });
+            }
+            if bid.price >= ask.price {
+                return Err(EngineError::InvalidQuote {
+                    reason: format!("bid {} is not below its ask {}", price_utils::format(bid.price), price_utils::format(ask.price)),
+                });
+            }
+        }
+        for mut order in quote.orders() {
+            if !order_ids::is_historical(order.id) {
+                order.qty = self.lot_size.normalize(order.qty)?;
+            }
+            self.validate_order(&order)?;
+        }
+
+        // Legs stay tracked until they are pulled, so a failed cancel leaves
+        // the rest for the next quote to pull
+        let mut outcome = QuoteOutcome::default();
+        let legs = self.quotes.get(&quote.owner).cloned().unwrap_or_default();
+        for (pulled, &order_id) in legs.iter().enumerate() {
+            // Legs that have since filled are already gone
+            if self.order_state(order_id).is_none() {
+                continue;
+            }
+            match self.cancel(order_id) {
+                Ok(qty) => outcome.cancelled.push((order_id, qty)),
+                Err(e) => {
+                    self.quotes.insert(quote.owner, legs[pulled..].to_vec());
+                    return Err(e);
+                }
+            }
+        }
+        self.quotes.remove(&quote.owner);
+        for order in quote.orders() {
+            let order_id = order.id;
+            match self.place(order) {
+                Ok(trades) => outcome.trades.extend(trades),
+                Err(e) => outcome.refused.push((order_id, e)),
+            }
+            if self.order_state(order_id).is_some() {
+                outcome.resting.push(order_id);
+            }
+        }
+        log_order_operation("QUOTE", quote.owner, Some(&format!(
+            "{} legs pulled, {} resting, {} refused", outcome.cancelled.len(), outcome.resting.len(), outcome.refused.len()
+        )));
+        if !outcome.resting.is_empty() {
+            self.quotes.insert(quote.owner, outcome.resting.clone());
+        }
+        Ok(outcome)
+    }
 }
 
 #[cfg(test)]
@@ -783,6 +2541,250 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_quote_cancel_replaces_both_sides() {
+        let mut book = TestOrderBook::new();
+        let ts = now_ns();
+        let leg = |order_id, price, qty| QuoteLeg { order_id, price, qty };
+        let outcome = book.quote(TwoSidedQuote::new(7, leg(1, 99_0000, 100), leg(2, 101_0000, 100), ts)).unwrap();
+        assert_eq!((outcome.resting, outcome.cancelled), (vec![1, 2], vec![]));
+
+        // The ask fills in part; requoting pulls the rest of it and the bid
+        book.place(Order::new_market(3, Side::Buy, 40, ts)).unwrap();
+        let outcome = book.quote(TwoSidedQuote::new(7, leg(4, 99_5000, 100), leg(5, 100_5000, 100), ts)).unwrap();
+        assert_eq!(outcome.cancelled, vec![(1, 100), (2, 60)]);
+        assert_eq!((book.best_bid(), book.best_ask()), (Some(99_5000), Some(100_5000)));
+
+        // An invalid or crossed quote changes nothing
+        assert!(book.quote(TwoSidedQuote::new(7, leg(6, 99_0000, 0), leg(8, 101_0000, 100), ts)).is_err());
+        assert!(book.quote(TwoSidedQuote::new(7, leg(6, 101_0000, 10), leg(8, 100_0000, 10), ts)).is_err());
+        assert_eq!(book.resting_orders().iter().map(|order| order.id).collect::<Vec<_>>(), vec![4, 5]);
+
+        // A marketable leg trades, and other owners' quotes stay put
+        book.place(Order::new_limit(9, Side::Sell, 30, 99_8000, ts)).unwrap();
+        let outcome = book.quote(TwoSidedQuote { owner: 8, bid: Some(leg(10, 99_8000, 50)), ask: None, ts }).unwrap();
+        assert_eq!((outcome.trades.len(), outcome.resting.clone()), (1, vec![10]));
+        assert_eq!(book.depth_at(Side::Buy, 99_5000), 100);
+
+        // A leg a book rule refuses is reported once the old quote is pulled
+        book.set_crossed_book_policy(CrossedBookPolicy::RejectIncoming);
+        book.set_match_filter(Some(|_, _| false));
+        let outcome = book.quote(TwoSidedQuote::new(7, leg(11, 99_0000, 10), leg(12, 99_8000, 10), ts)).unwrap();
+        assert_eq!(outcome.resting, vec![11]);
+        assert!(matches!(outcome.refused[..], [(12, EngineError::CrossedBook { .. })]));
+    }
+
+    /// A level with only the methods `QueueDiscipline` requires
+    #[derive(Default)]
+    struct MinimalLevel(FifoLevel);
+
+    impl QueueDiscipline for MinimalLevel {
+        fn enqueue(&mut self, order: Order) {
+            self.0.enqueue(order)
+        }
+
+        fn match_against(&mut self, taker_id: OrderId, taker_side: Side, taker_qty: Qty, price: Price) -> (Qty, Vec<Trade>) {
+            self.0.match_against(taker_id, taker_side, taker_qty, price)
+        }
+
+        fn cancel(&mut self, order_id: OrderId) -> Qty {
+            self.0.cancel(order_id)
+        }
+
+        fn total_qty(&self) -> Qty {
+            self.0.total_qty()
+        }
+
+        fn is_empty(&self) -> bool {
+            self.0.is_empty()
+        }
+
+        fn touch(&mut self, ts: u128) {
+            self.0.touch(ts)
+        }
+
+        fn last_ts(&self) -> u128 {
+            self.0.last_ts()
+        }
+
+        fn order_count(&self) -> usize {
+            self.0.order_count()
+        }
+
+        fn oldest_order_ts(&self) -> Option<u128> {
+            self.0.oldest_order_ts()
+        }
+
+        fn orders(&self) -> Vec<Order> {
+            self.0.orders()
+        }
+    }
+
+    /// An engine with only the methods `OrderBookEngine` requires, and the
+    /// `resting_orders` its other defaults read
+    struct MinimalEngine(OrderBook<MinimalLevel>);
+
+    impl OrderBookEngine for MinimalEngine {
+        fn place(&mut self, order: Order) -> EngineResult<Vec<Trade>> {
+            self.0.place(order)
+        }
+
+        fn cancel(&mut self, order_id: OrderId) -> EngineResult<Qty> {
+            self.0.cancel(order_id)
+        }
+
+        fn best_bid(&self) -> Option<Price> {
+            self.0.best_bid()
+        }
+
+        fn best_ask(&self) -> Option<Price> {
+            self.0.best_ask()
+        }
+
+        fn depth_at(&self, side: Side, price: Price) -> Qty {
+            self.0.depth_at(side, price)
+        }
+
+        fn snapshot(&self) -> DepthSnapshot {
+            self.0.snapshot()
+        }
+
+        fn resting_orders(&self) -> Vec<Order> {
+            self.0.resting_orders()
+        }
+    }
+
+    #[test]
+    fn test_trait_defaults_serve_minimal_implementations() {
+        let ts = now_ns();
+        let mut level = MinimalLevel::default();
+        level.enqueue(Order::new_limit(1, Side::Sell, 50, 1_010_000, ts));
+        level.enqueue(Order::new_limit(2, Side::Sell, 30, 1_010_000, ts));
+        assert_eq!(level.resting(), vec![(1, 50), (2, 30)]);
+        // Without a way to step over an order, a filter that rules any out
+        // leaves the level as it is
+        let (left, trades) = level.match_against_filtered(9, Side::Buy, 60, 1_010_000, &|maker_id, _| maker_id != 2);
+        assert_eq!((left, trades.len(), level.total_qty()), (60, 0, 80));
+        let (left, trades) = level.match_against_filtered(9, Side::Buy, 60, 1_010_000, &|_, _| true);
+        assert_eq!((left, trades.len(), level.total_qty()), (0, 2, 20));
+
+        // Books on such levels still see each order and honour the filter
+        let mut book = OrderBook::<MinimalLevel>::new();
+        book.place(Order::new_limit(1, Side::Sell, 50, 1_010_000, ts)).unwrap();
+        book.place(Order::new_limit(2, Side::Sell, 30, 1_010_000, ts)).unwrap();
+        assert_eq!(book.order_state(2).map(|state| (state.qty, state.queue_position)), Some((30, 1)));
+        book.set_match_filter(Some(|_, _| false));
+        let trades = book.place(Order::new_limit(3, Side::Buy, 20, 1_010_000, ts)).unwrap();
+        assert!(trades.is_empty());
+        assert_eq!(book.depth_at(Side::Sell, 1_010_000), 80);
+
+        // A partly filled maker stays indexed, so it can be cancelled and
+        // its id isn't free for reuse
+        book.set_match_filter(None);
+        let trades = book.place(Order::new_limit(4, Side::Buy, 20, 1_010_000, ts)).unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(book.depth_at(Side::Sell, 1_010_000), 60);
+        assert!(book.place(Order::new_limit(1, Side::Sell, 10, 1_020_000, ts)).is_err());
+        assert_eq!(book.cancel(1), Ok(30));
+
+        // Settings the engine has no support for are ignored
+        let mut engine = MinimalEngine(OrderBook::new());
+        engine.set_match_filter(Some(|_, _| false));
+        engine.set_lot_size(LotSize { size: 100, ..LotSize::default() });
+        engine.set_depth_limit(DepthLimit::levels(1));
+        engine.place(Order::new_limit(1, Side::Sell, 50, 1_010_000, ts)).unwrap();
+        engine.place(Order::new_limit(2, Side::Sell, 30, 1_020_000, ts)).unwrap();
+        engine.place(Order::new_limit(3, Side::Buy, 20, 990_000, ts)).unwrap();
+        assert!(engine.take_pruned().is_empty());
+
+        // Previews run on a rebuilt book
+        let preview = engine.preview(Order::new_market(4, Side::Buy, 60, ts), None).unwrap();
+        assert_eq!((preview.filled, preview.levels_consumed), (60, 1));
+        assert_eq!(engine.depth_at(Side::Sell, 1_010_000), 50);
+
+        let leg = |order_id, price, qty| QuoteLeg { order_id, price, qty };
+        let quote = TwoSidedQuote::new(7, leg(5, 1_000_000, 10), leg(6, 1_005_000, 10), ts);
+        assert!(matches!(engine.quote(quote), Err(EngineError::Reject { .. })));
+        assert_eq!(engine.pay_priority_fee(2, 5), Ok(0));
+        assert_eq!(engine.pay_priority_fee(9, 5), Err(EngineError::UnknownOrder { order_id: 9 }));
+
+        engine.clear();
+        assert!(engine.resting_orders().is_empty());
+        assert_eq!((engine.best_bid(), engine.best_ask()), (None, None));
+    }
+
+    #[test]
+    fn test_preview_matches_without_touching_the_book() {
+        let mut book = OrderBook::<FifoLevel>::new();
+        book.place(Order::new_limit(1, Side::Sell, 50, 1_010_000, now_ns())).unwrap();
+        book.place(Order::new_limit(2, Side::Sell, 30, 1_020_000, now_ns())).unwrap();
+        book.place(Order::new_limit(3, Side::Buy, 20, 990_000, now_ns())).unwrap();
+        let resting = book.resting_orders();
+
+        // Fills the best ask, rests the rest at its limit below the next one
+        let preview = book.preview(Order::new_limit(4, Side::Buy, 80, 1_015_000, now_ns()), None).unwrap();
+        assert_eq!(preview.trades.len(), 1);
+        assert_eq!((preview.filled, preview.remaining, preview.rests), (50, 30, true));
+        assert_eq!(preview.avg_price, Some(1_010_000.0));
+        assert_eq!(preview.levels_consumed, 1);
+        assert_eq!((preview.before.best_bid, preview.before.best_ask), (Some(990_000), Some(1_010_000)));
+        assert_eq!((preview.after.best_bid, preview.after.bid_qty), (Some(1_015_000), 30));
+        assert_eq!((preview.after.best_ask, preview.after.ask_qty), (Some(1_020_000), 30));
+
+        // A market sell that takes the whole bid side leaves it empty; one
+        // larger than it is refused as it would be on placement
+        let preview = book.preview(Order::new_market(5, Side::Sell, 20, now_ns()), None).unwrap();
+        assert_eq!((preview.filled, preview.remaining, preview.rests), (20, 0, false));
+        assert_eq!((preview.after.best_bid, preview.after.best_ask), (None, Some(1_010_000)));
+        assert!(book.preview(Order::new_market(5, Side::Sell, 100, now_ns()), None).is_err());
+
+        // Self-match prevention and duplicate ids apply as they would on placement
+        book.set_stp_mode(StpMode::CancelResting);
+        book.set_stp_group(Some(7));
+        book.place(Order::new_limit(6, Side::Sell, 10, 1_000_000, now_ns())).unwrap();
+        book.set_stp_group(None);
+        let preview = book.preview(Order::new_limit(8, Side::Buy, 10, 1_000_000, now_ns()), Some(7)).unwrap();
+        assert_eq!(preview.filled, 0);
+        assert_eq!(preview.pruned.iter().map(|pruned| pruned.order_id).collect::<Vec<_>>(), vec![6]);
+        assert!(book.preview(Order::new_limit(1, Side::Buy, 10, 990_000, now_ns()), None).is_err());
+
+        assert_eq!(book.resting_orders().len(), resting.len() + 1);
+        assert_eq!(book.best_ask(), Some(1_000_000));
+    }
+
+    #[test]
+    fn test_preview_logs_nothing() {
+        use std::io::Write;
+        use std::sync::Mutex;
+
+        #[derive(Clone, Default)]
+        struct Captured(Arc<Mutex<Vec<u8>>>);
+
+        impl Write for Captured {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut book = OrderBook::<FifoLevel>::new();
+        book.place(Order::new_limit(1, Side::Sell, 50, 1_010_000, now_ns())).unwrap();
+
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt().with_writer(move || writer.clone()).finish();
+        tracing::subscriber::with_default(subscriber, || {
+            let preview = book.preview(Order::new_limit(2, Side::Buy, 80, 1_015_000, now_ns()), None).unwrap();
+            assert_eq!(preview.filled, 50);
+        });
+        // The trade never happened, so nothing says it did
+        assert!(captured.0.lock().unwrap().is_empty());
+    }
+
     #[test]
     fn test_order_book_creation() {
         let book = TestOrderBook::new();
@@ -930,7 +2932,7 @@ mod tests {
         
         book.place(order1).unwrap();
         let result = book.place(order2);
-        assert!(matches!(result, Err(EngineError::Reject { .. })));
+        assert!(matches!(result, Err(EngineError::DuplicateOrder { order_id: 3 })));
     }
 
     #[test]
@@ -982,6 +2984,25 @@ mod tests {
         assert!(snapshot.asks[0].latency_ms < 1000000);
     }
 
+    #[test]
+    fn test_snapshot_order_counts_and_notional() {
+        let mut book = TestOrderBook::new();
+        for (id, side, qty, price) in [(1, Side::Buy, 100, 500000), (2, Side::Buy, 50, 500000), (3, Side::Buy, 20, 495000), (4, Side::Sell, 10, 510000)] {
+            book.place(create_test_order(id, side, qty, OrderType::Limit { price })).unwrap();
+        }
+
+        let snapshot = book.snapshot();
+        assert_eq!((snapshot.bids[0].orders, snapshot.bids[0].notional), (2, 75_000_000));
+        assert_eq!(snapshot.bid_totals, SideTotals { levels: 2, orders: 3, qty: 170, notional: 84_900_000 });
+        assert_eq!(snapshot.ask_totals, SideTotals { levels: 1, orders: 1, qty: 10, notional: 5_100_000 });
+
+        // A partial fill shrinks the level's notional but keeps its order
+        book.place(create_test_order(5, Side::Sell, 110, OrderType::Market)).unwrap();
+        let snapshot = book.snapshot();
+        assert_eq!((snapshot.bids[0].orders, snapshot.bids[0].qty, snapshot.bids[0].notional), (1, 40, 20_000_000));
+        assert_eq!(snapshot.bid_totals.orders, 2);
+    }
+
     #[test]
     fn test_spread_history_tracking() {
         let mut book = TestOrderBook::new();
@@ -1172,4 +3193,262 @@ mod tests {
         assert_eq!(book.depth_at(Side::Sell, 520000), 150); // Untouched
         assert_eq!(book.best_ask(), Some(510000));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_clear_removes_all_orders() {
+        let mut book = TestOrderBook::new();
+        
+        book.place(create_test_order(1, Side::Buy, 100, OrderType::Limit { price: 490000 })).unwrap();
+        book.place(create_test_order(2, Side::Sell, 100, OrderType::Limit { price: 510000 })).unwrap();
+        
+        book.clear();
+        
+        assert_eq!(book.best_bid(), None);
+        assert_eq!(book.best_ask(), None);
+        assert!(book.cancel(1).is_err());
+        
+        // Previously used IDs can be placed again
+        assert!(book.place(create_test_order(1, Side::Buy, 100, OrderType::Limit { price: 490000 })).is_ok());
+    }
+
+    #[test]
+    fn test_stats_keep_running_totals_readers_diff() {
+        let mut book = TestOrderBook::new();
+        book.place(create_test_order(1, Side::Buy, 100, OrderType::Limit { price: 490000 })).unwrap();
+        book.place(create_test_order(2, Side::Buy, 100, OrderType::Limit { price: 480000 })).unwrap();
+        book.place(create_test_order(3, Side::Sell, 100, OrderType::Limit { price: 510000 })).unwrap();
+        book.place(create_test_order(4, Side::Sell, 50, OrderType::Limit { price: 510000 })).unwrap();
+        book.cancel(2).unwrap();
+        book.place(create_test_order(5, Side::Buy, 120, OrderType::Market)).unwrap();
+
+        let stats = book.stats();
+        assert_eq!((stats.resting_orders, stats.bid_levels, stats.ask_levels), (2, 1, 1));
+        assert_eq!((stats.adds, stats.cancels, stats.trades), (4, 1, 2));
+        assert_eq!(book.collect_stats().unwrap(), stats);
+
+        // Each reader diffs against its own previous reading
+        let (mut health, mut analytics) = (StatsReader::default(), StatsReader::default());
+        let first = health.read(1_000_000_000, stats);
+        assert_eq!((first.activity.adds, first.activity.resting_orders_change, first.activity.interval_ns), (4, 2, 0));
+        assert_eq!(first.activity.adds_per_sec, 0.0);
+        book.cancel(1).unwrap();
+        let report = health.read(3_000_000_000, book.stats());
+        assert_eq!((report.totals.cancels, report.activity.cancels, report.activity.adds), (2, 1, 0));
+        assert_eq!((report.activity.resting_orders_change, report.activity.bid_levels_change), (-1, -1));
+        assert_eq!(report.activity.cancels_per_sec, 0.5);
+        assert_eq!(analytics.read(3_000_000_000, book.stats()).activity.cancels, 2);
+
+        // A clock that went back starts the reader over
+        let report = health.read(2_000_000_000, book.stats());
+        assert_eq!((report.activity.cancels, report.activity.interval_ns), (2, 0));
+
+        // An engine without counts reports its composition
+        let engine = MinimalEngine(OrderBook::new());
+        assert_eq!(engine.stats(), EngineStats::default());
+        assert_eq!(EngineStats::of_orders(&book.resting_orders()), EngineStats { adds: 0, cancels: 0, trades: 0, ..book.stats() });
+    }
+
+    #[test]
+    fn test_filled_orders_leave_the_index() {
+        let mut book = TestOrderBook::new();
+        book.place(create_test_order(1, Side::Sell, 100, OrderType::Limit { price: 510000 })).unwrap();
+        book.place(create_test_order(2, Side::Sell, 50, OrderType::Limit { price: 510000 })).unwrap();
+        book.place(create_test_order(4, Side::Sell, 50, OrderType::Limit { price: 520000 })).unwrap();
+        book.set_stp_group(Some(7));
+        book.place(create_test_order(5, Side::Buy, 10, OrderType::Limit { price: 480000 })).unwrap();
+        book.set_stp_group(None);
+        book.place(create_test_order(3, Side::Buy, 120, OrderType::Market)).unwrap();
+        book.place(create_test_order(6, Side::Sell, 10, OrderType::Limit { price: 480000 })).unwrap();
+
+        // Filled makers are forgotten as they fill, partly filled ones kept
+        assert!(matches!(book.cancel(1), Err(EngineError::UnknownOrder { order_id: 1 })));
+        assert!(book.stp_groups.is_empty());
+        assert_eq!(book.order_index.keys().copied().collect::<HashSet<_>>(), HashSet::from([2, 4]));
+        assert!(book.place(create_test_order(1, Side::Buy, 10, OrderType::Limit { price: 490000 })).is_ok());
+
+        // Compaction is left with nothing to forget
+        assert_eq!(book.compact_book().unwrap().entries, 0);
+        assert_eq!(book.cancel(2).unwrap(), 30);
+    }
+
+    #[test]
+    fn test_lot_size_enforcement() {
+        use crate::types::OddLotPolicy;
+
+        let mut book = TestOrderBook::new();
+        book.set_lot_size(LotSize::new(100));
+        
+        let result = book.place(create_test_order(1, Side::Buy, 150, OrderType::Limit { price: 490000 }));
+        assert!(matches!(result, Err(EngineError::OddLot { qty: 150, lot_size: 100 })));
+        assert!(book.place(create_test_order(2, Side::Buy, 200, OrderType::Limit { price: 490000 })).is_ok());
+        
+        // Historical orders keep whatever quantity the data had
+        let historical = order_ids::historical(3).unwrap();
+        assert!(book.place(create_test_order(historical, Side::Buy, 37, OrderType::Limit { price: 490000 })).is_ok());
+        
+        book.set_lot_size(LotSize::new(100).with_policy(OddLotPolicy::RoundDown));
+        book.place(create_test_order(4, Side::Sell, 250, OrderType::Limit { price: 510000 })).unwrap();
+        assert_eq!(book.depth_at(Side::Sell, 510000), 200);
+        assert!(book.place(create_test_order(5, Side::Sell, 50, OrderType::Limit { price: 510000 })).is_err());
+    }
+
+    #[test]
+    fn test_depth_limit_rejects_or_prunes_far_orders() {
+        let limit = |price| OrderType::Limit { price };
+        let mut book = TestOrderBook::new();
+        book.set_depth_limit(DepthLimit::levels(2).with_band(2000));
+        book.place(create_test_order(1, Side::Buy, 100, limit(500000))).unwrap();
+        book.place(create_test_order(2, Side::Buy, 100, limit(499000))).unwrap();
+
+        // Past the level cap or the band, but joining a level is fine
+        let result = book.place(create_test_order(3, Side::Buy, 100, limit(498500)));
+        assert!(matches!(result, Err(EngineError::BeyondDepthLimit { price: 498500, .. })));
+        let result = book.place(create_test_order(4, Side::Sell, 100, limit(503000)));
+        assert!(result.is_ok(), "empty side has no touch to measure from");
+        assert!(book.place(create_test_order(5, Side::Sell, 100, limit(505001))).is_err());
+        book.place(create_test_order(6, Side::Buy, 50, limit(499000))).unwrap();
+        assert!(book.take_pruned().is_empty());
+
+        // A better bid pushes the worst level out, notifying its owners
+        book.place(create_test_order(7, Side::Buy, 100, limit(500500))).unwrap();
+        let pruned = book.take_pruned();
+        assert_eq!(pruned.iter().map(|o| (o.order_id, o.qty)).collect::<Vec<_>>(), vec![(2, 100), (6, 50)]);
+        assert!(pruned.iter().all(|o| o.side == Side::Buy && o.price == 499000));
+        assert_eq!(book.depth_at(Side::Buy, 499000), 0);
+        assert!(matches!(book.cancel(2), Err(EngineError::UnknownOrder { .. })));
+
+        // Under the prune policy the far order is accepted, then dropped
+        book.set_depth_limit(DepthLimit::levels(2).with_band(2000).with_policy(DepthPolicy::Prune));
+        book.place(create_test_order(8, Side::Buy, 100, limit(490000))).unwrap();
+        assert_eq!(book.take_pruned(), vec![PrunedOrder { order_id: 8, side: Side::Buy, price: 490000, qty: 100, reason: PruneReason::DepthLimit }]);
+        assert_eq!(book.best_bid(), Some(500500));
+        assert_eq!(book.depth_at(Side::Buy, 500000), 100);
+
+        // Tightening the limit prunes what it no longer covers
+        book.set_depth_limit(DepthLimit::levels(1));
+        assert_eq!(book.take_pruned().iter().map(|o| o.order_id).collect::<Vec<_>>(), vec![1]);
+    }
+
+    #[test]
+    fn test_crossed_book_policies() {
+        fn never(_maker: OrderId, _taker: OrderId) -> bool { false }
+        fn only_maker_one(maker: OrderId, _taker: OrderId) -> bool { maker == 1 }
+        let limit = |price| OrderType::Limit { price };
+        let book_with = |policy, filter: MatchFilter| {
+            let mut book = TestOrderBook::new();
+            book.place(create_test_order(1, Side::Sell, 30, limit(1010000))).unwrap();
+            book.place(create_test_order(2, Side::Sell, 100, limit(1010000))).unwrap();
+            book.set_match_filter(Some(filter));
+            book.set_crossed_book_policy(policy);
+            book
+        };
+
+        // Flagged orders rest and the snapshot shows the state
+        let mut book = book_with(CrossedBookPolicy::FlagAndContinue, never);
+        book.place(create_test_order(3, Side::Buy, 50, limit(1010000))).unwrap();
+        assert_eq!(book.snapshot().book_state, BookState::Locked);
+        book.place(create_test_order(4, Side::Buy, 50, limit(1020000))).unwrap();
+        let snapshot = book.snapshot();
+        assert_eq!(snapshot.book_state, BookState::Crossed);
+        assert_eq!((snapshot.crossings.locked, snapshot.crossings.crossed), (1, 1));
+
+        // Auto-matching trades through the filter at the resting prices
+        let mut book = book_with(CrossedBookPolicy::AutoMatch, never);
+        let trades = book.place(create_test_order(3, Side::Buy, 40, limit(1020000))).unwrap();
+        assert_eq!(trades.iter().map(|t| (t.maker_id, t.price, t.qty)).collect::<Vec<_>>(),
+                   vec![(1, 1010000, 30), (2, 1010000, 10)]);
+        let snapshot = book.snapshot();
+        assert_eq!(snapshot.book_state, BookState::Normal);
+        assert_eq!(snapshot.crossings.auto_matched, 1);
+
+        // Rejection refuses the resting part; fills already made stand
+        let mut book = book_with(CrossedBookPolicy::RejectIncoming, never);
+        let result = book.place(create_test_order(3, Side::Buy, 40, limit(1010000)));
+        assert!(matches!(result, Err(EngineError::CrossedBook { price: 1010000, state: BookState::Locked })));
+        let mut book = book_with(CrossedBookPolicy::RejectIncoming, only_maker_one);
+        let trades = book.place(create_test_order(3, Side::Buy, 50, limit(1010000))).unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(book.depth_at(Side::Buy, 1010000), 0);
+        assert_eq!(book.snapshot().crossings.rejected, 1);
+    }
+
+    #[test]
+    fn test_self_match_prevention_cancels_taker_or_resting() {
+        let limit = |price| OrderType::Limit { price };
+        let book_with = |mode| {
+            let mut book = TestOrderBook::new();
+            book.set_stp_mode(mode);
+            book.place(create_test_order(1, Side::Sell, 10, limit(1000000))).unwrap();
+            book.set_stp_group(Some(7));
+            book.place(create_test_order(2, Side::Sell, 10, limit(1010000))).unwrap();
+            book.set_stp_group(None);
+            book.place(create_test_order(3, Side::Sell, 10, limit(1020000))).unwrap();
+            book.set_stp_group(Some(7));
+            book
+        };
+        let makers = |trades: Vec<Trade>| trades.iter().map(|t| t.maker_id).collect::<Vec<_>>();
+
+        // The taker stops at its own group's quote and its remainder is cancelled
+        let mut book = book_with(StpMode::CancelTaker);
+        assert_eq!(makers(book.place(create_test_order(4, Side::Buy, 30, limit(1020000))).unwrap()), vec![1]);
+        assert_eq!(book.best_bid(), None);
+        assert_eq!(book.depth_at(Side::Sell, 1010000), 10);
+        assert_eq!(book.take_pruned(), vec![PrunedOrder { order_id: 4, side: Side::Buy, price: 1020000, qty: 20, reason: PruneReason::SelfMatch }]);
+        assert!(matches!(book.place(create_test_order(5, Side::Buy, 5, limit(1010000))), Err(EngineError::SelfTrade { order_id: 5 })));
+
+        // Or the quote is cancelled and matching carries on past it
+        let mut book = book_with(StpMode::CancelResting);
+        assert_eq!(makers(book.place(create_test_order(4, Side::Buy, 30, limit(1020000))).unwrap()), vec![1, 3]);
+        assert_eq!(book.take_pruned(), vec![PrunedOrder { order_id: 2, side: Side::Sell, price: 1010000, qty: 10, reason: PruneReason::SelfMatch }]);
+        assert_eq!(book.depth_at(Side::Buy, 1020000), 10);
+        assert_eq!(book.best_ask(), None);
+
+        // Orders outside the group trade with it as usual
+        let mut book = book_with(StpMode::CancelTaker);
+        book.set_stp_group(None);
+        assert_eq!(makers(book.place(create_test_order(4, Side::Buy, 30, limit(1020000))).unwrap()), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_trades_take_the_taker_order_time() {
+        let mut book = TestOrderBook::new();
+        book.place(create_test_order(1, Side::Sell, 50, OrderType::Limit { price: 1010000 })).unwrap();
+        book.place(create_test_order(2, Side::Sell, 50, OrderType::Limit { price: 1020000 })).unwrap();
+
+        let mut taker = create_test_order(3, Side::Buy, 60, OrderType::Market);
+        taker.ts -= 1_000_000_000;
+        let trades = book.place(taker.clone()).unwrap();
+        assert_eq!(trades.len(), 2);
+        assert!(trades.iter().all(|trade| trade.ts == taker.ts));
+
+        book.set_trade_timestamps(TradeTimestamps::WallClock);
+        let taker = create_test_order(4, Side::Buy, 10, OrderType::Limit { price: 1020000 });
+        let before = now_ns();
+        let trades = book.place(Order { ts: before - 1_000_000_000, ..taker }).unwrap();
+        assert!(trades[0].ts >= before);
+    }
+
+    #[test]
+    fn test_order_timestamps_are_checked_against_the_engine_clock() {
+        const HOUR_NS: u128 = 3_600_000_000_000;
+        let mut book = TestOrderBook::new();
+        let order = |id, ts| Order::new_limit(id, Side::Buy, 10, 1_000_000, ts);
+
+        // Until the clock is set the wall clock stands in
+        assert!(book.place(order(1, now_ns() - 2 * HOUR_NS)).is_err());
+
+        // Simulated time far from the host's is fine once the clock follows it
+        let clock = 1_000 * HOUR_NS;
+        book.set_clock(clock);
+        book.place(order(2, clock - HOUR_NS / 2)).unwrap();
+        assert!(book.place(order(3, clock + 2 * HOUR_NS)).is_err());
+        assert!(book.place(order(4, clock - 2 * HOUR_NS)).is_err());
+        // Replayed orders keep their recorded time, however old
+        book.place(order(order_ids::historical(5).unwrap(), 0)).unwrap();
+
+        // Stamping trades from the wall clock checks against it instead
+        book.set_trade_timestamps(TradeTimestamps::WallClock);
+        assert!(book.place(order(6, clock)).is_err());
+        book.place(order(7, now_ns())).unwrap();
+    }
+}
@@ -0,0 +1,157 @@
+//! Market data entitlements
+//!
+//! Exchanges license their data in tiers: top of book only, a few levels of
+//! depth, the full book by price, or every order. With entitlements
+//! enabled, a `/ws` or `/feed` client presents an API key when it connects
+//! and the tier the key is licensed for limits what it receives: the levels
+//! of each snapshot, and whether it may negotiate the market-by-order feed.
+//! Clients without a key get the default tier. The depth tensor needs a
+//! full-depth key, and the streaming publisher, whose consumers present no
+//! key, is held to the default tier.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::masking::DepthMask;
+
+/// Data product a session is licensed for, from least to most detail
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EntitlementTier {
+    /// Best bid and offer only
+    Bbo,
+    /// Five levels a side
+    Depth5,
+    /// Every level, by price
+    Full,
+    /// Every level and the market-by-order feed
+    #[default]
+    Mbo,
+}
+
+impl EntitlementTier {
+    /// Levels a side the tier shows, if it caps them
+    pub fn max_levels(&self) -> Option<usize> {
+        match self {
+            Self::Bbo => Some(1),
+            Self::Depth5 => Some(5),
+            Self::Full | Self::Mbo => None,
+        }
+    }
+
+    /// Whether the tier shows every level
+    pub fn full_depth(&self) -> bool {
+        self.max_levels().is_none()
+    }
+
+    /// Whether the tier includes the market-by-order feed
+    pub fn includes_orders(&self) -> bool {
+        *self == Self::Mbo
+    }
+
+    /// `mask`, showing no more levels than the tier allows
+    pub fn restrict(&self, mask: DepthMask) -> DepthMask {
+        let max_levels = match (mask.max_levels, self.max_levels()) {
+            (Some(mask), Some(tier)) => Some(mask.min(tier)),
+            (mask, tier) => mask.or(tier),
+        };
+        DepthMask { max_levels, ..mask }
+    }
+}
+
+impl fmt::Display for EntitlementTier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Bbo => "bbo",
+            Self::Depth5 => "depth5",
+            Self::Full => "full",
+            Self::Mbo => "mbo",
+        })
+    }
+}
+
+/// API keys and the data tiers they are licensed for
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct EntitlementConfig {
+    /// Limit each `/ws` session and `/feed` relay to its tier; every client
+    /// gets `mbo` when off
+    pub enabled: bool,
+    /// Tier of clients that connect without a key
+    #[serde(default = "default_tier")]
+    pub default_tier: EntitlementTier,
+    /// Tier of each API key, which clients send as `?api_key=<key>`
+    #[serde(default)]
+    pub keys: BTreeMap<String, EntitlementTier>,
+}
+
+fn default_tier() -> EntitlementTier {
+    EntitlementTier::Bbo
+}
+
+impl Default for EntitlementConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            default_tier: default_tier(),
+            keys: BTreeMap::new(),
+        }
+    }
+}
+
+impl EntitlementConfig {
+    /// Tier of a client presenting `key`, or `None` for a key that is not
+    /// configured
+    pub fn tier(&self, key: Option<&str>) -> Option<EntitlementTier> {
+        if !self.enabled {
+            return Some(EntitlementTier::Mbo);
+        }
+        match key {
+            Some(key) => self.keys.get(key).copied(),
+            None => Some(self.default_tier),
+        }
+    }
+
+    /// Tier of channels whose consumers present no key
+    pub fn public_tier(&self) -> EntitlementTier {
+        match self.enabled {
+            true => self.default_tier,
+            false => EntitlementTier::Mbo,
+        }
+    }
+
+    /// Check the keys
+    pub fn validate(&self) -> Result<(), String> {
+        if self.keys.keys().any(|key| key.is_empty()) {
+            return Err("Entitlement API keys cannot be empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_pick_tiers_that_cap_depth() {
+        let config = EntitlementConfig {
+            enabled: true,
+            keys: BTreeMap::from([("vendor".to_string(), EntitlementTier::Depth5), ("desk".to_string(), EntitlementTier::Mbo)]),
+            ..EntitlementConfig::default()
+        };
+        assert_eq!(config.tier(None), Some(EntitlementTier::Bbo));
+        assert_eq!(config.tier(Some("vendor")), Some(EntitlementTier::Depth5));
+        assert_eq!(config.tier(Some("stolen")), None);
+        assert_eq!(EntitlementConfig::default().tier(Some("stolen")), Some(EntitlementTier::Mbo));
+        assert_eq!((config.public_tier(), EntitlementConfig::default().public_tier()), (EntitlementTier::Bbo, EntitlementTier::Mbo));
+
+        // The tier caps a role's levels but never widens them
+        let role = DepthMask { max_levels: Some(3), hide_order_counts: true, ..DepthMask::default() };
+        assert_eq!(EntitlementTier::Depth5.restrict(role).max_levels, Some(3));
+        assert_eq!(EntitlementTier::Bbo.restrict(role), DepthMask { max_levels: Some(1), ..role });
+        assert_eq!(EntitlementTier::Full.restrict(DepthMask::default()), DepthMask::default());
+        assert!(EntitlementTier::Mbo.includes_orders() && !EntitlementTier::Full.includes_orders());
+        assert!(EntitlementTier::Full.full_depth() && !EntitlementTier::Depth5.full_depth());
+    }
+}
@@ -1,8 +1,9 @@
 use thiserror::Error;
+use crate::engine::BookState;
 use crate::types::{OrderId, Price, Qty};
 
 /// Comprehensive error types for the order book engine
-#[derive(Error, Debug, Clone, PartialEq)]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
 pub enum EngineError {
     /// Order not found for cancellation or modification
     #[error("Order with ID {order_id} not found")]
@@ -20,6 +21,26 @@ pub enum EngineError {
     #[error("Order rejected: {reason}")]
     Reject { reason: String },
 
+    /// An order with this id is already in the book
+    #[error("Order ID {order_id} already exists")]
+    DuplicateOrder { order_id: OrderId },
+
+    /// Order timestamp more than an hour from the engine's time
+    #[error("Order timestamp {ts} is too far in the {direction}")]
+    TimestampOutOfRange { ts: u128, direction: &'static str },
+
+    /// Market order ran out of liquidity before it filled
+    #[error("Market order partially filled: {unfilled} shares unfilled")]
+    PartialFill { unfilled: Qty },
+
+    /// Two-sided quote whose legs do not form a valid pair
+    #[error("Invalid quote: {reason}")]
+    InvalidQuote { reason: String },
+
+    /// Refused while the market is in a pre-open state
+    #[error("Market is pre-open: {reason}")]
+    PreOpen { reason: String },
+
     /// Market order cannot be placed when no opposite side exists
     #[error("Market order cannot be executed: no liquidity on opposite side")]
     NoLiquidity,
@@ -32,6 +53,10 @@ pub enum EngineError {
     #[error("Order quantity {qty} exceeds maximum allowed {max_qty}")]
     QtyTooLarge { qty: Qty, max_qty: Qty },
 
+    /// Order quantity is not a whole number of lots
+    #[error("Order quantity {qty} is not a multiple of the lot size {lot_size}")]
+    OddLot { qty: Qty, lot_size: Qty },
+
     /// Price is outside allowed range
     #[error("Price {price} is outside allowed range [{min_price}, {max_price}]")]
     PriceOutOfRange {
@@ -40,6 +65,39 @@ pub enum EngineError {
         max_price: Price,
     },
 
+    /// Order would rest past the book's depth limit
+    #[error("Price {price} is beyond the depth limit: {reason}")]
+    BeyondDepthLimit { price: Price, reason: String },
+
+    /// Order would rest locking or crossing the opposite side of the book
+    #[error("Order at {price} would leave the book {state}")]
+    CrossedBook { price: Price, state: BookState },
+
+    /// Value of a quantity of contracts does not fit in settlement units
+    #[error("Value of {qty} contracts at {price} overflows the account's range")]
+    ValueOverflow { qty: Qty, price: Price },
+
+    /// Order would take its owner past the notional velocity limit
+    #[error("Velocity limit for {owner}: {notional} notional would exceed {limit} in the window ({window_notional} already submitted)")]
+    VelocityLimit {
+        owner: String,
+        notional: u64,
+        window_notional: u64,
+        limit: u64,
+    },
+
+    /// Order would leave its account without the equity its positions need
+    #[error("Insufficient buying power: requires {required} margin, equity {equity}")]
+    InsufficientMargin { required: i64, equity: i64 },
+
+    /// Order entry is down for a simulated gateway outage
+    #[error("Gateway unavailable: order entry is down")]
+    GatewayUnavailable,
+
+    /// Order entry refused the order because the matching engine is busy
+    #[error("Order entry throttled: matching engine busy, retry after {retry_after_ms}ms")]
+    Throttled { retry_after_ms: u64 },
+
     /// Order book is in an invalid state
     #[error("Order book internal error: {details}")]
     InternalError { details: String },
@@ -96,6 +154,12 @@ impl EngineError {
         }
     }
 
+    /// Whether a risk control refused the order: the velocity limit, the
+    /// margin check or self-trade prevention
+    pub fn is_risk_rejection(&self) -> bool {
+        matches!(self, Self::VelocityLimit { .. } | Self::InsufficientMargin { .. } | Self::SelfTrade { .. })
+    }
+
     /// Check if this is a recoverable error
     pub fn is_recoverable(&self) -> bool {
         match self {
@@ -103,10 +167,23 @@ impl EngineError {
             Self::InvalidPrice { .. } => false,
             Self::InvalidQty { .. } => false,
             Self::Reject { .. } => true,
+            Self::DuplicateOrder { .. } => false,
+            Self::TimestampOutOfRange { .. } => false,
+            Self::PartialFill { .. } => true,
+            Self::InvalidQuote { .. } => false,
+            Self::PreOpen { .. } => true,
             Self::NoLiquidity => true,
             Self::SelfTrade { .. } => true,
             Self::QtyTooLarge { .. } => false,
+            Self::OddLot { .. } => false,
             Self::PriceOutOfRange { .. } => false,
+            Self::BeyondDepthLimit { .. } => true,
+            Self::CrossedBook { .. } => true,
+            Self::ValueOverflow { .. } => false,
+            Self::VelocityLimit { .. } => true,
+            Self::InsufficientMargin { .. } => true,
+            Self::GatewayUnavailable => true,
+            Self::Throttled { .. } => true,
             Self::InternalError { .. } => false,
             Self::DataError { .. } => true,
             Self::NetworkError { .. } => true,
@@ -121,10 +198,23 @@ impl EngineError {
             Self::InvalidPrice { .. } => ErrorSeverity::Error,
             Self::InvalidQty { .. } => ErrorSeverity::Error,
             Self::Reject { .. } => ErrorSeverity::Info,
+            Self::DuplicateOrder { .. } => ErrorSeverity::Error,
+            Self::TimestampOutOfRange { .. } => ErrorSeverity::Error,
+            Self::PartialFill { .. } => ErrorSeverity::Info,
+            Self::InvalidQuote { .. } => ErrorSeverity::Error,
+            Self::PreOpen { .. } => ErrorSeverity::Info,
             Self::NoLiquidity => ErrorSeverity::Warning,
             Self::SelfTrade { .. } => ErrorSeverity::Warning,
             Self::QtyTooLarge { .. } => ErrorSeverity::Error,
+            Self::OddLot { .. } => ErrorSeverity::Error,
             Self::PriceOutOfRange { .. } => ErrorSeverity::Error,
+            Self::BeyondDepthLimit { .. } => ErrorSeverity::Info,
+            Self::CrossedBook { .. } => ErrorSeverity::Info,
+            Self::ValueOverflow { .. } => ErrorSeverity::Error,
+            Self::VelocityLimit { .. } => ErrorSeverity::Warning,
+            Self::InsufficientMargin { .. } => ErrorSeverity::Warning,
+            Self::GatewayUnavailable => ErrorSeverity::Warning,
+            Self::Throttled { .. } => ErrorSeverity::Info,
             Self::InternalError { .. } => ErrorSeverity::Critical,
             Self::DataError { .. } => ErrorSeverity::Warning,
             Self::NetworkError { .. } => ErrorSeverity::Warning,
@@ -0,0 +1,696 @@
+//! Feature export for model training
+//!
+//! A [`FeatureExporter`] samples the book on a fixed grid of simulation time
+//! and writes one [`FeatureVector`] per grid point to CSV or, with the
+//! `parquet` feature, Parquet. Grid points between two snapshots carry the
+//! state of the earlier one forward, so rows are evenly spaced whether the
+//! snapshots come from synthetic flow or a replayed dataset.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::engine::{BookLevelPoint, DepthSnapshot};
+use crate::time::ms_to_ns;
+use crate::price_format::{PricePrecision, PRICE_DECIMALS};
+use crate::types::{Qty, Side};
+
+/// Output format for exported features
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum FeatureFormat {
+    #[default]
+    Csv,
+    /// Requires the `parquet` feature
+    Parquet,
+}
+
+impl FeatureFormat {
+    /// Infer the format from a file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "csv" => Some(Self::Csv),
+            "parquet" | "pq" => Some(Self::Parquet),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for FeatureFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "parquet" | "pq" => Ok(Self::Parquet),
+            _ => Err(format!("Unknown feature format '{}', expected csv or parquet", s)),
+        }
+    }
+}
+
+impl fmt::Display for FeatureFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Csv => write!(f, "csv"),
+            Self::Parquet => write!(f, "parquet"),
+        }
+    }
+}
+
+/// Where and how finely to export features
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct FeatureExportConfig {
+    /// Export features while the server runs
+    pub enabled: bool,
+    /// Output file, overwritten if it exists
+    pub path: PathBuf,
+    /// Output format (inferred from the path extension if unset, else CSV)
+    #[serde(default)]
+    pub format: Option<FeatureFormat>,
+    /// Milliseconds of simulation time between rows
+    pub interval_ms: u64,
+    /// Book levels per side to report quantity for
+    pub depth_levels: usize,
+    /// Rows of mid returns the volatility is taken over
+    pub volatility_window: usize,
+}
+
+impl Default for FeatureExportConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: PathBuf::from("features.csv"),
+            format: None,
+            interval_ms: 100,
+            depth_levels: 5,
+            volatility_window: 20,
+        }
+    }
+}
+
+impl FeatureExportConfig {
+    /// Check the sampling settings
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval_ms == 0 {
+            return Err("Feature export interval must be greater than 0".to_string());
+        }
+        if self.path.as_os_str().is_empty() {
+            return Err("Feature export path cannot be empty".to_string());
+        }
+        if self.depth_levels == 0 {
+            return Err("Feature export depth levels must be greater than 0".to_string());
+        }
+        if self.volatility_window < 2 {
+            return Err("Feature export volatility window must be at least 2".to_string());
+        }
+        Ok(())
+    }
+
+    /// The format to write `path` in
+    pub fn resolved_format(&self) -> FeatureFormat {
+        self.format
+            .or_else(|| FeatureFormat::from_path(&self.path))
+            .unwrap_or_default()
+    }
+}
+
+/// Feature export error types
+#[derive(Debug, thiserror::Error)]
+pub enum FeatureError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("CSV error: {0}")]
+    Csv(#[from] csv::Error),
+
+    #[error("Parquet error: {0}")]
+    Parquet(String),
+
+    #[error("{0} feature export is not compiled in; rebuild with --features {0}")]
+    Unavailable(FeatureFormat),
+}
+
+pub type FeatureResult<T> = Result<T, FeatureError>;
+
+/// Book and tape features at one grid point
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeatureVector {
+    /// Grid time in simulation nanoseconds
+    pub ts: u128,
+    /// Mid price, if both sides are quoted
+    pub mid: Option<f64>,
+    /// Best ask minus best bid in ticks
+    pub spread: Option<i64>,
+    /// Quantity at each of the best bid levels, zero past the visible book
+    pub bid_qty: Vec<Qty>,
+    /// Quantity at each of the best ask levels, zero past the visible book
+    pub ask_qty: Vec<Qty>,
+    /// Bid minus ask quantity over the reported levels, as a share of both
+    pub imbalance: Option<f64>,
+    /// +1 if the last trade was buyer-initiated, -1 if seller-initiated, 0 before any trade
+    pub last_trade_sign: i8,
+    /// Standard deviation of log mid returns over the volatility window
+    pub volatility: Option<f64>,
+}
+
+impl FeatureVector {
+    /// Column names for `depth_levels` levels, in row order
+    pub fn columns(depth_levels: usize) -> Vec<String> {
+        let mut columns = vec!["ts".to_string(), "mid".to_string(), "spread".to_string()];
+        columns.extend((1..=depth_levels).map(|level| format!("bid_qty_{}", level)));
+        columns.extend((1..=depth_levels).map(|level| format!("ask_qty_{}", level)));
+        columns.extend(["imbalance", "last_trade_sign", "volatility"].map(String::from));
+        columns
+    }
+
+    /// Values in column order, empty where a feature is undefined
+    fn record(&self) -> Vec<String> {
+        let optional = |value: Option<String>| value.unwrap_or_default();
+        // A mid can fall between ticks, so it keeps one place more than a price
+        let mid_precision = PricePrecision::default().with_decimals(PRICE_DECIMALS + 1);
+        let mut record = vec![
+            self.ts.to_string(),
+            optional(self.mid.and_then(|mid| mid_precision.format_units(mid))),
+            optional(self.spread.map(|spread| spread.to_string())),
+        ];
+        record.extend(self.bid_qty.iter().chain(&self.ask_qty).map(Qty::to_string));
+        record.push(optional(self.imbalance.map(|imbalance| imbalance.to_string())));
+        record.push(self.last_trade_sign.to_string());
+        record.push(optional(self.volatility.map(|volatility| volatility.to_string())));
+        record
+    }
+}
+
+/// Book state carried forward between snapshots
+#[derive(Debug, Clone)]
+struct BookState {
+    mid: Option<f64>,
+    spread: Option<i64>,
+    bid_qty: Vec<Qty>,
+    ask_qty: Vec<Qty>,
+    last_trade_sign: i8,
+}
+
+/// Most grid points a gap between snapshots is forward-filled over; a longer
+/// gap starts a new series
+pub const MAX_FILL_INTERVALS: u128 = 1_000;
+
+/// Turns irregular snapshots into rows on a fixed time grid
+#[derive(Debug, Clone)]
+pub struct FeatureSampler {
+    interval_ns: u128,
+    depth_levels: usize,
+    volatility_window: usize,
+    /// Next grid time to emit
+    next_ts: Option<u128>,
+    /// Time of the latest snapshot
+    last_ts: Option<u128>,
+    /// State as of the latest snapshot
+    latest: Option<BookState>,
+    /// Mids of recent rows, newest last
+    mids: VecDeque<f64>,
+}
+
+impl FeatureSampler {
+    pub fn new(config: &FeatureExportConfig) -> Self {
+        Self {
+            interval_ns: ms_to_ns(config.interval_ms.max(1)),
+            depth_levels: config.depth_levels,
+            volatility_window: config.volatility_window,
+            next_ts: None,
+            last_ts: None,
+            latest: None,
+            mids: VecDeque::with_capacity(config.volatility_window + 1),
+        }
+    }
+
+    /// Forget the series, so the next snapshot starts a new grid
+    pub fn reset(&mut self) {
+        self.next_ts = None;
+        self.last_ts = None;
+        self.latest = None;
+        self.mids.clear();
+    }
+
+    /// Take in a snapshot, returning the rows for every grid point up to its time
+    ///
+    /// Grid points before the snapshot carry the previous snapshot's state;
+    /// a grid point at the snapshot's own time uses the new state. A snapshot
+    /// earlier than the last one, or more than [`MAX_FILL_INTERVALS`] grid
+    /// points after it, is a seek or reset and starts a new series.
+    pub fn observe(&mut self, snapshot: &DepthSnapshot) -> Vec<FeatureVector> {
+        let max_gap = MAX_FILL_INTERVALS * self.interval_ns;
+        if self.last_ts.is_some_and(|last| snapshot.ts < last || snapshot.ts - last > max_gap) {
+            self.reset();
+        }
+        self.last_ts = Some(snapshot.ts);
+
+        let next_ts = *self.next_ts.get_or_insert_with(|| snapshot.ts.div_ceil(self.interval_ns) * self.interval_ns);
+        let mut rows = Vec::new();
+        let mut ts = next_ts;
+        while ts < snapshot.ts {
+            if let Some(state) = self.latest.clone() {
+                rows.push(self.row(ts, &state));
+            }
+            ts += self.interval_ns;
+        }
+
+        let last_trade_sign = match snapshot.trades.last() {
+            Some(trade) => match trade.aggressor {
+                Side::Buy => 1,
+                Side::Sell => -1,
+            },
+            None => self.latest.as_ref().map_or(0, |state| state.last_trade_sign),
+        };
+        let state = BookState {
+            mid: snapshot.mid,
+            spread: snapshot.spread,
+            bid_qty: self.level_qty(&snapshot.bids),
+            ask_qty: self.level_qty(&snapshot.asks),
+            last_trade_sign,
+        };
+        if ts == snapshot.ts {
+            rows.push(self.row(ts, &state));
+            ts += self.interval_ns;
+        }
+        self.latest = Some(state);
+        self.next_ts = Some(ts);
+        rows
+    }
+
+    fn level_qty(&self, levels: &[BookLevelPoint]) -> Vec<Qty> {
+        let mut qty: Vec<Qty> = levels.iter().take(self.depth_levels).map(|level| level.qty).collect();
+        qty.resize(self.depth_levels, 0);
+        qty
+    }
+
+    fn row(&mut self, ts: u128, state: &BookState) -> FeatureVector {
+        // Rows without a two-sided book break the return series
+        match state.mid.filter(|mid| *mid > 0.0) {
+            Some(mid) => {
+                self.mids.push_back(mid);
+                if self.mids.len() > self.volatility_window + 1 {
+                    self.mids.pop_front();
+                }
+            }
+            None => self.mids.clear(),
+        }
+
+        let bid_total: Qty = state.bid_qty.iter().sum();
+        let ask_total: Qty = state.ask_qty.iter().sum();
+        let imbalance = (bid_total + ask_total > 0)
+            .then(|| (bid_total as f64 - ask_total as f64) / (bid_total + ask_total) as f64);
+
+        FeatureVector {
+            ts,
+            mid: state.mid,
+            spread: state.spread,
+            bid_qty: state.bid_qty.clone(),
+            ask_qty: state.ask_qty.clone(),
+            imbalance,
+            last_trade_sign: state.last_trade_sign,
+            volatility: self.volatility(),
+        }
+    }
+
+    fn volatility(&self) -> Option<f64> {
+        if self.mids.len() <= self.volatility_window {
+            return None;
+        }
+        let returns: Vec<f64> = self.mids.iter().zip(self.mids.iter().skip(1))
+            .map(|(prev, next)| (next / prev).ln())
+            .collect();
+        let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+        let variance = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (returns.len() - 1) as f64;
+        Some(variance.sqrt())
+    }
+}
+
+/// Destination for feature rows
+trait FeatureSink: Send {
+    fn write(&mut self, rows: &[FeatureVector]) -> FeatureResult<()>;
+
+    /// Flush buffered rows and complete the file
+    fn finish(&mut self) -> FeatureResult<()>;
+}
+
+struct CsvSink {
+    writer: csv::Writer<BufWriter<File>>,
+}
+
+impl CsvSink {
+    fn create(path: &Path, depth_levels: usize) -> FeatureResult<Self> {
+        let mut writer = csv::Writer::from_writer(BufWriter::new(File::create(path)?));
+        writer.write_record(FeatureVector::columns(depth_levels))?;
+        Ok(Self { writer })
+    }
+}
+
+impl FeatureSink for CsvSink {
+    fn write(&mut self, rows: &[FeatureVector]) -> FeatureResult<()> {
+        for row in rows {
+            self.writer.write_record(row.record())?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> FeatureResult<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Samples snapshots and writes the rows to a file
+pub struct FeatureExporter {
+    sampler: FeatureSampler,
+    sink: Box<dyn FeatureSink>,
+    path: PathBuf,
+    format: FeatureFormat,
+    rows: u64,
+}
+
+impl FeatureExporter {
+    /// Create the configured output file, failing if its format is not compiled in
+    pub fn from_config(config: &FeatureExportConfig) -> FeatureResult<Self> {
+        let format = config.resolved_format();
+        let sink: Box<dyn FeatureSink> = match format {
+            FeatureFormat::Csv => Box::new(CsvSink::create(&config.path, config.depth_levels)?),
+            #[cfg(feature = "parquet")]
+            FeatureFormat::Parquet => Box::new(parquet::ParquetSink::create(&config.path, config.depth_levels)?),
+            #[allow(unreachable_patterns)]
+            format => return Err(FeatureError::Unavailable(format)),
+        };
+        Ok(Self {
+            sampler: FeatureSampler::new(config),
+            sink,
+            path: config.path.clone(),
+            format,
+            rows: 0,
+        })
+    }
+
+    /// Output file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Output format
+    pub fn format(&self) -> FeatureFormat {
+        self.format
+    }
+
+    /// Rows written so far
+    pub fn rows_written(&self) -> u64 {
+        self.rows
+    }
+
+    /// Start a new series, as after a seek or reset
+    pub fn reset(&mut self) {
+        self.sampler.reset();
+    }
+
+    /// Sample a snapshot, returning how many rows were written
+    pub fn record(&mut self, snapshot: &DepthSnapshot) -> FeatureResult<usize> {
+        let rows = self.sampler.observe(snapshot);
+        if !rows.is_empty() {
+            self.sink.write(&rows)?;
+            self.rows += rows.len() as u64;
+        }
+        Ok(rows.len())
+    }
+
+    /// Flush buffered rows and complete the file
+    ///
+    /// Parquet files are unreadable until finished, so call this before the
+    /// exporter is dropped.
+    pub fn finish(&mut self) -> FeatureResult<()> {
+        self.sink.finish()
+    }
+}
+
+#[cfg(feature = "parquet")]
+mod parquet {
+    use std::fs::File;
+    use std::path::Path;
+    use std::sync::Arc;
+    use parquet::data_type::{DoubleType, Int32Type, Int64Type};
+    use parquet::errors::ParquetError;
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+    use super::{FeatureError, FeatureResult, FeatureSink, FeatureVector};
+
+    /// Rows buffered per row group
+    const ROW_GROUP_ROWS: usize = 8192;
+
+    impl From<ParquetError> for FeatureError {
+        fn from(e: ParquetError) -> Self {
+            FeatureError::Parquet(e.to_string())
+        }
+    }
+
+    pub(super) struct ParquetSink {
+        writer: Option<SerializedFileWriter<File>>,
+        depth_levels: usize,
+        pending: Vec<FeatureVector>,
+    }
+
+    impl ParquetSink {
+        pub(super) fn create(path: &Path, depth_levels: usize) -> FeatureResult<Self> {
+            let qty_columns: String = FeatureVector::columns(depth_levels)[3..3 + 2 * depth_levels].iter()
+                .map(|column| format!("REQUIRED INT64 {};\n", column))
+                .collect();
+            let schema = format!(
+                "message features {{\n\
+                 REQUIRED INT64 ts;\n\
+                 OPTIONAL DOUBLE mid;\n\
+                 OPTIONAL INT64 spread;\n\
+                 {}\
+                 OPTIONAL DOUBLE imbalance;\n\
+                 REQUIRED INT32 last_trade_sign;\n\
+                 OPTIONAL DOUBLE volatility;\n\
+                 }}",
+                qty_columns
+            );
+            let schema = Arc::new(parse_message_type(&schema)?);
+            let properties = Arc::new(WriterProperties::builder().build());
+            let writer = SerializedFileWriter::new(File::create(path)?, schema, properties)?;
+            Ok(Self { writer: Some(writer), depth_levels, pending: Vec::new() })
+        }
+
+        fn flush_row_group(&mut self) -> FeatureResult<()> {
+            let Some(writer) = self.writer.as_mut() else {
+                return Ok(());
+            };
+            if self.pending.is_empty() {
+                return Ok(());
+            }
+            let rows = std::mem::take(&mut self.pending);
+            let mut group = writer.next_row_group()?;
+            let mut column = 0;
+            while let Some(mut writer) = group.next_column()? {
+                match column {
+                    0 => {
+                        let values: Vec<i64> = rows.iter().map(|row| row.ts as i64).collect();
+                        writer.typed::<Int64Type>().write_batch(&values, None, None)?;
+                    }
+                    1 => write_optional::<DoubleType>(&mut writer, rows.iter().map(|row| row.mid))?,
+                    2 => write_optional::<Int64Type>(&mut writer, rows.iter().map(|row| row.spread))?,
+                    c if c < 3 + 2 * self.depth_levels => {
+                        let level = c - 3;
+                        let values: Vec<i64> = rows.iter()
+                            .map(|row| match level.checked_sub(self.depth_levels) {
+                                None => row.bid_qty[level] as i64,
+                                Some(ask) => row.ask_qty[ask] as i64,
+                            })
+                            .collect();
+                        writer.typed::<Int64Type>().write_batch(&values, None, None)?;
+                    }
+                    c if c == 3 + 2 * self.depth_levels => {
+                        write_optional::<DoubleType>(&mut writer, rows.iter().map(|row| row.imbalance))?;
+                    }
+                    c if c == 4 + 2 * self.depth_levels => {
+                        let values: Vec<i32> = rows.iter().map(|row| row.last_trade_sign as i32).collect();
+                        writer.typed::<Int32Type>().write_batch(&values, None, None)?;
+                    }
+                    _ => write_optional::<DoubleType>(&mut writer, rows.iter().map(|row| row.volatility))?,
+                }
+                writer.close()?;
+                column += 1;
+            }
+            group.close()?;
+            Ok(())
+        }
+    }
+
+    /// Write a nullable column, with definition level 0 marking missing values
+    fn write_optional<T: parquet::data_type::DataType>(
+        writer: &mut parquet::file::writer::SerializedColumnWriter<'_>,
+        values: impl Iterator<Item = Option<T::T>>,
+    ) -> FeatureResult<()> {
+        let (mut present, mut levels) = (Vec::new(), Vec::new());
+        for value in values {
+            levels.push(i16::from(value.is_some()));
+            present.extend(value);
+        }
+        writer.typed::<T>().write_batch(&present, Some(&levels), None)?;
+        Ok(())
+    }
+
+    impl FeatureSink for ParquetSink {
+        fn write(&mut self, rows: &[FeatureVector]) -> FeatureResult<()> {
+            self.pending.extend_from_slice(rows);
+            if self.pending.len() >= ROW_GROUP_ROWS {
+                self.flush_row_group()?;
+            }
+            Ok(())
+        }
+
+        fn finish(&mut self) -> FeatureResult<()> {
+            self.flush_row_group()?;
+            if let Some(writer) = self.writer.take() {
+                writer.close()?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{OrderBook, OrderBookEngine};
+    use crate::queue_fifo::FifoLevel;
+    use crate::types::{Order, Trade};
+
+    fn config(interval_ms: u64) -> FeatureExportConfig {
+        FeatureExportConfig {
+            interval_ms,
+            depth_levels: 2,
+            volatility_window: 2,
+            ..Default::default()
+        }
+    }
+
+    fn snapshot(ts_ms: u64, bid: u64, ask: u64, trades: Vec<Trade>) -> DepthSnapshot {
+        let mut book = OrderBook::<FifoLevel>::new();
+        book.place(Order::new_limit(1, Side::Buy, bid, 99_0000, crate::time::now_ns())).unwrap();
+        book.place(Order::new_limit(2, Side::Sell, ask, 101_0000, crate::time::now_ns())).unwrap();
+        book.place(Order::new_limit(3, Side::Sell, 50, 102_0000, crate::time::now_ns())).unwrap();
+        let mut snapshot = book.snapshot();
+        snapshot.ts = ms_to_ns(ts_ms);
+        snapshot.trades = trades;
+        snapshot
+    }
+
+    fn trade(aggressor: Side) -> Trade {
+        Trade { maker_id: 1, taker_id: 2, price: 100_0000, qty: 10, ts: 0, aggressor }
+    }
+
+    #[test]
+    fn test_sampler_aligns_rows_to_grid() {
+        let mut sampler = FeatureSampler::new(&config(100));
+
+        // The first row is at the first grid point at or after the first snapshot
+        assert!(sampler.observe(&snapshot(50, 300, 100, vec![])).is_empty());
+        let rows = sampler.observe(&snapshot(100, 300, 100, vec![trade(Side::Sell)]));
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].ts, ms_to_ns(100));
+        assert_eq!(rows[0].bid_qty, vec![300, 0]);
+        assert_eq!(rows[0].ask_qty, vec![100, 50]);
+        assert_eq!(rows[0].spread, Some(2_0000));
+        assert_eq!(rows[0].imbalance, Some(150.0 / 450.0));
+        assert_eq!(rows[0].last_trade_sign, -1);
+        assert_eq!(rows[0].volatility, None);
+
+        // A gap forward-fills the earlier state, keeping the last trade sign
+        let rows = sampler.observe(&snapshot(420, 100, 100, vec![]));
+        assert_eq!(rows.iter().map(|row| row.ts).collect::<Vec<_>>(), vec![ms_to_ns(200), ms_to_ns(300), ms_to_ns(400)]);
+        assert!(rows.iter().all(|row| row.bid_qty == vec![300, 0] && row.last_trade_sign == -1));
+        // Flat mids over a full window have no volatility
+        assert_eq!(rows[1].volatility, Some(0.0));
+
+        let rows = sampler.observe(&snapshot(500, 100, 100, vec![trade(Side::Buy)]));
+        assert_eq!(rows.len(), 1);
+        assert_eq!((rows[0].bid_qty[0], rows[0].last_trade_sign), (100, 1));
+    }
+
+    #[test]
+    fn test_sampler_restarts_on_timeline_jumps() {
+        let mut sampler = FeatureSampler::new(&config(100));
+        sampler.observe(&snapshot(0, 300, 100, vec![trade(Side::Sell)]));
+        sampler.observe(&snapshot(500, 300, 100, vec![]));
+
+        // Seeking back starts a fresh grid rather than emitting nothing until
+        // the old one is caught up
+        let rows = sampler.observe(&snapshot(150, 100, 100, vec![]));
+        assert!(rows.is_empty());
+        let rows = sampler.observe(&snapshot(200, 100, 100, vec![]));
+        assert_eq!(rows.len(), 1);
+        assert_eq!((rows[0].ts, rows[0].last_trade_sign, rows[0].volatility), (ms_to_ns(200), 0, None));
+
+        // A gap past the cap is not filled
+        let far = 200 + 100 * (MAX_FILL_INTERVALS as u64 + 1);
+        assert!(sampler.observe(&snapshot(far + 50, 100, 100, vec![])).is_empty());
+        let rows = sampler.observe(&snapshot(far + 200, 100, 100, vec![]));
+        assert_eq!(rows.iter().map(|row| row.ts).collect::<Vec<_>>(), vec![ms_to_ns(far + 100), ms_to_ns(far + 200)]);
+    }
+
+    #[test]
+    fn test_exporter_writes_csv() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = FeatureExportConfig {
+            enabled: true,
+            path: dir.path().join("features.csv"),
+            ..config(100)
+        };
+        assert_eq!(config.resolved_format(), FeatureFormat::Csv);
+        let mut exporter = FeatureExporter::from_config(&config).unwrap();
+        exporter.record(&snapshot(0, 300, 100, vec![])).unwrap();
+        exporter.record(&snapshot(250, 300, 100, vec![trade(Side::Buy)])).unwrap();
+        exporter.finish().unwrap();
+        assert_eq!(exporter.rows_written(), 3);
+
+        let csv = std::fs::read_to_string(&config.path).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "ts,mid,spread,bid_qty_1,bid_qty_2,ask_qty_1,ask_qty_2,imbalance,last_trade_sign,volatility");
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].starts_with("0,100.00000,20000,300,0,100,50,"));
+        assert!(lines[1].ends_with(",0,"));
+
+        assert!(FeatureExportConfig { volatility_window: 1, ..config.clone() }.validate().is_err());
+        assert_eq!(
+            FeatureExportConfig { path: PathBuf::from("out.parquet"), ..config }.resolved_format(),
+            FeatureFormat::Parquet
+        );
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_exporter_writes_parquet() {
+        use ::parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = FeatureExportConfig {
+            enabled: true,
+            path: dir.path().join("features.parquet"),
+            ..config(100)
+        };
+        let mut exporter = FeatureExporter::from_config(&config).unwrap();
+        exporter.record(&snapshot(0, 300, 100, vec![])).unwrap();
+        exporter.record(&snapshot(250, 300, 100, vec![trade(Side::Buy)])).unwrap();
+        exporter.finish().unwrap();
+
+        let reader = SerializedFileReader::new(File::open(&config.path).unwrap()).unwrap();
+        let metadata = reader.metadata();
+        assert_eq!(metadata.file_metadata().num_rows(), 3);
+        let columns: Vec<String> = metadata.file_metadata().schema_descr().columns().iter()
+            .map(|column| column.name().to_string())
+            .collect();
+        assert_eq!(columns, FeatureVector::columns(2));
+    }
+}
@@ -0,0 +1,463 @@
+//! Market data feed messages
+//!
+//! Turns the stream of depth snapshots into sequenced feed messages: one per
+//! trade, plus an incremental depth update listing only the price levels that
+//! changed since the previous snapshot. Every message carries the next
+//! sequence number so consumers can detect gaps.
+//!
+//! A consumer that finds a gap need not start over from a full snapshot: it
+//! sends a [`ResyncRequest`] naming the side and price range it doubts, with
+//! its own levels there, and gets back only the levels that differ from the
+//! published book.
+//!
+//! Before the open, snapshots may also carry the pre-open auction imbalance;
+//! each newly published one goes out as its own message.
+//!
+//! Messages made from a snapshot carry its pipeline watermark, if it has one.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::auction::AuctionImbalance;
+use crate::engine::{BookLevelPoint, DepthSnapshot};
+use crate::metrics::PipelineWatermark;
+use crate::types::{Price, Qty, Side, Trade};
+
+/// New resting quantity at a price; zero means the level is gone
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LevelUpdate {
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub price: Price,
+    pub qty: Qty,
+}
+
+/// Levels that changed between two snapshots
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DepthUpdate {
+    pub seq: u64,
+    pub ts: u128,
+    pub bids: Vec<LevelUpdate>,
+    pub asks: Vec<LevelUpdate>,
+    /// Pipeline timing of the data source events behind the snapshot
+    #[serde(default)]
+    pub watermark: Option<PipelineWatermark>,
+}
+
+/// One message on the feed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedMessage {
+    Trade {
+        seq: u64,
+        trade: Trade,
+        /// Printed late under the block trade rules
+        #[serde(default)]
+        block: bool,
+        /// Pipeline timing of the data source events behind the snapshot
+        #[serde(default)]
+        watermark: Option<PipelineWatermark>,
+    },
+    Depth(DepthUpdate),
+    /// Pre-open indicative price and imbalance
+    Imbalance {
+        seq: u64,
+        imbalance: AuctionImbalance,
+        /// Pipeline timing of the data source events behind the snapshot
+        #[serde(default)]
+        watermark: Option<PipelineWatermark>,
+    },
+}
+
+impl FeedMessage {
+    /// Sequence number of the message
+    pub fn seq(&self) -> u64 {
+        match self {
+            Self::Trade { seq, .. } => *seq,
+            Self::Depth(update) => update.seq,
+            Self::Imbalance { seq, .. } => *seq,
+        }
+    }
+}
+
+/// Part of the book a delta subscriber wants corrected after a sequence gap
+///
+/// `bids` and `asks` are the subscriber's own levels within the range. The
+/// correction lists only the levels where they differ from the book, so
+/// leaving them empty asks for every level in the range.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResyncRequest {
+    /// Side to correct; both sides if unset
+    #[serde(default)]
+    pub side: Option<Side>,
+    /// Lowest price to correct, inclusive
+    #[serde(default)]
+    pub min_price: Option<Price>,
+    /// Highest price to correct, inclusive
+    #[serde(default)]
+    pub max_price: Option<Price>,
+    #[serde(default)]
+    pub bids: Vec<LevelUpdate>,
+    #[serde(default)]
+    pub asks: Vec<LevelUpdate>,
+}
+
+impl ResyncRequest {
+    /// Check the range
+    pub fn validate(&self) -> Result<(), String> {
+        if let (Some(min), Some(max)) = (self.min_price, self.max_price) {
+            if min > max {
+                return Err(format!("Resync range is empty: min_price {} is above max_price {}", min, max));
+            }
+        }
+        Ok(())
+    }
+
+    fn covers(&self, side: Side, price: Price) -> bool {
+        self.side.is_none_or(|wanted| wanted == side)
+            && self.min_price.is_none_or(|min| price >= min)
+            && self.max_price.is_none_or(|max| price <= max)
+    }
+}
+
+/// Wire encoding for feed messages
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedFormat {
+    #[default]
+    Json,
+    Bincode,
+}
+
+impl FeedFormat {
+    /// Encode a message
+    pub fn encode<T: Serialize>(&self, message: &T) -> Result<Vec<u8>, String> {
+        match self {
+            Self::Json => serde_json::to_vec(message).map_err(|e| e.to_string()),
+            Self::Bincode => bincode::serialize(message).map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Decode a message
+    pub fn decode(&self, bytes: &[u8]) -> Result<FeedMessage, String> {
+        match self {
+            Self::Json => serde_json::from_slice(bytes).map_err(|e| e.to_string()),
+            Self::Bincode => bincode::deserialize(bytes).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for FeedFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json => write!(f, "json"),
+            Self::Bincode => write!(f, "bincode"),
+        }
+    }
+}
+
+impl FromStr for FeedFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "bincode" | "bin" => Ok(Self::Bincode),
+            _ => Err(format!("Unknown feed format: {} (expected json or bincode)", s)),
+        }
+    }
+}
+
+/// Builds sequenced feed messages from successive snapshots
+///
+/// Depth is tracked within the snapshot's depth limit, so a level that falls
+/// outside it is reported as removed.
+#[derive(Debug, Clone)]
+pub struct FeedSequencer {
+    next_seq: u64,
+    /// Timestamp of the last snapshot with depth changes
+    last_ts: u128,
+    bids: BTreeMap<Price, Qty>,
+    asks: BTreeMap<Price, Qty>,
+    /// Timestamp of the last imbalance sent
+    last_imbalance_ts: Option<u128>,
+}
+
+impl Default for FeedSequencer {
+    fn default() -> Self {
+        Self {
+            next_seq: 1,
+            last_ts: 0,
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            last_imbalance_ts: None,
+        }
+    }
+}
+
+impl FeedSequencer {
+    /// Start a feed at sequence number 1
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sequence number the next message will get
+    pub fn next_seq(&self) -> u64 {
+        self.next_seq
+    }
+
+    /// Leave a gap of `count` sequence numbers for messages that were never
+    /// made, so consumers see that something was lost
+    pub fn skip(&mut self, count: u64) {
+        self.next_seq += count;
+    }
+
+    fn take_seq(&mut self) -> u64 {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        seq
+    }
+
+    /// Messages for a snapshot: its trades, then the depth changes, if any,
+    /// then its auction imbalance if it was not sent before
+    pub fn on_snapshot(&mut self, snapshot: &DepthSnapshot) -> Vec<FeedMessage> {
+        let mut messages: Vec<FeedMessage> = snapshot.trades.iter()
+            .map(|trade| FeedMessage::Trade {
+                seq: self.take_seq(),
+                trade: trade.clone(),
+                block: snapshot.block_trades.contains(trade),
+                watermark: snapshot.watermark,
+            })
+            .collect();
+
+        messages.extend(self.on_depth(snapshot).map(FeedMessage::Depth));
+
+        if let Some(imbalance) = snapshot.auction.filter(|imbalance| self.last_imbalance_ts != Some(imbalance.ts)) {
+            self.last_imbalance_ts = Some(imbalance.ts);
+            messages.push(FeedMessage::Imbalance { seq: self.take_seq(), imbalance, watermark: snapshot.watermark });
+        }
+        messages
+    }
+
+    /// The levels that changed in a snapshot, if any, as the next message
+    ///
+    /// For feeds that carry depth alone.
+    pub fn on_depth(&mut self, snapshot: &DepthSnapshot) -> Option<DepthUpdate> {
+        let bids = Self::diff(&mut self.bids, &snapshot.bids);
+        let asks = Self::diff(&mut self.asks, &snapshot.asks);
+        if bids.is_empty() && asks.is_empty() {
+            return None;
+        }
+        self.last_ts = snapshot.ts;
+        Some(DepthUpdate {
+            seq: self.take_seq(),
+            ts: snapshot.ts,
+            bids,
+            asks,
+            watermark: snapshot.watermark,
+        })
+    }
+
+    /// Number of levels tracked on one side
+    pub fn level_count(&self, side: Side) -> usize {
+        match side {
+            Side::Buy => self.bids.len(),
+            Side::Sell => self.asks.len(),
+        }
+    }
+
+    /// Best price on one side
+    pub fn best(&self, side: Side) -> Option<Price> {
+        match side {
+            Side::Buy => self.bids.keys().next_back().copied(),
+            Side::Sell => self.asks.keys().next().copied(),
+        }
+    }
+
+    /// Current depth on one side, best price first
+    pub fn levels(&self, side: Side) -> Vec<LevelUpdate> {
+        let to_update = |(&price, &qty): (&Price, &Qty)| LevelUpdate { price, qty };
+        match side {
+            Side::Buy => self.bids.iter().rev().map(to_update).collect(),
+            Side::Sell => self.asks.iter().map(to_update).collect(),
+        }
+    }
+
+    /// Corrections for a subscriber's copy of part of the book
+    ///
+    /// The update carries the sequence number of the last message sent:
+    /// applying it, then the messages after that one, brings the range back
+    /// in line with the feed. Levels outside the range are left alone.
+    pub fn resync(&self, request: &ResyncRequest) -> DepthUpdate {
+        let correct = |side: Side, book: &BTreeMap<Price, Qty>, known: &[LevelUpdate]| {
+            let in_range = |price: &Price| request.covers(side, *price);
+            let known: BTreeMap<Price, Qty> = known.iter()
+                .filter(|level| level.qty > 0 && in_range(&level.price))
+                .map(|level| (level.price, level.qty))
+                .collect();
+            let current: BTreeMap<Price, Qty> = book.iter()
+                .filter(|(price, _)| in_range(price))
+                .map(|(&price, &qty)| (price, qty))
+                .collect();
+            Self::changes(&known, &current)
+        };
+        DepthUpdate {
+            seq: self.next_seq - 1,
+            ts: self.last_ts,
+            bids: correct(Side::Buy, &self.bids, &request.bids),
+            asks: correct(Side::Sell, &self.asks, &request.asks),
+            watermark: None,
+        }
+    }
+
+    /// Replace `book` with `levels`, returning the changed levels in price order
+    fn diff(book: &mut BTreeMap<Price, Qty>, levels: &[BookLevelPoint]) -> Vec<LevelUpdate> {
+        let current: BTreeMap<Price, Qty> = levels.iter()
+            .filter(|level| level.qty > 0)
+            .map(|level| (level.price, level.qty))
+            .collect();
+        let changes = Self::changes(book, &current);
+        *book = current;
+        changes
+    }
+
+    /// Updates that turn `old` into `new`, in price order
+    fn changes(old: &BTreeMap<Price, Qty>, new: &BTreeMap<Price, Qty>) -> Vec<LevelUpdate> {
+        let mut changes: Vec<LevelUpdate> = old.keys()
+            .filter(|price| !new.contains_key(price))
+            .map(|&price| LevelUpdate { price, qty: 0 })
+            .collect();
+        changes.extend(new.iter()
+            .filter(|(price, qty)| old.get(price) != Some(qty))
+            .map(|(&price, &qty)| LevelUpdate { price, qty }));
+        changes.sort_by_key(|change| change.price);
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{OrderBook, OrderBookEngine};
+    use crate::queue_fifo::FifoLevel;
+    use crate::types::Order;
+
+    #[test]
+    fn test_sequencer_emits_trades_and_changed_levels() {
+        let mut book = OrderBook::<FifoLevel>::new();
+        let mut feed = FeedSequencer::new();
+        let ts = crate::time::now_ns();
+
+        book.place(Order::new_limit(1, Side::Buy, 100, 99_0000, ts)).unwrap();
+        book.place(Order::new_limit(2, Side::Sell, 100, 101_0000, ts)).unwrap();
+        book.place(Order::new_limit(3, Side::Sell, 50, 102_0000, ts)).unwrap();
+        let snapshot = book.snapshot();
+        assert_eq!(feed.on_snapshot(&snapshot), vec![FeedMessage::Depth(DepthUpdate {
+            seq: 1,
+            ts: snapshot.ts,
+            bids: vec![LevelUpdate { price: 99_0000, qty: 100 }],
+            asks: vec![LevelUpdate { price: 101_0000, qty: 100 }, LevelUpdate { price: 102_0000, qty: 50 }],
+            watermark: None,
+        })]);
+
+        // Unchanged book, no message
+        assert!(feed.on_snapshot(&book.snapshot()).is_empty());
+
+        // A trade empties the best ask
+        let trades = book.place(Order::new_market(4, Side::Buy, 100, ts)).unwrap();
+        let mut snapshot = book.snapshot();
+        snapshot.trades = trades;
+        let messages = feed.on_snapshot(&snapshot);
+        assert_eq!(messages.len(), 2);
+        assert!(matches!(&messages[0], FeedMessage::Trade { seq: 2, trade, block: false, .. } if trade.qty == 100));
+        match &messages[1] {
+            FeedMessage::Depth(update) => {
+                assert_eq!(update.seq, 3);
+                assert!(update.bids.is_empty());
+                assert_eq!(update.asks, vec![LevelUpdate { price: 101_0000, qty: 0 }]);
+            }
+            other => panic!("expected depth update, got {:?}", other),
+        }
+        assert_eq!(feed.next_seq(), 4);
+        assert_eq!(feed.levels(Side::Sell), vec![LevelUpdate { price: 102_0000, qty: 50 }]);
+
+        // Both encodings round trip
+        for format in [FeedFormat::Json, FeedFormat::Bincode] {
+            for message in &messages {
+                assert_eq!(&format.decode(&format.encode(message).unwrap()).unwrap(), message);
+            }
+        }
+        assert_eq!("bin".parse::<FeedFormat>().unwrap(), FeedFormat::Bincode);
+    }
+
+    #[test]
+    fn test_auction_imbalance_is_sent_once_per_publication() {
+        let mut feed = FeedSequencer::new();
+        let mut snapshot = OrderBook::<FifoLevel>::new().snapshot();
+        let imbalance = AuctionImbalance {
+            ts: 5,
+            indicative_price: Some(100_0000),
+            paired_qty: 300,
+            imbalance_qty: 120,
+            imbalance_side: Some(Side::Buy),
+            queued_orders: 4,
+        };
+        snapshot.auction = Some(imbalance);
+        let messages = feed.on_snapshot(&snapshot);
+        assert_eq!(messages, vec![FeedMessage::Imbalance { seq: 1, imbalance, watermark: None }]);
+        for format in [FeedFormat::Json, FeedFormat::Bincode] {
+            assert_eq!(format.decode(&format.encode(&messages[0]).unwrap()).unwrap(), messages[0]);
+        }
+
+        // The same publication again is not repeated, a newer one is
+        assert!(feed.on_snapshot(&snapshot).is_empty());
+        snapshot.auction = Some(AuctionImbalance { ts: 6, ..imbalance });
+        assert_eq!(feed.on_snapshot(&snapshot).iter().map(FeedMessage::seq).collect::<Vec<_>>(), vec![2]);
+    }
+
+    #[test]
+    fn test_resync_corrects_only_the_requested_range() {
+        let mut book = OrderBook::<FifoLevel>::new();
+        let mut feed = FeedSequencer::new();
+        let ts = crate::time::now_ns();
+        for (id, side, qty, price) in [
+            (1, Side::Buy, 100, 99_0000),
+            (2, Side::Buy, 40, 98_0000),
+            (3, Side::Sell, 100, 101_0000),
+            (4, Side::Sell, 60, 102_0000),
+            (5, Side::Sell, 20, 105_0000),
+        ] {
+            book.place(Order::new_limit(id, side, qty, price, ts)).unwrap();
+        }
+        let snapshot = book.snapshot();
+        feed.on_snapshot(&snapshot);
+
+        // The subscriber missed a change at 102 and still holds a level at 103
+        let request = ResyncRequest {
+            side: Some(Side::Sell),
+            min_price: Some(101_0000),
+            max_price: Some(103_0000),
+            asks: vec![
+                LevelUpdate { price: 101_0000, qty: 100 },
+                LevelUpdate { price: 102_0000, qty: 10 },
+                LevelUpdate { price: 103_0000, qty: 5 },
+            ],
+            ..ResyncRequest::default()
+        };
+        let correction = feed.resync(&request);
+        assert_eq!((correction.seq, correction.ts), (1, snapshot.ts));
+        assert!(correction.bids.is_empty());
+        assert_eq!(correction.asks, vec![
+            LevelUpdate { price: 102_0000, qty: 60 },
+            LevelUpdate { price: 103_0000, qty: 0 },
+        ]);
+
+        // Without levels of its own, the subscriber gets the whole range
+        let request = ResyncRequest { side: Some(Side::Buy), min_price: Some(98_5000), ..ResyncRequest::default() };
+        assert_eq!(feed.resync(&request).bids, vec![LevelUpdate { price: 99_0000, qty: 100 }]);
+
+        let inverted = ResyncRequest { min_price: Some(2), max_price: Some(1), ..ResyncRequest::default() };
+        assert!(inverted.validate().is_err());
+    }
+}
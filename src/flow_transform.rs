@@ -0,0 +1,346 @@
+//! Stress variants of historical order flow
+//!
+//! One capture can stand in for a rougher day. A [`FlowTransformDataSource`]
+//! rewrites the flow it replays:
+//!
+//! - order sizes scale by one factor, never rounding to zero
+//! - a share of limit orders become market orders, taking liquidity the
+//!   recorded flow only offered
+//! - a share of cancels are dropped, leaving their orders resting
+//!
+//! Each choice is drawn from the seed and the id of the order it concerns,
+//! so the same file and settings give the same variant on every replay, and
+//! a replay that seeks or opens a time window makes the same choices from
+//! there on as one played straight through. The settings and what they
+//! changed are carried in the data source metadata, and so in the run report.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::data::{DataError, DataResult, DataSource, DataSourceMetadata, ErrorPolicy, MarketEvent};
+use crate::types::{OrderId, OrderType, Qty};
+
+/// Salts telling apart the draws made for one order
+const MARKET_DRAW: u64 = 1;
+const CANCEL_DRAW: u64 = 2;
+
+/// How to rewrite replayed flow
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct FlowTransformConfig {
+    /// Rewrite the replayed flow
+    pub enabled: bool,
+    /// Factor order sizes are multiplied by before rounding
+    pub size_scale: f64,
+    /// Share of limit orders placed as market orders instead
+    pub market_fraction: f64,
+    /// Share of cancels dropped
+    pub cancel_drop_fraction: f64,
+    /// Seed for choosing which orders and cancels are rewritten
+    pub seed: u64,
+}
+
+impl Default for FlowTransformConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            size_scale: 1.0,
+            market_fraction: 0.0,
+            cancel_drop_fraction: 0.0,
+            seed: 0,
+        }
+    }
+}
+
+impl FlowTransformConfig {
+    /// Check the scale factor and shares
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.size_scale.is_finite() && self.size_scale > 0.0) {
+            return Err("Flow transform size scale must be a positive number".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.market_fraction) {
+            return Err("Flow transform market fraction must be between 0 and 1".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.cancel_drop_fraction) {
+            return Err("Flow transform cancel drop fraction must be between 0 and 1".to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether the transform leaves every event as it is
+    pub fn is_identity(&self) -> bool {
+        self.size_scale == 1.0 && self.market_fraction == 0.0 && self.cancel_drop_fraction == 0.0
+    }
+}
+
+impl std::fmt::Display for FlowTransformConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "sizes x{}, {:.0}% of limits as markets, {:.0}% of cancels dropped (seed {})",
+            self.size_scale, self.market_fraction * 100.0, self.cancel_drop_fraction * 100.0, self.seed,
+        )
+    }
+}
+
+/// What a transform changed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlowTransformStats {
+    /// Order placements seen
+    pub placements: u64,
+    /// Placements whose size changed
+    pub resized: u64,
+    /// Limit orders placed as market orders
+    pub converted_to_market: u64,
+    /// Cancels seen
+    pub cancels: u64,
+    /// Cancels dropped
+    pub dropped_cancels: u64,
+}
+
+/// A transform's settings and effect, as recorded for a run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AppliedTransform {
+    pub config: FlowTransformConfig,
+    pub stats: FlowTransformStats,
+}
+
+/// Rewrites events one at a time
+#[derive(Debug, Clone)]
+pub struct FlowTransform {
+    config: FlowTransformConfig,
+    stats: FlowTransformStats,
+}
+
+impl FlowTransform {
+    pub fn new(config: FlowTransformConfig) -> Self {
+        Self { config, stats: FlowTransformStats::default() }
+    }
+
+    pub fn config(&self) -> &FlowTransformConfig {
+        &self.config
+    }
+
+    pub fn stats(&self) -> FlowTransformStats {
+        self.stats
+    }
+
+    /// Start counting changes over
+    pub fn reset(&mut self) {
+        *self = Self::new(self.config.clone());
+    }
+
+    /// The event as the variant has it, or `None` if the variant drops it
+    pub fn apply(&mut self, mut event: MarketEvent) -> Option<MarketEvent> {
+        match &mut event {
+            MarketEvent::OrderPlacement(order) => {
+                self.stats.placements += 1;
+                let qty = self.qty(order.qty);
+                if qty != order.qty {
+                    order.qty = qty;
+                    self.stats.resized += 1;
+                }
+                if matches!(order.order_type, OrderType::Limit { .. }) && self.draw(self.config.market_fraction, order.id, MARKET_DRAW) {
+                    order.order_type = OrderType::Market;
+                    self.stats.converted_to_market += 1;
+                }
+            }
+            MarketEvent::OrderCancellation { order_id, .. } => {
+                self.stats.cancels += 1;
+                if self.draw(self.config.cancel_drop_fraction, *order_id, CANCEL_DRAW) {
+                    self.stats.dropped_cancels += 1;
+                    return None;
+                }
+            }
+            MarketEvent::OrderModification { new_qty, .. } => {
+                *new_qty = new_qty.map(|qty| self.qty(qty));
+            }
+            _ => {}
+        }
+        Some(event)
+    }
+
+    /// A modification to zero stays zero; any other size keeps at least one
+    fn qty(&self, qty: Qty) -> Qty {
+        if qty == 0 || self.config.size_scale == 1.0 {
+            return qty;
+        }
+        ((qty as f64 * self.config.size_scale).round() as Qty).max(1)
+    }
+
+    /// Whether `order_id` is among the share chosen for the draw `salt`,
+    /// whatever was drawn for the events before it
+    fn draw(&self, share: f64, order_id: OrderId, salt: u64) -> bool {
+        let seed = self.config.seed ^ order_id.wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ salt.rotate_left(32);
+        share > 0.0 && StdRng::seed_from_u64(seed).gen_bool(share)
+    }
+}
+
+/// Data source wrapper replaying a stress variant of the wrapped flow
+pub struct FlowTransformDataSource {
+    /// Wrapped data source
+    inner: Box<dyn DataSource>,
+    transform: FlowTransform,
+}
+
+impl FlowTransformDataSource {
+    /// Wrap a data source with the given transform
+    pub fn new(inner: Box<dyn DataSource>, config: FlowTransformConfig) -> DataResult<Self> {
+        config.validate().map_err(DataError::validation)?;
+        Ok(Self { inner, transform: FlowTransform::new(config) })
+    }
+
+    /// What the transform has changed since the last reset
+    pub fn stats(&self) -> FlowTransformStats {
+        self.transform.stats()
+    }
+
+    /// Unwrap the inner data source
+    pub fn into_inner(self) -> Box<dyn DataSource> {
+        self.inner
+    }
+}
+
+impl DataSource for FlowTransformDataSource {
+    fn next_event(&mut self) -> DataResult<Option<MarketEvent>> {
+        while let Some(event) = self.inner.next_event()? {
+            if let Some(event) = self.transform.apply(event) {
+                return Ok(Some(event));
+            }
+        }
+        Ok(None)
+    }
+
+    fn seek_to_time(&mut self, timestamp: u128) -> DataResult<()> {
+        self.transform.reset();
+        self.inner.seek_to_time(timestamp)
+    }
+
+    fn set_playback_speed(&mut self, multiplier: f64) -> DataResult<()> {
+        self.inner.set_playback_speed(multiplier)
+    }
+
+    fn is_finished(&self) -> bool {
+        self.inner.is_finished()
+    }
+
+    fn current_position(&self) -> Option<u128> {
+        self.inner.current_position()
+    }
+
+    fn duration(&self) -> Option<(u128, u128)> {
+        self.inner.duration()
+    }
+
+    fn reset(&mut self) -> DataResult<()> {
+        self.transform.reset();
+        self.inner.reset()
+    }
+
+    fn metadata(&self) -> DataSourceMetadata {
+        let mut metadata = self.inner.metadata();
+        metadata.flow_transform = Some(AppliedTransform {
+            config: self.transform.config().clone(),
+            stats: self.transform.stats(),
+        });
+        metadata
+    }
+
+    fn set_paused(&mut self, paused: bool) -> DataResult<()> {
+        self.inner.set_paused(paused)
+    }
+
+    fn is_paused(&self) -> bool {
+        self.inner.is_paused()
+    }
+
+    fn last_symbol(&self) -> Option<&str> {
+        self.inner.last_symbol()
+    }
+
+    fn last_line(&self) -> Option<usize> {
+        self.inner.last_line()
+    }
+
+    fn filter_symbols(&mut self, symbols: Vec<String>) -> DataResult<()> {
+        self.inner.filter_symbols(symbols)
+    }
+
+    fn set_time_window(&mut self, start: u128, end: u128) -> DataResult<()> {
+        self.transform.reset();
+        self.inner.set_time_window(start, end)
+    }
+
+    fn set_error_policy(&mut self, policy: ErrorPolicy) -> DataResult<()> {
+        self.inner.set_error_policy(policy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{DataFormat, DataFormatDetector};
+    use crate::types::{Order, Side};
+    use tempfile::NamedTempFile;
+
+    /// Two hundred orders, each cancelled straight after it rests
+    fn flow() -> (NamedTempFile, Box<dyn DataSource>) {
+        let mut events = Vec::new();
+        for id in 0..200u64 {
+            let ts = 1_000 + id as u128 * 10;
+            events.push(MarketEvent::OrderPlacement(Order::new_limit(id + 1, Side::Buy, 10, 100_0000, ts)));
+            events.push(MarketEvent::OrderCancellation { order_id: id + 1, timestamp: ts + 5, reason: None });
+        }
+        let temp_file = NamedTempFile::with_suffix(".json").unwrap();
+        DataFormat::Json.write_file(temp_file.path(), &events).unwrap();
+        let mut source = DataFormatDetector::create_data_source(temp_file.path()).unwrap();
+        source.set_paused(true).unwrap();
+        (temp_file, source)
+    }
+
+    fn replay(source: &mut dyn DataSource) -> Vec<MarketEvent> {
+        let mut events = Vec::new();
+        while let Some(event) = source.next_event().unwrap() {
+            events.push(event);
+        }
+        events
+    }
+
+    #[test]
+    fn test_variant_is_reproducible_and_recorded() {
+        let config = FlowTransformConfig {
+            enabled: true,
+            size_scale: 2.5,
+            market_fraction: 0.25,
+            cancel_drop_fraction: 0.5,
+            seed: 7,
+        };
+        let (_file, inner) = flow();
+        let mut source = FlowTransformDataSource::new(inner, config.clone()).unwrap();
+        let first = replay(&mut source);
+
+        let stats = source.stats();
+        assert_eq!((stats.placements, stats.resized, stats.cancels), (200, 200, 200));
+        assert!(stats.converted_to_market > 20 && stats.converted_to_market < 80);
+        assert!(stats.dropped_cancels > 60 && stats.dropped_cancels < 140);
+        assert_eq!(first.len() as u64, 400 - stats.dropped_cancels);
+        assert!(first.iter().all(|event| match event {
+            MarketEvent::OrderPlacement(order) => order.qty == 25,
+            _ => true,
+        }));
+        let recorded = source.metadata().flow_transform.unwrap();
+        assert_eq!((recorded.config, recorded.stats), (config.clone(), stats));
+
+        source.reset().unwrap();
+        assert_eq!(replay(&mut source), first);
+
+        // Seeking lands on the choices a straight replay makes from there
+        source.seek_to_time(1_500).unwrap();
+        let sought = replay(&mut source);
+        assert!(sought.len() < first.len());
+        assert_eq!(sought, first[first.len() - sought.len()..]);
+
+        assert!(FlowTransformConfig { market_fraction: 1.5, ..config }.validate().is_err());
+    }
+}
@@ -0,0 +1,243 @@
+//! Funding payments for perpetual-style instruments
+//!
+//! A perpetual has no expiry to pull its price back to the underlying, so
+//! longs and shorts exchange a periodic funding payment instead. The premium
+//! of the book mid over the mark price is sampled every step; at each
+//! settlement the average premium plus the interest leg, clamped, becomes the
+//! funding rate, and every open position pays or receives that fraction of
+//! its notional at the mark. A positive rate has longs pay shorts.
+
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::time::ms_to_ns;
+use crate::types::{ContractSpec, Price};
+
+/// Funding schedule and rate parameters
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct FundingConfig {
+    /// Settle funding between longs and shorts
+    pub enabled: bool,
+    /// Simulation time between settlements (milliseconds)
+    pub interval_ms: u64,
+    /// Rate added to the average premium at each settlement
+    pub interest_rate: f64,
+    /// Largest rate charged in one settlement, either way
+    pub max_rate: f64,
+    /// Weight of each new mid in the mark price average when no external
+    /// reference price is available, in (0.0, 1.0]
+    pub mark_smoothing: f64,
+}
+
+impl Default for FundingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            // Real perps settle every 8 hours; simulated time moves slower
+            interval_ms: 1_000,
+            interest_rate: 0.0001,
+            max_rate: 0.0075,
+            mark_smoothing: 0.05,
+        }
+    }
+}
+
+impl FundingConfig {
+    /// Check the schedule and rates
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval_ms == 0 {
+            return Err("Funding interval must be greater than 0".to_string());
+        }
+        if self.max_rate < 0.0 || self.max_rate >= 1.0 {
+            return Err("Funding max rate must be in [0.0, 1.0)".to_string());
+        }
+        if !self.interest_rate.is_finite() {
+            return Err("Funding interest rate must be finite".to_string());
+        }
+        if self.mark_smoothing <= 0.0 || self.mark_smoothing > 1.0 {
+            return Err("Funding mark smoothing must be in (0.0, 1.0]".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// One funding settlement
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FundingSettlement {
+    /// Simulation time of the settlement
+    pub ts: u128,
+    /// Fraction of notional longs pay shorts (negative when shorts pay)
+    pub rate: f64,
+    /// Average premium of the mid over the mark since the last settlement
+    pub premium: f64,
+    /// Mark price positions were valued at
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub mark: Price,
+}
+
+impl FundingSettlement {
+    /// Amount a position pays at this settlement, in settlement units
+    ///
+    /// Negative when the position receives funding.
+    pub fn payment(&self, contract: &ContractSpec, position: i64) -> i64 {
+        let notional = contract.notional(position.unsigned_abs(), self.mark).unwrap_or(i64::MAX) as f64;
+        (position.signum() as f64 * self.rate * notional).round() as i64
+    }
+}
+
+/// Cumulative funding statistics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct FundingStats {
+    pub settlements: u64,
+    /// Sum of the rates settled so far
+    pub cumulative_rate: f64,
+    pub last: Option<FundingSettlement>,
+}
+
+/// Samples the premium and decides when funding settles, and at what rate
+#[derive(Debug, Clone)]
+pub struct FundingSchedule {
+    config: FundingConfig,
+    /// External mark price, e.g. from quote events in historical replay
+    index_price: Option<Price>,
+    /// Smoothed mid used as the mark without an external price
+    smoothed_mid: Option<f64>,
+    premium_sum: f64,
+    premium_samples: u64,
+    next_settlement: Option<u128>,
+    stats: FundingStats,
+}
+
+impl FundingSchedule {
+    /// Create a schedule with the given parameters
+    pub fn new(config: FundingConfig) -> Self {
+        Self {
+            config,
+            index_price: None,
+            smoothed_mid: None,
+            premium_sum: 0.0,
+            premium_samples: 0,
+            next_settlement: None,
+            stats: FundingStats::default(),
+        }
+    }
+
+    /// Get the funding configuration
+    pub fn config(&self) -> &FundingConfig {
+        &self.config
+    }
+
+    /// Get cumulative funding statistics
+    pub fn stats(&self) -> &FundingStats {
+        &self.stats
+    }
+
+    /// Use an external reference price as the mark
+    pub fn set_index_price(&mut self, price: Price) {
+        self.index_price = Some(price);
+    }
+
+    /// Current mark price: the external reference if set, else the smoothed mid
+    pub fn mark_price(&self) -> Option<Price> {
+        self.index_price.or(self.smoothed_mid.map(|mid| mid.round() as Price))
+    }
+
+    /// Sample the premium after a step, returning a settlement if one is due
+    pub fn on_step(&mut self, mid: Option<Price>, ts: u128) -> Option<FundingSettlement> {
+        let next_settlement = *self.next_settlement.get_or_insert(ts + ms_to_ns(self.config.interval_ms));
+
+        if let Some(mid) = mid {
+            let alpha = self.config.mark_smoothing;
+            let smoothed = self.smoothed_mid.map_or(mid as f64, |smoothed| smoothed + alpha * (mid as f64 - smoothed));
+            self.smoothed_mid = Some(smoothed);
+            if let Some(mark) = self.mark_price().filter(|&mark| mark > 0) {
+                self.premium_sum += (mid as f64 - mark as f64) / mark as f64;
+                self.premium_samples += 1;
+            }
+        }
+
+        if ts < next_settlement {
+            return None;
+        }
+        self.next_settlement = Some(next_settlement + ms_to_ns(self.config.interval_ms));
+        let mark = self.mark_price()?;
+
+        let premium = if self.premium_samples > 0 { self.premium_sum / self.premium_samples as f64 } else { 0.0 };
+        self.premium_sum = 0.0;
+        self.premium_samples = 0;
+        let rate = (premium + self.config.interest_rate).clamp(-self.config.max_rate, self.config.max_rate);
+
+        let settlement = FundingSettlement { ts, rate, premium, mark };
+        self.stats.settlements += 1;
+        self.stats.cumulative_rate += rate;
+        self.stats.last = Some(settlement);
+        Some(settlement)
+    }
+
+    /// Reset the schedule and statistics
+    pub fn reset(&mut self) {
+        *self = Self::new(self.config.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> FundingConfig {
+        FundingConfig {
+            enabled: true,
+            interval_ms: 10,
+            interest_rate: 0.0,
+            max_rate: 0.01,
+            mark_smoothing: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_settles_on_schedule_at_the_average_premium() {
+        let mut schedule = FundingSchedule::new(config());
+        schedule.set_index_price(10_000);
+
+        // Mid 1% and 3% over the index: 2% average, clamped to 1%
+        assert!(schedule.on_step(Some(10_100), 0).is_none());
+        assert!(schedule.on_step(Some(10_300), ms_to_ns(5)).is_none());
+        let settlement = schedule.on_step(None, ms_to_ns(10)).unwrap();
+        assert!((settlement.premium - 0.02).abs() < 1e-12);
+        assert_eq!(settlement.rate, 0.01);
+        assert_eq!(settlement.mark, 10_000);
+
+        // The next window starts from scratch: mid under the index, shorts pay
+        assert!(schedule.on_step(Some(9_950), ms_to_ns(15)).is_none());
+        let settlement = schedule.on_step(None, ms_to_ns(20)).unwrap();
+        assert!((settlement.rate + 0.005).abs() < 1e-12);
+        assert_eq!(schedule.stats().settlements, 2);
+    }
+
+    #[test]
+    fn test_payments_flow_from_longs_to_shorts() {
+        let settlement = FundingSettlement { ts: 0, rate: 0.001, premium: 0.001, mark: 500_000 };
+        let linear = ContractSpec::default();
+        // 100 long at 500000 ticks: 0.1% of 50,000,000
+        assert_eq!(settlement.payment(&linear, 100), 50_000);
+        assert_eq!(settlement.payment(&linear, -100), -50_000);
+        assert_eq!(settlement.payment(&linear, 0), 0);
+
+        // Inverse contracts pay in base units
+        let inverse = ContractSpec::inverse(10_000);
+        assert_eq!(settlement.payment(&inverse, 1000), 2_000_000);
+    }
+
+    #[test]
+    fn test_smoothed_mid_is_the_mark_without_an_index() {
+        let mut schedule = FundingSchedule::new(FundingConfig { mark_smoothing: 0.5, ..config() });
+        schedule.on_step(Some(10_000), 0);
+        schedule.on_step(Some(10_200), ms_to_ns(1));
+        assert_eq!(schedule.mark_price(), Some(10_100));
+
+        // A rising mid trades over its own average, so longs pay
+        let settlement = schedule.on_step(Some(10_400), ms_to_ns(10)).unwrap();
+        assert!(settlement.rate > 0.0);
+        assert!(FundingConfig { interval_ms: 0, ..config() }.validate().is_err());
+        assert!(FundingConfig { mark_smoothing: 0.0, ..config() }.validate().is_err());
+    }
+}
@@ -0,0 +1,435 @@
+//! Simulated exchange gateway outages
+//!
+//! Scheduled outages under `[[network.outages]]` take order entry down for a
+//! stretch of simulation time. While the gateway is down new orders are
+//! refused, or with `outage_policy = "queue"` held and sent together the
+//! moment it comes back, the way client reconnect storms hit a real venue.
+//! Agents hear about disconnects, reconnects and refused orders through
+//! [`SyntheticAgent::on_gateway`](crate::agents::SyntheticAgent::on_gateway)
+//! and can back off or resend as they see fit.
+//!
+//! With `[network.busy]` enabled, order entry also throttles the way an
+//! overloaded matching engine does: orders past a sustained rate (plus a
+//! burst allowance) and every order during a random busy spell are refused
+//! with a hint of how long to wait before retrying, so client backoff logic
+//! can be exercised.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::time::ms_to_ns;
+
+/// One scheduled gateway outage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct GatewayOutage {
+    /// Simulation time after the first step at which the gateway goes down
+    /// (milliseconds)
+    pub start_ms: u64,
+    /// How long it stays down (milliseconds)
+    pub duration_ms: u64,
+}
+
+impl GatewayOutage {
+    /// Check the window
+    pub fn validate(&self) -> Result<(), String> {
+        if self.duration_ms == 0 {
+            return Err("Gateway outage duration must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+
+    fn covers(&self, elapsed_ms: u64) -> bool {
+        elapsed_ms >= self.start_ms && elapsed_ms - self.start_ms < self.duration_ms
+    }
+}
+
+/// What happens to orders sent while the gateway is down
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OutagePolicy {
+    /// Refuse them
+    #[default]
+    Reject,
+    /// Hold them and submit them all at once on recovery
+    Queue,
+}
+
+/// Overload model for order entry
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct BusyConfig {
+    /// Throttle order entry
+    pub enabled: bool,
+    /// Orders per second of simulation time order entry sustains
+    pub orders_per_sec: f64,
+    /// Orders that may arrive back to back on top of the sustained rate
+    pub burst: u32,
+    /// Average busy spells per second of simulation time
+    pub busy_per_sec: f64,
+    /// How long a busy spell refuses every order (milliseconds)
+    pub busy_ms: u64,
+}
+
+impl Default for BusyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            orders_per_sec: 500.0,
+            burst: 20,
+            busy_per_sec: 0.0,
+            busy_ms: 50,
+        }
+    }
+}
+
+impl BusyConfig {
+    /// Check the model
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.orders_per_sec > 0.0 && self.orders_per_sec.is_finite()) {
+            return Err("Busy order rate must be greater than 0".to_string());
+        }
+        if self.burst == 0 {
+            return Err("Busy burst must be at least 1 order".to_string());
+        }
+        if !(self.busy_per_sec >= 0.0 && self.busy_per_sec.is_finite()) {
+            return Err("Busy spells per second cannot be negative".to_string());
+        }
+        if self.busy_per_sec > 0.0 && self.busy_ms == 0 {
+            return Err("Busy spell duration must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Rate limit and busy spells of an overloaded matching engine
+#[derive(Debug, Clone)]
+struct BusyModel {
+    config: BusyConfig,
+    /// Seed of `rng`, kept so a reset replays the same busy spells
+    seed: u64,
+    rng: StdRng,
+    /// Simulation time each order uses up at the sustained rate
+    cost_ns: u128,
+    /// Simulation time banked for orders, up to a burst's worth
+    credit_ns: u128,
+    /// Simulation time tokens were last added at
+    last_refill: Option<u128>,
+    /// Simulation time of the last poll, for the chance of a busy spell
+    last_poll: Option<u128>,
+    /// Simulation time the current busy spell ends
+    busy_until: u128,
+}
+
+impl BusyModel {
+    fn new(config: BusyConfig, seed: u64) -> Self {
+        let cost_ns = (1e9 / config.orders_per_sec).round().max(1.0) as u128;
+        Self {
+            config,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            cost_ns,
+            credit_ns: cost_ns * config.burst as u128,
+            last_refill: None,
+            last_poll: None,
+            busy_until: 0,
+        }
+    }
+
+    /// Maybe start a busy spell, reporting whether one started
+    fn poll(&mut self, now: u128) -> bool {
+        let elapsed_ns = self.last_poll.map_or(0, |last| now.saturating_sub(last));
+        self.last_poll = Some(now);
+        if now < self.busy_until || self.config.busy_per_sec <= 0.0 {
+            return false;
+        }
+        let chance = 1.0 - (-self.config.busy_per_sec * elapsed_ns as f64 / 1e9).exp();
+        if self.rng.gen::<f64>() >= chance {
+            return false;
+        }
+        self.busy_until = now + ms_to_ns(self.config.busy_ms);
+        true
+    }
+
+    /// Take an order at `now`, or say how long to wait before retrying (milliseconds)
+    fn admit(&mut self, now: u128) -> Result<(), u64> {
+        let credit_ns = self.credit_at(now);
+        self.last_refill = Some(self.last_refill.map_or(now, |last| last.max(now)));
+        self.credit_ns = credit_ns;
+        self.check(now)?;
+        self.credit_ns -= self.cost_ns;
+        Ok(())
+    }
+
+    /// Whether an order would be taken at `now`, without taking it
+    fn check(&self, now: u128) -> Result<(), u64> {
+        let retry_after_ms = |wait_ns: u128| wait_ns.div_ceil(1_000_000).max(1) as u64;
+        if now < self.busy_until {
+            return Err(retry_after_ms(self.busy_until - now));
+        }
+        let credit_ns = self.credit_at(now);
+        if credit_ns < self.cost_ns {
+            return Err(retry_after_ms(self.cost_ns - credit_ns));
+        }
+        Ok(())
+    }
+
+    /// Credit banked by `now`, up to a burst's worth
+    fn credit_at(&self, now: u128) -> u128 {
+        let elapsed_ns = self.last_refill.map_or(0, |last| now.saturating_sub(last));
+        (self.credit_ns + elapsed_ns).min(self.cost_ns * self.config.burst as u128)
+    }
+}
+
+/// A change in the gateway's state, or an order it turned away
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GatewayEvent<T> {
+    Disconnected,
+    Reconnected,
+    Rejected(T),
+    /// Refused by an overloaded matching engine; retrying sooner than
+    /// `retry_after_ms` of simulation time is refused again
+    Throttled { order: T, retry_after_ms: u64 },
+}
+
+/// Outage counts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GatewayStats {
+    /// Outages started
+    pub outages: u64,
+    /// Orders refused while down
+    pub rejected: u64,
+    /// Orders held while down
+    pub queued: u64,
+    /// Held orders sent on recovery
+    pub released: u64,
+    /// Orders refused by an overloaded matching engine
+    pub throttled: u64,
+    /// Orders let through by the order entry rate limit, when there is one
+    pub admitted: u64,
+    /// Busy spells started
+    pub busy_spells: u64,
+}
+
+/// Order entry that goes down on a schedule
+#[derive(Debug, Clone)]
+pub struct Gateway<T> {
+    outages: Vec<GatewayOutage>,
+    policy: OutagePolicy,
+    /// Simulation time of the first poll, which outage times count from
+    origin: Option<u128>,
+    down: bool,
+    queue: Vec<T>,
+    stats: GatewayStats,
+    /// Overload model (optional)
+    busy: Option<BusyModel>,
+}
+
+impl<T> Default for Gateway<T> {
+    fn default() -> Self {
+        Self::new(Vec::new(), OutagePolicy::default())
+    }
+}
+
+impl<T> Gateway<T> {
+    /// A gateway with the given outage schedule
+    pub fn new(outages: Vec<GatewayOutage>, policy: OutagePolicy) -> Self {
+        Self {
+            outages,
+            policy,
+            origin: None,
+            down: false,
+            queue: Vec::new(),
+            stats: GatewayStats::default(),
+            busy: None,
+        }
+    }
+
+    /// Throttle order entry under the given overload model, drawing busy
+    /// spells from a generator seeded with `seed`
+    pub fn with_busy(mut self, config: BusyConfig, seed: u64) -> Self {
+        self.busy = Some(BusyModel::new(config, seed));
+        self
+    }
+
+    /// Whether order entry is down
+    pub fn is_down(&self) -> bool {
+        self.down
+    }
+
+    /// Get outage counts
+    pub fn stats(&self) -> &GatewayStats {
+        &self.stats
+    }
+
+    /// Return to the state before the first poll: up, with no held orders,
+    /// no counts, and outage times counting from the next poll
+    pub fn reset(&mut self) {
+        self.origin = None;
+        self.down = false;
+        self.queue.clear();
+        self.stats = GatewayStats::default();
+        if let Some(busy) = self.busy.as_mut() {
+            *busy = BusyModel::new(busy.config, busy.seed);
+        }
+    }
+
+    /// Orders held for the next recovery
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Bring the gateway's state up to simulation time `now`, returning the
+    /// change if it went down or came back
+    pub fn poll(&mut self, now: u128) -> Option<GatewayEvent<T>> {
+        if self.busy.as_mut().is_some_and(|busy| busy.poll(now)) {
+            self.stats.busy_spells += 1;
+        }
+        if self.outages.is_empty() {
+            return None;
+        }
+        let origin = *self.origin.get_or_insert(now);
+        let elapsed_ms = (now.saturating_sub(origin) / 1_000_000) as u64;
+        let down = self.outages.iter().any(|outage| outage.covers(elapsed_ms));
+        if down == self.down {
+            return None;
+        }
+        self.down = down;
+        if down {
+            self.stats.outages += 1;
+            Some(GatewayEvent::Disconnected)
+        } else {
+            Some(GatewayEvent::Reconnected)
+        }
+    }
+
+    /// Offer an order for entry: it comes straight back while the gateway is
+    /// up, is held under the queue policy, and is returned as rejected
+    /// otherwise
+    pub fn submit(&mut self, order: T) -> Result<Option<T>, T> {
+        if !self.down {
+            return Ok(Some(order));
+        }
+        match self.policy {
+            OutagePolicy::Queue => {
+                self.stats.queued += 1;
+                self.queue.push(order);
+                Ok(None)
+            }
+            OutagePolicy::Reject => {
+                self.stats.rejected += 1;
+                Err(order)
+            }
+        }
+    }
+
+    /// Let an order through the overload model at simulation time `now`, or
+    /// refuse it with how long to wait before retrying (milliseconds)
+    pub fn admit(&mut self, now: u128) -> Result<(), u64> {
+        let Some(busy) = self.busy.as_mut() else {
+            return Ok(());
+        };
+        match busy.admit(now) {
+            Ok(()) => {
+                self.stats.admitted += 1;
+                Ok(())
+            }
+            Err(retry_after_ms) => {
+                self.stats.throttled += 1;
+                Err(retry_after_ms)
+            }
+        }
+    }
+
+    /// Whether [`admit`](Self::admit) would let an order through at `now`,
+    /// without using up any of the rate limit
+    pub fn check(&self, now: u128) -> Result<(), u64> {
+        self.busy.as_ref().map_or(Ok(()), |busy| busy.check(now))
+    }
+
+    /// Take the orders held during the outage, oldest first
+    pub fn take_queued(&mut self) -> Vec<T> {
+        let queued = std::mem::take(&mut self.queue);
+        self.stats.released += queued.len() as u64;
+        queued
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: u128 = 1_000_000;
+
+    #[test]
+    fn test_outage_schedule_and_policies() {
+        let outage = GatewayOutage { start_ms: 10, duration_ms: 5 };
+        let mut gateway: Gateway<u32> = Gateway::new(vec![outage], OutagePolicy::Reject);
+        assert_eq!(gateway.poll(1_000 * MS), None);
+        assert_eq!(gateway.submit(1), Ok(Some(1)));
+
+        assert_eq!(gateway.poll(1_010 * MS), Some(GatewayEvent::Disconnected));
+        assert_eq!(gateway.poll(1_012 * MS), None);
+        assert_eq!(gateway.submit(2), Err(2));
+        assert_eq!(gateway.poll(1_015 * MS), Some(GatewayEvent::Reconnected));
+        assert_eq!(*gateway.stats(), GatewayStats { outages: 1, rejected: 1, ..GatewayStats::default() });
+        assert!(GatewayOutage { duration_ms: 0, ..outage }.validate().is_err());
+    }
+
+    #[test]
+    fn test_queued_orders_burst_on_recovery() {
+        let mut gateway = Gateway::new(vec![GatewayOutage { start_ms: 0, duration_ms: 5 }], OutagePolicy::Queue);
+        assert_eq!(gateway.poll(0), Some(GatewayEvent::Disconnected));
+        assert_eq!(gateway.submit("a"), Ok(None));
+        assert_eq!(gateway.submit("b"), Ok(None));
+        assert_eq!(gateway.queued(), 2);
+
+        assert_eq!(gateway.poll(5 * MS), Some(GatewayEvent::Reconnected));
+        assert_eq!(gateway.take_queued(), vec!["a", "b"]);
+        assert_eq!(gateway.stats().released, 2);
+    }
+
+    #[test]
+    fn test_reset_drops_held_orders_and_restarts_schedule() {
+        let mut gateway = Gateway::new(vec![GatewayOutage { start_ms: 0, duration_ms: 5 }], OutagePolicy::Queue);
+        assert_eq!(gateway.poll(0), Some(GatewayEvent::Disconnected));
+        assert_eq!(gateway.submit("a"), Ok(None));
+
+        gateway.reset();
+        assert!(!gateway.is_down());
+        assert_eq!((gateway.queued(), *gateway.stats()), (0, GatewayStats::default()));
+        // The outage counts from the first poll after the reset
+        assert_eq!(gateway.poll(100 * MS), Some(GatewayEvent::Disconnected));
+        assert_eq!(gateway.poll(105 * MS), Some(GatewayEvent::Reconnected));
+        assert!(gateway.take_queued().is_empty());
+    }
+
+    #[test]
+    fn test_rate_limit_and_busy_spells_hint_retry() {
+        let config = BusyConfig { enabled: true, orders_per_sec: 100.0, burst: 2, ..BusyConfig::default() };
+        let mut gateway: Gateway<u32> = Gateway::default().with_busy(config, 7);
+
+        // The burst goes through, then one order per 10ms
+        assert_eq!(gateway.admit(0), Ok(()));
+        assert_eq!(gateway.check(0), Ok(()));
+        assert_eq!(gateway.admit(0), Ok(()));
+        assert_eq!(gateway.check(0), Err(10));
+        assert_eq!(gateway.admit(0), Err(10));
+        assert_eq!(gateway.admit(4 * MS), Err(6));
+        assert_eq!(gateway.admit(10 * MS), Ok(()));
+        assert_eq!(gateway.stats().throttled, 2);
+
+        // A busy spell refuses everything until it ends
+        let busy = BusyConfig { busy_per_sec: 1e6, busy_ms: 30, ..config };
+        let mut gateway: Gateway<u32> = Gateway::default().with_busy(busy, 7);
+        assert_eq!(gateway.poll(0), None);
+        gateway.poll(MS);
+        assert_eq!(gateway.stats().busy_spells, 1);
+        assert_eq!(gateway.admit(11 * MS), Err(20));
+        assert_eq!(gateway.admit(31 * MS), Ok(()));
+
+        assert!(BusyConfig { burst: 0, ..config }.validate().is_err());
+        assert!(BusyConfig { busy_per_sec: 1.0, busy_ms: 0, ..config }.validate().is_err());
+        assert!(config.validate().is_ok());
+    }
+}
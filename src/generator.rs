@@ -0,0 +1,757 @@
+use crate::data::{DataError, MarketEvent};
+use crate::engine::{EngineCommand, OrderBook, OrderBookEngine};
+use crate::error::EngineResult;
+use crate::queue_fifo::FifoLevel;
+use crate::sim::{MarketMakerConfig, OrderGenerationConfig, Simulator};
+use crate::time::now_ns;
+use crate::types::{price_utils, LotSize, Order, OrderId, Price, Qty, Side, Trade};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+
+/// Market regime used to parameterize generated order flow
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MarketProfile {
+    /// Tight spreads, deep liquidity, mostly passive flow
+    Calm,
+    /// Wide spreads, thin liquidity, aggressive flow
+    Volatile,
+    /// Persistent buying pressure that walks the price up
+    Trending,
+}
+
+impl MarketProfile {
+    /// Market maker parameters for this profile
+    pub fn market_maker_config(&self) -> MarketMakerConfig {
+        match self {
+            Self::Calm => MarketMakerConfig {
+                target_spread: price_utils::from_f64(0.01),
+                max_inventory: 1000,
+                order_size: 200,
+                mm_probability: 0.9,
+                inventory_skew: 0.0005,
+                inventory_bands: Vec::new(),
+                two_sided_quotes: false,
+            },
+            Self::Volatile => MarketMakerConfig {
+                target_spread: price_utils::from_f64(0.05),
+                max_inventory: 500,
+                order_size: 100,
+                mm_probability: 0.5,
+                inventory_skew: 0.002,
+                inventory_bands: Vec::new(),
+                two_sided_quotes: false,
+            },
+            Self::Trending => MarketMakerConfig::default(),
+        }
+    }
+
+    /// Order generation parameters for this profile
+    pub fn order_generation_config(&self) -> OrderGenerationConfig {
+        match self {
+            Self::Calm => OrderGenerationConfig {
+                market_order_prob: 0.1,
+                mean_order_interval_ns: 5_000_000,
+                min_order_size: 10,
+                max_order_size: 100,
+                price_range_fraction: 0.002,
+                buy_probability: 0.5,
+                max_slippage_ticks: None,
+            },
+            Self::Volatile => OrderGenerationConfig {
+                market_order_prob: 0.4,
+                mean_order_interval_ns: 500_000,
+                min_order_size: 50,
+                max_order_size: 1000,
+                price_range_fraction: 0.03,
+                buy_probability: 0.5,
+                max_slippage_ticks: None,
+            },
+            Self::Trending => OrderGenerationConfig {
+                market_order_prob: 0.3,
+                mean_order_interval_ns: 1_000_000,
+                min_order_size: 10,
+                max_order_size: 500,
+                price_range_fraction: 0.01,
+                buy_probability: 0.7,
+                max_slippage_ticks: None,
+            },
+        }
+    }
+}
+
+impl std::str::FromStr for MarketProfile {
+    type Err = DataError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "calm" => Ok(Self::Calm),
+            "volatile" => Ok(Self::Volatile),
+            "trending" => Ok(Self::Trending),
+            _ => Err(DataError::validation(format!(
+                "Unknown market profile: {} (expected calm, volatile or trending)", s
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for MarketProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Calm => write!(f, "calm"),
+            Self::Volatile => write!(f, "volatile"),
+            Self::Trending => write!(f, "trending"),
+        }
+    }
+}
+
+/// Generates reproducible sample data by running the simulator's synthetic
+/// order flow and recording every accepted order plus top-of-book quotes
+#[derive(Debug, Clone)]
+pub struct DataGenerator {
+    profile: MarketProfile,
+    seed: u64,
+    start_time: u128,
+    lot_size: LotSize,
+}
+
+impl DataGenerator {
+    /// Timestamp of the first generated event unless overridden (2023-11-14)
+    pub const DEFAULT_START_TIME: u128 = 1_700_000_000_000_000_000;
+
+    /// Create a generator for the given profile
+    pub fn new(profile: MarketProfile) -> Self {
+        Self {
+            profile,
+            seed: 42,
+            start_time: Self::DEFAULT_START_TIME,
+            lot_size: LotSize::default(),
+        }
+    }
+
+    /// Set the random seed; the same seed always produces the same events
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Set the timestamp the generated data starts at
+    pub fn with_start_time(mut self, start_time: u128) -> Self {
+        self.start_time = start_time;
+        self
+    }
+
+    /// Generate order sizes in whole lots
+    pub fn with_lot_size(mut self, lot_size: LotSize) -> Self {
+        self.lot_size = lot_size;
+        self
+    }
+
+    /// Generate exactly `event_count` events
+    ///
+    /// Trades are not written: replaying the order placements through the
+    /// engine reproduces them, so recording them too would double-count.
+    pub fn generate(&self, event_count: usize) -> EngineResult<Vec<MarketEvent>> {
+        let mut simulator = Simulator::with_seed(OrderBook::<FifoLevel>::new(), self.seed)
+            .with_market_maker_config(self.profile.market_maker_config())
+            .with_order_generation_config(self.profile.order_generation_config())
+            .with_lot_size(self.lot_size);
+        simulator.record_events(true);
+
+        let mut events = Vec::with_capacity(event_count);
+        let mut clock = self.start_time;
+        let mut last_quote = (None, None);
+
+        while events.len() < event_count {
+            // The engine only accepts orders stamped near the wall clock, so the
+            // simulator runs in real time and events are re-based onto `clock`
+            let step_start = now_ns();
+            simulator.set_time(step_start);
+            simulator.step()?;
+            clock += simulator.current_time() - step_start;
+
+            for mut event in simulator.take_recorded_events() {
+                if let MarketEvent::OrderPlacement(ref mut order) = event {
+                    order.ts = clock;
+                }
+                events.push(event);
+            }
+
+            let engine = &simulator.engine;
+            let quote = (engine.best_bid(), engine.best_ask());
+            if quote != last_quote {
+                last_quote = quote;
+                events.push(MarketEvent::Quote {
+                    bid: quote.0,
+                    ask: quote.1,
+                    bid_qty: quote.0.map(|price| engine.depth_at(Side::Buy, price)),
+                    ask_qty: quote.1.map(|price| engine.depth_at(Side::Sell, price)),
+                    timestamp: clock,
+                });
+            }
+        }
+
+        events.truncate(event_count);
+        Ok(events)
+    }
+}
+
+/// Pre-generates the synthetic order stream without running it through an
+/// engine
+///
+/// [`DataGenerator`] lets the book shape each order, so its output depends on
+/// the engine it ran against. This generator is open-loop: market maker
+/// quotes sit around a reference price that each taker market order moves
+/// by half the target spread in its direction, and taker flow follows the
+/// order generation settings. The same seed and settings always give the
+/// same orders, so one file can be replayed against differently configured
+/// engines and any difference in the outcome comes from the engine alone.
+///
+/// Settings that need a book to act on are not applied: inventory skew and
+/// limits (nothing is filled while generating) and the taker slippage budget.
+#[derive(Debug, Clone)]
+pub struct OrderStreamGenerator {
+    market_maker: MarketMakerConfig,
+    order_generation: OrderGenerationConfig,
+    seed: u64,
+    start_time: u128,
+    lot_size: LotSize,
+    initial_price: Price,
+}
+
+impl OrderStreamGenerator {
+    /// Generate flow with the given market maker and taker settings
+    pub fn new(market_maker: MarketMakerConfig, order_generation: OrderGenerationConfig) -> Self {
+        Self {
+            market_maker,
+            order_generation,
+            seed: 42,
+            start_time: DataGenerator::DEFAULT_START_TIME,
+            lot_size: LotSize::default(),
+            initial_price: price_utils::from_f64(100.0),
+        }
+    }
+
+    /// Generate flow for a market profile
+    pub fn for_profile(profile: MarketProfile) -> Self {
+        Self::new(profile.market_maker_config(), profile.order_generation_config())
+    }
+
+    /// Set the random seed; the same seed always produces the same stream
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Set the timestamp the stream starts at
+    pub fn with_start_time(mut self, start_time: u128) -> Self {
+        self.start_time = start_time;
+        self
+    }
+
+    /// Generate order sizes in whole lots
+    pub fn with_lot_size(mut self, lot_size: LotSize) -> Self {
+        self.lot_size = lot_size;
+        self
+    }
+
+    /// Set the reference price the stream starts at, in ticks
+    pub fn with_initial_price(mut self, price: Price) -> Self {
+        self.initial_price = price;
+        self
+    }
+
+    /// Generate exactly `event_count` order placements and cancellations
+    ///
+    /// When the market maker requotes a side, its previous quote there is
+    /// cancelled first; replay ignores the cancel if the quote has filled.
+    pub fn generate(&self, event_count: usize) -> Vec<MarketEvent> {
+        let mm = &self.market_maker;
+        let flow = &self.order_generation;
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let whole_lots = |qty: Qty| self.lot_size.round_down(qty).max(self.lot_size.size);
+        let half_spread = (mm.target_spread / 2).max(1);
+
+        let mut events = Vec::with_capacity(event_count);
+        let mut next_id: OrderId = 1;
+        let mut clock = self.start_time;
+        let mut reference = self.initial_price.max(half_spread + 1);
+        let mut quotes: [Option<OrderId>; 2] = [None, None];
+
+        while events.len() < event_count {
+            clock += rng.gen_range(flow.mean_order_interval_ns / 2..=flow.mean_order_interval_ns * 2) as u128;
+
+            for (slot, side) in [Side::Buy, Side::Sell].into_iter().enumerate() {
+                if rng.gen::<f64>() >= mm.mm_probability {
+                    continue;
+                }
+                if let Some(order_id) = quotes[slot].take() {
+                    events.push(MarketEvent::OrderCancellation {
+                        order_id,
+                        timestamp: clock,
+                        reason: Some("requote".to_string()),
+                    });
+                }
+                let price = match side {
+                    Side::Buy => reference - half_spread,
+                    Side::Sell => reference + half_spread,
+                };
+                events.push(MarketEvent::OrderPlacement(Order::new_limit(next_id, side, whole_lots(mm.order_size), price, clock)));
+                quotes[slot] = Some(next_id);
+                next_id += 1;
+            }
+
+            let side = if rng.gen_bool(flow.buy_probability) { Side::Buy } else { Side::Sell };
+            let qty = whole_lots(rng.gen_range(flow.min_order_size..=flow.max_order_size));
+            if rng.gen::<f64>() < flow.market_order_prob {
+                events.push(MarketEvent::OrderPlacement(Order::new_market(next_id, side, qty, clock)));
+                next_id += 1;
+                reference = match side {
+                    Side::Buy => reference.saturating_add(half_spread),
+                    Side::Sell => reference.saturating_sub(half_spread).max(half_spread + 1),
+                };
+            } else {
+                let mid = price_utils::to_f64(reference);
+                let offset = rng.gen_range(0..=price_utils::from_f64(mid * flow.price_range_fraction));
+                let price = match (side, rng.gen::<bool>()) {
+                    (Side::Buy, true) | (Side::Sell, false) => reference.saturating_sub(offset),
+                    (Side::Buy, false) | (Side::Sell, true) => reference.saturating_add(offset),
+                };
+                if price > 0 {
+                    events.push(MarketEvent::OrderPlacement(Order::new_limit(next_id, side, qty, price, clock)));
+                    next_id += 1;
+                }
+            }
+        }
+
+        events.truncate(event_count);
+        events
+    }
+}
+
+/// One operation of an engine workload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WorkloadOp {
+    Place(Order),
+    Cancel(OrderId),
+}
+
+impl WorkloadOp {
+    /// Apply the operation to an engine, returning any trades
+    pub fn apply<E: OrderBookEngine>(&self, engine: &mut E) -> EngineResult<Vec<Trade>> {
+        match self {
+            Self::Place(order) => engine.place(order.clone()),
+            Self::Cancel(order_id) => engine.cancel(*order_id).map(|_| Vec::new()),
+        }
+    }
+}
+
+/// A generated workload: orders that build the starting book, then the
+/// operations to measure
+#[derive(Debug, Clone, Default)]
+pub struct Workload {
+    pub initial: Vec<Order>,
+    pub ops: Vec<WorkloadOp>,
+}
+
+impl Workload {
+    /// The workload as one engine command stream: the starting book, then
+    /// the operations
+    pub fn commands(&self) -> Vec<EngineCommand> {
+        self.initial.iter().cloned().map(EngineCommand::Place)
+            .chain(self.ops.iter().map(|op| match op {
+                WorkloadOp::Place(order) => EngineCommand::Place(order.clone()),
+                WorkloadOp::Cancel(order_id) => EngineCommand::Cancel(*order_id),
+            }))
+            .collect()
+    }
+}
+
+/// Cancel-heavy order flow clustered at the touch, as produced by
+/// high-frequency market makers
+///
+/// Most activity is cancel/replace: a resting order near the touch is pulled
+/// and requoted at one of the best few levels. Every so often a market order
+/// takes the order at the front of the opposite touch. The generator tracks
+/// the book it builds, so every cancel targets an order that is still
+/// resting when the operations are applied in order to an empty FIFO book.
+#[derive(Debug, Clone)]
+pub struct HftWorkload {
+    seed: u64,
+    cancels_per_trade: u32,
+    touch_levels: u32,
+    resting_per_side: usize,
+    order_qty: Qty,
+    mid: Price,
+    tick: Price,
+}
+
+impl Default for HftWorkload {
+    fn default() -> Self {
+        Self {
+            seed: 42,
+            cancels_per_trade: 20,
+            touch_levels: 3,
+            resting_per_side: 50,
+            order_qty: 100,
+            mid: price_utils::from_f64(100.0),
+            tick: price_utils::from_f64(0.01),
+        }
+    }
+}
+
+impl HftWorkload {
+    /// Create a workload with 20 cancel/replaces per trade over the best
+    /// 3 levels
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the random seed; the same seed always produces the same workload
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Set how many cancel/replaces happen per trade
+    pub fn with_cancels_per_trade(mut self, cancels_per_trade: u32) -> Self {
+        self.cancels_per_trade = cancels_per_trade;
+        self
+    }
+
+    /// Set how many levels from the touch orders are quoted at
+    pub fn with_touch_levels(mut self, touch_levels: u32) -> Self {
+        self.touch_levels = touch_levels.max(1);
+        self
+    }
+
+    /// Set how many orders rest on each side of the starting book
+    pub fn with_resting_per_side(mut self, resting_per_side: usize) -> Self {
+        self.resting_per_side = resting_per_side.max(1);
+        self
+    }
+
+    /// Generate the starting book and `op_count` operations
+    pub fn generate(&self, op_count: usize) -> Workload {
+        let mut stream = self.stream();
+        let initial = stream.seed_book();
+        Workload {
+            initial,
+            ops: stream.take(op_count).collect(),
+        }
+    }
+
+    /// Stream operations without end, for runs too long to generate up front
+    ///
+    /// Call `seed_book` first to get the orders that build the starting book.
+    pub fn stream(&self) -> HftStream {
+        HftStream {
+            workload: self.clone(),
+            rng: StdRng::seed_from_u64(self.seed),
+            book: WorkloadBook::default(),
+            next_id: 1,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Level to quote at, weighted towards the touch
+    fn level(&self, rng: &mut StdRng) -> u32 {
+        let mut level = 0;
+        while level + 1 < self.touch_levels && rng.gen_bool(0.5) {
+            level += 1;
+        }
+        level
+    }
+
+    /// A resting order at a level near the touch, never crossing the mid
+    fn quote(&self, rng: &mut StdRng, id: OrderId, side: Side) -> Order {
+        let offset = (self.level(rng) as Price + 1) * self.tick;
+        let price = match side {
+            Side::Buy => self.mid.saturating_sub(offset).max(1),
+            Side::Sell => self.mid + offset,
+        };
+        Order::new_limit(id, side, self.order_qty, price, now_ns())
+    }
+}
+
+/// Endless operation stream produced by `HftWorkload::stream`
+#[derive(Debug)]
+pub struct HftStream {
+    workload: HftWorkload,
+    rng: StdRng,
+    book: WorkloadBook,
+    next_id: OrderId,
+    pending: VecDeque<WorkloadOp>,
+}
+
+impl HftStream {
+    /// Orders that build the starting book; place them before the stream's
+    /// operations
+    pub fn seed_book(&mut self) -> Vec<Order> {
+        let mut orders = Vec::with_capacity(self.workload.resting_per_side * 2);
+        for _ in 0..self.workload.resting_per_side {
+            for side in [Side::Buy, Side::Sell] {
+                orders.push(self.quote(side));
+            }
+        }
+        orders
+    }
+
+    /// Quote a new resting order and track it
+    fn quote(&mut self, side: Side) -> Order {
+        let order = self.workload.quote(&mut self.rng, self.next_id, side);
+        self.next_id += 1;
+        self.book.add(&order);
+        order
+    }
+
+    /// Queue the operations of one step: a trade and a requote, or a
+    /// cancel/replace
+    fn step(&mut self) {
+        let side = if self.rng.gen_bool(0.5) { Side::Buy } else { Side::Sell };
+        let trade_probability = 1.0 / (self.workload.cancels_per_trade as f64 + 1.0);
+        if self.rng.gen_bool(trade_probability) {
+            // Take the front order at the opposite touch
+            if self.book.take_front(side.opposite()) {
+                self.pending.push_back(WorkloadOp::Place(Order::new_market(self.next_id, side, self.workload.order_qty, now_ns())));
+                self.next_id += 1;
+            }
+            // Requote so the side does not drain
+            let order = self.quote(side.opposite());
+            self.pending.push_back(WorkloadOp::Place(order));
+        } else {
+            let level = self.workload.level(&mut self.rng) as usize;
+            if let Some(order_id) = self.book.remove_near_touch(side, level, &mut self.rng) {
+                self.pending.push_back(WorkloadOp::Cancel(order_id));
+            }
+            let order = self.quote(side);
+            self.pending.push_back(WorkloadOp::Place(order));
+        }
+    }
+}
+
+impl Iterator for HftStream {
+    type Item = WorkloadOp;
+
+    fn next(&mut self) -> Option<WorkloadOp> {
+        while self.pending.is_empty() {
+            self.step();
+        }
+        self.pending.pop_front()
+    }
+}
+
+/// The generator's model of the book it is building
+#[derive(Debug, Default)]
+struct WorkloadBook {
+    bids: BTreeMap<Price, VecDeque<OrderId>>,
+    asks: BTreeMap<Price, VecDeque<OrderId>>,
+}
+
+impl WorkloadBook {
+    fn side(&mut self, side: Side) -> &mut BTreeMap<Price, VecDeque<OrderId>> {
+        match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        }
+    }
+
+    fn add(&mut self, order: &Order) {
+        if let Some(price) = order.price() {
+            self.side(order.side).entry(price).or_default().push_back(order.id);
+        }
+    }
+
+    /// Price of the level `depth` levels from the touch, or the deepest one
+    fn level_price(&mut self, side: Side, depth: usize) -> Option<Price> {
+        let levels = self.side(side);
+        let count = levels.len();
+        let depth = depth.min(count.checked_sub(1)?);
+        match side {
+            Side::Buy => levels.keys().rev().nth(depth).copied(),
+            Side::Sell => levels.keys().nth(depth).copied(),
+        }
+    }
+
+    /// Remove the order a full-size market order would fill
+    fn take_front(&mut self, side: Side) -> bool {
+        let Some(price) = self.level_price(side, 0) else {
+            return false;
+        };
+        self.remove_at(side, price, 0);
+        true
+    }
+
+    /// Remove a random order from a level near the touch
+    fn remove_near_touch(&mut self, side: Side, depth: usize, rng: &mut StdRng) -> Option<OrderId> {
+        let price = self.level_price(side, depth)?;
+        let len = self.side(side).get(&price)?.len();
+        self.remove_at(side, price, rng.gen_range(0..len))
+    }
+
+    fn remove_at(&mut self, side: Side, price: Price, index: usize) -> Option<OrderId> {
+        let levels = self.side(side);
+        let queue = levels.get_mut(&price)?;
+        let order_id = queue.remove(index);
+        if queue.is_empty() {
+            levels.remove(&price);
+        }
+        order_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{DataFormat, DataFormatDetector};
+
+    fn mid(event: &MarketEvent) -> Option<f64> {
+        match event {
+            MarketEvent::Quote { bid: Some(bid), ask: Some(ask), .. } => {
+                Some(price_utils::mid_price(*bid, *ask))
+            }
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_generation_is_reproducible() {
+        let generator = DataGenerator::new(MarketProfile::Volatile).with_seed(7);
+        let first = generator.generate(500).unwrap();
+        let second = generator.generate(500).unwrap();
+
+        assert_eq!(first.len(), 500);
+        assert_eq!(first, second);
+        assert!(first[0].timestamp() >= DataGenerator::DEFAULT_START_TIME);
+        assert!(first.windows(2).all(|pair| pair[0].timestamp() <= pair[1].timestamp()));
+        assert!(first.iter().any(|event| matches!(event, MarketEvent::OrderPlacement(_))));
+        assert!(first.iter().any(|event| matches!(event, MarketEvent::Quote { .. })));
+    }
+
+    #[test]
+    fn test_generated_orders_respect_lot_size() {
+        let events = DataGenerator::new(MarketProfile::Volatile)
+            .with_lot_size(LotSize::new(100))
+            .generate(300)
+            .unwrap();
+        let orders: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                MarketEvent::OrderPlacement(order) => Some(order.qty),
+                _ => None,
+            })
+            .collect();
+        assert!(!orders.is_empty());
+        assert!(orders.iter().all(|qty| qty % 100 == 0));
+    }
+
+    #[test]
+    fn test_trending_profile_moves_price_up() {
+        let events = DataGenerator::new(MarketProfile::Trending).generate(5000).unwrap();
+        let mids: Vec<f64> = events.iter().filter_map(mid).collect();
+
+        assert!(mids.len() > 2);
+        assert!(mids.last().unwrap() > mids.first().unwrap());
+    }
+
+    #[test]
+    fn test_generated_files_round_trip_in_every_format() {
+        let events = DataGenerator::new(MarketProfile::Calm).generate(200).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+
+        for name in ["sample.csv", "sample.jsonl", "sample.bin"] {
+            let path = dir.path().join(name);
+            DataFormat::from_path(&path).unwrap().write_file(&path, &events).unwrap();
+
+            let mut source = DataFormatDetector::create_data_source(&path).unwrap();
+            source.set_paused(true).unwrap();
+            let mut read_back = Vec::new();
+            while let Some(event) = source.next_event().unwrap() {
+                read_back.push(event);
+            }
+            assert_eq!(read_back, events, "{} did not round-trip", name);
+        }
+    }
+
+    #[test]
+    fn test_order_stream_is_reproducible_without_an_engine() {
+        let generator = OrderStreamGenerator::for_profile(MarketProfile::Volatile)
+            .with_seed(11)
+            .with_lot_size(LotSize::new(10));
+        let stream = generator.generate(1_000);
+        assert_eq!(stream.len(), 1_000);
+        assert_eq!(stream, generator.generate(1_000));
+        assert_ne!(stream, generator.clone().with_seed(12).generate(1_000));
+        assert!(stream.windows(2).all(|pair| pair[0].timestamp() <= pair[1].timestamp()));
+
+        // Every cancel refers to an earlier quote, and sizes are whole lots
+        let mut placed = std::collections::HashSet::new();
+        for event in &stream {
+            match event {
+                MarketEvent::OrderPlacement(order) => {
+                    assert!(placed.insert(order.id));
+                    assert_eq!(order.qty % 10, 0);
+                }
+                MarketEvent::OrderCancellation { order_id, .. } => assert!(placed.contains(order_id)),
+                other => panic!("unexpected event {:?}", other),
+            }
+        }
+        assert!(stream.iter().any(|event| matches!(event, MarketEvent::OrderPlacement(order) if order.is_market())));
+    }
+
+    #[test]
+    fn test_order_stream_replays_against_different_engines() {
+        use crate::engine::DepthLimit;
+
+        let stream = OrderStreamGenerator::for_profile(MarketProfile::Calm).with_seed(3).generate(400);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stream.jsonl");
+        DataFormat::from_path(&path).unwrap().write_file(&path, &stream).unwrap();
+
+        let replay = |depth_limit: DepthLimit| {
+            let mut source = DataFormatDetector::create_data_source(&path).unwrap();
+            source.set_paused(true).unwrap();
+            let mut simulator = Simulator::with_seed(OrderBook::<FifoLevel>::new(), 1)
+                .with_depth_limit(depth_limit)
+                .with_data_source(source);
+            let trades = simulator.run_steps(stream.len()).unwrap();
+            let fills: Vec<(Price, Qty)> = trades.iter().map(|trade| (trade.price, trade.qty)).collect();
+            (fills, simulator.snapshot())
+        };
+
+        // The same file replays identically, and only the engine setting changes the book
+        let (fills, deep) = replay(DepthLimit::default());
+        assert!(!fills.is_empty());
+        assert_eq!(fills, replay(DepthLimit::default()).0);
+        assert!(deep.bids.len() > 3 || deep.asks.len() > 3);
+
+        let (_, shallow) = replay(DepthLimit::levels(3));
+        assert!(shallow.bids.len() <= 3 && shallow.asks.len() <= 3);
+    }
+
+    #[test]
+    fn test_hft_workload_cancels_live_orders() {
+        let workload = HftWorkload::new().with_seed(3).generate(2_000);
+        assert_eq!(workload.ops.len(), 2_000);
+
+        let cancels = workload.ops.iter().filter(|op| matches!(op, WorkloadOp::Cancel(_))).count();
+        let takers = workload.ops.iter()
+            .filter(|op| matches!(op, WorkloadOp::Place(order) if order.price().is_none()))
+            .count();
+        assert!(takers > 0);
+        assert!(cancels > takers * 10);
+
+        // Replayed against an empty book, every operation succeeds
+        let mut book = OrderBook::<FifoLevel>::new();
+        for order in &workload.initial {
+            assert!(book.place(order.clone()).unwrap().is_empty());
+        }
+        for op in &workload.ops {
+            op.apply(&mut book).unwrap();
+        }
+        let spread = book.spread().unwrap();
+        assert!(spread > 0 && spread <= 2 * 3 * price_utils::from_f64(0.01) as i64);
+    }
+}
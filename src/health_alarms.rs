@@ -0,0 +1,346 @@
+//! Rate-of-change alarms on server health
+//!
+//! Thresholds on absolute values miss a server that is getting worse but is
+//! not yet broken. Each check compares health against earlier checks: how
+//! fast errors are piling up, whether the step duration has been creeping up
+//! over the trend window, and whether sending snapshots to clients is taking
+//! longer than it did. A rule fires once when its condition starts holding
+//! and resolves once it stops, so a sustained problem raises one alert
+//! rather than one per check. Alerts go to the log, the activity timeline
+//! and, if configured, a webhook.
+
+use std::collections::{BTreeSet, VecDeque};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+
+/// Health alarm settings
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct HealthAlarmConfig {
+    /// Check health and raise alarms
+    #[serde(default)]
+    pub enabled: bool,
+    /// How often health is checked
+    pub interval_ms: u64,
+    /// Checks a trend is measured over
+    pub trend_window: usize,
+    /// Fire when errors arrive at least this fast (per second)
+    #[serde(default)]
+    pub error_rate_per_sec: Option<f64>,
+    /// Fire when the average step duration has risen by at least this
+    /// fraction over the trend window
+    #[serde(default)]
+    pub step_duration_rise: Option<f64>,
+    /// Fire when the mean time to send a snapshot to a client has risen by at
+    /// least this fraction over the trend window
+    #[serde(default)]
+    pub broadcast_lag_rise: Option<f64>,
+    /// POST each alert here as JSON
+    #[serde(default)]
+    pub webhook_url: Option<String>,
+    /// Give up on a webhook call after this long
+    pub webhook_timeout_ms: u64,
+}
+
+impl Default for HealthAlarmConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_ms: 1000,
+            trend_window: 10,
+            error_rate_per_sec: Some(5.0),
+            step_duration_rise: Some(0.5),
+            broadcast_lag_rise: Some(1.0),
+            webhook_url: None,
+            webhook_timeout_ms: 2000,
+        }
+    }
+}
+
+impl HealthAlarmConfig {
+    /// Check the settings
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval_ms == 0 {
+            return Err("Health alarm interval must be greater than 0".to_string());
+        }
+        if self.trend_window == 0 {
+            return Err("Health alarm trend window must be at least 1 check".to_string());
+        }
+        for (name, threshold) in [
+            ("error_rate_per_sec", self.error_rate_per_sec),
+            ("step_duration_rise", self.step_duration_rise),
+            ("broadcast_lag_rise", self.broadcast_lag_rise),
+        ] {
+            if threshold.is_some_and(|threshold| !threshold.is_finite() || threshold <= 0.0) {
+                return Err(format!("Health alarm threshold {} must be positive", name));
+            }
+        }
+        if let Some(url) = &self.webhook_url {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(format!("Health alarm webhook URL '{}' must be http or https", url));
+            }
+            if self.webhook_timeout_ms == 0 {
+                return Err("Health alarm webhook timeout must be greater than 0".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// How often health is checked
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms)
+    }
+}
+
+/// What an alarm watches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlarmRule {
+    /// Errors per second since the last check
+    ErrorRate,
+    /// Rise in the average step duration over the trend window
+    StepDuration,
+    /// Rise in the mean snapshot send time over the trend window
+    BroadcastLag,
+}
+
+/// Whether an alert raises or clears its alarm
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlarmState {
+    Firing,
+    Resolved,
+}
+
+/// An alarm starting or stopping
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HealthAlert {
+    pub rule: AlarmRule,
+    pub state: AlarmState,
+    /// Measured value: errors per second, or the fractional rise
+    pub value: f64,
+    pub threshold: f64,
+    /// Wall-clock time of the check (milliseconds since the epoch)
+    pub time_ms: u64,
+    pub message: String,
+}
+
+/// Health counters at one check
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HealthSample {
+    /// Wall-clock time of the check (milliseconds since the epoch)
+    pub time_ms: u64,
+    pub total_errors: u64,
+    pub avg_step_duration_ms: f64,
+    /// Snapshots sent to individual clients, and the time it took in total
+    pub client_sends: u64,
+    pub send_total_ns: u64,
+}
+
+/// What a check measured, kept for the trend window
+#[derive(Debug, Clone, Copy)]
+struct Observation {
+    step_ms: f64,
+    /// Mean send time since the previous check, if anything was sent
+    send_mean_ns: Option<f64>,
+}
+
+/// Rise of `current` over `base` as a fraction of `base`
+fn rise(base: f64, current: f64) -> Option<f64> {
+    (base > 0.0).then(|| current / base - 1.0)
+}
+
+/// Alarm rules evaluated against successive health samples
+#[derive(Debug, Clone)]
+pub struct HealthAlarms {
+    config: HealthAlarmConfig,
+    previous: Option<HealthSample>,
+    history: VecDeque<Observation>,
+    firing: BTreeSet<AlarmRule>,
+}
+
+impl HealthAlarms {
+    /// Start with no samples and nothing firing
+    pub fn new(config: HealthAlarmConfig) -> Self {
+        Self {
+            config,
+            previous: None,
+            history: VecDeque::new(),
+            firing: BTreeSet::new(),
+        }
+    }
+
+    /// The alarm settings
+    pub fn config(&self) -> &HealthAlarmConfig {
+        &self.config
+    }
+
+    /// Alarms currently firing
+    pub fn firing(&self) -> Vec<AlarmRule> {
+        self.firing.iter().copied().collect()
+    }
+
+    /// Check a sample against the earlier ones, returning the alarms that
+    /// started or stopped firing
+    pub fn evaluate(&mut self, sample: HealthSample) -> Vec<HealthAlert> {
+        let Some(previous) = self.previous.replace(sample) else {
+            self.history.push_back(Observation { step_ms: sample.avg_step_duration_ms, send_mean_ns: None });
+            return Vec::new();
+        };
+        let sends = sample.client_sends.saturating_sub(previous.client_sends);
+        let observation = Observation {
+            step_ms: sample.avg_step_duration_ms,
+            send_mean_ns: (sends > 0).then(|| sample.send_total_ns.saturating_sub(previous.send_total_ns) as f64 / sends as f64),
+        };
+        self.history.push_back(observation);
+        while self.history.len() > self.config.trend_window + 1 {
+            self.history.pop_front();
+        }
+        // Trends need a full window behind them
+        let base = (self.history.len() > self.config.trend_window)
+            .then(|| self.history.front().copied())
+            .flatten();
+        let elapsed_secs = sample.time_ms.saturating_sub(previous.time_ms) as f64 / 1000.0;
+
+        let window = self.config.trend_window;
+        let readings = [
+            (
+                AlarmRule::ErrorRate,
+                self.config.error_rate_per_sec,
+                (elapsed_secs > 0.0).then(|| sample.total_errors.saturating_sub(previous.total_errors) as f64 / elapsed_secs),
+            ),
+            (
+                AlarmRule::StepDuration,
+                self.config.step_duration_rise,
+                base.and_then(|base| rise(base.step_ms, observation.step_ms)),
+            ),
+            (
+                AlarmRule::BroadcastLag,
+                self.config.broadcast_lag_rise,
+                base.and_then(|base| rise(base.send_mean_ns?, observation.send_mean_ns?)),
+            ),
+        ];
+
+        let mut alerts = Vec::new();
+        for (rule, threshold, value) in readings {
+            let (Some(threshold), Some(value)) = (threshold, value) else {
+                continue;
+            };
+            let state = if value >= threshold && self.firing.insert(rule) {
+                AlarmState::Firing
+            } else if value < threshold && self.firing.remove(&rule) {
+                AlarmState::Resolved
+            } else {
+                continue;
+            };
+            let message = match (rule, state) {
+                (AlarmRule::ErrorRate, AlarmState::Firing) => format!("Errors arriving at {:.1}/s, at or over {:.1}/s", value, threshold),
+                (AlarmRule::ErrorRate, AlarmState::Resolved) => format!("Error rate back to {:.1}/s", value),
+                (AlarmRule::StepDuration, AlarmState::Firing) => format!(
+                    "Step duration up {:.0}% to {:.3}ms over the last {} checks",
+                    value * 100.0, observation.step_ms, window
+                ),
+                (AlarmRule::StepDuration, AlarmState::Resolved) => format!("Step duration steady at {:.3}ms", observation.step_ms),
+                (AlarmRule::BroadcastLag, AlarmState::Firing) => format!(
+                    "Snapshot send time up {:.0}% to {:.0}ns over the last {} checks",
+                    value * 100.0, observation.send_mean_ns.unwrap_or_default(), window
+                ),
+                (AlarmRule::BroadcastLag, AlarmState::Resolved) => format!(
+                    "Snapshot send time steady at {:.0}ns", observation.send_mean_ns.unwrap_or_default()
+                ),
+            };
+            alerts.push(HealthAlert { rule, state, value, threshold, time_ms: sample.time_ms, message });
+        }
+        alerts
+    }
+}
+
+/// Posts alerts to a webhook
+#[derive(Debug, Clone)]
+pub struct AlarmWebhook {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl AlarmWebhook {
+    /// A webhook for the configured URL, if there is one
+    pub fn from_config(config: &HealthAlarmConfig) -> Result<Option<Self>, String> {
+        let Some(url) = &config.webhook_url else {
+            return Ok(None);
+        };
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.webhook_timeout_ms))
+            .build()
+            .map_err(|e| format!("Failed to build webhook client: {}", e))?;
+        Ok(Some(Self { url: url.clone(), client }))
+    }
+
+    /// URL alerts are posted to
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// POST an alert as JSON
+    pub async fn send(&self, alert: &HealthAlert) -> Result<(), String> {
+        self.client.post(&self.url)
+            .json(alert)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map(|_| ())
+            .map_err(|e| format!("Webhook {} failed: {}", self.url, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(second: u64, total_errors: u64, avg_step_duration_ms: f64, client_sends: u64, send_total_ns: u64) -> HealthSample {
+        HealthSample { time_ms: second * 1000, total_errors, avg_step_duration_ms, client_sends, send_total_ns }
+    }
+
+    #[test]
+    fn test_alarms_fire_once_on_change_and_resolve() {
+        let mut alarms = HealthAlarms::new(HealthAlarmConfig {
+            enabled: true,
+            trend_window: 2,
+            ..HealthAlarmConfig::default()
+        });
+        assert!(alarms.evaluate(sample(0, 0, 1.0, 0, 0)).is_empty());
+        // 10 errors in a second; trends have no full window yet
+        let alerts = alarms.evaluate(sample(1, 10, 1.0, 10, 10_000));
+        assert_eq!(alerts.len(), 1);
+        assert_eq!((alerts[0].rule, alerts[0].state, alerts[0].value), (AlarmRule::ErrorRate, AlarmState::Firing, 10.0));
+        // Still spiking does not fire again
+        assert!(alarms.evaluate(sample(2, 20, 1.2, 20, 20_000)).is_empty());
+        assert_eq!(alarms.firing(), vec![AlarmRule::ErrorRate]);
+
+        // Errors stop while steps slow by 60% and sends by 200% over the window
+        let alerts = alarms.evaluate(sample(3, 20, 1.6, 30, 50_000));
+        let changes: Vec<_> = alerts.iter().map(|alert| (alert.rule, alert.state)).collect();
+        assert_eq!(changes, vec![
+            (AlarmRule::ErrorRate, AlarmState::Resolved),
+            (AlarmRule::StepDuration, AlarmState::Firing),
+            (AlarmRule::BroadcastLag, AlarmState::Firing),
+        ]);
+        assert!((alerts[1].value - 0.6).abs() < 1e-9);
+        assert_eq!(alarms.firing(), vec![AlarmRule::StepDuration, AlarmRule::BroadcastLag]);
+
+        // Once things level off each trend clears when the rise leaves its window
+        let alerts = alarms.evaluate(sample(4, 20, 1.6, 40, 80_000));
+        assert_eq!(alerts.iter().map(|alert| (alert.rule, alert.state)).collect::<Vec<_>>(), vec![(AlarmRule::StepDuration, AlarmState::Resolved)]);
+        let alerts = alarms.evaluate(sample(5, 20, 1.6, 50, 110_000));
+        assert_eq!(alerts.iter().map(|alert| (alert.rule, alert.state)).collect::<Vec<_>>(), vec![(AlarmRule::BroadcastLag, AlarmState::Resolved)]);
+        assert!(alarms.firing().is_empty());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(HealthAlarmConfig::default().validate().is_ok());
+        assert!(HealthAlarmConfig { trend_window: 0, ..HealthAlarmConfig::default() }.validate().is_err());
+        assert!(HealthAlarmConfig { error_rate_per_sec: Some(0.0), ..HealthAlarmConfig::default() }.validate().is_err());
+        assert!(HealthAlarmConfig { webhook_url: Some("ftp://alerts".to_string()), ..HealthAlarmConfig::default() }.validate().is_err());
+        assert!(HealthAlarmConfig { webhook_url: Some("https://alerts.example/hook".to_string()), ..HealthAlarmConfig::default() }.validate().is_ok());
+    }
+}
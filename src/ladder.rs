@@ -0,0 +1,52 @@
+//! Price ladder order entry
+//!
+//! A DOM or ladder UI works in single clicks on price rows: join the bid or
+//! ask at a row, flatten the position, pull everything working at a row.
+//! Each [`LadderAction`] stands for what would otherwise take the frontend
+//! several round trips (look up its orders, cancel them one by one, size a
+//! closing order from its position), and the simulator applies it in one
+//! call so no step runs in between. Ladder orders are manual orders, but
+//! clicks only pull the orders the ladder placed, and the position is that
+//! of the ladder's own fills; orders a trading session placed are its own.
+
+use serde::{Deserialize, Serialize};
+use crate::types::{OrderId, Price, Qty, Side, Trade};
+
+/// One click on the ladder
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum LadderAction {
+    /// Rest a buy on the bid side, or a sell on the ask side, at a row
+    Join {
+        side: Side,
+        qty: Qty,
+        /// Row to join, as ticks, a decimal string or a scaled object; the
+        /// side's best price when omitted
+        #[serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            deserialize_with = "crate::price_format::deserialize_optional_price"
+        )]
+        price: Option<Price>,
+    },
+    /// Pull every working ladder order, then close the position at market
+    Flatten,
+    /// Pull every working ladder order at a row
+    CancelAtPrice {
+        #[serde(deserialize_with = "crate::price_format::deserialize_price")]
+        price: Price,
+    },
+}
+
+/// What a click did
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LadderOutcome {
+    /// Order placed by the click, if any
+    pub order_id: Option<OrderId>,
+    /// Trades of the order placed
+    pub trades: Vec<Trade>,
+    /// Orders pulled
+    pub cancelled: Vec<OrderId>,
+    /// Ladder position after the click
+    pub position: i64,
+}
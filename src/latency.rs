@@ -0,0 +1,145 @@
+//! Strategy latency accounting
+//!
+//! With `latency.enabled`, an order a configured agent decides on reaches the
+//! book only after `decision_ns` of decision time plus the network model's
+//! latency. The simulator holds it in flight until then, while the rest of
+//! the market (other agents, or the replayed feed in hybrid mode) keeps
+//! moving the book. When it arrives, its immediate fills are compared with
+//! what it would have filled against the book the agent saw when it decided.
+//! That difference, the latency cost, shows how much a strategy's results
+//! depend on the latency assumed.
+
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::cost_model::ExecutionCost;
+use crate::types::{Qty, Side, Trade};
+
+/// Modeled latency of strategy actions
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct LatencyConfig {
+    /// Delay agent orders and account for what the delay cost
+    pub enabled: bool,
+    /// Time an agent takes to decide, added to the network latency
+    /// (nanoseconds)
+    pub decision_ns: u64,
+}
+
+impl Default for LatencyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            decision_ns: 50_000, // 50 microseconds
+        }
+    }
+}
+
+impl LatencyConfig {
+    /// Check the latency settings
+    pub fn validate(&self) -> Result<(), String> {
+        if self.decision_ns > 60_000_000_000 {
+            return Err("Decision latency must be at most 60 seconds".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Latency and its cost over an agent's actions
+///
+/// Only immediate fills are compared; fills an order gets later while
+/// resting are not attributed to latency.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LatencyCost {
+    /// Orders that reached the book
+    pub actions: u64,
+    /// Decision plus network latency summed over the actions (nanoseconds)
+    pub total_latency_ns: u128,
+    /// Quantity the orders filled on arrival
+    pub filled_qty: Qty,
+    /// Quantity they would have filled against the book they were decided on
+    pub zero_latency_qty: Qty,
+    /// How much worse the arrival fills were priced than the zero-latency
+    /// ones, over the quantity both filled (ticks times quantity)
+    pub price_cost_ticks: f64,
+}
+
+impl LatencyCost {
+    /// Account for one action that reached the book after `latency_ns`
+    pub fn record(&mut self, side: Side, latency_ns: u64, zero_latency: &ExecutionCost, trades: &[Trade]) {
+        self.actions += 1;
+        self.total_latency_ns += latency_ns as u128;
+        let filled: Qty = trades.iter().map(|trade| trade.qty).sum();
+        self.filled_qty += filled;
+        self.zero_latency_qty += zero_latency.filled_qty;
+
+        let common = filled.min(zero_latency.filled_qty);
+        if let (true, Some(zero_latency_avg)) = (common > 0, zero_latency.avg_price) {
+            let notional: u128 = trades.iter().map(|trade| trade.price as u128 * trade.qty as u128).sum();
+            let avg = notional as f64 / filled as f64;
+            let worse = match side {
+                Side::Buy => avg - zero_latency_avg,
+                Side::Sell => zero_latency_avg - avg,
+            };
+            self.price_cost_ticks += worse * common as f64;
+        }
+    }
+
+    /// Add another agent's figures
+    pub fn merge(&mut self, other: &LatencyCost) {
+        self.actions += other.actions;
+        self.total_latency_ns += other.total_latency_ns;
+        self.filled_qty += other.filled_qty;
+        self.zero_latency_qty += other.zero_latency_qty;
+        self.price_cost_ticks += other.price_cost_ticks;
+    }
+
+    /// Average latency of an action (nanoseconds)
+    pub fn avg_latency_ns(&self) -> f64 {
+        if self.actions == 0 {
+            0.0
+        } else {
+            self.total_latency_ns as f64 / self.actions as f64
+        }
+    }
+
+    /// Quantity that would have filled at zero latency but did not; negative
+    /// when latency let more fill
+    pub fn missed_qty(&self) -> i64 {
+        self.zero_latency_qty as i64 - self.filled_qty as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cost_model::CostModel;
+    use crate::engine::{OrderBook, OrderBookEngine};
+    use crate::queue_fifo::FifoLevel;
+    use crate::types::Order;
+
+    #[test]
+    fn test_latency_cost_against_decision_book() {
+        let mut book = OrderBook::<FifoLevel>::new();
+        let ts = crate::time::now_ns();
+        book.place(Order::new_limit(1, Side::Sell, 10, 100_0000, ts)).unwrap();
+        book.place(Order::new_limit(2, Side::Sell, 10, 100_0200, ts)).unwrap();
+        let zero_latency = CostModel::from_snapshot(&book.snapshot()).expected_fill(Side::Buy, 100_0200, 15);
+        assert_eq!(zero_latency.filled_qty, 15);
+
+        // Someone else takes the best ask while the order is in flight
+        book.place(Order::new_market(3, Side::Buy, 10, ts)).unwrap();
+        let trades = book.place(Order::new_limit(4, Side::Buy, 15, 100_0200, ts)).unwrap();
+
+        let mut cost = LatencyCost::default();
+        cost.record(Side::Buy, 150_000, &zero_latency, &trades);
+        assert_eq!((cost.filled_qty, cost.zero_latency_qty, cost.missed_qty()), (10, 15, 5));
+        // The arrival paid 100.02 on the 10 it got, against a zero-latency average
+        let zero_latency_avg = (10.0 * 100_0000.0 + 5.0 * 100_0200.0) / 15.0;
+        assert!((cost.price_cost_ticks - 10.0 * (100_0200.0 - zero_latency_avg)).abs() < 1e-6);
+
+        let mut total = LatencyCost::default();
+        total.merge(&cost);
+        total.merge(&cost);
+        assert_eq!(total.actions, 2);
+        assert_eq!(total.avg_latency_ns(), 150_000.0);
+    }
+}
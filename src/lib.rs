@@ -4,50 +4,182 @@ pub mod error;
 pub mod logging;
 pub mod queue;
 pub mod queue_fifo;
+pub mod queue_priority;
+pub mod queue_compare;
+pub mod plugin;
 pub mod engine;
 pub mod data;
+pub mod catalog;
+pub mod analytics;
+pub mod spread_history;
+pub mod book_shape;
+pub mod order_age;
+pub mod cost_model;
+pub mod agents;
+pub mod features;
+pub mod depth_tensor;
+pub mod generator;
+pub mod anonymize;
+pub mod flow_transform;
+pub mod margin;
+pub mod funding;
+pub mod obligations;
+pub mod settlement;
+pub mod price_bands;
+pub mod paper;
+pub mod ladder;
+pub mod velocity;
+pub mod gateway;
+pub mod latency;
+pub mod warmup;
+pub mod throttle;
+pub mod auction;
+pub mod shadow;
+pub mod ab_shadow;
+pub mod report;
+pub mod stress;
+pub mod adversary;
+pub mod debugger;
+pub mod restore;
+pub mod clock_isolation;
+pub mod order_refs;
 pub mod sim;
+pub mod sim_thread;
+pub mod feed;
+pub mod binary;
+pub mod playback;
+pub mod bookmarks;
+pub mod segments;
+pub mod publish;
+pub mod alerts;
+pub mod redis_bridge;
+pub mod relay;
+pub mod activity;
+pub mod tape;
+pub mod masking;
+pub mod entitlements;
+pub mod degradation;
+pub mod tenants;
+pub mod price_format;
+pub mod mbo;
+pub mod protocol;
+pub mod client;
+pub mod conformance;
+pub mod scenario;
 pub mod server;
 pub mod config;
 pub mod metrics;
+pub mod metrics_store;
+pub mod health_alarms;
+pub mod webhooks;
+pub mod verify;
 pub mod memory;
 
 // Re-export core types for convenience
-pub use types::{Order, OrderId, OrderType, Price, Qty, Side, Trade};
+pub use types::{Order, OrderId, OrderType, Price, Qty, Side, Trade, ExecutionSummary, MakerFill, LotSize, OddLotPolicy, Liquidity, RolePnl, ContractSpec, Payoff};
 
-// Re-export price utilities
-pub use types::price_utils;
+// Re-export price and order id utilities
+pub use types::{order_ids, price_utils};
 
 // Re-export error types
 pub use error::{EngineError, EngineResult, ErrorSeverity};
 
 // Re-export time utilities
-pub use time::{now_ns, ms_to_ns, ns_to_ms, ns_to_secs, secs_to_ns, elapsed_ns, format_ns};
+pub use time::{now_ns, wall_clock_isolated, WallClockIsolation, ms_to_ns, ns_to_ms, ns_to_secs, secs_to_ns, elapsed_ns, format_ns, date_to_ns};
 
 // Re-export logging functions
 pub use logging::{init_logging, init_test_logging, log_engine_error, log_order_operation, log_trade};
 
 // Re-export queue discipline trait and implementations
-pub use queue::QueueDiscipline;
+pub use queue::{MatchFilter, QueueDiscipline};
 pub use queue_fifo::FifoLevel;
+pub use queue_priority::PriorityFeeLevel;
+pub use queue_compare::{DisciplineComparison, DisciplineReplay, DisciplineStats, SizeBucketStats, replay_discipline};
+pub use plugin::{AgentConstructor, DisciplineRegistry, DynLevel, LevelFactory, PluginError, Plugins};
 
 // Re-export engine types and traits
-pub use engine::{OrderBookEngine, OrderBook, DepthSnapshot, BookLevelPoint};
+pub use engine::{OrderBookEngine, OrderBook, DepthSnapshot, BboUpdate, BookLevelPoint, SideTotals, CommandEngine, EngineCommand, EngineEvent, DepthLimit, DepthPolicy, OrderPreview, PrunedOrder, PruneReason, StpGroup, StpMode, OrderState, BookState, CrossedBookPolicy, CrossingCounters, EngineStats, EngineActivity, EngineStatsReport, StatsReader, TradeTimestamps, QuoteLeg, QuoteOutcome, QuoteOwner, TwoSidedQuote};
 
 // Re-export data ingestion types and traits
-pub use data::{DataSource, MarketEvent, MarketStatusType, DataError, DataResult, DataSourceMetadata};
+pub use data::{DataSource, MarketEvent, MarketStatusType, DataError, DataResult, DataSourceMetadata, DataFormatDetector, DataFormat, ErrorPolicy, ThinningDataSource, ThinningPolicy, MonotonicDataSource, OrderingPolicy, HeartbeatDataSource, CachedDataSource, BlockCacheConfig, BlockCacheStats, ChainedDataSource, ChainedFile, TimestampConfig, TimestampUnit, TimestampNormalizer, NormalizedDataSource, ExternalSorter, SortReport, JsonDataSource};
+pub use catalog::{DatasetCatalog, DatasetEntry};
+pub use generator::{DataGenerator, HftWorkload, HftStream, MarketProfile, OrderStreamGenerator, Workload, WorkloadOp};
+pub use anonymize::{AnonymizeConfig, AnonymizeReport, Anonymizer, anonymize_source};
+pub use flow_transform::{AppliedTransform, FlowTransform, FlowTransformConfig, FlowTransformDataSource, FlowTransformStats};
 
 // Re-export simulation types and traits
-pub use sim::{Simulator, NetModel, SimulationMode, MarketMakerConfig, OrderGenerationConfig};
+pub use sim::{Simulator, NetModel, PathLatency, SimulationMode, MarketMakerConfig, InventoryBand, BandStatus, OrderGenerationConfig, HedgeConfig, Hedger, ReplayStatus, HybridInteraction, FillAccounting, FillStats, MetricsPoint, Agent, LiquidityTtlConfig, SelfMatchConfig, BookImage, ImageOrder, MANUAL_QUOTE_OWNERS};
+pub use sim_thread::{Lane, LaneDepths, SimulatorHandle, StepOutput};
+pub use agents::{AgentSpec, AgentRegistry, AgentFactory, AgentError, AgentOrder, AgentQuote, MarketView, SyntheticAgent, MarketMakerAgent, TakerAgent};
+pub use paper::{PaperTrader, PaperOrder, PaperFill, PaperAccount, PaperSubmission};
+pub use ladder::{LadderAction, LadderOutcome};
+pub use margin::{MarginConfig, MarginStatus, MarginCall};
+pub use funding::{FundingConfig, FundingSchedule, FundingSettlement, FundingStats};
+pub use obligations::{ComplianceReport, Maker, MakerQuote, ObligationConfig, ObligationMonitor};
+pub use price_bands::{PriceBand, PriceBandConfig, PriceBandStats, PriceBands, ReferenceSource};
+pub use settlement::{Holdings, PendingSettlement, ReconciliationBreak, SettlementConfig, SettlementCycle, SettlementLedger, SettlementStats};
+pub use velocity::{VelocityConfig, VelocityCounters, NotionalThrottle};
+pub use gateway::{BusyConfig, Gateway, GatewayEvent, GatewayOutage, GatewayStats, OutagePolicy};
+pub use latency::{LatencyConfig, LatencyCost};
+pub use warmup::{WarmupConfig, WarmupController, WarmupBoundary};
+pub use throttle::{FlowThrottle, ThrottleAdjustment, ThrottleConfig};
+pub use order_refs::{UnmatchedOrderPolicy, UnmatchedOrderReport, UnmatchedOrders};
+pub use auction::{AuctionConfig, AuctionImbalance, PreOpenQueue};
+pub use shadow::{ShadowBook, ShadowConfig};
+pub use ab_shadow::{AbReport, AbShadow, AbShadowConfig, AbDivergence, AbDivergenceKind, AbTotals, LevelDifference};
+pub use report::{RunReport, ReportFormat};
+pub use stress::{StressConfig, StressReport, LatencyHistogram};
+pub use adversary::{AdversaryAgent, AdversaryConfig, AdversaryParams, AdversaryPattern, AdversaryReport, Component, Degradation, StageReport, ADVERSARY_KIND};
+pub use debugger::{bisect, journal_replay, DebugStep, Divergence, EngineDebugger, EngineSettings, JournalingEngine};
+pub use restore::{even_checkpoints, verify_restores, RestoreCheck, RestoreReport};
+pub use clock_isolation::IsolatedEngine;
+pub use analytics::{AnalyticsConfig, VolumeProfile, VolumeProfiles, VolumeBucket, VolumeProfileSummary, SessionProfile, SignedVolume, TradeClassifier, OddLotVolume};
+pub use spread_history::{SpreadBar, SpreadHistory, SpreadSeries, SpreadTier};
+pub use book_shape::{BookShape, Concentration, SideShape};
+pub use order_age::{LevelAge, OrderAges, SideAges};
+pub use cost_model::{CostModel, ExecutionCost};
+pub use features::{FeatureExportConfig, FeatureExporter, FeatureFormat, FeatureSampler, FeatureVector, FeatureError, FeatureResult};
+pub use depth_tensor::{DepthTensor, DepthTensorConfig, DepthTensorRecorder, DEPTH_TENSOR_MAGIC};
+
+pub use feed::{FeedMessage, FeedFormat, FeedSequencer, DepthUpdate, LevelUpdate, ResyncRequest};
+pub use binary::{BinaryDepthStream, BinaryFrame};
+pub use playback::{Recording, RecordingConfig, RecordingInfo, SessionRecorder};
+pub use bookmarks::{Bookmark, BookmarkConfig, BookmarkError, BookmarkStore};
+pub use segments::{read_segments, CorruptTail, SegmentIndex, SegmentInfo, SegmentRead, SegmentWriter};
+pub use publish::{FeedPublisher, FeedSink, PublisherBackend, PublisherConfig, PublishError, PublishResult};
+pub use alerts::{Alert, AlertCondition, AlertError, AlertId, AlertSubscription, PriceAlerts};
+pub use redis_bridge::{RedisBridge, RedisBridgeConfig};
+pub use relay::{FeedLog, FrameAction, RelayConfig, RelayCursor, SequencedSnapshot};
+pub use activity::{Activity, ActivityConfig, ActivityKind, ActivityLog};
+pub use tape::{BlockTradeConfig, TapeFilter, TapeStats};
+pub use masking::{DepthMask, MaskingConfig};
+pub use entitlements::{EntitlementConfig, EntitlementTier};
+pub use degradation::{DegradationConfig, DegradationChange, DegradationStatus, StepBudget};
+pub use tenants::{Tenant, TenantConfig, TenantQuota};
+pub use price_format::{parse_price, PriceFormat, PricePrecision, Rounding, PRICE_EXPONENT};
+pub use mbo::{OrderFeed, OrderFeedEvent, OrderTracker, OrderUpdate, OrderUpdates};
+
+// Re-export protocol and client types
+pub use protocol::{
+    ApiError, ApiResult, Capability, ClientCommand, ClientRequest, CommandAck, ErrorCode, Handshake, PlaybackEvent, PlaybackFrame, RefusedLeg, ReportKind, ServerMessage, ServerReply, SessionReport,
+    MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+};
+pub use client::{OrderBookClient, BookBuilder, BookBuildError, ClientError, ClientResult};
+pub use conformance::{CheckResult, CheckStatus, ConformanceConfig, ConformanceReport};
+pub use scenario::{Scenario, ScenarioCheck, ScenarioReport, SCENARIOS};
 
 // Re-export server types and functions
-pub use server::{AppState, start_server, create_router, start_simulation_loop};
+pub use server::{AppState, ServerDiscipline, start_server, start_server_with_config, start_server_with_plugins, start_server_with_tenants, create_router, start_simulation_loop, start_relay_loop, ConnectionLimits, ConnectionLimiter, TradingSession};
 
 // Re-export configuration types
-pub use config::{Config, ServerConfig, SimulationConfig, DataSourceConfig, LoggingConfig, ConfigError};
+pub use config::{Config, ServerConfig, TlsConfig, SimulationConfig, BroadcastConfig, DataSourceConfig, LoggingConfig, LogMode, ConfigError};
 
 // Re-export metrics types
-pub use metrics::{PerformanceMetrics, PerformanceSnapshot, PerformanceMonitor, init_metrics_exporter};
+pub use metrics::{BroadcastMetrics, BroadcastSummary, EventStamp, Percentiles, PerformanceMetrics, PerformanceSnapshot, PerformanceMonitor, PipelineMetrics, PipelineSummary, PipelineWatermark, PhaseSummary, ProfileMetrics, ProfileSummary, StepPhase, StepProfile, init_metrics_exporter};
+pub use metrics_store::{MetricsStore, MetricsStoreConfig, MetricsSample, MarketSample, RunSummary, StoreError, StoreResult};
+pub use health_alarms::{HealthAlarmConfig, HealthAlarms, HealthAlert, HealthSample, AlarmRule, AlarmState, AlarmWebhook};
+pub use webhooks::{WebhookConfig, WebhookEndpoint, WebhookEvent, WebhookEventKind, WebhookPayload, Webhooks};
+pub use verify::{Baseline, Mismatch, RunOutcome};
 
 // Re-export memory management types
-pub use memory::{CircularBuffer, OrderPool, StringInterner, MemoryTracker};
+pub use memory::{CircularBuffer, OrderPool, StringInterner, MemoryTracker, MemoryConfig, MemoryPressure, EvictionPolicy, HistoryUsage};
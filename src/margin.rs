@@ -0,0 +1,226 @@
+//! Margin and buying-power rules for trading accounts
+//!
+//! Accounts start with a cash balance and may hold positions worth up to
+//! their equity divided by the initial margin rate. Orders that would take
+//! them past that are refused before they trade, and an account whose equity
+//! falls below the maintenance requirement on its open positions is put on
+//! margin call until it recovers.
+//!
+//! Positions are valued through the traded [`ContractSpec`], so for inverse
+//! contracts cash, equity and requirements are all in base units.
+
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::error::{EngineError, EngineResult};
+use crate::types::{ContractSpec, Price, Qty, Side};
+
+/// Margin configuration shared by every account
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct MarginConfig {
+    /// Enforce buying power and issue margin calls
+    pub enabled: bool,
+    /// Cash each new account starts with (in ticks, or base units for
+    /// inverse contracts)
+    pub starting_cash: i64,
+    /// Fraction of position value that equity must cover to open it
+    pub initial_margin: f64,
+    /// Fraction of position value that equity must keep covering
+    pub maintenance_margin: f64,
+}
+
+impl Default for MarginConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            starting_cash: 1_000_000_000, // $100,000 in ticks
+            initial_margin: 0.5,          // 2x leverage
+            maintenance_margin: 0.25,
+        }
+    }
+}
+
+impl MarginConfig {
+    /// Check the configured rates
+    pub fn validate(&self) -> Result<(), String> {
+        if self.starting_cash < 0 {
+            return Err("Starting cash cannot be negative".to_string());
+        }
+        if self.initial_margin <= 0.0 || self.initial_margin > 1.0 {
+            return Err("Initial margin must be in (0.0, 1.0]".to_string());
+        }
+        if self.maintenance_margin < 0.0 || self.maintenance_margin > self.initial_margin {
+            return Err("Maintenance margin must be between 0.0 and the initial margin".to_string());
+        }
+        Ok(())
+    }
+
+    /// Margin figures for a position and cash balance at a mark price
+    ///
+    /// Figures past the i64 range saturate, which leaves an overflowing
+    /// position short of margin rather than wrapping into a surplus.
+    pub fn status(&self, contract: &ContractSpec, position: i64, cash: i64, mark: Price) -> MarginStatus {
+        let equity = cash.saturating_add(contract.saturating_value_of(position, mark));
+        let exposure = contract.notional(position.unsigned_abs(), mark).unwrap_or(i64::MAX);
+        let initial_requirement = (exposure as f64 * self.initial_margin).ceil() as i64;
+        let maintenance_requirement = (exposure as f64 * self.maintenance_margin).ceil() as i64;
+        MarginStatus {
+            equity,
+            exposure,
+            initial_requirement,
+            maintenance_requirement,
+            buying_power: (equity.saturating_sub(initial_requirement) as f64 / self.initial_margin).max(0.0) as i64,
+            margin_call: equity < maintenance_requirement,
+        }
+    }
+
+    /// Refuse an order whose worst case would leave the account short of
+    /// initial margin
+    ///
+    /// `position` and `cash` should already assume the account's other open
+    /// orders fill, since they could fill alongside this one.
+    #[allow(clippy::too_many_arguments)]
+    pub fn check_order(
+        &self,
+        contract: &ContractSpec,
+        position: i64,
+        cash: i64,
+        mark: Price,
+        side: Side,
+        qty: Qty,
+        price: Price,
+    ) -> EngineResult<()> {
+        let signed_qty = match side {
+            Side::Buy => qty as i64,
+            Side::Sell => -(qty as i64),
+        };
+        let overflow = EngineError::ValueOverflow { qty, price };
+        let projected_position = position.checked_add(signed_qty).ok_or(overflow.clone())?;
+        // Cash moves at the order price, the position is valued at the mark
+        let projected_cash = cash.checked_sub(contract.value_of(signed_qty, price)?).ok_or(overflow)?;
+        let projected = self.status(contract, projected_position, projected_cash, mark);
+
+        // Orders that only shrink the position are always allowed
+        if projected_position.unsigned_abs() <= position.unsigned_abs() && projected_position.signum() * position.signum() >= 0 {
+            return Ok(());
+        }
+        if projected.equity < projected.initial_requirement {
+            return Err(EngineError::InsufficientMargin {
+                required: projected.initial_requirement,
+                equity: projected.equity,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Margin figures for one account
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MarginStatus {
+    /// Cash plus positions at the mark (in ticks)
+    pub equity: i64,
+    /// Absolute value of positions at the mark (in ticks)
+    pub exposure: i64,
+    /// Equity needed to open the current positions
+    pub initial_requirement: i64,
+    /// Equity needed to keep the current positions
+    pub maintenance_requirement: i64,
+    /// Additional position value the account can open
+    pub buying_power: i64,
+    /// Equity is below the maintenance requirement
+    pub margin_call: bool,
+}
+
+/// An account's equity dropping below its maintenance requirement
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MarginCall {
+    pub account: String,
+    pub equity: i64,
+    pub maintenance_requirement: i64,
+    /// Mark price the account was valued at
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub mark: Price,
+    pub ts: u128,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> MarginConfig {
+        MarginConfig {
+            enabled: true,
+            starting_cash: 1_000_000,
+            initial_margin: 0.5,
+            maintenance_margin: 0.25,
+        }
+    }
+
+    #[test]
+    fn test_status_and_buying_power() {
+        let config = config();
+        let linear = ContractSpec::default();
+        assert!(config.validate().is_ok());
+
+        // Long 100 at 10000 bought with cash
+        let status = config.status(&linear, 100, 0, 10000);
+        assert_eq!(status.equity, 1_000_000);
+        assert_eq!(status.exposure, 1_000_000);
+        assert_eq!(status.initial_requirement, 500_000);
+        assert_eq!(status.buying_power, 1_000_000);
+        assert!(!status.margin_call);
+
+        // Fully levered long after the price halves
+        let status = config.status(&linear, 200, -1_000_000, 6000);
+        assert_eq!(status.equity, 200_000);
+        assert_eq!(status.maintenance_requirement, 300_000);
+        assert!(status.margin_call);
+        assert_eq!(status.buying_power, 0);
+
+        // Deeply negative equity saturates instead of overflowing
+        let status = config.status(&linear, -100, i64::MIN, 10000);
+        assert_eq!(status.equity, i64::MIN);
+        assert!(status.margin_call);
+        assert_eq!(status.buying_power, 0);
+    }
+
+    #[test]
+    fn test_check_order_enforces_initial_margin() {
+        let config = config();
+        let linear = ContractSpec::default();
+
+        // 2x leverage: 200 at 10000 on 1,000,000 of equity is the limit
+        assert!(config.check_order(&linear, 0, 1_000_000, 10000, Side::Buy, 200, 10000).is_ok());
+        assert!(config.check_order(&linear, 0, 1_000_000, 10000, Side::Buy, 201, 10000).is_err());
+        // With 150 already bid for, another 100 is too much
+        assert!(config.check_order(&linear, 150, -500_000, 10000, Side::Buy, 100, 10000).is_err());
+        // Shorts too
+        assert!(config.check_order(&linear, 0, 1_000_000, 10000, Side::Sell, 201, 10000).is_err());
+
+        // Reducing a position is allowed even when under water
+        assert!(config.check_order(&linear, 200, -1_000_000, 6000, Side::Sell, 100, 6000).is_ok());
+        // Flipping through zero is a new position and is checked
+        assert!(config.check_order(&linear, 200, -1_000_000, 6000, Side::Sell, 400, 6000).is_err());
+
+        let bad = MarginConfig { maintenance_margin: 0.6, ..config };
+        assert!(bad.validate().is_err());
+    }
+
+    #[test]
+    fn test_inverse_margin_in_base_units() {
+        let config = config();
+        // $1 contracts at $50,000 are worth 2,000 base units each
+        let inverse = ContractSpec::inverse(10_000);
+        let mark = 500_000_000;
+
+        // 1,000,000 base units of equity supports 1,000 contracts at 2x
+        assert!(config.check_order(&inverse, 0, 1_000_000, mark, Side::Buy, 1000, mark).is_ok());
+        assert!(config.check_order(&inverse, 0, 1_000_000, mark, Side::Buy, 1001, mark).is_err());
+
+        // The fully levered long falls under maintenance at $40,000: each
+        // contract is now worth 2,500 base units
+        let status = config.status(&inverse, 1000, 3_000_000, 400_000_000);
+        assert_eq!(status.exposure, 2_500_000);
+        assert_eq!(status.equity, 500_000);
+        assert!(status.margin_call);
+    }
+}
@@ -0,0 +1,179 @@
+//! Masked depth for differentiated data products
+//!
+//! Exchanges sell the same book at different levels of detail: top of book
+//! only, a few levels, or full depth with sizes rounded into bands. A
+//! [`DepthMask`] takes a snapshot to one of these products by capping the
+//! levels shown, rounding level quantities to bands and hiding how many
+//! orders make up each level. Any mask but the exact one also withholds
+//! market-by-order updates, which would show every order's size.
+//! [`MaskingConfig`] picks the mask for each outgoing channel, and for `/ws`
+//! clients that connect with a named role.
+
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::engine::{BookLevelPoint, DepthSnapshot, SideTotals};
+use crate::types::Qty;
+
+/// How much of the book's detail a snapshot shows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct DepthMask {
+    /// Show each level's quantity rounded to the nearest multiple of this
+    /// band, and at least one band
+    #[serde(default)]
+    pub qty_band: Option<Qty>,
+    /// Leave out the number of orders at each level and order ages
+    #[serde(default)]
+    pub hide_order_counts: bool,
+    /// Show at most this many levels a side
+    #[serde(default)]
+    pub max_levels: Option<usize>,
+}
+
+impl DepthMask {
+    /// Whether the mask shows the book as it is
+    pub fn is_exact(&self) -> bool {
+        self.qty_band.is_none_or(|band| band == 1) && !self.hide_order_counts && self.max_levels.is_none()
+    }
+
+    /// Check the band and level cap
+    pub fn validate(&self) -> Result<(), String> {
+        if self.qty_band == Some(0) {
+            return Err("Depth mask quantity band must be greater than 0".to_string());
+        }
+        if self.max_levels == Some(0) {
+            return Err("Depth mask level cap must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+
+    /// A level's quantity as the mask shows it
+    pub fn qty(&self, qty: Qty) -> Qty {
+        match self.qty_band {
+            Some(band) if band > 1 && qty > 0 => ((qty + band / 2) / band).max(1) * band,
+            _ => qty,
+        }
+    }
+
+    /// The snapshot as the mask shows it; side totals cover the levels shown
+    pub fn apply(&self, snapshot: &DepthSnapshot) -> DepthSnapshot {
+        let mut masked = snapshot.clone();
+        if self.is_exact() {
+            return masked;
+        }
+        masked.bids = self.levels(&snapshot.bids);
+        masked.asks = self.levels(&snapshot.asks);
+        masked.bid_totals = SideTotals::of(&masked.bids);
+        masked.ask_totals = SideTotals::of(&masked.asks);
+        if let (Some(max_levels), Some(ages)) = (self.max_levels, masked.order_ages.as_mut()) {
+            ages.bids.levels.truncate(max_levels);
+            ages.asks.levels.truncate(max_levels);
+        }
+        // Market-by-order updates carry every order's exact size, so no
+        // inexact mask passes them on
+        masked.order_seq = None;
+        masked.orders = None;
+        if self.hide_order_counts {
+            masked.order_ages = None;
+        }
+        masked
+    }
+
+    fn levels(&self, levels: &[BookLevelPoint]) -> Vec<BookLevelPoint> {
+        levels.iter()
+            .take(self.max_levels.unwrap_or(usize::MAX))
+            .map(|level| {
+                let qty = self.qty(level.qty);
+                BookLevelPoint {
+                    qty,
+                    orders: if self.hide_order_counts { 0 } else { level.orders },
+                    notional: level.price as u128 * qty as u128,
+                    ..*level
+                }
+            })
+            .collect()
+    }
+}
+
+/// Depth masks for each outgoing channel and client role
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct MaskingConfig {
+    /// Snapshots sent to `/ws` clients that name no role
+    #[serde(default)]
+    pub ws: DepthMask,
+    /// Sequenced snapshots sent to relays on `/feed`
+    #[serde(default)]
+    pub feed: DepthMask,
+    /// Depth updates the streaming publisher sends to its broker
+    #[serde(default)]
+    pub publisher: DepthMask,
+    /// Masks `/ws` clients choose by connecting with `?role=<name>`, in
+    /// place of `ws`
+    #[serde(default)]
+    pub roles: BTreeMap<String, DepthMask>,
+}
+
+impl MaskingConfig {
+    /// Mask for a `/ws` client with the given role, or `None` for an
+    /// unknown role
+    pub fn for_role(&self, role: Option<&str>) -> Option<DepthMask> {
+        match role {
+            Some(role) => self.roles.get(role).copied(),
+            None => Some(self.ws),
+        }
+    }
+
+    /// Check every mask
+    pub fn validate(&self) -> Result<(), String> {
+        self.ws.validate()?;
+        self.feed.validate()?;
+        self.publisher.validate()?;
+        for (role, mask) in &self.roles {
+            mask.validate().map_err(|e| format!("{} (role '{}')", e, role))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{OrderBook, OrderBookEngine};
+    use crate::queue_fifo::FifoLevel;
+    use crate::types::{Order, Side};
+
+    #[test]
+    fn test_mask_rounds_caps_and_hides() {
+        let mut book: OrderBook<FifoLevel> = OrderBook::new();
+        book.set_clock(0);
+        for (id, (side, qty, price)) in [(Side::Buy, 130, 99), (Side::Buy, 40, 99), (Side::Buy, 20, 98), (Side::Sell, 260, 101), (Side::Sell, 75, 102)].into_iter().enumerate() {
+            book.place(Order::new_limit(id as u64 + 1, side, qty, price, id as u128)).unwrap();
+        }
+        let snapshot = book.snapshot();
+        let exact = DepthMask::default().apply(&snapshot);
+        assert_eq!((exact.bids.len(), exact.bids[0].qty, exact.bids[0].orders), (2, 170, 2));
+
+        let mask = DepthMask { qty_band: Some(100), hide_order_counts: true, max_levels: Some(1) };
+        let masked = mask.apply(&snapshot);
+        assert_eq!(masked.bids.len(), 1);
+        assert_eq!((masked.bids[0].qty, masked.bids[0].orders, masked.bids[0].notional), (200, 0, 19_800));
+        assert_eq!(masked.asks[0].qty, 300);
+        assert_eq!((masked.bid_totals.levels, masked.bid_totals.qty, masked.bid_totals.orders), (1, 200, 0));
+        // A small level still shows a band
+        assert_eq!(mask.qty(20), 100);
+        assert_eq!(mask.qty(0), 0);
+    }
+
+    #[test]
+    fn test_roles_pick_their_mask() {
+        let top = DepthMask { max_levels: Some(1), ..DepthMask::default() };
+        let config = MaskingConfig { roles: BTreeMap::from([("retail".to_string(), top)]), ..MaskingConfig::default() };
+        assert_eq!(config.for_role(None), Some(DepthMask::default()));
+        assert_eq!(config.for_role(Some("retail")), Some(top));
+        assert_eq!(config.for_role(Some("unknown")), None);
+        assert!(config.validate().is_ok());
+
+        let zero = MaskingConfig { feed: DepthMask { qty_band: Some(0), ..DepthMask::default() }, ..MaskingConfig::default() };
+        assert!(zero.validate().is_err());
+    }
+}
@@ -0,0 +1,390 @@
+//! Market-by-order feed
+//!
+//! The simulator turns what the engine reports for each command it carries
+//! out into [`OrderUpdate`]s for the orders the command changed. Each one
+//! states an order's whole resting state rather than a change to it, so
+//! applying an update twice, or on top of a newer image of the book, leaves
+//! the same book. Updates also carry the priority fees an order paid and
+//! when it reached the book, which together give its place in the queue.
+//! The server merges the updates of the steps between two snapshot
+//! broadcasts in an [`OrderFeed`], numbers them, and sends them to `/ws`
+//! clients that negotiated [`crate::protocol::Capability::MarketByOrder`]
+//! ahead of the snapshot they lead to. That snapshot carries the same number as its `order_seq`. A
+//! client that joins late starts from an image of the whole book.
+//! [`crate::client::BookBuilder`] rebuilds the book from these messages.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use crate::engine::{EngineCommand, EngineEvent};
+use crate::error::EngineResult;
+use crate::types::{Order, OrderId, Price, Qty, Side, Trade};
+
+/// Resting state of one order; a quantity of zero means it left the book
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderUpdate {
+    pub order_id: OrderId,
+    pub side: Side,
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub price: Price,
+    pub qty: Qty,
+    /// When the order was placed
+    pub ts: u128,
+    /// Priority fees paid, which put it ahead of orders at its price that
+    /// paid less
+    #[serde(default)]
+    pub priority_fee: u64,
+    /// Place among the orders that reached the book, which ranks orders at
+    /// its price that paid the same fees
+    #[serde(default)]
+    pub arrival: u64,
+}
+
+impl OrderUpdate {
+    /// Whether the order left the book
+    pub fn is_removal(&self) -> bool {
+        self.qty == 0
+    }
+
+    /// Key that sorts the orders at one price into queue priority order
+    pub fn queue_key(&self) -> (Reverse<u64>, u64) {
+        (Reverse(self.priority_fee), self.arrival)
+    }
+}
+
+/// Kind of [`OrderUpdates`] message
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderFeedEvent {
+    #[default]
+    Orders,
+}
+
+/// Numbered order changes leading to the snapshot with the same `order_seq`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderUpdates {
+    #[serde(rename = "type", default)]
+    pub kind: OrderFeedEvent,
+    pub seq: u64,
+    /// Simulation time of the snapshot they lead to
+    pub ts: u128,
+    /// Every resting order, replacing whatever the client had
+    #[serde(default)]
+    pub reset: bool,
+    pub updates: Vec<OrderUpdate>,
+}
+
+/// Follows the engine's resting orders through the commands it carries out,
+/// collecting the updates of the orders each one changed
+#[derive(Debug, Default)]
+pub struct OrderTracker {
+    resting: HashMap<OrderId, OrderUpdate>,
+    next_arrival: u64,
+    updates: Vec<OrderUpdate>,
+}
+
+impl OrderTracker {
+    /// Follow a book from its resting orders, given in queue priority order
+    /// with the priority fees each paid; the first updates list them all
+    pub fn with_orders(orders: impl IntoIterator<Item = (Order, u64)>) -> Self {
+        let mut tracker = Self::default();
+        for (order, priority_fee) in orders {
+            if let Some(price) = order.price() {
+                tracker.rest(&order, price, order.qty, priority_fee);
+            }
+        }
+        tracker
+    }
+
+    /// Note what a command did to the book, given what the engine reported
+    /// for it; `resting_qty` tells what an order has resting, since a book
+    /// rule may have refused part of a placed order and a refused command
+    /// may have changed the book before it failed
+    pub fn observe(&mut self, command: &EngineCommand, outcome: &EngineResult<EngineEvent>, resting_qty: impl Fn(OrderId) -> Option<Qty>) {
+        match (command, outcome) {
+            (EngineCommand::Place(order), Ok(EngineEvent::Placed(trades))) => {
+                self.fill(trades);
+                if let (Some(price), Some(qty)) = (order.price(), resting_qty(order.id)) {
+                    self.rest(order, price, qty, 0);
+                }
+            }
+            // A refused order may have traded before it was stopped, and the
+            // error leaves its trades out
+            (EngineCommand::Place(order), Err(_)) => {
+                self.reconcile(|update| reaches(order, update), &resting_qty);
+                if let (Some(price), Some(qty)) = (order.price(), resting_qty(order.id)) {
+                    self.rest(order, price, qty, 0);
+                }
+            }
+            (EngineCommand::Cancel(order_id), Ok(EngineEvent::Cancelled(_))) => self.remove(*order_id),
+            (EngineCommand::Clear, Ok(_)) => {
+                let mut order_ids: Vec<OrderId> = self.resting.keys().copied().collect();
+                order_ids.sort_unstable();
+                for order_id in order_ids {
+                    self.remove(order_id);
+                }
+            }
+            (_, Ok(EngineEvent::Pruned(orders))) => {
+                for pruned in orders {
+                    self.remove(pruned.order_id);
+                }
+            }
+            (EngineCommand::PayPriorityFee { order_id, .. }, Ok(EngineEvent::PriorityFeePaid(fee))) if *fee > 0 => {
+                if let Some(update) = self.resting.get_mut(order_id) {
+                    update.priority_fee += fee;
+                    self.updates.push(*update);
+                }
+            }
+            (EngineCommand::Quote(quote), Ok(EngineEvent::Quoted(outcome))) => {
+                for (order_id, _) in &outcome.cancelled {
+                    self.remove(*order_id);
+                }
+                self.fill(&outcome.trades);
+                for leg in quote.orders().filter(|leg| outcome.resting.contains(&leg.id)) {
+                    if let (Some(price), Some(qty)) = (leg.price(), resting_qty(leg.id)) {
+                        self.rest(&leg, price, qty, 0);
+                    }
+                }
+            }
+            // Legs pulled before a failed cancel are gone
+            (EngineCommand::Quote(_), Err(_)) => self.reconcile(|_| true, &resting_qty),
+            _ => {}
+        }
+    }
+
+    /// Take the updates collected since the last call, in the order the
+    /// changes happened
+    pub fn take(&mut self) -> Vec<OrderUpdate> {
+        std::mem::take(&mut self.updates)
+    }
+
+    fn rest(&mut self, order: &Order, price: Price, qty: Qty, priority_fee: u64) {
+        let update = OrderUpdate { order_id: order.id, side: order.side, price, qty, ts: order.ts, priority_fee, arrival: self.next_arrival };
+        self.next_arrival += 1;
+        self.resting.insert(order.id, update);
+        self.updates.push(update);
+    }
+
+    /// Take the makers' fills off what they have resting
+    fn fill(&mut self, trades: &[Trade]) {
+        for trade in trades {
+            let Some(maker) = self.resting.get_mut(&trade.maker_id) else {
+                continue;
+            };
+            maker.qty = maker.qty.saturating_sub(trade.qty);
+            let update = *maker;
+            if update.is_removal() {
+                self.resting.remove(&trade.maker_id);
+            }
+            self.updates.push(update);
+        }
+    }
+
+    /// Bring the tracked orders `reached` picks out in line with what the
+    /// engine has resting, best price and first in line first
+    fn reconcile(&mut self, reached: impl Fn(&OrderUpdate) -> bool, resting_qty: &impl Fn(OrderId) -> Option<Qty>) {
+        let mut changed: Vec<OrderUpdate> = self.resting.values()
+            .filter(|update| reached(update))
+            .filter_map(|update| {
+                let qty = resting_qty(update.order_id).unwrap_or(0);
+                (qty != update.qty).then_some(OrderUpdate { qty, ..*update })
+            })
+            .collect();
+        changed.sort_unstable_by_key(|update| {
+            let distance = match update.side {
+                Side::Buy => Price::MAX - update.price,
+                Side::Sell => update.price,
+            };
+            (update.side == Side::Sell, distance, update.queue_key())
+        });
+        for update in changed {
+            match update.is_removal() {
+                true => self.resting.remove(&update.order_id),
+                false => self.resting.insert(update.order_id, update),
+            };
+            self.updates.push(update);
+        }
+    }
+
+    fn remove(&mut self, order_id: OrderId) {
+        if let Some(update) = self.resting.remove(&order_id) {
+            self.updates.push(OrderUpdate { qty: 0, ..update });
+        }
+    }
+}
+
+/// Merges step updates between broadcasts, numbers them for publication and
+/// keeps the book they add up to, for clients that need an image
+#[derive(Debug, Default)]
+pub struct OrderFeed {
+    seq: u64,
+    ts: u128,
+    published: HashMap<OrderId, OrderUpdate>,
+    pending: Vec<OrderUpdate>,
+    pending_index: HashMap<OrderId, usize>,
+}
+
+impl OrderFeed {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of the last published updates; 0 before any
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    /// Hold a step's updates until the next publication; a later update to
+    /// the same order replaces the earlier one
+    pub fn absorb(&mut self, updates: Vec<OrderUpdate>) {
+        for update in updates {
+            match self.pending_index.get(&update.order_id) {
+                Some(&index) => self.pending[index] = update,
+                None => {
+                    self.pending_index.insert(update.order_id, self.pending.len());
+                    self.pending.push(update);
+                }
+            }
+        }
+    }
+
+    /// Number the held updates for the snapshot at `ts`, or `None` when
+    /// nothing published has changed
+    pub fn publish(&mut self, ts: u128) -> Option<Arc<OrderUpdates>> {
+        self.pending_index.clear();
+        // Orders placed and gone between two broadcasts were never published
+        let updates: Vec<OrderUpdate> = std::mem::take(&mut self.pending).into_iter()
+            .filter(|update| !update.is_removal() || self.published.contains_key(&update.order_id))
+            .collect();
+        if updates.is_empty() {
+            return None;
+        }
+        for update in &updates {
+            match update.is_removal() {
+                true => self.published.remove(&update.order_id),
+                false => self.published.insert(update.order_id, *update),
+            };
+        }
+        self.seq += 1;
+        self.ts = ts;
+        Some(Arc::new(OrderUpdates { kind: OrderFeedEvent::Orders, seq: self.seq, ts, reset: false, updates }))
+    }
+
+    /// Every published resting order as of the last publication, each
+    /// level's orders in queue priority order
+    pub fn image(&self) -> OrderUpdates {
+        let mut updates: Vec<OrderUpdate> = self.published.values().copied().collect();
+        updates.sort_unstable_by_key(|update| (update.side == Side::Sell, update.price, update.queue_key()));
+        OrderUpdates { kind: OrderFeedEvent::Orders, seq: self.seq, ts: self.ts, reset: true, updates }
+    }
+}
+
+/// Whether a resting order is one `order` could trade against
+fn reaches(order: &Order, resting: &OrderUpdate) -> bool {
+    resting.side != order.side && order.price().is_none_or(|limit| match order.side {
+        Side::Buy => resting.price <= limit,
+        Side::Sell => resting.price >= limit,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::BookBuilder;
+    use crate::engine::{CommandEngine, OrderBook};
+    use crate::queue_fifo::FifoLevel;
+    use crate::queue_priority::PriorityFeeLevel;
+
+    /// Carry out a command on `book`, noting what it did in `tracker`
+    fn run<E: CommandEngine>(book: &mut E, tracker: &mut OrderTracker, command: EngineCommand) {
+        let outcome = book.execute(command.clone());
+        tracker.observe(&command, &outcome, |order_id| book.order_resting_qty(order_id));
+    }
+
+    fn limit(id: OrderId, side: Side, qty: Qty, price: Price) -> EngineCommand {
+        EngineCommand::Place(Order::new_limit(id, side, qty, price, id as u128))
+    }
+
+    #[test]
+    fn test_engine_events_merge_into_numbered_updates() {
+        let mut book = OrderBook::<FifoLevel>::new();
+        let mut tracker = OrderTracker::default();
+        let mut feed = OrderFeed::new();
+        run(&mut book, &mut tracker, EngineCommand::SetClock(0));
+
+        run(&mut book, &mut tracker, limit(1, Side::Buy, 10, 99));
+        run(&mut book, &mut tracker, limit(2, Side::Sell, 5, 101));
+        let step = tracker.take();
+        assert_eq!(step.len(), 2);
+        feed.absorb(step);
+        let first = feed.publish(1).unwrap();
+        assert_eq!((first.seq, first.reset, first.updates.len()), (1, false, 2));
+
+        // A fill, a cancel and an order that came and went before the broadcast
+        run(&mut book, &mut tracker, limit(4, Side::Sell, 6, 99));
+        run(&mut book, &mut tracker, limit(3, Side::Sell, 7, 102));
+        run(&mut book, &mut tracker, EngineCommand::Cancel(2));
+        feed.absorb(tracker.take());
+        run(&mut book, &mut tracker, EngineCommand::Cancel(3));
+        feed.absorb(tracker.take());
+        let second = feed.publish(2).unwrap();
+        assert_eq!(second.seq, 2);
+        assert_eq!(second.updates, vec![
+            OrderUpdate { order_id: 1, side: Side::Buy, price: 99, qty: 4, ts: 1, priority_fee: 0, arrival: 0 },
+            OrderUpdate { order_id: 2, side: Side::Sell, price: 101, qty: 0, ts: 2, priority_fee: 0, arrival: 1 },
+        ]);
+
+        // Nothing new keeps the number
+        feed.absorb(tracker.take());
+        assert!(feed.publish(3).is_none());
+        assert_eq!(feed.seq(), 2);
+
+        let image = feed.image();
+        assert_eq!((image.seq, image.ts, image.reset), (2, 2, true));
+        assert_eq!(image.updates, vec![OrderUpdate { order_id: 1, side: Side::Buy, price: 99, qty: 4, ts: 1, priority_fee: 0, arrival: 0 }]);
+    }
+
+    #[test]
+    fn test_refused_market_order_fills_are_tracked() {
+        let mut book = OrderBook::<FifoLevel>::new();
+        let mut tracker = OrderTracker::default();
+        run(&mut book, &mut tracker, EngineCommand::SetClock(0));
+        run(&mut book, &mut tracker, limit(1, Side::Sell, 10, 101));
+        run(&mut book, &mut tracker, limit(2, Side::Sell, 10, 102));
+        run(&mut book, &mut tracker, limit(3, Side::Buy, 10, 99));
+        tracker.take();
+
+        // The unfilled rest refuses the order after it swept the asks
+        let sweep = EngineCommand::Place(Order::new_market(4, Side::Buy, 25, 4));
+        run(&mut book, &mut tracker, sweep);
+        assert_eq!(tracker.take().iter().map(|update| (update.order_id, update.qty)).collect::<Vec<_>>(), vec![(1, 0), (2, 0)]);
+        assert!(tracker.resting.keys().eq([3].iter()));
+    }
+
+    #[test]
+    fn test_queues_follow_priority_fees() {
+        let mut book = OrderBook::<PriorityFeeLevel>::new();
+        let mut tracker = OrderTracker::default();
+        let mut feed = OrderFeed::new();
+        let mut builder = BookBuilder::new();
+        run(&mut book, &mut tracker, EngineCommand::SetClock(0));
+
+        for order_id in 1..=3 {
+            run(&mut book, &mut tracker, limit(order_id, Side::Buy, 10, 99));
+        }
+        feed.absorb(tracker.take());
+        builder.apply(&feed.publish(1).unwrap()).unwrap();
+        // Equal fees leave the earlier order ahead
+        for (ts, order_id) in [(2, 3), (3, 2)] {
+            run(&mut book, &mut tracker, EngineCommand::PayPriorityFee { order_id, fee: 5 });
+            feed.absorb(tracker.take());
+            builder.apply(&feed.publish(ts).unwrap()).unwrap();
+        }
+
+        let queue: Vec<OrderId> = book.open_orders().iter().map(|order| order.id).collect();
+        assert_eq!(queue, vec![2, 3, 1]);
+        assert_eq!(feed.image().updates.iter().map(|update| update.order_id).collect::<Vec<_>>(), queue);
+        assert_eq!(builder.queue(Side::Buy, 99).iter().map(|update| update.order_id).collect::<Vec<_>>(), queue);
+    }
+}
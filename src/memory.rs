@@ -1,34 +1,202 @@
-use std::collections::VecDeque;
-
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::hash::Hash;
+use std::mem::size_of;
+use std::ops::{Add, AddAssign};
 use std::sync::atomic::{AtomicUsize, Ordering};
 
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+
+/// What a full buffer gives up to make room for a new item
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EvictionPolicy {
+    /// Drop the oldest item
+    #[default]
+    DropOldest,
+    /// Drop every other item, halving the resolution but keeping the full span
+    Downsample,
+}
+
+/// Occupancy of a bounded history
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryUsage {
+    pub len: usize,
+    pub capacity: usize,
+    /// Items evicted to stay within the capacity
+    pub evicted: usize,
+}
+
+/// What compacting a structure reclaimed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Compaction {
+    /// Stale entries dropped
+    pub entries: usize,
+    /// Spare capacity released, estimated from the element size
+    pub bytes: usize,
+}
+
+impl Compaction {
+    /// Capacity released by shrinking a collection of `T` from `before` to
+    /// `after` slots
+    pub fn released<T>(before: usize, after: usize) -> Self {
+        Self { entries: 0, bytes: before.saturating_sub(after) * size_of::<T>() }
+    }
+
+    /// Count `entries` stale entries dropped as well
+    pub fn with_entries(mut self, entries: usize) -> Self {
+        self.entries += entries;
+        self
+    }
+}
+
+impl Add for Compaction {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self { entries: self.entries + other.entries, bytes: self.bytes + other.bytes }
+    }
+}
+
+impl AddAssign for Compaction {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+/// Release a deque's capacity beyond `budget` items, or beyond what it
+/// holds if that is more
+pub fn shrink_deque<T>(deque: &mut VecDeque<T>, budget: usize) -> Compaction {
+    let before = deque.capacity();
+    deque.shrink_to(budget.max(deque.len()));
+    Compaction::released::<T>(before, deque.capacity())
+}
+
+/// Release a vector's spare capacity
+pub fn shrink_vec<T>(vec: &mut Vec<T>) -> Compaction {
+    let before = vec.capacity();
+    vec.shrink_to_fit();
+    Compaction::released::<T>(before, vec.capacity())
+}
+
+/// Release a map's spare capacity
+pub fn shrink_map<K: Eq + Hash, V>(map: &mut HashMap<K, V>) -> Compaction {
+    let before = map.capacity();
+    map.shrink_to_fit();
+    Compaction::released::<(K, V)>(before, map.capacity())
+}
+
+/// A compaction pass over the server's state
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompactionReport {
+    /// Wall-clock time of the pass (milliseconds since the epoch)
+    pub time_ms: u64,
+    /// What each structure gave up, by name
+    pub structures: BTreeMap<String, Compaction>,
+    pub total: Compaction,
+    /// Resident memory before and after the pass
+    pub rss_before_bytes: usize,
+    pub rss_after_bytes: usize,
+}
+
+impl CompactionReport {
+    /// Add what one structure gave up
+    pub fn record(&mut self, name: &str, compaction: Compaction) {
+        self.total += compaction;
+        *self.structures.entry(name.to_string()).or_default() += compaction;
+    }
+}
+
 /// Memory-efficient circular buffer for time series data
 #[derive(Debug, Clone)]
 pub struct CircularBuffer<T> {
     data: VecDeque<T>,
     max_size: usize,
     total_added: usize,
+    evicted: usize,
+    policy: EvictionPolicy,
 }
 
 impl<T> CircularBuffer<T> {
     /// Create a new circular buffer with specified maximum size
     pub fn new(max_size: usize) -> Self {
+        Self::with_policy(max_size, EvictionPolicy::default())
+    }
+
+    /// Create a new circular buffer that evicts according to `policy`
+    pub fn with_policy(max_size: usize, policy: EvictionPolicy) -> Self {
         Self {
             data: VecDeque::with_capacity(max_size),
             max_size,
             total_added: 0,
+            evicted: 0,
+            policy,
         }
     }
 
-    /// Add an item to the buffer, removing oldest if at capacity
+    /// Add an item to the buffer, evicting per the policy if at capacity
     pub fn push(&mut self, item: T) {
         if self.data.len() >= self.max_size {
-            self.data.pop_front();
+            match self.policy {
+                EvictionPolicy::Downsample if self.max_size >= 2 => {
+                    // Keep the newest item and every other one before it
+                    let len = self.data.len();
+                    let mut index = 0;
+                    self.data.retain(|_| {
+                        index += 1;
+                        (len - index).is_multiple_of(2)
+                    });
+                    self.evicted += len - self.data.len();
+                }
+                _ => {
+                    if self.data.pop_front().is_some() {
+                        self.evicted += 1;
+                    }
+                }
+            }
+        }
+        if self.max_size == 0 {
+            self.evicted += 1;
+        } else {
+            self.data.push_back(item);
         }
-        self.data.push_back(item);
         self.total_added += 1;
     }
 
+    /// Get the eviction policy
+    pub fn policy(&self) -> EvictionPolicy {
+        self.policy
+    }
+
+    /// Change the eviction policy
+    pub fn set_policy(&mut self, policy: EvictionPolicy) {
+        self.policy = policy;
+    }
+
+    /// Change the maximum size, dropping the oldest items if it shrinks
+    pub fn set_capacity(&mut self, new_size: usize) {
+        self.max_size = new_size;
+        let excess = self.data.len().saturating_sub(new_size);
+        self.evict_oldest(excess);
+    }
+
+    /// Drop up to `count` of the oldest items, returning how many were dropped
+    pub fn evict_oldest(&mut self, count: usize) -> usize {
+        let count = count.min(self.data.len());
+        self.data.drain(..count);
+        self.evicted += count;
+        count
+    }
+
+    /// Length, capacity and eviction count
+    pub fn usage(&self) -> HistoryUsage {
+        HistoryUsage {
+            len: self.data.len(),
+            capacity: self.max_size,
+            evicted: self.evicted,
+        }
+    }
+
     /// Get the current size of the buffer
     pub fn len(&self) -> usize {
         self.data.len()
@@ -77,18 +245,110 @@ impl<T> CircularBuffer<T> {
         self.data.iter().cloned().collect()
     }
 
+    /// Release capacity beyond the buffer's maximum size, left behind when
+    /// the size was lowered
+    pub fn compact(&mut self) -> Compaction {
+        shrink_deque(&mut self.data, self.max_size)
+    }
+
     /// Shrink the buffer to a new smaller size
     pub fn shrink_to(&mut self, new_size: usize) {
         if new_size < self.max_size {
-            self.max_size = new_size;
-            while self.data.len() > new_size {
-                self.data.pop_front();
-            }
+            self.set_capacity(new_size);
             self.data.shrink_to_fit();
         }
     }
 }
 
+/// Budgets for the in-memory histories and the process-wide memory limit
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct MemoryConfig {
+    /// Trades kept on the simulator's tape
+    pub trade_tape: usize,
+    /// Inventory and PnL points kept for reports
+    pub metrics_history: usize,
+    /// Paper fills and margin calls kept once their orders close
+    pub closed_orders: usize,
+    /// What a full history gives up to make room
+    pub eviction: EvictionPolicy,
+    /// Resident memory limit in megabytes; unset disables the check
+    pub rss_limit_mb: Option<u64>,
+    /// Fraction of the limit at which to warn and report degraded health
+    pub warning_threshold: f64,
+    /// How often histories and indexes are compacted to their budgets;
+    /// 0 turns compaction off
+    #[serde(default = "default_compaction_interval_ms")]
+    pub compaction_interval_ms: u64,
+}
+
+fn default_compaction_interval_ms() -> u64 {
+    60_000
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            trade_tape: 1_000,
+            metrics_history: 400,
+            closed_orders: 10_000,
+            eviction: EvictionPolicy::DropOldest,
+            rss_limit_mb: None,
+            warning_threshold: 0.8,
+            compaction_interval_ms: default_compaction_interval_ms(),
+        }
+    }
+}
+
+impl MemoryConfig {
+    /// Check the budgets and limit
+    pub fn validate(&self) -> Result<(), String> {
+        if self.trade_tape == 0 || self.metrics_history == 0 || self.closed_orders == 0 {
+            return Err("Memory history budgets must be greater than 0".to_string());
+        }
+        if self.rss_limit_mb == Some(0) {
+            return Err("Memory RSS limit must be greater than 0".to_string());
+        }
+        if self.warning_threshold <= 0.0 || self.warning_threshold > 1.0 {
+            return Err("Memory warning threshold must be between 0.0 and 1.0".to_string());
+        }
+        Ok(())
+    }
+
+    /// How often to compact, if at all
+    pub fn compaction_interval(&self) -> Option<std::time::Duration> {
+        (self.compaction_interval_ms > 0).then(|| std::time::Duration::from_millis(self.compaction_interval_ms))
+    }
+
+    /// The RSS limit in bytes
+    pub fn rss_limit_bytes(&self) -> Option<usize> {
+        self.rss_limit_mb.map(|mb| mb as usize * 1024 * 1024)
+    }
+
+    /// Classify a resident memory reading against the limit
+    ///
+    /// A reading of 0 means memory could not be measured and is never
+    /// reported as pressure.
+    pub fn pressure(&self, rss_bytes: usize) -> MemoryPressure {
+        match self.rss_limit_bytes() {
+            Some(limit) if rss_bytes > 0 && rss_bytes >= limit => MemoryPressure::Critical,
+            Some(limit) if rss_bytes > 0 && rss_bytes as f64 >= limit as f64 * self.warning_threshold => MemoryPressure::Warning,
+            _ => MemoryPressure::Normal,
+        }
+    }
+}
+
+/// How close the process is to its memory limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryPressure {
+    #[default]
+    Normal,
+    /// Past the warning threshold
+    Warning,
+    /// At or over the limit
+    Critical,
+}
+
 /// Memory pool for reusing order objects to reduce allocations
 pub struct OrderPool {
     available: VecDeque<crate::types::Order>,
@@ -384,6 +644,69 @@ mod tests {
         assert_eq!(buffer.to_vec(), vec![3, 4, 5]);
     }
 
+    #[test]
+    fn test_circular_buffer_eviction_policies() {
+        let mut buffer = CircularBuffer::with_policy(4, EvictionPolicy::Downsample);
+        for i in 1..=5 {
+            buffer.push(i);
+        }
+        // The full buffer halved itself, keeping the newest and every other one
+        assert_eq!(buffer.to_vec(), vec![2, 4, 5]);
+        assert_eq!(buffer.usage(), HistoryUsage { len: 3, capacity: 4, evicted: 2 });
+
+        buffer.set_policy(EvictionPolicy::DropOldest);
+        buffer.push(6);
+        buffer.push(7);
+        assert_eq!(buffer.to_vec(), vec![4, 5, 6, 7]);
+        assert_eq!(buffer.usage().evicted, 3);
+
+        assert_eq!(buffer.evict_oldest(3), 3);
+        assert_eq!(buffer.to_vec(), vec![7]);
+        buffer.set_capacity(10);
+        assert_eq!(buffer.capacity(), 10);
+        assert_eq!(buffer.usage().evicted, 6);
+    }
+
+    #[test]
+    fn test_compaction_releases_capacity_past_the_budget() {
+        let mut buffer = CircularBuffer::new(1000);
+        for i in 0..1000u64 {
+            buffer.push(i);
+        }
+        assert_eq!(buffer.compact(), Compaction::default());
+
+        buffer.set_capacity(10);
+        let compaction = buffer.compact();
+        assert_eq!(buffer.len(), 10);
+        assert!(compaction.bytes >= 900 * size_of::<u64>());
+
+        let mut report = CompactionReport::default();
+        report.record("trade_tape", compaction);
+        report.record("trade_tape", Compaction::default().with_entries(3));
+        assert_eq!(report.structures["trade_tape"], Compaction { entries: 3, bytes: compaction.bytes });
+        assert_eq!(report.total, report.structures["trade_tape"]);
+    }
+
+    #[test]
+    fn test_memory_pressure() {
+        let mut config = MemoryConfig::default();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.pressure(usize::MAX), MemoryPressure::Normal);
+
+        config.rss_limit_mb = Some(100);
+        let mb = 1024 * 1024;
+        assert_eq!(config.pressure(0), MemoryPressure::Normal);
+        assert_eq!(config.pressure(79 * mb), MemoryPressure::Normal);
+        assert_eq!(config.pressure(80 * mb), MemoryPressure::Warning);
+        assert_eq!(config.pressure(100 * mb), MemoryPressure::Critical);
+
+        config.warning_threshold = 1.5;
+        assert!(config.validate().is_err());
+        config.warning_threshold = 0.8;
+        config.rss_limit_mb = Some(0);
+        assert!(config.validate().is_err());
+    }
+
     #[test]
     fn test_order_pool() {
         let mut pool = OrderPool::new();
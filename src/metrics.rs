@@ -4,6 +4,7 @@ use std::sync::Arc;
 use serde::{Deserialize, Serialize};
 use metrics::{counter, gauge, histogram};
 use sysinfo::{System, SystemExt, CpuExt, ProcessExt};
+use crate::stress::LatencyHistogram;
 
 /// Performance metrics collector for the order book system
 #[derive(Debug, Clone)]
@@ -28,6 +29,8 @@ pub struct PerformanceMetrics {
     events_ingested: Arc<AtomicU64>,
     ingestion_errors: Arc<AtomicU64>,
     ingestion_rate: Arc<AtomicU64>,
+    records_skipped: Arc<AtomicU64>,
+    records_quarantined: Arc<AtomicU64>,
     
     /// System metrics
     cpu_usage_percent: Arc<AtomicU64>,
@@ -59,6 +62,8 @@ impl PerformanceMetrics {
             events_ingested: Arc::new(AtomicU64::new(0)),
             ingestion_errors: Arc::new(AtomicU64::new(0)),
             ingestion_rate: Arc::new(AtomicU64::new(0)),
+            records_skipped: Arc::new(AtomicU64::new(0)),
+            records_quarantined: Arc::new(AtomicU64::new(0)),
             cpu_usage_percent: Arc::new(AtomicU64::new(0)),
             start_time: Instant::now(),
         }
@@ -120,6 +125,17 @@ impl PerformanceMetrics {
         histogram!("data_ingestion_duration_ns", duration_ns as f64);
     }
 
+    /// Record a bad data record dropped by a skip or quarantine error policy
+    pub fn record_corrupt_record(&self, quarantined: bool) {
+        if quarantined {
+            self.records_quarantined.fetch_add(1, Ordering::Relaxed);
+            counter!("ingestion_records_quarantined_total", 1);
+        } else {
+            self.records_skipped.fetch_add(1, Ordering::Relaxed);
+            counter!("ingestion_records_skipped_total", 1);
+        }
+    }
+
     /// Update system metrics (CPU, memory)
     pub fn update_system_metrics(&self, system: &System) {
         // Update CPU usage
@@ -165,6 +181,8 @@ impl PerformanceMetrics {
             events_ingested: self.events_ingested.load(Ordering::Relaxed),
             ingestion_errors: self.ingestion_errors.load(Ordering::Relaxed),
             ingestion_rate: self.ingestion_rate.load(Ordering::Relaxed),
+            records_skipped: self.records_skipped.load(Ordering::Relaxed),
+            records_quarantined: self.records_quarantined.load(Ordering::Relaxed),
             cpu_usage_percent: self.cpu_usage_percent.load(Ordering::Relaxed),
             uptime_seconds: self.start_time.elapsed().as_secs(),
         }
@@ -183,12 +201,14 @@ impl PerformanceMetrics {
         self.events_ingested.store(0, Ordering::Relaxed);
         self.ingestion_errors.store(0, Ordering::Relaxed);
         self.ingestion_rate.store(0, Ordering::Relaxed);
+        self.records_skipped.store(0, Ordering::Relaxed);
+        self.records_quarantined.store(0, Ordering::Relaxed);
         self.cpu_usage_percent.store(0, Ordering::Relaxed);
     }
 }
 
 /// Snapshot of performance metrics at a point in time
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct PerformanceSnapshot {
     pub orders_processed: u64,
     pub orders_failed: u64,
@@ -201,6 +221,10 @@ pub struct PerformanceSnapshot {
     pub events_ingested: u64,
     pub ingestion_errors: u64,
     pub ingestion_rate: u64,
+    #[serde(default)]
+    pub records_skipped: u64,
+    #[serde(default)]
+    pub records_quarantined: u64,
     pub cpu_usage_percent: u64,
     pub uptime_seconds: u64,
 }
@@ -289,20 +313,358 @@ impl PerformanceMonitor {
     }
 }
 
+/// Cost of broadcasting snapshots to WebSocket clients
+///
+/// Each client gets its own serialization, since clients choose their price
+/// format, so serialization time and payload size are sampled per client
+/// along with the time to hand the message to its socket.
+#[derive(Debug, Clone, Default)]
+pub struct BroadcastMetrics {
+    serialization_ns: LatencyHistogram,
+    send_ns: LatencyHistogram,
+    /// Time spent sending, summed, so a mean can be taken over any interval
+    send_total_ns: u64,
+    payload_bytes: LatencyHistogram,
+    broadcasts: u64,
+    last_fanout: usize,
+    max_fanout: usize,
+}
+
+impl BroadcastMetrics {
+    /// Record a snapshot handed to `clients` connected clients
+    pub fn record_fanout(&mut self, clients: usize) {
+        self.broadcasts += 1;
+        self.last_fanout = clients;
+        self.max_fanout = self.max_fanout.max(clients);
+        gauge!("snapshot_fanout_clients", clients as f64);
+    }
+
+    /// Record serializing a snapshot for one client
+    pub fn record_serialization(&mut self, duration: Duration, bytes: usize) {
+        let duration_ns = duration.as_nanos() as u64;
+        self.serialization_ns.record(duration_ns);
+        self.payload_bytes.record(bytes as u64);
+        histogram!("snapshot_serialization_duration_ns", duration_ns as f64);
+        histogram!("snapshot_payload_bytes", bytes as f64);
+    }
+
+    /// Record sending a serialized snapshot to one client
+    pub fn record_send(&mut self, duration: Duration) {
+        let duration_ns = duration.as_nanos() as u64;
+        self.send_ns.record(duration_ns);
+        self.send_total_ns = self.send_total_ns.saturating_add(duration_ns);
+        histogram!("snapshot_send_duration_ns", duration_ns as f64);
+    }
+
+    /// Snapshots sent to individual clients and the total time spent sending them
+    pub fn send_totals(&self) -> (u64, u64) {
+        (self.send_ns.count(), self.send_total_ns)
+    }
+
+    /// Counts and percentiles since the server started
+    pub fn summary(&self) -> BroadcastSummary {
+        BroadcastSummary {
+            broadcasts: self.broadcasts,
+            last_fanout: self.last_fanout,
+            max_fanout: self.max_fanout,
+            client_sends: self.send_ns.count(),
+            serialization_ns: Percentiles::of(&self.serialization_ns),
+            send_ns: Percentiles::of(&self.send_ns),
+            payload_bytes: Percentiles::of(&self.payload_bytes),
+        }
+    }
+}
+
+/// Percentiles of a sampled value
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Percentiles {
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+impl Percentiles {
+    /// Percentiles of a histogram's samples
+    pub fn of(histogram: &LatencyHistogram) -> Self {
+        Self {
+            p50: histogram.percentile(0.5),
+            p90: histogram.percentile(0.9),
+            p99: histogram.percentile(0.99),
+            max: histogram.max(),
+        }
+    }
+}
+
+/// Snapshot broadcast costs as reported by `/health`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BroadcastSummary {
+    /// Snapshots broadcast
+    pub broadcasts: u64,
+    /// Clients the latest snapshot went to
+    pub last_fanout: usize,
+    /// Most clients any snapshot went to
+    pub max_fanout: usize,
+    /// Snapshots sent to individual clients
+    pub client_sends: u64,
+    /// Time to serialize a snapshot for one client
+    pub serialization_ns: Percentiles,
+    /// Time to send a serialized snapshot to one client
+    pub send_ns: Percentiles,
+    /// Size of a serialized snapshot
+    pub payload_bytes: Percentiles,
+}
+
+/// Wall-clock times one data source event reached the simulator and the engine
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventStamp {
+    /// Timestamp the event carries
+    pub event_ts: u128,
+    /// Taken from the data source (nanoseconds since the epoch)
+    pub ingest_ns: u128,
+    /// Applied to the engine (nanoseconds since the epoch)
+    pub match_ns: u128,
+}
+
+/// Pipeline timing of the data source events behind one published message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PipelineWatermark {
+    pub events: usize,
+    /// Earliest ingest among them (nanoseconds since the epoch)
+    pub first_ingest_ns: u128,
+    /// Latest match among them (nanoseconds since the epoch)
+    pub last_match_ns: u128,
+    /// When the message was handed to the broadcast (nanoseconds since the epoch)
+    pub publish_ns: u128,
+}
+
+impl PipelineWatermark {
+    /// Watermark of a message carrying the events stamped `stamps`, or `None`
+    /// when it carries none
+    pub fn of(stamps: &[EventStamp], publish_ns: u128) -> Option<Self> {
+        Some(Self {
+            events: stamps.len(),
+            first_ingest_ns: stamps.iter().map(|stamp| stamp.ingest_ns).min()?,
+            last_match_ns: stamps.iter().map(|stamp| stamp.match_ns).max()?,
+            publish_ns,
+        })
+    }
+
+    /// Longest any of the events took from ingest to publish
+    pub fn end_to_end_ns(&self) -> u128 {
+        self.publish_ns.saturating_sub(self.first_ingest_ns)
+    }
+}
+
+/// Latency of data source events from ingest through matching to publishing
+///
+/// Every event is sampled, so a message carrying many events weighs as
+/// many samples.
+#[derive(Debug, Clone, Default)]
+pub struct PipelineMetrics {
+    ingest_to_match_ns: LatencyHistogram,
+    match_to_publish_ns: LatencyHistogram,
+    end_to_end_ns: LatencyHistogram,
+    messages: u64,
+}
+
+impl PipelineMetrics {
+    /// Record the events of a message published at `publish_ns`
+    pub fn record(&mut self, stamps: &[EventStamp], publish_ns: u128) {
+        if stamps.is_empty() {
+            return;
+        }
+        self.messages += 1;
+        for stamp in stamps {
+            let stages = [
+                (&mut self.ingest_to_match_ns, stamp.ingest_ns, stamp.match_ns, "pipeline_ingest_to_match_ns"),
+                (&mut self.match_to_publish_ns, stamp.match_ns, publish_ns, "pipeline_match_to_publish_ns"),
+                (&mut self.end_to_end_ns, stamp.ingest_ns, publish_ns, "pipeline_end_to_end_ns"),
+            ];
+            for (histogram, from, to, name) in stages {
+                let ns = to.saturating_sub(from).min(u64::MAX as u128) as u64;
+                histogram.record(ns);
+                histogram!(name, ns as f64);
+            }
+        }
+    }
+
+    /// Counts and percentiles since the server started
+    pub fn summary(&self) -> PipelineSummary {
+        PipelineSummary {
+            events: self.end_to_end_ns.count(),
+            messages: self.messages,
+            ingest_to_match_ns: Percentiles::of(&self.ingest_to_match_ns),
+            match_to_publish_ns: Percentiles::of(&self.match_to_publish_ns),
+            end_to_end_ns: Percentiles::of(&self.end_to_end_ns),
+        }
+    }
+}
+
+/// Pipeline latency as reported by `/health`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PipelineSummary {
+    /// Data source events published
+    pub events: u64,
+    /// Messages that carried at least one of them
+    pub messages: u64,
+    /// From the data source to the engine
+    pub ingest_to_match_ns: Percentiles,
+    /// From the engine to the broadcast
+    pub match_to_publish_ns: Percentiles,
+    /// From the data source to the broadcast
+    pub end_to_end_ns: Percentiles,
+}
+
+/// Part of a simulation step that step profiling attributes time to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepPhase {
+    /// Producing the step's orders: agents, synthetic flow, data events
+    Generation,
+    /// Engine commands: placing, matching and cancelling orders
+    Matching,
+    /// Bookkeeping after the step's orders: spreads, fills, PnL, funding
+    /// and settlement
+    Metrics,
+    /// Taking the book's snapshot
+    Snapshot,
+    /// Exports, BBO updates and handing the snapshot to its subscribers
+    Broadcast,
+}
+
+impl StepPhase {
+    pub const ALL: [Self; 5] = [Self::Generation, Self::Matching, Self::Metrics, Self::Snapshot, Self::Broadcast];
+
+    /// Name used in metrics labels and logs
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Generation => "generation",
+            Self::Matching => "matching",
+            Self::Metrics => "metrics",
+            Self::Snapshot => "snapshot",
+            Self::Broadcast => "broadcast",
+        }
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+/// Time one simulation step spent in each phase
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StepProfile {
+    phase_ns: [u64; 5],
+}
+
+impl StepProfile {
+    /// Attribute `duration` to `phase`
+    pub fn add(&mut self, phase: StepPhase, duration: Duration) {
+        let ns = &mut self.phase_ns[phase.index()];
+        *ns = ns.saturating_add(duration.as_nanos().min(u64::MAX as u128) as u64);
+    }
+
+    /// Time spent in `phase` (nanoseconds)
+    pub fn get(&self, phase: StepPhase) -> u64 {
+        self.phase_ns[phase.index()]
+    }
+
+    /// Time spent in every phase together (nanoseconds)
+    pub fn total_ns(&self) -> u64 {
+        self.phase_ns.iter().fold(0u64, |total, ns| total.saturating_add(*ns))
+    }
+}
+
+/// Step time by phase since the server started
+#[derive(Debug, Clone, Default)]
+pub struct ProfileMetrics {
+    steps: u64,
+    totals: StepProfile,
+    phase_ns: [LatencyHistogram; 5],
+}
+
+impl ProfileMetrics {
+    /// Record the phases of one step
+    pub fn record(&mut self, profile: &StepProfile) {
+        self.steps += 1;
+        for phase in StepPhase::ALL {
+            let ns = profile.get(phase);
+            self.totals.phase_ns[phase.index()] = self.totals.get(phase).saturating_add(ns);
+            self.phase_ns[phase.index()].record(ns);
+            counter!("step_phase_ns_total", ns, "phase" => phase.name());
+            histogram!("step_phase_duration_ns", ns as f64, "phase" => phase.name());
+        }
+    }
+
+    /// Steps profiled
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Totals, shares and percentiles of each phase
+    pub fn summary(&self) -> ProfileSummary {
+        let total = self.totals.total_ns();
+        ProfileSummary {
+            steps: self.steps,
+            total_ns: total,
+            phases: StepPhase::ALL.iter().map(|&phase| PhaseSummary {
+                phase,
+                total_ns: self.totals.get(phase),
+                share_pct: match total {
+                    0 => 0.0,
+                    total => self.totals.get(phase) as f64 * 100.0 / total as f64,
+                },
+                per_step_ns: Percentiles::of(&self.phase_ns[phase.index()]),
+            }).collect(),
+        }
+    }
+}
+
+/// Step profile as reported by `/health`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProfileSummary {
+    /// Steps profiled
+    pub steps: u64,
+    /// Time spent in every phase of those steps
+    pub total_ns: u64,
+    pub phases: Vec<PhaseSummary>,
+}
+
+impl std::fmt::Display for ProfileSummary {
+    /// One line: each phase's share of step time and its mean per step
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} steps:", self.steps)?;
+        for phase in &self.phases {
+            let mean_us = phase.total_ns as f64 / self.steps.max(1) as f64 / 1000.0;
+            write!(f, " {} {:.1}% ({:.1}us)", phase.phase.name(), phase.share_pct, mean_us)?;
+        }
+        Ok(())
+    }
+}
+
+/// Time spent in one phase of the profiled steps
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PhaseSummary {
+    pub phase: StepPhase,
+    pub total_ns: u64,
+    /// Share of the time spent in every phase
+    pub share_pct: f64,
+    /// Time a step spent in the phase
+    pub per_step_ns: Percentiles,
+}
+
 /// Initialize metrics exporter for Prometheus
 pub fn init_metrics_exporter(port: u16) -> Result<(), Box<dyn std::error::Error>> {
     use metrics_exporter_prometheus::PrometheusBuilder;
     
     let builder = PrometheusBuilder::new();
-    let handle = builder
+    builder
         .with_http_listener(([0, 0, 0, 0], port))
         .install()?;
     
     tracing::info!("Metrics server started on port {}", port);
     
-    // Keep the handle alive
-    let _ = handle;
-    
     Ok(())
 }
 
@@ -311,6 +673,26 @@ mod tests {
     use super::*;
     use std::time::Duration;
 
+    #[test]
+    fn test_profile_attributes_step_time_to_phases() {
+        let mut metrics = ProfileMetrics::default();
+        for _ in 0..2 {
+            let mut profile = StepProfile::default();
+            profile.add(StepPhase::Generation, Duration::from_micros(30));
+            profile.add(StepPhase::Matching, Duration::from_micros(50));
+            profile.add(StepPhase::Matching, Duration::from_micros(10));
+            profile.add(StepPhase::Broadcast, Duration::from_micros(10));
+            assert_eq!(profile.total_ns(), 100_000);
+            metrics.record(&profile);
+        }
+        let summary = metrics.summary();
+        assert_eq!((summary.steps, summary.total_ns), (2, 200_000));
+        let shares: Vec<f64> = summary.phases.iter().map(|phase| phase.share_pct).collect();
+        assert_eq!(shares, vec![30.0, 60.0, 0.0, 0.0, 10.0]);
+        assert_eq!(summary.phases[1].total_ns, 120_000);
+        assert!(summary.to_string().contains("matching 60.0% (60.0us)"));
+    }
+
     #[test]
     fn test_performance_metrics_creation() {
         let metrics = PerformanceMetrics::new();
@@ -367,6 +749,8 @@ mod tests {
             events_ingested: 950,
             ingestion_errors: 50,
             ingestion_rate: 2000,
+            records_skipped: 0,
+            records_quarantined: 0,
             cpu_usage_percent: 75,
             uptime_seconds: 3600,
         };
@@ -379,6 +763,51 @@ mod tests {
         assert_eq!(snapshot.avg_snapshot_latency_us(), 100.0);
     }
 
+    #[test]
+    fn test_broadcast_metrics_summary() {
+        let mut metrics = BroadcastMetrics::default();
+        assert_eq!(metrics.summary(), BroadcastSummary::default());
+
+        metrics.record_fanout(3);
+        metrics.record_fanout(1);
+        for bytes in [1_000, 1_000, 1_000, 8_000] {
+            metrics.record_serialization(Duration::from_micros(20), bytes);
+            metrics.record_send(Duration::from_micros(5));
+        }
+
+        let summary = metrics.summary();
+        assert_eq!((summary.broadcasts, summary.last_fanout, summary.max_fanout), (2, 1, 3));
+        assert_eq!(summary.client_sends, 4);
+        assert_eq!(summary.payload_bytes.max, 8_000);
+        assert!(summary.payload_bytes.p50 <= 1_000 && summary.payload_bytes.p50 >= 1_000 - 1_000 / 16);
+        assert!(summary.payload_bytes.p99 > 7_000);
+        assert_eq!(summary.send_ns.max, 5_000);
+        assert!(summary.serialization_ns.p90 <= 20_000);
+    }
+
+    #[test]
+    fn test_pipeline_metrics_per_stage() {
+        let mut metrics = PipelineMetrics::default();
+        let stamps = [
+            EventStamp { event_ts: 1, ingest_ns: 1_000, match_ns: 1_500 },
+            EventStamp { event_ts: 2, ingest_ns: 2_000, match_ns: 2_100 },
+        ];
+        metrics.record(&[], 9_000);
+        metrics.record(&stamps, 3_000);
+
+        let watermark = PipelineWatermark::of(&stamps, 3_000).unwrap();
+        assert_eq!((watermark.events, watermark.first_ingest_ns, watermark.last_match_ns), (2, 1_000, 2_100));
+        assert_eq!(watermark.end_to_end_ns(), 2_000);
+        assert!(PipelineWatermark::of(&[], 3_000).is_none());
+
+        let summary = metrics.summary();
+        assert_eq!((summary.events, summary.messages), (2, 1));
+        assert_eq!(summary.ingest_to_match_ns.max, 500);
+        assert_eq!(summary.match_to_publish_ns.max, 1_500);
+        assert_eq!(summary.end_to_end_ns.max, 2_000);
+        assert!(summary.end_to_end_ns.p50 <= 1_000 && summary.end_to_end_ns.p50 >= 1_000 - 1_000 / 16);
+    }
+
     #[test]
     fn test_metrics_reset() {
         let metrics = PerformanceMetrics::new();
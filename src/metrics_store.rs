@@ -0,0 +1,366 @@
+//! Persisted metrics history
+//!
+//! A [`MetricsStore`] appends periodic samples of the performance counters and
+//! the simulator's market metrics to an embedded SQLite database, keyed by run
+//! id. Samples outlive the process, so a run can be analysed afterwards and
+//! compared with earlier runs without scraping logs.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use rusqlite::{params, Connection, Row};
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::engine::CommandEngine;
+use crate::logging::current_timestamp;
+use crate::metrics::PerformanceSnapshot;
+use crate::sim::Simulator;
+use crate::types::Price;
+
+/// Where and how often to persist metrics samples
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct MetricsStoreConfig {
+    /// Persist samples while the server runs
+    pub enabled: bool,
+    /// SQLite database file, created if missing
+    pub path: PathBuf,
+    /// Milliseconds between samples
+    pub interval_ms: u64,
+    /// Key for this run's samples; defaults to `run-<start time in ms>`
+    #[serde(default)]
+    pub run_id: Option<String>,
+}
+
+impl Default for MetricsStoreConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: PathBuf::from("metrics.db"),
+            interval_ms: 1000,
+            run_id: None,
+        }
+    }
+}
+
+impl MetricsStoreConfig {
+    /// Check the sampling settings
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval_ms == 0 {
+            return Err("Metrics store interval must be greater than 0".to_string());
+        }
+        if self.path.as_os_str().is_empty() {
+            return Err("Metrics store path cannot be empty".to_string());
+        }
+        if self.run_id.as_deref().is_some_and(str::is_empty) {
+            return Err("Metrics store run id cannot be empty".to_string());
+        }
+        Ok(())
+    }
+
+    /// The configured run id, or one derived from the current time
+    pub fn resolved_run_id(&self) -> String {
+        self.run_id.clone().unwrap_or_else(|| format!("run-{}", current_timestamp()))
+    }
+}
+
+/// Metrics store error types
+#[derive(Debug, thiserror::Error)]
+pub enum StoreError {
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+
+    #[error("Serialization error: {0}")]
+    Serialize(#[from] serde_json::Error),
+
+    #[error("Metrics store task failed: {0}")]
+    Task(#[from] tokio::task::JoinError),
+}
+
+pub type StoreResult<T> = Result<T, StoreError>;
+
+/// Most samples one history query returns
+pub const MAX_HISTORY_SAMPLES: usize = 10_000;
+
+/// Market state at the time of a sample
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MarketSample {
+    pub inventory: i64,
+    pub cash: i64,
+    pub pnl: i64,
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub best_bid: Option<Price>,
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub best_ask: Option<Price>,
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub spread: Option<i64>,
+    /// Trades over the run so far
+    pub trades: u64,
+    /// Quantity traded over the run so far
+    pub volume: u64,
+}
+
+impl MarketSample {
+    /// Capture the simulator's metrics and top of book
+    pub fn from_simulator<E: CommandEngine>(simulator: &Simulator<E>) -> Self {
+        let metrics = simulator.get_metrics();
+        let signed = simulator.volume_profiles().signed_volume();
+        let (best_bid, best_ask) = simulator.engine.best_bid_ask();
+        Self {
+            inventory: metrics.inventory,
+            cash: metrics.cash,
+            pnl: metrics.pnl,
+            best_bid,
+            best_ask,
+            spread: simulator.engine.quote_spread(),
+            trades: signed.buy_trades + signed.sell_trades,
+            volume: signed.buy_qty + signed.sell_qty,
+        }
+    }
+}
+
+/// One persisted sample
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricsSample {
+    pub run_id: String,
+    /// Wall-clock time in milliseconds since the Unix epoch
+    pub ts_ms: u64,
+    pub market: MarketSample,
+    pub performance: PerformanceSnapshot,
+}
+
+/// Sample count and time span of one stored run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunSummary {
+    pub run_id: String,
+    pub samples: u64,
+    pub first_ts_ms: u64,
+    pub last_ts_ms: u64,
+}
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS metrics_samples (
+        run_id TEXT NOT NULL,
+        ts_ms INTEGER NOT NULL,
+        inventory INTEGER NOT NULL,
+        cash INTEGER NOT NULL,
+        pnl INTEGER NOT NULL,
+        best_bid INTEGER,
+        best_ask INTEGER,
+        spread INTEGER,
+        trades INTEGER NOT NULL,
+        volume INTEGER NOT NULL,
+        performance TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS metrics_samples_run_ts ON metrics_samples (run_id, ts_ms);
+";
+
+/// SQLite-backed metrics history, appending under one run id
+#[derive(Debug)]
+pub struct MetricsStore {
+    conn: Connection,
+    run_id: String,
+    interval: Duration,
+}
+
+impl MetricsStore {
+    /// Open or create the database at `path`, recording under `run_id`
+    pub fn open<P: AsRef<Path>>(path: P, run_id: impl Into<String>) -> StoreResult<Self> {
+        Self::init(Connection::open(path)?, run_id.into())
+    }
+
+    /// Open a database that lives only as long as the store
+    pub fn open_in_memory(run_id: impl Into<String>) -> StoreResult<Self> {
+        Self::init(Connection::open_in_memory()?, run_id.into())
+    }
+
+    /// Open the database described by a configuration
+    pub fn from_config(config: &MetricsStoreConfig) -> StoreResult<Self> {
+        Ok(Self::open(&config.path, config.resolved_run_id())?
+            .with_interval(Duration::from_millis(config.interval_ms)))
+    }
+
+    fn init(conn: Connection, run_id: String) -> StoreResult<Self> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            conn,
+            run_id,
+            interval: Duration::from_millis(MetricsStoreConfig::default().interval_ms),
+        })
+    }
+
+    /// Set how often the server records a sample
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// How often the server records a sample
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// The run id new samples are stored under
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    /// Append a sample for this run
+    pub fn record(&self, ts_ms: u64, market: &MarketSample, performance: &PerformanceSnapshot) -> StoreResult<()> {
+        self.conn.execute(
+            "INSERT INTO metrics_samples
+                (run_id, ts_ms, inventory, cash, pnl, best_bid, best_ask, spread, trades, volume, performance)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                self.run_id,
+                ts_ms as i64,
+                market.inventory,
+                market.cash,
+                market.pnl,
+                market.best_bid.map(|price| price as i64),
+                market.best_ask.map(|price| price as i64),
+                market.spread,
+                market.trades as i64,
+                market.volume as i64,
+                serde_json::to_string(performance)?,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Up to `limit` samples for `run_id` with `from <= ts_ms <= to`, oldest first
+    pub fn query(&self, run_id: &str, from: Option<u64>, to: Option<u64>, limit: usize) -> StoreResult<Vec<MetricsSample>> {
+        let mut statement = self.conn.prepare(
+            "SELECT run_id, ts_ms, inventory, cash, pnl, best_bid, best_ask, spread, trades, volume, performance
+             FROM metrics_samples
+             WHERE run_id = ?1 AND ts_ms >= ?2 AND ts_ms <= ?3
+             ORDER BY ts_ms, rowid
+             LIMIT ?4",
+        )?;
+        let from = from.unwrap_or(0).min(i64::MAX as u64) as i64;
+        let to = to.unwrap_or(u64::MAX).min(i64::MAX as u64) as i64;
+        let limit = limit.min(i64::MAX as usize) as i64;
+        let rows = statement.query_map(params![run_id, from, to, limit], |row| Ok((Self::sample_row(row)?, row.get::<_, String>(10)?)))?;
+
+        let mut samples = Vec::new();
+        for row in rows {
+            let (mut sample, performance) = row?;
+            sample.performance = serde_json::from_str(&performance)?;
+            samples.push(sample);
+        }
+        Ok(samples)
+    }
+
+    /// This run's samples with `from <= ts_ms <= to`, oldest first, up to
+    /// [`MAX_HISTORY_SAMPLES`]
+    pub fn history(&self, from: Option<u64>, to: Option<u64>) -> StoreResult<Vec<MetricsSample>> {
+        self.query(&self.run_id, from, to, MAX_HISTORY_SAMPLES)
+    }
+
+    /// The most recent sample for `run_id`
+    pub fn latest(&self, run_id: &str) -> StoreResult<Option<MetricsSample>> {
+        let from = self.conn.query_row(
+            "SELECT MAX(ts_ms) FROM metrics_samples WHERE run_id = ?1",
+            params![run_id],
+            |row| row.get::<_, Option<i64>>(0),
+        )?;
+        match from {
+            Some(ts_ms) => Ok(self.query(run_id, Some(ts_ms as u64), None, usize::MAX)?.pop()),
+            None => Ok(None),
+        }
+    }
+
+    /// Every stored run, oldest first
+    pub fn runs(&self) -> StoreResult<Vec<RunSummary>> {
+        let mut statement = self.conn.prepare(
+            "SELECT run_id, COUNT(*), MIN(ts_ms), MAX(ts_ms)
+             FROM metrics_samples
+             GROUP BY run_id
+             ORDER BY MIN(ts_ms)",
+        )?;
+        let runs = statement.query_map([], |row| {
+            Ok(RunSummary {
+                run_id: row.get(0)?,
+                samples: row.get::<_, i64>(1)? as u64,
+                first_ts_ms: row.get::<_, i64>(2)? as u64,
+                last_ts_ms: row.get::<_, i64>(3)? as u64,
+            })
+        })?;
+        Ok(runs.collect::<Result<_, _>>()?)
+    }
+
+    /// Read a row's columns, leaving the performance snapshot to the caller
+    fn sample_row(row: &Row) -> rusqlite::Result<MetricsSample> {
+        Ok(MetricsSample {
+            run_id: row.get(0)?,
+            ts_ms: row.get::<_, i64>(1)? as u64,
+            market: MarketSample {
+                inventory: row.get(2)?,
+                cash: row.get(3)?,
+                pnl: row.get(4)?,
+                best_bid: row.get::<_, Option<i64>>(5)?.map(|price| price as Price),
+                best_ask: row.get::<_, Option<i64>>(6)?.map(|price| price as Price),
+                spread: row.get(7)?,
+                trades: row.get::<_, i64>(8)? as u64,
+                volume: row.get::<_, i64>(9)? as u64,
+            },
+            performance: PerformanceSnapshot::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn market(pnl: i64) -> MarketSample {
+        MarketSample {
+            pnl,
+            best_bid: Some(999_900),
+            best_ask: None,
+            trades: 3,
+            volume: 300,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_record_and_query_by_run_and_time() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("metrics.db");
+        let performance = PerformanceSnapshot { orders_processed: 42, ..Default::default() };
+
+        {
+            let store = MetricsStore::open(&path, "first").unwrap();
+            for ts_ms in [1_000, 2_000, 3_000] {
+                store.record(ts_ms, &market(ts_ms as i64), &performance).unwrap();
+            }
+        }
+
+        // Samples survive reopening, and a new run keeps its own
+        let store = MetricsStore::open(&path, "second").unwrap();
+        store.record(5_000, &market(-1), &performance).unwrap();
+
+        let samples = store.query("first", Some(1_500), Some(3_000), MAX_HISTORY_SAMPLES).unwrap();
+        assert_eq!(samples.iter().map(|sample| sample.ts_ms).collect::<Vec<_>>(), vec![2_000, 3_000]);
+        assert_eq!(samples[0].market, market(2_000));
+        assert_eq!(samples[0].performance.orders_processed, 42);
+        assert_eq!(store.query("first", None, None, 2).unwrap().last().unwrap().ts_ms, 2_000);
+
+        assert_eq!(store.history(None, None).unwrap().len(), 1);
+        assert_eq!(store.latest("first").unwrap().unwrap().ts_ms, 3_000);
+        assert!(store.latest("missing").unwrap().is_none());
+        assert_eq!(store.runs().unwrap(), vec![
+            RunSummary { run_id: "first".to_string(), samples: 3, first_ts_ms: 1_000, last_ts_ms: 3_000 },
+            RunSummary { run_id: "second".to_string(), samples: 1, first_ts_ms: 5_000, last_ts_ms: 5_000 },
+        ]);
+    }
+
+    #[test]
+    fn test_config_validation() {
+        let config = MetricsStoreConfig::default();
+        assert!(config.validate().is_ok());
+        assert!(config.resolved_run_id().starts_with("run-"));
+        assert!(MetricsStoreConfig { interval_ms: 0, ..Default::default() }.validate().is_err());
+        assert!(MetricsStoreConfig { run_id: Some(String::new()), ..Default::default() }.validate().is_err());
+    }
+}
@@ -0,0 +1,383 @@
+//! Market-maker obligations
+//!
+//! Exchanges with formal market-making programs hold their makers to a
+//! two-sided quote for most of each session, no wider than a maximum spread
+//! and with at least a minimum size displayed on each side. An
+//! [`ObligationMonitor`] samples every maker's own best bid and ask after
+//! each step and credits the simulation time until the next sample to what
+//! that quote met. At the end of each session it closes a
+//! [`ComplianceReport`] per maker: the share of the session spent quoting
+//! within the obligations, and where the rest of the time went.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::agents::AgentId;
+use crate::time::ms_to_ns;
+use crate::types::{Price, Qty};
+
+/// Obligations makers are scored against
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ObligationConfig {
+    /// Score market makers against the obligations
+    pub enabled: bool,
+    /// Widest spread between a maker's own bid and ask that counts as quoting (ticks)
+    pub max_spread_ticks: Price,
+    /// Smallest size a maker must display at its best price on each side
+    pub min_size: Qty,
+    /// Share of each session a maker must spend quoting to comply (percent)
+    pub min_quoted_pct: f64,
+    /// Simulation time per session (milliseconds)
+    pub session_ms: u64,
+    /// Closed sessions kept per maker
+    pub history: usize,
+}
+
+impl Default for ObligationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_spread_ticks: 500,
+            min_size: 100,
+            min_quoted_pct: 90.0,
+            session_ms: 60_000,
+            history: 100,
+        }
+    }
+}
+
+impl ObligationConfig {
+    /// Check the obligations and the session length
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_spread_ticks == 0 {
+            return Err("Obligation max spread must be greater than 0".to_string());
+        }
+        if !(0.0..=100.0).contains(&self.min_quoted_pct) {
+            return Err("Obligation quoted time must be between 0 and 100 percent".to_string());
+        }
+        if self.session_ms == 0 {
+            return Err("Obligation session length must be greater than 0".to_string());
+        }
+        if self.history == 0 {
+            return Err("Obligation history must keep at least one session".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A participant held to the obligations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Maker {
+    /// The simulator's built-in market-making flow
+    Synthetic,
+    /// A configured agent in the market-maker role
+    Agent(AgentId),
+}
+
+impl fmt::Display for Maker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Synthetic => write!(f, "synthetic market maker"),
+            Self::Agent(id) => write!(f, "agent {}", id),
+        }
+    }
+}
+
+/// A maker's own best bid and ask, with the size resting at each
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MakerQuote {
+    pub bid: Option<(Price, Qty)>,
+    pub ask: Option<(Price, Qty)>,
+}
+
+impl MakerQuote {
+    /// Take a resting order into account
+    pub fn add_bid(&mut self, price: Price, qty: Qty) {
+        self.bid = Some(match self.bid {
+            Some((best, size)) if best == price => (best, size + qty),
+            Some((best, size)) if best > price => (best, size),
+            _ => (price, qty),
+        });
+    }
+
+    /// Take a resting order into account
+    pub fn add_ask(&mut self, price: Price, qty: Qty) {
+        self.ask = Some(match self.ask {
+            Some((best, size)) if best == price => (best, size + qty),
+            Some((best, size)) if best < price => (best, size),
+            _ => (price, qty),
+        });
+    }
+
+    /// How the quote falls short of the obligations, if it does
+    fn shortfall(&self, config: &ObligationConfig) -> Option<Shortfall> {
+        let (Some((bid, bid_qty)), Some((ask, ask_qty))) = (self.bid, self.ask) else {
+            return Some(Shortfall::OneSided);
+        };
+        if ask.saturating_sub(bid) > config.max_spread_ticks {
+            Some(Shortfall::Wide)
+        } else if bid_qty.min(ask_qty) < config.min_size {
+            Some(Shortfall::Thin)
+        } else {
+            None
+        }
+    }
+}
+
+/// Why a quote did not count
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shortfall {
+    /// No bid or no ask
+    OneSided,
+    /// Spread past the maximum
+    Wide,
+    /// Less than the minimum size on a side
+    Thin,
+}
+
+/// A maker's record for one session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ComplianceReport {
+    pub maker: Maker,
+    /// Session number, counting from 0
+    pub session: u64,
+    /// Simulation time the session started
+    pub start: u128,
+    /// Simulation time the session ended, or was last sampled if it is still open
+    pub end: u128,
+    /// Time within the obligations (nanoseconds)
+    pub quoted_ns: u128,
+    /// Time quoting on one side or none (nanoseconds)
+    pub one_sided_ns: u128,
+    /// Time with the spread past the maximum (nanoseconds)
+    pub wide_ns: u128,
+    /// Time with less than the minimum size on a side (nanoseconds)
+    pub thin_ns: u128,
+    /// Share of the session spent within the obligations (percent)
+    pub quoted_pct: f64,
+    /// Whether the quoted share meets the obligation
+    pub compliant: bool,
+}
+
+/// Time a maker's quotes spent in each state this session
+#[derive(Debug, Clone, Copy, Default)]
+struct Tally {
+    quoted_ns: u128,
+    one_sided_ns: u128,
+    wide_ns: u128,
+    thin_ns: u128,
+}
+
+impl Tally {
+    fn credit(&mut self, shortfall: Option<Shortfall>, ns: u128) {
+        match shortfall {
+            None => self.quoted_ns += ns,
+            Some(Shortfall::OneSided) => self.one_sided_ns += ns,
+            Some(Shortfall::Wide) => self.wide_ns += ns,
+            Some(Shortfall::Thin) => self.thin_ns += ns,
+        }
+    }
+
+    fn total_ns(&self) -> u128 {
+        self.quoted_ns + self.one_sided_ns + self.wide_ns + self.thin_ns
+    }
+}
+
+/// Scores makers' quotes against the obligations, session by session
+#[derive(Debug, Clone)]
+pub struct ObligationMonitor {
+    config: ObligationConfig,
+    session: u64,
+    session_start: Option<u128>,
+    /// Time and quotes of the last sample; the quotes stand until the next
+    last_sample: Option<(u128, BTreeMap<Maker, MakerQuote>)>,
+    /// Every maker seen so far, with its tally for the open session
+    tallies: BTreeMap<Maker, Tally>,
+    /// Closed sessions, oldest first
+    reports: VecDeque<ComplianceReport>,
+}
+
+impl ObligationMonitor {
+    /// Create a monitor with the given obligations
+    pub fn new(config: ObligationConfig) -> Self {
+        Self {
+            config,
+            session: 0,
+            session_start: None,
+            last_sample: None,
+            tallies: BTreeMap::new(),
+            reports: VecDeque::new(),
+        }
+    }
+
+    /// Get the obligations
+    pub fn config(&self) -> &ObligationConfig {
+        &self.config
+    }
+
+    /// Sample the makers' quotes at `ts`, returning the reports of any
+    /// sessions that ended since the last sample
+    ///
+    /// A maker seen once stays scored: when it has nothing resting it is
+    /// quoting on neither side.
+    pub fn on_step(&mut self, ts: u128, quotes: BTreeMap<Maker, MakerQuote>) -> Vec<ComplianceReport> {
+        let session_ns = ms_to_ns(self.config.session_ms);
+        let mut session_start = *self.session_start.get_or_insert(ts);
+        let mut closed = Vec::new();
+
+        if let Some((mut last, previous)) = self.last_sample.take() {
+            while ts >= session_start + session_ns {
+                let session_end = session_start + session_ns;
+                self.credit(&previous, session_end.saturating_sub(last));
+                closed.extend(self.close_session(session_start, session_end));
+                last = last.max(session_end);
+                session_start = session_end;
+            }
+            self.credit(&previous, ts.saturating_sub(last));
+        }
+        self.session_start = Some(session_start);
+        for maker in quotes.keys() {
+            self.tallies.entry(*maker).or_default();
+        }
+        self.last_sample = Some((ts, quotes));
+        closed
+    }
+
+    /// Credit `ns` to each maker's quote in `quotes`
+    fn credit(&mut self, quotes: &BTreeMap<Maker, MakerQuote>, ns: u128) {
+        if ns == 0 {
+            return;
+        }
+        for (maker, tally) in self.tallies.iter_mut() {
+            let quote = quotes.get(maker).copied().unwrap_or_default();
+            tally.credit(quote.shortfall(&self.config), ns);
+        }
+    }
+
+    /// Close the open session, starting a new one for every maker
+    fn close_session(&mut self, start: u128, end: u128) -> Vec<ComplianceReport> {
+        let reports: Vec<ComplianceReport> = self.tallies.iter()
+            .map(|(&maker, tally)| self.report(maker, tally, start, end))
+            .collect();
+        for tally in self.tallies.values_mut() {
+            *tally = Tally::default();
+        }
+        self.session += 1;
+
+        let kept = self.config.history * self.tallies.len().max(1);
+        self.reports.extend(reports.iter().cloned());
+        while self.reports.len() > kept {
+            self.reports.pop_front();
+        }
+        reports
+    }
+
+    fn report(&self, maker: Maker, tally: &Tally, start: u128, end: u128) -> ComplianceReport {
+        let total = tally.total_ns();
+        let quoted_pct = if total > 0 { tally.quoted_ns as f64 * 100.0 / total as f64 } else { 0.0 };
+        ComplianceReport {
+            maker,
+            session: self.session,
+            start,
+            end,
+            quoted_ns: tally.quoted_ns,
+            one_sided_ns: tally.one_sided_ns,
+            wide_ns: tally.wide_ns,
+            thin_ns: tally.thin_ns,
+            quoted_pct,
+            compliant: total > 0 && quoted_pct >= self.config.min_quoted_pct,
+        }
+    }
+
+    /// Reports of the open session so far, up to the last sample
+    pub fn open_session(&self) -> Vec<ComplianceReport> {
+        let (Some(start), Some((last, _))) = (self.session_start, self.last_sample.as_ref()) else {
+            return Vec::new();
+        };
+        self.tallies.iter()
+            .map(|(&maker, tally)| self.report(maker, tally, start, *last))
+            .collect()
+    }
+
+    /// Reports of the closed sessions, oldest first
+    pub fn reports(&self) -> impl Iterator<Item = &ComplianceReport> {
+        self.reports.iter()
+    }
+
+    /// Start over from the first session
+    pub fn reset(&mut self) {
+        *self = Self::new(self.config.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ObligationConfig {
+        ObligationConfig {
+            enabled: true,
+            max_spread_ticks: 100,
+            min_size: 10,
+            min_quoted_pct: 75.0,
+            session_ms: 10,
+            history: 4,
+        }
+    }
+
+    fn quote(bid: Price, ask: Price, qty: Qty) -> MakerQuote {
+        let mut quote = MakerQuote::default();
+        quote.add_bid(bid, qty);
+        quote.add_ask(ask, qty);
+        quote
+    }
+
+    #[test]
+    fn test_scores_quoted_time_per_session() {
+        let ms = ms_to_ns(1);
+        let mut monitor = ObligationMonitor::new(config());
+        let both = |synthetic: MakerQuote, agent: MakerQuote| {
+            BTreeMap::from([(Maker::Synthetic, synthetic), (Maker::Agent(3), agent)])
+        };
+
+        // The synthetic maker quotes within the obligations throughout; the
+        // agent is too wide for 2ms, thin for 2ms and gone for the rest
+        assert!(monitor.on_step(0, both(quote(1000, 1050, 20), quote(1000, 1200, 20))).is_empty());
+        assert!(monitor.on_step(2 * ms, both(quote(1000, 1050, 20), quote(1000, 1050, 5))).is_empty());
+        assert!(monitor.on_step(4 * ms, BTreeMap::from([(Maker::Synthetic, quote(1000, 1050, 20))])).is_empty());
+        let closed = monitor.on_step(15 * ms, BTreeMap::new());
+
+        assert_eq!(closed.len(), 2);
+        let synthetic = &closed[0];
+        assert_eq!((synthetic.maker, synthetic.session, synthetic.end), (Maker::Synthetic, 0, 10 * ms));
+        assert_eq!(synthetic.quoted_ns, 10 * ms);
+        assert!(synthetic.compliant);
+        let agent = &closed[1];
+        assert_eq!((agent.wide_ns, agent.thin_ns, agent.one_sided_ns), (2 * ms, 2 * ms, 6 * ms));
+        assert_eq!(agent.quoted_pct, 0.0);
+        assert!(!agent.compliant);
+
+        // The next session has the synthetic maker's quote until it was
+        // pulled, then nothing
+        let open = monitor.open_session();
+        assert_eq!((open[0].session, open[0].start, open[0].end), (1, 10 * ms, 15 * ms));
+        assert_eq!(open[0].quoted_ns, 5 * ms);
+        assert_eq!(monitor.reports().count(), 2);
+    }
+
+    #[test]
+    fn test_quote_keeps_the_best_price_per_side() {
+        let mut quote = MakerQuote::default();
+        quote.add_bid(990, 5);
+        quote.add_bid(1000, 3);
+        quote.add_bid(1000, 4);
+        quote.add_bid(980, 50);
+        quote.add_ask(1020, 6);
+        quote.add_ask(1010, 2);
+        assert_eq!(quote.bid, Some((1000, 7)));
+        assert_eq!(quote.ask, Some((1010, 2)));
+        assert_eq!(quote.shortfall(&config()), Some(Shortfall::Thin));
+    }
+}
@@ -0,0 +1,119 @@
+//! Ages of resting orders
+//!
+//! How long orders sit in the book is a quick check of how realistic
+//! synthetic flow is against a real feed: generated books tend to churn
+//! their whole depth at the same pace, while real books keep a long tail of
+//! stale orders away from the touch. Each side reports the age distribution
+//! of all its resting orders and the age of every level's oldest order.
+//! Ages are measured against the simulation clock, so they stay meaningful
+//! for replays and accelerated runs.
+
+use serde::{Deserialize, Serialize};
+use crate::types::{Order, OrderType, Price, Side};
+
+/// Age of the oldest order at one price level
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LevelAge {
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub price: Price,
+    pub orders: usize,
+    pub oldest_ns: u64,
+}
+
+/// Age distribution of one side's resting orders
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SideAges {
+    pub orders: usize,
+    pub p50_ns: u64,
+    pub p90_ns: u64,
+    pub max_ns: u64,
+    /// Levels best first
+    pub levels: Vec<LevelAge>,
+}
+
+impl SideAges {
+    /// Ages at `now` of one side's levels, best first, each given as its
+    /// price, its oldest order's timestamp and the timestamps of all its orders
+    pub fn measure(now: u128, levels: impl IntoIterator<Item = (Price, Option<u128>, Vec<u128>)>) -> Self {
+        let age = |ts: u128| now.saturating_sub(ts) as u64;
+        let mut ages = Vec::new();
+        let levels: Vec<LevelAge> = levels.into_iter()
+            .filter_map(|(price, oldest, order_ts)| {
+                ages.extend(order_ts.iter().map(|&ts| age(ts)));
+                oldest.map(|ts| LevelAge { price, orders: order_ts.len(), oldest_ns: age(ts) })
+            })
+            .collect();
+        ages.sort_unstable();
+        let percentile = |p: f64| match ages.len() {
+            0 => 0,
+            n => ages[((p * n as f64).ceil() as usize).clamp(1, n) - 1],
+        };
+        Self {
+            orders: ages.len(),
+            p50_ns: percentile(0.5),
+            p90_ns: percentile(0.9),
+            max_ns: ages.last().copied().unwrap_or(0),
+            levels,
+        }
+    }
+}
+
+/// Age distributions of both sides of the book
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderAges {
+    pub bids: SideAges,
+    pub asks: SideAges,
+}
+
+impl OrderAges {
+    /// Ages at `now` of resting orders listed side by side, best level
+    /// first, as an engine's open orders are
+    pub fn from_orders(now: u128, orders: &[Order]) -> Self {
+        let side = |side: Side| {
+            let mut levels: Vec<(Price, Option<u128>, Vec<u128>)> = Vec::new();
+            for order in orders.iter().filter(|order| order.side == side) {
+                let OrderType::Limit { price } = order.order_type else {
+                    continue;
+                };
+                match levels.last_mut() {
+                    Some((level_price, oldest, order_ts)) if *level_price == price => {
+                        *oldest = (*oldest).min(Some(order.ts));
+                        order_ts.push(order.ts);
+                    }
+                    _ => levels.push((price, Some(order.ts), vec![order.ts])),
+                }
+            }
+            SideAges::measure(now, levels)
+        };
+        Self {
+            bids: side(Side::Buy),
+            asks: side(Side::Sell),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ages_per_side_and_level() {
+        let orders = vec![
+            Order::new_limit(1, Side::Buy, 10, 100_0000, 1_000),
+            Order::new_limit(2, Side::Buy, 10, 100_0000, 4_000),
+            Order::new_limit(3, Side::Buy, 10, 99_0000, 2_000),
+            Order::new_limit(4, Side::Sell, 10, 101_0000, 9_000),
+        ];
+        let ages = OrderAges::from_orders(10_000, &orders);
+
+        assert_eq!((ages.bids.orders, ages.bids.p50_ns, ages.bids.p90_ns, ages.bids.max_ns), (3, 8_000, 9_000, 9_000));
+        assert_eq!(ages.bids.levels, vec![
+            LevelAge { price: 100_0000, orders: 2, oldest_ns: 9_000 },
+            LevelAge { price: 99_0000, orders: 1, oldest_ns: 8_000 },
+        ]);
+        assert_eq!((ages.asks.orders, ages.asks.p50_ns, ages.asks.max_ns), (1, 1_000, 1_000));
+        // Orders stamped after `now` count as brand new
+        assert_eq!(OrderAges::from_orders(500, &orders).bids.max_ns, 0);
+        assert_eq!(SideAges::measure(0, []), SideAges::default());
+    }
+}
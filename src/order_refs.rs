@@ -0,0 +1,178 @@
+//! Historical order references the book has never seen
+//!
+//! A partial-day file starts with orders already resting. Its cancels and
+//! modifications of those orders name ids the book has never seen, and
+//! are quietly dropped, leaving the replayed book without the depth the
+//! real one had. [`UnmatchedOrders`] counts these references so a run shows
+//! how much of its data fell through. Ids the data has placed stay known
+//! after their orders fill or are cancelled, so a late reference to one of
+//! those is dropped without being counted. With [`UnmatchedOrderPolicy::Placeholder`],
+//! a modification that names its new price and quantity also rests a
+//! placeholder order in its place, so the book keeps the depth the data
+//! says was there.
+
+use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::types::{order_ids, OrderId, Price, Side};
+
+/// Ids of unmatched orders kept for the report
+const SAMPLE_IDS: usize = 20;
+
+/// What to do about a cancel or modification naming an unknown order
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UnmatchedOrderPolicy {
+    /// Count the reference and drop it
+    #[default]
+    Ignore,
+    /// Count it, and rest a placeholder for a modification that gives its
+    /// new price and quantity
+    Placeholder,
+}
+
+/// Unmatched order references seen so far
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnmatchedOrderReport {
+    /// Cancels naming an unknown order
+    pub cancels: u64,
+    /// Modifications naming an unknown order
+    pub modifications: u64,
+    /// Different unknown orders referenced
+    pub distinct_orders: u64,
+    /// Placeholder orders rested in place of unknown ones
+    pub placeholders: u64,
+    /// The first few unknown ids, as they appear in the data
+    pub sample_ids: Vec<OrderId>,
+}
+
+/// Counts references to orders the book has never seen
+#[derive(Debug, Clone, Default)]
+pub struct UnmatchedOrders {
+    policy: UnmatchedOrderPolicy,
+    /// Ids the data has placed, resting or not
+    placed: HashSet<OrderId>,
+    seen: HashSet<OrderId>,
+    report: UnmatchedOrderReport,
+}
+
+impl UnmatchedOrders {
+    pub fn new(policy: UnmatchedOrderPolicy) -> Self {
+        Self { policy, ..Self::default() }
+    }
+
+    pub fn policy(&self) -> UnmatchedOrderPolicy {
+        self.policy
+    }
+
+    pub fn report(&self) -> &UnmatchedOrderReport {
+        &self.report
+    }
+
+    /// Note an order the data placed, so later references to it are not
+    /// counted once it has left the book
+    pub fn place(&mut self, order_id: OrderId) {
+        self.placed.insert(order_id);
+    }
+
+    /// Count a cancel of an order the book does not hold, unless the data
+    /// placed it
+    pub fn cancel(&mut self, order_id: OrderId) {
+        if self.placed.contains(&order_id) {
+            return;
+        }
+        self.report.cancels += 1;
+        self.reference(order_id);
+    }
+
+    /// Count a modification of an order the book does not hold, returning
+    /// whether to rest a placeholder for it; one the data placed has filled
+    /// or been cancelled, and is left alone
+    pub fn modification(&mut self, order_id: OrderId) -> bool {
+        if self.placed.contains(&order_id) {
+            return false;
+        }
+        self.report.modifications += 1;
+        self.reference(order_id);
+        self.policy == UnmatchedOrderPolicy::Placeholder
+    }
+
+    /// Count a placeholder rested in place of an unknown order
+    pub fn placeholder(&mut self, order_id: OrderId) {
+        self.placed.insert(order_id);
+        self.report.placeholders += 1;
+    }
+
+    /// Forget everything counted, for a new data source
+    pub fn reset(&mut self) {
+        *self = Self::new(self.policy);
+    }
+
+    fn reference(&mut self, order_id: OrderId) {
+        if !self.seen.insert(order_id) {
+            return;
+        }
+        self.report.distinct_orders += 1;
+        if self.report.sample_ids.len() < SAMPLE_IDS {
+            // Report ids as the data has them, without the historical tag
+            self.report.sample_ids.push(order_ids::source_id(order_id));
+        }
+    }
+}
+
+/// Side a placeholder at `price` most plausibly rested on, given the book's
+/// best bid and ask; `None` when the price is the mid or the book is empty
+pub fn placeholder_side(price: Price, best_bid: Option<Price>, best_ask: Option<Price>) -> Option<Side> {
+    match (best_bid, best_ask) {
+        (Some(bid), _) if price <= bid => Some(Side::Buy),
+        (_, Some(ask)) if price >= ask => Some(Side::Sell),
+        (Some(bid), Some(ask)) => match (price * 2).cmp(&(bid + ask)) {
+            std::cmp::Ordering::Less => Some(Side::Buy),
+            std::cmp::Ordering::Greater => Some(Side::Sell),
+            std::cmp::Ordering::Equal => None,
+        },
+        (Some(_), None) => Some(Side::Sell),
+        (None, Some(_)) => Some(Side::Buy),
+        (None, None) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_distinct_unknown_orders() {
+        let mut unmatched = UnmatchedOrders::new(UnmatchedOrderPolicy::Ignore);
+        unmatched.cancel(order_ids::historical(7).unwrap());
+        assert!(!unmatched.modification(order_ids::historical(7).unwrap()));
+        assert!(!unmatched.modification(order_ids::historical(9).unwrap()));
+        let report = unmatched.report();
+        assert_eq!((report.cancels, report.modifications, report.distinct_orders), (1, 2, 2));
+        assert_eq!(report.sample_ids, vec![7, 9]);
+
+        // Orders the data placed are known once they have left the book
+        unmatched.place(11);
+        unmatched.cancel(11);
+        assert!(!unmatched.modification(11));
+        assert_eq!((unmatched.report().cancels, unmatched.report().modifications), (1, 2));
+
+        let mut placing = UnmatchedOrders::new(UnmatchedOrderPolicy::Placeholder);
+        assert!(placing.modification(3));
+        placing.placeholder(3);
+        assert!(!placing.modification(3));
+        placing.reset();
+        assert_eq!(placing.report(), &UnmatchedOrderReport::default());
+        assert_eq!(placing.policy(), UnmatchedOrderPolicy::Placeholder);
+    }
+
+    #[test]
+    fn test_placeholder_side_follows_the_touch() {
+        assert_eq!(placeholder_side(99, Some(100), Some(102)), Some(Side::Buy));
+        assert_eq!(placeholder_side(103, Some(100), Some(102)), Some(Side::Sell));
+        assert_eq!(placeholder_side(101, Some(100), Some(104)), Some(Side::Buy));
+        assert_eq!(placeholder_side(102, Some(100), Some(104)), None);
+        assert_eq!(placeholder_side(105, Some(100), None), Some(Side::Sell));
+        assert_eq!(placeholder_side(100, None, None), None);
+    }
+}
@@ -0,0 +1,861 @@
+//! Paper trading against the live book
+//!
+//! Paper orders are matched against the book's state without ever being
+//! placed in it. Marketable orders fill against the displayed depth, and
+//! orders that rest join the back of the queue at their price: they only
+//! fill once trades have worked through the quantity that was ahead of them,
+//! or once the market trades or quotes through their price.
+//!
+//! Optional pre-trade checks run before an order is accepted: buying power
+//! under [`MarginConfig`] and notional velocity under [`VelocityConfig`].
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use crate::engine::{BookLevelPoint, DepthSnapshot, MAX_PRICE, MAX_QTY, MIN_PRICE};
+use crate::error::{EngineError, EngineResult};
+use crate::funding::FundingSettlement;
+use crate::margin::{MarginCall, MarginConfig, MarginStatus};
+use crate::memory::{CircularBuffer, Compaction, EvictionPolicy, HistoryUsage};
+use crate::velocity::{NotionalThrottle, VelocityConfig, VelocityCounters};
+use crate::types::{price_utils, ContractSpec, OrderId, Price, Qty, Side, Trade};
+
+/// A simulated execution for a paper order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaperFill {
+    pub order_id: OrderId,
+    pub account: String,
+    pub side: Side,
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub price: Price,
+    pub qty: Qty,
+    pub ts: u128,
+}
+
+/// A paper order resting at its limit price
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaperOrder {
+    pub id: OrderId,
+    pub account: String,
+    pub side: Side,
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub price: Price,
+    /// Quantity still open
+    pub remaining: Qty,
+    /// Book quantity at this price that has to trade before this order fills
+    pub queue_ahead: Qty,
+    pub ts: u128,
+    /// Crossing book quantity this order has already filled against, which
+    /// stays on display because paper fills never consume it
+    #[serde(skip)]
+    crossing_taken: Qty,
+}
+
+impl PaperOrder {
+    /// Whether a price is at least as good as this order's limit for the
+    /// other side of the trade
+    fn crossed_by(&self, price: Price) -> bool {
+        match self.side {
+            Side::Buy => price <= self.price,
+            Side::Sell => price >= self.price,
+        }
+    }
+}
+
+/// Position and cash for one paper account
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaperAccount {
+    /// Net position (positive = long, negative = short)
+    pub position: i64,
+    /// Cash in ticks, or base units for inverse contracts
+    pub cash: i64,
+    /// Total quantity traded
+    pub volume: Qty,
+    /// Number of fills
+    pub fill_count: u64,
+    /// Net funding received (negative when paid), already included in cash
+    #[serde(default)]
+    pub funding: i64,
+}
+
+impl PaperAccount {
+    // Submissions are refused when they could overflow the account, so the
+    // saturation here only guards fills the checks could not foresee
+    fn apply(&mut self, contract: &ContractSpec, fill: &PaperFill) {
+        let signed_qty = signed(fill.side, fill.qty);
+        self.position = saturate(self.position as i128 + signed_qty);
+        self.cash = saturate(self.cash as i128 - contract.saturating_value_of(signed_qty as i64, fill.price) as i128);
+        self.volume = self.volume.saturating_add(fill.qty);
+        self.fill_count += 1;
+    }
+
+    /// Mark-to-market value, or just cash without a mark
+    pub fn equity(&self, contract: &ContractSpec, mark: Option<Price>) -> i64 {
+        let value = mark.map_or(0, |mark| contract.saturating_value_of(self.position, mark) as i128);
+        saturate(self.cash as i128 + value)
+    }
+}
+
+/// Quantity signed by side: positive buying, negative selling
+fn signed(side: Side, qty: Qty) -> i128 {
+    match side {
+        Side::Buy => qty as i128,
+        Side::Sell => -(qty as i128),
+    }
+}
+
+/// Clamp a wide amount into the account's i64 fields
+fn saturate(amount: i128) -> i64 {
+    amount.clamp(i64::MIN as i128, i64::MAX as i128) as i64
+}
+
+/// Absolute value of `qty` contracts at a price, past any limit when it
+/// overflows
+fn notional(contract: &ContractSpec, qty: Qty, price: Price) -> u128 {
+    contract.notional(qty, price).map_or(u128::MAX, |notional| notional as u128)
+}
+
+/// Outcome of submitting a paper order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaperSubmission {
+    pub order_id: OrderId,
+    /// Fills taken immediately from displayed liquidity
+    pub fills: Vec<PaperFill>,
+    /// The unfilled remainder of a limit order, now resting
+    pub resting: Option<PaperOrder>,
+}
+
+/// Paper trading layer keeping its own orders, accounts and fills
+///
+/// The layer never touches the book; feed it the book's state and the trades
+/// from each step through [`PaperTrader::on_market_update`]. With margin
+/// enabled, accounts open with the configured starting cash, orders are
+/// checked against buying power, and accounts falling below maintenance
+/// raise margin calls. With a velocity limit, each account's submitted
+/// notional is capped over a rolling window.
+#[derive(Debug, Clone)]
+pub struct PaperTrader {
+    next_order_id: OrderId,
+    orders: BTreeMap<OrderId, PaperOrder>,
+    /// Accounts by name, ordered so margin calls are raised in name order
+    accounts: BTreeMap<String, PaperAccount>,
+    fills: CircularBuffer<PaperFill>,
+    margin: Option<MarginConfig>,
+    margin_calls: CircularBuffer<MarginCall>,
+    /// Accounts currently on margin call
+    on_call: HashSet<String>,
+    velocity: Option<NotionalThrottle>,
+    /// Contract the accounts trade, for valuing fills and positions
+    contract: ContractSpec,
+}
+
+impl PaperTrader {
+    /// Number of fills kept in the history by default
+    pub const DEFAULT_FILL_HISTORY: usize = 10_000;
+
+    /// Create a paper trader keeping up to `fill_history` recent fills
+    pub fn new(fill_history: usize) -> Self {
+        Self {
+            next_order_id: 1,
+            orders: BTreeMap::new(),
+            accounts: BTreeMap::new(),
+            fills: CircularBuffer::new(fill_history),
+            margin: None,
+            margin_calls: CircularBuffer::new(fill_history),
+            on_call: HashSet::new(),
+            velocity: None,
+            contract: ContractSpec::default(),
+        }
+    }
+
+    /// Enforce margin on paper accounts
+    pub fn with_margin(mut self, config: MarginConfig) -> Self {
+        self.set_margin(Some(config));
+        self
+    }
+
+    /// Enable or disable margin; accounts that already exist keep their cash
+    pub fn set_margin(&mut self, config: Option<MarginConfig>) {
+        self.margin = config.filter(|config| config.enabled);
+        self.on_call.clear();
+    }
+
+    /// Get the margin configuration if margin is enforced
+    pub fn margin(&self) -> Option<&MarginConfig> {
+        self.margin.as_ref()
+    }
+
+    /// Value fills and positions as this contract
+    pub fn with_contract(mut self, contract: ContractSpec) -> Self {
+        self.set_contract(contract);
+        self
+    }
+
+    /// Change the contract; existing accounts keep their cash and positions
+    pub fn set_contract(&mut self, contract: ContractSpec) {
+        self.contract = contract;
+    }
+
+    /// Get the contract accounts are valued in
+    pub fn contract(&self) -> &ContractSpec {
+        &self.contract
+    }
+
+    /// Limit the notional each account submits per rolling window
+    pub fn with_velocity(mut self, config: VelocityConfig) -> Self {
+        self.set_velocity(Some(config));
+        self
+    }
+
+    /// Enable or disable the velocity limit, starting every window afresh
+    pub fn set_velocity(&mut self, config: Option<VelocityConfig>) {
+        self.velocity = config.filter(|config| config.enabled).map(NotionalThrottle::new);
+    }
+
+    /// Get the velocity configuration if the limit is enforced
+    pub fn velocity(&self) -> Option<&VelocityConfig> {
+        self.velocity.as_ref().map(NotionalThrottle::config)
+    }
+
+    /// Velocity counters for an account
+    pub fn velocity_counters(&self, account: &str) -> Option<VelocityCounters> {
+        self.velocity.as_ref()?.counters(account)
+    }
+
+    /// Velocity counters for every account that has submitted
+    pub fn all_velocity_counters(&self) -> HashMap<String, VelocityCounters> {
+        self.velocity.as_ref().map(|velocity| velocity.all_counters().clone()).unwrap_or_default()
+    }
+
+    /// Submit a paper order for an account
+    ///
+    /// A market order (`price` of `None`) fills what it can against the
+    /// displayed depth and drops the rest. A limit order fills through the
+    /// depth up to its price, then rests behind the book's existing quantity
+    /// at that price.
+    pub fn submit(
+        &mut self,
+        account: &str,
+        side: Side,
+        qty: Qty,
+        price: Option<Price>,
+        book: &DepthSnapshot,
+        ts: u128,
+    ) -> EngineResult<PaperSubmission> {
+        if qty == 0 {
+            return Err(EngineError::InvalidQty { qty });
+        }
+        if qty > MAX_QTY {
+            return Err(EngineError::QtyTooLarge { qty, max_qty: MAX_QTY });
+        }
+        if price == Some(0) {
+            return Err(EngineError::InvalidPrice { price: 0 });
+        }
+        if let Some(price) = price.filter(|price| !(MIN_PRICE..=MAX_PRICE).contains(price)) {
+            return Err(EngineError::PriceOutOfRange { price, min_price: MIN_PRICE, max_price: MAX_PRICE });
+        }
+        if account.is_empty() {
+            return Err(EngineError::reject("paper orders need an account"));
+        }
+
+        let opposite = match side {
+            Side::Buy => &book.asks,
+            Side::Sell => &book.bids,
+        };
+        let mut remaining = qty;
+        let mut executions = Vec::new();
+        for level in opposite {
+            if remaining == 0 {
+                break;
+            }
+            let within_limit = match (side, price) {
+                (_, None) => true,
+                (Side::Buy, Some(limit)) => level.price <= limit,
+                (Side::Sell, Some(limit)) => level.price >= limit,
+            };
+            if !within_limit {
+                break;
+            }
+            let fill_qty = remaining.min(level.qty);
+            if fill_qty == 0 {
+                continue;
+            }
+            remaining -= fill_qty;
+            executions.push((level.price, fill_qty));
+        }
+
+        // The account has to be able to hold the order filled in full: its
+        // fills so far, and a limit order's remainder at its limit
+        let (position, cash) = self.projected_position(account, side);
+        let resting_qty = if price.is_some() { remaining } else { 0 };
+        let value = |qty: Qty, price: Price| self.contract.value_of(signed(side, qty) as i64, price).map(i128::from);
+        let filled_cash = executions
+            .iter()
+            .map(|&(fill_price, fill_qty)| value(fill_qty, fill_price))
+            .sum::<EngineResult<i128>>()?;
+        let resting_cash = match price {
+            Some(price) => value(resting_qty, price)?,
+            None => 0,
+        };
+        let projected_position = position + signed(side, qty - remaining + resting_qty);
+        let projected_cash = cash - filled_cash - resting_cash;
+        if i64::try_from(projected_position).is_err() || i64::try_from(projected_cash).is_err() {
+            let price = price.or(executions.last().map(|&(price, _)| price)).unwrap_or_default();
+            return Err(EngineError::ValueOverflow { qty, price });
+        }
+
+        if let Some(margin) = &self.margin {
+            // Market orders are priced at the worst level they reach
+            let worst_price = price.or(executions.last().map(|&(price, _)| price));
+            if let Some(order_price) = worst_price {
+                let mark = book.mid.map(price_utils::from_f64).unwrap_or(order_price);
+                let filled = qty - remaining;
+                let order_qty = if price.is_some() { qty } else { filled };
+                margin.check_order(&self.contract, saturate(position), saturate(cash), mark, side, order_qty, order_price)?;
+            }
+        }
+
+        if let Some(velocity) = &mut self.velocity {
+            // Limit orders count at their full size, market orders at what
+            // they fill
+            let contract = self.contract;
+            let notional = match price {
+                Some(price) => notional(&contract, qty, price),
+                None => executions.iter().map(|&(price, qty)| notional(&contract, qty, price)).sum(),
+            };
+            // Beyond u64 the order is over any limit, so it saturates
+            velocity.check(account, u64::try_from(notional).unwrap_or(u64::MAX), ts)?;
+        }
+
+        let order_id = self.next_order_id;
+        self.next_order_id += 1;
+
+        let fills: Vec<PaperFill> = executions
+            .into_iter()
+            .map(|(fill_price, fill_qty)| PaperFill {
+                order_id,
+                account: account.to_string(),
+                side,
+                price: fill_price,
+                qty: fill_qty,
+                ts,
+            })
+            .collect();
+        self.account_entry(account);
+        self.record_fills(&fills);
+
+        let resting = match price {
+            Some(price) if remaining > 0 => {
+                let own_side = match side {
+                    Side::Buy => &book.bids,
+                    Side::Sell => &book.asks,
+                };
+                let order = PaperOrder {
+                    id: order_id,
+                    account: account.to_string(),
+                    side,
+                    price,
+                    remaining,
+                    queue_ahead: level_qty(own_side, price),
+                    ts,
+                    crossing_taken: qty - remaining,
+                };
+                self.orders.insert(order_id, order.clone());
+                Some(order)
+            }
+            _ => None,
+        };
+
+        Ok(PaperSubmission { order_id, fills, resting })
+    }
+
+    /// Cancel a resting paper order
+    pub fn cancel(&mut self, order_id: OrderId) -> EngineResult<PaperOrder> {
+        self.orders
+            .remove(&order_id)
+            .ok_or(EngineError::UnknownOrder { order_id })
+    }
+
+    /// Advance resting paper orders with the trades and book state from a step
+    ///
+    /// Trades at an order's price first work through the quantity queued ahead
+    /// of it, then fill it. Trades through its price, or opposite quotes
+    /// crossing it, fill it at its limit. Whatever the book no longer shows at
+    /// the order's price can no longer be ahead of it.
+    pub fn on_market_update(&mut self, book: &DepthSnapshot, trades: &[Trade]) -> Vec<PaperFill> {
+        let mut fills = Vec::new();
+
+        for order in self.orders.values_mut() {
+            for trade in trades {
+                if order.remaining == 0 {
+                    break;
+                }
+                if !order.crossed_by(trade.price) {
+                    continue;
+                }
+                let fill_qty = if trade.price == order.price {
+                    let consumed = trade.qty.saturating_sub(order.queue_ahead);
+                    order.queue_ahead = order.queue_ahead.saturating_sub(trade.qty);
+                    consumed.min(order.remaining)
+                } else {
+                    order.queue_ahead = 0;
+                    order.remaining
+                };
+                if fill_qty > 0 {
+                    order.remaining -= fill_qty;
+                    fills.push(PaperFill {
+                        order_id: order.id,
+                        account: order.account.clone(),
+                        side: order.side,
+                        price: order.price,
+                        qty: fill_qty,
+                        ts: trade.ts,
+                    });
+                }
+            }
+
+            let (own_side, opposite) = match order.side {
+                Side::Buy => (&book.bids, &book.asks),
+                Side::Sell => (&book.asks, &book.bids),
+            };
+            let crossing: Qty = opposite
+                .iter()
+                .take_while(|level| order.crossed_by(level.price))
+                .map(|level| level.qty)
+                .sum();
+            order.crossing_taken = order.crossing_taken.min(crossing);
+            let fill_qty = (crossing - order.crossing_taken).min(order.remaining);
+            if fill_qty > 0 {
+                order.remaining -= fill_qty;
+                order.crossing_taken += fill_qty;
+                fills.push(PaperFill {
+                    order_id: order.id,
+                    account: order.account.clone(),
+                    side: order.side,
+                    price: order.price,
+                    qty: fill_qty,
+                    ts: book.ts,
+                });
+            }
+
+            order.queue_ahead = order.queue_ahead.min(level_qty(own_side, order.price));
+        }
+
+        self.orders.retain(|_, order| order.remaining > 0);
+        self.record_fills(&fills);
+        self.check_maintenance(book);
+        fills
+    }
+
+    /// Pay or receive funding on every account's position
+    pub fn settle_funding(&mut self, settlement: &FundingSettlement) {
+        for account in self.accounts.values_mut() {
+            let paid = settlement.payment(&self.contract, account.position);
+            account.cash -= paid;
+            account.funding -= paid;
+        }
+    }
+
+    fn record_fills(&mut self, fills: &[PaperFill]) {
+        for fill in fills {
+            let contract = self.contract;
+            self.account_entry(&fill.account).apply(&contract, fill);
+            self.fills.push(fill.clone());
+        }
+    }
+
+    fn account_entry(&mut self, account: &str) -> &mut PaperAccount {
+        let starting_cash = self.margin.as_ref().map_or(0, |margin| margin.starting_cash);
+        self.accounts.entry(account.to_string()).or_insert_with(|| PaperAccount {
+            cash: starting_cash,
+            ..PaperAccount::default()
+        })
+    }
+
+    /// Position and cash for an account if all its open orders on `side`
+    /// filled at their limits
+    fn projected_position(&self, account: &str, side: Side) -> (i128, i128) {
+        let (mut position, mut cash) = match self.accounts.get(account) {
+            Some(summary) => (summary.position as i128, summary.cash as i128),
+            None => (0, self.margin.as_ref().map_or(0, |margin| margin.starting_cash) as i128),
+        };
+        for order in self.orders.values().filter(|order| order.account == account && order.side == side) {
+            let signed_qty = signed(side, order.remaining);
+            position += signed_qty;
+            cash -= self.contract.saturating_value_of(signed_qty as i64, order.price) as i128;
+        }
+        (position, cash)
+    }
+
+    /// Re-value accounts at the book mid and raise a margin call, in account
+    /// name order, for each account that has newly fallen below maintenance
+    fn check_maintenance(&mut self, book: &DepthSnapshot) {
+        let (Some(margin), Some(mid)) = (&self.margin, book.mid) else {
+            return;
+        };
+        let mark = price_utils::from_f64(mid);
+        for (name, summary) in &self.accounts {
+            let status = margin.status(&self.contract, summary.position, summary.cash, mark);
+            if !status.margin_call {
+                self.on_call.remove(name);
+            } else if self.on_call.insert(name.clone()) {
+                tracing::warn!("Margin call for paper account {}: equity {} below maintenance {}",
+                               name, status.equity, status.maintenance_requirement);
+                self.margin_calls.push(MarginCall {
+                    account: name.clone(),
+                    equity: status.equity,
+                    maintenance_requirement: status.maintenance_requirement,
+                    mark,
+                    ts: book.ts,
+                });
+            }
+        }
+    }
+
+    /// Whether the layer has anything to update on each step
+    pub fn is_active(&self) -> bool {
+        !self.orders.is_empty() || (self.margin.is_some() && !self.accounts.is_empty())
+    }
+
+    /// Margin figures for an account at a mark price
+    pub fn margin_status(&self, account: &str, mark: Price) -> Option<MarginStatus> {
+        let margin = self.margin.as_ref()?;
+        let summary = self.accounts.get(account)?;
+        Some(margin.status(&self.contract, summary.position, summary.cash, mark))
+    }
+
+    /// Whether an account is currently on margin call
+    pub fn is_on_margin_call(&self, account: &str) -> bool {
+        self.on_call.contains(account)
+    }
+
+    /// Recent margin calls, oldest first
+    pub fn margin_calls(&self) -> Vec<MarginCall> {
+        self.margin_calls.to_vec()
+    }
+
+    /// Whether any paper orders are resting
+    pub fn has_open_orders(&self) -> bool {
+        !self.orders.is_empty()
+    }
+
+    /// Get a resting paper order
+    pub fn order(&self, order_id: OrderId) -> Option<&PaperOrder> {
+        self.orders.get(&order_id)
+    }
+
+    /// Resting paper orders for an account
+    pub fn open_orders(&self, account: &str) -> Vec<&PaperOrder> {
+        self.orders.values().filter(|order| order.account == account).collect()
+    }
+
+    /// Get a paper account
+    pub fn account(&self, account: &str) -> Option<&PaperAccount> {
+        self.accounts.get(account)
+    }
+
+    /// All paper accounts by name
+    pub fn accounts(&self) -> &BTreeMap<String, PaperAccount> {
+        &self.accounts
+    }
+
+    /// Recent fills for an account, oldest first
+    pub fn fills_for(&self, account: &str) -> Vec<PaperFill> {
+        self.fills.iter().filter(|fill| fill.account == account).cloned().collect()
+    }
+
+    /// Keep at most `size` fills and margin calls, evicting per `policy`
+    pub fn set_history(&mut self, size: usize, policy: EvictionPolicy) {
+        self.fills.set_capacity(size);
+        self.fills.set_policy(policy);
+        self.margin_calls.set_capacity(size);
+        self.margin_calls.set_policy(policy);
+    }
+
+    /// Occupancy of the fill and margin call histories
+    pub fn history_usage(&self) -> [(&'static str, HistoryUsage); 2] {
+        [("paper_fills", self.fills.usage()), ("margin_calls", self.margin_calls.usage())]
+    }
+
+    /// Drop the oldest half of the fill and margin call histories, returning
+    /// how many entries were dropped
+    pub fn shed_history(&mut self) -> usize {
+        self.fills.evict_oldest(self.fills.len() / 2)
+            + self.margin_calls.evict_oldest(self.margin_calls.len() / 2)
+    }
+
+    /// Release capacity beyond the fill and margin call budgets and left
+    /// spare in the margin call set
+    pub fn compact(&mut self) -> Compaction {
+        let on_call_before = self.on_call.capacity();
+        self.on_call.shrink_to_fit();
+        self.fills.compact()
+            + self.margin_calls.compact()
+            + Compaction::released::<String>(on_call_before, self.on_call.capacity())
+    }
+
+    /// Drop all paper orders, accounts and fills
+    pub fn reset(&mut self) {
+        self.next_order_id = 1;
+        self.orders.clear();
+        self.accounts.clear();
+        self.fills.clear();
+        self.margin_calls.clear();
+        self.on_call.clear();
+        if let Some(velocity) = &mut self.velocity {
+            velocity.reset();
+        }
+    }
+}
+
+impl Default for PaperTrader {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_FILL_HISTORY)
+    }
+}
+
+fn level_qty(levels: &[BookLevelPoint], price: Price) -> Qty {
+    levels
+        .iter()
+        .find(|level| level.price == price)
+        .map_or(0, |level| level.qty)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{OrderBook, OrderBookEngine};
+    use crate::queue_fifo::FifoLevel;
+    use crate::time::now_ns;
+    use crate::types::Order;
+
+    type TestOrderBook = OrderBook<FifoLevel>;
+
+    fn trade(price: Price, qty: Qty) -> Trade {
+        Trade { maker_id: 0, taker_id: 0, price, qty, ts: 0, aggressor: Side::Sell }
+    }
+
+    fn book() -> TestOrderBook {
+        let mut book = TestOrderBook::new();
+        book.place(Order::new_limit(1, Side::Buy, 100, 499000, now_ns())).unwrap();
+        book.place(Order::new_limit(2, Side::Sell, 50, 501000, now_ns())).unwrap();
+        book.place(Order::new_limit(3, Side::Sell, 80, 502000, now_ns())).unwrap();
+        book
+    }
+
+    #[test]
+    fn test_marketable_order_walks_depth_without_touching_book() {
+        let book = book();
+        let mut paper = PaperTrader::default();
+
+        let submission = paper
+            .submit("alice", Side::Buy, 100, Some(502000), &book.snapshot(), 1)
+            .unwrap();
+        assert_eq!(submission.fills.len(), 2);
+        assert_eq!((submission.fills[0].price, submission.fills[0].qty), (501000, 50));
+        assert_eq!((submission.fills[1].price, submission.fills[1].qty), (502000, 50));
+        assert!(submission.resting.is_none());
+
+        // The book still shows everything
+        assert_eq!(book.depth_at(Side::Sell, 501000), 50);
+        assert_eq!(book.depth_at(Side::Sell, 502000), 80);
+
+        let account = paper.account("alice").unwrap();
+        assert_eq!(account.position, 100);
+        assert_eq!(account.cash, -(50 * 501000 + 50 * 502000));
+        assert_eq!(paper.fills_for("alice").len(), 2);
+
+        // Market orders drop whatever the depth cannot fill
+        let submission = paper.submit("bob", Side::Sell, 150, None, &book.snapshot(), 2).unwrap();
+        assert_eq!(submission.fills.len(), 1);
+        assert_eq!(submission.fills[0].qty, 100);
+        assert!(!paper.has_open_orders());
+    }
+
+    #[test]
+    fn test_inverse_contract_accounts_in_base_units() {
+        let book = book();
+        let contract = ContractSpec::inverse(10_000);
+        let mut paper = PaperTrader::default().with_contract(contract);
+
+        paper.submit("alice", Side::Buy, 50, None, &book.snapshot(), 1).unwrap();
+        let account = paper.account("alice").unwrap();
+        assert_eq!(account.position, 50);
+        // 50 contracts of $1 at $50.10 are worth 50 / 50.10 of the base asset
+        assert_eq!(account.cash, 99_800_399);
+        assert_eq!(account.equity(&contract, Some(501000)), 0);
+        // The long loses base units as the price falls
+        assert!(account.equity(&contract, Some(499000)) < 0);
+        assert!(account.equity(&contract, Some(502000)) > 0);
+    }
+
+    #[test]
+    fn test_orders_beyond_engine_bounds_or_account_range_are_refused() {
+        let book = book();
+        let contract = ContractSpec { multiplier: 100, ..ContractSpec::default() };
+        let mut paper = PaperTrader::default().with_contract(contract);
+
+        let err = paper.submit("alice", Side::Buy, MAX_QTY + 1, None, &book.snapshot(), 1).unwrap_err();
+        assert!(matches!(err, EngineError::QtyTooLarge { .. }));
+        let err = paper.submit("alice", Side::Sell, 10, Some(MAX_PRICE + 1), &book.snapshot(), 1).unwrap_err();
+        assert!(matches!(err, EngineError::PriceOutOfRange { .. }));
+
+        // Selling the largest order at the highest price would take cash past i64
+        let err = paper.submit("alice", Side::Sell, MAX_QTY, Some(MAX_PRICE), &book.snapshot(), 1).unwrap_err();
+        assert!(matches!(err, EngineError::ValueOverflow { .. }));
+
+        // Half fits, and the open half counts against the next order
+        paper.submit("alice", Side::Sell, MAX_QTY / 2, Some(MAX_PRICE), &book.snapshot(), 1).unwrap();
+        let err = paper.submit("alice", Side::Sell, MAX_QTY / 2, Some(MAX_PRICE), &book.snapshot(), 2).unwrap_err();
+        assert!(matches!(err, EngineError::ValueOverflow { .. }));
+        assert_eq!(paper.open_orders("alice").len(), 1);
+    }
+
+    #[test]
+    fn test_crossing_liquidity_is_not_filled_twice() {
+        let mut book = book();
+        let mut paper = PaperTrader::default();
+
+        let submission = paper
+            .submit("alice", Side::Buy, 60, Some(501000), &book.snapshot(), 1)
+            .unwrap();
+        assert_eq!(submission.fills[0].qty, 50);
+        let order_id = submission.resting.unwrap().id;
+
+        // The ask it already took is still displayed
+        assert!(paper.on_market_update(&book.snapshot(), &[]).is_empty());
+
+        // New crossing liquidity fills the remainder at the paper limit
+        book.place(Order::new_limit(4, Side::Sell, 30, 501000, now_ns())).unwrap();
+        let fills = paper.on_market_update(&book.snapshot(), &[]);
+        assert_eq!((fills[0].price, fills[0].qty), (501000, 10));
+        assert!(paper.order(order_id).is_none());
+    }
+
+    #[test]
+    fn test_resting_order_waits_for_queue_ahead() {
+        let book = book();
+        let mut paper = PaperTrader::default();
+
+        let submission = paper
+            .submit("alice", Side::Buy, 40, Some(499000), &book.snapshot(), 1)
+            .unwrap();
+        let order = submission.resting.unwrap();
+        assert_eq!(order.queue_ahead, 100);
+
+        // 70 of the 100 ahead trades away
+        assert!(paper.on_market_update(&book.snapshot(), &[trade(499000, 70)]).is_empty());
+        assert_eq!(paper.order(order.id).unwrap().queue_ahead, 30);
+
+        // The next 50 clears the queue and fills 20
+        let fills = paper.on_market_update(&book.snapshot(), &[trade(499000, 50)]);
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].qty, 20);
+        assert_eq!(paper.order(order.id).unwrap().remaining, 20);
+
+        // A trade through the price fills the rest
+        let fills = paper.on_market_update(&book.snapshot(), &[trade(498000, 5)]);
+        assert_eq!(fills[0].qty, 20);
+        assert!(paper.order(order.id).is_none());
+        assert_eq!(paper.account("alice").unwrap().position, 40);
+    }
+
+    #[test]
+    fn test_cancellations_ahead_move_order_up() {
+        let mut book = book();
+        let mut paper = PaperTrader::default();
+
+        let order_id = paper
+            .submit("alice", Side::Buy, 10, Some(499000), &book.snapshot(), 1)
+            .unwrap()
+            .order_id;
+        book.cancel(1).unwrap();
+        paper.on_market_update(&book.snapshot(), &[]);
+        assert_eq!(paper.order(order_id).unwrap().queue_ahead, 0);
+
+        let fills = paper.on_market_update(&book.snapshot(), &[trade(499000, 4)]);
+        assert_eq!(fills[0].qty, 4);
+
+        paper.cancel(order_id).unwrap();
+        assert!(paper.cancel(order_id).is_err());
+    }
+
+    #[test]
+    fn test_margin_limits_orders_and_raises_calls() {
+        let book = book();
+        let margin = MarginConfig {
+            enabled: true,
+            starting_cash: 10_000_000,
+            initial_margin: 0.5,
+            maintenance_margin: 0.25,
+        };
+        let mut paper = PaperTrader::default().with_margin(margin);
+
+        // 2x leverage on 10,000,000 covers 40 at 499000 but not 41, counting
+        // the resting bid
+        paper.submit("alice", Side::Buy, 40, Some(499000), &book.snapshot(), 1).unwrap();
+        assert_eq!(paper.account("alice").unwrap().cash, 10_000_000);
+        assert!(paper.submit("alice", Side::Buy, 1, Some(499000), &book.snapshot(), 2).is_err());
+
+        paper.on_market_update(&book.snapshot(), &[trade(498000, 10)]);
+        assert_eq!(paper.account("alice").unwrap().position, 40);
+        assert!(paper.margin_calls().is_empty());
+
+        // The market falls away and equity drops below maintenance
+        let mut crashed = TestOrderBook::new();
+        crashed.place(Order::new_limit(1, Side::Buy, 100, 300000, now_ns())).unwrap();
+        crashed.place(Order::new_limit(2, Side::Sell, 100, 300200, now_ns())).unwrap();
+        paper.on_market_update(&crashed.snapshot(), &[]);
+        paper.on_market_update(&crashed.snapshot(), &[]);
+        assert!(paper.is_on_margin_call("alice"));
+        assert_eq!(paper.margin_calls().len(), 1);
+        assert!(paper.margin_status("alice", 300100).unwrap().margin_call);
+
+        // Selling down is still allowed while on call
+        paper.submit("alice", Side::Sell, 40, None, &crashed.snapshot(), 3).unwrap();
+        paper.on_market_update(&crashed.snapshot(), &[]);
+        assert!(!paper.is_on_margin_call("alice"));
+    }
+
+    #[test]
+    fn test_margin_calls_are_raised_in_account_order() {
+        let book = book();
+        let margin = MarginConfig {
+            enabled: true,
+            starting_cash: 10_000_000,
+            initial_margin: 0.5,
+            maintenance_margin: 0.25,
+        };
+        let mut paper = PaperTrader::default().with_margin(margin);
+        for (ts, account) in ["carol", "alice", "bob"].into_iter().enumerate() {
+            paper.submit(account, Side::Buy, 39, None, &book.snapshot(), ts as u128 + 1).unwrap();
+        }
+
+        let mut crashed = TestOrderBook::new();
+        crashed.place(Order::new_limit(1, Side::Buy, 100, 300000, now_ns())).unwrap();
+        crashed.place(Order::new_limit(2, Side::Sell, 100, 300200, now_ns())).unwrap();
+        paper.on_market_update(&crashed.snapshot(), &[]);
+        let accounts: Vec<String> = paper.margin_calls().into_iter().map(|call| call.account).collect();
+        assert_eq!(accounts, vec!["alice", "bob", "carol"]);
+    }
+
+    #[test]
+    fn test_velocity_limit_refuses_fast_submissions() {
+        let book = book();
+        let velocity = VelocityConfig {
+            enabled: true,
+            max_notional: 499000 * 30,
+            window_secs: 10,
+        };
+        let mut paper = PaperTrader::default().with_velocity(velocity);
+
+        paper.submit("alice", Side::Buy, 20, Some(499000), &book.snapshot(), 1).unwrap();
+        let err = paper.submit("alice", Side::Buy, 11, Some(499000), &book.snapshot(), 2).unwrap_err();
+        assert!(matches!(err, EngineError::VelocityLimit { window_notional, .. } if window_notional == 499000 * 20));
+        paper.submit("alice", Side::Buy, 10, Some(499000), &book.snapshot(), 3).unwrap();
+
+        let counters = paper.velocity_counters("alice").unwrap();
+        assert_eq!(counters.accepted, 2);
+        assert_eq!(counters.rejected, 1);
+        assert_eq!(paper.all_velocity_counters().len(), 1);
+    }
+}
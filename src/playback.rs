@@ -0,0 +1,445 @@
+//! Recorded sessions for instant replay
+//!
+//! An admin can record a window of the snapshots the server publishes, with
+//! the trades they carry, under a name. Clients can then ask for a recording
+//! to be played back over their own WebSocket at a chosen speed, to revisit
+//! an interesting market move in the UI. Playback only resends what was
+//! published; the engine is not involved.
+//!
+//! With `recordings.enabled`, each recording is also written to its own
+//! directory as a segmented log (see [`crate::segments`]) and loaded back at
+//! startup. The files are written by a thread of their own, fed over a
+//! channel, so publishing a snapshot never waits on the disk. A crash
+//! mid-write damages at most the last segment, whose unreadable tail is
+//! skipped with a warning when the recording is loaded.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::engine::DepthSnapshot;
+use crate::segments::{read_segments, CorruptTail, SegmentWriter, INDEX_FILE};
+
+/// Snapshots a recording keeps unless told otherwise
+pub const DEFAULT_MAX_SNAPSHOTS: usize = 10_000;
+
+/// Frames of a playback queued for a client before the playback waits on it
+pub const PLAYBACK_BUFFER: usize = 16;
+
+/// Where recordings are saved
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RecordingConfig {
+    /// Save recordings to disk and load them at startup
+    pub enabled: bool,
+    /// Directory holding one directory of segments per recording
+    pub dir: PathBuf,
+    /// Snapshots per segment file
+    pub segment_snapshots: u64,
+}
+
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: PathBuf::from("recordings"),
+            segment_snapshots: 1_000,
+        }
+    }
+}
+
+impl RecordingConfig {
+    /// Check the directory and segment size
+    pub fn validate(&self) -> Result<(), String> {
+        if self.dir.as_os_str().is_empty() {
+            return Err("Recordings directory cannot be empty".to_string());
+        }
+        if self.segment_snapshots == 0 {
+            return Err("Recording segments must hold at least one snapshot".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Summary of a recording
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RecordingInfo {
+    pub name: String,
+    pub snapshots: usize,
+    /// Timestamps of the first and last snapshot recorded
+    pub start_ts: Option<u128>,
+    pub end_ts: Option<u128>,
+    /// Still taking snapshots
+    pub recording: bool,
+    /// Segment tails skipped as unreadable when the recording was loaded
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub corrupt: Vec<CorruptTail>,
+}
+
+/// Published snapshots recorded under a name
+#[derive(Debug, Clone)]
+pub struct Recording {
+    name: String,
+    /// Shared with playbacks in progress, so starting one copies no snapshot
+    snapshots: Vec<Arc<DepthSnapshot>>,
+    max_snapshots: usize,
+    /// Length of the window in snapshot time, from the first snapshot
+    duration_ns: Option<u128>,
+    active: bool,
+    corrupt: Vec<CorruptTail>,
+}
+
+impl Recording {
+    /// Name the recording is kept under
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Snapshots in the order they were published
+    pub fn snapshots(&self) -> &[Arc<DepthSnapshot>] {
+        &self.snapshots
+    }
+
+    /// Summarize the recording
+    pub fn info(&self) -> RecordingInfo {
+        RecordingInfo {
+            name: self.name.clone(),
+            snapshots: self.snapshots.len(),
+            start_ts: self.snapshots.first().map(|snapshot| snapshot.ts),
+            end_ts: self.snapshots.last().map(|snapshot| snapshot.ts),
+            recording: self.active,
+            corrupt: self.corrupt.clone(),
+        }
+    }
+
+    /// Wait before each snapshot when played back at `speed` times the
+    /// recorded pace; the first is sent at once
+    pub fn pacing(&self, speed: f64) -> Vec<Duration> {
+        let mut previous = self.snapshots.first().map_or(0, |snapshot| snapshot.ts);
+        self.snapshots.iter()
+            .map(|snapshot| {
+                let gap = snapshot.ts.saturating_sub(previous);
+                previous = snapshot.ts;
+                Duration::from_nanos((gap as f64 / speed) as u64)
+            })
+            .collect()
+    }
+
+    /// Take a snapshot unless the window is over, stopping once it is or
+    /// the recording is full; returns whether it was taken
+    fn record(&mut self, snapshot: &DepthSnapshot) -> bool {
+        let window_over = self.snapshots.first()
+            .zip(self.duration_ns)
+            .is_some_and(|(first, duration)| snapshot.ts.saturating_sub(first.ts) > duration);
+        if !window_over {
+            self.snapshots.push(Arc::new(snapshot.clone()));
+        }
+        self.active = !window_over && self.snapshots.len() < self.max_snapshots;
+        !window_over
+    }
+
+    /// Load a recording saved in `dir`, skipping unreadable segment tails
+    fn load(name: &str, dir: &Path) -> std::io::Result<Self> {
+        let read = read_segments(dir)?;
+        let snapshots = read.records.iter()
+            .map(|record| serde_json::from_slice::<DepthSnapshot>(record).map(Arc::new))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            name: name.to_string(),
+            max_snapshots: snapshots.len(),
+            snapshots,
+            duration_ns: None,
+            active: false,
+            corrupt: read.corrupt,
+        })
+    }
+}
+
+/// Work for the thread that saves recordings, done in the order sent
+#[derive(Debug)]
+enum DiskOp {
+    /// Start a new log for a recording, replacing any saved copy
+    Start { name: String, dir: PathBuf },
+    Append { name: String, snapshot: Box<DepthSnapshot> },
+    /// Seal the log of a recording that stopped
+    Finish { name: String },
+    /// Delete a recording's saved copy
+    Remove { name: String, dir: PathBuf },
+    /// Reply once everything sent before has been written
+    Sync(mpsc::SyncSender<()>),
+}
+
+/// Save recordings as `ops` arrive, until every sender is gone
+fn write_recordings(ops: mpsc::Receiver<DiskOp>, segment_snapshots: u64) {
+    let mut writers = BTreeMap::new();
+    for op in ops {
+        match op {
+            DiskOp::Start { name, dir } => match SegmentWriter::create(dir, segment_snapshots) {
+                Ok(writer) => {
+                    writers.insert(name, writer);
+                }
+                // Keep recording in memory; there is no saved copy
+                Err(e) => tracing::warn!("Cannot save recording '{}': {}", name, e),
+            },
+            DiskOp::Append { name, snapshot } => {
+                let Some(writer) = writers.get_mut(&name) else {
+                    continue;
+                };
+                let appended = serde_json::to_vec(&snapshot).map_err(std::io::Error::from)
+                    .and_then(|json| writer.append(snapshot.ts, &json));
+                if let Err(e) = appended {
+                    // Keep recording in memory; the saved copy ends here
+                    tracing::warn!("Failed to save snapshot of recording '{}': {}", name, e);
+                    writers.remove(&name);
+                }
+            }
+            DiskOp::Finish { name } => {
+                if let Some(Err(e)) = writers.remove(&name).map(SegmentWriter::finish) {
+                    tracing::warn!("Failed to seal recording '{}': {}", name, e);
+                }
+            }
+            DiskOp::Remove { name, dir } => {
+                writers.remove(&name);
+                if dir.exists() {
+                    if let Err(e) = fs::remove_dir_all(&dir) {
+                        tracing::warn!("Failed to delete {}: {}", dir.display(), e);
+                    }
+                }
+            }
+            DiskOp::Sync(done) => {
+                let _ = done.send(());
+            }
+        }
+    }
+}
+
+/// Hand work to the disk thread behind `disk`, if there is one
+fn send_to(disk: &Option<mpsc::Sender<DiskOp>>, op: DiskOp) {
+    if disk.as_ref().is_some_and(|disk| disk.send(op).is_err()) {
+        tracing::warn!("Recording writer has stopped; the saved copies are incomplete");
+    }
+}
+
+/// Recordings by name, fed every published snapshot
+///
+/// Clones share the thread saving to disk, when there is one.
+#[derive(Debug, Clone, Default)]
+pub struct SessionRecorder {
+    recordings: BTreeMap<String, Recording>,
+    /// Where recordings are saved, when they are
+    saved: Option<RecordingConfig>,
+    /// Channel to the thread writing the saved copies
+    disk: Option<mpsc::Sender<DiskOp>>,
+}
+
+impl SessionRecorder {
+    /// Start with no recordings
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Save recordings under `config.dir`, starting with those already there
+    pub fn saved(config: RecordingConfig) -> std::io::Result<Self> {
+        fs::create_dir_all(&config.dir)?;
+        let mut recordings = BTreeMap::new();
+        for entry in fs::read_dir(&config.dir)? {
+            let path = entry?.path();
+            let Some(name) = path.file_name().and_then(|name| name.to_str()).map(str::to_string) else {
+                continue;
+            };
+            if !path.join(INDEX_FILE).is_file() {
+                continue;
+            }
+            match Recording::load(&name, &path) {
+                Ok(recording) => {
+                    recordings.insert(name, recording);
+                }
+                Err(e) => tracing::warn!("Skipping recording {}: {}", path.display(), e),
+            }
+        }
+        let (disk, ops) = mpsc::channel();
+        let segment_snapshots = config.segment_snapshots;
+        std::thread::Builder::new()
+            .name("recordings".to_string())
+            .spawn(move || write_recordings(ops, segment_snapshots))?;
+        Ok(Self { recordings, saved: Some(config), disk: Some(disk) })
+    }
+
+    /// Hand work to the disk thread, if recordings are saved
+    fn send(&self, op: DiskOp) {
+        send_to(&self.disk, op);
+    }
+
+    /// Wait until everything sent to disk so far has been written
+    pub fn sync(&self) {
+        let (done, written) = mpsc::sync_channel(1);
+        self.send(DiskOp::Sync(done));
+        let _ = written.recv();
+    }
+
+    /// Start recording under `name`, replacing a finished recording of that
+    /// name, for at most `max_snapshots` snapshots and, if set, `duration_ns`
+    /// of snapshot time
+    pub fn start(&mut self, name: &str, max_snapshots: usize, duration_ns: Option<u128>) -> Result<(), String> {
+        if name.trim().is_empty() {
+            return Err("Recording name must not be empty".to_string());
+        }
+        if max_snapshots == 0 {
+            return Err("A recording must keep at least one snapshot".to_string());
+        }
+        if self.recordings.get(name).is_some_and(|recording| recording.active) {
+            return Err(format!("Recording '{}' is already in progress", name));
+        }
+        if let Some(config) = &self.saved {
+            if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                return Err("Saved recording names may only use letters, digits, '-' and '_'".to_string());
+            }
+            self.send(DiskOp::Start { name: name.to_string(), dir: config.dir.join(name) });
+        }
+        self.recordings.insert(name.to_string(), Recording {
+            name: name.to_string(),
+            snapshots: Vec::new(),
+            max_snapshots,
+            duration_ns,
+            active: true,
+            corrupt: Vec::new(),
+        });
+        Ok(())
+    }
+
+    /// Stop taking snapshots for a recording
+    pub fn stop(&mut self, name: &str) -> Option<RecordingInfo> {
+        let recording = self.recordings.get_mut(name)?;
+        recording.active = false;
+        let info = recording.info();
+        self.finish(name);
+        Some(info)
+    }
+
+    /// Seal the saved log of a recording that stopped
+    fn finish(&self, name: &str) {
+        self.send(DiskOp::Finish { name: name.to_string() });
+    }
+
+    /// Whether any recording is taking snapshots
+    pub fn is_recording(&self) -> bool {
+        self.recordings.values().any(|recording| recording.active)
+    }
+
+    /// Offer a published snapshot to every active recording
+    pub fn record(&mut self, snapshot: &DepthSnapshot) {
+        let mut finished = Vec::new();
+        for recording in self.recordings.values_mut().filter(|recording| recording.active) {
+            if recording.record(snapshot) && self.disk.is_some() {
+                send_to(&self.disk, DiskOp::Append { name: recording.name.clone(), snapshot: Box::new(snapshot.clone()) });
+            }
+            if !recording.active {
+                finished.push(recording.name.clone());
+            }
+        }
+        for name in finished {
+            self.finish(&name);
+        }
+    }
+
+    /// Get a recording by name
+    pub fn get(&self, name: &str) -> Option<&Recording> {
+        self.recordings.get(name)
+    }
+
+    /// Delete a recording, and its saved copy
+    pub fn remove(&mut self, name: &str) -> Option<RecordingInfo> {
+        let recording = self.recordings.remove(name)?;
+        if let Some(config) = &self.saved {
+            self.send(DiskOp::Remove { name: name.to_string(), dir: config.dir.join(name) });
+        }
+        Some(recording.info())
+    }
+
+    /// Summaries of every recording, by name
+    pub fn list(&self) -> Vec<RecordingInfo> {
+        self.recordings.values().map(Recording::info).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{OrderBook, OrderBookEngine};
+    use crate::queue_fifo::FifoLevel;
+
+    fn snapshot(ts: u128) -> DepthSnapshot {
+        DepthSnapshot { ts, ..OrderBook::<FifoLevel>::new().snapshot() }
+    }
+
+    #[test]
+    fn test_records_a_window_and_paces_playback() {
+        let mut recorder = SessionRecorder::new();
+        assert!(recorder.start("", 10, None).is_err());
+        recorder.start("spike", 10, Some(2_000_000)).unwrap();
+        assert!(recorder.start("spike", 10, None).is_err());
+        recorder.start("short", 2, None).unwrap();
+
+        for ts in [1_000_000, 2_000_000, 3_000_000, 4_000_000] {
+            recorder.record(&snapshot(ts));
+        }
+        // The window ends 2ms after the first snapshot; the other recording fills up
+        let spike = recorder.get("spike").unwrap();
+        assert_eq!(spike.info(), RecordingInfo {
+            name: "spike".to_string(),
+            snapshots: 3,
+            start_ts: Some(1_000_000),
+            end_ts: Some(3_000_000),
+            recording: false,
+            corrupt: Vec::new(),
+        });
+        assert_eq!(recorder.get("short").unwrap().snapshots().len(), 2);
+        assert!(!recorder.is_recording());
+
+        // Twice as fast halves the gaps
+        assert_eq!(spike.pacing(2.0), vec![Duration::ZERO, Duration::from_micros(500), Duration::from_micros(500)]);
+
+        // Finished recordings can be replaced or removed
+        recorder.start("spike", 10, None).unwrap();
+        assert_eq!(recorder.stop("spike").map(|info| info.snapshots), Some(0));
+        assert_eq!(recorder.remove("short").map(|info| info.snapshots), Some(2));
+        assert_eq!(recorder.list().len(), 1);
+    }
+
+    #[test]
+    fn test_saved_recordings_survive_a_crash() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = RecordingConfig { enabled: true, dir: dir.path().to_path_buf(), segment_snapshots: 2 };
+        let mut recorder = SessionRecorder::saved(config.clone()).unwrap();
+        assert!(recorder.start("../escape", 10, None).is_err());
+        recorder.start("spike", 10, None).unwrap();
+        for ts in 1..=5 {
+            recorder.record(&snapshot(ts * 1_000_000));
+        }
+
+        // The process dies mid-write, leaving half of the last snapshot
+        recorder.sync();
+        drop(recorder);
+        let tail = dir.path().join("spike").join("segment-000002.seg");
+        let bytes = fs::read(&tail).unwrap();
+        fs::write(&tail, &bytes[..bytes.len() / 2]).unwrap();
+
+        let mut recorder = SessionRecorder::saved(config).unwrap();
+        let info = recorder.get("spike").unwrap().info();
+        assert_eq!((info.snapshots, info.end_ts, info.recording), (4, Some(4_000_000), false));
+        assert_eq!(info.corrupt.len(), 1);
+        assert_eq!(info.corrupt[0].file, "segment-000002.seg");
+
+        // Deleting and starting again under the name happen in that order
+        recorder.remove("spike").unwrap();
+        recorder.start("spike", 10, None).unwrap();
+        recorder.record(&snapshot(9_000_000));
+        recorder.sync();
+        assert_eq!(read_segments(&dir.path().join("spike")).unwrap().records.len(), 1);
+        recorder.remove("spike").unwrap();
+        recorder.sync();
+        assert!(!dir.path().join("spike").exists());
+    }
+}
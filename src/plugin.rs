@@ -0,0 +1,312 @@
+//! Queue discipline and agent plugins
+//!
+//! The server picks its queue discipline by name
+//! (`simulation.queue_discipline`) and builds `[[agents]]` entries by kind.
+//! A crate depending on this one adds names of its own without changes here:
+//! it implements [`QueueDiscipline`] or [`SyntheticAgent`], registers a
+//! constructor for it with [`Plugins`], builds its engine with
+//! [`Plugins::book`] and starts the server with
+//! [`start_server_with_plugins`](crate::server::start_server_with_plugins).
+//! `examples/queue_plugin.rs` registers a LIFO discipline and a quoting agent
+//! this way.
+//!
+//! A discipline's [`QueueDiscipline::orders`] listing is load-bearing: the
+//! engine reads it to tell filled makers from partly filled ones, to apply
+//! match filters and self-match prevention, and to find orders for cancels
+//! and quotes. It must list every order the level holds with its remaining
+//! quantity, and `resting` must agree with it if overridden.
+//!
+//! The book behind a registered name is an `OrderBook<DynLevel>`, whose price
+//! levels are boxed and dispatched at run time; code that fixes its
+//! discipline at compile time keeps using `OrderBook<FifoLevel>` and the like,
+//! and the server runs either. Factories and constructors are closures, so
+//! they can capture the configuration they build with.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+use rand::rngs::StdRng;
+use crate::agents::{AgentRegistry, SyntheticAgent};
+use crate::engine::OrderBook;
+use crate::queue::QueueDiscipline;
+use crate::queue_fifo::FifoLevel;
+use crate::queue_priority::PriorityFeeLevel;
+use crate::sim::{MarketMakerConfig, OrderGenerationConfig};
+use crate::types::{Order, OrderId, Price, Qty, Side, Trade};
+
+/// A price level of a discipline chosen at run time
+pub type DynLevel = Box<dyn QueueDiscipline + Send>;
+
+/// Creates an empty price level of one discipline, with whatever
+/// configuration it captured when registered
+pub type LevelFactory = Arc<dyn Fn() -> DynLevel + Send + Sync>;
+
+/// Builds one agent from an `[[agents]]` entry's parameters and its own
+/// random generator, with whatever configuration it captured when registered
+pub type AgentConstructor = Arc<dyn Fn(&toml::Table, StdRng) -> Result<Box<dyn SyntheticAgent>, String> + Send + Sync>;
+
+/// Plugin lookup error types
+#[derive(Debug, thiserror::Error)]
+pub enum PluginError {
+    #[error("Unknown queue discipline '{name}', expected one of: {known}")]
+    UnknownDiscipline { name: String, known: String },
+}
+
+impl QueueDiscipline for DynLevel {
+    fn enqueue(&mut self, order: Order) {
+        (**self).enqueue(order)
+    }
+
+    fn match_against(&mut self, taker_id: OrderId, taker_side: Side, taker_qty: Qty, price: Price) -> (Qty, Vec<Trade>) {
+        (**self).match_against(taker_id, taker_side, taker_qty, price)
+    }
+
+    fn match_against_filtered(
+        &mut self,
+        taker_id: OrderId,
+        taker_side: Side,
+        taker_qty: Qty,
+        price: Price,
+        filter: &dyn Fn(OrderId, OrderId) -> bool,
+    ) -> (Qty, Vec<Trade>) {
+        (**self).match_against_filtered(taker_id, taker_side, taker_qty, price, filter)
+    }
+
+    fn match_against_at(&mut self, taker_id: OrderId, taker_side: Side, taker_qty: Qty, price: Price, ts: u128) -> (Qty, Vec<Trade>) {
+        (**self).match_against_at(taker_id, taker_side, taker_qty, price, ts)
+    }
+
+    fn match_against_filtered_at(
+        &mut self,
+        taker_id: OrderId,
+        taker_side: Side,
+        taker_qty: Qty,
+        price: Price,
+        ts: u128,
+        filter: &dyn Fn(OrderId, OrderId) -> bool,
+    ) -> (Qty, Vec<Trade>) {
+        (**self).match_against_filtered_at(taker_id, taker_side, taker_qty, price, ts, filter)
+    }
+
+    fn cancel(&mut self, order_id: OrderId) -> Qty {
+        (**self).cancel(order_id)
+    }
+
+    fn total_qty(&self) -> Qty {
+        (**self).total_qty()
+    }
+
+    fn is_empty(&self) -> bool {
+        (**self).is_empty()
+    }
+
+    fn touch(&mut self, ts: u128) {
+        (**self).touch(ts)
+    }
+
+    fn last_ts(&self) -> u128 {
+        (**self).last_ts()
+    }
+
+    fn order_count(&self) -> usize {
+        (**self).order_count()
+    }
+
+    fn oldest_order_ts(&self) -> Option<u128> {
+        (**self).oldest_order_ts()
+    }
+
+    fn resting(&self) -> Vec<(OrderId, Qty)> {
+        (**self).resting()
+    }
+
+    fn orders(&self) -> Vec<Order> {
+        (**self).orders()
+    }
+
+    fn pay_priority_fee(&mut self, order_id: OrderId, fee: u64) -> u64 {
+        (**self).pay_priority_fee(order_id, fee)
+    }
+
+    fn priority_fee_paid(&self, order_id: OrderId) -> u64 {
+        (**self).priority_fee_paid(order_id)
+    }
+}
+
+/// A FIFO level, so `OrderBook::<DynLevel>::new()` behaves like the default book
+impl Default for DynLevel {
+    fn default() -> Self {
+        Box::new(FifoLevel::default())
+    }
+}
+
+/// Queue disciplines by name
+#[derive(Clone, Default)]
+pub struct DisciplineRegistry {
+    factories: BTreeMap<String, LevelFactory>,
+}
+
+impl fmt::Debug for DisciplineRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.names()).finish()
+    }
+}
+
+impl DisciplineRegistry {
+    /// An empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The built-in `fifo` and `priority_fee` disciplines
+    pub fn builtin() -> Self {
+        let mut registry = Self::new();
+        registry.register("fifo", || Box::new(FifoLevel::default()));
+        registry.register("priority_fee", || Box::new(PriorityFeeLevel::default()));
+        registry
+    }
+
+    /// Register or replace the discipline called `name`
+    pub fn register<F>(&mut self, name: impl Into<String>, factory: F) -> &mut Self
+    where
+        F: Fn() -> DynLevel + Send + Sync + 'static,
+    {
+        self.factories.insert(name.into(), Arc::new(factory));
+        self
+    }
+
+    /// Registered names, sorted
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.factories.keys().map(String::as_str)
+    }
+
+    /// Level factory of the discipline called `name`
+    pub fn factory(&self, name: &str) -> Result<LevelFactory, PluginError> {
+        self.factories.get(name).cloned().ok_or_else(|| PluginError::UnknownDiscipline {
+            name: name.to_string(),
+            known: self.names().collect::<Vec<_>>().join(", "),
+        })
+    }
+}
+
+/// Disciplines and agent kinds a downstream crate adds to the built-in ones
+#[derive(Clone)]
+pub struct Plugins {
+    disciplines: DisciplineRegistry,
+    agents: BTreeMap<String, AgentConstructor>,
+}
+
+impl fmt::Debug for Plugins {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Plugins")
+            .field("disciplines", &self.disciplines)
+            .field("agents", &self.agents.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Default for Plugins {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Plugins {
+    /// The built-in disciplines and agent kinds only
+    pub fn new() -> Self {
+        Self {
+            disciplines: DisciplineRegistry::builtin(),
+            agents: BTreeMap::new(),
+        }
+    }
+
+    /// Add a queue discipline, replacing any registered under `name`
+    pub fn with_discipline<F>(mut self, name: impl Into<String>, factory: F) -> Self
+    where
+        F: Fn() -> DynLevel + Send + Sync + 'static,
+    {
+        self.disciplines.register(name, factory);
+        self
+    }
+
+    /// Add an agent kind for `[[agents]]` entries, replacing any registered
+    /// under `kind`, built-in kinds included
+    pub fn with_agent<F>(mut self, kind: impl Into<String>, constructor: F) -> Self
+    where
+        F: Fn(&toml::Table, StdRng) -> Result<Box<dyn SyntheticAgent>, String> + Send + Sync + 'static,
+    {
+        self.agents.insert(kind.into(), Arc::new(constructor));
+        self
+    }
+
+    /// Registered queue disciplines
+    pub fn disciplines(&self) -> &DisciplineRegistry {
+        &self.disciplines
+    }
+
+    /// An empty book whose levels follow the discipline called `name`
+    pub fn book(&self, name: &str) -> Result<OrderBook<DynLevel>, PluginError> {
+        let factory = self.disciplines.factory(name)?;
+        Ok(OrderBook::with_level_factory(move || factory()))
+    }
+
+    /// The built-in agent kinds, defaulting to the given configs, and every
+    /// registered one
+    pub fn agent_registry(&self, market_maker: &MarketMakerConfig, order_generation: &OrderGenerationConfig) -> AgentRegistry {
+        let mut registry = AgentRegistry::builtin(market_maker, order_generation);
+        for (kind, constructor) in &self.agents {
+            let constructor = constructor.clone();
+            registry.register(kind.clone(), move |params, rng| constructor(params, rng));
+        }
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use crate::agents::{AgentOrder, AgentSpec, MarketView};
+    use crate::engine::OrderBookEngine;
+    use crate::sim::Agent;
+    use crate::time::now_ns;
+
+    struct Idle;
+
+    impl SyntheticAgent for Idle {
+        fn role(&self) -> Agent {
+            Agent::Taker
+        }
+
+        fn on_step(&mut self, _market: &MarketView<'_>) -> Vec<AgentOrder> {
+            Vec::new()
+        }
+    }
+
+    #[test]
+    fn test_registered_discipline_and_agent_are_found_by_name() {
+        let levels = Arc::new(AtomicUsize::new(0));
+        let built = levels.clone();
+        let plugins = Plugins::new()
+            .with_discipline("priority", move || {
+                built.fetch_add(1, Ordering::Relaxed);
+                Box::new(PriorityFeeLevel::default())
+            })
+            .with_agent("idle", |_, _| Ok(Box::new(Idle)));
+
+        let mut book = plugins.book("priority").unwrap();
+        book.place(Order::new_limit(1, Side::Sell, 10, 1_000_000, now_ns())).unwrap();
+        book.place(Order::new_limit(2, Side::Sell, 10, 1_000_000, now_ns())).unwrap();
+        assert_eq!(book.pay_priority_fee(2, 5).unwrap(), 5);
+        let trades = book.place(Order::new_market(3, Side::Buy, 10, now_ns())).unwrap();
+        assert_eq!(trades[0].maker_id, 2);
+        assert_eq!(levels.load(Ordering::Relaxed), 1);
+
+        let error = plugins.book("lifo").err().unwrap();
+        assert_eq!(error.to_string(), "Unknown queue discipline 'lifo', expected one of: fifo, priority, priority_fee");
+
+        let registry = plugins.agent_registry(&MarketMakerConfig::default(), &OrderGenerationConfig::default());
+        assert_eq!(registry.kinds().collect::<Vec<_>>(), vec!["adversary", "idle", "market_maker", "taker"]);
+        assert!(registry.create(&AgentSpec::new("idle")).is_ok());
+    }
+}
@@ -0,0 +1,304 @@
+//! Limit-up/limit-down price bands around a replayed reference price
+//!
+//! In hybrid mode synthetic flow trades against a book that also carries
+//! historical orders, and nothing stops it walking that book far away from
+//! where the historical market traded. [`PriceBands`] tracks a reference
+//! price from the replayed events, either a rolling average of their prices
+//! or the prior close, and bands it by a configured fraction on either side.
+//! A synthetic limit order priced through the band on its aggressive side is
+//! refused, and a market order is cut to the depth resting inside the band,
+//! so synthetic orders can only trade at prices within it.
+
+use std::collections::VecDeque;
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::data::MarketEvent;
+use crate::engine::DepthSnapshot;
+use crate::time::ms_to_ns;
+use crate::types::{Order, OrderType, Price, Qty, Side};
+
+/// Where the reference price comes from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReferenceSource {
+    /// Mean of the replayed trade prices and two-sided quote mids within
+    /// the last `window_ms`
+    #[default]
+    Rolling,
+    /// `prior_close`, or the first replayed price when it is not set
+    PriorClose,
+}
+
+/// Reference price and band width
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PriceBandConfig {
+    /// Enforce bands on synthetic orders in hybrid mode
+    pub enabled: bool,
+    pub reference: ReferenceSource,
+    /// Simulation time a rolling reference averages over (milliseconds)
+    pub window_ms: u64,
+    /// Band half-width as a fraction of the reference price
+    pub band_pct: f64,
+    /// Prior close in ticks for the `prior_close` reference
+    #[serde(default)]
+    pub prior_close: Option<Price>,
+}
+
+impl Default for PriceBandConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            reference: ReferenceSource::Rolling,
+            window_ms: 300_000, // 5 minutes
+            band_pct: 0.05,
+            prior_close: None,
+        }
+    }
+}
+
+impl PriceBandConfig {
+    /// Check the window and band width
+    pub fn validate(&self) -> Result<(), String> {
+        if self.window_ms == 0 {
+            return Err("Price band window must be greater than 0".to_string());
+        }
+        if !(self.band_pct > 0.0 && self.band_pct < 1.0) {
+            return Err("Price band percentage must be in (0.0, 1.0)".to_string());
+        }
+        if self.prior_close == Some(0) {
+            return Err("Price band prior close must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Prices synthetic orders may trade at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PriceBand {
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub reference: Price,
+    /// Lowest price a sell may trade at
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub lower: Price,
+    /// Highest price a buy may trade at
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub upper: Price,
+}
+
+impl PriceBand {
+    /// Band `band_pct` either side of `reference`
+    pub fn around(reference: Price, band_pct: f64) -> Self {
+        Self {
+            reference,
+            lower: (reference as f64 * (1.0 - band_pct)).ceil() as Price,
+            upper: (reference as f64 * (1.0 + band_pct)).floor() as Price,
+        }
+    }
+
+    pub fn contains(&self, price: Price) -> bool {
+        (self.lower..=self.upper).contains(&price)
+    }
+}
+
+/// Band checks so far
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PriceBandStats {
+    /// Orders checked against a band
+    pub checked: u64,
+    /// Limit orders priced through the band, and market orders with no
+    /// depth inside it
+    pub rejected: u64,
+    /// Market orders cut to the depth inside the band
+    pub trimmed: u64,
+    /// Quantity cut from those orders
+    pub trimmed_qty: u64,
+}
+
+/// Reference price tracked from replayed events and the band around it
+#[derive(Debug, Clone)]
+pub struct PriceBands {
+    config: PriceBandConfig,
+    /// Replayed prices within the rolling window, oldest first
+    prints: VecDeque<(u128, Price)>,
+    print_sum: u128,
+    /// First replayed price, standing in for a missing prior close
+    first_print: Option<Price>,
+    stats: PriceBandStats,
+}
+
+impl PriceBands {
+    pub fn new(config: PriceBandConfig) -> Self {
+        Self {
+            config,
+            prints: VecDeque::new(),
+            print_sum: 0,
+            first_print: None,
+            stats: PriceBandStats::default(),
+        }
+    }
+
+    /// Get the band configuration
+    pub fn config(&self) -> &PriceBandConfig {
+        &self.config
+    }
+
+    /// Get band check statistics
+    pub fn stats(&self) -> &PriceBandStats {
+        &self.stats
+    }
+
+    /// Take the price of a replayed trade or two-sided quote into the reference
+    pub fn observe(&mut self, event: &MarketEvent) {
+        let price = match *event {
+            MarketEvent::Trade { price, .. } => price,
+            MarketEvent::Quote { bid: Some(bid), ask: Some(ask), .. }
+            | MarketEvent::BestBidOffer { best_bid: Some(bid), best_ask: Some(ask), .. } => (bid + ask) / 2,
+            _ => return,
+        };
+        let ts = event.timestamp();
+        self.first_print.get_or_insert(price);
+        self.prints.push_back((ts, price));
+        self.print_sum += price as u128;
+        let window = ms_to_ns(self.config.window_ms);
+        while self.prints.front().is_some_and(|&(first, _)| first + window <= ts) {
+            let (_, dropped) = self.prints.pop_front().unwrap();
+            self.print_sum -= dropped as u128;
+        }
+    }
+
+    /// Current reference price, if the replay has supplied one yet
+    pub fn reference(&self) -> Option<Price> {
+        match self.config.reference {
+            ReferenceSource::Rolling if self.prints.is_empty() => None,
+            ReferenceSource::Rolling => Some((self.print_sum / self.prints.len() as u128) as Price),
+            ReferenceSource::PriorClose => self.config.prior_close.or(self.first_print),
+        }
+    }
+
+    /// Current band, if there is a reference to band
+    pub fn band(&self) -> Option<PriceBand> {
+        self.reference().map(|reference| PriceBand::around(reference, self.config.band_pct))
+    }
+
+    /// Check a synthetic order against the band, returning what may go to
+    /// the book
+    ///
+    /// Orders pass unchanged while there is no reference yet. A buy limit
+    /// above the band or a sell limit below it is refused; one on the passive
+    /// side of the band can only rest there. A market order is cut to the
+    /// opposite depth it can reach without trading through the band, and
+    /// refused if there is none;
+    /// `book` is only taken for market orders.
+    pub fn admit(&mut self, mut order: Order, book: impl FnOnce() -> DepthSnapshot) -> Option<Order> {
+        let Some(band) = self.band() else {
+            return Some(order);
+        };
+        self.stats.checked += 1;
+        match order.order_type {
+            OrderType::Limit { price } => {
+                let through = match order.side {
+                    Side::Buy => price > band.upper,
+                    Side::Sell => price < band.lower,
+                };
+                if through {
+                    self.stats.rejected += 1;
+                    return None;
+                }
+            }
+            OrderType::Market => {
+                let book = book();
+                let opposite = match order.side {
+                    Side::Buy => &book.asks,
+                    Side::Sell => &book.bids,
+                };
+                let inside: Qty = opposite.iter()
+                    .filter(|level| match order.side {
+                        Side::Buy => level.price <= band.upper,
+                        Side::Sell => level.price >= band.lower,
+                    })
+                    .map(|level| level.qty)
+                    .sum();
+                if inside == 0 {
+                    self.stats.rejected += 1;
+                    return None;
+                }
+                if inside < order.qty {
+                    self.stats.trimmed += 1;
+                    self.stats.trimmed_qty += order.qty - inside;
+                    order.qty = inside;
+                }
+            }
+        }
+        Some(order)
+    }
+
+    /// Forget the replayed prices, keeping the statistics
+    pub fn rewind(&mut self) {
+        self.prints.clear();
+        self.print_sum = 0;
+        self.first_print = None;
+    }
+
+    /// Forget the replayed prices and statistics
+    pub fn reset(&mut self) {
+        *self = Self::new(self.config.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{OrderBook, OrderBookEngine};
+    use crate::queue_fifo::FifoLevel;
+
+    fn trade(price: Price, ms: u64) -> MarketEvent {
+        MarketEvent::Trade { price, qty: 1, side: Side::Buy, timestamp: ms_to_ns(ms), trade_id: None }
+    }
+
+    #[test]
+    fn test_rolling_reference_follows_the_window() {
+        let config = PriceBandConfig { enabled: true, window_ms: 1_000, band_pct: 0.1, ..PriceBandConfig::default() };
+        let mut bands = PriceBands::new(config.clone());
+        assert_eq!(bands.band(), None);
+
+        bands.observe(&trade(1_000, 0));
+        bands.observe(&MarketEvent::Quote { bid: Some(1_100), ask: Some(1_300), bid_qty: None, ask_qty: None, timestamp: ms_to_ns(500) });
+        assert_eq!(bands.reference(), Some(1_100));
+        bands.observe(&trade(1_400, 1_200));
+        assert_eq!(bands.band(), Some(PriceBand { reference: 1_300, lower: 1_170, upper: 1_430 }));
+
+        let prior_close = PriceBandConfig { reference: ReferenceSource::PriorClose, ..config.clone() };
+        let mut bands = PriceBands::new(prior_close.clone());
+        bands.observe(&trade(1_000, 0));
+        bands.observe(&trade(2_000, 10));
+        assert_eq!(bands.reference(), Some(1_000));
+        assert_eq!(PriceBands::new(PriceBandConfig { prior_close: Some(900), ..prior_close }).reference(), Some(900));
+        assert!(PriceBandConfig { band_pct: 1.0, ..config }.validate().is_err());
+    }
+
+    #[test]
+    fn test_orders_through_the_band_are_refused_or_trimmed() {
+        let mut bands = PriceBands::new(PriceBandConfig { enabled: true, band_pct: 0.1, ..PriceBandConfig::default() });
+        let mut engine = OrderBook::<FifoLevel>::new();
+        engine.set_clock(0);
+        let order = Order::new_limit(1, Side::Buy, 10, 2_000, 0);
+        assert_eq!(bands.admit(order.clone(), || engine.snapshot()), Some(order));
+
+        bands.observe(&trade(1_000, 0));
+        assert_eq!(bands.admit(Order::new_limit(1, Side::Buy, 10, 1_101, 0), || engine.snapshot()), None);
+        assert!(bands.admit(Order::new_limit(2, Side::Buy, 10, 500, 0), || engine.snapshot()).is_some());
+        assert_eq!(bands.admit(Order::new_limit(3, Side::Sell, 10, 899, 0), || engine.snapshot()), None);
+
+        // An ask below the band is still reachable for a buy
+        for (id, price, qty) in [(10, 1_050, 4), (11, 1_100, 3), (12, 1_500, 100), (13, 800, 2)] {
+            engine.place(Order::new_limit(id, Side::Sell, qty, price, 0)).unwrap();
+        }
+        assert_eq!(bands.admit(Order::new_market(4, Side::Buy, 10, 0), || engine.snapshot()).unwrap().qty, 9);
+        assert_eq!(bands.admit(Order::new_market(5, Side::Sell, 10, 0), || engine.snapshot()), None);
+        assert_eq!(*bands.stats(), PriceBandStats { checked: 5, rejected: 3, trimmed: 1, trimmed_qty: 1 });
+
+        bands.rewind();
+        assert_eq!(bands.band(), None);
+        assert_eq!(bands.stats().checked, 5);
+    }
+}
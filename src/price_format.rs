@@ -0,0 +1,583 @@
+//! Price representation in the JSON APIs
+//!
+//! Prices are integer ticks of 10^-4 internally, which external consumers
+//! cannot read without knowing the scale. A [`PriceFormat`] chosen per
+//! connection or request decides how prices are written in outgoing messages:
+//! raw `ticks` (the default), `scaled` ticks with a `price_exponent`
+//! alongside, or `decimal` strings such as `"100.2500"`. Order entry accepts
+//! any of these forms whatever the output format: an integer is ticks, a
+//! string is a decimal, and `{"value": 10025, "exponent": -2}` is a scaled
+//! integer.
+//!
+//! Price fields are marked in their types, with
+//! `#[serde(serialize_with = "crate::price_format::ticks")]` for tick amounts
+//! and [`units`] for currency amounts such as a mid. They take the format of
+//! the enclosing [`PriceFormat::scope`] as they are serialized; outside one
+//! they are plain ticks, so binary encodings and saved state never change.
+//!
+//! Decimal output goes through a [`PricePrecision`]: the instrument's tick,
+//! the places to show and the rounding rule (half-even by default). Rendering
+//! works on exact decimal digits rather than `f64` division, so exports never
+//! show binary floating-point noise and ties always round the same way.
+
+use std::cell::Cell;
+use std::fmt;
+use std::marker::PhantomData;
+use std::str::FromStr;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use schemars::JsonSchema;
+use serde_json::Value;
+use crate::types::Price;
+
+/// Decimal places in a tick price
+pub const PRICE_DECIMALS: u32 = 4;
+
+/// Power of ten a tick price is scaled by
+pub const PRICE_EXPONENT: i32 = -(PRICE_DECIMALS as i32);
+
+thread_local! {
+    /// Format typed price fields serialized on this thread are written in
+    static RENDERING: Cell<Option<(PriceFormat, PricePrecision)>> = const { Cell::new(None) };
+}
+
+/// How a price with more precision than is shown gets rounded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Rounding {
+    /// To nearest, ties to the even neighbour
+    #[default]
+    HalfEven,
+    /// To nearest, ties away from zero
+    HalfUp,
+    /// Toward zero
+    Truncate,
+}
+
+impl Rounding {
+    /// `value / divisor` rounded by this rule; `divisor` must be positive
+    pub fn divide(&self, value: i128, divisor: i128) -> i128 {
+        let (quotient, remainder) = (value / divisor, value % divisor);
+        if remainder == 0 {
+            return quotient;
+        }
+        let twice = remainder.abs() * 2;
+        let away = match self {
+            Self::HalfEven => twice > divisor || (twice == divisor && quotient % 2 != 0),
+            Self::HalfUp => twice >= divisor,
+            Self::Truncate => false,
+        };
+        match (away, value < 0) {
+            (false, _) => quotient,
+            (true, false) => quotient + 1,
+            (true, true) => quotient - 1,
+        }
+    }
+}
+
+/// Tick, decimal places and rounding prices are rendered with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct PricePrecision {
+    /// Decimal places shown
+    pub decimals: u32,
+    /// Smallest price increment of the instrument, in engine ticks
+    pub tick: Price,
+    /// How prices between ticks or places are rounded
+    pub rounding: Rounding,
+}
+
+impl Default for PricePrecision {
+    fn default() -> Self {
+        Self {
+            decimals: PRICE_DECIMALS,
+            tick: 1,
+            rounding: Rounding::HalfEven,
+        }
+    }
+}
+
+/// Most decimal places a price is rendered with
+const MAX_DECIMALS: u32 = 12;
+
+impl PricePrecision {
+    /// Check the tick and places
+    pub fn validate(&self) -> Result<(), String> {
+        if self.tick == 0 {
+            return Err("Price tick must be greater than 0".to_string());
+        }
+        if self.decimals > MAX_DECIMALS {
+            return Err(format!("Price decimals cannot exceed {}", MAX_DECIMALS));
+        }
+        Ok(())
+    }
+
+    /// The same tick and rounding with a different number of places
+    pub fn with_decimals(self, decimals: u32) -> Self {
+        Self { decimals, ..self }
+    }
+
+    /// Render a price in engine ticks
+    pub fn format_price(&self, price: Price) -> String {
+        self.format_decimal(price as i128, PRICE_DECIMALS)
+    }
+
+    /// Render a signed amount of engine ticks
+    pub fn format_ticks(&self, ticks: i64) -> String {
+        self.format_decimal(ticks as i128, PRICE_DECIMALS)
+    }
+
+    /// Render a fractional amount of engine ticks, such as an average price
+    pub fn format_tick_fraction(&self, ticks: f64) -> Option<String> {
+        let (mantissa, scale) = decimal_digits(ticks)?;
+        Some(self.format_decimal(mantissa, scale + PRICE_DECIMALS))
+    }
+
+    /// Render a price in currency units, such as a mid
+    pub fn format_units(&self, units: f64) -> Option<String> {
+        let (mantissa, scale) = decimal_digits(units)?;
+        Some(self.format_decimal(mantissa, scale))
+    }
+
+    /// Render `mantissa * 10^-scale` currency units, snapped to the tick and
+    /// then rounded to the shown places
+    fn format_decimal(&self, mantissa: i128, scale: u32) -> String {
+        let pow = |exp: u32| 10_i128.pow(exp);
+        // Work at engine tick resolution or finer so the tick is whole
+        let scale_up = PRICE_DECIMALS.saturating_sub(scale);
+        let (mut mantissa, mut scale) = (mantissa * pow(scale_up), scale + scale_up);
+
+        let tick = self.tick.max(1) as i128 * pow(scale - PRICE_DECIMALS);
+        mantissa = self.rounding.divide(mantissa, tick) * tick;
+
+        let decimals = self.decimals.min(MAX_DECIMALS);
+        if decimals < scale {
+            mantissa = self.rounding.divide(mantissa, pow(scale - decimals));
+        } else {
+            mantissa *= pow(decimals - scale);
+        }
+        scale = decimals;
+
+        let sign = if mantissa < 0 { "-" } else { "" };
+        let (whole, fraction) = (mantissa.unsigned_abs() / pow(scale) as u128, mantissa.unsigned_abs() % pow(scale) as u128);
+        match scale {
+            0 => format!("{}{}", sign, whole),
+            _ => format!("{}{}.{:0width$}", sign, whole, fraction, width = scale as usize),
+        }
+    }
+}
+
+/// Exact decimal digits of the shortest representation of `value`
+///
+/// Returns `mantissa` and `scale` with `value == mantissa * 10^-scale`, or
+/// nothing for values that are not finite or too large to render.
+fn decimal_digits(value: f64) -> Option<(i128, u32)> {
+    if !value.is_finite() || value.abs() >= 1e20 {
+        return None;
+    }
+    // Display never uses an exponent and prints the shortest digits that
+    // read back as the same value
+    let text = value.abs().to_string();
+    let (whole, fraction) = text.split_once('.').unwrap_or((&text, ""));
+    // Places past these are below any tick and any f64 precision
+    let fraction = &fraction[..fraction.len().min(MAX_FRACTION_DIGITS)];
+    let mantissa: i128 = format!("{}{}", whole, fraction).parse().ok()?;
+    Some((if value < 0.0 { -mantissa } else { mantissa }, fraction.len() as u32))
+}
+
+/// Fraction digits of an `f64` kept when rendering it
+const MAX_FRACTION_DIGITS: usize = 18;
+
+/// How prices are written in outgoing JSON
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceFormat {
+    /// Integer ticks, as the engine stores them
+    #[default]
+    Ticks,
+    /// Integer ticks with the message's `price_exponent` (-4)
+    Scaled,
+    /// Decimal strings with four places
+    Decimal,
+}
+
+impl PriceFormat {
+    /// Serialize a message with its prices in this format
+    pub fn to_json<T: Serialize>(&self, message: &T, precision: &PricePrecision) -> serde_json::Result<String> {
+        match self {
+            Self::Scaled => serde_json::to_string(&self.to_value(message, precision)?),
+            _ => self.scope(precision, || serde_json::to_string(message)),
+        }
+    }
+
+    /// Serialize a message to a JSON value with its prices in this format
+    ///
+    /// Scaled messages get a top-level `price_exponent`; decimal strings
+    /// follow `precision`.
+    pub fn to_value<T: Serialize>(&self, message: &T, precision: &PricePrecision) -> serde_json::Result<Value> {
+        let mut value = self.scope(precision, || serde_json::to_value(message))?;
+        if let (Self::Scaled, Value::Object(object)) = (self, &mut value) {
+            object.insert("price_exponent".to_string(), PRICE_EXPONENT.into());
+        }
+        Ok(value)
+    }
+
+    /// Run `f` with the typed price fields it serializes in this format
+    pub fn scope<T>(&self, precision: &PricePrecision, f: impl FnOnce() -> T) -> T {
+        let _rendering = PriceRendering::enter(*self, *precision);
+        f()
+    }
+}
+
+/// Sets the format of typed price fields serialized on the current thread
+/// until dropped
+struct PriceRendering {
+    previous: Option<(PriceFormat, PricePrecision)>,
+    /// Tied to the thread whose format it set
+    _thread: PhantomData<*const ()>,
+}
+
+impl PriceRendering {
+    fn enter(format: PriceFormat, precision: PricePrecision) -> Self {
+        let previous = RENDERING.with(|rendering| rendering.replace(Some((format, precision))));
+        Self { previous, _thread: PhantomData }
+    }
+}
+
+impl Drop for PriceRendering {
+    fn drop(&mut self) {
+        RENDERING.with(|rendering| rendering.set(self.previous));
+    }
+}
+
+/// An amount of engine ticks: a price, a spread or a notional
+pub trait TickAmount: Serialize {
+    /// The amount as a decimal string, or null when there is none
+    fn decimal(&self, precision: &PricePrecision) -> Value;
+}
+
+impl TickAmount for u64 {
+    fn decimal(&self, precision: &PricePrecision) -> Value {
+        Value::String(precision.format_price(*self))
+    }
+}
+
+impl TickAmount for i64 {
+    fn decimal(&self, precision: &PricePrecision) -> Value {
+        Value::String(precision.format_ticks(*self))
+    }
+}
+
+impl TickAmount for u128 {
+    fn decimal(&self, precision: &PricePrecision) -> Value {
+        Value::String(precision.format_decimal(i128::try_from(*self).unwrap_or(i128::MAX), PRICE_DECIMALS))
+    }
+}
+
+impl TickAmount for i128 {
+    fn decimal(&self, precision: &PricePrecision) -> Value {
+        Value::String(precision.format_decimal(*self, PRICE_DECIMALS))
+    }
+}
+
+/// A fractional amount of ticks, such as an average price
+impl TickAmount for f64 {
+    fn decimal(&self, precision: &PricePrecision) -> Value {
+        precision.format_tick_fraction(*self).map_or(Value::Null, Value::String)
+    }
+}
+
+impl<T: TickAmount> TickAmount for &T {
+    fn decimal(&self, precision: &PricePrecision) -> Value {
+        (**self).decimal(precision)
+    }
+}
+
+impl<T: TickAmount> TickAmount for Option<T> {
+    fn decimal(&self, precision: &PricePrecision) -> Value {
+        self.as_ref().map_or(Value::Null, |amount| amount.decimal(precision))
+    }
+}
+
+impl<T: TickAmount> TickAmount for Vec<T> {
+    fn decimal(&self, precision: &PricePrecision) -> Value {
+        Value::Array(self.iter().map(|amount| amount.decimal(precision)).collect())
+    }
+}
+
+/// Serialize an amount of engine ticks in the format of the enclosing
+/// [`PriceFormat::scope`]
+///
+/// Scaled output keeps the ticks, since the message's `price_exponent` says
+/// how to read them.
+pub fn ticks<T: TickAmount, S: Serializer>(amount: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    match RENDERING.with(Cell::get) {
+        Some((PriceFormat::Decimal, precision)) => amount.decimal(&precision).serialize(serializer),
+        _ => amount.serialize(serializer),
+    }
+}
+
+/// Serialize `(time, ticks)` samples, such as a spread history, with the
+/// ticks in the format of the enclosing [`PriceFormat::scope`]
+pub fn timed_ticks<T: TickAmount, S: Serializer>(samples: &[(u128, T)], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(samples.iter().map(|(ts, amount)| (ts, Ticks(amount))))
+}
+
+/// Tick amount that serializes through [`ticks`], for bodies built with
+/// `json!` from untyped values
+#[derive(Debug, Clone, Copy)]
+pub struct Ticks<T>(pub T);
+
+impl<T: TickAmount> Serialize for Ticks<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ticks(&self.0, serializer)
+    }
+}
+
+/// An amount already in currency units, such as a mid
+pub trait UnitAmount: Serialize {
+    /// The amount in this format, when it differs from the plain number
+    fn render(&self, format: PriceFormat, precision: &PricePrecision) -> Value;
+}
+
+impl UnitAmount for f64 {
+    fn render(&self, format: PriceFormat, precision: &PricePrecision) -> Value {
+        match format {
+            PriceFormat::Decimal => precision.format_units(*self).map_or(Value::Null, Value::String),
+            PriceFormat::Scaled => (self * 10_f64.powi(PRICE_DECIMALS as i32)).into(),
+            PriceFormat::Ticks => (*self).into(),
+        }
+    }
+}
+
+impl<T: UnitAmount> UnitAmount for Option<T> {
+    fn render(&self, format: PriceFormat, precision: &PricePrecision) -> Value {
+        self.as_ref().map_or(Value::Null, |amount| amount.render(format, precision))
+    }
+}
+
+/// Serialize a price in currency units in the format of the enclosing
+/// [`PriceFormat::scope`]: scaled output is in ticks like every other price
+pub fn units<T: UnitAmount, S: Serializer>(amount: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    match RENDERING.with(Cell::get) {
+        Some((format @ (PriceFormat::Decimal | PriceFormat::Scaled), precision)) => amount.render(format, &precision).serialize(serializer),
+        _ => amount.serialize(serializer),
+    }
+}
+
+/// Currency amount that serializes through [`units`], for bodies built
+/// with `json!` from untyped values
+#[derive(Debug, Clone, Copy)]
+pub struct Units<T>(pub T);
+
+impl<T: UnitAmount> Serialize for Units<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        units(&self.0, serializer)
+    }
+}
+
+impl fmt::Display for PriceFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ticks => write!(f, "ticks"),
+            Self::Scaled => write!(f, "scaled"),
+            Self::Decimal => write!(f, "decimal"),
+        }
+    }
+}
+
+impl FromStr for PriceFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ticks" => Ok(Self::Ticks),
+            "scaled" => Ok(Self::Scaled),
+            "decimal" => Ok(Self::Decimal),
+            _ => Err(format!("Unknown price format: {} (expected ticks, scaled or decimal)", s)),
+        }
+    }
+}
+
+/// Parse an order entry price: integer ticks, a decimal string, or a scaled
+/// `{"value", "exponent"}` object
+///
+/// Prices finer than a tick are refused rather than rounded.
+pub fn parse_price(value: &Value) -> Result<Price, String> {
+    match value {
+        Value::Number(n) => n.as_u64().ok_or_else(|| format!("Price {} is not a whole number of ticks", n)),
+        Value::String(s) => parse_decimal(s),
+        Value::Object(object) => {
+            let mantissa = object.get("value").and_then(Value::as_u64)
+                .ok_or("Scaled price needs a non-negative integer 'value'")?;
+            let exponent = object.get("exponent").and_then(Value::as_i64)
+                .ok_or("Scaled price needs an integer 'exponent'")?;
+            scale(mantissa, exponent)
+        }
+        _ => Err("Price must be ticks, a decimal string or a scaled object".to_string()),
+    }
+}
+
+fn parse_decimal(s: &str) -> Result<Price, String> {
+    let invalid = || format!("Invalid decimal price '{}'", s);
+    let (whole, fraction) = s.trim().split_once('.').unwrap_or((s.trim(), ""));
+    if whole.is_empty() && fraction.is_empty() {
+        return Err(invalid());
+    }
+    if !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let mantissa: u64 = format!("{}{}", whole, fraction).parse().map_err(|_| invalid())?;
+    scale(mantissa, -(fraction.len() as i64))
+}
+
+/// Ticks of `mantissa * 10^exponent`
+fn scale(mantissa: u64, exponent: i64) -> Result<Price, String> {
+    let shift = exponent - PRICE_EXPONENT as i64;
+    let overflow = || format!("Price {}e{} is out of range", mantissa, exponent);
+    if shift >= 0 {
+        let factor = u32::try_from(shift).ok().and_then(|shift| 10_u64.checked_pow(shift)).ok_or_else(overflow)?;
+        mantissa.checked_mul(factor).ok_or_else(overflow)
+    } else {
+        let divisor = u32::try_from(-shift).ok().and_then(|shift| 10_u64.checked_pow(shift));
+        match divisor {
+            Some(divisor) if mantissa.is_multiple_of(divisor) => Ok(mantissa / divisor),
+            _ => Err(format!("Price {}e{} is finer than a tick", mantissa, exponent)),
+        }
+    }
+}
+
+/// Deserialize an order entry price in any accepted form
+pub fn deserialize_price<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Price, D::Error> {
+    parse_price(&Value::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+}
+
+/// Deserialize an optional order entry price in any accepted form
+pub fn deserialize_optional_price<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Price>, D::Error> {
+    match Option::<Value>::deserialize(deserializer)? {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => parse_price(&value).map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Side, Trade};
+
+    #[derive(Serialize)]
+    struct Quote {
+        #[serde(serialize_with = "ticks")]
+        best_bid: Option<Price>,
+        #[serde(serialize_with = "ticks")]
+        spread: i64,
+        #[serde(serialize_with = "units")]
+        mid: Option<f64>,
+        #[serde(serialize_with = "timed_ticks")]
+        recent_spreads: Vec<(u128, i64)>,
+        trades: Vec<Trade>,
+        #[serde(serialize_with = "ticks")]
+        notional: u128,
+        /// A count, whatever its name
+        price: u64,
+    }
+
+    #[test]
+    fn test_renders_prices_in_each_format() {
+        let message = Quote {
+            best_bid: Some(1002500),
+            spread: -100,
+            mid: Some(100.25),
+            recent_spreads: vec![(1, 300)],
+            trades: vec![Trade { maker_id: 1, taker_id: 2, price: 1002500, qty: 7, ts: 0, aggressor: Side::Buy }],
+            notional: 7017500,
+            price: 3,
+        };
+
+        let precision = PricePrecision::default();
+        let decimal = PriceFormat::Decimal.to_value(&message, &precision).unwrap();
+        assert_eq!(decimal["best_bid"], "100.2500");
+        assert_eq!(decimal["spread"], "-0.0100");
+        assert_eq!(decimal["mid"], "100.2500");
+        assert_eq!(decimal["recent_spreads"][0], serde_json::json!([1, "0.0300"]));
+        assert_eq!(decimal["trades"][0]["price"], "100.2500");
+        assert_eq!(decimal["notional"], "701.7500");
+        assert_eq!(decimal["price"], 3);
+
+        let scaled = PriceFormat::Scaled.to_value(&message, &precision).unwrap();
+        assert_eq!(scaled["best_bid"], 1002500);
+        assert_eq!(scaled["mid"], 1002500.0);
+        assert_eq!(scaled["price_exponent"], -4);
+
+        // Outside a scope, and in ticks, prices are plain
+        let plain = serde_json::to_value(&message).unwrap();
+        assert_eq!((plain["best_bid"].as_u64(), plain["mid"].as_f64()), (Some(1002500), Some(100.25)));
+        assert_eq!(PriceFormat::Ticks.to_value(&message, &precision).unwrap(), plain);
+        // Scopes nest and restore the outer format
+        PriceFormat::Decimal.scope(&precision, || {
+            assert_eq!(PriceFormat::Ticks.to_value(&message, &precision).unwrap(), plain);
+            assert_eq!(serde_json::to_value(&message).unwrap(), decimal);
+        });
+
+        let trade = Trade { maker_id: 1, taker_id: 2, price: 99_0000, qty: 5, ts: 0, aggressor: Side::Buy };
+        assert!(PriceFormat::Scaled.to_json(&trade, &precision).unwrap().contains(r#""price_exponent":-4"#));
+        assert_eq!(PriceFormat::Ticks.to_json(&trade, &precision).unwrap(), serde_json::to_string(&trade).unwrap());
+        assert!(PriceFormat::Decimal.to_json(&trade, &precision).unwrap().contains(r#""price":"99.0000""#));
+
+        // The instrument's places and tick carry through to decimal output
+        let cents = PricePrecision { decimals: 2, tick: 100, ..precision };
+        assert!(PriceFormat::Decimal.to_json(&trade, &cents).unwrap().contains(r#""price":"99.00""#));
+        assert_eq!("Decimal".parse::<PriceFormat>(), Ok(PriceFormat::Decimal));
+    }
+
+    #[test]
+    fn test_rounding_rules_at_edge_values() {
+        assert_eq!([5, 15, 25, -5, -15].map(|value| Rounding::HalfEven.divide(value, 10)), [0, 2, 2, 0, -2]);
+        assert_eq!([5, 15, 25, -5, -15].map(|value| Rounding::HalfUp.divide(value, 10)), [1, 2, 3, -1, -2]);
+        assert_eq!([19, -19].map(|value| Rounding::Truncate.divide(value, 10)), [1, -1]);
+
+        let precision = PricePrecision::default();
+        assert_eq!(precision.format_ticks(0), "0.0000");
+        assert_eq!(precision.format_ticks(1), "0.0001");
+        assert_eq!(precision.format_ticks(-1), "-0.0001");
+        assert_eq!(precision.format_ticks(i64::MAX), "922337203685477.5807");
+        assert_eq!(precision.format_ticks(i64::MIN), "-922337203685477.5808");
+        assert_eq!(precision.format_price(u64::MAX), "1844674407370955.1615");
+
+        // Half-tick mids and averages round to even rather than drifting up
+        assert_eq!(precision.format_units(100.00005).as_deref(), Some("100.0000"));
+        assert_eq!(precision.format_units(100.00015).as_deref(), Some("100.0002"));
+        assert_eq!(precision.format_tick_fraction(1_002_500.5).as_deref(), Some("100.2500"));
+        assert_eq!(precision.format_tick_fraction(1_002_501.5).as_deref(), Some("100.2502"));
+        // Binary noise in a computed value never shows
+        assert_eq!(precision.format_units(0.1 + 0.2).as_deref(), Some("0.3000"));
+        assert_eq!(precision.format_units(f64::NAN), None);
+        assert_eq!(precision.format_units(1e300), None);
+        assert_eq!(precision.format_units(1e-300).as_deref(), Some("0.0000"));
+
+        // Coarser ticks and fewer places
+        let nickel = PricePrecision { decimals: 2, tick: 500, rounding: Rounding::HalfEven };
+        assert_eq!(nickel.format_ticks(1_002_500), "100.25");
+        assert_eq!(nickel.format_ticks(1_002_749), "100.25");
+        assert_eq!(nickel.format_ticks(1_002_750), "100.30");
+        assert_eq!(nickel.with_decimals(0).format_ticks(1_005_000), "100");
+        assert_eq!(PricePrecision { rounding: Rounding::HalfUp, ..nickel }.format_ticks(1_002_250), "100.25");
+        assert_eq!(precision.with_decimals(6).format_ticks(1_002_500), "100.250000");
+        assert!(PricePrecision { tick: 0, ..precision }.validate().is_err());
+        assert!(precision.with_decimals(13).validate().is_err());
+    }
+
+    #[test]
+    fn test_parses_order_entry_prices() {
+        assert_eq!(parse_price(&serde_json::json!(1002500)), Ok(1002500));
+        assert_eq!(parse_price(&serde_json::json!("100.25")), Ok(1002500));
+        assert_eq!(parse_price(&serde_json::json!("100")), Ok(1000000));
+        assert_eq!(parse_price(&serde_json::json!({ "value": 10025, "exponent": -2 })), Ok(1002500));
+        assert_eq!(parse_price(&serde_json::json!({ "value": 1, "exponent": 2 })), Ok(1000000));
+
+        // Sub-tick and malformed prices are refused, not rounded
+        assert!(parse_price(&serde_json::json!("100.00001")).is_err());
+        assert!(parse_price(&serde_json::json!("1e2")).is_err());
+        assert!(parse_price(&serde_json::json!("-1")).is_err());
+        assert!(parse_price(&serde_json::json!(99.5)).is_err());
+    }
+}
@@ -0,0 +1,792 @@
+//! WebSocket protocol messages
+//!
+//! Clients send [`ClientRequest`]s as JSON text frames on `/ws`. The server
+//! answers each structured command with a [`ServerReply`] carrying the same
+//! `request_id`, interleaved with the depth snapshots it broadcasts and any
+//! [`SessionReport`]s meant for this connection alone, and the
+//! [`PlaybackFrame`]s of any recording it asked to play back. Decode
+//! incoming frames as [`ServerMessage`] to tell them apart.
+//!
+//! A client opens with a `hello` command naming the highest protocol version
+//! it speaks; the server answers with the version both sides speak and the
+//! [`Capability`]s it will use. Connections that never say hello get
+//! version 1, so frontends written before a change keep working.
+//!
+//! Opt-in capabilities such as [`Capability::BinaryDepth`] are only used
+//! when the hello lists them, since they change what the client receives.
+
+use std::fmt;
+use serde::{Deserialize, Deserializer, Serialize};
+use crate::engine::{DepthSnapshot, QuoteOutcome};
+use crate::error::EngineError;
+use crate::mbo::OrderUpdates;
+use crate::price_format::PriceFormat;
+use crate::sim::ReplayStatus;
+use crate::types::{ExecutionSummary, OrderId, Price, Qty, Side, Trade};
+
+/// Command understood by the `/ws` endpoint
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum ClientCommand {
+    GetHealth,
+    ResetMetrics,
+    SetSimulationSpeed { speed: f64 },
+    ReplayPause,
+    ReplayResume,
+    ReplaySpeed { speed: f64 },
+    ReplaySeek { timestamp: u64 },
+    ReplayStatus,
+    /// Limit order when `price` is set, market order otherwise; the price may
+    /// be ticks, a decimal string or a scaled object
+    PlaceTestOrder {
+        #[serde(deserialize_with = "deserialize_side")]
+        side: Side,
+        qty: Qty,
+        #[serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            deserialize_with = "crate::price_format::deserialize_optional_price"
+        )]
+        price: Option<Price>,
+    },
+    /// Replace this connection's two-sided quote in one step; a side left
+    /// out is pulled
+    Quote {
+        #[serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            deserialize_with = "crate::price_format::deserialize_optional_price"
+        )]
+        bid_price: Option<Price>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        bid_qty: Option<Qty>,
+        #[serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            deserialize_with = "crate::price_format::deserialize_optional_price"
+        )]
+        ask_price: Option<Price>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        ask_qty: Option<Qty>,
+    },
+    SetCancelOnDisconnect { enabled: bool },
+    /// Write prices in outgoing messages in this format
+    SetPriceFormat {
+        #[serde(deserialize_with = "deserialize_price_format")]
+        format: PriceFormat,
+    },
+    /// Negotiate the protocol version, optionally limiting the capabilities
+    /// used to those listed
+    Hello {
+        version: u32,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        capabilities: Vec<Capability>,
+    },
+    /// Play a recorded session back over this connection, replacing any
+    /// playback in progress
+    PlayRecording {
+        name: String,
+        #[serde(default = "default_playback_speed")]
+        speed: f64,
+    },
+    StopPlayback,
+}
+
+fn default_playback_speed() -> f64 {
+    1.0
+}
+
+/// Side in any case, `buy` as well as `Buy`
+fn deserialize_side<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Side, D::Error> {
+    match String::deserialize(deserializer)?.to_ascii_lowercase().as_str() {
+        "buy" => Ok(Side::Buy),
+        "sell" => Ok(Side::Sell),
+        _ => Err(serde::de::Error::custom("Invalid side, must be 'buy' or 'sell'")),
+    }
+}
+
+/// Price format in any case, as the REST endpoints take it
+fn deserialize_price_format<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PriceFormat, D::Error> {
+    String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+}
+
+/// Highest protocol version this build speaks
+pub const PROTOCOL_VERSION: u32 = 3;
+
+/// Oldest protocol version this build still serves
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+/// Optional part of the protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// Acks and errors carrying the command's `request_id`
+    CommandReplies,
+    /// Resting orders pulled when the connection drops
+    CancelOnDisconnect,
+    /// Replay pause, resume, speed and seek commands
+    ReplayControls,
+    /// Unsolicited [`SessionReport`]s such as block trades
+    SessionReports,
+    /// Book updates as fixed-layout binary frames (see [`crate::binary`])
+    /// instead of JSON snapshots
+    BinaryDepth,
+    /// Every resting order's changes ahead of each snapshot (see
+    /// [`crate::mbo`]), when the server runs an order feed
+    MarketByOrder,
+}
+
+impl Capability {
+    /// Every capability, oldest first
+    pub const ALL: [Capability; 6] = [
+        Self::CommandReplies,
+        Self::CancelOnDisconnect,
+        Self::ReplayControls,
+        Self::SessionReports,
+        Self::BinaryDepth,
+        Self::MarketByOrder,
+    ];
+
+    /// First protocol version with this capability
+    pub fn since(&self) -> u32 {
+        match self {
+            Self::SessionReports => 2,
+            Self::BinaryDepth | Self::MarketByOrder => 3,
+            _ => 1,
+        }
+    }
+
+    /// Whether the capability is used only when asked for by name
+    pub fn opt_in(&self) -> bool {
+        matches!(self, Self::BinaryDepth | Self::MarketByOrder)
+    }
+
+    /// Capability a connection must have negotiated to send `command`
+    pub fn required_by(command: &ClientCommand) -> Option<Self> {
+        match command {
+            ClientCommand::ReplayPause
+            | ClientCommand::ReplayResume
+            | ClientCommand::ReplaySpeed { .. }
+            | ClientCommand::ReplaySeek { .. }
+            | ClientCommand::ReplayStatus => Some(Self::ReplayControls),
+            ClientCommand::SetCancelOnDisconnect { .. } => Some(Self::CancelOnDisconnect),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::CommandReplies => "command_replies",
+            Self::CancelOnDisconnect => "cancel_on_disconnect",
+            Self::ReplayControls => "replay_controls",
+            Self::SessionReports => "session_reports",
+            Self::BinaryDepth => "binary_depth",
+            Self::MarketByOrder => "market_by_order",
+        })
+    }
+}
+
+/// Protocol version and capabilities agreed for a connection
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Handshake {
+    pub version: u32,
+    pub capabilities: Vec<Capability>,
+    /// Server build
+    #[serde(default)]
+    pub server: String,
+}
+
+impl Default for Handshake {
+    fn default() -> Self {
+        Self::legacy()
+    }
+}
+
+impl Handshake {
+    /// Protocol for connections that never say hello: version 1
+    pub fn legacy() -> Self {
+        Self::at_version(MIN_PROTOCOL_VERSION, &[])
+    }
+
+    /// Agree on the highest version both sides speak, limited to the
+    /// `requested` capabilities if any are listed, or to those that are not
+    /// opt-in if none are; `None` if the client only speaks versions this
+    /// build no longer serves
+    pub fn negotiate(client_version: u32, requested: &[Capability]) -> Option<Self> {
+        let version = client_version.min(PROTOCOL_VERSION);
+        (version >= MIN_PROTOCOL_VERSION).then(|| Self::at_version(version, requested))
+    }
+
+    fn at_version(version: u32, requested: &[Capability]) -> Self {
+        let capabilities = Capability::ALL
+            .into_iter()
+            .filter(|capability| capability.since() <= version)
+            .filter(|capability| match requested {
+                [] => !capability.opt_in(),
+                requested => requested.contains(capability),
+            })
+            .collect();
+        Self { version, capabilities, server: env!("CARGO_PKG_VERSION").to_string() }
+    }
+
+    /// Whether a capability is in use on this connection
+    pub fn supports(&self, capability: Capability) -> bool {
+        self.capabilities.contains(&capability)
+    }
+}
+
+/// A command with the id its reply will carry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClientRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<u64>,
+    #[serde(flatten)]
+    pub command: ClientCommand,
+}
+
+/// Result of a command that succeeded
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CommandAck {
+    /// Id assigned to a placed order
+    #[serde(default)]
+    pub order_id: Option<OrderId>,
+    /// Trades a placed order executed, one per maker
+    #[serde(default)]
+    pub trades: Vec<Trade>,
+    /// The placed order's fills taken together
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub execution: Option<ExecutionSummary>,
+    /// What a book rule refused to rest of a placed limit order once its
+    /// fills were taken out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refused_qty: Option<Qty>,
+    /// Legs of a quote left resting
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub resting: Vec<OrderId>,
+    /// Legs of a quote the book refused, with why
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub refused: Vec<RefusedLeg>,
+    /// Replay transport state, answering `replay_status`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replay: Option<ReplayStatus>,
+}
+
+/// A quote leg the book refused once the previous quote was pulled
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RefusedLeg {
+    pub order_id: OrderId,
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl CommandAck {
+    /// Acknowledge a placed order and the trades it executed
+    pub fn placed(order_id: OrderId, trades: Vec<Trade>) -> Self {
+        let execution = ExecutionSummary::aggregate(&trades).into_iter().next();
+        Self { order_id: Some(order_id), trades, execution, ..Self::default() }
+    }
+
+    /// Acknowledge a replaced quote, its trades and the legs left resting
+    pub fn quoted(outcome: QuoteOutcome) -> Self {
+        let refused = outcome.refused.into_iter()
+            .map(|(order_id, e)| {
+                let error = ApiError::from(e);
+                RefusedLeg { order_id, code: error.code, message: error.message }
+            })
+            .collect();
+        Self { trades: outcome.trades, resting: outcome.resting, refused, ..Self::default() }
+    }
+}
+
+/// Machine-readable reason a command was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    /// The message was blank
+    MessageEmpty,
+    /// The message was over the size limit
+    MessageTooLarge,
+    /// The message has no `command`
+    CommandMissing,
+    /// The `command` is not one the server knows
+    CommandUnknown,
+    /// A required field is absent
+    FieldMissing,
+    /// A field has the wrong type or an unusable value
+    FieldInvalid,
+    /// `side` is neither buy nor sell
+    SideInvalid,
+    /// Quantity of zero
+    QtyZero,
+    /// Quantity that is not a usable size
+    QtyInvalid,
+    /// Quantity over the maximum order size
+    QtyTooLarge,
+    /// Quantity not a whole number of lots
+    QtyOddLot,
+    /// Limit price of zero
+    PriceZero,
+    /// Price that is not a valid tick price
+    PriceInvalid,
+    /// Price outside the allowed range
+    PriceOutOfRange,
+    /// Price past the book's depth limit
+    PriceBeyondDepthLimit,
+    /// Price that would lock or cross the book
+    PriceCrossesBook,
+    /// No order with the given id is resting
+    OrderUnknown,
+    /// An order with the given id is already resting
+    OrderDuplicate,
+    /// Order timestamp too far from the engine's time
+    TimestampOutOfRange,
+    /// A market order ran out of liquidity before it filled
+    PartialFill,
+    /// The quote's legs do not form a valid pair
+    QuoteInvalid,
+    /// The market is pre-open and does not take this now
+    MarketPreOpen,
+    /// A market order found nothing to trade against
+    NoLiquidity,
+    /// The order would trade against its owner
+    SelfTrade,
+    /// The order's value does not fit the account's arithmetic
+    ValueOverflow,
+    /// The order would exceed the notional velocity limit
+    VelocityLimit,
+    /// The account lacks the margin the order needs
+    InsufficientMargin,
+    /// Order entry is down for a simulated gateway outage
+    GatewayUnavailable,
+    /// Order entry is throttling; retry after `retry_after_ms`
+    Throttled,
+    /// The client's protocol version is not served
+    VersionUnsupported,
+    /// The command needs a capability the connection did not negotiate
+    CapabilityNotNegotiated,
+    /// The data source refused the request
+    DataSource,
+    /// No recording has the given name
+    RecordingUnknown,
+    /// The path names no resource the server has
+    NotFound,
+    /// The request itself is malformed
+    RequestInvalid,
+    /// A connection limit has been reached
+    ConnectionLimit,
+    /// The server cannot handle the request now
+    Unavailable,
+    /// Refused for another reason given in the message
+    Rejected,
+    /// The server failed to handle the command
+    Internal,
+}
+
+/// A refused command, with the field at fault if there is one
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error, Serialize, Deserialize)]
+#[error("{message}")]
+pub struct ApiError {
+    pub code: ErrorCode,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    /// Human-readable explanation
+    pub message: String,
+    /// Wait this long before retrying (milliseconds of simulation time)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after_ms: Option<u64>,
+}
+
+pub type ApiResult<T> = Result<T, ApiError>;
+
+impl ApiError {
+    /// Refuse with a code and message
+    pub fn new<S: Into<String>>(code: ErrorCode, message: S) -> Self {
+        Self { code, field: None, message: message.into(), retry_after_ms: None }
+    }
+
+    /// Refuse because of a field
+    pub fn on_field<S: Into<String>>(code: ErrorCode, field: &str, message: S) -> Self {
+        Self { code, field: Some(field.to_string()), message: message.into(), retry_after_ms: None }
+    }
+
+    /// A required field is absent
+    pub fn missing(field: &str) -> Self {
+        Self::on_field(ErrorCode::FieldMissing, field, format!("Missing '{}' field", field))
+    }
+
+    /// A field has the wrong type or an unusable value
+    pub fn invalid<S: Into<String>>(field: &str, message: S) -> Self {
+        Self::on_field(ErrorCode::FieldInvalid, field, message)
+    }
+}
+
+impl From<EngineError> for ApiError {
+    fn from(error: EngineError) -> Self {
+        let message = error.to_string();
+        let (code, field) = match &error {
+            EngineError::UnknownOrder { .. } => (ErrorCode::OrderUnknown, Some("order_id")),
+            EngineError::InvalidPrice { price: 0 } => (ErrorCode::PriceZero, Some("price")),
+            EngineError::InvalidPrice { .. } => (ErrorCode::PriceInvalid, Some("price")),
+            EngineError::InvalidQty { qty: 0 } => (ErrorCode::QtyZero, Some("qty")),
+            EngineError::InvalidQty { .. } => (ErrorCode::QtyInvalid, Some("qty")),
+            EngineError::QtyTooLarge { .. } => (ErrorCode::QtyTooLarge, Some("qty")),
+            EngineError::OddLot { .. } => (ErrorCode::QtyOddLot, Some("qty")),
+            EngineError::PriceOutOfRange { .. } => (ErrorCode::PriceOutOfRange, Some("price")),
+            EngineError::BeyondDepthLimit { .. } => (ErrorCode::PriceBeyondDepthLimit, Some("price")),
+            EngineError::CrossedBook { .. } => (ErrorCode::PriceCrossesBook, Some("price")),
+            EngineError::NoLiquidity => (ErrorCode::NoLiquidity, None),
+            EngineError::SelfTrade { .. } => (ErrorCode::SelfTrade, None),
+            EngineError::ValueOverflow { .. } => (ErrorCode::ValueOverflow, Some("qty")),
+            EngineError::VelocityLimit { .. } => (ErrorCode::VelocityLimit, None),
+            EngineError::InsufficientMargin { .. } => (ErrorCode::InsufficientMargin, None),
+            EngineError::GatewayUnavailable => (ErrorCode::GatewayUnavailable, None),
+            EngineError::Throttled { .. } => (ErrorCode::Throttled, None),
+            EngineError::DuplicateOrder { .. } => (ErrorCode::OrderDuplicate, Some("order_id")),
+            EngineError::TimestampOutOfRange { .. } => (ErrorCode::TimestampOutOfRange, Some("ts")),
+            EngineError::PartialFill { .. } => (ErrorCode::PartialFill, Some("qty")),
+            EngineError::InvalidQuote { .. } => (ErrorCode::QuoteInvalid, None),
+            EngineError::PreOpen { .. } => (ErrorCode::MarketPreOpen, None),
+            EngineError::Reject { .. } => (ErrorCode::Rejected, None),
+            EngineError::DataError { .. } => (ErrorCode::DataSource, None),
+            EngineError::InternalError { .. }
+            | EngineError::NetworkError { .. }
+            | EngineError::SerializationError { .. } => (ErrorCode::Internal, None),
+        };
+        let retry_after_ms = match error {
+            EngineError::Throttled { retry_after_ms } => Some(retry_after_ms),
+            _ => None,
+        };
+        Self { code, field: field.map(str::to_string), message, retry_after_ms }
+    }
+}
+
+/// Server answer to a structured command
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerReply {
+    Ack {
+        #[serde(default)]
+        request_id: Option<u64>,
+        #[serde(flatten)]
+        ack: CommandAck,
+    },
+    Error {
+        #[serde(default)]
+        request_id: Option<u64>,
+        #[serde(flatten)]
+        error: ApiError,
+    },
+    Hello {
+        #[serde(default)]
+        request_id: Option<u64>,
+        #[serde(flatten)]
+        handshake: Handshake,
+    },
+}
+
+impl ServerReply {
+    /// Id of the request this answers
+    pub fn request_id(&self) -> Option<u64> {
+        match self {
+            Self::Ack { request_id, .. } | Self::Error { request_id, .. } | Self::Hello { request_id, .. } => *request_id,
+        }
+    }
+}
+
+/// Kind of [`SessionReport`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportKind {
+    /// A block trade the session took part in, ahead of the public print
+    BlockTrade,
+}
+
+/// Unsolicited report sent only to the sessions it concerns
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionReport {
+    #[serde(rename = "type")]
+    pub kind: ReportKind,
+    pub trade: Trade,
+}
+
+impl SessionReport {
+    /// Report a block trade to one of its parties
+    pub fn block_trade(trade: Trade) -> Self {
+        Self { kind: ReportKind::BlockTrade, trade }
+    }
+}
+
+/// Kind of [`PlaybackFrame`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaybackEvent {
+    /// A recorded snapshot
+    Playback,
+    /// The recording has been played to the end
+    PlaybackEnd,
+}
+
+/// One step of a recording played back to a single client
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlaybackFrame {
+    #[serde(rename = "type")]
+    pub kind: PlaybackEvent,
+    pub recording: String,
+    /// Position of the snapshot in the recording
+    pub index: usize,
+    /// Snapshots in the recording
+    pub total: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<Box<DepthSnapshot>>,
+}
+
+impl PlaybackFrame {
+    /// Frame for the recorded snapshot at `index`
+    pub fn snapshot(recording: &str, index: usize, total: usize, snapshot: DepthSnapshot) -> Self {
+        Self { kind: PlaybackEvent::Playback, recording: recording.to_string(), index, total, snapshot: Some(Box::new(snapshot)) }
+    }
+
+    /// Frame marking the end of a recording
+    pub fn end(recording: &str, total: usize) -> Self {
+        Self { kind: PlaybackEvent::PlaybackEnd, recording: recording.to_string(), index: total, total, snapshot: None }
+    }
+}
+
+/// Any text frame the server sends on `/ws`
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum ServerMessage {
+    Reply(Box<ServerReply>),
+    Report(SessionReport),
+    Playback(PlaybackFrame),
+    Orders(OrderUpdates),
+    Snapshot(Box<DepthSnapshot>),
+}
+
+// Tagged and flattened derives buffer the input in a form that cannot hold
+// u128 timestamps, so replies and messages dispatch on `type` by hand.
+impl<'de> Deserialize<'de> for ServerReply {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Fields {
+            #[serde(rename = "type")]
+            kind: String,
+            #[serde(default)]
+            request_id: Option<u64>,
+            #[serde(default)]
+            order_id: Option<OrderId>,
+            #[serde(default)]
+            trades: Vec<Trade>,
+            #[serde(default)]
+            execution: Option<ExecutionSummary>,
+            #[serde(default)]
+            refused_qty: Option<Qty>,
+            #[serde(default)]
+            resting: Vec<OrderId>,
+            #[serde(default)]
+            refused: Vec<RefusedLeg>,
+            #[serde(default)]
+            replay: Option<ReplayStatus>,
+            #[serde(default)]
+            message: Option<String>,
+            #[serde(default)]
+            code: Option<ErrorCode>,
+            #[serde(default)]
+            field: Option<String>,
+            #[serde(default)]
+            retry_after_ms: Option<u64>,
+            #[serde(default)]
+            version: Option<u32>,
+            #[serde(default)]
+            capabilities: Vec<Capability>,
+            #[serde(default)]
+            server: String,
+        }
+
+        let fields = Fields::deserialize(deserializer)?;
+        match (fields.kind.as_str(), fields.message) {
+            ("ack", _) => Ok(Self::Ack {
+                request_id: fields.request_id,
+                ack: CommandAck {
+                    order_id: fields.order_id,
+                    trades: fields.trades,
+                    execution: fields.execution,
+                    refused_qty: fields.refused_qty,
+                    resting: fields.resting,
+                    refused: fields.refused,
+                    replay: fields.replay,
+                },
+            }),
+            // Servers from before error codes only sent the message
+            ("error", Some(message)) => Ok(Self::Error {
+                request_id: fields.request_id,
+                error: ApiError {
+                    code: fields.code.unwrap_or(ErrorCode::Rejected),
+                    field: fields.field,
+                    message,
+                    retry_after_ms: fields.retry_after_ms,
+                },
+            }),
+            ("error", None) => Err(serde::de::Error::missing_field("message")),
+            ("hello", _) => Ok(Self::Hello {
+                request_id: fields.request_id,
+                handshake: Handshake {
+                    version: fields.version.ok_or_else(|| serde::de::Error::missing_field("version"))?,
+                    capabilities: fields.capabilities,
+                    server: fields.server,
+                },
+            }),
+            (kind, _) => Err(serde::de::Error::unknown_variant(kind, &["ack", "error", "hello"])),
+        }
+    }
+}
+
+// Replies and reports carry a `type` field and snapshots do not
+impl<'de> Deserialize<'de> for ServerMessage {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        let message = match value.get("type").map(|kind| kind.as_str()) {
+            Some(Some("block_trade")) => serde_json::from_value(value).map(Self::Report),
+            Some(Some("playback" | "playback_end")) => serde_json::from_value(value).map(Self::Playback),
+            Some(Some("orders")) => serde_json::from_value(value).map(Self::Orders),
+            Some(_) => serde_json::from_value(value).map(Self::Reply),
+            None => serde_json::from_value(value).map(Self::Snapshot),
+        };
+        message.map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{OrderBook, OrderBookEngine};
+    use crate::queue_fifo::FifoLevel;
+    use crate::types::Order;
+
+    #[test]
+    fn test_wire_format() {
+        let request = ClientRequest {
+            request_id: Some(7),
+            command: ClientCommand::PlaceTestOrder { side: Side::Buy, qty: 100, price: Some(99_0000) },
+        };
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json, serde_json::json!({
+            "request_id": 7, "command": "place_test_order", "side": "Buy", "qty": 100, "price": 99_0000,
+        }));
+        assert_eq!(serde_json::from_value::<ClientRequest>(json).unwrap(), request);
+
+        let mut book = OrderBook::<FifoLevel>::new();
+        let ts = crate::time::now_ns();
+        book.place(Order::new_limit(1, Side::Sell, 100, 101_0000, ts)).unwrap();
+        let trades = book.place(Order::new_market(2, Side::Buy, 40, ts)).unwrap();
+        for reply in [
+            ServerReply::Ack { request_id: Some(7), ack: CommandAck::placed(2, trades.clone()) },
+            ServerReply::Error { request_id: Some(8), error: EngineError::InvalidQty { qty: 0 }.into() },
+            ServerReply::Hello { request_id: Some(9), handshake: Handshake::legacy() },
+        ] {
+            let json = serde_json::to_string(&reply).unwrap();
+            assert!(matches!(serde_json::from_str(&json).unwrap(), ServerMessage::Reply(r) if *r == reply));
+        }
+
+        let report = SessionReport::block_trade(trades[0].clone());
+        let json = serde_json::to_value(ServerMessage::Report(report.clone())).unwrap();
+        assert_eq!(json["type"], "block_trade");
+        assert!(matches!(serde_json::from_value(json).unwrap(), ServerMessage::Report(r) if r == report));
+
+        let quote: ClientRequest = serde_json::from_str(
+            r#"{"command": "quote", "bid_price": "99.50", "bid_qty": 100, "ask_price": 1005000, "ask_qty": 100}"#
+        ).unwrap();
+        assert_eq!(quote.command, ClientCommand::Quote {
+            bid_price: Some(99_5000), bid_qty: Some(100), ask_price: Some(100_5000), ask_qty: Some(100),
+        });
+        let quoted = ServerReply::Ack { request_id: Some(10), ack: CommandAck::quoted(QuoteOutcome {
+            resting: vec![11],
+            refused: vec![(12, EngineError::CrossedBook { price: 99_0000, state: crate::engine::BookState::Crossed })],
+            ..QuoteOutcome::default()
+        }) };
+        let json = serde_json::to_string(&quoted).unwrap();
+        assert!(json.contains(r#""code":"PRICE_CROSSES_BOOK""#), "{}", json);
+        assert!(matches!(serde_json::from_str(&json).unwrap(), ServerMessage::Reply(r) if *r == quoted));
+
+        let hello: ClientRequest = serde_json::from_str(r#"{"command": "hello", "version": 1}"#).unwrap();
+        assert_eq!(hello.command, ClientCommand::Hello { version: 1, capabilities: vec![] });
+
+        let snapshot = serde_json::to_string(&OrderBook::<FifoLevel>::new().snapshot()).unwrap();
+        assert!(matches!(serde_json::from_str(&snapshot).unwrap(), ServerMessage::Snapshot(_)));
+
+        let mut feed = crate::mbo::OrderFeed::new();
+        feed.absorb(crate::mbo::OrderTracker::with_orders([(Order::new_limit(3, Side::Buy, 10, 99_0000, ts), 0)]).take());
+        let orders = (*feed.publish(ts).unwrap()).clone();
+        let json = serde_json::to_string(&ServerMessage::Orders(orders.clone())).unwrap();
+        assert!(json.contains(r#""type":"orders""#), "{}", json);
+        assert!(matches!(serde_json::from_str(&json).unwrap(), ServerMessage::Orders(o) if o == orders));
+
+        let play: ClientRequest = serde_json::from_str(r#"{"command": "play_recording", "name": "spike"}"#).unwrap();
+        assert_eq!(play.command, ClientCommand::PlayRecording { name: "spike".to_string(), speed: 1.0 });
+        let frame = PlaybackFrame::snapshot("spike", 0, 2, OrderBook::<FifoLevel>::new().snapshot());
+        let json = serde_json::to_string(&ServerMessage::Playback(frame)).unwrap();
+        assert!(matches!(serde_json::from_str(&json).unwrap(), ServerMessage::Playback(f) if f.snapshot.is_some()));
+        let json = serde_json::to_string(&ServerMessage::Playback(PlaybackFrame::end("spike", 2))).unwrap();
+        assert!(matches!(serde_json::from_str(&json).unwrap(), ServerMessage::Playback(f) if f.kind == PlaybackEvent::PlaybackEnd));
+    }
+
+    #[test]
+    fn test_negotiates_highest_shared_version() {
+        // Newer clients get this build's version, older ones their own
+        let newer = Handshake::negotiate(PROTOCOL_VERSION + 1, &[]).unwrap();
+        assert_eq!(newer.version, PROTOCOL_VERSION);
+        assert!(newer.supports(Capability::SessionReports));
+        let legacy = Handshake::negotiate(1, &[]).unwrap();
+        assert_eq!(legacy, Handshake::legacy());
+        assert!(legacy.supports(Capability::CommandReplies));
+        assert!(!legacy.supports(Capability::SessionReports));
+        assert!(Handshake::negotiate(0, &[]).is_none());
+
+        // Clients can opt out of capabilities they do not handle
+        let limited = Handshake::negotiate(2, &[Capability::CommandReplies]).unwrap();
+        assert_eq!(limited.capabilities, vec![Capability::CommandReplies]);
+
+        // Binary depth must be asked for, and only from version 3
+        assert!(!newer.supports(Capability::BinaryDepth));
+        assert!(Handshake::negotiate(3, &[Capability::BinaryDepth]).unwrap().supports(Capability::BinaryDepth));
+        assert!(Handshake::negotiate(2, &[Capability::BinaryDepth]).unwrap().capabilities.is_empty());
+        assert!(!newer.supports(Capability::MarketByOrder));
+    }
+
+    #[test]
+    fn test_errors_carry_codes_and_fields() {
+        let reply = ServerReply::Error { request_id: Some(3), error: EngineError::InvalidQty { qty: 0 }.into() };
+        let json = serde_json::to_value(&reply).unwrap();
+        assert_eq!(json["code"], "QTY_ZERO");
+        assert_eq!(json["field"], "qty");
+        assert_eq!(json["message"], "Invalid quantity: 0. Quantity must be positive");
+        assert!(json.get("retry_after_ms").is_none());
+
+        // Engine refusals each keep their own code
+        let codes = [
+            (EngineError::DuplicateOrder { order_id: 1 }, ErrorCode::OrderDuplicate),
+            (EngineError::TimestampOutOfRange { ts: 0, direction: "past" }, ErrorCode::TimestampOutOfRange),
+            (EngineError::PartialFill { unfilled: 5 }, ErrorCode::PartialFill),
+            (EngineError::InvalidQuote { reason: "crossed".to_string() }, ErrorCode::QuoteInvalid),
+            (EngineError::PreOpen { reason: "queue is full".to_string() }, ErrorCode::MarketPreOpen),
+        ];
+        for (error, code) in codes {
+            assert_eq!(ApiError::from(error).code, code);
+        }
+
+        // Throttle refusals say when to retry, and clients read it back
+        let throttled = ServerReply::Error { request_id: Some(4), error: EngineError::Throttled { retry_after_ms: 25 }.into() };
+        let json = serde_json::to_string(&throttled).unwrap();
+        assert!(json.contains(r#""code":"THROTTLED""#) && json.contains(r#""retry_after_ms":25"#));
+        assert!(matches!(serde_json::from_str(&json).unwrap(), ServerMessage::Reply(r) if *r == throttled));
+
+        let missing = serde_json::to_value(ServerReply::Error { request_id: None, error: ApiError::missing("side") }).unwrap();
+        assert_eq!((&missing["code"], &missing["field"]), (&serde_json::json!("FIELD_MISSING"), &serde_json::json!("side")));
+
+        // Errors without a code read as plain rejections
+        let old: ServerReply = serde_json::from_str(r#"{"type": "error", "message": "Speed must be positive"}"#).unwrap();
+        assert!(matches!(old, ServerReply::Error { error: ApiError { code: ErrorCode::Rejected, field: None, .. }, .. }));
+    }
+}
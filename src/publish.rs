@@ -0,0 +1,521 @@
+//! Streaming publisher for downstream data pipelines
+//!
+//! A [`FeedPublisher`] subscribes to the snapshot broadcast, turns each
+//! snapshot into sequenced feed messages (see [`crate::feed`]) and publishes
+//! trades and incremental depth updates to separate topics on a message
+//! broker. Price-level alerts (see [`crate::alerts`]) are checked against each
+//! depth update and published to a third topic. Broker clients are optional
+//! dependencies: build with `--features kafka`, `--features nats` or
+//! `--features redis` to enable a backend.
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use tokio::sync::{broadcast, Mutex};
+use tracing::{debug, warn};
+use crate::alerts::PriceAlerts;
+use crate::engine::DepthSnapshot;
+use crate::feed::{FeedFormat, FeedMessage, FeedSequencer};
+use crate::masking::DepthMask;
+use crate::types::Side;
+
+/// Message broker to publish to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PublisherBackend {
+    #[default]
+    Kafka,
+    Nats,
+    Redis,
+}
+
+impl PublisherBackend {
+    /// Cargo feature that compiles in the backend's client
+    pub fn feature(&self) -> &'static str {
+        match self {
+            Self::Kafka => "kafka",
+            Self::Nats => "nats",
+            Self::Redis => "redis",
+        }
+    }
+}
+
+impl fmt::Display for PublisherBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.feature())
+    }
+}
+
+/// Broker connection, topics and encoding for the streaming publisher
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PublisherConfig {
+    /// Publish trades and depth updates while the server runs
+    pub enabled: bool,
+    pub backend: PublisherBackend,
+    /// Broker address: `host:port` for Kafka, `nats://host:port` for NATS,
+    /// `redis://host:port` for Redis
+    pub url: String,
+    /// Topic (Kafka), subject (NATS) or channel (Redis) for trades
+    pub trade_topic: String,
+    /// Topic (Kafka), subject (NATS) or channel (Redis) for depth updates
+    pub depth_topic: String,
+    /// Topic (Kafka), subject (NATS) or channel (Redis) for price-level alerts
+    #[serde(default = "default_alert_topic")]
+    pub alert_topic: String,
+    /// Topic (Kafka), subject (NATS) or channel (Redis) for pre-open auction imbalances
+    #[serde(default = "default_imbalance_topic")]
+    pub imbalance_topic: String,
+    /// Message encoding
+    #[serde(default)]
+    pub format: FeedFormat,
+}
+
+impl Default for PublisherConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            backend: PublisherBackend::Kafka,
+            url: "localhost:9092".to_string(),
+            trade_topic: "orderbook.trades".to_string(),
+            depth_topic: "orderbook.depth".to_string(),
+            alert_topic: default_alert_topic(),
+            imbalance_topic: default_imbalance_topic(),
+            format: FeedFormat::Json,
+        }
+    }
+}
+
+fn default_alert_topic() -> String {
+    "orderbook.alerts".to_string()
+}
+
+fn default_imbalance_topic() -> String {
+    "orderbook.imbalance".to_string()
+}
+
+impl PublisherConfig {
+    /// Check the connection settings
+    pub fn validate(&self) -> Result<(), String> {
+        if self.url.is_empty() {
+            return Err("Publisher URL cannot be empty".to_string());
+        }
+        if self.trade_topic.is_empty() || self.depth_topic.is_empty() || self.alert_topic.is_empty() || self.imbalance_topic.is_empty() {
+            return Err("Publisher topics cannot be empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Publisher error types
+#[derive(Debug, thiserror::Error)]
+pub enum PublishError {
+    #[error("{0} support is not compiled in; rebuild with --features {0}")]
+    Unavailable(PublisherBackend),
+
+    #[error("Failed to connect to {backend} at {url}: {message}")]
+    Connect {
+        backend: PublisherBackend,
+        url: String,
+        message: String,
+    },
+
+    #[error("Failed to publish to {topic}: {message}")]
+    Send { topic: String, message: String },
+
+    #[error("Failed to encode feed message: {0}")]
+    Encode(String),
+}
+
+pub type PublishResult<T> = Result<T, PublishError>;
+
+/// Future returned by [`FeedSink::send`]
+pub type SendFuture<'a> = Pin<Box<dyn Future<Output = PublishResult<()>> + Send + 'a>>;
+
+/// Destination for encoded feed messages
+pub trait FeedSink: Send {
+    /// Send one encoded message to a topic
+    fn send<'a>(&'a mut self, topic: &'a str, payload: Vec<u8>) -> SendFuture<'a>;
+}
+
+/// Connect a sink for `backend`, failing if its feature is not compiled in
+pub(crate) async fn connect_sink(backend: PublisherBackend, url: &str) -> PublishResult<Box<dyn FeedSink>> {
+    match backend {
+        #[cfg(feature = "kafka")]
+        PublisherBackend::Kafka => Ok(Box::new(kafka::KafkaSink::connect(url).await?)),
+        #[cfg(feature = "nats")]
+        PublisherBackend::Nats => Ok(Box::new(nats::NatsSink::connect(url).await?)),
+        #[cfg(feature = "redis")]
+        PublisherBackend::Redis => Ok(Box::new(redis::RedisSink::connect(url).await?)),
+        #[allow(unreachable_patterns)]
+        backend => {
+            let _ = url;
+            Err(PublishError::Unavailable(backend))
+        }
+    }
+}
+
+/// Publishes sequenced trades and depth updates to a broker
+pub struct FeedPublisher {
+    config: PublisherConfig,
+    sink: Box<dyn FeedSink>,
+    /// Shared with the server, which answers resync requests from it
+    sequencer: Arc<Mutex<FeedSequencer>>,
+    /// Shared with the server, which registers subscriptions in it
+    alerts: Arc<Mutex<PriceAlerts>>,
+    /// Depth detail the published book shows
+    mask: DepthMask,
+    published: u64,
+}
+
+impl FeedPublisher {
+    /// Connect to the configured broker
+    pub async fn connect(config: PublisherConfig) -> PublishResult<Self> {
+        let sink = connect_sink(config.backend, &config.url).await?;
+        Ok(Self::with_sink(config, sink))
+    }
+
+    /// Publish through an already connected sink
+    pub fn with_sink(config: PublisherConfig, sink: Box<dyn FeedSink>) -> Self {
+        Self {
+            config,
+            sink,
+            sequencer: Arc::new(Mutex::new(FeedSequencer::new())),
+            alerts: Arc::new(Mutex::new(PriceAlerts::new())),
+            mask: DepthMask::default(),
+            published: 0,
+        }
+    }
+
+    /// Publish the book as `mask` shows it
+    pub fn with_mask(mut self, mask: DepthMask) -> Self {
+        self.mask = mask;
+        self
+    }
+
+    /// Messages published so far
+    pub fn published(&self) -> u64 {
+        self.published
+    }
+
+    /// The book as published, for correcting subscribers after a gap
+    pub fn sequencer(&self) -> Arc<Mutex<FeedSequencer>> {
+        self.sequencer.clone()
+    }
+
+    /// Price-level alert subscriptions checked against each depth update
+    pub fn alerts(&self) -> Arc<Mutex<PriceAlerts>> {
+        self.alerts.clone()
+    }
+
+    /// Publish the trades and depth changes in a snapshot, and any alerts
+    /// they set off, returning how many messages were sent
+    pub async fn publish_snapshot(&mut self, snapshot: &DepthSnapshot) -> PublishResult<usize> {
+        let masked;
+        let snapshot = match self.mask.is_exact() {
+            true => snapshot,
+            false => {
+                masked = self.mask.apply(snapshot);
+                &masked
+            }
+        };
+        let (messages, quotes) = {
+            let mut sequencer = self.sequencer.lock().await;
+            let messages = sequencer.on_snapshot(snapshot);
+            (messages, (sequencer.best(Side::Buy), sequencer.best(Side::Sell)))
+        };
+        let alerts = match messages.iter().find_map(|message| match message {
+            FeedMessage::Depth(update) => Some(update),
+            FeedMessage::Trade { .. } | FeedMessage::Imbalance { .. } => None,
+        }) {
+            Some(update) => self.alerts.lock().await.on_update(update, quotes.0, quotes.1),
+            None => Vec::new(),
+        };
+
+        for message in &messages {
+            let topic = match message {
+                FeedMessage::Trade { .. } => &self.config.trade_topic,
+                FeedMessage::Depth(_) => &self.config.depth_topic,
+                FeedMessage::Imbalance { .. } => &self.config.imbalance_topic,
+            };
+            let payload = self.config.format.encode(message).map_err(PublishError::Encode)?;
+            self.sink.send(topic, payload).await?;
+            self.published += 1;
+        }
+        for alert in &alerts {
+            let payload = self.config.format.encode(alert).map_err(PublishError::Encode)?;
+            self.sink.send(&self.config.alert_topic, payload).await?;
+            self.published += 1;
+        }
+        Ok(messages.len() + alerts.len())
+    }
+
+    /// Publish every snapshot from the broadcast until it closes
+    ///
+    /// Snapshots missed while the publisher lags are skipped, and the
+    /// sequence jumps by one number per skipped snapshot so consumers see
+    /// the gap. The next depth update still brings their book up to date,
+    /// or they can resync, but the missed trades are not republished.
+    pub async fn run(mut self, mut snapshots: broadcast::Receiver<DepthSnapshot>) {
+        loop {
+            match snapshots.recv().await {
+                Ok(snapshot) => {
+                    if let Err(e) = self.publish_snapshot(&snapshot).await {
+                        warn!("Feed publisher: {}", e);
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("Feed publisher fell behind and skipped {} snapshots", skipped);
+                    self.sequencer.lock().await.skip(skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+        debug!("Feed publisher stopped after {} messages", self.published);
+    }
+}
+
+#[cfg(feature = "kafka")]
+mod kafka {
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+    use rskafka::client::partition::{Compression, PartitionClient, UnknownTopicHandling};
+    use rskafka::client::{Client, ClientBuilder};
+    use rskafka::chrono::DateTime;
+    use rskafka::record::Record;
+    use crate::logging::current_timestamp;
+    use super::{FeedSink, PublishError, PublishResult, PublisherBackend, SendFuture};
+
+    /// Produces to partition 0 of each topic
+    pub struct KafkaSink {
+        client: Client,
+        partitions: BTreeMap<String, Arc<PartitionClient>>,
+    }
+
+    impl KafkaSink {
+        pub async fn connect(url: &str) -> PublishResult<Self> {
+            let client = ClientBuilder::new(vec![url.to_string()])
+                .build()
+                .await
+                .map_err(|e| PublishError::Connect {
+                    backend: PublisherBackend::Kafka,
+                    url: url.to_string(),
+                    message: e.to_string(),
+                })?;
+            Ok(Self { client, partitions: BTreeMap::new() })
+        }
+
+        async fn partition(&mut self, topic: &str) -> PublishResult<Arc<PartitionClient>> {
+            if let Some(partition) = self.partitions.get(topic) {
+                return Ok(partition.clone());
+            }
+            let partition = self.client
+                .partition_client(topic, 0, UnknownTopicHandling::Retry)
+                .await
+                .map_err(|e| PublishError::Send { topic: topic.to_string(), message: e.to_string() })?;
+            let partition = Arc::new(partition);
+            self.partitions.insert(topic.to_string(), partition.clone());
+            Ok(partition)
+        }
+    }
+
+    impl FeedSink for KafkaSink {
+        fn send<'a>(&'a mut self, topic: &'a str, payload: Vec<u8>) -> SendFuture<'a> {
+            Box::pin(async move {
+                let record = Record {
+                    key: None,
+                    value: Some(payload),
+                    headers: BTreeMap::new(),
+                    timestamp: DateTime::from_timestamp_millis(current_timestamp() as i64).unwrap_or_default(),
+                };
+                self.partition(topic).await?
+                    .produce(vec![record], Compression::NoCompression)
+                    .await
+                    .map_err(|e| PublishError::Send { topic: topic.to_string(), message: e.to_string() })?;
+                Ok(())
+            })
+        }
+    }
+}
+
+#[cfg(feature = "nats")]
+mod nats {
+    use super::{FeedSink, PublishError, PublishResult, PublisherBackend, SendFuture};
+
+    pub struct NatsSink {
+        client: async_nats::Client,
+    }
+
+    impl NatsSink {
+        pub async fn connect(url: &str) -> PublishResult<Self> {
+            let client = async_nats::connect(url)
+                .await
+                .map_err(|e| PublishError::Connect {
+                    backend: PublisherBackend::Nats,
+                    url: url.to_string(),
+                    message: e.to_string(),
+                })?;
+            Ok(Self { client })
+        }
+    }
+
+    impl FeedSink for NatsSink {
+        fn send<'a>(&'a mut self, topic: &'a str, payload: Vec<u8>) -> SendFuture<'a> {
+            Box::pin(async move {
+                self.client
+                    .publish(topic.to_string(), payload.into())
+                    .await
+                    .map_err(|e| PublishError::Send { topic: topic.to_string(), message: e.to_string() })
+            })
+        }
+    }
+}
+
+#[cfg(feature = "redis")]
+mod redis {
+    use ::redis::aio::MultiplexedConnection;
+    use ::redis::AsyncCommands;
+    use super::{FeedSink, PublishError, PublishResult, PublisherBackend, SendFuture};
+
+    /// Publishes each message on the Redis channel named by the topic
+    pub struct RedisSink {
+        connection: MultiplexedConnection,
+    }
+
+    impl RedisSink {
+        pub async fn connect(url: &str) -> PublishResult<Self> {
+            let connect_error = |e: ::redis::RedisError| PublishError::Connect {
+                backend: PublisherBackend::Redis,
+                url: url.to_string(),
+                message: e.to_string(),
+            };
+            let client = ::redis::Client::open(url).map_err(connect_error)?;
+            let connection = client.get_multiplexed_async_connection().await.map_err(connect_error)?;
+            Ok(Self { connection })
+        }
+    }
+
+    impl FeedSink for RedisSink {
+        fn send<'a>(&'a mut self, topic: &'a str, payload: Vec<u8>) -> SendFuture<'a> {
+            Box::pin(async move {
+                self.connection
+                    .publish::<_, _, i64>(topic, payload)
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| PublishError::Send { topic: topic.to_string(), message: e.to_string() })
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use crate::engine::{OrderBook, OrderBookEngine};
+    use crate::metrics::PipelineWatermark;
+    use crate::queue_fifo::FifoLevel;
+    use crate::types::{Order, Side};
+
+    type Sent = Arc<Mutex<Vec<(String, Vec<u8>)>>>;
+
+    /// Keeps every message it is sent
+    #[derive(Clone, Default)]
+    struct RecordingSink(Sent);
+
+    impl FeedSink for RecordingSink {
+        fn send<'a>(&'a mut self, topic: &'a str, payload: Vec<u8>) -> SendFuture<'a> {
+            self.0.lock().unwrap().push((topic.to_string(), payload));
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_publishes_trades_and_depth_to_their_topics() {
+        let sink = RecordingSink::default();
+        let config = PublisherConfig { format: FeedFormat::Bincode, ..Default::default() };
+        let mut publisher = FeedPublisher::with_sink(config, Box::new(sink.clone()));
+
+        let mut book = OrderBook::<FifoLevel>::new();
+        let ts = crate::time::now_ns();
+        book.place(Order::new_limit(1, Side::Sell, 100, 101_0000, ts)).unwrap();
+        let mut snapshot = book.snapshot();
+        snapshot.trades = book.place(Order::new_market(2, Side::Buy, 40, ts)).unwrap();
+        snapshot.asks = book.snapshot().asks;
+        // Block trades reach the trade topic tagged
+        snapshot.block_trades = snapshot.trades.clone();
+        let watermark = PipelineWatermark { events: 1, first_ingest_ns: 10, last_match_ns: 20, publish_ns: 30 };
+        snapshot.watermark = Some(watermark);
+
+        assert_eq!(publisher.publish_snapshot(&snapshot).await.unwrap(), 2);
+        assert_eq!(publisher.published(), 2);
+
+        let sent = sink.0.lock().unwrap().clone();
+        assert_eq!(sent[0].0, "orderbook.trades");
+        assert_eq!(sent[1].0, "orderbook.depth");
+        let trade = FeedFormat::Bincode.decode(&sent[0].1).unwrap();
+        assert!(matches!(trade, FeedMessage::Trade { seq: 1, trade, block: true, watermark: Some(marked) } if trade.qty == 40 && marked == watermark));
+        let FeedMessage::Depth(update) = FeedFormat::Bincode.decode(&sent[1].1).unwrap() else { panic!("expected a depth update") };
+        assert_eq!((update.seq, update.watermark), (2, Some(watermark)));
+    }
+
+    #[tokio::test]
+    async fn test_publishes_alerts_to_their_topic() {
+        use crate::alerts::{Alert, AlertCondition};
+
+        let sink = RecordingSink::default();
+        let mut publisher = FeedPublisher::with_sink(PublisherConfig::default(), Box::new(sink.clone()));
+        let condition = AlertCondition::AskAtMost { price: 101_0000 };
+        publisher.alerts().lock().await.subscribe(None, condition, false).unwrap();
+
+        let mut book = OrderBook::<FifoLevel>::new();
+        let ts = crate::time::now_ns();
+        book.place(Order::new_limit(1, Side::Sell, 100, 102_0000, ts)).unwrap();
+        assert_eq!(publisher.publish_snapshot(&book.snapshot()).await.unwrap(), 1);
+        book.place(Order::new_limit(2, Side::Sell, 100, 101_0000, ts)).unwrap();
+        assert_eq!(publisher.publish_snapshot(&book.snapshot()).await.unwrap(), 2);
+
+        let sent = sink.0.lock().unwrap().clone();
+        assert_eq!(sent[2].0, "orderbook.alerts");
+        let alert: Alert = serde_json::from_slice(&sent[2].1).unwrap();
+        assert_eq!((alert.alert_id, alert.seq, alert.condition, alert.value), (1, 2, condition, Some(101_0000)));
+    }
+
+    #[tokio::test]
+    async fn test_lagging_leaves_a_sequence_gap() {
+        let sink = RecordingSink::default();
+        let config = PublisherConfig { format: FeedFormat::Bincode, ..Default::default() };
+        let publisher = FeedPublisher::with_sink(config, Box::new(sink.clone()));
+
+        // Three snapshots into room for one: the first two are lost
+        let (tx, rx) = broadcast::channel(1);
+        let mut book = OrderBook::<FifoLevel>::new();
+        let ts = crate::time::now_ns();
+        for (id, price) in [(1, 101_0000), (2, 102_0000), (3, 103_0000)] {
+            book.place(Order::new_limit(id, Side::Sell, 10, price, ts)).unwrap();
+            tx.send(book.snapshot()).unwrap();
+        }
+        drop(tx);
+        publisher.run(rx).await;
+
+        let sent = sink.0.lock().unwrap().clone();
+        assert_eq!(sent.len(), 1);
+        let FeedMessage::Depth(update) = FeedFormat::Bincode.decode(&sent[0].1).unwrap() else { panic!("expected a depth update") };
+        assert_eq!(update.seq, 3);
+        assert_eq!(update.asks.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_backend_without_feature_is_unavailable() {
+        #[cfg(not(feature = "nats"))]
+        {
+            let config = PublisherConfig { backend: PublisherBackend::Nats, ..Default::default() };
+            let err = FeedPublisher::connect(config).await.err().unwrap();
+            assert!(matches!(err, PublishError::Unavailable(PublisherBackend::Nats)));
+            assert!(err.to_string().contains("--features nats"));
+        }
+        assert!(PublisherConfig { trade_topic: String::new(), ..Default::default() }.validate().is_err());
+    }
+}
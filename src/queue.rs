@@ -1,5 +1,9 @@
 use crate::types::{Order, OrderId, Price, Qty, Side, Trade};
 
+/// Decides whether an incoming order may execute against a resting order,
+/// given `(maker_id, taker_id)`
+pub type MatchFilter = fn(OrderId, OrderId) -> bool;
+
 /// Trait defining the interface for order queue disciplines
 /// 
 /// This trait abstracts different matching algorithms (FIFO, Pro-Rata, etc.)
@@ -29,6 +33,68 @@ pub trait QueueDiscipline {
         price: Price,
     ) -> (Qty, Vec<Trade>);
 
+    /// Match a taker order against only the orders `filter` allows, leaving
+    /// the rest of the queue in place with its priority intact
+    /// 
+    /// Disciplines that cannot step over orders match only when the filter
+    /// allows every resting order, and otherwise leave the level untouched.
+    /// 
+    /// # Returns
+    /// * Tuple of (remaining_taker_qty, trades_generated)
+    fn match_against_filtered(
+        &mut self,
+        taker_id: OrderId,
+        taker_side: Side,
+        taker_qty: Qty,
+        price: Price,
+        filter: &dyn Fn(OrderId, OrderId) -> bool,
+    ) -> (Qty, Vec<Trade>) {
+        if self.resting().iter().all(|&(maker_id, _)| filter(maker_id, taker_id)) {
+            self.match_against(taker_id, taker_side, taker_qty, price)
+        } else {
+            (taker_qty, Vec::new())
+        }
+    }
+
+    /// Match a taker order as [`Self::match_against`] does, giving its
+    /// trades the timestamp `ts`
+    /// 
+    /// The default restamps the trades `match_against` returns.
+    fn match_against_at(
+        &mut self,
+        taker_id: OrderId,
+        taker_side: Side,
+        taker_qty: Qty,
+        price: Price,
+        ts: u128,
+    ) -> (Qty, Vec<Trade>) {
+        let (remaining, mut trades) = self.match_against(taker_id, taker_side, taker_qty, price);
+        for trade in &mut trades {
+            trade.ts = ts;
+        }
+        (remaining, trades)
+    }
+
+    /// Match a taker order as [`Self::match_against_filtered`] does, giving
+    /// its trades the timestamp `ts`
+    /// 
+    /// The default restamps the trades `match_against_filtered` returns.
+    fn match_against_filtered_at(
+        &mut self,
+        taker_id: OrderId,
+        taker_side: Side,
+        taker_qty: Qty,
+        price: Price,
+        ts: u128,
+        filter: &dyn Fn(OrderId, OrderId) -> bool,
+    ) -> (Qty, Vec<Trade>) {
+        let (remaining, mut trades) = self.match_against_filtered(taker_id, taker_side, taker_qty, price, filter);
+        for trade in &mut trades {
+            trade.ts = ts;
+        }
+        (remaining, trades)
+    }
+
     /// Cancel an order from the queue
     /// 
     /// # Arguments
@@ -44,8 +110,11 @@ pub trait QueueDiscipline {
     /// Check if the queue is empty
     fn is_empty(&self) -> bool;
 
-    /// Mark this price level as recently active (for latency tracking)
-    fn touch(&mut self);
+    /// Mark this price level as active at `ts` (for latency tracking)
+    ///
+    /// Levels stamp their own activity with the timestamps of the orders and
+    /// trades they see; the engine stamps the rest from its clock.
+    fn touch(&mut self, ts: u128);
 
     /// Get the timestamp of the last activity on this price level
     fn last_ts(&self) -> u128;
@@ -55,4 +124,31 @@ pub trait QueueDiscipline {
 
     /// Get the oldest order timestamp in the queue (for latency calculations)
     fn oldest_order_ts(&self) -> Option<u128>;
-}
\ No newline at end of file
+
+    /// Get the resting orders and their remaining quantities, in priority order
+    fn resting(&self) -> Vec<(OrderId, Qty)> {
+        self.orders().into_iter().map(|order| (order.id, order.qty)).collect()
+    }
+
+    /// Get the resting orders as they stand, in priority order
+    ///
+    /// The engine relies on this listing to tell filled makers from partly
+    /// filled ones, to apply match filters and self-match prevention, and to
+    /// find orders for cancels and quotes, so it must list every order the
+    /// level holds with its remaining quantity.
+    fn orders(&self) -> Vec<Order>;
+
+    /// Pay a fee to move a resting order ahead in the queue
+    /// 
+    /// # Returns
+    /// * Fee charged; disciplines without fee priority charge nothing and
+    ///   leave the queue as it is
+    fn pay_priority_fee(&mut self, _order_id: OrderId, _fee: u64) -> u64 {
+        0
+    }
+
+    /// Priority fees a resting order has paid, 0 if it is not here
+    fn priority_fee_paid(&self, _order_id: OrderId) -> u64 {
+        0
+    }
+}
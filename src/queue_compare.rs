@@ -0,0 +1,353 @@
+//! Side-by-side comparison of queue disciplines
+//!
+//! A [`DisciplineComparison`] replays one recorded command stream (an engine
+//! journal, or a generated [`Workload`](crate::generator::Workload)) into a
+//! fresh book for each registered [`QueueDiscipline`] and reports what each
+//! did with it: trades produced, how much of each order size bucket filled,
+//! and how long filled quantity waited in the queue. Waits are measured
+//! between the recorded timestamps of the resting order and the order that
+//! took it, so they do not depend on how fast the replay runs.
+
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::engine::{CommandEngine, EngineCommand, EngineEvent, OrderBook, OrderBookEngine};
+use crate::queue::QueueDiscipline;
+use crate::queue_fifo::FifoLevel;
+use crate::queue_priority::PriorityFeeLevel;
+use crate::time::now_ns;
+use crate::types::{OrderId, Qty};
+
+/// Default upper bounds of the order size buckets
+pub const DEFAULT_SIZE_BUCKETS: [Qty; 3] = [10, 100, 1_000];
+
+/// Replays a command stream into a book of one discipline
+pub type DisciplineReplay = fn(&[EngineCommand], &[Qty]) -> DisciplineStats;
+
+/// Limit orders of one size range and how much of them filled
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SizeBucketStats {
+    /// Largest order size in the bucket; unbounded for the last bucket
+    pub max_qty: Option<Qty>,
+    pub orders: usize,
+    pub qty: Qty,
+    /// Quantity of these orders that traded, passively or aggressively
+    pub filled: Qty,
+}
+
+impl SizeBucketStats {
+    /// Share of the bucket's quantity that traded
+    pub fn fill_ratio(&self) -> f64 {
+        if self.qty == 0 {
+            0.0
+        } else {
+            self.filled as f64 / self.qty as f64
+        }
+    }
+}
+
+/// What one discipline made of a command stream
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DisciplineStats {
+    pub discipline: String,
+    pub commands: usize,
+    /// Commands the engine refused, such as cancels of orders that had
+    /// already filled under this discipline
+    pub rejected: usize,
+    pub trades: usize,
+    pub volume: Qty,
+    /// Time each unit of passive fill spent resting, averaged over the
+    /// filled quantity (nanoseconds)
+    pub avg_queue_wait_ns: f64,
+    pub fills_by_size: Vec<SizeBucketStats>,
+}
+
+impl DisciplineStats {
+    /// Empty statistics with one bucket per bound plus an unbounded one
+    fn with_buckets(size_buckets: &[Qty]) -> Self {
+        let fills_by_size = size_buckets.iter().map(|&max_qty| Some(max_qty))
+            .chain(std::iter::once(None))
+            .map(|max_qty| SizeBucketStats { max_qty, ..SizeBucketStats::default() })
+            .collect();
+        Self { fills_by_size, ..Self::default() }
+    }
+}
+
+/// Replay `commands` into a fresh book of discipline `D`
+///
+/// Orders are restamped as they are placed, and the book's clock moved with
+/// them, since the engine checks their timestamps against its clock or the
+/// wall clock and a journal's may be far from either; queue waits use the
+/// recorded ones.
+pub fn replay_discipline<D: QueueDiscipline + Default + 'static>(commands: &[EngineCommand], size_buckets: &[Qty]) -> DisciplineStats {
+    let mut book = OrderBook::<D>::new();
+    let mut stats = DisciplineStats::with_buckets(size_buckets);
+    // Recorded placement time and size bucket of each limit order
+    let mut placed: HashMap<OrderId, (u128, usize)> = HashMap::new();
+    let mut waited: u128 = 0;
+    let mut passive_qty: Qty = 0;
+
+    for command in commands {
+        stats.commands += 1;
+        let (command, recorded_ts) = match command.clone() {
+            EngineCommand::Place(mut order) => {
+                let recorded_ts = order.ts;
+                if order.is_limit() {
+                    let bucket = size_buckets.iter().position(|&max_qty| order.qty <= max_qty)
+                        .unwrap_or(size_buckets.len());
+                    stats.fills_by_size[bucket].orders += 1;
+                    stats.fills_by_size[bucket].qty += order.qty;
+                    placed.insert(order.id, (recorded_ts, bucket));
+                }
+                order.ts = now_ns();
+                book.set_clock(order.ts);
+                (EngineCommand::Place(order), recorded_ts)
+            }
+            command => (command, 0),
+        };
+
+        match book.execute(command) {
+            Ok(EngineEvent::Placed(trades)) => {
+                for trade in trades {
+                    stats.trades += 1;
+                    stats.volume += trade.qty;
+                    for order_id in [trade.maker_id, trade.taker_id] {
+                        if let Some(&(_, bucket)) = placed.get(&order_id) {
+                            stats.fills_by_size[bucket].filled += trade.qty;
+                        }
+                    }
+                    if let Some(&(placed_ts, _)) = placed.get(&trade.maker_id) {
+                        waited += recorded_ts.saturating_sub(placed_ts) * trade.qty as u128;
+                        passive_qty += trade.qty;
+                    }
+                }
+            }
+            Ok(_) => {}
+            Err(_) => stats.rejected += 1,
+        }
+    }
+
+    if passive_qty > 0 {
+        stats.avg_queue_wait_ns = waited as f64 / passive_qty as f64;
+    }
+    stats
+}
+
+/// Queue disciplines to compare, in registration order
+#[derive(Debug, Clone)]
+pub struct DisciplineComparison {
+    disciplines: Vec<(String, DisciplineReplay)>,
+    size_buckets: Vec<Qty>,
+}
+
+impl Default for DisciplineComparison {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl DisciplineComparison {
+    /// No disciplines registered
+    pub fn new() -> Self {
+        Self {
+            disciplines: Vec::new(),
+            size_buckets: DEFAULT_SIZE_BUCKETS.to_vec(),
+        }
+    }
+
+    /// The disciplines this crate provides
+    pub fn builtin() -> Self {
+        let mut comparison = Self::new();
+        comparison.register::<FifoLevel>("fifo");
+        comparison.register::<PriorityFeeLevel>("priority_fee");
+        comparison
+    }
+
+    /// Bucket limit orders by size with these upper bounds, ascending
+    pub fn with_size_buckets(mut self, mut size_buckets: Vec<Qty>) -> Self {
+        size_buckets.sort_unstable();
+        size_buckets.dedup();
+        self.size_buckets = size_buckets;
+        self
+    }
+
+    /// Add a discipline, replacing any registered under the same name
+    pub fn register<D: QueueDiscipline + Default + 'static>(&mut self, name: impl Into<String>) {
+        let name = name.into();
+        let replay: DisciplineReplay = replay_discipline::<D>;
+        match self.disciplines.iter_mut().find(|(registered, _)| *registered == name) {
+            Some(entry) => entry.1 = replay,
+            None => self.disciplines.push((name, replay)),
+        }
+    }
+
+    /// Registered discipline names
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.disciplines.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Replay `commands` under one discipline
+    pub fn run(&self, name: &str, commands: &[EngineCommand]) -> Option<DisciplineStats> {
+        let (name, replay) = self.disciplines.iter().find(|(registered, _)| registered == name)?;
+        let mut stats = replay(commands, &self.size_buckets);
+        stats.discipline = name.clone();
+        Some(stats)
+    }
+
+    /// Replay `commands` under every discipline
+    pub fn compare(&self, commands: &[EngineCommand]) -> Vec<DisciplineStats> {
+        self.names()
+            .filter_map(|name| self.run(name, commands))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::HftWorkload;
+    use crate::types::{Order, Price, Side, Trade};
+
+    /// Last in, first out: the newest order at a level fills first
+    #[derive(Debug, Default)]
+    struct LifoLevel {
+        orders: Vec<Order>,
+    }
+
+    impl QueueDiscipline for LifoLevel {
+        fn enqueue(&mut self, order: Order) {
+            self.orders.push(order);
+        }
+
+        fn match_against(&mut self, taker_id: OrderId, taker_side: Side, taker_qty: Qty, price: Price) -> (Qty, Vec<Trade>) {
+            self.match_against_filtered(taker_id, taker_side, taker_qty, price, &|_, _| true)
+        }
+
+        fn match_against_filtered(
+            &mut self,
+            taker_id: OrderId,
+            taker_side: Side,
+            mut taker_qty: Qty,
+            price: Price,
+            filter: &dyn Fn(OrderId, OrderId) -> bool,
+        ) -> (Qty, Vec<Trade>) {
+            let mut trades = Vec::new();
+            for maker in self.orders.iter_mut().rev() {
+                if taker_qty == 0 {
+                    break;
+                }
+                if !filter(maker.id, taker_id) {
+                    continue;
+                }
+                let qty = taker_qty.min(maker.qty);
+                maker.qty -= qty;
+                taker_qty -= qty;
+                trades.push(Trade { maker_id: maker.id, taker_id, price, qty, ts: now_ns(), aggressor: taker_side });
+            }
+            self.orders.retain(|order| order.qty > 0);
+            (taker_qty, trades)
+        }
+
+        fn cancel(&mut self, order_id: OrderId) -> Qty {
+            match self.orders.iter().position(|order| order.id == order_id) {
+                Some(index) => self.orders.remove(index).qty,
+                None => 0,
+            }
+        }
+
+        fn total_qty(&self) -> Qty {
+            self.orders.iter().map(|order| order.qty).sum()
+        }
+
+        fn is_empty(&self) -> bool {
+            self.orders.is_empty()
+        }
+
+        fn touch(&mut self, _ts: u128) {}
+
+        fn last_ts(&self) -> u128 {
+            0
+        }
+
+        fn order_count(&self) -> usize {
+            self.orders.len()
+        }
+
+        fn oldest_order_ts(&self) -> Option<u128> {
+            self.orders.iter().map(|order| order.ts).min()
+        }
+
+        fn resting(&self) -> Vec<(OrderId, Qty)> {
+            self.orders.iter().rev().map(|order| (order.id, order.qty)).collect()
+        }
+
+        fn orders(&self) -> Vec<Order> {
+            self.orders.iter().rev().cloned().collect()
+        }
+    }
+
+    #[test]
+    fn test_compares_disciplines_on_one_stream() {
+        let commands = vec![
+            EngineCommand::Place(Order::new_limit(1, Side::Sell, 5, 101_0000, 1_000)),
+            EngineCommand::Place(Order::new_limit(2, Side::Sell, 50, 101_0000, 2_000)),
+            EngineCommand::Place(Order::new_market(3, Side::Buy, 5, 4_000)),
+            EngineCommand::Cancel(1),
+        ];
+        let mut comparison = DisciplineComparison::new();
+        comparison.register::<FifoLevel>("fifo");
+        comparison.register::<LifoLevel>("lifo");
+        assert_eq!(comparison.names().collect::<Vec<_>>(), vec!["fifo", "lifo"]);
+
+        let stats = comparison.compare(&commands);
+        let (fifo, lifo) = (&stats[0], &stats[1]);
+        assert_eq!((fifo.discipline.as_str(), lifo.discipline.as_str()), ("fifo", "lifo"));
+        assert_eq!((fifo.trades, fifo.volume), (1, 5));
+        assert_eq!((lifo.trades, lifo.volume), (1, 5));
+
+        // FIFO fills the older small order, which can then no longer be cancelled
+        assert_eq!(fifo.avg_queue_wait_ns, 3_000.0);
+        assert_eq!(fifo.fills_by_size[0], SizeBucketStats { max_qty: Some(10), orders: 1, qty: 5, filled: 5 });
+        assert_eq!(fifo.rejected, 1);
+
+        // LIFO fills the newer large order
+        assert_eq!(lifo.avg_queue_wait_ns, 2_000.0);
+        assert_eq!(lifo.fills_by_size[0].filled, 0);
+        assert_eq!(lifo.fills_by_size[1].filled, 5);
+        assert_eq!(lifo.fills_by_size[1].fill_ratio(), 0.1);
+        assert_eq!(lifo.rejected, 0);
+        assert_eq!(lifo.fills_by_size.len(), 4);
+        assert!(lifo.fills_by_size[3].max_qty.is_none());
+    }
+
+    #[test]
+    fn test_priority_fees_reorder_the_queue() {
+        let commands = vec![
+            EngineCommand::Place(Order::new_limit(1, Side::Sell, 5, 101_0000, 1_000)),
+            EngineCommand::Place(Order::new_limit(2, Side::Sell, 5, 101_0000, 2_000)),
+            EngineCommand::PayPriorityFee { order_id: 2, fee: 100 },
+            EngineCommand::Place(Order::new_market(3, Side::Buy, 5, 4_000)),
+        ];
+        let stats = DisciplineComparison::builtin().compare(&commands);
+        let (fifo, priority_fee) = (&stats[0], &stats[1]);
+        assert_eq!(priority_fee.discipline, "priority_fee");
+        assert_eq!(fifo.avg_queue_wait_ns, 3_000.0);
+        assert_eq!(priority_fee.avg_queue_wait_ns, 2_000.0);
+    }
+
+    #[test]
+    fn test_replays_generated_workload() {
+        let commands = HftWorkload::new().with_seed(5).generate(2_000).commands();
+        let comparison = DisciplineComparison::builtin().with_size_buckets(vec![100, 10]);
+        let stats = comparison.run("fifo", &commands).unwrap();
+        assert_eq!(stats.commands, commands.len());
+        // The workload's cancels are built against a FIFO book
+        assert_eq!(stats.rejected, 0);
+        assert!(stats.trades > 0);
+        assert_eq!(stats.fills_by_size.iter().map(|bucket| bucket.max_qty).collect::<Vec<_>>(), vec![Some(10), Some(100), None]);
+        assert!(comparison.run("pro_rata", &commands).is_none());
+
+        // Without fees being paid, fee priority reduces to time priority
+        let priority_fee = comparison.run("priority_fee", &commands).unwrap();
+        assert_eq!(DisciplineStats { discipline: "fifo".to_string(), ..priority_fee }, stats);
+    }
+}
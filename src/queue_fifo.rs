@@ -23,7 +23,7 @@ impl FifoLevel {
         Self {
             orders: VecDeque::new(),
             total_qty: 0,
-            last_activity_ts: now_ns(),
+            last_activity_ts: 0,
         }
     }
 
@@ -44,19 +44,41 @@ impl Default for FifoLevel {
 impl QueueDiscipline for FifoLevel {
     fn enqueue(&mut self, order: Order) {
         self.total_qty += order.qty;
+        let ts = order.ts;
         self.orders.push_back(order);
-        self.touch();
+        self.touch(ts);
     }
 
     fn match_against(
         &mut self,
         taker_id: OrderId,
-        _taker_side: Side,
+        taker_side: Side,
+        taker_qty: Qty,
+        price: Price,
+    ) -> (Qty, Vec<Trade>) {
+        self.match_against_at(taker_id, taker_side, taker_qty, price, now_ns())
+    }
+
+    fn match_against_filtered(
+        &mut self,
+        taker_id: OrderId,
+        taker_side: Side,
+        taker_qty: Qty,
+        price: Price,
+        filter: &dyn Fn(OrderId, OrderId) -> bool,
+    ) -> (Qty, Vec<Trade>) {
+        self.match_against_filtered_at(taker_id, taker_side, taker_qty, price, now_ns(), filter)
+    }
+
+    fn match_against_at(
+        &mut self,
+        taker_id: OrderId,
+        taker_side: Side,
         mut taker_qty: Qty,
         price: Price,
+        trade_ts: u128,
     ) -> (Qty, Vec<Trade>) {
         let mut trades = Vec::new();
-        let trade_ts = now_ns();
 
         // Process orders in FIFO order (front to back)
         while taker_qty > 0 && !self.orders.is_empty() {
@@ -72,6 +94,7 @@ impl QueueDiscipline for FifoLevel {
                 price,
                 qty: trade_qty,
                 ts: trade_ts,
+                aggressor: taker_side,
             };
             trades.push(trade);
 
@@ -86,7 +109,52 @@ impl QueueDiscipline for FifoLevel {
             }
         }
 
-        self.touch();
+        self.touch(trade_ts);
+        (taker_qty, trades)
+    }
+
+    fn match_against_filtered_at(
+        &mut self,
+        taker_id: OrderId,
+        taker_side: Side,
+        mut taker_qty: Qty,
+        price: Price,
+        trade_ts: u128,
+        filter: &dyn Fn(OrderId, OrderId) -> bool,
+    ) -> (Qty, Vec<Trade>) {
+        let mut trades = Vec::new();
+
+        // Walk the queue in time priority, stepping over orders the taker may not hit
+        let mut index = 0;
+        while taker_qty > 0 && index < self.orders.len() {
+            let maker_order = &mut self.orders[index];
+            if !filter(maker_order.id, taker_id) {
+                index += 1;
+                continue;
+            }
+
+            let trade_qty = std::cmp::min(taker_qty, maker_order.qty);
+            trades.push(Trade {
+                maker_id: maker_order.id,
+                taker_id,
+                price,
+                qty: trade_qty,
+                ts: trade_ts,
+                aggressor: taker_side,
+            });
+
+            taker_qty -= trade_qty;
+            maker_order.qty -= trade_qty;
+            self.total_qty -= trade_qty;
+
+            if maker_order.qty == 0 {
+                self.orders.remove(index);
+            } else {
+                index += 1;
+            }
+        }
+
+        self.touch(trade_ts);
         (taker_qty, trades)
     }
 
@@ -96,7 +164,6 @@ impl QueueDiscipline for FifoLevel {
             if self.orders[i].id == order_id {
                 let cancelled_order = self.orders.remove(i).unwrap();
                 self.total_qty -= cancelled_order.qty;
-                self.touch();
                 return cancelled_order.qty;
             }
         }
@@ -111,8 +178,8 @@ impl QueueDiscipline for FifoLevel {
         self.orders.is_empty()
     }
 
-    fn touch(&mut self) {
-        self.last_activity_ts = now_ns();
+    fn touch(&mut self, ts: u128) {
+        self.last_activity_ts = ts;
     }
 
     fn last_ts(&self) -> u128 {
@@ -126,6 +193,14 @@ impl QueueDiscipline for FifoLevel {
     fn oldest_order_ts(&self) -> Option<u128> {
         self.orders.front().map(|order| order.ts)
     }
+
+    fn resting(&self) -> Vec<(OrderId, Qty)> {
+        self.orders.iter().map(|order| (order.id, order.qty)).collect()
+    }
+
+    fn orders(&self) -> Vec<Order> {
+        self.orders.iter().cloned().collect()
+    }
 }
 
 #[cfg(test)]
@@ -257,6 +332,26 @@ mod tests {
         assert_eq!(level.total_qty(), 0);
     }
 
+    #[test]
+    fn test_fifo_matching_filtered_skips_disallowed_makers() {
+        let mut level = FifoLevel::new();
+        
+        level.enqueue(create_test_order(1, Side::Buy, 100, 5000));
+        level.enqueue(create_test_order(2, Side::Buy, 100, 5000));
+        level.enqueue(create_test_order(3, Side::Buy, 100, 5000));
+        
+        // Only even makers may trade; the skipped order keeps its place
+        let (remaining_qty, trades) =
+            level.match_against_filtered(9, Side::Sell, 150, 5000, &|maker, _| maker % 2 == 0);
+        
+        assert_eq!(remaining_qty, 50);
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].maker_id, 2);
+        assert_eq!(level.order_count(), 2);
+        assert_eq!(level.total_qty(), 200);
+        assert_eq!(level.orders.front().map(|o| o.id), Some(1));
+    }
+
     #[test]
     fn test_cancel_order() {
         let mut level = FifoLevel::new();
@@ -311,37 +406,22 @@ mod tests {
 
     #[test]
     fn test_timestamp_tracking() {
+        // Activity is stamped from the timestamps the level sees, never the wall clock
         let mut level = FifoLevel::new();
-        let initial_ts = level.last_ts();
-        
-        // Sleep briefly to ensure timestamp difference
-        std::thread::sleep(std::time::Duration::from_millis(1));
-        
-        // Touch should update timestamp
-        level.touch();
-        assert!(level.last_ts() > initial_ts);
-        
-        let touch_ts = level.last_ts();
-        
-        // Enqueue should update timestamp
-        std::thread::sleep(std::time::Duration::from_millis(1));
-        let order = create_test_order(1, Side::Buy, 100, 5000);
-        level.enqueue(order);
-        assert!(level.last_ts() > touch_ts);
-        
-        let enqueue_ts = level.last_ts();
-        
-        // Match should update timestamp
-        std::thread::sleep(std::time::Duration::from_millis(1));
-        level.match_against(2, Side::Sell, 50, 5000);
-        assert!(level.last_ts() > enqueue_ts);
-        
-        let match_ts = level.last_ts();
-        
-        // Cancel should update timestamp
-        std::thread::sleep(std::time::Duration::from_millis(1));
+        assert_eq!(level.last_ts(), 0);
+
+        level.touch(1_000);
+        assert_eq!(level.last_ts(), 1_000);
+
+        level.enqueue(Order::new_limit(1, Side::Buy, 100, 5000, 2_000));
+        assert_eq!(level.last_ts(), 2_000);
+
+        level.match_against_at(2, Side::Sell, 50, 5000, 3_000);
+        assert_eq!(level.last_ts(), 3_000);
+
+        // Cancels are stamped by the engine, which knows the time
         level.cancel(1);
-        assert!(level.last_ts() > match_ts);
+        assert_eq!(level.last_ts(), 3_000);
     }
 
     #[test]
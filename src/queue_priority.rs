@@ -0,0 +1,300 @@
+//! Experimental fee-for-priority queue discipline
+//!
+//! For mechanism-design research: a resting order can pay a priority fee to
+//! move ahead of orders that paid less at its price level. Fees add up, are
+//! kept when the order is later filled or cancelled, and rank orders before
+//! time does; orders that paid the same total keep time priority between
+//! them, so a level where nobody pays behaves exactly like FIFO.
+
+use std::collections::VecDeque;
+use crate::queue::QueueDiscipline;
+use crate::types::{Order, OrderId, Price, Qty, Side, Trade};
+use crate::time::now_ns;
+
+/// A resting order and the priority fees it has paid
+#[derive(Debug, Clone)]
+struct FeeOrder {
+    order: Order,
+    fee: u64,
+    /// Arrival at the level, which ranks orders that paid the same
+    seq: u64,
+}
+
+impl FeeOrder {
+    /// Whether this order is ahead of `other` in the queue
+    fn ranks_before(&self, other: &FeeOrder) -> bool {
+        (self.fee, other.seq) > (other.fee, self.seq)
+    }
+}
+
+/// Price level ordered by priority fee paid, then by arrival
+#[derive(Debug, Clone)]
+pub struct PriorityFeeLevel {
+    /// Orders in priority order
+    orders: VecDeque<FeeOrder>,
+    /// Total quantity available at this level
+    total_qty: Qty,
+    /// Fees paid by orders at this level, including ones since removed
+    fees_collected: u64,
+    /// Arrival number of the next order to join
+    next_seq: u64,
+    /// Timestamp of last activity (for latency heatmap)
+    last_activity_ts: u128,
+}
+
+impl PriorityFeeLevel {
+    /// Create a new empty level
+    pub fn new() -> Self {
+        Self {
+            orders: VecDeque::new(),
+            total_qty: 0,
+            fees_collected: 0,
+            next_seq: 0,
+            last_activity_ts: 0,
+        }
+    }
+
+    /// Total priority fees paid at this level
+    pub fn fees_collected(&self) -> u64 {
+        self.fees_collected
+    }
+
+    /// Priority fees a resting order has paid, if it is at this level
+    pub fn fee_paid(&self, order_id: OrderId) -> Option<u64> {
+        self.orders.iter().find(|entry| entry.order.id == order_id).map(|entry| entry.fee)
+    }
+
+    /// Trade `qty` against the order at `index`, dropping it once filled
+    fn fill(&mut self, index: usize, taker_id: OrderId, taker_side: Side, qty: Qty, price: Price, ts: u128) -> Trade {
+        let maker = &mut self.orders[index].order;
+        maker.qty -= qty;
+        self.total_qty -= qty;
+        let trade = Trade { maker_id: maker.id, taker_id, price, qty, ts, aggressor: taker_side };
+        if maker.qty == 0 {
+            self.orders.remove(index);
+        }
+        trade
+    }
+}
+
+impl Default for PriorityFeeLevel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl QueueDiscipline for PriorityFeeLevel {
+    fn enqueue(&mut self, order: Order) {
+        self.total_qty += order.qty;
+        let ts = order.ts;
+        // Nothing paid yet, so it joins behind everyone
+        self.orders.push_back(FeeOrder { order, fee: 0, seq: self.next_seq });
+        self.next_seq += 1;
+        self.touch(ts);
+    }
+
+    fn match_against(
+        &mut self,
+        taker_id: OrderId,
+        taker_side: Side,
+        taker_qty: Qty,
+        price: Price,
+    ) -> (Qty, Vec<Trade>) {
+        self.match_against_at(taker_id, taker_side, taker_qty, price, now_ns())
+    }
+
+    fn match_against_filtered(
+        &mut self,
+        taker_id: OrderId,
+        taker_side: Side,
+        taker_qty: Qty,
+        price: Price,
+        filter: &dyn Fn(OrderId, OrderId) -> bool,
+    ) -> (Qty, Vec<Trade>) {
+        self.match_against_filtered_at(taker_id, taker_side, taker_qty, price, now_ns(), filter)
+    }
+
+    fn match_against_at(
+        &mut self,
+        taker_id: OrderId,
+        taker_side: Side,
+        taker_qty: Qty,
+        price: Price,
+        trade_ts: u128,
+    ) -> (Qty, Vec<Trade>) {
+        self.match_against_filtered_at(taker_id, taker_side, taker_qty, price, trade_ts, &|_, _| true)
+    }
+
+    fn match_against_filtered_at(
+        &mut self,
+        taker_id: OrderId,
+        taker_side: Side,
+        mut taker_qty: Qty,
+        price: Price,
+        trade_ts: u128,
+        filter: &dyn Fn(OrderId, OrderId) -> bool,
+    ) -> (Qty, Vec<Trade>) {
+        let mut trades = Vec::new();
+
+        let mut index = 0;
+        while taker_qty > 0 && index < self.orders.len() {
+            let maker = &self.orders[index].order;
+            if !filter(maker.id, taker_id) {
+                index += 1;
+                continue;
+            }
+            let trade_qty = taker_qty.min(maker.qty);
+            let filled = trade_qty == maker.qty;
+            trades.push(self.fill(index, taker_id, taker_side, trade_qty, price, trade_ts));
+            taker_qty -= trade_qty;
+            if !filled {
+                index += 1;
+            }
+        }
+
+        self.touch(trade_ts);
+        (taker_qty, trades)
+    }
+
+    fn cancel(&mut self, order_id: OrderId) -> Qty {
+        match self.orders.iter().position(|entry| entry.order.id == order_id) {
+            Some(index) => {
+                let cancelled = self.orders.remove(index).unwrap().order;
+                self.total_qty -= cancelled.qty;
+                cancelled.qty
+            }
+            None => 0,
+        }
+    }
+
+    fn total_qty(&self) -> Qty {
+        self.total_qty
+    }
+
+    fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+
+    fn touch(&mut self, ts: u128) {
+        self.last_activity_ts = ts;
+    }
+
+    fn last_ts(&self) -> u128 {
+        self.last_activity_ts
+    }
+
+    fn order_count(&self) -> usize {
+        self.orders.len()
+    }
+
+    fn oldest_order_ts(&self) -> Option<u128> {
+        self.orders.iter().map(|entry| entry.order.ts).min()
+    }
+
+    fn resting(&self) -> Vec<(OrderId, Qty)> {
+        self.orders.iter().map(|entry| (entry.order.id, entry.order.qty)).collect()
+    }
+
+    fn orders(&self) -> Vec<Order> {
+        self.orders.iter().map(|entry| entry.order.clone()).collect()
+    }
+
+    fn pay_priority_fee(&mut self, order_id: OrderId, fee: u64) -> u64 {
+        // Paying nothing changes nothing, including the order's place
+        if fee == 0 {
+            return 0;
+        }
+        let Some(index) = self.orders.iter().position(|entry| entry.order.id == order_id) else {
+            return 0;
+        };
+        let mut entry = self.orders.remove(index).unwrap();
+        entry.fee += fee;
+        self.fees_collected += fee;
+
+        // Behind every order that has paid more, or as much and arrived earlier
+        let position = self.orders.iter().position(|other| entry.ranks_before(other)).unwrap_or(self.orders.len());
+        self.orders.insert(position, entry);
+        fee
+    }
+
+    fn priority_fee_paid(&self, order_id: OrderId) -> u64 {
+        self.fee_paid(order_id).unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit(id: OrderId, qty: Qty) -> Order {
+        Order::new_limit(id, Side::Sell, qty, 100_0000, now_ns())
+    }
+
+    fn queue(level: &PriorityFeeLevel) -> Vec<OrderId> {
+        level.resting().into_iter().map(|(order_id, _)| order_id).collect()
+    }
+
+    #[test]
+    fn test_fees_buy_priority_ahead_of_time() {
+        let mut level = PriorityFeeLevel::new();
+        for id in 1..=4 {
+            level.enqueue(limit(id, 10));
+        }
+        assert_eq!(queue(&level), vec![1, 2, 3, 4]);
+
+        assert_eq!(level.pay_priority_fee(3, 5), 5);
+        assert_eq!(level.pay_priority_fee(4, 5), 5);
+        assert_eq!(level.pay_priority_fee(2, 2), 2);
+        // Equal fees keep time priority; unknown orders are not charged
+        assert_eq!(queue(&level), vec![3, 4, 2, 1]);
+        assert_eq!(level.pay_priority_fee(9, 5), 0);
+
+        // Fees accumulate
+        assert_eq!(level.pay_priority_fee(2, 4), 4);
+        assert_eq!(queue(&level), vec![2, 3, 4, 1]);
+        assert_eq!(level.fee_paid(2), Some(6));
+        assert_eq!(level.fees_collected(), 16);
+
+        let (remaining, trades) = level.match_against(10, Side::Buy, 15, 100_0000);
+        assert_eq!(remaining, 0);
+        assert_eq!(trades.iter().map(|trade| (trade.maker_id, trade.qty)).collect::<Vec<_>>(), vec![(2, 10), (3, 5)]);
+        assert_eq!(level.total_qty(), 25);
+
+        // Fees stay paid when an order leaves
+        assert_eq!(level.cancel(3), 5);
+        assert_eq!(level.fees_collected(), 16);
+        assert_eq!(queue(&level), vec![4, 1]);
+    }
+
+    #[test]
+    fn test_equal_fees_rank_by_arrival() {
+        let mut level = PriorityFeeLevel::new();
+        for id in 1..=4 {
+            level.enqueue(limit(id, 10));
+        }
+        // Paying nothing keeps an order's place
+        assert_eq!(level.pay_priority_fee(1, 0), 0);
+        assert_eq!(queue(&level), vec![1, 2, 3, 4]);
+
+        // An order that catches up with a later arrival goes ahead of it
+        level.pay_priority_fee(4, 3);
+        level.pay_priority_fee(2, 3);
+        assert_eq!(queue(&level), vec![2, 4, 1, 3]);
+        level.pay_priority_fee(3, 1);
+        level.pay_priority_fee(1, 1);
+        assert_eq!(queue(&level), vec![2, 4, 1, 3]);
+    }
+
+    #[test]
+    fn test_filtered_match_keeps_fee_priority() {
+        let mut level = PriorityFeeLevel::new();
+        level.enqueue(limit(1, 10));
+        level.enqueue(limit(2, 10));
+        level.pay_priority_fee(2, 1);
+
+        let (remaining, trades) = level.match_against_filtered(10, Side::Buy, 10, 100_0000, &|maker_id, _| maker_id != 2);
+        assert_eq!(remaining, 0);
+        assert_eq!(trades[0].maker_id, 1);
+        assert_eq!(queue(&level), vec![2]);
+    }
+}
@@ -0,0 +1,175 @@
+//! Redis pub/sub mirror of the WebSocket snapshot broadcast
+//!
+//! The [`RedisBridge`] publishes every depth snapshot on a Redis channel as
+//! the same JSON the WebSocket endpoint sends, so read-only consumers can
+//! scale out behind Redis instead of connecting to the engine host. Trades
+//! can also go to a channel of their own for dashboards that only chart
+//! prints. Requires `--features redis`.
+
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use tokio::sync::broadcast;
+use tracing::{debug, warn};
+use crate::engine::DepthSnapshot;
+use crate::publish::{connect_sink, FeedSink, PublishError, PublishResult, PublisherBackend};
+
+/// Redis connection and channels for the snapshot bridge
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RedisBridgeConfig {
+    /// Mirror snapshots to Redis while the server runs
+    pub enabled: bool,
+    /// Server address, e.g. `redis://127.0.0.1:6379`
+    pub url: String,
+    /// Channel receiving each snapshot as WebSocket JSON
+    pub snapshot_channel: String,
+    /// Channel receiving each trade as JSON; omit to skip trades
+    #[serde(default)]
+    pub trade_channel: Option<String>,
+}
+
+impl Default for RedisBridgeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: "redis://127.0.0.1:6379".to_string(),
+            snapshot_channel: "orderbook:snapshots".to_string(),
+            trade_channel: Some("orderbook:trades".to_string()),
+        }
+    }
+}
+
+impl RedisBridgeConfig {
+    /// Check the connection settings
+    pub fn validate(&self) -> Result<(), String> {
+        if self.url.is_empty() {
+            return Err("Redis URL cannot be empty".to_string());
+        }
+        if self.snapshot_channel.is_empty() || self.trade_channel.as_deref() == Some("") {
+            return Err("Redis channels cannot be empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Mirrors the snapshot broadcast to Redis channels
+pub struct RedisBridge {
+    config: RedisBridgeConfig,
+    sink: Box<dyn FeedSink>,
+    published: u64,
+}
+
+impl RedisBridge {
+    /// Connect to the configured Redis server
+    pub async fn connect(config: RedisBridgeConfig) -> PublishResult<Self> {
+        let sink = connect_sink(PublisherBackend::Redis, &config.url).await?;
+        Ok(Self::with_sink(config, sink))
+    }
+
+    /// Publish through an already connected sink
+    pub fn with_sink(config: RedisBridgeConfig, sink: Box<dyn FeedSink>) -> Self {
+        Self { config, sink, published: 0 }
+    }
+
+    /// Messages published so far
+    pub fn published(&self) -> u64 {
+        self.published
+    }
+
+    /// Publish a snapshot and its trades, returning how many messages were sent
+    pub async fn publish_snapshot(&mut self, snapshot: &DepthSnapshot) -> PublishResult<usize> {
+        let mut sent = 0;
+        if let Some(channel) = &self.config.trade_channel {
+            for trade in &snapshot.trades {
+                let payload = serde_json::to_vec(trade).map_err(|e| PublishError::Encode(e.to_string()))?;
+                self.sink.send(channel, payload).await?;
+                sent += 1;
+            }
+        }
+
+        let payload = serde_json::to_vec(snapshot).map_err(|e| PublishError::Encode(e.to_string()))?;
+        self.sink.send(&self.config.snapshot_channel, payload).await?;
+        sent += 1;
+
+        self.published += sent as u64;
+        Ok(sent)
+    }
+
+    /// Mirror every snapshot from the broadcast until it closes
+    ///
+    /// Like a slow WebSocket client, a lagging bridge skips snapshots rather
+    /// than queueing them.
+    pub async fn run(mut self, mut snapshots: broadcast::Receiver<DepthSnapshot>) {
+        loop {
+            match snapshots.recv().await {
+                Ok(snapshot) => {
+                    if let Err(e) = self.publish_snapshot(&snapshot).await {
+                        warn!("Redis bridge: {}", e);
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("Redis bridge fell behind and skipped {} snapshots", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+        debug!("Redis bridge stopped after {} messages", self.published);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use crate::engine::{OrderBook, OrderBookEngine};
+    use crate::publish::SendFuture;
+    use crate::queue_fifo::FifoLevel;
+    use crate::types::{Order, Side, Trade};
+
+    type Sent = Arc<Mutex<Vec<(String, Vec<u8>)>>>;
+
+    #[derive(Clone, Default)]
+    struct RecordingSink(Sent);
+
+    impl FeedSink for RecordingSink {
+        fn send<'a>(&'a mut self, topic: &'a str, payload: Vec<u8>) -> SendFuture<'a> {
+            self.0.lock().unwrap().push((topic.to_string(), payload));
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mirrors_websocket_json_and_trades() {
+        let sink = RecordingSink::default();
+        let mut bridge = RedisBridge::with_sink(RedisBridgeConfig::default(), Box::new(sink.clone()));
+
+        let mut book = OrderBook::<FifoLevel>::new();
+        let ts = crate::time::now_ns();
+        book.place(Order::new_limit(1, Side::Sell, 100, 101_0000, ts)).unwrap();
+        let mut snapshot = book.snapshot();
+        snapshot.trades = book.place(Order::new_market(2, Side::Buy, 40, ts)).unwrap();
+
+        assert_eq!(bridge.publish_snapshot(&snapshot).await.unwrap(), 2);
+        let sent = sink.0.lock().unwrap().clone();
+        assert_eq!(sent[0].0, "orderbook:trades");
+        assert_eq!(serde_json::from_slice::<Trade>(&sent[0].1).unwrap().qty, 40);
+        assert_eq!(sent[1].0, "orderbook:snapshots");
+        assert_eq!(sent[1].1, serde_json::to_string(&snapshot).unwrap().into_bytes());
+
+        // Without a trade channel only the snapshot goes out
+        let config = RedisBridgeConfig { trade_channel: None, ..Default::default() };
+        let mut bridge = RedisBridge::with_sink(config, Box::new(RecordingSink::default()));
+        assert_eq!(bridge.publish_snapshot(&snapshot).await.unwrap(), 1);
+        assert_eq!(bridge.published(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_connect_without_feature_is_unavailable() {
+        #[cfg(not(feature = "redis"))]
+        {
+            let err = RedisBridge::connect(RedisBridgeConfig::default()).await.err().unwrap();
+            assert!(matches!(err, PublishError::Unavailable(PublisherBackend::Redis)));
+        }
+        let config = RedisBridgeConfig { trade_channel: Some(String::new()), ..Default::default() };
+        assert!(config.validate().is_err());
+    }
+}
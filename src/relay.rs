@@ -0,0 +1,266 @@
+//! Multi-process feed fan-out
+//!
+//! The engine instance numbers every snapshot it broadcasts and keeps the
+//! most recent ones in a [`FeedLog`]. Relay instances subscribe to its
+//! `/feed` WebSocket and re-broadcast each snapshot to their own clients.
+//! After a disconnect or a sequence gap a relay reconnects with
+//! `?from=<seq>` and the upstream replays what it missed. If those snapshots
+//! have already left the log, the upstream sends its latest snapshot flagged
+//! as a reset instead. Relays keep the upstream's sequence numbers, so they
+//! can be chained.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::engine::DepthSnapshot;
+use crate::memory::{shrink_deque, Compaction};
+
+/// A snapshot with its position in the feed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SequencedSnapshot {
+    pub seq: u64,
+    /// Earlier snapshots could not be replayed; start over from this one
+    #[serde(default)]
+    pub reset: bool,
+    pub snapshot: DepthSnapshot,
+}
+
+/// Upstream feed and replay settings
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RelayConfig {
+    /// Engine feed to relay, e.g. `ws://engine:8080`; unset runs the engine
+    #[serde(default)]
+    pub upstream: Option<String>,
+    /// Key the upstream licenses the relayed depth to, when it enforces
+    /// entitlements
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Snapshots kept for relays that reconnect after a gap
+    pub replay_capacity: usize,
+    /// Wait before reconnecting to the upstream
+    pub reconnect_delay_ms: u64,
+}
+
+impl Default for RelayConfig {
+    fn default() -> Self {
+        Self {
+            upstream: None,
+            api_key: None,
+            replay_capacity: 1000,
+            reconnect_delay_ms: 1000,
+        }
+    }
+}
+
+impl RelayConfig {
+    /// Check the relay settings
+    pub fn validate(&self) -> Result<(), String> {
+        if self.replay_capacity == 0 {
+            return Err("Relay replay capacity must be greater than 0".to_string());
+        }
+        if self.reconnect_delay_ms == 0 {
+            return Err("Relay reconnect delay must be greater than 0".to_string());
+        }
+        if let Some(upstream) = &self.upstream {
+            if !upstream.starts_with("ws://") && !upstream.starts_with("wss://") {
+                return Err(format!("Relay upstream must be a ws:// or wss:// URL: {}", upstream));
+            }
+        }
+        Ok(())
+    }
+
+    /// Delay between reconnection attempts
+    pub fn reconnect_delay(&self) -> Duration {
+        Duration::from_millis(self.reconnect_delay_ms)
+    }
+
+    /// Feed URL on the upstream, resuming at `from` if given
+    pub fn feed_url(&self, from: Option<u64>) -> Option<String> {
+        let upstream = self.upstream.as_deref()?.trim_end_matches('/');
+        let params: Vec<String> = from.map(|from| format!("from={}", from)).into_iter()
+            .chain(self.api_key.as_ref().map(|key| format!("api_key={}", key)))
+            .collect();
+        Some(match params.is_empty() {
+            true => format!("{}/feed", upstream),
+            false => format!("{}/feed?{}", upstream, params.join("&")),
+        })
+    }
+}
+
+/// Recently broadcast snapshots, numbered in order
+#[derive(Debug, Clone)]
+pub struct FeedLog {
+    next_seq: u64,
+    frames: VecDeque<SequencedSnapshot>,
+    capacity: usize,
+}
+
+impl FeedLog {
+    /// Keep up to `capacity` snapshots for replay
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            next_seq: 1,
+            frames: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Number the next snapshot and keep it
+    pub fn append(&mut self, snapshot: DepthSnapshot) -> SequencedSnapshot {
+        let frame = SequencedSnapshot { seq: self.next_seq, reset: false, snapshot };
+        self.insert(frame.clone());
+        frame
+    }
+
+    /// Keep a snapshot numbered upstream
+    ///
+    /// A reset or out-of-order frame discards the log, since the snapshots
+    /// before it can no longer be replayed as a contiguous run.
+    pub fn insert(&mut self, frame: SequencedSnapshot) {
+        if frame.reset || frame.seq != self.next_seq {
+            self.frames.clear();
+        }
+        self.next_seq = frame.seq + 1;
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(frame);
+    }
+
+    /// Sequence number of the last snapshot, if any
+    pub fn last_seq(&self) -> Option<u64> {
+        self.frames.back().map(|frame| frame.seq)
+    }
+
+    /// Release capacity beyond the log's budget
+    pub fn compact(&mut self) -> Compaction {
+        shrink_deque(&mut self.frames, self.capacity)
+    }
+
+    /// Number of snapshots kept
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Whether no snapshots are kept
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Snapshots a subscriber starting at `from` should receive first
+    ///
+    /// Returns the kept snapshots from `from` on when the log still covers
+    /// them. Otherwise, including for a new subscriber, returns the latest
+    /// snapshot flagged as a reset.
+    pub fn replay(&self, from: Option<u64>) -> Vec<SequencedSnapshot> {
+        let oldest = self.frames.front().map(|frame| frame.seq);
+        match (from, oldest) {
+            (Some(from), _) if from == self.next_seq => Vec::new(),
+            (Some(from), Some(oldest)) if from >= oldest && from < self.next_seq => {
+                self.frames.iter().skip((from - oldest) as usize).cloned().collect()
+            }
+            _ => self.frames.back()
+                .map(|frame| SequencedSnapshot { reset: true, ..frame.clone() })
+                .into_iter()
+                .collect(),
+        }
+    }
+}
+
+/// What a relay should do with a frame from its upstream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameAction {
+    /// Next in sequence (or a reset): re-broadcast it
+    Apply,
+    /// Already seen, e.g. overlap after a replay
+    Duplicate,
+    /// Frames are missing; reconnect from `expected`
+    Gap { expected: u64 },
+}
+
+/// Tracks the last upstream sequence number a relay applied
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RelayCursor {
+    last_seq: Option<u64>,
+}
+
+impl RelayCursor {
+    /// Decide what to do with a frame, advancing past applied ones
+    pub fn accept(&mut self, frame: &SequencedSnapshot) -> FrameAction {
+        let action = match self.last_seq {
+            _ if frame.reset => FrameAction::Apply,
+            None => FrameAction::Apply,
+            Some(last) if frame.seq <= last => FrameAction::Duplicate,
+            Some(last) if frame.seq == last + 1 => FrameAction::Apply,
+            Some(last) => FrameAction::Gap { expected: last + 1 },
+        };
+        if action == FrameAction::Apply {
+            self.last_seq = Some(frame.seq);
+        }
+        action
+    }
+
+    /// Sequence number to resume from after reconnecting
+    pub fn resume_from(&self) -> Option<u64> {
+        self.last_seq.map(|seq| seq + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{OrderBook, OrderBookEngine};
+    use crate::queue_fifo::FifoLevel;
+
+    fn snapshot() -> DepthSnapshot {
+        OrderBook::<FifoLevel>::new().snapshot()
+    }
+
+    #[test]
+    fn test_log_replays_from_sequence_or_resets() {
+        let mut log = FeedLog::new(3);
+        for _ in 0..5 {
+            log.append(snapshot());
+        }
+        assert_eq!(log.last_seq(), Some(5));
+        assert_eq!(log.len(), 3);
+
+        let seqs = |frames: Vec<SequencedSnapshot>| frames.iter().map(|f| (f.seq, f.reset)).collect::<Vec<_>>();
+        assert_eq!(seqs(log.replay(Some(4))), vec![(4, false), (5, false)]);
+        assert_eq!(seqs(log.replay(Some(6))), vec![]);
+        // Too old, too new (upstream restarted) and brand new subscribers resync
+        assert_eq!(seqs(log.replay(Some(2))), vec![(5, true)]);
+        assert_eq!(seqs(log.replay(Some(40))), vec![(5, true)]);
+        assert_eq!(seqs(log.replay(None)), vec![(5, true)]);
+        assert!(FeedLog::new(3).replay(None).is_empty());
+
+        // A relay's log keeps upstream numbering and drops history across a gap
+        log.insert(SequencedSnapshot { seq: 9, reset: true, snapshot: snapshot() });
+        assert_eq!(seqs(log.replay(Some(9))), vec![(9, true)]);
+        assert_eq!(log.len(), 1);
+        assert_eq!(log.append(snapshot()).seq, 10);
+    }
+
+    #[test]
+    fn test_cursor_detects_gaps_and_duplicates() {
+        let frame = |seq, reset| SequencedSnapshot { seq, reset, snapshot: snapshot() };
+        let mut cursor = RelayCursor::default();
+        assert_eq!(cursor.resume_from(), None);
+
+        assert_eq!(cursor.accept(&frame(7, true)), FrameAction::Apply);
+        assert_eq!(cursor.accept(&frame(8, false)), FrameAction::Apply);
+        assert_eq!(cursor.accept(&frame(8, false)), FrameAction::Duplicate);
+        assert_eq!(cursor.accept(&frame(11, false)), FrameAction::Gap { expected: 9 });
+        assert_eq!(cursor.resume_from(), Some(9));
+        assert_eq!(cursor.accept(&frame(3, true)), FrameAction::Apply);
+        assert_eq!(cursor.resume_from(), Some(4));
+
+        let config = RelayConfig { upstream: Some("ws://engine:8080/".to_string()), ..Default::default() };
+        assert_eq!(config.feed_url(Some(9)).unwrap(), "ws://engine:8080/feed?from=9");
+        let keyed = RelayConfig { api_key: Some("relay".to_string()), ..config };
+        assert_eq!(keyed.feed_url(Some(9)).unwrap(), "ws://engine:8080/feed?from=9&api_key=relay");
+        assert_eq!(keyed.feed_url(None).unwrap(), "ws://engine:8080/feed?api_key=relay");
+        assert!(RelayConfig { upstream: Some("engine:8080".to_string()), ..Default::default() }.validate().is_err());
+    }
+}
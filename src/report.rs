@@ -0,0 +1,556 @@
+//! End-of-run reports
+//!
+//! A [`RunReport`] captures a simulator's metrics and the series behind the
+//! demo charts (spread over time, inventory, PnL) and renders them as a
+//! self-contained HTML page with inline SVG charts, or as Markdown tables.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::analytics::VolumeProfileSummary;
+use crate::data::DataSourceMetadata;
+use crate::engine::CommandEngine;
+use crate::metrics::PerformanceSnapshot;
+use crate::sim::{FillAccounting, HedgeStats, MetricsPoint, SimulationMode, Simulator};
+use crate::time::now_ns;
+use crate::types::{price_utils, ContractSpec, ExecutionSummary, Metrics, Payoff};
+use crate::price_format::PricePrecision;
+use crate::warmup::WarmupBoundary;
+use crate::funding::FundingStats;
+use crate::latency::LatencyCost;
+
+/// Output format for a run report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    #[default]
+    Html,
+    Markdown,
+}
+
+impl ReportFormat {
+    /// Infer the format from a file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "html" | "htm" => Some(Self::Html),
+            "md" | "markdown" => Some(Self::Markdown),
+            _ => None,
+        }
+    }
+
+    /// HTTP content type for the rendered report
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Html => "text/html; charset=utf-8",
+            Self::Markdown => "text/markdown; charset=utf-8",
+        }
+    }
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "html" => Ok(Self::Html),
+            "markdown" | "md" => Ok(Self::Markdown),
+            _ => Err(format!("Unknown report format '{}', expected html or markdown", s)),
+        }
+    }
+}
+
+impl fmt::Display for ReportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Html => write!(f, "html"),
+            Self::Markdown => write!(f, "markdown"),
+        }
+    }
+}
+
+/// Most rows of each series listed in a Markdown report
+const MARKDOWN_SERIES_ROWS: usize = 20;
+
+/// Snapshot of a run for reporting
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunReport {
+    /// When the report was generated (nanoseconds since the epoch)
+    pub generated_at: u128,
+    pub mode: SimulationMode,
+    pub metrics: Metrics,
+    pub fills: FillAccounting,
+    pub hedging: Option<HedgeStats>,
+    pub data_source: Option<DataSourceMetadata>,
+    pub performance: Option<PerformanceSnapshot>,
+    /// Spread over time as (timestamp, spread in ticks), at the finest
+    /// resolution that still covers the run
+    pub spreads: Vec<(u128, i64)>,
+    /// Inventory and PnL over time
+    pub metrics_history: Vec<MetricsPoint>,
+    /// Traded volume by price over the run
+    pub volume_profile: VolumeProfileSummary,
+    /// Traded volume by price for each retained session
+    pub session_volume_profiles: Vec<VolumeProfileSummary>,
+    /// Recent trades grouped by aggressive order, with each maker's fills
+    #[serde(default)]
+    pub executions: Vec<ExecutionSummary>,
+    /// Where the warm-up ended and measurement began, if one ran
+    #[serde(default)]
+    pub warmup: Option<WarmupBoundary>,
+    /// Contract the cash and PnL figures are accounted in
+    #[serde(default)]
+    pub contract: ContractSpec,
+    /// Funding settlements, if funding is enabled
+    #[serde(default)]
+    pub funding: Option<FundingStats>,
+    /// Agent order latency and its cost, if latency accounting is enabled
+    #[serde(default)]
+    pub latency: Option<LatencyCost>,
+}
+
+impl RunReport {
+    /// Capture a report from the simulator's current state
+    pub fn from_simulator<E: CommandEngine>(simulator: &Simulator<E>) -> Self {
+        Self {
+            generated_at: now_ns(),
+            mode: simulator.mode(),
+            metrics: simulator.get_metrics().clone(),
+            fills: *simulator.fill_accounting(),
+            hedging: simulator.hedger().map(|hedger| hedger.stats().clone()),
+            data_source: simulator.data_source_metadata(),
+            performance: None,
+            spreads: simulator.spread_history.overview(),
+            metrics_history: simulator.metrics_history(),
+            volume_profile: simulator.volume_profiles().run().summary(None),
+            session_volume_profiles: simulator.volume_profiles().session_summaries(),
+            executions: simulator.recent_executions(),
+            warmup: simulator.warmup_boundary(),
+            contract: *simulator.contract(),
+            funding: simulator.funding().map(|funding| *funding.stats()),
+            latency: simulator.latency_cost(),
+        }
+    }
+
+    /// Include engine performance figures
+    pub fn with_performance(mut self, performance: PerformanceSnapshot) -> Self {
+        self.performance = Some(performance);
+        self
+    }
+
+    /// Render the report in the given format
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Html => self.render_html(),
+            ReportFormat::Markdown => self.render_markdown(),
+        }
+    }
+
+    /// Render the report and write it to a file
+    pub fn write_to(&self, path: &Path, format: ReportFormat) -> io::Result<()> {
+        fs::write(path, self.render(format))
+    }
+
+    /// Format a cash or PnL amount in the contract's settlement currency
+    fn format_settlement(&self, amount: i64) -> String {
+        match self.contract.payoff {
+            Payoff::Linear => format_ticks(amount),
+            Payoff::Inverse => format!("{:.8} base", amount as f64 / ContractSpec::INVERSE_UNITS as f64),
+        }
+    }
+
+    /// Label/value rows shared by both formats
+    fn summary_rows(&self) -> Vec<(&'static str, String)> {
+        let mut rows = vec![
+            ("Mode", format!("{:?}", self.mode)),
+            ("Inventory", self.metrics.inventory.to_string()),
+            ("Cash", self.format_settlement(self.metrics.cash)),
+            ("PnL", self.format_settlement(self.metrics.pnl)),
+        ];
+        if let Some(warmup) = &self.warmup {
+            let outcome = if warmup.converged { "steady" } else { "step budget exhausted" };
+            rows.push(("Warm-up", format!("{} steps ({}), measured from {} ns", warmup.steps, outcome, warmup.ts)));
+        }
+        if let Some(data_source) = &self.data_source {
+            rows.push(("Data source", format!("{} ({})", data_source.name, data_source.source_type)));
+            if let Some(event_count) = data_source.event_count {
+                rows.push(("Data events", event_count.to_string()));
+            }
+        }
+        if let Some(hedging) = &self.hedging {
+            rows.push(("Hedges", hedging.hedge_count.to_string()));
+            rows.push(("Hedged quantity", hedging.total_hedged_qty.to_string()));
+            rows.push(("Hedging cost", self.format_settlement(hedging.total_cost)));
+        }
+        if let Some(funding) = &self.funding {
+            rows.push(("Funding settlements", funding.settlements.to_string()));
+            rows.push(("Cumulative funding rate", format!("{:.4}%", funding.cumulative_rate * 100.0)));
+            rows.push(("Funding received", self.format_settlement(self.metrics.funding)));
+        }
+        if let Some(latency) = &self.latency {
+            rows.push(("Agent actions delayed", latency.actions.to_string()));
+            rows.push(("Average strategy latency", format!("{:.0} ns", latency.avg_latency_ns())));
+            rows.push(("Latency price cost", format!("{:.1} ticks", latency.price_cost_ticks)));
+            rows.push(("Fills missed to latency", latency.missed_qty().to_string()));
+        }
+        if let Some(performance) = &self.performance {
+            rows.push(("Orders processed", performance.orders_processed.to_string()));
+            rows.push(("Order success rate", format!("{:.2}%", performance.success_rate())));
+            rows.push(("Placement latency", format!("{} ns", performance.order_placement_latency_ns)));
+            rows.push(("Uptime", format!("{} s", performance.uptime_seconds)));
+        }
+        rows
+    }
+
+    fn fill_rows(&self) -> [(&'static str, u64, u64); 4] {
+        let fills = &self.fills;
+        [
+            ("Synthetic / synthetic", fills.synthetic.trades, fills.synthetic.qty),
+            ("Synthetic taker / historical maker", fills.synthetic_vs_historical.trades, fills.synthetic_vs_historical.qty),
+            ("Historical taker / synthetic maker", fills.historical_vs_synthetic.trades, fills.historical_vs_synthetic.qty),
+            ("Historical / historical", fills.historical.trades, fills.historical.qty),
+        ]
+    }
+
+    /// Cells for the most recent executions, newest first
+    fn execution_rows(&self) -> Vec<[String; 6]> {
+        self.executions.iter().rev().take(MARKDOWN_SERIES_ROWS)
+            .map(|execution| [
+                execution.taker_id.to_string(),
+                format!("{:?}", execution.aggressor),
+                execution.qty.to_string(),
+                format!("${}", PricePrecision::default().format_tick_fraction(execution.avg_price).unwrap_or_default()),
+                execution.levels_swept.to_string(),
+                execution.fills.len().to_string(),
+            ])
+            .collect()
+    }
+
+    fn render_markdown(&self) -> String {
+        let mut out = String::new();
+        out.push_str("# Simulation Run Report\n\n");
+        out.push_str(&format!("Generated at {} ns\n\n", self.generated_at));
+
+        out.push_str("## Summary\n\n| Metric | Value |\n|---|---|\n");
+        for (label, value) in self.summary_rows() {
+            out.push_str(&format!("| {} | {} |\n", label, value.replace('|', "\\|")));
+        }
+
+        out.push_str("\n## Fills\n\n| Taker / maker | Trades | Quantity |\n|---|---|---|\n");
+        for (label, trades, qty) in self.fill_rows() {
+            out.push_str(&format!("| {} | {} | {} |\n", label, trades, qty));
+        }
+
+        out.push_str("\n## Executions\n\n");
+        if self.executions.is_empty() {
+            out.push_str("No executions recorded.\n");
+        } else {
+            out.push_str("| Taker | Side | Quantity | Avg price | Levels swept | Fills |\n|---|---|---|---|---|---|\n");
+            for row in self.execution_rows() {
+                out.push_str(&format!("| {} |\n", row.join(" | ")));
+            }
+        }
+
+        out.push_str("\n## Spread\n\n");
+        if self.spreads.is_empty() {
+            out.push_str("No spread history recorded.\n");
+        } else {
+            out.push_str("| Timestamp (ns) | Spread (ticks) |\n|---|---|\n");
+            for (ts, spread) in sample(&self.spreads, MARKDOWN_SERIES_ROWS) {
+                out.push_str(&format!("| {} | {} |\n", ts, spread));
+            }
+        }
+
+        out.push_str("\n## Inventory and PnL\n\n");
+        if self.metrics_history.is_empty() {
+            out.push_str("No inventory history recorded.\n");
+        } else {
+            out.push_str("| Timestamp (ns) | Inventory | PnL |\n|---|---|---|\n");
+            for point in sample(&self.metrics_history, MARKDOWN_SERIES_ROWS) {
+                out.push_str(&format!("| {} | {} | {} |\n", point.ts, point.inventory, self.format_settlement(point.pnl)));
+            }
+        }
+
+        out.push_str("\n## Volume Profile\n\n");
+        if self.volume_profile.buckets.is_empty() {
+            out.push_str("No trades recorded.\n");
+        } else {
+            out.push_str(&format!(
+                "Bucket width {}, point of control {}, sessions recorded {}\n\n",
+                format_ticks(self.volume_profile.bucket_ticks as i64),
+                self.volume_profile.point_of_control.map_or("-".to_string(), |price| format_ticks(price as i64)),
+                self.session_volume_profiles.len(),
+            ));
+            out.push_str("| Price | Quantity | Trades |\n|---|---|---|\n");
+            for bucket in self.volume_profile.buckets.iter().rev() {
+                out.push_str(&format!("| {} | {} | {} |\n", format_ticks(bucket.price as i64), bucket.qty, bucket.trades));
+            }
+        }
+        out
+    }
+
+    fn render_html(&self) -> String {
+        let mut out = String::new();
+        out.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+        out.push_str("<title>Simulation Run Report</title>\n<style>\n");
+        out.push_str("body { font-family: sans-serif; margin: 2em; color: #222; }\n");
+        out.push_str("table { border-collapse: collapse; margin-bottom: 1.5em; }\n");
+        out.push_str("th, td { border: 1px solid #ccc; padding: 4px 10px; text-align: left; }\n");
+        out.push_str("svg { border: 1px solid #ddd; background: #fafafa; margin-bottom: 1.5em; }\n");
+        out.push_str("</style>\n</head>\n<body>\n<h1>Simulation Run Report</h1>\n");
+        out.push_str(&format!("<p>Generated at {} ns</p>\n", self.generated_at));
+
+        out.push_str("<h2>Summary</h2>\n<table>\n");
+        for (label, value) in self.summary_rows() {
+            out.push_str(&format!("<tr><th>{}</th><td>{}</td></tr>\n", label, escape_html(&value)));
+        }
+        out.push_str("</table>\n");
+
+        out.push_str("<h2>Fills</h2>\n<table>\n<tr><th>Taker / maker</th><th>Trades</th><th>Quantity</th></tr>\n");
+        for (label, trades, qty) in self.fill_rows() {
+            out.push_str(&format!("<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n", label, trades, qty));
+        }
+        out.push_str("</table>\n");
+
+        out.push_str("<h2>Executions</h2>\n");
+        if self.executions.is_empty() {
+            out.push_str("<p>No executions recorded.</p>\n");
+        } else {
+            out.push_str("<table>\n<tr><th>Taker</th><th>Side</th><th>Quantity</th><th>Avg price</th><th>Levels swept</th><th>Fills</th></tr>\n");
+            for row in self.execution_rows() {
+                out.push_str(&format!("<tr><td>{}</td></tr>\n", row.join("</td><td>")));
+            }
+            out.push_str("</table>\n");
+        }
+
+        let spreads: Vec<(f64, f64)> = self.spreads
+            .iter()
+            .map(|&(ts, spread)| (ts as f64, spread as f64))
+            .collect();
+        let inventory: Vec<(f64, f64)> = self.metrics_history
+            .iter()
+            .map(|point| (point.ts as f64, point.inventory as f64))
+            .collect();
+        let (pnl_unit, to_units): (&str, fn(i64) -> f64) = match self.contract.payoff {
+            Payoff::Linear => ("$", ticks_to_dollars),
+            Payoff::Inverse => ("base", |amount| amount as f64 / ContractSpec::INVERSE_UNITS as f64),
+        };
+        let pnl: Vec<(f64, f64)> = self.metrics_history
+            .iter()
+            .map(|point| (point.ts as f64, to_units(point.pnl)))
+            .collect();
+        out.push_str("<h2>Spread (ticks)</h2>\n");
+        out.push_str(&svg_line_chart(&spreads, "#1f77b4"));
+        out.push_str("<h2>Inventory</h2>\n");
+        out.push_str(&svg_line_chart(&inventory, "#ff7f0e"));
+        out.push_str(&format!("<h2>PnL ({})</h2>\n", pnl_unit));
+        out.push_str(&svg_line_chart(&pnl, "#2ca02c"));
+        out.push_str("<h2>Volume Profile</h2>\n");
+        if let Some(price) = self.volume_profile.point_of_control {
+            out.push_str(&format!("<p>Point of control {} ({} sessions recorded)</p>\n",
+                                  format_ticks(price as i64), self.session_volume_profiles.len()));
+        }
+        out.push_str(&svg_volume_profile(&self.volume_profile));
+
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+}
+
+fn ticks_to_dollars(ticks: i64) -> f64 {
+    price_utils::to_units(ticks)
+}
+
+/// Format a tick amount as dollars to the cent, rounding half to even
+fn format_ticks(ticks: i64) -> String {
+    let amount = PricePrecision::default().with_decimals(2).format_ticks(ticks);
+    match amount.strip_prefix('-') {
+        Some(amount) => format!("-${}", amount),
+        None => format!("${}", amount),
+    }
+}
+
+/// Pick at most `max` evenly spaced items, always keeping the last
+fn sample<T: Copy>(items: &[T], max: usize) -> Vec<T> {
+    if items.len() <= max {
+        return items.to_vec();
+    }
+    let step = items.len().div_ceil(max);
+    let mut picked: Vec<T> = items.iter().step_by(step).copied().collect();
+    if !(items.len() - 1).is_multiple_of(step) {
+        picked.push(items[items.len() - 1]);
+    }
+    picked
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Render a series as an inline SVG line chart with its value range labelled
+fn svg_line_chart(points: &[(f64, f64)], color: &str) -> String {
+    const WIDTH: f64 = 720.0;
+    const HEIGHT: f64 = 200.0;
+    const PAD: f64 = 30.0;
+
+    if points.is_empty() {
+        return "<p>No data recorded.</p>\n".to_string();
+    }
+
+    let (min_x, max_x) = bounds(points.iter().map(|p| p.0));
+    let (min_y, max_y) = bounds(points.iter().map(|p| p.1));
+    let scale = |value: f64, min: f64, max: f64, span: f64| {
+        if max > min { (value - min) / (max - min) * span } else { span / 2.0 }
+    };
+
+    let coords: Vec<String> = points
+        .iter()
+        .map(|&(x, y)| {
+            let px = PAD + scale(x, min_x, max_x, WIDTH - 2.0 * PAD);
+            let py = HEIGHT - PAD - scale(y, min_y, max_y, HEIGHT - 2.0 * PAD);
+            format!("{:.1},{:.1}", px, py)
+        })
+        .collect();
+
+    format!(
+        "<svg width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" xmlns=\"http://www.w3.org/2000/svg\">\n\
+         <polyline fill=\"none\" stroke=\"{color}\" stroke-width=\"1.5\" points=\"{points}\"/>\n\
+         <text x=\"4\" y=\"{top}\" font-size=\"11\">{max_y}</text>\n\
+         <text x=\"4\" y=\"{bottom}\" font-size=\"11\">{min_y}</text>\n\
+         </svg>\n",
+        w = WIDTH,
+        h = HEIGHT,
+        color = color,
+        points = coords.join(" "),
+        top = PAD - 8.0,
+        bottom = HEIGHT - PAD + 16.0,
+        max_y = format_axis(max_y),
+        min_y = format_axis(min_y),
+    )
+}
+
+/// Render a volume profile as horizontal bars, highest price at the top
+fn svg_volume_profile(profile: &VolumeProfileSummary) -> String {
+    const WIDTH: f64 = 720.0;
+    const LABEL: f64 = 90.0;
+    const ROW: f64 = 14.0;
+
+    let Some(max_qty) = profile.buckets.iter().map(|bucket| bucket.qty).max().filter(|&qty| qty > 0) else {
+        return "<p>No data recorded.</p>\n".to_string();
+    };
+
+    let height = ROW * profile.buckets.len() as f64;
+    let mut out = format!(
+        "<svg width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" xmlns=\"http://www.w3.org/2000/svg\">\n",
+        w = WIDTH,
+        h = height,
+    );
+    for (row, bucket) in profile.buckets.iter().rev().enumerate() {
+        let y = row as f64 * ROW;
+        let bar = (WIDTH - LABEL - 60.0) * bucket.qty as f64 / max_qty as f64;
+        let color = if Some(bucket.price) == profile.point_of_control { "#d62728" } else { "#9467bd" };
+        out.push_str(&format!(
+            "<text x=\"4\" y=\"{ty:.1}\" font-size=\"10\">{label}</text>\
+             <rect x=\"{LABEL}\" y=\"{y:.1}\" width=\"{bar:.1}\" height=\"{bh:.1}\" fill=\"{color}\"/>\
+             <text x=\"{qx:.1}\" y=\"{ty:.1}\" font-size=\"10\">{qty}</text>\n",
+            ty = y + ROW - 3.0,
+            label = format_ticks(bucket.price as i64),
+            bh = ROW - 2.0,
+            qx = LABEL + bar + 4.0,
+            qty = bucket.qty,
+        ));
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+fn bounds(values: impl Iterator<Item = f64>) -> (f64, f64) {
+    values.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| (min.min(value), max.max(value)))
+}
+
+fn format_axis(value: f64) -> String {
+    if value.fract() == 0.0 { format!("{}", value) } else { format!("{:.2}", value) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::OrderBook;
+    use crate::queue_fifo::FifoLevel;
+
+    fn report() -> RunReport {
+        let mut simulator = Simulator::with_seed(OrderBook::<FifoLevel>::new(), 42);
+        simulator.run_steps(50).unwrap();
+        RunReport::from_simulator(&simulator)
+    }
+
+    #[test]
+    fn test_format_parsing() {
+        assert_eq!("md".parse::<ReportFormat>().unwrap(), ReportFormat::Markdown);
+        assert_eq!("HTML".parse::<ReportFormat>().unwrap(), ReportFormat::Html);
+        assert!("pdf".parse::<ReportFormat>().is_err());
+        assert_eq!(ReportFormat::from_path(Path::new("run.md")), Some(ReportFormat::Markdown));
+        assert_eq!(ReportFormat::from_path(Path::new("run.htm")), Some(ReportFormat::Html));
+        assert_eq!(ReportFormat::from_path(Path::new("run.txt")), None);
+    }
+
+    #[test]
+    fn test_render_html_is_self_contained() {
+        let html = report().render(ReportFormat::Html);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<svg"));
+        assert!(html.contains("Spread (ticks)"));
+        assert!(html.contains("Volume Profile"));
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("src=\""));
+    }
+
+    #[test]
+    fn test_render_markdown_and_write() {
+        let report = report();
+        let markdown = report.render(ReportFormat::Markdown);
+        assert!(markdown.starts_with("# Simulation Run Report"));
+        assert!(markdown.contains("| Inventory |"));
+        assert!(markdown.contains("## Fills"));
+        assert!(markdown.contains("## Executions"));
+        assert!(markdown.contains("## Volume Profile"));
+        assert!(!markdown.contains("| Warm-up |"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("report.md");
+        report.write_to(&path, ReportFormat::Markdown).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), markdown);
+    }
+
+    #[test]
+    fn test_report_records_warmup_boundary() {
+        use crate::warmup::WarmupConfig;
+
+        let mut simulator = Simulator::with_seed(OrderBook::<FifoLevel>::new(), 42);
+        let config = WarmupConfig { enabled: true, min_levels_per_side: 1, max_spread_variance: None, window_steps: 5, max_steps: 500 };
+        let boundary = simulator.warm_up(config).unwrap();
+        assert!(boundary.converged);
+
+        // Nothing from the warm-up shows up in the measured stats
+        assert!(simulator.recent_executions().is_empty());
+        assert_eq!(simulator.get_metrics().inventory, 0);
+
+        simulator.run_steps(20).unwrap();
+        let report = RunReport::from_simulator(&simulator);
+        assert_eq!(report.warmup, Some(boundary));
+        assert!(report.render(ReportFormat::Markdown).contains("| Warm-up |"));
+    }
+
+    #[test]
+    fn test_sample_keeps_last_point() {
+        let items: Vec<u32> = (0..45).collect();
+        let picked = sample(&items, 20);
+        assert!(picked.len() <= 21);
+        assert_eq!(picked[0], 0);
+        assert_eq!(*picked.last().unwrap(), 44);
+        assert_eq!(sample(&items[..5], 20).len(), 5);
+    }
+}
@@ -0,0 +1,218 @@
+//! Checking saved book restores against a continuous run
+//!
+//! A long simulation that is stopped and restored from a [`BookImage`]
+//! should carry on exactly as if it had never stopped. [`verify_restores`]
+//! checks that on a journal of engine commands. It replays the whole journal
+//! once without stopping. At each checkpoint it also rebuilds the book two
+//! ways:
+//!
+//! - cold: replaying the journal from the start up to the checkpoint
+//! - warm: saving that book, writing and reading the image as JSON, and
+//!   loading it into a fresh simulator
+//!
+//! Then it replays the rest of the journal on the restored book, comparing
+//! [`OrderBook::state_hash`] after every command with the continuous run.
+//! The report gives the time each start took, the image's size, and the
+//! first command after which a restored book parts from the continuous one.
+//!
+//! [`OrderBook::state_hash`]: crate::engine::OrderBook::state_hash
+
+use std::time::Instant;
+use serde::Serialize;
+use crate::debugger::{EngineDebugger, EngineSettings};
+use crate::engine::EngineCommand;
+use crate::error::{EngineError, EngineResult};
+use crate::sim::{BookImage, HybridInteraction, SimulationMode, Simulator};
+
+/// One checkpoint's restore, checked against the continuous run
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RestoreCheck {
+    /// Commands applied before the book was saved
+    pub checkpoint: usize,
+    /// Resting orders in the saved image
+    pub image_orders: usize,
+    /// Size of the image as JSON
+    pub image_bytes: usize,
+    /// Time to rebuild the book by replaying the journal up to the checkpoint
+    pub cold_start_ns: u64,
+    /// Time to read the image and load it into a fresh simulator
+    pub warm_start_ns: u64,
+    /// Whether the restored book matched the continuous run's at the checkpoint
+    pub restored_match: bool,
+    /// First command after which the restored book differed from the
+    /// continuous run's
+    pub divergence: Option<usize>,
+    /// Whether the two books matched at the end of the journal
+    pub final_match: bool,
+}
+
+impl RestoreCheck {
+    pub fn passed(&self) -> bool {
+        self.restored_match && self.divergence.is_none() && self.final_match
+    }
+}
+
+/// Restores checked over a journal
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RestoreReport {
+    /// Commands in the journal
+    pub commands: usize,
+    /// Time to replay the whole journal without stopping, hashing the book
+    /// after every command
+    pub continuous_ns: u64,
+    pub checks: Vec<RestoreCheck>,
+}
+
+impl RestoreReport {
+    /// Whether every restore carried on exactly as the continuous run did
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(RestoreCheck::passed)
+    }
+}
+
+/// `count` checkpoints spread evenly through a journal of `commands`,
+/// excluding its ends
+pub fn even_checkpoints(commands: usize, count: usize) -> Vec<usize> {
+    let mut checkpoints: Vec<usize> = (1..=count).map(|k| k * commands / (count + 1)).collect();
+    checkpoints.dedup();
+    checkpoints
+}
+
+/// Save and restore the book at each checkpoint of `journal`, replayed
+/// under `settings`, and compare each restore with a continuous run
+pub fn verify_restores(journal: &[EngineCommand], settings: EngineSettings, checkpoints: &[usize]) -> EngineResult<RestoreReport> {
+    // Book state after each command of an uninterrupted run, from the start
+    let start = Instant::now();
+    let mut continuous = EngineDebugger::new(journal.to_vec(), settings);
+    let mut hashes = vec![continuous.state_hash()];
+    while let Some(step) = continuous.step() {
+        hashes.push(step.state_hash);
+    }
+    let continuous_ns = start.elapsed().as_nanos() as u64;
+
+    let mut checks = Vec::with_capacity(checkpoints.len());
+    for &checkpoint in checkpoints {
+        let checkpoint = checkpoint.min(journal.len());
+
+        let start = Instant::now();
+        let cold = EngineDebugger::at(journal.to_vec(), settings, checkpoint);
+        let cold_start_ns = start.elapsed().as_nanos() as u64;
+
+        // The image holds the match filter as the hybrid policy it implements
+        let mut saver = Simulator::new(cold.into_book());
+        let filter = journal[..checkpoint].iter().rev().find_map(|command| match command {
+            EngineCommand::SetMatchFilter(filter) => Some(*filter),
+            _ => None,
+        });
+        if let Some(interaction) = filter.flatten().and_then(|filter| HybridInteraction::from_match_filter(Some(filter))) {
+            saver.set_mode(SimulationMode::Hybrid);
+            saver.set_hybrid_interaction(interaction);
+        }
+        let image = saver.save_book();
+        let bytes = serde_json::to_vec(&image).map_err(|e| EngineError::serialization(e.to_string()))?;
+
+        let start = Instant::now();
+        let image: BookImage = serde_json::from_slice(&bytes).map_err(|e| EngineError::serialization(e.to_string()))?;
+        let mut restored = Simulator::new(settings.book());
+        restored.load_book(&image)?;
+        let warm_start_ns = start.elapsed().as_nanos() as u64;
+
+        let book = restored.engine;
+        let restored_match = book.state_hash() == hashes[checkpoint];
+        let mut resumed = EngineDebugger::resume(journal.to_vec(), settings, book, checkpoint);
+        let mut divergence = None;
+        while let Some(step) = resumed.step() {
+            if divergence.is_none() && step.state_hash != hashes[step.index + 1] {
+                divergence = Some(step.index);
+            }
+        }
+
+        checks.push(RestoreCheck {
+            checkpoint,
+            image_orders: image.orders.len(),
+            image_bytes: bytes.len(),
+            cold_start_ns,
+            warm_start_ns,
+            restored_match,
+            divergence,
+            final_match: resumed.state_hash() == hashes[journal.len()],
+        });
+    }
+
+    Ok(RestoreReport { commands: journal.len(), continuous_ns, checks })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::DepthLimit;
+    use crate::time::now_ns;
+    use crate::types::{order_ids, Order, Side};
+
+    fn journal() -> Vec<EngineCommand> {
+        let ts = now_ns();
+        let mut journal = Vec::new();
+        for id in 0..40u64 {
+            let side = if id % 2 == 0 { Side::Buy } else { Side::Sell };
+            let price = match side {
+                Side::Buy => 99_0000 - (id % 5) * 100,
+                Side::Sell => 101_0000 + (id % 5) * 100,
+            };
+            journal.push(EngineCommand::Place(Order::new_limit(id + 1, side, 10 + id, price, ts)));
+            if id % 7 == 6 {
+                journal.push(EngineCommand::Cancel(id - 2));
+            }
+            if id % 9 == 8 {
+                journal.push(EngineCommand::Place(Order::new_market(1_000 + id, side, 25, ts)));
+            }
+        }
+        journal
+    }
+
+    #[test]
+    fn test_restored_books_follow_the_continuous_run() {
+        let journal = journal();
+        let checkpoints = even_checkpoints(journal.len(), 3);
+        assert_eq!(checkpoints.len(), 3);
+
+        let report = verify_restores(&journal, EngineSettings::default(), &checkpoints).unwrap();
+        assert_eq!(report.commands, journal.len());
+        for check in &report.checks {
+            assert!(check.passed(), "restore at {} diverged at {:?}", check.checkpoint, check.divergence);
+            assert!(check.image_orders > 0 && check.image_bytes > 0);
+        }
+        assert!(report.passed());
+    }
+
+    #[test]
+    fn test_the_match_filter_survives_a_restore() {
+        // Under the shadow match filter a historical ask rests crossing a
+        // synthetic bid, and keeps doing so in the restored book
+        let ts = now_ns();
+        let journal = vec![
+            EngineCommand::SetMatchFilter(HybridInteraction::Shadow.match_filter()),
+            EngineCommand::Place(Order::new_limit(1, Side::Buy, 10, 101_0000, ts)),
+            EngineCommand::Place(Order::new_limit(order_ids::historical(2).unwrap(), Side::Sell, 10, 100_0000, ts)),
+            EngineCommand::Place(Order::new_limit(3, Side::Buy, 5, 99_0000, ts)),
+        ];
+        let report = verify_restores(&journal, EngineSettings::default(), &[2, 3]).unwrap();
+        assert!(report.passed(), "{:?}", report.checks);
+    }
+
+    #[test]
+    fn test_a_lossy_restore_is_caught() {
+        // An image holds no depth limit, so the restored book keeps levels
+        // the continuous one prunes
+        let ts = now_ns();
+        let journal = vec![
+            EngineCommand::SetDepthLimit(DepthLimit::levels(1)),
+            EngineCommand::Place(Order::new_limit(1, Side::Buy, 10, 99_0000, ts)),
+            EngineCommand::Place(Order::new_limit(2, Side::Buy, 10, 98_0000, ts)),
+        ];
+        let report = verify_restores(&journal, EngineSettings::default(), &[2]).unwrap();
+        let check = &report.checks[0];
+        assert!(check.restored_match && !check.final_match);
+        assert_eq!(check.divergence, Some(2));
+        assert!(!report.passed());
+    }
+}
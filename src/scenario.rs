@@ -0,0 +1,218 @@
+//! Canned end-to-end scenarios
+//!
+//! A [`Scenario`] ships a configuration and a data file with the crate and
+//! runs them in hybrid mode: the data file drives the outside market while
+//! the configured agents trade in it. Each scenario exercises several
+//! features at once, and its [`ScenarioReport`] checks that each one did
+//! what it should, so a scenario doubles as a demo and as an integration
+//! test across features.
+//!
+//! `latency-mm` pegs a ladder of outside liquidity to a replayed quote
+//! stream, runs a market maker that pegs its two-sided quote to the mid it
+//! sees, and has two takers race it while seeing the market late. The data
+//! places and pulls resting orders and halts trading into a reopening
+//! auction.
+
+use std::fs;
+use std::path::PathBuf;
+use serde::Serialize;
+use crate::config::{Config, ConfigError};
+use crate::data::{CsvDataSource, DataResult, DataSource, MarketStatusType};
+use crate::engine::OrderBook;
+use crate::error::{EngineError, EngineResult};
+use crate::latency::LatencyCost;
+use crate::queue::QueueDiscipline;
+use crate::sim::{SimulationMode, Simulator};
+
+/// A configuration and data file run together
+#[derive(Debug, Clone, Copy)]
+pub struct Scenario {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Configuration file contents (TOML)
+    pub config: &'static str,
+    /// Data file contents (CSV)
+    pub data: &'static str,
+    /// Steps to run: one per data event, and a few for orders still in flight
+    pub steps: usize,
+}
+
+/// Every scenario shipped with the crate
+pub const SCENARIOS: [Scenario; 1] = [
+    Scenario {
+        name: "latency-mm",
+        description: "Pegged market maker and slow takers racing a replayed quote stream through cancels and a halt",
+        config: include_str!("../scenarios/latency-mm/config.toml"),
+        data: include_str!("../scenarios/latency-mm/data.csv"),
+        steps: 320,
+    },
+];
+
+impl Scenario {
+    /// Scenario shipped under this name
+    pub fn find(name: &str) -> Option<&'static Scenario> {
+        SCENARIOS.iter().find(|scenario| scenario.name == name)
+    }
+
+    /// Parse and check the scenario's configuration, which only names what
+    /// it changes from the defaults
+    pub fn config(&self) -> Result<Config, ConfigError> {
+        let config = Config::from_toml_over_defaults(self.config)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Open the scenario's data, written out to a temporary file first
+    pub fn data_source(&self) -> DataResult<Box<dyn DataSource>> {
+        let path = self.data_path();
+        fs::write(&path, self.data)?;
+        let source = CsvDataSource::new(&path);
+        // The source holds the file open; the name is no longer needed
+        let _ = fs::remove_file(&path);
+        let mut source = source?;
+        // Events a step apart, however far apart their timestamps
+        source.set_playback_speed(f64::MAX)?;
+        Ok(Box::new(source))
+    }
+
+    fn data_path(&self) -> PathBuf {
+        std::env::temp_dir().join(format!("scenario-{}.{}.csv", self.name, std::process::id()))
+    }
+
+    /// Run a simulator built from the scenario's configuration over its data
+    pub fn run<D: QueueDiscipline>(&self, simulator: Simulator<OrderBook<D>>) -> EngineResult<ScenarioReport> {
+        let data_source = self.data_source().map_err(|e| EngineError::data(format!("Scenario data: {}", e)))?;
+        let mut simulator = simulator.with_data_source(data_source);
+        simulator.set_mode(SimulationMode::Hybrid);
+        simulator.record_activity(true);
+
+        let mut report = ScenarioReport { scenario: self.name.to_string(), ..ScenarioReport::default() };
+        let cancels_before = simulator.engine_stats()?.cancels;
+        for _ in 0..self.steps {
+            let trades = simulator.step()?;
+            report.steps += 1;
+            report.trades += trades.len() as u64;
+            report.volume += trades.iter().map(|trade| trade.qty).sum::<u64>();
+            report.max_queued = report.max_queued.max(simulator.pre_open_orders());
+            for activity in simulator.take_activity() {
+                match activity.status {
+                    Some(MarketStatusType::Halted) => report.halts += 1,
+                    Some(MarketStatusType::Open) if report.halts > 0 => report.reopened = true,
+                    _ => {}
+                }
+            }
+        }
+        report.cancels = simulator.engine_stats()?.cancels - cancels_before;
+        report.queued_at_end = simulator.pre_open_orders();
+        report.reanchors = simulator.shadow_book().map_or(0, |shadow| shadow.reanchors());
+        report.quotes = simulator.agent_stats().iter().filter(|agent| agent.kind == "market_maker").map(|agent| agent.orders).sum();
+        report.expired = simulator.expired_orders();
+        report.latency = simulator.latency_cost().unwrap_or_default();
+        report.checks = report.evaluate();
+        Ok(report)
+    }
+}
+
+/// What a scenario run did, and whether each feature it exercises held up
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ScenarioReport {
+    pub scenario: String,
+    pub steps: usize,
+    pub trades: u64,
+    pub volume: u64,
+    /// Times the outside liquidity was re-pegged to a new quote
+    pub reanchors: u64,
+    /// Orders and quotes the market maker sent
+    pub quotes: u64,
+    /// Resting orders cancelled: pulled by the data, replaced by a re-peg or
+    /// a new quote, or expired
+    pub cancels: u64,
+    /// Synthetic orders cancelled for outliving their TTL
+    pub expired: u64,
+    /// What latency cost the agents, over all their orders
+    pub latency: LatencyCost,
+    /// Halts in the data
+    pub halts: u64,
+    /// Whether trading reopened after a halt
+    pub reopened: bool,
+    /// Most orders waiting for the open at once
+    pub max_queued: usize,
+    /// Orders still waiting for the open when the run ended
+    pub queued_at_end: usize,
+    pub checks: Vec<ScenarioCheck>,
+}
+
+/// One feature a scenario exercises and whether it held up
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScenarioCheck {
+    pub name: &'static str,
+    pub passed: bool,
+    pub detail: String,
+}
+
+impl ScenarioReport {
+    /// Whether every check passed
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.passed)
+    }
+
+    fn evaluate(&self) -> Vec<ScenarioCheck> {
+        let latency = &self.latency;
+        // A race is lost when the book moved between decision and arrival
+        let raced = latency.filled_qty != latency.zero_latency_qty || latency.price_cost_ticks != 0.0;
+        vec![
+            ScenarioCheck {
+                name: "pegs",
+                passed: self.reanchors > 0 && self.quotes > 0,
+                detail: format!("{} re-pegs of outside liquidity, {} market maker quotes", self.reanchors, self.quotes),
+            },
+            ScenarioCheck {
+                name: "cancels",
+                passed: self.cancels > 0,
+                detail: format!("{} resting orders cancelled ({} expired)", self.cancels, self.expired),
+            },
+            ScenarioCheck {
+                name: "latency races",
+                passed: latency.actions > 0 && raced,
+                detail: format!(
+                    "{} delayed orders filled {} where they would have filled {}, at {:.0} ticks of price cost",
+                    latency.actions, latency.filled_qty, latency.zero_latency_qty, latency.price_cost_ticks
+                ),
+            },
+            ScenarioCheck {
+                name: "halts",
+                passed: self.halts > 0 && self.reopened && self.max_queued > 0 && self.queued_at_end == 0,
+                detail: format!(
+                    "{} halts, {} orders held for the reopening auction, {} left waiting",
+                    self.halts, self.max_queued, self.queued_at_end
+                ),
+            },
+            ScenarioCheck {
+                name: "trading",
+                passed: self.trades > 0,
+                detail: format!("{} trades of {} in total over {} steps", self.trades, self.volume, self.steps),
+            },
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shipped_scenarios_load() {
+        // Running them takes the CLI's simulator setup; see the `serve` binary's tests
+        for scenario in &SCENARIOS {
+            scenario.config().unwrap();
+            let mut source = scenario.data_source().unwrap();
+            let mut events = 0;
+            while source.next_event().unwrap().is_some() {
+                events += 1;
+            }
+            assert!(events < scenario.steps, "{} has more events than steps", scenario.name);
+        }
+        assert!(Scenario::find("latency-mm").is_some());
+        assert!(Scenario::find("unknown").is_none());
+    }
+}
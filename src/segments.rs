@@ -0,0 +1,294 @@
+//! Segmented record files with integrity checksums
+//!
+//! A [`SegmentWriter`] appends records to numbered segment files in one
+//! directory and starts a new segment every `records_per_segment` records.
+//! Each record is framed by its length and a CRC-32 of its bytes, and
+//! `index.json` lists the segments with their record counts and time spans.
+//! A segment is synced to disk and marked sealed in the index before the next
+//! one opens, so a crash mid-write can only damage the segment being written.
+//!
+//! [`read_segments`] checks every record. A segment ends at its first
+//! truncated record or checksum mismatch: the rest of it is skipped with a
+//! warning and reported as a [`CorruptTail`], and reading carries on with the
+//! next segment instead of failing.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
+use serde::{Deserialize, Serialize};
+
+/// Segment list kept next to the segments
+pub const INDEX_FILE: &str = "index.json";
+
+/// Length and checksum in front of every record
+const RECORD_HEADER_LEN: usize = 8;
+
+/// One segment file of a log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SegmentInfo {
+    /// File name within the log directory
+    pub file: String,
+    /// Records written, final once sealed
+    pub records: u64,
+    pub first_ts: Option<u128>,
+    pub last_ts: Option<u128>,
+    /// Completely written and synced; only the last segment can be open
+    pub sealed: bool,
+}
+
+/// Segments of a log, oldest first
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SegmentIndex {
+    pub segments: Vec<SegmentInfo>,
+}
+
+impl SegmentIndex {
+    /// Read the index of the log in `dir`
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        let json = fs::read(dir.join(INDEX_FILE))?;
+        serde_json::from_slice(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Replace the index of the log in `dir` through a temporary file, so a
+    /// crash leaves either the old index or the new one. The file is synced
+    /// before the rename and the directory after it, so neither the rename
+    /// nor the index it points at can be lost.
+    fn store(&self, dir: &Path) -> io::Result<()> {
+        let temp = dir.join(format!("{}.tmp", INDEX_FILE));
+        let mut file = File::create(&temp)?;
+        file.write_all(&serde_json::to_vec_pretty(self)?)?;
+        file.sync_all()?;
+        fs::rename(temp, dir.join(INDEX_FILE))?;
+        sync_dir(dir)
+    }
+
+    /// Records across every segment
+    pub fn records(&self) -> u64 {
+        self.segments.iter().map(|segment| segment.records).sum()
+    }
+}
+
+/// Sync a directory's entries to disk
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+/// Directories cannot be opened for syncing here; renames are durable once
+/// the file system commits them
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Appends checksummed records to a segmented log
+#[derive(Debug)]
+pub struct SegmentWriter {
+    dir: PathBuf,
+    records_per_segment: u64,
+    index: SegmentIndex,
+    /// The open segment, the last in the index
+    file: Option<BufWriter<File>>,
+}
+
+impl SegmentWriter {
+    /// Start an empty log in `dir`, replacing any log already there
+    pub fn create(dir: impl Into<PathBuf>, records_per_segment: u64) -> io::Result<Self> {
+        let dir = dir.into();
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        fs::create_dir_all(&dir)?;
+        let writer = Self {
+            dir,
+            records_per_segment: records_per_segment.max(1),
+            index: SegmentIndex::default(),
+            file: None,
+        };
+        writer.index.store(&writer.dir)?;
+        Ok(writer)
+    }
+
+    /// Directory the log is written to
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Segments written so far
+    pub fn index(&self) -> &SegmentIndex {
+        &self.index
+    }
+
+    /// Append a record stamped `ts`, opening a new segment when the current
+    /// one is full
+    pub fn append(&mut self, ts: u128, payload: &[u8]) -> io::Result<()> {
+        let full = self.index.segments.last().is_some_and(|segment| segment.records >= self.records_per_segment);
+        if self.file.is_none() || full {
+            self.seal()?;
+            let file = format!("segment-{:06}.seg", self.index.segments.len());
+            self.file = Some(BufWriter::new(File::create(self.dir.join(&file))?));
+            self.index.segments.push(SegmentInfo { file, records: 0, first_ts: None, last_ts: None, sealed: false });
+            self.index.store(&self.dir)?;
+        }
+
+        let len = u32::try_from(payload.len()).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Record too large"))?;
+        let file = self.file.as_mut().expect("segment opened above");
+        file.write_u32::<LittleEndian>(len)?;
+        file.write_u32::<LittleEndian>(crc32fast::hash(payload))?;
+        file.write_all(payload)?;
+        file.flush()?;
+
+        let segment = self.index.segments.last_mut().expect("segment opened above");
+        segment.records += 1;
+        segment.first_ts.get_or_insert(ts);
+        segment.last_ts = Some(ts);
+        Ok(())
+    }
+
+    /// Sync the open segment and mark it sealed in the index
+    fn seal(&mut self) -> io::Result<()> {
+        let Some(file) = self.file.take() else {
+            return Ok(());
+        };
+        file.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        if let Some(segment) = self.index.segments.last_mut() {
+            segment.sealed = true;
+        }
+        self.index.store(&self.dir)
+    }
+
+    /// Seal the last segment, returning the final index
+    pub fn finish(mut self) -> io::Result<SegmentIndex> {
+        self.seal()?;
+        Ok(self.index)
+    }
+}
+
+/// The unreadable end of a segment, skipped when reading
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CorruptTail {
+    pub file: String,
+    /// Byte offset of the first bad record
+    pub offset: u64,
+    /// Records read from the segment before it
+    pub records_read: u64,
+    pub reason: String,
+}
+
+/// Records read back from a segmented log
+#[derive(Debug, Clone, Default)]
+pub struct SegmentRead {
+    /// Payloads of every intact record, in write order
+    pub records: Vec<Vec<u8>>,
+    /// Segment tails skipped because they failed their checks
+    pub corrupt: Vec<CorruptTail>,
+}
+
+/// Read every intact record of the log in `dir`
+///
+/// Only a missing or unreadable index is an error. A segment file that is
+/// missing or ends in a truncated or mismatched record gives up its tail
+/// with a warning.
+pub fn read_segments(dir: &Path) -> io::Result<SegmentRead> {
+    let index = SegmentIndex::load(dir)?;
+    let mut read = SegmentRead::default();
+    for segment in &index.segments {
+        let bytes = match fs::read(dir.join(&segment.file)) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                skip_tail(dir, &mut read, segment, 0, 0, format!("unreadable: {}", e));
+                continue;
+            }
+        };
+
+        let (mut offset, mut records_read) = (0, 0);
+        while offset < bytes.len() {
+            let rest = &bytes[offset..];
+            if rest.len() < RECORD_HEADER_LEN {
+                skip_tail(dir, &mut read, segment, offset, records_read, "truncated record header".to_string());
+                break;
+            }
+            let len = LittleEndian::read_u32(rest) as usize;
+            let crc = LittleEndian::read_u32(&rest[4..]);
+            let Some(payload) = rest.get(RECORD_HEADER_LEN..RECORD_HEADER_LEN + len) else {
+                skip_tail(dir, &mut read, segment, offset, records_read,
+                          format!("truncated record of {} bytes", len));
+                break;
+            };
+            if crc32fast::hash(payload) != crc {
+                skip_tail(dir, &mut read, segment, offset, records_read, "checksum mismatch".to_string());
+                break;
+            }
+            read.records.push(payload.to_vec());
+            offset += RECORD_HEADER_LEN + len;
+            records_read += 1;
+        }
+    }
+    Ok(read)
+}
+
+fn skip_tail(dir: &Path, read: &mut SegmentRead, segment: &SegmentInfo, offset: usize, records_read: u64, reason: String) {
+    tracing::warn!("Skipping corrupt tail of {} at byte {} after {} record(s): {}",
+                   dir.join(&segment.file).display(), offset, records_read, reason);
+    read.corrupt.push(CorruptTail { file: segment.file.clone(), offset: offset as u64, records_read, reason });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_log(dir: &Path, records: u64) -> SegmentWriter {
+        let mut writer = SegmentWriter::create(dir, 3).unwrap();
+        for ts in 0..records {
+            writer.append(ts as u128, format!("record {}", ts).as_bytes()).unwrap();
+        }
+        writer
+    }
+
+    #[test]
+    fn test_records_roll_into_sealed_segments() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log");
+        let writer = write_log(&path, 7);
+
+        // Full segments are sealed before the next opens
+        let index = SegmentIndex::load(&path).unwrap();
+        assert_eq!(index.segments.iter().map(|segment| segment.sealed).collect::<Vec<_>>(), vec![true, true, false]);
+        let index = writer.finish().unwrap();
+        assert_eq!((index.records(), index.segments[2].first_ts, index.segments[2].last_ts), (7, Some(6), Some(6)));
+        assert_eq!(SegmentIndex::load(&path).unwrap(), index);
+
+        let read = read_segments(&path).unwrap();
+        assert_eq!(read.records.len(), 7);
+        assert_eq!(read.records[4], b"record 4");
+        assert!(read.corrupt.is_empty());
+    }
+
+    #[test]
+    fn test_corrupt_tails_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log");
+        let writer = write_log(&path, 8);
+        drop(writer);
+
+        // A crash mid-write leaves half a record at the end of the open segment
+        let tail = path.join("segment-000002.seg");
+        let bytes = fs::read(&tail).unwrap();
+        fs::write(&tail, &bytes[..bytes.len() - 3]).unwrap();
+        // and a flipped bit in a sealed segment fails its checksum
+        let sealed = path.join("segment-000000.seg");
+        let mut bytes = fs::read(&sealed).unwrap();
+        let second = RECORD_HEADER_LEN + "record 0".len();
+        bytes[second + RECORD_HEADER_LEN] ^= 1;
+        fs::write(&sealed, bytes).unwrap();
+
+        let read = read_segments(&path).unwrap();
+        assert_eq!(read.records.len(), 1 + 3 + 1);
+        assert_eq!(read.corrupt, vec![
+            CorruptTail { file: "segment-000000.seg".to_string(), offset: second as u64, records_read: 1, reason: "checksum mismatch".to_string() },
+            CorruptTail { file: "segment-000002.seg".to_string(), offset: second as u64, records_read: 1, reason: "truncated record of 8 bytes".to_string() },
+        ]);
+        assert!(read_segments(&dir.path().join("missing")).is_err());
+    }
+}
@@ -0,0 +1,584 @@
+//! Trade settlement and position reconciliation
+//!
+//! Matching only agrees a trade; the securities and cash change hands on a
+//! later settlement date. A [`SettlementLedger`] books every trade for the
+//! close of the cycle `lag_cycles` after the one it traded in (T+N, with a
+//! cycle standing in for a business day) and settles what is due at each
+//! cycle close. A due trade fails with a configurable probability and rolls
+//! to the next close, so for a while the settled holdings of its buyer and
+//! seller fall short of what they are owed. Each close reconciles the two,
+//! and checks the position booked for each account against the one the
+//! simulator actually holds, reporting every account that differs as a
+//! [`ReconciliationBreak`]. Accounts are the registered agents, plus one per
+//! role for the flow no registered agent owns.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::agents::AgentId;
+use crate::sim::Agent;
+use crate::time::ms_to_ns;
+use crate::types::{ContractSpec, OrderId, Price, Qty, Trade};
+
+/// Settlement cycle and failure parameters
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SettlementConfig {
+    /// Settle trades on a delay and reconcile positions
+    pub enabled: bool,
+    /// Simulation time per settlement cycle (milliseconds)
+    pub cycle_ms: u64,
+    /// Cycles between a trade and its settlement, the N of T+N
+    pub lag_cycles: u32,
+    /// Chance that a due trade fails to settle at a close and rolls to the next
+    pub fail_probability: f64,
+    /// Closed cycles kept
+    pub history: usize,
+}
+
+impl Default for SettlementConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cycle_ms: 1_000,
+            lag_cycles: 2,
+            fail_probability: 0.01,
+            history: 100,
+        }
+    }
+}
+
+impl SettlementConfig {
+    /// Check the cycle length and failure probability
+    pub fn validate(&self) -> Result<(), String> {
+        if self.cycle_ms == 0 {
+            return Err("Settlement cycle must be greater than 0".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.fail_probability) {
+            return Err("Settlement fail probability must be in [0.0, 1.0]".to_string());
+        }
+        if self.history == 0 {
+            return Err("Settlement history must keep at least one cycle".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Party to a trade: a registered agent, or the flow of a role no
+/// registered agent owns
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct Account {
+    pub role: Agent,
+    pub agent: Option<AgentId>,
+}
+
+impl Account {
+    /// The flow of `role` that no registered agent owns
+    pub fn role(role: Agent) -> Self {
+        Self { role, agent: None }
+    }
+
+    /// Account of the order placed under `role`, by `agent` if one owns it
+    pub fn of(role: Agent, agent: Option<AgentId>) -> Self {
+        Self { role, agent }
+    }
+}
+
+impl fmt::Display for Account {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.agent {
+            Some(id) => write!(f, "{:?} agent {}", self.role, id),
+            None => write!(f, "{:?}", self.role),
+        }
+    }
+}
+
+impl From<Account> for String {
+    fn from(account: Account) -> Self {
+        account.to_string()
+    }
+}
+
+impl TryFrom<String> for Account {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let (role, agent) = match value.split_once(" agent ") {
+            Some((role, id)) => (role, Some(id.parse().map_err(|_| format!("Invalid agent id in account {}", value))?)),
+            None => (value.as_str(), None),
+        };
+        let role = match role {
+            "MarketMaker" => Agent::MarketMaker,
+            "Taker" => Agent::Taker,
+            "Historical" => Agent::Historical,
+            "Manual" => Agent::Manual,
+            _ => return Err(format!("Unknown account {}", value)),
+        };
+        Ok(Self { role, agent })
+    }
+}
+
+/// Position and cash of one account
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Holdings {
+    pub position: i64,
+    /// Settlement units received less paid
+    pub cash: i64,
+}
+
+impl Holdings {
+    fn apply(&mut self, qty: i64, cash: i64) {
+        self.position += qty;
+        self.cash += cash;
+    }
+}
+
+/// A trade waiting for its settlement
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingSettlement {
+    pub maker_id: OrderId,
+    pub taker_id: OrderId,
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub price: Price,
+    pub qty: Qty,
+    /// Simulation time of the trade
+    pub ts: u128,
+    pub buyer: Account,
+    pub seller: Account,
+    /// Cash the buyer pays the seller
+    pub notional: i64,
+    /// Cycle it traded in
+    pub trade_cycle: u64,
+    /// Cycle at whose close it is due
+    pub due_cycle: u64,
+    /// Closes it has failed at so far
+    pub failures: u32,
+}
+
+/// An account whose settled holdings differ from its contractual ones, or
+/// whose booked position differs from the one the simulator holds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReconciliationBreak {
+    pub account: Account,
+    /// Cycle whose close found the break
+    pub cycle: u64,
+    /// Holdings from every trade due by this close
+    pub expected: Holdings,
+    /// Holdings from the trades that did settle
+    pub settled: Holdings,
+    /// Failed trades of the account still waiting to settle
+    pub failed_trades: usize,
+    /// Position from every trade booked so far, due or not
+    pub booked: i64,
+    /// Position the simulator holds for the account, if it still tracks it
+    pub actual: Option<i64>,
+}
+
+impl ReconciliationBreak {
+    /// Position the account is short of what it is owed
+    pub fn position_break(&self) -> i64 {
+        self.expected.position - self.settled.position
+    }
+
+    /// Cash the account is short of what it is owed
+    pub fn cash_break(&self) -> i64 {
+        self.expected.cash - self.settled.cash
+    }
+
+    /// Position the simulator holds beyond what the ledger booked
+    pub fn booking_break(&self) -> i64 {
+        self.actual.map_or(0, |actual| actual - self.booked)
+    }
+}
+
+/// What one cycle close settled
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SettlementCycle {
+    pub cycle: u64,
+    /// Simulation time of the close
+    pub ts: u128,
+    /// Trades that settled, including earlier failures
+    pub settled: usize,
+    /// Due trades that failed and rolled to the next close
+    pub failed: usize,
+    pub breaks: Vec<ReconciliationBreak>,
+}
+
+/// Cumulative settlement statistics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SettlementStats {
+    /// Trades booked for settlement
+    pub trades: u64,
+    pub settled: u64,
+    /// Failed settlement attempts; a trade counts once per close it fails at
+    pub failures: u64,
+    pub cycles: u64,
+    /// Cycles passed over without a close after a clock jump
+    pub skipped_cycles: u64,
+    /// Cycle closes that found at least one break
+    pub cycles_with_breaks: u64,
+}
+
+/// Books trades for settlement and reconciles accounts at each cycle close
+#[derive(Debug, Clone)]
+pub struct SettlementLedger {
+    config: SettlementConfig,
+    seed: u64,
+    rng: StdRng,
+    /// Simulation time of the current cycle's close
+    next_close: Option<u128>,
+    cycle: u64,
+    /// In booking order
+    pending: VecDeque<PendingSettlement>,
+    expected: BTreeMap<Account, Holdings>,
+    settled: BTreeMap<Account, Holdings>,
+    /// Position from every booked trade, due or not
+    booked: BTreeMap<Account, i64>,
+    cycles: VecDeque<SettlementCycle>,
+    stats: SettlementStats,
+}
+
+impl SettlementLedger {
+    /// Create a ledger whose failures are drawn from a generator seeded with `seed`
+    pub fn new(config: SettlementConfig, seed: u64) -> Self {
+        Self {
+            config,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            next_close: None,
+            cycle: 0,
+            pending: VecDeque::new(),
+            expected: BTreeMap::new(),
+            settled: BTreeMap::new(),
+            booked: BTreeMap::new(),
+            cycles: VecDeque::new(),
+            stats: SettlementStats::default(),
+        }
+    }
+
+    /// Get the settlement configuration
+    pub fn config(&self) -> &SettlementConfig {
+        &self.config
+    }
+
+    /// Get cumulative settlement statistics
+    pub fn stats(&self) -> &SettlementStats {
+        &self.stats
+    }
+
+    /// Cycle trades are currently booked in
+    pub fn cycle(&self) -> u64 {
+        self.cycle
+    }
+
+    /// Trades not yet settled, oldest first
+    pub fn pending(&self) -> impl Iterator<Item = &PendingSettlement> {
+        self.pending.iter()
+    }
+
+    /// Holdings per account from every trade due by the last close
+    pub fn expected(&self) -> &BTreeMap<Account, Holdings> {
+        &self.expected
+    }
+
+    /// Holdings per account from the trades that settled
+    pub fn settled(&self) -> &BTreeMap<Account, Holdings> {
+        &self.settled
+    }
+
+    /// Position per account from every booked trade, due or not
+    pub fn booked(&self) -> &BTreeMap<Account, i64> {
+        &self.booked
+    }
+
+    /// Breaks found at the last close
+    pub fn breaks(&self) -> &[ReconciliationBreak] {
+        self.cycles.back().map_or(&[], |cycle| &cycle.breaks)
+    }
+
+    /// Closed cycles, oldest first
+    pub fn cycles(&self) -> impl Iterator<Item = &SettlementCycle> {
+        self.cycles.iter()
+    }
+
+    /// Book a trade between `buyer` and `seller` for settlement
+    pub fn record(&mut self, trade: &Trade, buyer: Account, seller: Account, contract: &ContractSpec) {
+        *self.booked.entry(buyer).or_default() += trade.qty as i64;
+        *self.booked.entry(seller).or_default() -= trade.qty as i64;
+        self.pending.push_back(PendingSettlement {
+            maker_id: trade.maker_id,
+            taker_id: trade.taker_id,
+            price: trade.price,
+            qty: trade.qty,
+            ts: trade.ts,
+            buyer,
+            seller,
+            notional: contract.notional(trade.qty, trade.price).unwrap_or(i64::MAX),
+            trade_cycle: self.cycle,
+            due_cycle: self.cycle + self.config.lag_cycles as u64,
+            failures: 0,
+        });
+        self.stats.trades += 1;
+    }
+
+    /// Close the cycles that ended by `ts`, settling each and reconciling
+    /// against `positions`, the simulator's position per account.
+    ///
+    /// A jump over many cycles closes only the `lag_cycles + 1` of them that
+    /// bring every booked trade due and passes over the rest. A clock that
+    /// went back restarts the cycle grid from `ts`.
+    pub fn on_step(&mut self, ts: u128, positions: &BTreeMap<Account, i64>) -> Vec<SettlementCycle> {
+        let cycle_ns = ms_to_ns(self.config.cycle_ms);
+        // A clock before the current cycle's start went back
+        let mut next_close = match self.next_close {
+            Some(next_close) if ts + cycle_ns >= next_close => next_close,
+            _ => ts + cycle_ns,
+        };
+        if ts < next_close {
+            self.next_close = Some(next_close);
+            return Vec::new();
+        }
+
+        let due = ((ts - next_close) / cycle_ns + 1) as u64;
+        let closes = due.min(self.config.lag_cycles as u64 + 1);
+        let mut closed = Vec::with_capacity(closes as usize);
+        for _ in 0..closes {
+            closed.push(self.close(next_close, positions));
+            next_close += cycle_ns;
+        }
+        let skipped = due - closes;
+        self.cycle += skipped;
+        self.stats.skipped_cycles += skipped;
+        self.next_close = Some(next_close + skipped as u128 * cycle_ns);
+        closed
+    }
+
+    /// Settle what is due at the current cycle's close and reconcile
+    fn close(&mut self, ts: u128, positions: &BTreeMap<Account, i64>) -> SettlementCycle {
+        let cycle = self.cycle;
+        let (mut settled, mut failed) = (0, 0);
+        let mut index = 0;
+        while index < self.pending.len() {
+            let trade = &mut self.pending[index];
+            if trade.due_cycle > cycle {
+                index += 1;
+                continue;
+            }
+            // Owed from its due date on, whether or not it settles
+            if trade.failures == 0 {
+                let qty = trade.qty as i64;
+                self.expected.entry(trade.buyer).or_default().apply(qty, -trade.notional);
+                self.expected.entry(trade.seller).or_default().apply(-qty, trade.notional);
+            }
+            if self.rng.gen_bool(self.config.fail_probability) {
+                trade.failures += 1;
+                failed += 1;
+                index += 1;
+                continue;
+            }
+            let trade = self.pending.remove(index).unwrap();
+            let qty = trade.qty as i64;
+            self.settled.entry(trade.buyer).or_default().apply(qty, -trade.notional);
+            self.settled.entry(trade.seller).or_default().apply(-qty, trade.notional);
+            settled += 1;
+        }
+
+        let breaks = self.reconcile(cycle, positions);
+        self.stats.settled += settled as u64;
+        self.stats.failures += failed as u64;
+        self.stats.cycles += 1;
+        if !breaks.is_empty() {
+            self.stats.cycles_with_breaks += 1;
+        }
+        let report = SettlementCycle { cycle, ts, settled, failed, breaks };
+        self.cycles.push_back(report.clone());
+        while self.cycles.len() > self.config.history {
+            self.cycles.pop_front();
+        }
+        self.cycle += 1;
+        report
+    }
+
+    /// Accounts whose settled holdings differ from their contractual ones or
+    /// whose booked position differs from the simulator's
+    fn reconcile(&self, cycle: u64, positions: &BTreeMap<Account, i64>) -> Vec<ReconciliationBreak> {
+        let mut accounts: Vec<Account> = self.expected.keys().chain(self.booked.keys()).chain(positions.keys()).copied().collect();
+        accounts.sort_unstable();
+        accounts.dedup();
+        accounts.into_iter()
+            .filter_map(|account| {
+                let expected = self.expected.get(&account).copied().unwrap_or_default();
+                let settled = self.settled.get(&account).copied().unwrap_or_default();
+                let actual = self.actual_position(account, positions);
+                let booked = self.booked_position(account, positions);
+                (settled != expected || actual.is_some_and(|actual| actual != booked)).then(|| ReconciliationBreak {
+                    account,
+                    cycle,
+                    expected,
+                    settled,
+                    failed_trades: self.pending.iter()
+                        .filter(|trade| trade.failures > 0 && (trade.buyer == account || trade.seller == account))
+                        .count(),
+                    booked,
+                    actual,
+                })
+            })
+            .collect()
+    }
+
+    /// Position the simulator holds for `account`. An agent it no longer
+    /// tracks has nothing to compare; its fills stay in its role's flow
+    fn actual_position(&self, account: Account, positions: &BTreeMap<Account, i64>) -> Option<i64> {
+        match account.agent {
+            Some(_) => positions.get(&account).copied(),
+            None => Some(positions.get(&account).copied().unwrap_or_default()),
+        }
+    }
+
+    /// Position booked for `account`, with a role's flow taking in the
+    /// bookings of its agents the simulator no longer tracks
+    fn booked_position(&self, account: Account, positions: &BTreeMap<Account, i64>) -> i64 {
+        match account.agent {
+            Some(_) => self.booked.get(&account).copied().unwrap_or_default(),
+            None => self.booked.iter()
+                .filter(|(booked, _)| booked.role == account.role
+                    && (booked.agent.is_none() || !positions.contains_key(booked)))
+                .map(|(_, &position)| position)
+                .sum(),
+        }
+    }
+
+    /// Drop every booked trade and closed cycle, restarting the failure draws
+    pub fn reset(&mut self) {
+        *self = Self::new(self.config.clone(), self.seed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Side;
+
+    fn config(fail_probability: f64) -> SettlementConfig {
+        SettlementConfig {
+            enabled: true,
+            cycle_ms: 10,
+            lag_cycles: 1,
+            fail_probability,
+            history: 10,
+        }
+    }
+
+    fn trade(qty: Qty, price: Price) -> Trade {
+        Trade { maker_id: 1, taker_id: 2, price, qty, ts: 0, aggressor: Side::Buy }
+    }
+
+    fn flat() -> BTreeMap<Account, i64> {
+        BTreeMap::new()
+    }
+
+    #[test]
+    fn test_trades_settle_after_the_lag() {
+        let mut ledger = SettlementLedger::new(config(0.0), 1);
+        let contract = ContractSpec::default();
+        let (taker, maker) = (Account::role(Agent::Taker), Account::role(Agent::MarketMaker));
+        let positions = BTreeMap::from([(taker, 10), (maker, -10)]);
+        assert!(ledger.on_step(0, &flat()).is_empty());
+        ledger.record(&trade(10, 100), taker, maker, &contract);
+
+        // T+1: nothing is due at the trade cycle's own close
+        let closed = ledger.on_step(ms_to_ns(10), &positions);
+        assert_eq!(closed[0].settled, 0);
+        assert!(ledger.settled().is_empty());
+        assert_eq!(ledger.pending().count(), 1);
+
+        let closed = ledger.on_step(ms_to_ns(25), &positions);
+        assert_eq!((closed[0].cycle, closed[0].settled), (1, 1));
+        assert!(closed[0].breaks.is_empty());
+        assert_eq!(ledger.settled()[&taker], Holdings { position: 10, cash: -1_000 });
+        assert_eq!(ledger.settled()[&maker], Holdings { position: -10, cash: 1_000 });
+        assert_eq!(ledger.stats().trades, 1);
+        assert!(SettlementConfig { fail_probability: 1.5, ..config(0.0) }.validate().is_err());
+    }
+
+    #[test]
+    fn test_failed_trades_break_until_they_settle() {
+        let mut ledger = SettlementLedger::new(config(1.0), 1);
+        let contract = ContractSpec::default();
+        let (manual, maker) = (Account::role(Agent::Manual), Account::role(Agent::MarketMaker));
+        let positions = BTreeMap::from([(manual, 10), (maker, -10)]);
+        ledger.on_step(0, &flat());
+        ledger.record(&trade(10, 100), manual, maker, &contract);
+
+        // Both sides of the failed trade break at its due close and after
+        ledger.on_step(ms_to_ns(20), &positions);
+        let breaks = ledger.breaks();
+        assert_eq!(breaks.len(), 2);
+        let manual = breaks.iter().find(|b| b.account == manual).unwrap();
+        assert_eq!((manual.position_break(), manual.cash_break(), manual.failed_trades), (10, -1_000, 1));
+        assert_eq!(manual.booking_break(), 0);
+        ledger.on_step(ms_to_ns(30), &positions);
+        assert_eq!(ledger.breaks().len(), 2);
+        assert_eq!(ledger.pending().next().unwrap().failures, 2);
+
+        // Once it settles the accounts reconcile again
+        ledger.config.fail_probability = 0.0;
+        let closed = ledger.on_step(ms_to_ns(40), &positions);
+        assert_eq!((closed[0].settled, closed[0].failed), (1, 0));
+        assert!(ledger.breaks().is_empty());
+        assert_eq!(ledger.expected(), ledger.settled());
+        assert_eq!(ledger.stats().failures, 2);
+        assert_eq!(ledger.stats().cycles_with_breaks, 2);
+    }
+
+    #[test]
+    fn test_positions_reconcile_per_account() {
+        let mut ledger = SettlementLedger::new(config(0.0), 1);
+        let contract = ContractSpec::default();
+        let (agent, maker) = (Account::of(Agent::Taker, Some(3)), Account::role(Agent::MarketMaker));
+        ledger.on_step(0, &flat());
+        ledger.record(&trade(10, 100), agent, maker, &contract);
+
+        // A position the simulator holds but the ledger never booked breaks
+        let mut positions = BTreeMap::from([(agent, 10), (maker, -10), (Account::role(Agent::Taker), 5)]);
+        ledger.on_step(ms_to_ns(10), &positions);
+        let breaks = ledger.breaks();
+        assert_eq!(breaks.len(), 1);
+        assert_eq!((breaks[0].account, breaks[0].booking_break()), (Account::role(Agent::Taker), 5));
+        assert_eq!(Account::try_from(agent.to_string()), Ok(agent));
+
+        // A removed agent's bookings fold into its role's flow
+        positions.remove(&agent);
+        positions.insert(Account::role(Agent::Taker), 10);
+        ledger.on_step(ms_to_ns(20), &positions);
+        assert!(ledger.breaks().is_empty());
+    }
+
+    #[test]
+    fn test_clock_jumps_close_a_bounded_number_of_cycles() {
+        let mut ledger = SettlementLedger::new(config(0.0), 1);
+        let contract = ContractSpec::default();
+        let (taker, maker) = (Account::role(Agent::Taker), Account::role(Agent::MarketMaker));
+        let positions = BTreeMap::from([(taker, 10), (maker, -10)]);
+        ledger.on_step(0, &flat());
+        ledger.record(&trade(10, 100), taker, maker, &contract);
+
+        // A day's jump closes enough cycles to settle what is booked, no more
+        let day = ms_to_ns(86_400_000);
+        let closed = ledger.on_step(day, &positions);
+        assert_eq!(closed.len(), 2);
+        assert_eq!(closed.iter().map(|cycle| cycle.settled).sum::<usize>(), 1);
+        assert_eq!(ledger.cycle(), 8_640_000);
+        assert_eq!(ledger.stats().skipped_cycles, 8_640_000 - 2);
+        assert!(ledger.on_step(day + ms_to_ns(5), &positions).is_empty());
+        assert_eq!(ledger.on_step(day + ms_to_ns(10), &positions).len(), 1);
+
+        // Going back restarts the grid rather than stopping closes
+        assert!(ledger.on_step(ms_to_ns(1), &positions).is_empty());
+        let closed = ledger.on_step(ms_to_ns(11), &positions);
+        assert_eq!((closed.len(), closed[0].ts), (1, ms_to_ns(11)));
+    }
+}
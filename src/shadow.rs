@@ -0,0 +1,182 @@
+//! Shadow book anchored to an external quote stream
+//!
+//! For pseudo-live demos the simulator can follow a real market's prices
+//! from a source that carries only quotes. Each two-sided quote re-anchors a
+//! ladder of liquidity on the external bid and ask, replacing the previous
+//! ladder, so the local book tracks the outside market while local agents
+//! trade against the ladder and add depth of their own. The ladder stands in
+//! for the external market, so its orders are treated as historical
+//! liquidity.
+
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::types::{OrderId, Price, Qty, Side};
+
+/// Shape of the ladder placed around each external quote
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct ShadowConfig {
+    /// Re-anchor liquidity to quotes from the data source
+    pub enabled: bool,
+    /// Price levels on each side
+    pub levels: usize,
+    /// Distance between levels in ticks
+    pub level_spacing: Price,
+    /// Quantity at each level, and at the top when the quote has no size
+    pub level_qty: Qty,
+    /// Smallest move of either side of the quote, in ticks, that re-anchors
+    pub min_move: Price,
+}
+
+impl Default for ShadowConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            levels: 5,
+            level_spacing: 100,
+            level_qty: 100,
+            min_move: 1,
+        }
+    }
+}
+
+impl ShadowConfig {
+    /// Check the ladder shape
+    pub fn validate(&self) -> Result<(), String> {
+        if self.levels == 0 {
+            return Err("Shadow book needs at least one level".to_string());
+        }
+        if self.level_spacing == 0 {
+            return Err("Shadow level spacing must be greater than 0".to_string());
+        }
+        if self.level_qty == 0 {
+            return Err("Shadow level quantity must be greater than 0".to_string());
+        }
+        if self.min_move == 0 {
+            return Err("Shadow minimum move must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Ladder of liquidity following the external best bid and offer
+#[derive(Debug, Clone)]
+pub struct ShadowBook {
+    config: ShadowConfig,
+    /// External bid and ask the current ladder was placed around
+    anchor: Option<(Price, Price)>,
+    /// Orders of the current ladder
+    orders: Vec<OrderId>,
+    reanchors: u64,
+}
+
+impl ShadowBook {
+    /// Start with no ladder
+    pub fn new(config: ShadowConfig) -> Self {
+        Self {
+            config,
+            anchor: None,
+            orders: Vec::new(),
+            reanchors: 0,
+        }
+    }
+
+    /// Get the ladder shape
+    pub fn config(&self) -> &ShadowConfig {
+        &self.config
+    }
+
+    /// External bid and ask the ladder sits on
+    pub fn anchor(&self) -> Option<(Price, Price)> {
+        self.anchor
+    }
+
+    /// Orders placed for the current ladder
+    pub fn orders(&self) -> &[OrderId] {
+        &self.orders
+    }
+
+    /// Times the ladder has moved to a new quote
+    pub fn reanchors(&self) -> u64 {
+        self.reanchors
+    }
+
+    /// Whether a quote has moved far enough from the anchor to replace the
+    /// ladder; crossed and locked quotes never do
+    pub fn should_reanchor(&self, bid: Price, ask: Price) -> bool {
+        if bid >= ask {
+            return false;
+        }
+        self.anchor.is_none_or(|(anchor_bid, anchor_ask)| {
+            bid.abs_diff(anchor_bid) >= self.config.min_move || ask.abs_diff(anchor_ask) >= self.config.min_move
+        })
+    }
+
+    /// Levels to place around a quote as (side, price, quantity), best first
+    /// on each side
+    ///
+    /// The top level takes the quote's size where it has one.
+    pub fn ladder(&self, bid: Price, ask: Price, bid_qty: Option<Qty>, ask_qty: Option<Qty>) -> Vec<(Side, Price, Qty)> {
+        let ShadowConfig { levels, level_spacing, level_qty, .. } = self.config;
+        let mut ladder = Vec::with_capacity(levels * 2);
+        for level in 0..levels as Price {
+            let offset = level * level_spacing;
+            let top = |quoted: Option<Qty>| if level == 0 { quoted.filter(|&qty| qty > 0).unwrap_or(level_qty) } else { level_qty };
+            if let Some(price) = bid.checked_sub(offset).filter(|&price| price > 0) {
+                ladder.push((Side::Buy, price, top(bid_qty)));
+            }
+            ladder.push((Side::Sell, ask + offset, top(ask_qty)));
+        }
+        ladder
+    }
+
+    /// Move the anchor to a new quote, returning the previous ladder's
+    /// orders for withdrawal
+    pub fn reanchor(&mut self, bid: Price, ask: Price) -> Vec<OrderId> {
+        self.anchor = Some((bid, ask));
+        self.reanchors += 1;
+        std::mem::take(&mut self.orders)
+    }
+
+    /// Record an order placed for the current ladder
+    pub fn track(&mut self, order_id: OrderId) {
+        self.orders.push(order_id);
+    }
+
+    /// Forget the ladder, as after the book is cleared
+    pub fn reset(&mut self) {
+        self.anchor = None;
+        self.orders.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ladder_follows_quotes_that_move_enough() {
+        let config = ShadowConfig { enabled: true, levels: 3, level_spacing: 100, level_qty: 50, min_move: 10 };
+        assert!(config.validate().is_ok());
+        assert!(ShadowConfig { levels: 0, ..config.clone() }.validate().is_err());
+        let mut shadow = ShadowBook::new(config);
+
+        assert!(shadow.should_reanchor(100_0000, 100_0200));
+        assert!(!shadow.should_reanchor(100_0200, 100_0200));
+        assert_eq!(shadow.ladder(100_0000, 100_0200, Some(70), None), vec![
+            (Side::Buy, 100_0000, 70), (Side::Sell, 100_0200, 50),
+            (Side::Buy, 99_9900, 50), (Side::Sell, 100_0300, 50),
+            (Side::Buy, 99_9800, 50), (Side::Sell, 100_0400, 50),
+        ]);
+        // Bid levels stop above zero
+        assert_eq!(shadow.ladder(150, 250, None, None).iter().filter(|(side, ..)| *side == Side::Buy).count(), 2);
+
+        assert!(shadow.reanchor(100_0000, 100_0200).is_empty());
+        shadow.track(1);
+        shadow.track(2);
+        assert!(!shadow.should_reanchor(100_0005, 100_0205));
+        assert!(shadow.should_reanchor(100_0000, 100_0210));
+        assert_eq!(shadow.reanchor(100_0000, 100_0210), vec![1, 2]);
+        assert_eq!((shadow.anchor(), shadow.reanchors()), (Some((100_0000, 100_0210)), 2));
+    }
+}
@@ -1,34 +1,123 @@
-use crate::engine::{OrderBookEngine, DepthSnapshot};
-use crate::data::{DataSource, MarketEvent};
-use crate::types::{Order, OrderId, Price, Qty, Side, Trade, Metrics, price_utils};
-use crate::time::now_ns;
-use crate::error::EngineResult;
-use crate::memory::CircularBuffer;
+use crate::agents::{AgentId, AgentInstance, AgentOrder, AgentQuote, AgentSpec, AgentStats, MarketView, SyntheticAgent};
+use crate::cost_model::{CostModel, ExecutionCost};
+use crate::engine::{CommandEngine, OrderBook, BookState, CrossedBookPolicy, DepthLimit, DepthSnapshot, EngineEvent, EngineCommand, EngineStats, OrderPreview, PruneReason, QuoteLeg, QuoteOutcome, QuoteOwner, StpGroup, StpMode, TradeTimestamps, TwoSidedQuote};
+use crate::queue::MatchFilter;
+use crate::data::{DataSource, DataSourceMetadata, MarketEvent, MarketStatusType};
+use crate::activity::{Activity, ActivityKind};
+use crate::gateway::{BusyConfig, Gateway, GatewayEvent, GatewayOutage, GatewayStats, OutagePolicy};
+use crate::types::{ContractSpec, ExecutionSummary, Liquidity, LotSize, Order, OrderId, OrderType, Price, Qty, Side, Trade, Metrics, order_ids, price_utils};
+use crate::time::{ms_to_ns, now_ns};
+use crate::metrics::{EventStamp, StepPhase, StepProfile};
+use crate::mbo::{OrderTracker, OrderUpdate};
+use crate::error::{EngineError, EngineResult};
+use crate::memory::{shrink_deque, shrink_map, shrink_vec, CircularBuffer, Compaction, HistoryUsage, MemoryConfig};
+use crate::analytics::{AnalyticsConfig, VolumeProfiles};
+use crate::spread_history::SpreadHistory;
+use crate::margin::{MarginConfig, MarginStatus};
+use crate::funding::{FundingConfig, FundingSchedule};
+use crate::obligations::{Maker, MakerQuote, ObligationConfig, ObligationMonitor};
+use crate::settlement::{Account, SettlementConfig, SettlementLedger};
+use crate::price_bands::{PriceBandConfig, PriceBands};
+use crate::velocity::VelocityConfig;
+use crate::latency::{LatencyConfig, LatencyCost};
+use crate::paper::{PaperFill, PaperSubmission, PaperTrader};
+use crate::warmup::{WarmupBoundary, WarmupConfig, WarmupController};
+use crate::throttle::{FlowThrottle, ThrottleConfig};
+use crate::order_refs::{placeholder_side, UnmatchedOrderPolicy, UnmatchedOrders};
+use crate::auction::{AuctionConfig, AuctionImbalance, PreOpenQueue};
+use crate::shadow::{ShadowBook, ShadowConfig};
+use crate::ab_shadow::{AbReport, AbShadow, AbShadowConfig};
+use crate::plugin::DynLevel;
+use crate::ladder::{LadderAction, LadderOutcome};
 use rand::{Rng, SeedableRng};
 use rand::rngs::StdRng;
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
 use tracing;
 
-/// Network latency simulation parameters
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct NetModel {
+/// Latency of one network path, sampled per message
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct PathLatency {
     /// Base latency in nanoseconds
     pub base_latency_ns: u64,
     /// Jitter range in nanoseconds (±jitter_ns)
     pub jitter_ns: u64,
+}
+
+impl PathLatency {
+    /// Create a path with the given base latency and jitter
+    pub fn new(base_latency_ns: u64, jitter_ns: u64) -> Self {
+        Self { base_latency_ns, jitter_ns }
+    }
+
+    /// Whether messages on this path always arrive instantly
+    pub fn is_instant(&self) -> bool {
+        self.base_latency_ns == 0 && self.jitter_ns == 0
+    }
+
+    /// Longest latency a message can see
+    pub fn max_ns(&self) -> u64 {
+        self.base_latency_ns + self.jitter_ns
+    }
+
+    /// Latency of one message
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> u64 {
+        let jitter = if self.jitter_ns > 0 {
+            rng.gen_range(-(self.jitter_ns as i64)..=(self.jitter_ns as i64))
+        } else {
+            0
+        };
+        
+        (self.base_latency_ns as i64 + jitter).max(0) as u64
+    }
+}
+
+/// Network latency simulation parameters
+///
+/// Orders travel to the exchange on the order path and their
+/// acknowledgements and fill reports come back on the ack path, while market
+/// data reaches agents on the data path. With the two return paths apart an
+/// agent can learn of its own fill before the book shows it, or the reverse.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct NetModel {
+    /// Latency of orders on their way to the exchange
+    #[serde(flatten)]
+    pub order_path: PathLatency,
+    /// Latency of acknowledgements and fill reports back to agents
+    #[serde(default, skip_serializing_if = "PathLatency::is_instant")]
+    pub ack_path: PathLatency,
+    /// Latency of market data reaching agents
+    #[serde(default, skip_serializing_if = "PathLatency::is_instant")]
+    pub data_path: PathLatency,
     /// Probability of packet drop (0.0 to 1.0)
     pub drop_prob: f64,
     /// Probability of packet reordering (0.0 to 1.0)
     pub reorder_prob: f64,
+    /// Scheduled gateway outages, during which order entry is down
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outages: Vec<GatewayOutage>,
+    /// Whether orders sent during an outage are refused or held for recovery
+    #[serde(default)]
+    pub outage_policy: OutagePolicy,
+    /// Throttling of order entry by an overloaded matching engine
+    #[serde(default)]
+    pub busy: BusyConfig,
 }
 
 impl Default for NetModel {
     fn default() -> Self {
         Self {
-            base_latency_ns: 100_000,    // 100 microseconds
-            jitter_ns: 50_000,          // ±50 microseconds
+            order_path: PathLatency::new(100_000, 50_000), // 100 ± 50 microseconds
+            ack_path: PathLatency::default(),
+            data_path: PathLatency::default(),
             drop_prob: 0.001,           // 0.1% drop rate
             reorder_prob: 0.01,         // 1% reorder rate
+            outages: Vec::new(),
+            outage_policy: OutagePolicy::default(),
+            busy: BusyConfig::default(),
         }
     }
 }
@@ -37,22 +126,41 @@ impl NetModel {
     /// Create a new network model with specified parameters
     pub fn new(base_latency_ns: u64, jitter_ns: u64, drop_prob: f64, reorder_prob: f64) -> Self {
         Self {
-            base_latency_ns,
-            jitter_ns,
+            order_path: PathLatency::new(base_latency_ns, jitter_ns),
+            ack_path: PathLatency::default(),
+            data_path: PathLatency::default(),
             drop_prob,
             reorder_prob,
+            outages: Vec::new(),
+            outage_policy: OutagePolicy::default(),
+            busy: BusyConfig::default(),
         }
     }
 
-    /// Calculate simulated latency for an operation
+    /// Delay acknowledgements and fill reports, and market data, on their
+    /// way back to agents
+    pub fn with_return_paths(mut self, ack_path: PathLatency, data_path: PathLatency) -> Self {
+        self.ack_path = ack_path;
+        self.data_path = data_path;
+        self
+    }
+
+    /// Throttle order entry under an overload model
+    pub fn with_busy(mut self, busy: BusyConfig) -> Self {
+        self.busy = busy;
+        self
+    }
+
+    /// Take order entry down for each of `outages`
+    pub fn with_outages(mut self, outages: Vec<GatewayOutage>, policy: OutagePolicy) -> Self {
+        self.outages = outages;
+        self.outage_policy = policy;
+        self
+    }
+
+    /// Calculate simulated latency for an order on its way to the exchange
     pub fn simulate_latency<R: Rng>(&self, rng: &mut R) -> u64 {
-        let jitter = if self.jitter_ns > 0 {
-            rng.gen_range(-(self.jitter_ns as i64)..=(self.jitter_ns as i64))
-        } else {
-            0
-        };
-        
-        (self.base_latency_ns as i64 + jitter).max(0) as u64
+        self.order_path.sample(rng)
     }
 
     /// Check if a packet should be dropped
@@ -67,19 +175,29 @@ impl NetModel {
 }
 
 /// Market simulation engine with configurable parameters
-pub struct Simulator<E: OrderBookEngine> {
+pub struct Simulator<E: CommandEngine> {
     /// The order book engine
     pub engine: E,
     /// Random number generator for deterministic simulation
     pub rng: StdRng,
+    /// Seed `rng` started from, which side streams derive theirs from
+    seed: u64,
     /// Network latency simulation model
     pub net: NetModel,
     /// Trading performance metrics
     pub metrics: Metrics,
-    /// Rolling spread history for visualization
-    pub recent_spreads: CircularBuffer<(u128, i64)>,
+    /// Recent spreads for visualization, with downsampled tiers for the
+    /// whole run
+    pub spread_history: SpreadHistory,
+    /// Rolling inventory and PnL history for reporting
+    metrics_history: CircularBuffer<MetricsPoint>,
+    /// Recent trades, including replayed prints
+    trade_tape: CircularBuffer<Trade>,
     /// Next order ID to assign
     next_order_id: OrderId,
+    /// Ids handed out to orders placed from outside; never starts over, so
+    /// no two sessions' orders share an id
+    session_orders: u64,
     /// Current simulation timestamp
     current_time: u128,
     /// Data source for historical replay (optional)
@@ -88,12 +206,299 @@ pub struct Simulator<E: OrderBookEngine> {
     mode: SimulationMode,
     /// Market making parameters
     market_maker_config: MarketMakerConfig,
+    /// Inventory band the synthetic market maker quotes in
+    market_maker_band: BandStatus,
     /// Order generation parameters
     order_gen_config: OrderGenerationConfig,
+    /// Inventory hedger (optional)
+    hedger: Option<Hedger>,
+    /// Transport state for replay mode
+    replay: ReplayTransport,
+    /// Accepted synthetic orders, collected when recording is enabled
+    recorded_events: Option<Vec<MarketEvent>>,
+    /// How synthetic orders may trade with historical ones in hybrid mode
+    hybrid_interaction: HybridInteraction,
+    /// Data cancels and modifications naming orders the book never saw
+    unmatched_orders: UnmatchedOrders,
+    /// Fills split by synthetic vs historical origin
+    fills: FillAccounting,
+    /// Paper orders and accounts matched against the book without touching it
+    paper: PaperTrader,
+    /// Traded volume by price over the run and per session
+    volume_profiles: VolumeProfiles,
+    /// Whether snapshots carry resting order ages
+    report_order_ages: bool,
+    /// Levels a side snapshots are cut to while the server sheds load
+    shed_levels: Option<usize>,
+    /// Lot size synthetic orders are generated in and the engine enforces
+    lot_size: LotSize,
+    /// Inventory, cash and maker/taker PnL per agent
+    agent_metrics: BTreeMap<Agent, Metrics>,
+    /// Agent, unfilled quantity and last fill time of resting non-historical orders
+    resting_agents: HashMap<OrderId, RestingOrder>,
+    /// Manual orders the price ladder placed, the only ones its clicks pull
+    ladder_orders: HashSet<OrderId>,
+    /// Net position of the ladder's fills, which a flatten closes
+    ladder_position: i64,
+    /// Expiry of untouched synthetic liquidity
+    liquidity_ttl: LiquidityTtlConfig,
+    /// Synthetic orders cancelled for outliving their TTL
+    expired_orders: u64,
+    /// Bound on the depth the engine retains
+    depth_limit: DepthLimit,
+    /// Orders the engine pruned past its depth limit
+    pruned_orders: u64,
+    /// STP groups of the order sources, when self-match prevention is on
+    self_match: Option<SelfMatchGroups>,
+    /// Orders self-match prevention cancelled, incoming or resting
+    self_matches: u64,
+    /// Configured synthetic agents by id
+    agents: BTreeMap<AgentId, AgentSlot>,
+    /// Id for the next agent added
+    next_agent_id: AgentId,
+    /// Whether a configured population replaced the built-in flow
+    builtin_replaced: bool,
+    /// Where the warm-up ended, if one ran
+    warmup: Option<WarmupBoundary>,
+    /// Payoff and size of the traded contract, for PnL, notional and margin
+    contract: ContractSpec,
+    /// Periodic funding between longs and shorts (optional)
+    funding: Option<FundingSchedule>,
+    /// Market makers scored against quoting obligations (optional)
+    obligations: Option<ObligationMonitor>,
+    /// Trades awaiting T+N settlement and reconciled holdings (optional)
+    settlement: Option<SettlementLedger>,
+    /// Limit-up/limit-down bands on synthetic orders in hybrid mode (optional)
+    price_bands: Option<PriceBands>,
+    /// Wall-clock time the data source last produced an event, heartbeats included
+    last_feed_event: Option<Instant>,
+    /// Ingest and match times of applied data source events, collected when enabled
+    pipeline_stamps: Option<Vec<EventStamp>>,
+    /// Resting orders followed for the market-by-order feed, while it is on
+    order_feed: Option<OrderTracker>,
+    /// Market activity for the timeline, collected when enabled
+    activity: Option<Vec<Activity>>,
+    /// Fills of resting paper orders, collected when enabled
+    paper_fills: Option<Vec<PaperFill>>,
+    /// Order entry, which scheduled outages take down
+    gateway: Gateway<PendingOrder>,
+    /// Modeled decision latency of agent orders (optional)
+    latency: Option<LatencyConfig>,
+    /// Agent orders decided on but not yet at the book
+    in_flight: Vec<InFlightOrder>,
+    /// Where the engine takes trade timestamps from
+    trade_timestamps: TradeTimestamps,
+    /// Time the engine last had its clock set to, if it has
+    engine_clock: Option<u128>,
+    /// Scales synthetic order flow down while steps overrun (optional)
+    throttle: Option<FlowThrottle>,
+    /// Orders held for the open during pre-open states, with who placed them (optional)
+    pre_open: Option<PreOpenQueue<(Agent, Option<AgentId>)>>,
+    /// Books published to agents that see market data late, oldest first
+    published: VecDeque<PublishedBook>,
+    /// Liquidity re-anchored to the data source's quotes (optional)
+    shadow: Option<ShadowBook>,
+    /// Second book every engine command is mirrored to for comparison (optional)
+    ab_shadow: Option<AbShadow>,
+    /// Time the current step spent in each phase, while profiling (optional)
+    profile: Option<StepProfile>,
+}
+
+/// The engine as the simulator drives it, mirroring every command to the
+/// A/B shadow book when there is one, following its resting orders for the
+/// market-by-order feed when that is on, and timing it when steps are profiled
+struct MirroredEngine<'a, E> {
+    engine: &'a mut E,
+    shadow: Option<&'a mut AbShadow>,
+    orders: Option<&'a mut OrderTracker>,
+    profile: Option<&'a mut StepProfile>,
+}
+
+impl<E: CommandEngine> CommandEngine for MirroredEngine<'_, E> {
+    fn execute(&mut self, command: EngineCommand) -> EngineResult<EngineEvent> {
+        let started = self.profile.is_some().then(Instant::now);
+        let observed = (self.shadow.is_some() || self.orders.is_some()).then(|| command.clone());
+        let outcome = self.engine.execute(command);
+        if let Some(command) = &observed {
+            if let Some(shadow) = self.shadow.as_deref_mut() {
+                shadow.mirror(command, &outcome, self.engine.best_bid_ask());
+            }
+            if let Some(orders) = self.orders.as_deref_mut() {
+                let engine = &*self.engine;
+                orders.observe(command, &outcome, |order_id| engine.order_resting_qty(order_id));
+            }
+        }
+        if let (Some(profile), Some(started)) = (self.profile.as_deref_mut(), started) {
+            profile.add(StepPhase::Matching, started.elapsed());
+        }
+        outcome
+    }
+
+    fn best_bid_ask(&self) -> (Option<Price>, Option<Price>) {
+        self.engine.best_bid_ask()
+    }
+
+    fn depth_snapshot(&self) -> DepthSnapshot {
+        self.engine.depth_snapshot()
+    }
+
+    fn depth_snapshot_top(&self, max_levels: usize) -> DepthSnapshot {
+        self.engine.depth_snapshot_top(max_levels)
+    }
+
+    fn open_orders(&self) -> Vec<Order> {
+        self.engine.open_orders()
+    }
+
+    fn order_priority_fee(&self, order_id: OrderId) -> u64 {
+        self.engine.order_priority_fee(order_id)
+    }
+
+    fn order_resting_qty(&self, order_id: OrderId) -> Option<Qty> {
+        self.engine.order_resting_qty(order_id)
+    }
+
+    fn preview_order(&self, order: Order, stp_group: Option<StpGroup>) -> EngineResult<OrderPreview> {
+        self.engine.preview_order(order, stp_group)
+    }
+}
+
+/// The market as an agent sees it
+#[derive(Debug, Clone)]
+struct PublishedBook {
+    /// Simulation time it was published
+    ts: u128,
+    best_bid: Option<Price>,
+    best_ask: Option<Price>,
+    mid: Option<f64>,
+    book: DepthSnapshot,
+}
+
+/// An agent order on its way to the book under latency accounting
+#[derive(Debug, Clone)]
+struct InFlightOrder {
+    /// Simulation time it reaches the book
+    due: u128,
+    owner: AgentId,
+    role: Agent,
+    order: Order,
+    /// Decision plus network latency
+    latency_ns: u64,
+    /// What it would have filled against the book it was decided on
+    zero_latency: ExecutionCost,
+}
+
+/// A synthetic order waiting at the gateway
+#[derive(Debug, Clone)]
+struct PendingOrder {
+    role: Agent,
+    /// Configured agent that sent it
+    owner: Option<AgentId>,
+    order: Order,
+}
+
+/// A configured agent with its entry and activity
+struct AgentSlot {
+    spec: AgentSpec,
+    agent: Box<dyn SyntheticAgent>,
+    paused: bool,
+    orders: u64,
+    /// Fills of its own orders
+    metrics: Metrics,
+    /// Latency of its orders and what it cost them
+    latency: LatencyCost,
+    /// Fill reports still on their way back: when each arrives and how it
+    /// moves inventory
+    unacked: Vec<(u128, i64)>,
+    /// Last of its orders it cancelled
+    last_cancelled: Option<OrderId>,
+}
+
+impl AgentSlot {
+    /// Inventory from the fills reported to the agent by `now`
+    fn reported_inventory(&mut self, now: u128) -> i64 {
+        self.unacked.retain(|&(due, _)| due > now);
+        self.metrics.inventory - self.unacked.iter().map(|&(_, change)| change).sum::<i64>()
+    }
+
+    /// Reset its activity and fills
+    fn reset(&mut self) {
+        self.orders = 0;
+        self.metrics = Metrics::new();
+        self.latency = LatencyCost::default();
+        self.unacked.clear();
+    }
+}
+
+/// A resting non-historical order, for attributing fills and expiring it
+#[derive(Debug, Clone, Copy)]
+struct RestingOrder {
+    agent: Agent,
+    /// Configured agent that placed it
+    owner: Option<AgentId>,
+    remaining: Qty,
+    /// Simulation time it was placed or last filled
+    touched_at: u128,
+}
+
+/// A resting order in a saved book, with who placed it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageOrder {
+    pub order: Order,
+    /// `None` for historical orders
+    pub agent: Option<Agent>,
+    /// Configured agent that placed it
+    pub owner: Option<AgentId>,
+    /// Priority fees it had paid, which rank it within its level
+    #[serde(default)]
+    pub priority_fee: u64,
+}
+
+/// Book state saved for restoring later
+///
+/// Holds the resting orders in queue priority order together with the order
+/// id counter, the match filter and the simulation clock, so a restored book
+/// continues as if the time in between had not passed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BookImage {
+    /// Wall-clock time it was saved (nanoseconds)
+    pub saved_at: u128,
+    /// Simulation time it was saved at
+    pub current_time: u128,
+    pub next_order_id: OrderId,
+    /// Order ids reserved for outside orders by then
+    #[serde(default)]
+    pub session_orders: u64,
+    /// Hybrid interaction policy filtering matches when it was saved;
+    /// `None` when every order could match
+    #[serde(default)]
+    pub interaction: Option<HybridInteraction>,
+    /// Bids best first, then asks best first, each level in queue order
+    pub orders: Vec<ImageOrder>,
+}
+
+/// Bit set on the owners of manual quotes, whose ids would otherwise
+/// collide with configured agents' ids
+pub const MANUAL_QUOTE_OWNERS: QuoteOwner = 1 << 63;
+
+/// Account manual orders' fills are reported under
+pub const MANUAL_ACCOUNT: &str = "manual";
+
+/// Restored orders keep their age up to this, well inside the engine's
+/// one-hour window on order timestamps
+const RESTORED_ORDER_MAX_AGE_NS: u128 = 50 * 60 * 1_000_000_000;
+
+/// Inventory and PnL at a point in the run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetricsPoint {
+    pub ts: u128,
+    pub inventory: i64,
+    pub pnl: i64,
 }
 
 /// Simulation modes
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum SimulationMode {
     /// Pure simulation with synthetic order flow
     Synthetic,
@@ -101,10 +506,151 @@ pub enum SimulationMode {
     Historical,
     /// Hybrid mode combining historical data with synthetic orders
     Hybrid,
+    /// Faithful market-data playback: only the data source's order events
+    /// touch the book, paced by a replay clock with transport controls
+    Replay,
+}
+
+/// Maximum consecutive unreadable records tolerated before a replay step fails
+const MAX_REPLAY_READ_ERRORS: usize = 100;
+
+/// Data source event with its ingest stamp, if stamps are collected
+type IngestedEvent = (MarketEvent, Option<(u128, u128)>);
+
+/// Clock and transport state used by `SimulationMode::Replay`
+#[derive(Debug, Clone)]
+struct ReplayTransport {
+    paused: bool,
+    speed: f64,
+    /// Current position of the replay clock (event time, nanoseconds)
+    clock: Option<u128>,
+    /// Event read ahead of the clock with its ingest stamp, applied once the
+    /// clock reaches it
+    pending: Option<IngestedEvent>,
+    /// Wall-clock instant of the previous replay step
+    last_tick: Option<Instant>,
+    /// Side and price of resting orders, needed to apply modifications
+    order_locations: HashMap<OrderId, (Side, Price)>,
+    events_applied: u64,
+}
+
+impl Default for ReplayTransport {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            speed: 1.0,
+            clock: None,
+            pending: None,
+            last_tick: None,
+            order_locations: HashMap::new(),
+            events_applied: 0,
+        }
+    }
+}
+
+/// Replay transport status reported to clients
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayStatus {
+    pub paused: bool,
+    pub speed: f64,
+    pub clock: Option<u128>,
+    pub events_applied: u64,
+    pub finished: bool,
+}
+
+/// How synthetic orders interact with historical liquidity in hybrid mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HybridInteraction {
+    /// Synthetic and historical orders never execute against each other
+    Shadow,
+    /// Synthetic orders may take historical liquidity, but historical orders
+    /// never execute against resting synthetic orders
+    PriceTaking,
+    /// Synthetic and historical orders match freely
+    #[default]
+    Interactive,
+}
+
+impl HybridInteraction {
+    /// Engine match filter implementing this policy
+    pub fn match_filter(&self) -> Option<MatchFilter> {
+        match self {
+            Self::Shadow => Some(same_origin),
+            Self::PriceTaking => Some(historical_never_takes_synthetic),
+            Self::Interactive => None,
+        }
+    }
+
+    /// Policy an engine match filter implements, `None` for a filter that
+    /// is no policy's
+    pub fn from_match_filter(filter: Option<MatchFilter>) -> Option<Self> {
+        [Self::Shadow, Self::PriceTaking, Self::Interactive].into_iter()
+            .find(|policy| match (policy.match_filter(), filter) {
+                (Some(own), Some(filter)) => std::ptr::fn_addr_eq(own, filter),
+                (own, filter) => own.is_none() && filter.is_none(),
+            })
+    }
+}
+
+fn same_origin(maker_id: OrderId, taker_id: OrderId) -> bool {
+    order_ids::is_historical(maker_id) == order_ids::is_historical(taker_id)
+}
+
+fn historical_never_takes_synthetic(maker_id: OrderId, taker_id: OrderId) -> bool {
+    order_ids::is_historical(maker_id) || !order_ids::is_historical(taker_id)
+}
+
+/// Executed trades and volume for one pairing of order origins
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FillStats {
+    pub trades: u64,
+    pub qty: u64,
+}
+
+/// Source of an order in the simulation, for attributing its fills
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Agent {
+    /// Synthetic market-making quotes
+    MarketMaker,
+    /// Synthetic taker flow
+    Taker,
+    /// Orders from historical data
+    Historical,
+    /// Orders placed directly through the simulator
+    Manual,
+}
+
+/// Fills split by whether the taker and maker were synthetic or historical
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FillAccounting {
+    /// Synthetic orders trading with each other
+    pub synthetic: FillStats,
+    /// Synthetic takers executing against historical liquidity
+    pub synthetic_vs_historical: FillStats,
+    /// Historical takers executing against synthetic liquidity
+    pub historical_vs_synthetic: FillStats,
+    /// Historical orders trading with each other
+    pub historical: FillStats,
+}
+
+impl FillAccounting {
+    /// Classify a trade by the origin of its taker and maker
+    pub fn record(&mut self, trade: &Trade) {
+        let stats = match (order_ids::is_historical(trade.taker_id), order_ids::is_historical(trade.maker_id)) {
+            (false, false) => &mut self.synthetic,
+            (false, true) => &mut self.synthetic_vs_historical,
+            (true, false) => &mut self.historical_vs_synthetic,
+            (true, true) => &mut self.historical,
+        };
+        stats.trades += 1;
+        stats.qty += trade.qty;
+    }
 }
 
 /// Market maker configuration parameters
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct MarketMakerConfig {
     /// Spread to maintain (in ticks)
     pub target_spread: Price,
@@ -116,6 +662,14 @@ pub struct MarketMakerConfig {
     pub mm_probability: f64,
     /// Inventory skew factor (how much to adjust prices based on inventory)
     pub inventory_skew: f64,
+    /// Thresholds past which quotes widen and shrink, in increasing order;
+    /// the skew alone applies when empty
+    #[serde(default)]
+    pub inventory_bands: Vec<InventoryBand>,
+    /// Replace the whole two-sided quote each step in one engine call
+    /// instead of adding orders; configured `market_maker` agents only
+    #[serde(default)]
+    pub two_sided_quotes: bool,
 }
 
 impl Default for MarketMakerConfig {
@@ -126,12 +680,104 @@ impl Default for MarketMakerConfig {
             order_size: 100,
             mm_probability: 0.7,
             inventory_skew: 0.001,  // 0.1% price adjustment per unit inventory
+            inventory_bands: Vec::new(),
+            two_sided_quotes: false,
+        }
+    }
+}
+
+impl MarketMakerConfig {
+    /// Check the spread, size, probability and inventory bands
+    pub fn validate(&self) -> Result<(), String> {
+        if self.target_spread == 0 {
+            return Err("Target spread cannot be 0".to_string());
+        }
+        if self.order_size == 0 {
+            return Err("Market maker order size cannot be 0".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.mm_probability) {
+            return Err("Market maker probability must be between 0.0 and 1.0".to_string());
+        }
+        if !self.inventory_skew.is_finite() {
+            return Err("Inventory skew must be finite".to_string());
         }
+        self.validate_bands()
+    }
+
+    /// Check the inventory bands
+    pub fn validate_bands(&self) -> Result<(), String> {
+        let mut previous = 0.0;
+        for band in &self.inventory_bands {
+            if band.threshold <= previous || band.threshold > 1.0 {
+                return Err("Inventory band thresholds must increase within (0.0, 1.0]".to_string());
+            }
+            if band.spread_multiplier < 1.0 {
+                return Err("Inventory band spread multiplier must be at least 1.0".to_string());
+            }
+            if band.size_multiplier <= 0.0 || band.size_multiplier > 1.0 {
+                return Err("Inventory band size multiplier must be in (0.0, 1.0]".to_string());
+            }
+            previous = band.threshold;
+        }
+        Ok(())
+    }
+
+    /// Number of band thresholds `inventory` has reached, either way; 0
+    /// below the first
+    pub fn inventory_band(&self, inventory: i64) -> usize {
+        let used = inventory.unsigned_abs() as f64 / self.max_inventory.max(1) as f64;
+        self.inventory_bands.iter().take_while(|band| used >= band.threshold).count()
+    }
+
+    /// Half spread and order size to quote with at `inventory`
+    pub fn quote_shape(&self, inventory: i64) -> (Price, Qty) {
+        let half_spread = self.target_spread / 2;
+        match self.inventory_band(inventory).checked_sub(1) {
+            Some(index) => {
+                let band = &self.inventory_bands[index];
+                (
+                    (half_spread as f64 * band.spread_multiplier).round() as Price,
+                    (self.order_size as f64 * band.size_multiplier).round() as Qty,
+                )
+            }
+            None => (half_spread, self.order_size),
+        }
+    }
+}
+
+/// Quoting past one inventory threshold
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct InventoryBand {
+    /// Share of `max_inventory`, long or short, where the band starts
+    pub threshold: f64,
+    /// Target spread multiplier inside the band
+    pub spread_multiplier: f64,
+    /// Order size multiplier inside the band; sizes still round up to a lot
+    pub size_multiplier: f64,
+}
+
+/// The inventory band a market maker quotes in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BandStatus {
+    /// Thresholds its inventory has reached; 0 below the first
+    pub band: usize,
+    /// Times it moved between bands
+    pub transitions: u64,
+}
+
+impl BandStatus {
+    /// Move to `band`, returning the band it left if that is a change
+    pub fn enter(&mut self, band: usize) -> Option<usize> {
+        if band == self.band {
+            return None;
+        }
+        self.transitions += 1;
+        Some(std::mem::replace(&mut self.band, band))
     }
 }
 
 /// Order generation configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct OrderGenerationConfig {
     /// Probability of generating a market order vs limit order
     pub market_order_prob: f64,
@@ -142,6 +788,17 @@ pub struct OrderGenerationConfig {
     pub max_order_size: Qty,
     /// Price range for limit orders (as fraction of mid-price)
     pub price_range_fraction: f64,
+    /// Probability that a taker order is a buy; above 0.5 trends the price up
+    #[serde(default = "default_buy_probability")]
+    pub buy_probability: f64,
+    /// Shrink market orders to what the book can fill within this slippage
+    /// from the mid, in ticks, skipping orders that would not fill a lot
+    #[serde(default)]
+    pub max_slippage_ticks: Option<Price>,
+}
+
+fn default_buy_probability() -> f64 {
+    0.5
 }
 
 impl Default for OrderGenerationConfig {
@@ -152,11 +809,318 @@ impl Default for OrderGenerationConfig {
             min_order_size: 10,
             max_order_size: 500,
             price_range_fraction: 0.02,  // ±2% from mid-price
+            buy_probability: default_buy_probability(),
+            max_slippage_ticks: None,
+        }
+    }
+}
+
+impl OrderGenerationConfig {
+    /// Check the probabilities, order sizes and price range
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.market_order_prob) {
+            return Err("Market order probability must be between 0.0 and 1.0".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.buy_probability) {
+            return Err("Buy probability must be between 0.0 and 1.0".to_string());
+        }
+        if self.min_order_size == 0 {
+            return Err("Minimum order size cannot be 0".to_string());
+        }
+        if self.max_order_size < self.min_order_size {
+            return Err("Maximum order size cannot be less than minimum order size".to_string());
+        }
+        if !(self.price_range_fraction >= 0.0 && self.price_range_fraction.is_finite()) {
+            return Err("Price range fraction must be a finite number of at least 0.0".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Inventory hedging configuration
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct HedgeConfig {
+    /// Enable periodic hedging of net inventory
+    pub enabled: bool,
+    /// Number of simulation steps between hedge checks
+    pub interval_steps: u64,
+    /// Minimum absolute inventory before a hedge is executed
+    pub inventory_threshold: i64,
+    /// Fixed cost charged per hedge execution (in ticks)
+    pub fixed_cost: i64,
+    /// Proportional cost in basis points of hedged notional
+    pub cost_bps: f64,
+}
+
+impl Default for HedgeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_steps: 10,
+            inventory_threshold: 100,
+            fixed_cost: 0,
+            cost_bps: 1.0,  // 1 basis point per hedge
+        }
+    }
+}
+
+/// Expiry of resting synthetic liquidity
+///
+/// Long synthetic runs otherwise pile up quotes far from the market that no
+/// one ever trades with. TTLs are in simulation time and restart on each fill.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct LiquidityTtlConfig {
+    /// Cancel synthetic orders left untouched past their agent's TTL
+    pub enabled: bool,
+    /// TTL for market maker quotes (milliseconds); unset never expires
+    #[serde(default)]
+    pub market_maker_ms: Option<u64>,
+    /// TTL for resting taker-flow limit orders (milliseconds); unset never expires
+    #[serde(default)]
+    pub taker_ms: Option<u64>,
+}
+
+impl Default for LiquidityTtlConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            market_maker_ms: Some(5_000),
+            taker_ms: Some(30_000),
+        }
+    }
+}
+
+impl LiquidityTtlConfig {
+    /// TTL in nanoseconds for an agent's orders, if they expire
+    pub fn ttl(&self, agent: Agent) -> Option<u128> {
+        if !self.enabled {
+            return None;
+        }
+        let ttl_ms = match agent {
+            Agent::MarketMaker => self.market_maker_ms,
+            Agent::Taker => self.taker_ms,
+            Agent::Historical | Agent::Manual => None,
+        };
+        ttl_ms.map(ms_to_ns)
+    }
+
+    /// Check the TTLs
+    pub fn validate(&self) -> Result<(), String> {
+        if self.market_maker_ms == Some(0) || self.taker_ms == Some(0) {
+            return Err("Liquidity TTL must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Self-match prevention between order sources sharing the engine
+///
+/// Orders from sources in one group never trade with each other; the mode
+/// decides which side of a would-be self-match is cancelled. By default the
+/// built-in taker flow never lifts the built-in market maker's quotes.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SelfMatchConfig {
+    /// Prevent self-matches within groups
+    pub enabled: bool,
+    /// Cancel the incoming order (`cancel_taker`) or the resting one (`cancel_resting`)
+    #[serde(default)]
+    pub mode: StpMode,
+    /// Built-in order sources in each named group. Configured `[[agents]]`
+    /// entries join a group with their own `stp_group`
+    #[serde(default = "default_self_match_groups")]
+    pub groups: BTreeMap<String, Vec<Agent>>,
+}
+
+fn default_self_match_groups() -> BTreeMap<String, Vec<Agent>> {
+    BTreeMap::from([("simulator".to_string(), vec![Agent::MarketMaker, Agent::Taker])])
+}
+
+impl Default for SelfMatchConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            mode: StpMode::default(),
+            groups: default_self_match_groups(),
         }
     }
 }
 
-impl<E: OrderBookEngine> Simulator<E> {
+impl SelfMatchConfig {
+    /// Check that group names are set and each source is in one group at most
+    pub fn validate(&self) -> Result<(), String> {
+        let mut grouped = HashSet::new();
+        for (name, members) in &self.groups {
+            if name.trim().is_empty() {
+                return Err("Self-match group names cannot be empty".to_string());
+            }
+            if let Some(agent) = members.iter().find(|agent| !grouped.insert(**agent)) {
+                return Err(format!("{:?} orders are in more than one self-match group", agent));
+            }
+            if members.contains(&Agent::Historical) {
+                return Err("Historical orders cannot join a self-match group".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// STP group ids for the simulator's order sources
+#[derive(Debug, Clone, Default)]
+struct SelfMatchGroups {
+    /// Group ids by name, assigned as names are first seen
+    ids: BTreeMap<String, StpGroup>,
+    /// Groups of the built-in order sources
+    roles: HashMap<Agent, StpGroup>,
+    /// Group the engine places orders in
+    current: Option<StpGroup>,
+}
+
+impl SelfMatchGroups {
+    fn new(config: &SelfMatchConfig) -> Self {
+        let mut groups = Self::default();
+        for (name, members) in &config.groups {
+            let id = groups.id(name);
+            groups.roles.extend(members.iter().map(|agent| (*agent, id)));
+        }
+        groups
+    }
+
+    fn id(&mut self, name: &str) -> StpGroup {
+        let next = self.ids.len() as StpGroup;
+        *self.ids.entry(name.to_string()).or_insert(next)
+    }
+}
+
+/// A single hedge execution against the mark price
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HedgeExecution {
+    /// Side of the hedge trade (opposite to the inventory sign)
+    pub side: Side,
+    /// Quantity hedged
+    pub qty: Qty,
+    /// Mark price the hedge was executed at
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub price: Price,
+    /// Total cost charged for the hedge (in ticks)
+    pub cost: i64,
+    /// Simulation timestamp of the hedge
+    pub ts: u128,
+}
+
+/// Cumulative hedging statistics
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HedgeStats {
+    pub hedge_count: u64,
+    pub total_hedged_qty: Qty,
+    pub total_cost: i64,
+}
+
+/// Hedger that periodically flattens net inventory at an external mark price
+///
+/// The mark defaults to the last external reference price (e.g. from quote
+/// events in historical replay) and falls back to the book mid-price.
+#[derive(Debug, Clone)]
+pub struct Hedger {
+    config: HedgeConfig,
+    /// Last external reference price in ticks
+    external_price: Option<Price>,
+    /// Steps elapsed since the last hedge check
+    steps_since_check: u64,
+    stats: HedgeStats,
+    /// Contract hedged, for valuing hedges and their cost
+    contract: ContractSpec,
+}
+
+impl Hedger {
+    /// Create a new hedger with the given configuration
+    pub fn new(config: HedgeConfig) -> Self {
+        Self {
+            config,
+            external_price: None,
+            steps_since_check: 0,
+            stats: HedgeStats::default(),
+            contract: ContractSpec::default(),
+        }
+    }
+
+    /// Value hedges as this contract
+    pub fn with_contract(mut self, contract: ContractSpec) -> Self {
+        self.contract = contract;
+        self
+    }
+
+    /// Update the external reference price used as the hedge mark
+    pub fn set_external_price(&mut self, price: Price) {
+        self.external_price = Some(price);
+    }
+
+    /// Get the current external reference price
+    pub fn external_price(&self) -> Option<Price> {
+        self.external_price
+    }
+
+    /// Get the hedging configuration
+    pub fn config(&self) -> &HedgeConfig {
+        &self.config
+    }
+
+    /// Get cumulative hedging statistics
+    pub fn stats(&self) -> &HedgeStats {
+        &self.stats
+    }
+
+    /// Advance one step and hedge if the interval has elapsed
+    pub fn on_step(&mut self, metrics: &mut Metrics, fallback_mark: Option<Price>, ts: u128) -> Option<HedgeExecution> {
+        self.steps_since_check += 1;
+        if self.steps_since_check < self.config.interval_steps.max(1) {
+            return None;
+        }
+        self.steps_since_check = 0;
+
+        if metrics.inventory.abs() < self.config.inventory_threshold {
+            return None;
+        }
+
+        self.hedge_now(metrics, fallback_mark, ts)
+    }
+
+    /// Flatten the full inventory immediately at the mark price
+    pub fn hedge_now(&mut self, metrics: &mut Metrics, fallback_mark: Option<Price>, ts: u128) -> Option<HedgeExecution> {
+        if metrics.inventory == 0 {
+            return None;
+        }
+        let mark = self.external_price.or(fallback_mark)?;
+
+        let side = if metrics.inventory > 0 { Side::Sell } else { Side::Buy };
+        let qty = metrics.inventory.unsigned_abs();
+        let cost = self.cost_for(qty, mark);
+
+        metrics.update_trade_for(&self.contract, side, qty, mark);
+        metrics.cash -= cost;
+        metrics.calculate_pnl_for(&self.contract, Some(mark));
+
+        self.stats.hedge_count += 1;
+        self.stats.total_hedged_qty += qty;
+        self.stats.total_cost += cost;
+
+        Some(HedgeExecution { side, qty, price: mark, cost, ts })
+    }
+
+    /// Calculate the cost of hedging `qty` at `price`
+    pub fn cost_for(&self, qty: Qty, price: Price) -> i64 {
+        let notional = self.contract.notional(qty, price).unwrap_or(i64::MAX) as f64;
+        self.config.fixed_cost + (notional * self.config.cost_bps / 10_000.0).round() as i64
+    }
+
+    /// Reset hedge state and statistics
+    pub fn reset(&mut self) {
+        self.external_price = None;
+        self.steps_since_check = 0;
+        self.stats = HedgeStats::default();
+    }
+}
+
+impl<E: CommandEngine> Simulator<E> {
     /// Create a new simulator with default parameters
     pub fn new(engine: E) -> Self {
         Self::with_seed(engine, 42)
@@ -166,282 +1130,2247 @@ impl<E: OrderBookEngine> Simulator<E> {
     pub fn with_seed(engine: E, seed: u64) -> Self {
         Self {
             engine,
+            seed,
             rng: StdRng::seed_from_u64(seed),
             net: NetModel::default(),
             metrics: Metrics::new(),
-            recent_spreads: CircularBuffer::new(400),
+            spread_history: SpreadHistory::default(),
+            metrics_history: CircularBuffer::new(400),
+            trade_tape: CircularBuffer::new(MemoryConfig::default().trade_tape),
             next_order_id: 1,
+            session_orders: 0,
             current_time: now_ns(),
             data_source: None,
             mode: SimulationMode::Synthetic,
             market_maker_config: MarketMakerConfig::default(),
+            market_maker_band: BandStatus::default(),
             order_gen_config: OrderGenerationConfig::default(),
+            hedger: None,
+            replay: ReplayTransport::default(),
+            recorded_events: None,
+            hybrid_interaction: HybridInteraction::default(),
+            unmatched_orders: UnmatchedOrders::default(),
+            fills: FillAccounting::default(),
+            paper: PaperTrader::default(),
+            volume_profiles: VolumeProfiles::default(),
+            report_order_ages: false,
+            shed_levels: None,
+            lot_size: LotSize::default(),
+            agent_metrics: BTreeMap::new(),
+            resting_agents: HashMap::new(),
+            ladder_orders: HashSet::new(),
+            ladder_position: 0,
+            liquidity_ttl: LiquidityTtlConfig::default(),
+            expired_orders: 0,
+            depth_limit: DepthLimit::default(),
+            pruned_orders: 0,
+            self_match: None,
+            self_matches: 0,
+            agents: BTreeMap::new(),
+            next_agent_id: 1,
+            builtin_replaced: false,
+            warmup: None,
+            contract: ContractSpec::default(),
+            funding: None,
+            obligations: None,
+            settlement: None,
+            price_bands: None,
+            pipeline_stamps: None,
+            order_feed: None,
+            last_feed_event: None,
+            activity: None,
+            paper_fills: None,
+            gateway: Gateway::default(),
+            latency: None,
+            in_flight: Vec::new(),
+            trade_timestamps: TradeTimestamps::default(),
+            engine_clock: None,
+            throttle: None,
+            pre_open: None,
+            published: VecDeque::new(),
+            shadow: None,
+            ab_shadow: None,
+            profile: None,
         }
     }
 
-    /// Set the network model for latency simulation
+    /// Set the network model for latency simulation, including its
+    /// scheduled gateway outages and order entry throttling
     pub fn with_network_model(mut self, net: NetModel) -> Self {
+        self.gateway = Gateway::new(net.outages.clone(), net.outage_policy);
+        if net.busy.enabled {
+            // Derived rather than drawn, so enabling it leaves `rng` untouched
+            let seed = self.seed ^ 0x9E37_79B9_7F4A_7C15;
+            self.gateway = self.gateway.with_busy(net.busy, seed);
+        }
         self.net = net;
         self
     }
 
-    /// Set the market maker configuration
-    pub fn with_market_maker_config(mut self, config: MarketMakerConfig) -> Self {
-        self.market_maker_config = config;
+    /// Replace the built-in market maker and taker with these agents
+    ///
+    /// Each agent quotes in turn every synthetic step, seeing the book as
+    /// the agents before it left it. Hybrid mode runs them in place of its
+    /// occasional market-making quotes.
+    pub fn with_agents(mut self, agents: Vec<AgentInstance>) -> Self {
+        self.agents.clear();
+        self.builtin_replaced = !agents.is_empty();
+        for instance in agents {
+            self.add_agent(instance);
+        }
         self
     }
 
-    /// Set the order generation configuration
-    pub fn with_order_generation_config(mut self, config: OrderGenerationConfig) -> Self {
-        self.order_gen_config = config;
-        self
+    /// Add an agent to the running population, returning its id
+    ///
+    /// It trades alongside the built-in market maker and taker unless a
+    /// configured population already replaced them.
+    pub fn add_agent(&mut self, instance: AgentInstance) -> AgentId {
+        let id = self.next_agent_id;
+        self.next_agent_id += 1;
+        self.agents.insert(id, AgentSlot {
+            spec: instance.spec,
+            agent: instance.agent,
+            paused: false,
+            orders: 0,
+            metrics: Metrics::new(),
+            latency: LatencyCost::default(),
+            unacked: Vec::new(),
+            last_cancelled: None,
+        });
+        id
     }
 
-    /// Set a data source for historical replay
-    pub fn with_data_source(mut self, data_source: Box<dyn DataSource>) -> Self {
-        self.data_source = Some(data_source);
-        self.mode = SimulationMode::Historical;
+    /// Whether the built-in market maker and taker send orders: unless a
+    /// configured population that is still running replaced them
+    fn builtin_flow(&self) -> bool {
+        !self.builtin_replaced || self.agents.is_empty()
+    }
+
+    /// Swap an agent's implementation, keeping its id, orders and fills.
+    /// Returns false if there is no such agent
+    pub fn replace_agent(&mut self, id: AgentId, instance: AgentInstance) -> bool {
+        let Some(slot) = self.agents.get_mut(&id) else {
+            return false;
+        };
+        slot.spec = instance.spec;
+        slot.agent = instance.agent;
+        true
+    }
+
+    /// Stop or resume an agent's trading; its resting orders stay in the book.
+    /// Returns false if there is no such agent
+    pub fn set_agent_paused(&mut self, id: AgentId, paused: bool) -> bool {
+        self.agents.get_mut(&id).map(|slot| slot.paused = paused).is_some()
+    }
+
+    /// Remove an agent and cancel its resting orders, returning its final stats
+    ///
+    /// Removing the last agent of a configured population brings back the
+    /// built-in market maker and taker.
+    pub fn remove_agent(&mut self, id: AgentId) -> Option<AgentStats> {
+        let stats = self.agent_stats_for(id)?;
+        self.agents.remove(&id);
+        let mut owned: Vec<OrderId> = self.resting_agents.iter()
+            .filter(|(_, resting)| resting.owner == Some(id))
+            .map(|(&order_id, _)| order_id)
+            .collect();
+        owned.sort_unstable();
+        for order_id in owned {
+            self.resting_agents.remove(&order_id);
+            let _ = self.mirrored().withdraw(order_id);
+        }
+        self.update_spread_history();
+        Some(stats)
+    }
+
+    /// Entry an agent was built from
+    pub fn agent_spec(&self, id: AgentId) -> Option<&AgentSpec> {
+        self.agents.get(&id).map(|slot| &slot.spec)
+    }
+
+    /// Activity and fills of one configured agent
+    pub fn agent_stats_for(&self, id: AgentId) -> Option<AgentStats> {
+        self.agents.get(&id).map(|slot| AgentStats {
+            id,
+            kind: slot.spec.kind.clone(),
+            role: slot.agent.role(),
+            paused: slot.paused,
+            orders: slot.orders,
+            metrics: slot.metrics.clone(),
+            latency: slot.latency.clone(),
+            inventory_band: slot.agent.inventory_band(),
+        })
+    }
+
+    /// Activity and fills of every configured agent, by id
+    pub fn agent_stats(&self) -> Vec<AgentStats> {
+        self.agents.keys().filter_map(|&id| self.agent_stats_for(id)).collect()
+    }
+
+    /// Set the market maker configuration
+    pub fn with_market_maker_config(mut self, config: MarketMakerConfig) -> Self {
+        self.market_maker_config = config;
         self
     }
 
-    /// Set simulation mode
-    pub fn set_mode(&mut self, mode: SimulationMode) {
-        self.mode = mode;
+    /// Inventory band the synthetic market maker last quoted in
+    pub fn market_maker_band(&self) -> BandStatus {
+        self.market_maker_band
     }
 
-    /// Get the next order ID
-    fn next_order_id(&mut self) -> OrderId {
-        let id = self.next_order_id;
-        self.next_order_id += 1;
-        id
+    /// Set the order generation configuration
+    pub fn with_order_generation_config(mut self, config: OrderGenerationConfig) -> Self {
+        self.order_gen_config = config;
+        self
     }
 
-    /// Generate a realistic market making order pair
-    fn generate_market_making_orders(&mut self) -> Vec<Order> {
-        let mut orders = Vec::new();
-        
-        // Get current market state
-        let best_bid = self.engine.best_bid();
-        let best_ask = self.engine.best_ask();
-        let mid_price = self.engine.mid_price();
-        
-        // Calculate target prices based on current market and inventory
-        let inventory_adjustment = self.metrics.inventory as f64 * self.market_maker_config.inventory_skew;
-        
-        let (target_bid, target_ask) = if let Some(mid) = mid_price {
-            let mid_ticks = price_utils::from_f64(mid);
-            let half_spread = self.market_maker_config.target_spread / 2;
-            
-            // Adjust prices based on inventory (positive inventory pushes prices down)
-            let adjustment_ticks = price_utils::from_f64(inventory_adjustment);
-            
-            let bid = mid_ticks.saturating_sub(half_spread).saturating_sub(adjustment_ticks);
-            let ask = mid_ticks.saturating_add(half_spread).saturating_sub(adjustment_ticks);
-            
-            (bid, ask)
-        } else {
-            // No market exists, create initial market around a base price
-            let base_price = price_utils::from_f64(100.0);  // $100 base price
-            let half_spread = self.market_maker_config.target_spread / 2;
-            
-            (base_price - half_spread, base_price + half_spread)
+    /// Enable periodic inventory hedging
+    pub fn with_hedger(mut self, config: HedgeConfig) -> Self {
+        self.hedger = Some(Hedger::new(config).with_contract(self.contract));
+        self
+    }
+
+    /// Account for fills as this contract
+    ///
+    /// Inverse contracts keep cash, PnL and margin in base units rather than
+    /// quote ticks, for the simulator, its agents, the hedger and paper
+    /// accounts alike.
+    pub fn with_contract(mut self, contract: ContractSpec) -> Self {
+        self.contract = contract;
+        self.paper.set_contract(contract);
+        self.hedger = self.hedger.take().map(|hedger| hedger.with_contract(contract));
+        self
+    }
+
+    /// Get the contract fills are accounted as
+    pub fn contract(&self) -> &ContractSpec {
+        &self.contract
+    }
+
+    /// Settle funding between long and short positions on a schedule
+    ///
+    /// The simulator's own metrics, each agent's and every paper account pay
+    /// or receive funding on their position at each settlement.
+    pub fn with_funding(mut self, config: FundingConfig) -> Self {
+        self.funding = Some(FundingSchedule::new(config));
+        self
+    }
+
+    /// Get the funding schedule if enabled
+    pub fn funding(&self) -> Option<&FundingSchedule> {
+        self.funding.as_ref()
+    }
+
+    /// Score market makers against quoting obligations each session
+    ///
+    /// The synthetic market-making flow and every configured agent in the
+    /// market-maker role is sampled after each step.
+    pub fn with_obligations(mut self, config: ObligationConfig) -> Self {
+        self.obligations = Some(ObligationMonitor::new(config));
+        self
+    }
+
+    /// Get the obligation monitor if enabled
+    pub fn obligations(&self) -> Option<&ObligationMonitor> {
+        self.obligations.as_ref()
+    }
+
+    /// Settle trades a number of cycles after they trade and reconcile
+    /// each role's settled holdings against what it is owed
+    ///
+    /// Reconciliation breaks are logged and, while activity is collected,
+    /// added to the timeline.
+    pub fn with_settlement(mut self, config: SettlementConfig) -> Self {
+        let seed = self.rng.gen();
+        self.settlement = Some(SettlementLedger::new(config, seed));
+        self
+    }
+
+    /// Get the settlement ledger if enabled
+    pub fn settlement(&self) -> Option<&SettlementLedger> {
+        self.settlement.as_ref()
+    }
+
+    /// Band synthetic orders around a reference price taken from the
+    /// replayed events while running in hybrid mode
+    ///
+    /// Limit orders priced through the band are refused and market orders
+    /// are cut to the depth inside it, so synthetic flow cannot drag the book
+    /// away from the historical market. Historical orders are never banded.
+    pub fn with_price_bands(mut self, config: PriceBandConfig) -> Self {
+        self.price_bands = Some(PriceBands::new(config));
+        self
+    }
+
+    /// Get the price bands if enabled
+    pub fn price_bands(&self) -> Option<&PriceBands> {
+        self.price_bands.as_ref()
+    }
+
+    /// Each market maker's own best quotes
+    fn maker_quotes(&self) -> BTreeMap<Maker, MakerQuote> {
+        let mut quotes: BTreeMap<Maker, MakerQuote> = BTreeMap::new();
+        for order in self.engine.open_orders() {
+            let Some(resting) = self.resting_agents.get(&order.id).filter(|resting| resting.agent == Agent::MarketMaker) else {
+                continue;
+            };
+            let Some(price) = order.price() else {
+                continue;
+            };
+            let quote = quotes.entry(resting.owner.map_or(Maker::Synthetic, Maker::Agent)).or_default();
+            match order.side {
+                Side::Buy => quote.add_bid(price, order.qty),
+                Side::Sell => quote.add_ask(price, order.qty),
+            }
+        }
+        quotes
+    }
+
+    /// Delay each agent order by a decision latency plus the network
+    /// latency, and account for what the delay cost its fills
+    pub fn with_latency(mut self, config: LatencyConfig) -> Self {
+        self.latency = Some(config);
+        self
+    }
+
+    /// Scale synthetic order generation down when steps approach their
+    /// interval or calls queue up, and back up once there is headroom
+    pub fn with_throttle(mut self, config: ThrottleConfig) -> Self {
+        self.throttle = Some(FlowThrottle::new(config));
+        self
+    }
+
+    /// Share of the full synthetic order rate being generated
+    pub fn throttle_rate(&self) -> f64 {
+        self.throttle.as_ref().map_or(1.0, FlowThrottle::rate)
+    }
+
+    /// Report how long the last step took against its interval and how much
+    /// work is waiting, adjusting the synthetic order rate if throttling
+    pub fn observe_load(&mut self, step: Duration, interval: Duration, queued: usize) {
+        let Some(adjustment) = self.throttle.as_mut().and_then(|throttle| throttle.observe(step, interval, queued)) else {
+            return;
         };
-        
-        // Check if we should place orders (based on probability and inventory limits)
-        let should_place_bid = self.rng.gen::<f64>() < self.market_maker_config.mm_probability
-            && self.metrics.inventory < self.market_maker_config.max_inventory
-            && (best_bid.is_none() || best_bid.unwrap() < target_bid);
-            
-        let should_place_ask = self.rng.gen::<f64>() < self.market_maker_config.mm_probability
-            && self.metrics.inventory > -self.market_maker_config.max_inventory
-            && (best_ask.is_none() || best_ask.unwrap() > target_ask);
-        
-        // Generate bid order
-        if should_place_bid && target_bid > 0 {
-            let order = Order::new_limit(
-                self.next_order_id(),
-                Side::Buy,
-                self.market_maker_config.order_size,
-                target_bid,
-                self.current_time,
-            );
-            orders.push(order);
+        let message = adjustment.to_string();
+        if adjustment.is_backoff() {
+            tracing::warn!("{}", message);
+        } else {
+            tracing::info!("{}", message);
         }
-        
-        // Generate ask order
-        if should_place_ask && target_ask > 0 {
-            let order = Order::new_limit(
-                self.next_order_id(),
-                Side::Sell,
-                self.market_maker_config.order_size,
-                target_ask,
-                self.current_time,
-            );
-            orders.push(order);
+        if let Some(activity) = self.activity.as_mut() {
+            activity.push(Activity::new(ActivityKind::Throttle, message).at(self.current_time));
         }
-        
-        orders
     }
 
-    /// Generate a random market taker order
-    fn generate_market_taker_order(&mut self) -> Option<Order> {
-        // Determine order side randomly
-        let side = if self.rng.gen::<bool>() { Side::Buy } else { Side::Sell };
-        
-        // Generate order size
-        let qty = self.rng.gen_range(
-            self.order_gen_config.min_order_size..=self.order_gen_config.max_order_size
-        );
-        
-        // Decide between market and limit order
-        let order = if self.rng.gen::<f64>() < self.order_gen_config.market_order_prob {
-            // Market order
-            Order::new_market(self.next_order_id(), side, qty, self.current_time)
-        } else {
-            // Limit order - price based on current market with some randomness
-            let price = self.generate_limit_order_price(side)?;
-            Order::new_limit(self.next_order_id(), side, qty, price, self.current_time)
-        };
-        
-        Some(order)
+    /// Queue orders instead of matching them while the market status is
+    /// `PreMarket` or `Auction`, publishing the auction imbalance as they build
+    pub fn with_auction(mut self, config: AuctionConfig) -> Self {
+        self.pre_open = Some(PreOpenQueue::new(config));
+        self
     }
 
-    /// Generate a price for a limit order based on current market
-    fn generate_limit_order_price(&mut self, side: Side) -> Option<Price> {
-        let mid_price = self.engine.mid_price()?;
-        let mid_ticks = price_utils::from_f64(mid_price);
-        
-        // Generate price within range of mid-price
-        let range_ticks = price_utils::from_f64(mid_price * self.order_gen_config.price_range_fraction);
-        let price_offset = self.rng.gen_range(0..=range_ticks);
-        
-        let price = match side {
-            Side::Buy => {
-                // Buy orders typically below mid-price
-                if self.rng.gen::<bool>() {
-                    mid_ticks.saturating_sub(price_offset)  // Below mid
-                } else {
-                    mid_ticks.saturating_add(price_offset)  // Above mid (aggressive)
-                }
-            }
-            Side::Sell => {
-                // Sell orders typically above mid-price
-                if self.rng.gen::<bool>() {
-                    mid_ticks.saturating_add(price_offset)  // Above mid
-                } else {
-                    mid_ticks.saturating_sub(price_offset)  // Below mid (aggressive)
-                }
-            }
-        };
-        
-        if price > 0 { Some(price) } else { None }
+    /// Follow the data source's quotes with a ladder of liquidity around the
+    /// external bid and ask, for pseudo-live runs tracking a real market
+    pub fn with_shadow_book(mut self, config: ShadowConfig) -> Self {
+        self.shadow = Some(ShadowBook::new(config));
+        self
     }
 
-    /// Process a market event from data source
-    fn process_market_event(&mut self, event: MarketEvent) -> EngineResult<Vec<Trade>> {
-        match event {
-            MarketEvent::OrderPlacement(order) => {
-                match self.engine.place(order) {
-                    Ok(trades) => Ok(trades),
-                    Err(e) => {
-                        // Log the error but continue simulation
-                        tracing::warn!("Order placement failed: {}", e);
-                        Ok(Vec::new())
-                    }
-                }
-            }
-            MarketEvent::OrderCancellation { order_id, .. } => {
-                match self.engine.cancel(order_id) {
-                    Ok(_) => Ok(Vec::new()),
-                    Err(_) => Ok(Vec::new()), // Ignore cancellation errors
-                }
+    /// Ladder following the external quotes, if enabled
+    pub fn shadow_book(&self) -> Option<&ShadowBook> {
+        self.shadow.as_ref()
+    }
+
+    /// Mirror every engine command to a second book under the settings
+    /// `config` pins, recording where the two diverge; the primary engine
+    /// still serves everything
+    ///
+    /// `book` is the empty book to shadow with, of the queue discipline
+    /// `config` names or else the primary's. Set it up before the engine
+    /// settings so the shadow book also takes the ones it does not pin.
+    pub fn with_ab_shadow(mut self, config: AbShadowConfig, book: OrderBook<DynLevel>) -> Self {
+        self.ab_shadow = Some(AbShadow::new(config, book, self.engine.open_orders()));
+        // The shadow book takes the clock with the next order
+        self.engine_clock = None;
+        self
+    }
+
+    /// How the A/B shadow book compares with the primary engine, if enabled
+    pub fn ab_report(&self) -> Option<AbReport> {
+        let shadow = self.ab_shadow.as_ref()?;
+        Some(shadow.report(&self.engine.depth_snapshot()))
+    }
+
+    /// The engine, with every command also sent to the A/B shadow book
+    fn mirrored(&mut self) -> MirroredEngine<'_, E> {
+        MirroredEngine {
+            engine: &mut self.engine,
+            shadow: self.ab_shadow.as_mut(),
+            orders: self.order_feed.as_mut(),
+            profile: self.profile.as_mut(),
+        }
+    }
+
+    /// Replace the shadow ladder with one around a new external quote,
+    /// returning any trades as it crosses local orders
+    fn reanchor_shadow(&mut self, bid: Price, ask: Price, bid_qty: Option<Qty>, ask_qty: Option<Qty>) -> Vec<Trade> {
+        let Some(shadow) = self.shadow.as_mut().filter(|shadow| shadow.should_reanchor(bid, ask)) else {
+            return Vec::new();
+        };
+        let ladder = shadow.ladder(bid, ask, bid_qty, ask_qty);
+        for order_id in shadow.reanchor(bid, ask) {
+            // Levels that have traded away are already gone
+            let _ = self.mirrored().withdraw(order_id);
+        }
+        
+        let mut trades = Vec::new();
+        for (side, price, qty) in ladder {
+            let qty = self.lot_size.round_down(qty);
+            if qty == 0 {
+                continue;
             }
-            MarketEvent::Trade { qty, side, .. } => {
-                // Convert trade event to synthetic order that will execute
-                let order = Order::new_market(self.next_order_id(), side, qty, self.current_time);
-                match self.engine.place(order) {
-                    Ok(trades) => Ok(trades),
-                    Err(e) => {
-                        // Log the error but continue simulation
-                        tracing::warn!("Market order failed: {}", e);
-                        Ok(Vec::new())
+            let order_id = order_ids::derived(self.next_order_id());
+            let order = Order::new_limit(order_id, side, qty, price, self.current_time);
+            match self.place_as(Agent::Historical, order) {
+                Ok(fills) => {
+                    if fills.iter().map(|trade| trade.qty).sum::<Qty>() < qty {
+                        if let Some(shadow) = self.shadow.as_mut() {
+                            shadow.track(order_id);
+                        }
                     }
+                    trades.extend(fills);
                 }
-            }
-            _ => {
-                // Other events (quotes, status changes) don't directly affect the order book
-                Ok(Vec::new())
+                Err(e) => tracing::warn!("Shadow order at {} rejected: {}", price, e),
             }
         }
+        trades
     }
 
-    /// Update metrics after trade execution
-    fn update_metrics(&mut self, trades: &[Trade], taker_side: Side) {
-        for trade in trades {
-            self.metrics.update_trade(taker_side, trade.qty, trade.price);
+    /// Latest published auction imbalance, while the market is pre-open
+    pub fn auction_imbalance(&self) -> Option<&AuctionImbalance> {
+        self.pre_open.as_ref().and_then(PreOpenQueue::latest)
+    }
+
+    /// Number of orders waiting for the open
+    pub fn pre_open_orders(&self) -> usize {
+        self.pre_open.as_ref().map_or(0, PreOpenQueue::len)
+    }
+
+    /// Change the market status as a data source's status event would,
+    /// returning the trades of any orders released at the open
+    pub fn set_market_status(&mut self, status: MarketStatusType, message: Option<&str>) -> Vec<Trade> {
+        let trades = self.change_status(status, self.current_time, message);
+        if !trades.is_empty() {
+            self.update_spread_history();
         }
-        
-        // Calculate PnL using current mid-price
-        if let Some(mid_price) = self.engine.mid_price() {
-            let mid_price_ticks = price_utils::from_f64(mid_price);
-            self.metrics.calculate_pnl(Some(mid_price_ticks));
+        trades
+    }
+
+    /// Record a status change, uncrossing the pre-open queue once the market
+    /// leaves its pre-open state and entering what is left into the book in
+    /// arrival order
+    fn change_status(&mut self, status: MarketStatusType, timestamp: u128, message: Option<&str>) -> Vec<Trade> {
+        self.record_status(status, timestamp, message);
+        let reference = self.trade_tape.back().map(|trade| trade.price)
+            .or(self.engine.quote_mid().map(price_utils::from_f64));
+        let uncross = match self.pre_open.as_mut() {
+            Some(queue) => queue.set_status(status, self.current_time, reference),
+            None => return Vec::new(),
+        };
+        if !uncross.fills.is_empty() || !uncross.released.is_empty() {
+            tracing::info!("Opening uncross: {} trades, releasing {} pre-open orders into the book",
+                           uncross.fills.len(), uncross.released.len());
+        }
+
+        // The uncross prices every pairing at once, so the mid before it
+        // stands for the mid each fill traded against
+        let pre_trade_mid = self.engine.quote_mid().map(price_utils::from_f64);
+        let mut trades = Vec::new();
+        for fill in uncross.fills {
+            let ((agent, owner), (maker, maker_owner)) = (fill.taker, fill.maker);
+            // The maker never rested, so it stands in the book's lookup just
+            // long enough for its fill to be attributed
+            if !order_ids::is_historical(fill.trade.maker_id) {
+                self.resting_agents.insert(fill.trade.maker_id, RestingOrder {
+                    agent: maker,
+                    owner: maker_owner,
+                    remaining: fill.trade.qty,
+                    touched_at: self.current_time,
+                });
+            }
+            self.account_fills(agent, owner, fill.trade.aggressor, std::slice::from_ref(&fill.trade), pre_trade_mid);
+            trades.push(fill.trade);
         }
+        for (order, (agent, owner)) in uncross.released {
+            let order_id = order.id;
+            match self.place_for(agent, owner, order) {
+                Ok(fills) => trades.extend(fills),
+                Err(e) => tracing::warn!("Pre-open order {} rejected at the open: {}", order_id, e),
+            }
+        }
+        trades
     }
 
-    /// Update spread history
-    fn update_spread_history(&mut self) {
-        if let Some(spread) = self.engine.spread() {
-            self.recent_spreads.push((self.current_time, spread));
+    /// Whether this step generates synthetic orders
+    ///
+    /// A throttled simulator skips whole steps of synthetic flow at random,
+    /// drawing from the seeded generator only while throttled so unthrottled
+    /// runs stay reproducible.
+    fn synthetic_flow_due(&mut self) -> bool {
+        let rate = self.throttle_rate();
+        rate >= 1.0 || self.rng.gen::<f64>() < rate
+    }
+
+    /// Latency and its cost summed over all agents, if latency accounting is
+    /// enabled
+    pub fn latency_cost(&self) -> Option<LatencyCost> {
+        self.latency.as_ref()?;
+        let mut total = LatencyCost::default();
+        for slot in self.agents.values() {
+            total.merge(&slot.latency);
         }
+        Some(total)
     }
 
-    /// Simulate network latency for an operation
-    fn simulate_network_latency(&mut self) {
-        let latency_ns = self.net.simulate_latency(&mut self.rng);
-        
-        // Simulate the delay by advancing current time
-        self.current_time += latency_ns as u128;
-        
-        // In a real implementation, this might involve actual delays or queuing
+    /// Cancel synthetic orders that rest untouched past their TTL
+    pub fn with_liquidity_ttl(mut self, config: LiquidityTtlConfig) -> Self {
+        self.liquidity_ttl = config;
+        self
     }
 
-    /// Run one simulation step
-    pub fn step(&mut self) -> EngineResult<Vec<Trade>> {
-        use crate::logging::{log_engine_error, log_data_ingestion};
-        
-        let step_start = std::time::Instant::now();
-        let mut all_trades = Vec::new();
-        let mut orders_processed = 0;
-        let mut errors_encountered = 0;
-        
-        // Advance simulation time
-        let time_advance = self.rng.gen_range(
-            self.order_gen_config.mean_order_interval_ns / 2
-            ..=self.order_gen_config.mean_order_interval_ns * 2
-        );
-        self.current_time += time_advance as u128;
-        
-        match self.mode {
-            SimulationMode::Historical => {
-                // Process events from data source
+    /// Keep order sources in one STP group from trading with each other
+    pub fn with_self_match(mut self, config: &SelfMatchConfig) -> Self {
+        self.apply_engine_command(EngineCommand::SetStpMode(config.mode));
+        self.self_match = Some(SelfMatchGroups::new(config));
+        self
+    }
+
+    /// Bound the engine's depth, pruning or rejecting orders far from the touch
+    pub fn with_depth_limit(mut self, limit: DepthLimit) -> Self {
+        self.depth_limit = limit;
+        self.apply_engine_command(EngineCommand::SetDepthLimit(limit));
+        self
+    }
+
+    /// Choose how the engine handles orders that would lock or cross the book,
+    /// which the hybrid interaction's match filter can otherwise allow
+    pub fn with_crossed_book_policy(mut self, policy: CrossedBookPolicy) -> Self {
+        self.apply_engine_command(EngineCommand::SetCrossedBookPolicy(policy));
+        self
+    }
+
+    /// Choose whether trades are stamped with the taker order's time or the
+    /// host clock
+    pub fn with_trade_timestamps(mut self, timestamps: TradeTimestamps) -> Self {
+        self.trade_timestamps = timestamps;
+        self.apply_engine_command(EngineCommand::SetTradeTimestamps(timestamps));
+        self
+    }
+
+    /// Forbid wall-clock reads inside the engine, so a run that would
+    /// depend on the host's time panics where it reads it
+    ///
+    /// The engine's clock starts at the simulation's, so it can be read
+    /// before the first step.
+    pub fn with_wall_clock_isolation(mut self, isolated: bool) -> Self {
+        if isolated {
+            self.set_engine_clock(self.current_time);
+        }
+        self.apply_engine_command(EngineCommand::SetWallClockIsolation(isolated));
+        self
+    }
+
+    /// Timestamp to give a recorded order on its way to the engine
+    ///
+    /// Recorded times are kept, so the trades they take carry them, unless
+    /// the engine stamps trades from the wall clock and so checks order
+    /// timestamps against it.
+    fn engine_ts(&self, recorded: u128) -> u128 {
+        match self.trade_timestamps {
+            TradeTimestamps::TakerOrder => recorded,
+            TradeTimestamps::WallClock => now_ns(),
+        }
+    }
+
+    /// Set how synthetic orders interact with historical liquidity in hybrid mode
+    pub fn with_hybrid_interaction(mut self, interaction: HybridInteraction) -> Self {
+        self.hybrid_interaction = interaction;
+        self
+    }
+
+    /// Change the hybrid interaction policy on a running simulator
+    pub fn set_hybrid_interaction(&mut self, interaction: HybridInteraction) {
+        self.hybrid_interaction = interaction;
+    }
+
+    /// Get the hybrid interaction policy
+    pub fn hybrid_interaction(&self) -> HybridInteraction {
+        self.hybrid_interaction
+    }
+
+    /// Interaction policy restricting matching, if any; only hybrid mode
+    /// mixes origins, so only it restricts matching
+    fn match_interaction(&self) -> Option<HybridInteraction> {
+        match self.mode {
+            SimulationMode::Hybrid => Some(self.hybrid_interaction),
+            _ => None,
+        }
+    }
+
+    /// Set what happens to data cancels and modifications of orders the book
+    /// never saw
+    pub fn with_unmatched_order_policy(mut self, policy: UnmatchedOrderPolicy) -> Self {
+        self.unmatched_orders = UnmatchedOrders::new(policy);
+        self
+    }
+
+    /// Inventory and PnL history, oldest first
+    pub fn metrics_history(&self) -> Vec<MetricsPoint> {
+        self.metrics_history.to_vec()
+    }
+
+    /// Bound the trade tape, metrics history and paper fill history, and set
+    /// how every history evicts once full
+    pub fn with_memory_config(mut self, config: &MemoryConfig) -> Self {
+        self.trade_tape.set_capacity(config.trade_tape);
+        self.trade_tape.set_policy(config.eviction);
+        self.metrics_history.set_capacity(config.metrics_history);
+        self.metrics_history.set_policy(config.eviction);
+        self.spread_history.set_policy(config.eviction);
+        self.paper.set_history(config.closed_orders, config.eviction);
+        self
+    }
+
+    /// Keep at most `points` raw entries of spread history
+    pub fn with_spread_history(mut self, points: usize) -> Self {
+        self.spread_history.set_recent_capacity(points);
+        self
+    }
+
+    /// Recent trades, oldest first
+    pub fn recent_trades(&self) -> Vec<Trade> {
+        self.trade_tape.to_vec()
+    }
+
+    /// Recent trades grouped by the order that took liquidity, oldest first
+    pub fn recent_executions(&self) -> Vec<ExecutionSummary> {
+        ExecutionSummary::aggregate(&self.trade_tape.to_vec())
+    }
+
+    /// Occupancy of each bounded history
+    pub fn history_usage(&self) -> BTreeMap<&'static str, HistoryUsage> {
+        let mut usage = BTreeMap::from([
+            ("trade_tape", self.trade_tape.usage()),
+            ("spread_history", self.spread_history.usage()),
+            ("metrics_history", self.metrics_history.usage()),
+        ]);
+        usage.extend(self.paper.history_usage());
+        usage
+    }
+
+    /// Drop the oldest half of every history to relieve memory pressure,
+    /// returning how many entries were dropped
+    pub fn shed_history(&mut self) -> usize {
+        self.trade_tape.evict_oldest(self.trade_tape.len() / 2)
+            + self.spread_history.evict_oldest(self.spread_history.len() / 2)
+            + self.metrics_history.evict_oldest(self.metrics_history.len() / 2)
+            + self.paper.shed_history()
+    }
+
+    /// Shrink histories to their budgets, forget orders that are no longer
+    /// resting and release spare capacity, reporting what each structure
+    /// gave up
+    ///
+    /// Long runs call this periodically so memory stays flat: buffers keep
+    /// capacity they no longer need after a budget is lowered or history is
+    /// shed, and the simulator's own order maps keep orders that filled away.
+    pub fn compact(&mut self) -> BTreeMap<&'static str, Compaction> {
+        let order_index = self.mirrored().compact_book().unwrap_or_else(|e| {
+            tracing::warn!("Failed to compact the engine: {}", e);
+            Compaction::default()
+        });
+        fn forget<V>(orders: &mut HashMap<OrderId, V>, resting: &HashSet<OrderId>) -> Compaction {
+            let before = orders.len();
+            orders.retain(|order_id, _| resting.contains(order_id));
+            shrink_map(orders).with_entries(before - orders.len())
+        }
+        let resting: HashSet<OrderId> = self.engine.open_orders().iter().map(|order| order.id).collect();
+        let resting_agents = forget(&mut self.resting_agents, &resting);
+        let replay_orders = forget(&mut self.replay.order_locations, &resting);
+        let ab_shadow = self.ab_shadow.as_mut().map(AbShadow::compact).unwrap_or_default();
+        BTreeMap::from([
+            ("order_index", order_index),
+            ("ab_shadow", ab_shadow),
+            ("resting_agents", resting_agents),
+            ("replay_orders", replay_orders),
+            ("trade_tape", self.trade_tape.compact()),
+            ("spread_history", self.spread_history.compact()),
+            ("metrics_history", self.metrics_history.compact()),
+            ("paper", self.paper.compact()),
+            ("published_books", shrink_deque(&mut self.published, 0)),
+            ("in_flight", shrink_vec(&mut self.in_flight)),
+        ])
+    }
+
+    /// Synthetic orders cancelled for outliving their TTL
+    pub fn expired_orders(&self) -> u64 {
+        self.expired_orders
+    }
+
+    /// Orders the engine pruned past its depth limit
+    pub fn pruned_orders(&self) -> u64 {
+        self.pruned_orders
+    }
+
+    /// Orders self-match prevention cancelled, incoming or resting
+    pub fn self_matches(&self) -> u64 {
+        self.self_matches
+    }
+
+    /// Get fills split by synthetic vs historical origin
+    pub fn fill_accounting(&self) -> &FillAccounting {
+        &self.fills
+    }
+
+    /// Inventory, cash and maker/taker PnL for each agent that has traded
+    pub fn agent_metrics(&self) -> &BTreeMap<Agent, Metrics> {
+        &self.agent_metrics
+    }
+
+    /// Build snapshots of only the best `max_levels` levels a side, and
+    /// without order ages, while the server sheds load; `None` restores
+    /// complete snapshots
+    pub fn set_shed_levels(&mut self, max_levels: Option<usize>) {
+        self.shed_levels = max_levels;
+    }
+
+    /// Set the analytics configuration
+    pub fn with_analytics_config(mut self, config: AnalyticsConfig) -> Self {
+        self.spread_history.set_tiers(&config.spread_tiers);
+        self.report_order_ages = config.order_ages;
+        self.volume_profiles = VolumeProfiles::new(config).with_lot_size(self.lot_size);
+        self
+    }
+
+    /// Trade in whole lots: the engine applies the lot-size rule to
+    /// non-historical orders and generated order sizes are whole lots
+    pub fn with_lot_size(mut self, lot_size: LotSize) -> Self {
+        self.lot_size = lot_size;
+        self.apply_engine_command(EngineCommand::SetLotSize(lot_size));
+        self.volume_profiles.set_lot_size(lot_size);
+        self
+    }
+
+    /// Get the lot-size rule
+    pub fn lot_size(&self) -> LotSize {
+        self.lot_size
+    }
+
+    /// Fit a generated size to whole lots, never going below one lot
+    fn whole_lots(&self, qty: Qty) -> Qty {
+        self.lot_size.round_down(qty).max(self.lot_size.size)
+    }
+
+    /// Get traded volume by price over the run and per session
+    pub fn volume_profiles(&self) -> &VolumeProfiles {
+        &self.volume_profiles
+    }
+
+    /// Enforce margin and buying power on paper accounts
+    pub fn with_margin(mut self, config: MarginConfig) -> Self {
+        self.paper.set_margin(Some(config));
+        self
+    }
+
+    /// Limit the notional each paper account submits per rolling window
+    pub fn with_velocity(mut self, config: VelocityConfig) -> Self {
+        self.paper.set_velocity(Some(config));
+        self
+    }
+
+    /// Get the paper trading layer
+    pub fn paper(&self) -> &PaperTrader {
+        &self.paper
+    }
+
+    /// Margin figures for a paper account at the book's mid
+    pub fn paper_margin(&self, account: &str) -> Option<MarginStatus> {
+        let mark = self.engine.quote_mid().map(price_utils::from_f64)?;
+        self.paper.margin_status(account, mark)
+    }
+
+    /// Submit a paper order against the current book
+    pub fn submit_paper_order(
+        &mut self,
+        account: &str,
+        side: Side,
+        qty: Qty,
+        price: Option<Price>,
+    ) -> EngineResult<PaperSubmission> {
+        if self.gateway.is_down() {
+            return Err(EngineError::GatewayUnavailable);
+        }
+        self.gateway.admit(self.current_time).map_err(|retry_after_ms| EngineError::Throttled { retry_after_ms })?;
+        let book = self.engine.depth_snapshot();
+        self.paper.submit(account, side, qty, price, &book, self.current_time)
+    }
+
+    /// Mark-to-market equity of a paper account at the book's mid
+    pub fn paper_equity(&self, account: &str) -> Option<i64> {
+        let mark = self.engine.quote_mid().map(price_utils::from_f64);
+        self.paper.account(account).map(|summary| summary.equity(&self.contract, mark))
+    }
+
+    /// Cancel a resting paper order
+    pub fn cancel_paper_order(&mut self, order_id: OrderId) -> EngineResult<()> {
+        self.paper.cancel(order_id).map(|_| ())
+    }
+
+    /// Get the inventory hedger if enabled
+    pub fn hedger(&self) -> Option<&Hedger> {
+        self.hedger.as_ref()
+    }
+
+    /// Set the external reference price used by the hedger
+    pub fn set_hedge_reference_price(&mut self, price: Price) {
+        if let Some(ref mut hedger) = self.hedger {
+            hedger.set_external_price(price);
+        }
+    }
+
+    /// Set a data source for historical replay
+    pub fn with_data_source(mut self, data_source: Box<dyn DataSource>) -> Self {
+        self.data_source = Some(data_source);
+        self.mode = SimulationMode::Historical;
+        self.last_feed_event = Some(Instant::now());
+        self
+    }
+
+    /// Attach a data source for faithful market-data replay
+    /// 
+    /// The replay clock paces playback, so the source's own real-time
+    /// throttling is disabled.
+    pub fn with_replay_source(mut self, data_source: Box<dyn DataSource>) -> Self {
+        // Replay never rejects a data source
+        let _ = self.attach_data_source(data_source, SimulationMode::Replay);
+        self
+    }
+
+    /// Attach or replace the data source on a running simulator
+    /// 
+    /// Switching into replay mode starts from an empty book so the replayed
+    /// book is reconstructed faithfully. Returns the previous data source.
+    pub fn attach_data_source(
+        &mut self,
+        mut data_source: Box<dyn DataSource>,
+        mode: SimulationMode,
+    ) -> EngineResult<Option<Box<dyn DataSource>>> {
+        if mode == SimulationMode::Synthetic {
+            return Err(EngineError::reject("Synthetic mode does not use a data source"));
+        }
+        
+        if mode == SimulationMode::Replay {
+            let _ = data_source.set_paused(true);
+            self.apply_engine_command(EngineCommand::Clear);
+            self.spread_history.clear();
+        }
+        if let Some(ref mut shadow) = self.shadow {
+            shadow.reset();
+        }
+        if let Some(ref mut bands) = self.price_bands {
+            bands.rewind();
+        }
+        if let Some(ref mut queue) = self.pre_open {
+            queue.reset();
+        }
+        
+        self.replay = ReplayTransport {
+            speed: self.replay.speed,
+            ..ReplayTransport::default()
+        };
+        self.unmatched_orders.reset();
+        self.mode = mode;
+        self.last_feed_event = Some(Instant::now());
+        Ok(self.data_source.replace(data_source))
+    }
+
+    /// Detach the current data source and fall back to synthetic order flow
+    pub fn detach_data_source(&mut self) -> Option<Box<dyn DataSource>> {
+        self.mode = SimulationMode::Synthetic;
+        self.replay = ReplayTransport {
+            speed: self.replay.speed,
+            ..ReplayTransport::default()
+        };
+        self.last_feed_event = None;
+        self.data_source.take()
+    }
+
+    /// Get metadata of the attached data source, if any, with the
+    /// references to unknown orders found in its data so far
+    pub fn data_source_metadata(&self) -> Option<DataSourceMetadata> {
+        self.data_source.as_ref().map(|data_source| DataSourceMetadata {
+            unmatched_orders: self.unmatched_orders.report().clone(),
+            ..data_source.metadata()
+        })
+    }
+
+    /// Get the current simulation mode
+    pub fn mode(&self) -> SimulationMode {
+        self.mode
+    }
+
+    /// Check whether a data source is attached
+    pub fn has_data_source(&self) -> bool {
+        self.data_source.is_some()
+    }
+
+    /// Time since the data source last produced an event, heartbeats included
+    /// 
+    /// `None` while no events are expected: without a data source, once it
+    /// has finished, or while replay is paused.
+    pub fn feed_silence(&self) -> Option<std::time::Duration> {
+        let data_source = self.data_source.as_ref()?;
+        if data_source.is_finished() || (self.mode == SimulationMode::Replay && self.replay.paused) {
+            return None;
+        }
+        self.last_feed_event.map(|last| last.elapsed())
+    }
+
+    /// Set simulation mode
+    pub fn set_mode(&mut self, mode: SimulationMode) {
+        self.mode = mode;
+    }
+
+    /// Get the next order ID
+    fn next_order_id(&mut self) -> OrderId {
+        let id = self.next_order_id;
+        self.next_order_id += 1;
+        id
+    }
+
+    /// Take an order id for an order placed from outside, such as a client
+    /// session, which no other order of this simulator is given, even after
+    /// a reset
+    pub fn reserve_order_id(&mut self) -> OrderId {
+        self.session_orders += 1;
+        order_ids::session(self.session_orders)
+    }
+
+    /// Generate a realistic market making order pair
+    fn generate_market_making_orders(&mut self) -> Vec<Order> {
+        let mut orders = Vec::new();
+        
+        // Get current market state
+        let (best_bid, best_ask) = self.engine.best_bid_ask();
+        let mid_price = self.engine.quote_mid();
+        
+        // Calculate target prices based on current market and inventory
+        let inventory_adjustment = self.metrics.inventory as f64 * self.market_maker_config.inventory_skew;
+        
+        // Widen and shrink quotes as inventory passes band thresholds
+        let inventory = self.metrics.inventory;
+        let band = self.market_maker_config.inventory_band(inventory);
+        if let Some(left) = self.market_maker_band.enter(band) {
+            tracing::info!("Market maker inventory {} moved from band {} to band {}", inventory, left, band);
+        }
+        let (half_spread, order_size) = self.market_maker_config.quote_shape(inventory);
+        
+        let (target_bid, target_ask) = if let Some(mid) = mid_price {
+            let mid_ticks = price_utils::from_f64(mid);
+            
+            // Adjust prices based on inventory (positive inventory pushes
+            // prices down, negative pushes them up)
+            let adjustment_ticks = price_utils::from_f64(inventory_adjustment.abs()) as i64 * inventory_adjustment.signum() as i64;
+            
+            let bid = mid_ticks.saturating_sub(half_spread).saturating_add_signed(-adjustment_ticks);
+            let ask = mid_ticks.saturating_add(half_spread).saturating_add_signed(-adjustment_ticks);
+            
+            (bid, ask)
+        } else {
+            // No market exists, create initial market around a base price
+            let base_price = price_utils::from_f64(100.0);  // $100 base price
+            
+            (base_price - half_spread, base_price + half_spread)
+        };
+        
+        // Check if we should place orders (based on probability and inventory limits)
+        let should_place_bid = self.rng.gen::<f64>() < self.market_maker_config.mm_probability
+            && self.metrics.inventory < self.market_maker_config.max_inventory
+            && (best_bid.is_none() || best_bid.unwrap() < target_bid);
+            
+        let should_place_ask = self.rng.gen::<f64>() < self.market_maker_config.mm_probability
+            && self.metrics.inventory > -self.market_maker_config.max_inventory
+            && (best_ask.is_none() || best_ask.unwrap() > target_ask);
+        
+        let order_size = self.whole_lots(order_size);
+        
+        // Generate bid order
+        if should_place_bid && target_bid > 0 {
+            let order = Order::new_limit(
+                self.next_order_id(),
+                Side::Buy,
+                order_size,
+                target_bid,
+                self.current_time,
+            );
+            orders.push(order);
+        }
+        
+        // Generate ask order
+        if should_place_ask && target_ask > 0 {
+            let order = Order::new_limit(
+                self.next_order_id(),
+                Side::Sell,
+                order_size,
+                target_ask,
+                self.current_time,
+            );
+            orders.push(order);
+        }
+        
+        orders
+    }
+
+    /// Generate a random market taker order
+    fn generate_market_taker_order(&mut self) -> Option<Order> {
+        // Determine order side randomly
+        let side = if self.rng.gen_bool(self.order_gen_config.buy_probability) { Side::Buy } else { Side::Sell };
+        
+        // Generate order size in whole lots
+        let qty = self.rng.gen_range(
+            self.order_gen_config.min_order_size..=self.order_gen_config.max_order_size
+        );
+        let qty = self.whole_lots(qty);
+        
+        // Decide between market and limit order
+        let order = if self.rng.gen::<f64>() < self.order_gen_config.market_order_prob {
+            // Market order, sized to the slippage the taker will accept
+            let qty = match self.order_gen_config.max_slippage_ticks {
+                Some(budget) => {
+                    let model = CostModel::from_snapshot(&self.engine.depth_snapshot());
+                    self.lot_size.round_down(qty.min(model.max_qty_within(side, budget as f64)))
+                }
+                None => qty,
+            };
+            if qty == 0 {
+                return None;
+            }
+            Order::new_market(self.next_order_id(), side, qty, self.current_time)
+        } else {
+            // Limit order - price based on current market with some randomness
+            let price = self.generate_limit_order_price(side)?;
+            Order::new_limit(self.next_order_id(), side, qty, price, self.current_time)
+        };
+        
+        Some(order)
+    }
+
+    /// Generate a price for a limit order based on current market
+    fn generate_limit_order_price(&mut self, side: Side) -> Option<Price> {
+        let mid_price = self.engine.quote_mid()?;
+        let mid_ticks = price_utils::from_f64(mid_price);
+        
+        // Generate price within range of mid-price
+        let range_ticks = price_utils::from_f64(mid_price * self.order_gen_config.price_range_fraction);
+        let price_offset = self.rng.gen_range(0..=range_ticks);
+        
+        let price = match side {
+            Side::Buy => {
+                // Buy orders typically below mid-price
+                if self.rng.gen::<bool>() {
+                    mid_ticks.saturating_sub(price_offset)  // Below mid
+                } else {
+                    mid_ticks.saturating_add(price_offset)  // Above mid (aggressive)
+                }
+            }
+            Side::Sell => {
+                // Sell orders typically above mid-price
+                if self.rng.gen::<bool>() {
+                    mid_ticks.saturating_add(price_offset)  // Above mid
+                } else {
+                    mid_ticks.saturating_sub(price_offset)  // Below mid (aggressive)
+                }
+            }
+        };
+        
+        if price > 0 { Some(price) } else { None }
+    }
+
+    /// Process a market event from data source
+    fn process_market_event(&mut self, event: MarketEvent) -> EngineResult<Vec<Trade>> {
+        if let Some(bands) = self.price_bands.as_mut() {
+            bands.observe(&event);
+        }
+        match event {
+            MarketEvent::OrderPlacement(mut order) => {
+                // Historical ids live in their own namespace so they cannot
+                // collide with the simulator's own orders in hybrid mode
+                let Some(engine_id) = order_ids::historical(order.id) else {
+                    tracing::warn!("Historical order id {} is outside the taggable range", order.id);
+                    return Ok(Vec::new());
+                };
+                order.id = engine_id;
+                order.ts = self.engine_ts(order.ts);
+                self.unmatched_orders.place(engine_id);
+                
+                match self.place_as(Agent::Historical, order) {
+                    Ok(trades) => Ok(trades),
+                    Err(e) => {
+                        // Log the error but continue simulation
+                        tracing::warn!("Order placement failed: {}", e);
+                        Ok(Vec::new())
+                    }
+                }
+            }
+            MarketEvent::OrderCancellation { order_id, .. } => {
+                let Some(engine_id) = order_ids::historical(order_id) else {
+                    return Ok(Vec::new());
+                };
+                if self.pre_open.as_mut().and_then(|queue| queue.cancel(engine_id)).is_some() {
+                    return Ok(Vec::new());
+                }
+                if self.mirrored().withdraw(engine_id).is_err() {
+                    self.unmatched_orders.cancel(engine_id);
+                }
+                Ok(Vec::new())
+            }
+            MarketEvent::Trade { qty, side, .. } => {
+                // Convert trade event to a market order that will execute; it
+                // stands in for historical flow, so it takes a historical id
+                let order_id = order_ids::derived(self.next_order_id());
+                let order = Order::new_market(order_id, side, qty, self.current_time);
+                match self.place_as(Agent::Historical, order) {
+                    Ok(trades) => Ok(trades),
+                    Err(e) => {
+                        // Log the error but continue simulation
+                        tracing::warn!("Market order failed: {}", e);
+                        Ok(Vec::new())
+                    }
+                }
+            }
+            MarketEvent::MarketStatus { status, timestamp, message } => {
+                Ok(self.change_status(status, timestamp, message.as_deref()))
+            }
+            MarketEvent::Quote { bid: Some(bid), ask: Some(ask), bid_qty, ask_qty, .. }
+            | MarketEvent::BestBidOffer { best_bid: Some(bid), best_ask: Some(ask), bid_qty, ask_qty, .. } => {
+                // Quotes provide the hedger's external mark, and only touch
+                // the book through the shadow ladder
+                self.set_hedge_reference_price((bid + ask) / 2);
+                if let Some(ref mut funding) = self.funding {
+                    funding.set_index_price((bid + ask) / 2);
+                }
+                Ok(self.reanchor_shadow(bid, ask, bid_qty, ask_qty))
+            }
+            _ => {
+                // Other events (one-sided quotes, heartbeats) don't directly affect the order book
+                Ok(Vec::new())
+            }
+        }
+    }
+
+    /// Apply a replayed event to the book without synthesizing any orders
+    /// 
+    /// Trade prints are passed through for the tape but never executed against
+    /// the book; the book is driven solely by order placements, cancellations
+    /// and modifications.
+    fn apply_replay_event(&mut self, event: MarketEvent) -> Vec<Trade> {
+        // Events are applied at their own time, whatever the clock says
+        self.set_engine_clock(event.timestamp());
+        match event {
+            MarketEvent::OrderPlacement(mut order) => {
+                order.ts = self.engine_ts(order.ts);
+                if let OrderType::Limit { price } = order.order_type {
+                    self.replay.order_locations.insert(order.id, (order.side, price));
+                }
+                self.unmatched_orders.place(order.id);
+                
+                let order_id = order.id;
+                self.select_stp_group(Agent::Historical, None);
+                self.mirrored().submit(order).unwrap_or_else(|e| {
+                    tracing::warn!("Replay order {} rejected: {}", order_id, e);
+                    Vec::new()
+                })
+            }
+            MarketEvent::OrderCancellation { order_id, .. } => {
+                self.replay.order_locations.remove(&order_id);
+                if self.mirrored().withdraw(order_id).is_err() {
+                    self.unmatched_orders.cancel(order_id);
+                }
+                Vec::new()
+            }
+            MarketEvent::MarketStatus { status, timestamp, message } => {
+                self.record_status(status, timestamp, message.as_deref());
+                Vec::new()
+            }
+            MarketEvent::OrderModification { order_id, new_qty, new_price, timestamp } => {
+                let Some((side, price)) = self.replay.order_locations.remove(&order_id) else {
+                    return self.rest_placeholder(order_id, new_qty, new_price, timestamp);
+                };
+                let Ok(remaining) = self.mirrored().withdraw(order_id) else {
+                    return self.rest_placeholder(order_id, new_qty, new_price, timestamp);
+                };
+                
+                // Apply the modification as cancel/replace
+                let qty = new_qty.unwrap_or(remaining);
+                let price = new_price.unwrap_or(price);
+                if qty == 0 {
+                    return Vec::new();
+                }
+                
+                self.replay.order_locations.insert(order_id, (side, price));
+                let order = Order::new_limit(order_id, side, qty, price, self.engine_ts(timestamp));
+                self.select_stp_group(Agent::Historical, None);
+                self.mirrored().submit(order).unwrap_or_else(|e| {
+                    tracing::warn!("Replay modification of order {} rejected: {}", order_id, e);
+                    Vec::new()
+                })
+            }
+            MarketEvent::Trade { price, qty, side, timestamp, .. } => {
+                vec![Trade { maker_id: 0, taker_id: 0, price, qty, ts: timestamp, aggressor: side }]
+            }
+            MarketEvent::Quote { bid: Some(bid), ask: Some(ask), .. }
+            | MarketEvent::BestBidOffer { best_bid: Some(bid), best_ask: Some(ask), .. } => {
+                self.set_hedge_reference_price((bid + ask) / 2);
+                Vec::new()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Count a replayed modification of an order the book does not hold, and
+    /// under the placeholder policy rest the order as modified, on the side
+    /// its price most plausibly belongs to
+    fn rest_placeholder(&mut self, order_id: OrderId, qty: Option<Qty>, price: Option<Price>, timestamp: u128) -> Vec<Trade> {
+        if !self.unmatched_orders.modification(order_id) {
+            return Vec::new();
+        }
+        let (Some(qty), Some(price)) = (qty, price) else {
+            return Vec::new();
+        };
+        let (best_bid, best_ask) = self.engine.best_bid_ask();
+        let Some(side) = placeholder_side(price, best_bid, best_ask).filter(|_| qty > 0) else {
+            return Vec::new();
+        };
+        
+        let order = Order::new_limit(order_id, side, qty, price, self.engine_ts(timestamp));
+        self.select_stp_group(Agent::Historical, None);
+        match self.mirrored().submit(order) {
+            Ok(trades) => {
+                self.replay.order_locations.insert(order_id, (side, price));
+                self.unmatched_orders.placeholder(order_id);
+                trades
+            }
+            Err(e) => {
+                tracing::warn!("Placeholder for replay order {} rejected: {}", order_id, e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Take the next replay event, using the read-ahead event if present
+    fn next_replay_event(&mut self) -> EngineResult<Option<IngestedEvent>> {
+        if let Some(pending) = self.replay.pending.take() {
+            return Ok(Some(pending));
+        }
+        
+        let Some(data_source) = self.data_source.as_mut() else {
+            return Ok(None);
+        };
+        
+        let mut read_errors = 0;
+        loop {
+            match data_source.next_event() {
+                Ok(event) => {
+                    if event.is_some() {
+                        self.last_feed_event = Some(Instant::now());
+                    }
+                    // Stamped as read, however long it then waits for the clock
+                    return Ok(event.map(|event| {
+                        let ingest_ns = self.ingest_stamp(&event);
+                        (event, ingest_ns)
+                    }));
+                }
+                Err(e) => {
+                    read_errors += 1;
+                    tracing::warn!("Skipping unreadable replay record: {}", e);
+                    if read_errors >= MAX_REPLAY_READ_ERRORS {
+                        return Err(EngineError::data(format!("Replay data source error: {}", e)));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Apply all events up to and including `target` on the replay clock
+    fn replay_until(&mut self, target: u128) -> EngineResult<Vec<Trade>> {
+        let mut trades = Vec::new();
+        
+        while let Some((event, ingest_ns)) = self.next_replay_event()? {
+            if event.timestamp() > target {
+                self.replay.pending = Some((event, ingest_ns));
+                break;
+            }
+            
+            trades.extend(self.apply_replay_event(event));
+            self.stamp_event(ingest_ns);
+            self.replay.events_applied += 1;
+        }
+        
+        let clock = self.replay.clock.map_or(target, |clock| clock.max(target));
+        self.replay.clock = Some(clock);
+        self.current_time = clock;
+        
+        Ok(trades)
+    }
+
+    /// Advance the replay clock by `advance_ns` of event time
+    /// 
+    /// The clock is anchored at the first event on the first call. Returns the
+    /// executions and trade prints that occurred in the window.
+    pub fn advance_replay(&mut self, advance_ns: u128) -> EngineResult<Vec<Trade>> {
+        if self.replay.clock.is_none() {
+            match self.next_replay_event()? {
+                Some((event, ingest_ns)) => {
+                    self.replay.clock = Some(event.timestamp());
+                    self.replay.pending = Some((event, ingest_ns));
+                }
+                None => return Ok(Vec::new()),
+            }
+        }
+        
+        let target = self.replay.clock.unwrap_or(0).saturating_add(advance_ns);
+        self.replay_until(target)
+    }
+
+    /// Apply a command that only reconfigures the engine, logging a failure
+    fn apply_engine_command(&mut self, command: EngineCommand) {
+        if let Err(e) = self.mirrored().execute(command) {
+            tracing::warn!("Engine command failed: {}", e);
+        }
+    }
+
+    /// Place an order on behalf of an agent, attributing the resulting fills
+    /// to the taking and resting agents
+    fn place_as(&mut self, agent: Agent, order: Order) -> EngineResult<Vec<Trade>> {
+        self.place_for(agent, None, order)
+    }
+
+    /// Place an order for an agent, and for the configured agent in `slot`
+    /// if there is one, tracking that agent's position
+    fn place_for(&mut self, agent: Agent, owner: Option<AgentId>, order: Order) -> EngineResult<Vec<Trade>> {
+        if let Some(queue) = self.pre_open.as_mut().filter(|queue| queue.is_collecting()) {
+            let max_orders = queue.config().max_orders;
+            return match queue.push(order, (agent, owner)) {
+                Ok(()) => Ok(Vec::new()),
+                Err(_) => Err(EngineError::PreOpen { reason: format!("queue is full ({} orders)", max_orders) }),
+            };
+        }
+        let pre_trade_mid = self.engine.quote_mid().map(price_utils::from_f64);
+        let (order_id, side) = (order.id, order.side);
+        let is_limit = matches!(order.order_type, OrderType::Limit { .. });
+        self.select_stp_group(agent, owner);
+        self.set_engine_clock(self.current_time);
+        let trades = match self.mirrored().submit(order) {
+            // Synthetic sources simply lose an order self-match prevention refused
+            Err(EngineError::SelfTrade { order_id }) => {
+                self.self_matches += 1;
+                return match agent {
+                    Agent::Manual => Err(EngineError::SelfTrade { order_id }),
+                    _ => Ok(Vec::new()),
+                };
+            }
+            result => result?,
+        };
+        self.account_fills(agent, owner, side, &trades, pre_trade_mid);
+        
+        // Historical orders are recognised by their id and need no entry. A
+        // book rule may have refused what the fills left, so only an order
+        // the engine holds is tracked
+        let resting = if is_limit && agent != Agent::Historical { self.engine.order_resting_qty(order_id) } else { None };
+        if let Some(remaining) = resting {
+            self.resting_agents.insert(order_id, RestingOrder {
+                agent,
+                owner,
+                remaining,
+                touched_at: self.current_time,
+            });
+        }
+        Ok(trades)
+    }
+    
+    /// Replace the two-sided quote of an agent, and of the configured agent
+    /// in `owner` if there is one, tracking positions as [`Self::place_for`]
+    /// does and updating the metrics with each leg's trades
+    fn quote_for(&mut self, agent: Agent, owner: Option<AgentId>, quote: TwoSidedQuote) -> EngineResult<QuoteOutcome> {
+        if self.pre_open.as_ref().is_some_and(|queue| queue.is_collecting()) {
+            return Err(EngineError::PreOpen { reason: "quotes are not taken".to_string() });
+        }
+        let pre_trade_mid = self.engine.quote_mid().map(price_utils::from_f64);
+        self.select_stp_group(agent, owner);
+        self.set_engine_clock(self.current_time);
+        let outcome = self.mirrored().requote(quote)?;
+        
+        // Record a synthetic quote's pulled legs and then its placed ones, in
+        // the order the engine applied them, so a replay rebuilds the same book
+        let recording = agent != Agent::Manual;
+        for (order_id, _) in &outcome.cancelled {
+            self.resting_agents.remove(order_id);
+            if let Some(events) = self.recorded_events.as_mut().filter(|_| recording) {
+                events.push(MarketEvent::OrderCancellation { order_id: *order_id, timestamp: self.current_time, reason: None });
+            }
+        }
+        for leg in quote.orders().filter(|_| recording) {
+            if !outcome.refused.iter().any(|(order_id, _)| *order_id == leg.id) {
+                let recorded = self.recordable(&leg);
+                self.record_placement(recorded);
+            }
+        }
+        self.self_matches += outcome.refused.iter()
+            .filter(|(_, e)| matches!(e, EngineError::SelfTrade { .. }))
+            .count() as u64;
+        for leg in quote.orders() {
+            let trades: Vec<Trade> = outcome.trades.iter().filter(|trade| trade.taker_id == leg.id).cloned().collect();
+            self.account_fills(agent, owner, leg.side, &trades, pre_trade_mid);
+            if !trades.is_empty() {
+                self.update_metrics();
+            }
+            let filled: Qty = trades.iter().map(|trade| trade.qty).sum();
+            if outcome.resting.contains(&leg.id) {
+                self.resting_agents.insert(leg.id, RestingOrder {
+                    agent,
+                    owner,
+                    remaining: leg.qty.saturating_sub(filled),
+                    touched_at: self.current_time,
+                });
+            }
+        }
+        Ok(outcome)
+    }
+    
+    /// Position held per settlement account: each registered agent's, and
+    /// what is left of its role's once those are taken out
+    fn settlement_positions(&self) -> BTreeMap<Account, i64> {
+        let mut positions: BTreeMap<Account, i64> = self.agent_metrics.iter()
+            .map(|(&role, metrics)| (Account::role(role), metrics.inventory))
+            .collect();
+        for (&id, slot) in &self.agents {
+            let role = slot.agent.role();
+            *positions.entry(Account::role(role)).or_default() -= slot.metrics.inventory;
+            positions.insert(Account::of(role, Some(id)), slot.metrics.inventory);
+        }
+        positions
+    }
+
+    /// Attribute the trades of an incoming order to it and to the resting
+    /// orders it hit
+    fn account_fills(&mut self, agent: Agent, owner: Option<AgentId>, side: Side, trades: &[Trade], pre_trade_mid: Option<Price>) {
+        let contract = self.contract;
+        for trade in trades {
+            let mid = pre_trade_mid.unwrap_or(trade.price);
+            self.agent_metrics.entry(agent).or_default()
+                .record_fill_for(&contract, Liquidity::Taker, side, trade.qty, trade.price, mid);
+            if let Some(slot) = owner.and_then(|id| self.agents.get_mut(&id)) {
+                slot.metrics.record_fill_for(&contract, Liquidity::Taker, side, trade.qty, trade.price, mid);
+            }
+            let (maker, maker_owner) = self.resting_agent(trade.maker_id, trade.qty);
+            self.agent_metrics.entry(maker).or_default()
+                .record_fill_for(&contract, Liquidity::Maker, side.opposite(), trade.qty, trade.price, mid);
+            // The headline metrics are the built-in market maker's book, taking
+            // its fills in the role its order played
+            if (agent, owner) == (Agent::MarketMaker, None) {
+                self.metrics.record_fill_for(&contract, Liquidity::Taker, side, trade.qty, trade.price, mid);
+            }
+            if (maker, maker_owner) == (Agent::MarketMaker, None) {
+                self.metrics.record_fill_for(&contract, Liquidity::Maker, side.opposite(), trade.qty, trade.price, mid);
+            }
+            for (order_id, fill_side) in [(trade.taker_id, side), (trade.maker_id, side.opposite())] {
+                if self.ladder_orders.contains(&order_id) {
+                    self.ladder_position += match fill_side {
+                        Side::Buy => trade.qty as i64,
+                        Side::Sell => -(trade.qty as i64),
+                    };
+                }
+            }
+            if let Some(settlement) = self.settlement.as_mut() {
+                let (taker, maker) = (Account::of(agent, owner), Account::of(maker, maker_owner));
+                let (buyer, seller) = if side == Side::Buy { (taker, maker) } else { (maker, taker) };
+                settlement.record(trade, buyer, seller, &contract);
+            }
+            if let Some(slot) = maker_owner.and_then(|id| self.agents.get_mut(&id)) {
+                slot.metrics.record_fill_for(&contract, Liquidity::Maker, side.opposite(), trade.qty, trade.price, mid);
+            }
+            for (id, fill_side) in [(owner, side), (maker_owner, side.opposite())] {
+                if let Some(id) = id {
+                    self.report_fill(id, fill_side, trade.qty);
+                }
+            }
+            if let Some(collected) = self.paper_fills.as_mut() {
+                for (fill_agent, order_id, fill_side) in [(agent, trade.taker_id, side), (maker, trade.maker_id, side.opposite())] {
+                    if fill_agent == Agent::Manual {
+                        collected.push(PaperFill {
+                            order_id,
+                            account: MANUAL_ACCOUNT.to_string(),
+                            side: fill_side,
+                            price: trade.price,
+                            qty: trade.qty,
+                            ts: trade.ts,
+                        });
+                    }
+                }
+            }
+        }
+    }
+    
+    /// Set the engine's clock to `now` if it is not there already, so it
+    /// checks order timestamps against simulated time
+    fn set_engine_clock(&mut self, now: u128) {
+        if self.engine_clock != Some(now) {
+            self.engine_clock = Some(now);
+            self.apply_engine_command(EngineCommand::SetClock(now));
+        }
+    }
+
+    /// Place the next order in its source's STP group: a configured agent's
+    /// own group, or its built-in role's
+    fn select_stp_group(&mut self, agent: Agent, owner: Option<AgentId>) {
+        let Some(groups) = self.self_match.as_mut() else {
+            return;
+        };
+        let group = match owner.and_then(|id| self.agents.get(&id)) {
+            Some(slot) => slot.spec.stp_group.as_deref().map(|name| groups.id(name)),
+            None => groups.roles.get(&agent).copied(),
+        };
+        if group != groups.current {
+            groups.current = group;
+            self.apply_engine_command(EngineCommand::SetStpGroup(group));
+        }
+    }
+    
+    /// Send a configured agent the report of a fill, which it sees once its
+    /// ack path latency has passed
+    fn report_fill(&mut self, id: AgentId, side: Side, qty: Qty) {
+        let Some(slot) = self.agents.get_mut(&id) else {
+            return;
+        };
+        let path = slot.spec.ack_path.unwrap_or(self.net.ack_path);
+        if path.is_instant() {
+            return;
+        }
+        let due = self.current_time + path.sample(&mut self.rng) as u128;
+        let change = match side {
+            Side::Buy => qty as i64,
+            Side::Sell => -(qty as i64),
+        };
+        slot.unacked.push((due, change));
+    }
+
+    /// The book as published right now
+    fn live_book(&self) -> PublishedBook {
+        let (best_bid, best_ask) = self.engine.best_bid_ask();
+        PublishedBook {
+            ts: self.current_time,
+            best_bid,
+            best_ask,
+            mid: self.engine.quote_mid(),
+            book: self.engine.depth_snapshot(),
+        }
+    }
+
+    /// Publish the current book to agents that see market data late,
+    /// dropping books no agent can be seeing any more
+    fn publish_market_data(&mut self) {
+        let paths = self.agents.values().map(|slot| slot.spec.data_path.unwrap_or(self.net.data_path));
+        let horizon = paths.map(|path| path.max_ns()).max().unwrap_or(0) as u128;
+        if horizon == 0 {
+            self.published.clear();
+            return;
+        }
+        // An agent delayed by at most the horizon sees the newest book old
+        // enough for it, so books older than one already past the horizon go
+        let now = self.current_time;
+        while self.published.get(1).is_some_and(|next| next.ts + horizon <= now) {
+            self.published.pop_front();
+        }
+        let live = self.live_book();
+        self.published.push_back(live);
+    }
+
+    /// Agent, and configured agent if any, behind a resting order that just filled `qty`
+    fn resting_agent(&mut self, order_id: OrderId, qty: Qty) -> (Agent, Option<AgentId>) {
+        if order_ids::is_historical(order_id) {
+            return (Agent::Historical, None);
+        }
+        let Some(resting) = self.resting_agents.get_mut(&order_id) else {
+            return (Agent::Manual, None);
+        };
+        let owner = (resting.agent, resting.owner);
+        resting.remaining = resting.remaining.saturating_sub(qty);
+        resting.touched_at = self.current_time;
+        if resting.remaining == 0 {
+            self.resting_agents.remove(&order_id);
+        }
+        owner
+    }
+
+    /// Let each configured agent trade in turn
+    fn step_agents(&mut self, all_trades: &mut Vec<Trade>, orders_processed: &mut usize, errors_encountered: &mut usize) -> EngineResult<()> {
+        use crate::logging::log_engine_error;
+        
+        let active: Vec<AgentId> = self.agents.iter()
+            .filter(|(_, slot)| !slot.paused)
+            .map(|(&id, _)| id)
+            .collect();
+        let decision_ns = self.latency.as_ref().map(|latency| latency.decision_ns);
+        self.publish_market_data();
+        for id in active {
+            let now = self.current_time;
+            let Some(slot) = self.agents.get_mut(&id) else {
+                continue;
+            };
+            let inventory = slot.reported_inventory(now);
+            let data_delay = slot.spec.data_path.unwrap_or(self.net.data_path).sample(&mut self.rng) as u128;
+            // A delayed agent sees the newest book published long enough ago,
+            // or the oldest one kept when none is that old yet
+            let delayed = (data_delay > 0)
+                .then(|| self.published.iter().rev().find(|published| published.ts + data_delay <= now).or(self.published.front()))
+                .flatten()
+                .cloned();
+            let market = delayed.unwrap_or_else(|| self.live_book());
+            let decided_on = decision_ns.map(|_| CostModel::from_snapshot(&market.book));
+            let view = MarketView {
+                ts: now,
+                best_bid: market.best_bid,
+                best_ask: market.best_ask,
+                mid: market.mid,
+                inventory,
+                lot_size: self.lot_size,
+                book: &market.book,
+            };
+            let Some(slot) = self.agents.get_mut(&id) else {
+                continue;
+            };
+            let role = slot.agent.role();
+            let band_before = slot.agent.inventory_band();
+            let orders = slot.agent.on_step(&view);
+            let quote = slot.agent.on_quote(&view);
+            let cancels = slot.agent.on_cancel(&view);
+            if let Some(status) = slot.agent.inventory_band().filter(|status| Some(*status) != band_before) {
+                tracing::info!("Agent {} ({}) inventory {} moved from band {} to band {}",
+                               id, slot.spec.kind, inventory, band_before.map_or(0, |before| before.band), status.band);
+            }
+            
+            for AgentOrder { side, qty, price } in orders {
+                *orders_processed += 1;
+                let network_ns = self.simulate_network_latency();
+                if self.net.should_drop(&mut self.rng) {
+                    tracing::trace!("Agent {} order dropped due to network simulation", id);
+                    continue;
+                }
+                
+                let order_id = self.next_order_id();
+                let order = match price {
+                    Some(price) => Order::new_limit(order_id, side, qty, price, self.current_time),
+                    None => Order::new_market(order_id, side, qty, self.current_time),
+                };
+                let Some(order) = self.through_gateway(role, Some(id), order) else {
+                    continue;
+                };
+                let Some(order) = self.within_price_bands(Some(id), order) else {
+                    continue;
+                };
+                if let (Some(decision_ns), Some(model)) = (decision_ns, &decided_on) {
+                    let zero_latency = match price {
+                        Some(price) => model.expected_fill(side, price, qty),
+                        None => model.sweep(side, qty),
+                    };
+                    let latency_ns = decision_ns + network_ns;
+                    self.in_flight.push(InFlightOrder {
+                        due: view.ts + latency_ns as u128,
+                        owner: id,
+                        role,
+                        order,
+                        latency_ns,
+                        zero_latency,
+                    });
+                    continue;
+                }
+                let recorded = self.recordable(&order);
+                match self.place_for(role, Some(id), order) {
+                    Ok(trades) => {
+                        self.record_placement(recorded);
+                        if let Some(slot) = self.agents.get_mut(&id) {
+                            slot.orders += 1;
+                        }
+                        if !trades.is_empty() {
+                            self.update_metrics();
+                            all_trades.extend(trades);
+                        }
+                    }
+                    Err(e) => {
+                        *errors_encountered += 1;
+                        log_engine_error(&e, Some(&format!("Agent {} order {}", id, order_id)));
+                        if !e.is_recoverable() {
+                            return Err(e);
+                        }
+                    }
+                }
+            }
+            
+            if let Some(quote) = quote {
+                *orders_processed += 1;
+                match self.agent_quote(id, role, quote) {
+                    Ok(trades) => all_trades.extend(trades),
+                    Err(e) => {
+                        *errors_encountered += 1;
+                        log_engine_error(&e, Some(&format!("Agent {} quote", id)));
+                        if !e.is_recoverable() {
+                            return Err(e);
+                        }
+                    }
+                }
+            }
+            if cancels > 0 {
+                *orders_processed += cancels;
+                self.agent_cancels(id, cancels);
+            }
+        }
+        Ok(())
+    }
+
+    /// Pull a configured agent's newest resting orders, each passing the
+    /// gateway like an order; once none are left, the last one pulled is
+    /// cancelled again, which the engine refuses
+    fn agent_cancels(&mut self, id: AgentId, count: usize) {
+        let mut owned: Vec<OrderId> = self.resting_agents.iter()
+            .filter(|(_, resting)| resting.owner == Some(id))
+            .map(|(&order_id, _)| order_id)
+            .collect();
+        owned.sort_unstable();
+        for _ in 0..count {
+            if self.gateway.is_down() || self.gateway.admit(self.current_time).is_err() {
+                continue;
+            }
+            let Some(slot) = self.agents.get_mut(&id) else {
+                return;
+            };
+            let Some(order_id) = owned.pop().or(slot.last_cancelled) else {
+                continue;
+            };
+            slot.last_cancelled = Some(order_id);
+            if self.mirrored().withdraw(order_id).is_err() {
+                continue;
+            }
+            self.resting_agents.remove(&order_id);
+            if let Some(events) = self.recorded_events.as_mut() {
+                events.push(MarketEvent::OrderCancellation { order_id, timestamp: self.current_time, reason: None });
+            }
+        }
+    }
+
+    /// Replace a configured agent's two-sided quote, returning its trades
+    ///
+    /// Legs the gateway or the price bands refuse are pulled instead, and
+    /// the agent is told as it would be about an order.
+    fn agent_quote(&mut self, id: AgentId, role: Agent, quote: AgentQuote) -> EngineResult<Vec<Trade>> {
+        let legs: Vec<(Side, Price, Qty)> = [(Side::Buy, quote.bid), (Side::Sell, quote.ask)].into_iter()
+            .filter_map(|(side, leg)| leg.map(|(price, qty)| (side, price, qty)))
+            .collect();
+        let admitted = if self.gateway.is_down() { Err(None) } else { self.gateway.admit(self.current_time).map_err(Some) };
+        if let Err(retry_after_ms) = admitted {
+            if let Some(slot) = self.agents.get_mut(&id) {
+                for &(side, price, qty) in &legs {
+                    let order = AgentOrder::limit(side, qty, price);
+                    slot.agent.on_gateway(&match retry_after_ms {
+                        Some(retry_after_ms) => GatewayEvent::Throttled { order, retry_after_ms },
+                        None => GatewayEvent::Rejected(order),
+                    });
+                }
+            }
+            return Ok(Vec::new());
+        }
+        
+        let (mut bid, mut ask) = (None, None);
+        for (side, price, qty) in legs {
+            let order = Order::new_limit(self.next_order_id(), side, qty, price, self.current_time);
+            let Some(order) = self.within_price_bands(Some(id), order) else {
+                continue;
+            };
+            let leg = Some(QuoteLeg { order_id: order.id, price: order.price().unwrap_or(price), qty: order.qty });
+            match side {
+                Side::Buy => bid = leg,
+                Side::Sell => ask = leg,
+            }
+        }
+        let quote = TwoSidedQuote { owner: id as QuoteOwner, bid, ask, ts: self.current_time };
+        let outcome = self.quote_for(role, Some(id), quote)?;
+        if let Some(slot) = self.agents.get_mut(&id) {
+            slot.orders += quote.orders().count() as u64;
+        }
+        Ok(outcome.trades)
+    }
+
+    /// Place the in-flight agent orders that have reached the book by now,
+    /// accounting for what their latency cost
+    fn release_in_flight(&mut self, all_trades: &mut Vec<Trade>, errors_encountered: &mut usize) -> EngineResult<()> {
+        use crate::logging::log_engine_error;
+        
+        let now = self.current_time;
+        if !self.in_flight.iter().any(|pending| pending.due <= now) {
+            return Ok(());
+        }
+        let (mut arrived, waiting): (Vec<InFlightOrder>, Vec<InFlightOrder>) =
+            std::mem::take(&mut self.in_flight).into_iter().partition(|pending| pending.due <= now);
+        self.in_flight = waiting;
+        arrived.sort_by_key(|pending| pending.due);
+        
+        for InFlightOrder { due, owner, role, mut order, latency_ns, zero_latency } in arrived {
+            // Orders of agents removed in the meantime go nowhere
+            if !self.agents.contains_key(&owner) {
+                continue;
+            }
+            order.ts = due;
+            let Some(order) = self.within_price_bands(Some(owner), order) else {
+                continue;
+            };
+            let (side, order_id) = (order.side, order.id);
+            let recorded = self.recordable(&order);
+            match self.place_for(role, Some(owner), order) {
+                Ok(trades) => {
+                    self.record_placement(recorded);
+                    if let Some(slot) = self.agents.get_mut(&owner) {
+                        slot.orders += 1;
+                        slot.latency.record(side, latency_ns, &zero_latency, &trades);
+                    }
+                    if !trades.is_empty() {
+                        self.update_metrics();
+                        all_trades.extend(trades);
+                    }
+                }
+                Err(e) => {
+                    *errors_encountered += 1;
+                    log_engine_error(&e, Some(&format!("Agent {} order {}", owner, order_id)));
+                    if !e.is_recoverable() {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Pass a synthetic order through the gateway, returning it if it can go
+    /// to the engine now
+    ///
+    /// While the gateway is down the order is held for recovery or refused,
+    /// and while the matching engine is busy it is refused with a retry
+    /// hint. A configured agent is told about either refusal.
+    fn through_gateway(&mut self, role: Agent, owner: Option<AgentId>, order: Order) -> Option<Order> {
+        let refusal = match self.gateway.submit(PendingOrder { role, owner, order }) {
+            Ok(None) => return None,
+            Ok(Some(PendingOrder { order, .. })) => match self.gateway.admit(self.current_time) {
+                Ok(()) => return Some(order),
+                Err(retry_after_ms) => {
+                    let refused = AgentOrder { side: order.side, qty: order.qty, price: order.price() };
+                    GatewayEvent::Throttled { order: refused, retry_after_ms }
+                }
+            },
+            Err(PendingOrder { order, .. }) => {
+                GatewayEvent::Rejected(AgentOrder { side: order.side, qty: order.qty, price: order.price() })
+            }
+        };
+        if let Some(slot) = owner.and_then(|id| self.agents.get_mut(&id)) {
+            slot.agent.on_gateway(&refusal);
+        }
+        None
+    }
+
+    /// Check a synthetic order against the price bands in hybrid mode,
+    /// returning what may go to the book and telling the owning agent when
+    /// the band refuses it
+    fn within_price_bands(&mut self, owner: Option<AgentId>, order: Order) -> Option<Order> {
+        let Some(bands) = self.price_bands.as_mut().filter(|_| self.mode == SimulationMode::Hybrid) else {
+            return Some(order);
+        };
+        let refused = AgentOrder { side: order.side, qty: order.qty, price: order.price() };
+        let engine = &self.engine;
+        if let Some(order) = bands.admit(order, || engine.depth_snapshot()) {
+            return Some(order);
+        }
+        tracing::debug!("Synthetic {:?} order of {} at {:?} refused outside the price band", refused.side, refused.qty, refused.price);
+        if let Some(slot) = owner.and_then(|id| self.agents.get_mut(&id)) {
+            slot.agent.on_gateway(&GatewayEvent::Rejected(refused));
+        }
+        None
+    }
+
+    /// Bring the gateway up to the current time, telling agents when it goes
+    /// down or comes back and sending the orders it held in one burst
+    fn poll_gateway(&mut self, all_trades: &mut Vec<Trade>, orders_processed: &mut usize, errors_encountered: &mut usize) -> EngineResult<()> {
+        use crate::logging::log_engine_error;
+        
+        let reconnected = match self.gateway.poll(self.current_time) {
+            Some(GatewayEvent::Disconnected) => false,
+            Some(GatewayEvent::Reconnected) => true,
+            _ => return Ok(()),
+        };
+        let notice = if reconnected { GatewayEvent::Reconnected } else { GatewayEvent::Disconnected };
+        for slot in self.agents.values_mut() {
+            slot.agent.on_gateway(&notice);
+        }
+        if !reconnected {
+            self.record_gateway("Gateway down; order entry unavailable".to_string());
+            return Ok(());
+        }
+        
+        let queued = self.gateway.take_queued();
+        self.record_gateway(format!("Gateway reconnected; {} held orders sent", queued.len()));
+        for PendingOrder { role, owner, mut order } in queued {
+            *orders_processed += 1;
+            order.ts = self.current_time;
+            let order_id = order.id;
+            let recorded = self.recordable(&order);
+            match self.place_for(role, owner, order) {
+                Ok(trades) => {
+                    self.record_placement(recorded);
+                    if let Some(slot) = owner.and_then(|id| self.agents.get_mut(&id)) {
+                        slot.orders += 1;
+                    }
+                    if !trades.is_empty() {
+                        self.update_metrics();
+                        all_trades.extend(trades);
+                    }
+                }
+                Err(e) => {
+                    *errors_encountered += 1;
+                    log_engine_error(&e, Some(&format!("Held order {}", order_id)));
+                    if !e.is_recoverable() {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether order entry is down for a gateway outage
+    pub fn gateway_down(&self) -> bool {
+        self.gateway.is_down()
+    }
+
+    /// Get gateway outage counts
+    pub fn gateway_stats(&self) -> &GatewayStats {
+        self.gateway.stats()
+    }
+
+    /// Cancel synthetic orders that have rested untouched past their TTL,
+    /// recording each cancel when recording is enabled
+    fn expire_stale_liquidity(&mut self) -> usize {
+        if !self.liquidity_ttl.enabled {
+            return 0;
+        }
+        
+        let now = self.current_time;
+        let mut stale: Vec<OrderId> = self.resting_agents.iter()
+            .filter(|(_, resting)| self.liquidity_ttl.ttl(resting.agent)
+                .is_some_and(|ttl| now.saturating_sub(resting.touched_at) >= ttl))
+            .map(|(&order_id, _)| order_id)
+            .collect();
+        stale.sort_unstable();
+        
+        let mut expired = 0;
+        for order_id in stale {
+            self.resting_agents.remove(&order_id);
+            if self.mirrored().withdraw(order_id).is_ok() {
+                expired += 1;
+                if let Some(events) = self.recorded_events.as_mut() {
+                    events.push(MarketEvent::OrderCancellation {
+                        order_id,
+                        timestamp: now,
+                        reason: Some("ttl".to_string()),
+                    });
+                }
+            }
+        }
+        self.expired_orders += expired as u64;
+        expired
+    }
+
+    /// Collect the orders the engine pruned past its depth limit or cancelled
+    /// to prevent a self-match, forgetting their owners and recording each
+    /// as a cancel when recording is enabled
+    fn settle_pruned_orders(&mut self) -> usize {
+        if !self.depth_limit.is_bounded() && self.self_match.is_none() {
+            return 0;
+        }
+        
+        let pruned = self.mirrored().collect_pruned().unwrap_or_else(|e| {
+            tracing::warn!("Failed to collect pruned orders: {}", e);
+            Vec::new()
+        });
+        for order in &pruned {
+            let owner = match self.resting_agents.remove(&order.order_id) {
+                Some(resting) => resting.agent,
+                None if order_ids::is_historical(order.order_id) => Agent::Historical,
+                None => Agent::Manual,
+            };
+            self.replay.order_locations.remove(&order.order_id);
+            let reason = match order.reason {
+                PruneReason::DepthLimit => {
+                    self.pruned_orders += 1;
+                    tracing::debug!("Pruned {:?} order {} ({:?} {} @ {}) past the depth limit",
+                                    owner, order.order_id, order.side, order.qty, order.price);
+                    "depth"
+                }
+                PruneReason::SelfMatch => {
+                    self.self_matches += 1;
+                    tracing::debug!("Cancelled {:?} order {} ({:?} {} @ {}) to prevent a self-match",
+                                    owner, order.order_id, order.side, order.qty, order.price);
+                    "stp"
+                }
+            };
+            if let Some(events) = self.recorded_events.as_mut() {
+                events.push(MarketEvent::OrderCancellation {
+                    order_id: order.order_id,
+                    timestamp: self.current_time,
+                    reason: Some(reason.to_string()),
+                });
+            }
+        }
+        pruned.len()
+    }
+
+    /// Mark the simulator's and agents' PnL to the mid after trades executed
+    fn update_metrics(&mut self) {
+        let contract = self.contract;
+        
+        // Calculate PnL using current mid-price
+        if let Some(mid_price) = self.engine.quote_mid() {
+            let mid_price_ticks = price_utils::from_f64(mid_price);
+            self.metrics.calculate_pnl_for(&contract, Some(mid_price_ticks));
+            for metrics in self.agent_metrics.values_mut() {
+                metrics.calculate_pnl_for(&contract, Some(mid_price_ticks));
+            }
+            for slot in self.agents.values_mut() {
+                slot.metrics.calculate_pnl_for(&contract, Some(mid_price_ticks));
+            }
+        }
+    }
+
+    /// Update spread history
+    fn update_spread_history(&mut self) {
+        if let Some(spread) = self.engine.quote_spread() {
+            self.spread_history.push(self.current_time, spread);
+        }
+    }
+
+    /// Simulate network latency for an operation
+    fn simulate_network_latency(&mut self) -> u64 {
+        let latency_ns = self.net.simulate_latency(&mut self.rng);
+        
+        // Simulate the delay by advancing current time
+        self.current_time += latency_ns as u128;
+        
+        // In a real implementation, this might involve actual delays or queuing
+        latency_ns
+    }
+
+    /// Run one simulation step
+    pub fn step(&mut self) -> EngineResult<Vec<Trade>> {
+        use crate::logging::{log_engine_error, log_data_ingestion};
+        
+        let step_start = std::time::Instant::now();
+        let mut all_trades = Vec::new();
+        let mut orders_processed = 0;
+        let mut errors_encountered = 0;
+        if let Some(profile) = self.profile.as_mut() {
+            *profile = StepProfile::default();
+        }
+        
+        // Advance simulation time (replay mode is driven by its own clock)
+        if self.mode != SimulationMode::Replay {
+            let time_advance = self.rng.gen_range(
+                self.order_gen_config.mean_order_interval_ns / 2
+                ..=self.order_gen_config.mean_order_interval_ns * 2
+            );
+            self.current_time += time_advance as u128;
+        }
+        
+        let match_filter = self.match_interaction().and_then(|interaction| interaction.match_filter());
+        self.apply_engine_command(EngineCommand::SetMatchFilter(match_filter));
+        self.poll_gateway(&mut all_trades, &mut orders_processed, &mut errors_encountered)?;
+        self.release_in_flight(&mut all_trades, &mut errors_encountered)?;
+        let synthetic_due = matches!(self.mode, SimulationMode::Synthetic | SimulationMode::Hybrid)
+            && self.synthetic_flow_due();
+        
+        match self.mode {
+            SimulationMode::Replay => {
+                let now = Instant::now();
+                let elapsed = self.replay.last_tick.map(|last| now.duration_since(last)).unwrap_or_default();
+                self.replay.last_tick = Some(now);
+                
+                if !self.replay.paused {
+                    let advance_ns = (elapsed.as_nanos() as f64 * self.replay.speed) as u128;
+                    let trades = self.advance_replay(advance_ns)?;
+                    orders_processed += trades.len();
+                    all_trades.extend(trades);
+                }
+            }
+            SimulationMode::Historical => {
+                // Process events from data source
+                if let Some(ref mut data_source) = self.data_source {
+                    match data_source.next_event() {
+                        Ok(Some(event)) => {
+                            self.last_feed_event = Some(Instant::now());
+                            let ingest_ns = self.ingest_stamp(&event);
+                            if !matches!(event, MarketEvent::Heartbeat { .. }) {
+                                orders_processed += 1;
+                            }
+                            self.current_time = event.timestamp();
+                            
+                            match self.process_market_event(event) {
+                                Ok(trades) => {
+                                    self.stamp_event(ingest_ns);
+                                    if !trades.is_empty() {
+                                        self.update_metrics();
+                                        all_trades.extend(trades);
+                                    }
+                                }
+                                Err(e) => {
+                                    errors_encountered += 1;
+                                    log_engine_error(&e, Some("Historical data processing"));
+                                    
+                                    // Continue processing unless it's a critical error
+                                    if !e.is_recoverable() {
+                                        return Err(e);
+                                    }
+                                }
+                            }
+                        }
+                        Ok(None) => {
+                            // End of data - log completion
+                            tracing::info!("Historical data replay completed");
+                        }
+                        Err(e) => {
+                            // Only fail-fast sources get here; skip and quarantine
+                            // policies drop bad records inside the source. Report
+                            // the error and stay in historical mode so the next
+                            // step carries on from the following record.
+                            let engine_error = crate::error::EngineError::data(format!("Data source error: {}", e));
+                            log_engine_error(&engine_error, Some("Data source reading"));
+                            return Err(engine_error);
+                        }
+                    }
+                }
+            }
+            SimulationMode::Synthetic if !synthetic_due => {}
+            SimulationMode::Synthetic if !self.builtin_flow() => {
+                self.step_agents(&mut all_trades, &mut orders_processed, &mut errors_encountered)?;
+            }
+            SimulationMode::Synthetic => {
+                // Agents added at runtime trade alongside the built-in flow
+                if !self.agents.is_empty() {
+                    self.step_agents(&mut all_trades, &mut orders_processed, &mut errors_encountered)?;
+                }
+                
+                // Generate synthetic orders
+                
+                // Market making orders
+                let mm_orders = self.generate_market_making_orders();
+                for order in mm_orders {
+                    orders_processed += 1;
+                    self.simulate_network_latency();
+                    
+                    if !self.net.should_drop(&mut self.rng) {
+                        let Some(order) = self.through_gateway(Agent::MarketMaker, None, order) else {
+                            continue;
+                        };
+                        let order_id = order.id;
+                        let recorded = self.recordable(&order);
+                        
+                        match self.place_as(Agent::MarketMaker, order) {
+                            Ok(trades) => {
+                                self.record_placement(recorded);
+                                if !trades.is_empty() {
+                                    self.update_metrics();
+                                    all_trades.extend(trades);
+                                }
+                            }
+                            Err(e) => {
+                                errors_encountered += 1;
+                                log_engine_error(&e, Some(&format!("Market maker order {}", order_id)));
+                                
+                                // Continue unless critical error
+                                if !e.is_recoverable() {
+                                    return Err(e);
+                                }
+                            }
+                        }
+                    } else {
+                        tracing::trace!("Market maker order dropped due to network simulation");
+                    }
+                }
+                
+                // Market taker orders
+                if let Some(taker_order) = self.generate_market_taker_order() {
+                    orders_processed += 1;
+                    self.simulate_network_latency();
+                    
+                    if let Some(taker_order) = (!self.net.should_drop(&mut self.rng))
+                        .then(|| self.through_gateway(Agent::Taker, None, taker_order))
+                        .flatten()
+                    {
+                        let order_id = taker_order.id;
+                        let recorded = self.recordable(&taker_order);
+                        
+                        match self.place_as(Agent::Taker, taker_order) {
+                            Ok(trades) => {
+                                self.record_placement(recorded);
+                                if !trades.is_empty() {
+                                    self.update_metrics();
+                                    all_trades.extend(trades);
+                                }
+                            }
+                            Err(e) => {
+                                errors_encountered += 1;
+                                log_engine_error(&e, Some(&format!("Market taker order {}", order_id)));
+                                
+                                // Continue unless critical error
+                                if !e.is_recoverable() {
+                                    return Err(e);
+                                }
+                            }
+                        }
+                    } else {
+                        tracing::trace!("Market taker order dropped due to network simulation or gateway outage");
+                    }
+                }
+            }
+            SimulationMode::Hybrid => {
+                // Combine historical data with synthetic orders
+                // First try to process historical event
                 if let Some(ref mut data_source) = self.data_source {
                     match data_source.next_event() {
                         Ok(Some(event)) => {
-                            orders_processed += 1;
+                            self.last_feed_event = Some(Instant::now());
+                            let ingest_ns = self.ingest_stamp(&event);
+                            if !matches!(event, MarketEvent::Heartbeat { .. }) {
+                                orders_processed += 1;
+                            }
                             self.current_time = event.timestamp();
                             
                             match self.process_market_event(event) {
                                 Ok(trades) => {
+                                    self.stamp_event(ingest_ns);
                                     if !trades.is_empty() {
-                                        self.update_metrics(&trades, Side::Buy); // Assume buy side for simplicity
+                                        self.update_metrics();
                                         all_trades.extend(trades);
                                     }
                                 }
                                 Err(e) => {
                                     errors_encountered += 1;
-                                    log_engine_error(&e, Some("Historical data processing"));
+                                    log_engine_error(&e, Some("Hybrid mode historical processing"));
                                     
-                                    // Continue processing unless it's a critical error
                                     if !e.is_recoverable() {
                                         return Err(e);
                                     }
@@ -449,488 +3378,2701 @@ impl<E: OrderBookEngine> Simulator<E> {
                             }
                         }
                         Ok(None) => {
-                            // End of data - log completion
-                            tracing::info!("Historical data replay completed");
+                            // End of historical data, continue with synthetic only
                         }
                         Err(e) => {
                             errors_encountered += 1;
-                            let engine_error = crate::error::EngineError::data(format!("Data source error: {}", e));
-                            log_engine_error(&engine_error, Some("Data source reading"));
+                            let engine_error = crate::error::EngineError::data(format!("Hybrid mode data error: {}", e));
+                            log_engine_error(&engine_error, Some("Hybrid mode data source"));
+                        }
+                    }
+                }
+                
+                // Then add some synthetic market making
+                if !synthetic_due {
+                    tracing::trace!("Hybrid mode synthetic flow skipped while throttled");
+                } else if !self.builtin_flow() {
+                    self.step_agents(&mut all_trades, &mut orders_processed, &mut errors_encountered)?;
+                } else {
+                    if !self.agents.is_empty() {
+                        self.step_agents(&mut all_trades, &mut orders_processed, &mut errors_encountered)?;
+                    }
+                    if self.rng.gen::<f64>() < 0.5 {  // 50% chance of synthetic order
+                        let mm_orders = self.generate_market_making_orders();
+                        for order in mm_orders {
+                            orders_processed += 1;
+                            self.simulate_network_latency();
                             
-                            // Switch to synthetic mode on data errors
-                            tracing::warn!("Switching to synthetic mode due to data source error");
-                            self.mode = SimulationMode::Synthetic;
+                            if !self.net.should_drop(&mut self.rng) {
+                                let Some(order) = self.through_gateway(Agent::MarketMaker, None, order)
+                                    .and_then(|order| self.within_price_bands(None, order))
+                                else {
+                                    continue;
+                                };
+                                let order_id = order.id;
+                                
+                                match self.place_as(Agent::MarketMaker, order) {
+                                    Ok(trades) => {
+                                        if !trades.is_empty() {
+                                            self.update_metrics();
+                                            all_trades.extend(trades);
+                                        }
+                                    }
+                                    Err(e) => {
+                                        errors_encountered += 1;
+                                        log_engine_error(&e, Some(&format!("Hybrid mode synthetic order {}", order_id)));
+                                        
+                                        if !e.is_recoverable() {
+                                            return Err(e);
+                                        }
+                                    }
+                                }
+                            }
                         }
                     }
                 }
             }
-            SimulationMode::Synthetic => {
-                // Generate synthetic orders
-                
-                // Market making orders
-                let mm_orders = self.generate_market_making_orders();
-                for order in mm_orders {
-                    orders_processed += 1;
-                    self.simulate_network_latency();
-                    
-                    if !self.net.should_drop(&mut self.rng) {
-                        let order_side = order.side;
-                        let order_id = order.id;
-                        
-                        match self.engine.place(order) {
-                            Ok(trades) => {
-                                if !trades.is_empty() {
-                                    self.update_metrics(&trades, order_side);
-                                    all_trades.extend(trades);
-                                }
-                            }
-                            Err(e) => {
-                                errors_encountered += 1;
-                                log_engine_error(&e, Some(&format!("Market maker order {}", order_id)));
-                                
-                                // Continue unless critical error
-                                if !e.is_recoverable() {
-                                    return Err(e);
-                                }
-                            }
-                        }
-                    } else {
-                        tracing::trace!("Market maker order dropped due to network simulation");
-                    }
-                }
-                
-                // Market taker orders
-                if let Some(taker_order) = self.generate_market_taker_order() {
-                    orders_processed += 1;
-                    self.simulate_network_latency();
-                    
-                    if !self.net.should_drop(&mut self.rng) {
-                        let taker_side = taker_order.side;
-                        let order_id = taker_order.id;
-                        
-                        match self.engine.place(taker_order) {
-                            Ok(trades) => {
-                                if !trades.is_empty() {
-                                    self.update_metrics(&trades, taker_side);
-                                    all_trades.extend(trades);
-                                }
-                            }
-                            Err(e) => {
-                                errors_encountered += 1;
-                                log_engine_error(&e, Some(&format!("Market taker order {}", order_id)));
-                                
-                                // Continue unless critical error
-                                if !e.is_recoverable() {
-                                    return Err(e);
-                                }
-                            }
-                        }
-                    } else {
-                        tracing::trace!("Market taker order dropped due to network simulation");
-                    }
-                }
+        }
+        
+        // The step's orders are in; what follows is bookkeeping
+        let generation_end = std::time::Instant::now();
+        let generation_matching = self.profile.map_or(0, |profile| profile.get(StepPhase::Matching));
+        
+        // Historical liquidity is never expired, so replay has nothing to sweep
+        let expired = match self.mode {
+            SimulationMode::Replay => 0,
+            _ => self.expire_stale_liquidity(),
+        };
+        
+        let pruned = self.settle_pruned_orders();
+        
+        // Update spread history if trades, expiries or pruning moved the book
+        if !all_trades.is_empty() || expired > 0 || pruned > 0 {
+            self.update_spread_history();
+        }
+        
+        for trade in &all_trades {
+            self.volume_profiles.record(trade);
+            self.trade_tape.push(trade.clone());
+        }
+        
+        // Replay trades are tape prints rather than executions
+        if self.mode != SimulationMode::Replay {
+            for trade in &all_trades {
+                self.fills.record(trade);
+            }
+        }
+        
+        // Paper orders see the same trades and resulting book as everyone else
+        if self.paper.is_active() {
+            let book = self.engine.depth_snapshot();
+            let fills = self.paper.on_market_update(&book, &all_trades);
+            if let Some(collected) = self.paper_fills.as_mut() {
+                collected.extend(fills);
+            }
+        }
+        
+        // Flatten inventory if the hedger is due
+        if let Some(ref mut hedger) = self.hedger {
+            let book_mark = self.engine.quote_mid().map(price_utils::from_f64);
+            if let Some(execution) = hedger.on_step(&mut self.metrics, book_mark, self.current_time) {
+                tracing::debug!("Hedged {} units ({:?}) at {} for cost {}",
+                               execution.qty, execution.side, execution.price, execution.cost);
+            }
+        }
+        
+        // Exchange funding between longs and shorts if a settlement is due
+        let mid = self.engine.quote_mid().map(price_utils::from_f64);
+        if let Some(settlement) = self.funding.as_mut().and_then(|funding| funding.on_step(mid, self.current_time)) {
+            let contract = self.contract;
+            let paid = settlement.payment(&contract, self.metrics.inventory);
+            self.metrics.settle_funding(paid);
+            for metrics in self.agent_metrics.values_mut() {
+                metrics.settle_funding(settlement.payment(&contract, metrics.inventory));
+            }
+            for slot in self.agents.values_mut() {
+                slot.metrics.settle_funding(settlement.payment(&contract, slot.metrics.inventory));
+            }
+            self.paper.settle_funding(&settlement);
+            tracing::debug!("Funding settled at rate {:.6} (mark {}), simulator paid {}",
+                           settlement.rate, settlement.mark, paid);
+        }
+        
+        // Score the makers' quotes as the step left them
+        if self.obligations.is_some() {
+            let quotes = self.maker_quotes();
+            let now = self.current_time;
+            for report in self.obligations.as_mut().map_or_else(Vec::new, |monitor| monitor.on_step(now, quotes)) {
+                if !report.compliant {
+                    tracing::warn!("{} quoted {:.1}% of session {}, short of its obligation",
+                                   report.maker, report.quoted_pct, report.session);
+                }
+            }
+        }
+        
+        // Settle what is due and report accounts that do not reconcile
+        let positions = if self.settlement.is_some() { self.settlement_positions() } else { BTreeMap::new() };
+        let closed = self.settlement.as_mut().map_or_else(Vec::new, |settlement| settlement.on_step(self.current_time, &positions));
+        for cycle in closed {
+            for reconciliation in &cycle.breaks {
+                let message = format!("Settlement cycle {} break for {}: position {} settled of {} owed, cash {} of {} ({} failed trades), position {} booked of {} held",
+                                      cycle.cycle, reconciliation.account,
+                                      reconciliation.settled.position, reconciliation.expected.position,
+                                      reconciliation.settled.cash, reconciliation.expected.cash,
+                                      reconciliation.failed_trades, reconciliation.booked,
+                                      reconciliation.actual.map_or_else(|| "untracked".to_string(), |actual| actual.to_string()));
+                tracing::warn!("{}", message);
+                if let Some(activity) = self.activity.as_mut() {
+                    activity.push(Activity::new(ActivityKind::Settlement, message).at(cycle.ts));
+                }
+            }
+        }
+        
+        // Publish the pre-open imbalance if one is due
+        if let Some(queue) = self.pre_open.as_mut() {
+            let reference = self.trade_tape.back().map(|trade| trade.price).or(mid);
+            if let Some(imbalance) = queue.poll(self.current_time, reference) {
+                tracing::debug!("Auction imbalance: {:?}", imbalance);
+            }
+        }
+        
+        let point = MetricsPoint {
+            ts: self.current_time,
+            inventory: self.metrics.inventory,
+            pnl: self.metrics.pnl,
+        };
+        if self.metrics_history.back().is_none_or(|last| (last.inventory, last.pnl) != (point.inventory, point.pnl)) {
+            self.metrics_history.push(point);
+        }
+        
+        if let Some(profile) = self.profile.as_mut() {
+            let bookkeeping_matching = profile.get(StepPhase::Matching) - generation_matching;
+            let generation = generation_end.duration_since(step_start).saturating_sub(Duration::from_nanos(generation_matching));
+            profile.add(StepPhase::Generation, generation);
+            profile.add(StepPhase::Metrics, generation_end.elapsed().saturating_sub(Duration::from_nanos(bookkeeping_matching)));
+        }
+        
+        // Log step completion metrics
+        let step_duration = step_start.elapsed();
+        if errors_encountered > 0 {
+            tracing::warn!("Simulation step completed with {} errors out of {} orders in {:?}", 
+                          errors_encountered, orders_processed, step_duration);
+        } else if orders_processed > 0 {
+            tracing::trace!("Simulation step: {} orders, {} trades in {:?}", 
+                           orders_processed, all_trades.len(), step_duration);
+        }
+        
+        // Log performance data for monitoring
+        let step_duration_ms = step_duration.as_millis() as f64;
+        log_data_ingestion("simulation_step", orders_processed, errors_encountered, step_duration_ms);
+        
+        Ok(all_trades)
+    }
+
+    /// Run simulation for a specified number of steps
+    pub fn run_steps(&mut self, steps: usize) -> EngineResult<Vec<Trade>> {
+        let mut all_trades = Vec::new();
+        
+        for _ in 0..steps {
+            let trades = self.step()?;
+            all_trades.extend(trades);
+        }
+        
+        Ok(all_trades)
+    }
+
+    /// Get current market snapshot
+    pub fn snapshot(&self) -> DepthSnapshot {
+        let mut snapshot = match self.shed_levels {
+            Some(max_levels) => self.engine.depth_snapshot_top(max_levels),
+            None => self.engine.depth_snapshot(),
+        };
+        
+        // Override with simulator's metrics and spread history
+        snapshot.metrics = self.metrics.clone();
+        snapshot.recent_spreads = self.spread_history.recent();
+        snapshot.ts = self.current_time;
+        snapshot.agents = self.agent_stats();
+        snapshot.auction = self.auction_imbalance().copied();
+        // Ages sort every resting order's timestamp, so only when asked for
+        // and not while shedding load
+        let report_ages = self.report_order_ages && self.shed_levels.is_none();
+        snapshot.order_ages = report_ages.then(|| self.engine.open_order_ages(self.current_time));
+        
+        snapshot
+    }
+
+    /// Book composition and the running totals of its churn
+    pub fn engine_stats(&mut self) -> EngineResult<EngineStats> {
+        self.engine.collect_stats()
+    }
+
+    /// Place an order directly (for testing or manual intervention)
+    ///
+    /// The order takes the simulation's time, as synthetic orders do.
+    pub fn place_order(&mut self, order: Order) -> EngineResult<Vec<Trade>> {
+        self.admit_order_entry()?;
+        self.place_admitted(order)
+    }
+
+    /// Pass order entry's outage and throttle checks for one manual order
+    fn admit_order_entry(&mut self) -> EngineResult<()> {
+        if self.gateway.is_down() {
+            return Err(EngineError::GatewayUnavailable);
+        }
+        self.gateway.admit(self.current_time).map_err(|retry_after_ms| EngineError::Throttled { retry_after_ms })
+    }
+
+    /// Place a manual order order entry has already admitted
+    fn place_admitted(&mut self, mut order: Order) -> EngineResult<Vec<Trade>> {
+        use crate::logging::log_order_operation;
+
+        order.ts = self.engine_ts(self.current_time);
+        log_order_operation("MANUAL_PLACE", order.id, Some("Direct order placement"));
+        match self.place_as(Agent::Manual, order) {
+            Ok(trades) => {
+                if !trades.is_empty() {
+                    self.update_metrics();
+                    self.update_spread_history();
+                }
+                Ok(trades)
+            }
+            Err(e) => {
+                use crate::logging::log_engine_error;
+                log_engine_error(&e, Some("Manual order placement"));
+                Err(e)
+            }
+        }
+    }
+
+    /// Replace a manual two-sided quote in one step, giving each leg a
+    /// reserved order id; a side left out is pulled
+    ///
+    /// `owner` tells apart the quotes of different sessions. It is kept
+    /// clear of configured agents' quotes with [`MANUAL_QUOTE_OWNERS`].
+    pub fn quote_order(&mut self, owner: QuoteOwner, bid: Option<(Price, Qty)>, ask: Option<(Price, Qty)>) -> EngineResult<QuoteOutcome> {
+        use crate::logging::log_order_operation;
+        
+        log_order_operation("MANUAL_QUOTE", owner, Some("Direct two-sided quote"));
+        if self.gateway.is_down() {
+            return Err(EngineError::GatewayUnavailable);
+        }
+        self.gateway.admit(self.current_time).map_err(|retry_after_ms| EngineError::Throttled { retry_after_ms })?;
+        
+        let mut leg = |(price, qty)| QuoteLeg { order_id: self.reserve_order_id(), price, qty };
+        let (bid, ask) = (bid.map(&mut leg), ask.map(&mut leg));
+        let quote = TwoSidedQuote { owner: MANUAL_QUOTE_OWNERS | owner, bid, ask, ts: self.current_time };
+        let outcome = self.quote_for(Agent::Manual, None, quote)?;
+        self.update_spread_history();
+        Ok(outcome)
+    }
+
+    /// What a manual order would do if placed now, a market order when
+    /// `price` is omitted
+    ///
+    /// Matching runs against a scratch copy of the levels the order reaches,
+    /// so neither the book nor the manual flow changes and the order id is
+    /// not used up. An order the gateway would refuse is refused the same
+    /// way, without counting against its rate limit.
+    pub fn preview_order(&self, side: Side, qty: Qty, price: Option<Price>) -> EngineResult<OrderPreview> {
+        if self.pre_open.as_ref().is_some_and(|queue| queue.is_collecting()) {
+            return Err(EngineError::PreOpen { reason: "orders wait for the opening auction".to_string() });
+        }
+        if self.gateway.is_down() {
+            return Err(EngineError::GatewayUnavailable);
+        }
+        self.gateway.check(self.current_time).map_err(|retry_after_ms| EngineError::Throttled { retry_after_ms })?;
+        let order = match price {
+            Some(price) => Order::new_limit(self.next_order_id, side, qty, price, self.current_time),
+            None => Order::new_market(self.next_order_id, side, qty, self.current_time),
+        };
+        let stp_group = self.self_match.as_ref().and_then(|groups| groups.roles.get(&Agent::Manual).copied());
+        self.engine.preview_order(order, stp_group)
+    }
+
+    /// Quantity an order has resting in the book or queued for the open
+    pub fn resting_qty(&self, order_id: OrderId) -> Option<Qty> {
+        self.pre_open.as_ref()
+            .and_then(|queue| queue.queued_qty(order_id))
+            .or_else(|| self.engine.order_resting_qty(order_id))
+    }
+
+    /// Cancel a resting order directly, returning its unfilled quantity
+    pub fn cancel_order(&mut self, order_id: OrderId) -> EngineResult<Qty> {
+        use crate::logging::log_order_operation;
+
+        log_order_operation("MANUAL_CANCEL", order_id, Some("Direct order cancellation"));
+        if let Some(remaining) = self.pre_open.as_mut().and_then(|queue| queue.cancel(order_id)) {
+            return Ok(remaining);
+        }
+        let remaining = self.mirrored().withdraw(order_id)?;
+        self.resting_agents.remove(&order_id);
+        self.update_spread_history();
+        Ok(remaining)
+    }
+
+    /// Position of the manual order flow, ladder and sessions together
+    pub fn manual_position(&self) -> i64 {
+        self.agent_metrics.get(&Agent::Manual).map_or(0, |metrics| metrics.inventory)
+    }
+
+    /// Position of the orders the price ladder placed, which a flatten closes
+    pub fn ladder_position(&self) -> i64 {
+        self.ladder_position
+    }
+
+    /// Apply a ladder click in one go, placing and pulling the ladder's own
+    /// orders; manual orders placed any other way are left alone
+    ///
+    /// A flatten is checked against order entry and the other side's
+    /// liquidity before anything is pulled, so a refused flatten leaves the
+    /// ladder's orders working.
+    pub fn ladder_click(&mut self, action: &LadderAction) -> EngineResult<LadderOutcome> {
+        let resting = &self.resting_agents;
+        self.ladder_orders.retain(|order_id| resting.contains_key(order_id));
+        let mut outcome = LadderOutcome::default();
+        match *action {
+            LadderAction::Join { side, qty, price } => {
+                let (best_bid, best_ask) = self.engine.best_bid_ask();
+                let (best, opposite) = match side {
+                    Side::Buy => (best_bid, best_ask),
+                    Side::Sell => (best_ask, best_bid),
+                };
+                let price = price.or(best).ok_or(EngineError::NoLiquidity)?;
+                let crosses = opposite.is_some_and(|opposite| match side {
+                    Side::Buy => price >= opposite,
+                    Side::Sell => price <= opposite,
+                });
+                if crosses {
+                    let state = if opposite == Some(price) { BookState::Locked } else { BookState::Crossed };
+                    return Err(EngineError::CrossedBook { price, state });
+                }
+                let order_id = self.next_order_id();
+                self.ladder_orders.insert(order_id);
+                let placed = self.place_order(Order::new_limit(order_id, side, qty, price, self.current_time));
+                if !self.resting_agents.contains_key(&order_id) {
+                    self.ladder_orders.remove(&order_id);
+                }
+                outcome.trades = placed?;
+                outcome.order_id = Some(order_id);
+            }
+            LadderAction::Flatten => {
+                let position = self.ladder_position;
+                let closing_side = if position > 0 { Side::Sell } else { Side::Buy };
+                if position != 0 {
+                    // The closing order takes liquidity other than the ladder's own
+                    let liquidity = self.engine.open_orders().into_iter()
+                        .any(|order| order.side == closing_side.opposite() && !self.ladder_orders.contains(&order.id));
+                    if !liquidity {
+                        return Err(EngineError::NoLiquidity);
+                    }
+                    self.admit_order_entry()?;
+                }
+                outcome.cancelled = self.cancel_ladder_orders(None);
+                if position != 0 {
+                    let order_id = self.next_order_id();
+                    let order = Order::new_market(order_id, closing_side, position.unsigned_abs(), self.current_time);
+                    self.ladder_orders.insert(order_id);
+                    let placed = self.place_admitted(order);
+                    self.ladder_orders.remove(&order_id);
+                    outcome.trades = placed?;
+                    outcome.order_id = Some(order_id);
+                }
+            }
+            LadderAction::CancelAtPrice { price } => {
+                outcome.cancelled = self.cancel_ladder_orders(Some(price));
+            }
+        }
+        outcome.position = self.ladder_position;
+        Ok(outcome)
+    }
+
+    /// Cancel the ladder's resting orders, only those at `price` if given,
+    /// returning their ids
+    fn cancel_ladder_orders(&mut self, price: Option<Price>) -> Vec<OrderId> {
+        let order_ids: Vec<OrderId> = self.engine.open_orders().into_iter()
+            .filter(|order| self.ladder_orders.contains(&order.id))
+            .filter(|order| price.is_none_or(|price| order.price() == Some(price)))
+            .map(|order| order.id)
+            .collect();
+        order_ids.into_iter()
+            .filter(|&order_id| self.cancel_order(order_id).is_ok())
+            .collect()
+    }
+
+    /// Pay a priority fee for a resting order, charging it to the order's
+    /// agent, and returning the fee the queue discipline charged
+    pub fn pay_priority_fee(&mut self, order_id: OrderId, fee: u64) -> EngineResult<u64> {
+        let charged = self.mirrored().buy_priority(order_id, fee)?;
+        if charged > 0 {
+            let (agent, owner) = match self.resting_agents.get(&order_id) {
+                Some(resting) => (resting.agent, resting.owner),
+                None if order_ids::is_historical(order_id) => (Agent::Historical, None),
+                None => (Agent::Manual, None),
+            };
+            self.agent_metrics.entry(agent).or_default().pay_priority_fee(charged);
+            if let Some(slot) = owner.and_then(|id| self.agents.get_mut(&id)) {
+                slot.metrics.pay_priority_fee(charged);
+            }
+        }
+        Ok(charged)
+    }
+
+    /// Capture the resting orders, their owners and fees, the match filter
+    /// and the order id and clock counters
+    pub fn save_book(&self) -> BookImage {
+        let orders = self.engine.open_orders().into_iter()
+            .map(|order| {
+                let resting = self.resting_agents.get(&order.id);
+                let agent = match resting {
+                    Some(resting) => Some(resting.agent),
+                    None if order_ids::is_historical(order.id) => None,
+                    None => Some(Agent::Manual),
+                };
+                let priority_fee = self.engine.order_priority_fee(order.id);
+                ImageOrder { order, agent, owner: resting.and_then(|resting| resting.owner), priority_fee }
+            })
+            .collect();
+        BookImage {
+            saved_at: now_ns(),
+            current_time: self.current_time,
+            next_order_id: self.next_order_id,
+            session_orders: self.session_orders,
+            interaction: self.match_interaction(),
+            orders,
+        }
+    }
+
+    /// Replace the book with a saved one, returning the orders restored
+    ///
+    /// The engine is cleared and the saved orders placed again in priority
+    /// order under the saved match filter, with the priority fees they had
+    /// paid, so each level keeps its queue. If the engine refuses one, the
+    /// book is put back as it was and the error returned. Orders on their way
+    /// to the book are dropped. The clock resumes where it was saved, moved on
+    /// by the wall-clock time since, and order timestamps move with it. Run
+    /// statistics are left as they are, and feeds see the restore as an
+    /// ordinary change to the book.
+    pub fn load_book(&mut self, image: &BookImage) -> EngineResult<usize> {
+        if self.mode == SimulationMode::Replay {
+            return Err(EngineError::reject("Replay reconstructs a recorded book and cannot load a saved one"));
+        }
+
+        let now = now_ns();
+        let shift = now.saturating_sub(image.saved_at);
+        let current_time = image.current_time + shift;
+        let oldest = now.saturating_sub(RESTORED_ORDER_MAX_AGE_NS);
+        let orders: Vec<ImageOrder> = image.orders.iter()
+            .map(|saved| {
+                let mut saved = saved.clone();
+                saved.order.ts = (saved.order.ts + shift).clamp(oldest, now);
+                saved
+            })
+            .collect();
+
+        // Keep the book as it stands to put back if the load fails
+        let current = self.save_book();
+        let resting_agents = std::mem::take(&mut self.resting_agents);
+        if let Err(e) = self.rebuild_book(&orders, image.interaction, current_time) {
+            if let Err(e) = self.rebuild_book(&current.orders, current.interaction, self.current_time) {
+                tracing::warn!("Could not put the book back after a failed load: {}", e);
+            }
+            self.resting_agents = resting_agents;
+            return Err(e);
+        }
+
+        self.in_flight.clear();
+        if let Some(ref mut shadow) = self.shadow {
+            shadow.reset();
+        }
+        if let Some(interaction) = image.interaction {
+            self.hybrid_interaction = interaction;
+        }
+        self.next_order_id = image.next_order_id;
+        self.session_orders = self.session_orders.max(image.session_orders);
+        self.current_time = current_time;
+        self.update_spread_history();
+        Ok(orders.len())
+    }
+
+    /// Clear the engine and place `orders` under the match filter of
+    /// `interaction`, tracking the ones agents own as touched at `touched_at`
+    fn rebuild_book(&mut self, orders: &[ImageOrder], interaction: Option<HybridInteraction>, touched_at: u128) -> EngineResult<()> {
+        self.apply_engine_command(EngineCommand::Clear);
+        self.resting_agents.clear();
+        let match_filter = interaction.and_then(|interaction| interaction.match_filter());
+        self.apply_engine_command(EngineCommand::SetMatchFilter(match_filter));
+        for saved in orders {
+            let (order_id, qty) = (saved.order.id, saved.order.qty);
+            self.select_stp_group(saved.agent.unwrap_or(Agent::Historical), saved.owner);
+            // Orders rest as long as they like, so each is checked at its own time
+            self.set_engine_clock(saved.order.ts);
+            self.mirrored().submit(saved.order.clone())?;
+            if saved.priority_fee > 0 {
+                self.mirrored().buy_priority(order_id, saved.priority_fee)?;
+            }
+            if let Some(agent) = saved.agent {
+                self.resting_agents.insert(order_id, RestingOrder { agent, owner: saved.owner, remaining: qty, touched_at });
+            }
+        }
+        Ok(())
+    }
+
+    /// Step until the book meets the warm-up criteria, then start measuring
+    ///
+    /// Once the book is steady (or `max_steps` runs out) every run statistic
+    /// is cleared while the book, its resting orders and the agents stay as
+    /// they are, so reported figures count from the boundary. Inventory and
+    /// cash built up while warming up are dropped with the rest, and the
+    /// gateway restarts, so its outage schedule also counts from the
+    /// boundary. The boundary is kept for the run report.
+    pub fn warm_up(&mut self, config: WarmupConfig) -> EngineResult<WarmupBoundary> {
+        use crate::logging::log_startup;
+        
+        if self.mode == SimulationMode::Replay {
+            return Err(EngineError::reject("Replay reconstructs a recorded book and has no warm-up"));
+        }
+        config.validate().map_err(EngineError::reject)?;
+        
+        let mut controller = WarmupController::new(config);
+        let boundary = loop {
+            self.step()?;
+            let book = self.engine.depth_snapshot();
+            let steady = controller.observe(self.engine.quote_spread(), book.bids.len(), book.asks.len());
+            if steady || controller.exhausted() {
+                break controller.boundary(self.current_time, steady, book.bids.len(), book.asks.len());
+            }
+        };
+        
+        self.metrics = Metrics::new();
+        self.agent_metrics.clear();
+        for slot in self.agents.values_mut() {
+            slot.reset();
+        }
+        self.published.clear();
+        self.expired_orders = 0;
+        self.pruned_orders = 0;
+        self.self_matches = 0;
+        self.spread_history.clear();
+        self.metrics_history.clear();
+        self.trade_tape.clear();
+        self.fills = FillAccounting::default();
+        self.volume_profiles.reset();
+        self.gateway.reset();
+        if let Some(ref mut funding) = self.funding {
+            funding.reset();
+        }
+        if let Some(ref mut obligations) = self.obligations {
+            obligations.reset();
+        }
+        if let Some(ref mut settlement) = self.settlement {
+            settlement.reset();
+        }
+        self.warmup = Some(boundary);
+        
+        let outcome = if boundary.converged { "steady" } else { "step budget exhausted" };
+        log_startup("Simulator", Some(&format!("Warm-up finished after {} steps ({})", boundary.steps, outcome)));
+        Ok(boundary)
+    }
+
+    /// Where the warm-up ended, if one ran
+    pub fn warmup_boundary(&self) -> Option<WarmupBoundary> {
+        self.warmup
+    }
+
+    /// Reset simulation metrics
+    pub fn reset_metrics(&mut self) {
+        use crate::logging::log_startup;
+        
+        self.metrics = Metrics::new();
+        self.agent_metrics.clear();
+        for slot in self.agents.values_mut() {
+            slot.reset();
+        }
+        // Positions restart flat, so the ledger reconciling them does too
+        if let Some(ref mut settlement) = self.settlement {
+            settlement.reset();
+        }
+        self.spread_history.clear();
+        self.metrics_history.clear();
+        log_startup("Simulator", Some("Metrics reset"));
+    }
+
+    /// Get current simulation time
+    pub fn current_time(&self) -> u128 {
+        self.current_time
+    }
+
+    /// Get simulation metrics
+    pub fn get_metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Reset simulation state
+    pub fn reset(&mut self) {
+        self.metrics = Metrics::new();
+        self.agent_metrics.clear();
+        self.market_maker_band = BandStatus::default();
+        self.resting_agents.clear();
+        self.ladder_orders.clear();
+        self.ladder_position = 0;
+        self.in_flight.clear();
+        for slot in self.agents.values_mut() {
+            slot.reset();
+        }
+        self.published.clear();
+        self.expired_orders = 0;
+        self.pruned_orders = 0;
+        self.self_matches = 0;
+        self.spread_history.clear();
+        self.metrics_history.clear();
+        self.trade_tape.clear();
+        self.current_time = now_ns();
+        self.next_order_id = 1;
+        self.fills = FillAccounting::default();
+        self.paper.reset();
+        self.volume_profiles.reset();
+        // Held orders carry ids the restarted counter hands out again
+        self.gateway.reset();
+        
+        if let Some(ref mut queue) = self.pre_open {
+            queue.reset();
+        }
+        
+        if let Some(ref mut hedger) = self.hedger {
+            hedger.reset();
+        }
+        
+        if let Some(ref mut funding) = self.funding {
+            funding.reset();
+        }
+        
+        if let Some(ref mut obligations) = self.obligations {
+            obligations.reset();
+        }
+        
+        if let Some(ref mut settlement) = self.settlement {
+            settlement.reset();
+        }
+        
+        if let Some(ref mut bands) = self.price_bands {
+            bands.reset();
+        }
+        
+        // Pull the shadow ladder, which a replay clears with the rest of the book
+        if let Some(mut shadow) = self.shadow.take() {
+            if self.mode != SimulationMode::Replay {
+                for &order_id in shadow.orders() {
+                    let _ = self.mirrored().withdraw(order_id);
+                }
+            }
+            shadow.reset();
+            self.shadow = Some(shadow);
+        }
+        
+        if let Some(ref mut data_source) = self.data_source {
+            let _ = data_source.reset();
+        }
+        
+        if self.mode == SimulationMode::Replay {
+            self.apply_engine_command(EngineCommand::Clear);
+            self.replay = ReplayTransport {
+                paused: self.replay.paused,
+                speed: self.replay.speed,
+                ..ReplayTransport::default()
+            };
+        }
+    }
+
+    /// Start or stop recording accepted synthetic orders as market events
+    pub fn record_events(&mut self, enabled: bool) {
+        self.recorded_events = enabled.then(Vec::new);
+    }
+
+    /// Take the events recorded since the last call
+    pub fn take_recorded_events(&mut self) -> Vec<MarketEvent> {
+        self.recorded_events.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Start or stop collecting market activity (status changes) for the timeline
+    pub fn record_activity(&mut self, enabled: bool) {
+        self.activity = enabled.then(Vec::new);
+    }
+
+    /// Take the market activity collected since the last call
+    pub fn take_activity(&mut self) -> Vec<Activity> {
+        self.activity.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Start or stop stamping data source events with the wall-clock times
+    /// they were ingested and applied to the engine
+    pub fn record_pipeline_stamps(&mut self, enabled: bool) {
+        self.pipeline_stamps = enabled.then(Vec::new);
+    }
+
+    /// Take the event stamps collected since the last call
+    pub fn take_pipeline_stamps(&mut self) -> Vec<EventStamp> {
+        self.pipeline_stamps.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Start or stop attributing each step's time to its phases
+    pub fn profile_steps(&mut self, enabled: bool) {
+        self.profile = enabled.then(StepProfile::default);
+    }
+
+    /// Time the last step spent generating orders, matching them and
+    /// updating metrics, while steps are profiled
+    pub fn take_step_profile(&mut self) -> Option<StepProfile> {
+        self.profile.as_mut().map(std::mem::take)
+    }
+
+    /// Start or stop following the resting orders through the engine's
+    /// commands for market-by-order updates
+    pub fn record_order_updates(&mut self, enabled: bool) {
+        self.order_feed = enabled.then(|| {
+            let engine = &self.engine;
+            OrderTracker::with_orders(engine.open_orders().into_iter().map(|order| {
+                let priority_fee = engine.order_priority_fee(order.id);
+                (order, priority_fee)
+            }))
+        });
+    }
+
+    /// Changes to the resting orders since the last call, in the order they
+    /// happened; the first call lists every order
+    pub fn take_order_updates(&mut self) -> Vec<OrderUpdate> {
+        self.order_feed.as_mut().map(OrderTracker::take).unwrap_or_default()
+    }
+
+    /// Ingest time of a data source event, while stamps are collected;
+    /// heartbeats never reach the engine and are not stamped
+    fn ingest_stamp(&self, event: &MarketEvent) -> Option<(u128, u128)> {
+        let stamped = self.pipeline_stamps.is_some() && !matches!(event, MarketEvent::Heartbeat { .. });
+        stamped.then(|| (event.timestamp(), now_ns()))
+    }
+
+    /// Stamp an event ingested at `ingest` as applied to the engine now
+    fn stamp_event(&mut self, ingest: Option<(u128, u128)>) {
+        if let (Some(stamps), Some((event_ts, ingest_ns))) = (self.pipeline_stamps.as_mut(), ingest) {
+            stamps.push(EventStamp { event_ts, ingest_ns, match_ns: now_ns() });
+        }
+    }
+
+    /// Start or stop collecting the fills of resting paper orders and of
+    /// manual orders, which are reported under [`MANUAL_ACCOUNT`]
+    pub fn record_paper_fills(&mut self, enabled: bool) {
+        self.paper_fills = enabled.then(Vec::new);
+    }
+
+    /// Take the paper and manual order fills collected since the last call
+    pub fn take_paper_fills(&mut self) -> Vec<PaperFill> {
+        self.paper_fills.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    fn record_gateway(&mut self, message: String) {
+        tracing::warn!("{}", message);
+        if let Some(activity) = self.activity.as_mut() {
+            activity.push(Activity::new(ActivityKind::Gateway, message).at(self.current_time));
+        }
+    }
+
+    fn record_status(&mut self, status: MarketStatusType, timestamp: u128, message: Option<&str>) {
+        tracing::info!("Market status changed to {:?}", status);
+        if let Some(activity) = self.activity.as_mut() {
+            activity.push(Activity::market_status(status, timestamp, message));
+        }
+    }
+
+    /// Copy an order for recording if recording is enabled
+    fn recordable(&self, order: &Order) -> Option<Order> {
+        self.recorded_events.as_ref().map(|_| order.clone())
+    }
+
+    fn record_placement(&mut self, order: Option<Order>) {
+        if let (Some(order), Some(events)) = (order, self.recorded_events.as_mut()) {
+            events.push(MarketEvent::OrderPlacement(order));
+        }
+    }
+
+    /// Set simulation time (useful for testing)
+    pub fn set_time(&mut self, time: u128) {
+        self.current_time = time;
+    }
+
+    /// Pause replay playback; the book stays at the current replay clock
+    pub fn pause_replay(&mut self) {
+        self.replay.paused = true;
+    }
+
+    /// Resume replay playback from the current replay clock
+    pub fn resume_replay(&mut self) {
+        self.replay.paused = false;
+        self.replay.last_tick = Some(Instant::now());
+    }
+
+    /// Set the replay speed multiplier (1.0 = real time)
+    pub fn set_replay_speed(&mut self, speed: f64) -> EngineResult<()> {
+        if !speed.is_finite() || speed <= 0.0 {
+            return Err(EngineError::reject("Replay speed must be positive"));
+        }
+        self.replay.speed = speed;
+        Ok(())
+    }
+
+    /// Jump the replay to `timestamp`, rebuilding the book from the start of the data
+    pub fn seek_replay(&mut self, timestamp: u128) -> EngineResult<()> {
+        let data_source = self.data_source.as_mut()
+            .ok_or_else(|| EngineError::reject("No data source attached for replay"))?;
+        data_source.reset()
+            .map_err(|e| EngineError::data(format!("Failed to rewind data source: {}", e)))?;
+        let _ = data_source.set_paused(true);
+        
+        self.apply_engine_command(EngineCommand::Clear);
+        self.spread_history.clear();
+        if let Some(ref mut shadow) = self.shadow {
+            shadow.reset();
+        }
+        if let Some(ref mut bands) = self.price_bands {
+            bands.rewind();
+        }
+        if let Some(ref mut queue) = self.pre_open {
+            queue.reset();
+        }
+        self.replay.pending = None;
+        self.replay.clock = None;
+        self.replay.order_locations.clear();
+        self.replay.events_applied = 0;
+        self.unmatched_orders.reset();
+        // Orders on their way or held at the gateway were sent on the
+        // timeline being left
+        self.in_flight.clear();
+        self.gateway.reset();
+        
+        // Anchor at the first event, then fast-forward to the target
+        self.advance_replay(0)?;
+        self.replay_until(timestamp)?;
+        self.update_spread_history();
+        Ok(())
+    }
+
+    /// Get the replay transport status
+    pub fn replay_status(&self) -> ReplayStatus {
+        ReplayStatus {
+            paused: self.replay.paused,
+            speed: self.replay.speed,
+            clock: self.replay.clock,
+            events_applied: self.replay.events_applied,
+            finished: self.replay.pending.is_none() && !self.has_more_data(),
+        }
+    }
+
+    /// Check if simulation has more data to process (for historical mode)
+    pub fn has_more_data(&self) -> bool {
+        match &self.data_source {
+            Some(data_source) => !data_source.is_finished(),
+            None => true,  // Synthetic mode always has more data
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{BookLevelPoint, OrderBook, OrderBookEngine};
+    use crate::queue_fifo::FifoLevel;
+    use crate::types::price_utils;
+
+    type TestOrderBook = OrderBook<FifoLevel>;
+
+    #[test]
+    fn test_simulator_creation() {
+        let engine = TestOrderBook::new();
+        let sim = Simulator::new(engine);
+        
+        assert_eq!(sim.mode, SimulationMode::Synthetic);
+        assert_eq!(sim.next_order_id, 1);
+        assert_eq!(sim.metrics.inventory, 0);
+        assert_eq!(sim.metrics.cash, 0);
+        assert_eq!(sim.metrics.pnl, 0);
+    }
+
+    #[test]
+    fn test_network_model() {
+        let net = NetModel::default();
+        let mut rng = StdRng::seed_from_u64(42);
+        
+        // Test latency simulation
+        let latency = net.simulate_latency(&mut rng);
+        assert!(latency > 0);
+        
+        // Test drop probability
+        let mut drops = 0;
+        for _ in 0..1000 {
+            if net.should_drop(&mut rng) {
+                drops += 1;
+            }
+        }
+        // Should be approximately 1 drop (0.1% of 1000)
+        assert!(drops < 10);  // Allow some variance
+        
+        // Test reorder probability
+        let mut reorders = 0;
+        for _ in 0..1000 {
+            if net.should_reorder(&mut rng) {
+                reorders += 1;
+            }
+        }
+        // Should be approximately 10 reorders (1% of 1000)
+        assert!(reorders > 0 && reorders < 50);  // Allow some variance
+    }
+
+    #[test]
+    fn test_market_maker_config() {
+        let config = MarketMakerConfig::default();
+        
+        assert_eq!(config.target_spread, price_utils::from_f64(0.01));
+        assert_eq!(config.max_inventory, 1000);
+        assert_eq!(config.order_size, 100);
+        assert_eq!(config.mm_probability, 0.7);
+    }
+
+    #[test]
+    fn test_synthetic_simulation_step() {
+        let engine = TestOrderBook::new();
+        let mut sim = Simulator::with_seed(engine, 42);
+        
+        // Run a few simulation steps
+        for _ in 0..10 {
+            let result = sim.step();
+            assert!(result.is_ok());
+        }
+        
+        // Should have generated some market activity
+        let snapshot = sim.snapshot();
+        
+        // Check that we have some market structure
+        // (exact values depend on random seed, but should have some activity)
+        assert!(snapshot.ts > 0);
+    }
+
+    #[test]
+    fn test_market_making_order_generation() {
+        let engine = TestOrderBook::new();
+        let mut sim = Simulator::with_seed(engine, 42);
+        
+        // Generate market making orders
+        let orders = sim.generate_market_making_orders();
+        
+        // Should generate orders to create initial market
+        // (exact behavior depends on random seed and market state)
+        assert!(orders.len() <= 2);  // At most bid and ask
+        
+        for order in orders {
+            assert!(order.qty > 0);
+            assert!(order.id > 0);
+            assert!(order.is_limit());
+        }
+    }
+
+    #[test]
+    fn test_market_taker_order_generation() {
+        let engine = TestOrderBook::new();
+        let mut sim = Simulator::with_seed(engine, 42);
+        
+        // First establish some market structure
+        for _ in 0..5 {
+            let _ = sim.step();
+        }
+        
+        // Generate a market taker order
+        if let Some(order) = sim.generate_market_taker_order() {
+            assert!(order.qty > 0);
+            assert!(order.id > 0);
+            assert!(order.qty >= sim.order_gen_config.min_order_size);
+            assert!(order.qty <= sim.order_gen_config.max_order_size);
+        }
+    }
+
+    #[test]
+    fn test_generated_orders_use_whole_lots() {
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 7).with_lot_size(LotSize::new(100));
+        assert!(sim.run_steps(50).is_ok());
+        
+        for _ in 0..20 {
+            for order in sim.generate_market_making_orders() {
+                assert_eq!(order.qty % 100, 0);
+            }
+            if let Some(order) = sim.generate_market_taker_order() {
+                assert!(order.qty >= 100);
+                assert_eq!(order.qty % 100, 0);
             }
-            SimulationMode::Hybrid => {
-                // Combine historical data with synthetic orders
-                // First try to process historical event
-                if let Some(ref mut data_source) = self.data_source {
-                    match data_source.next_event() {
-                        Ok(Some(event)) => {
-                            orders_processed += 1;
-                            self.current_time = event.timestamp();
-                            
-                            match self.process_market_event(event) {
-                                Ok(trades) => {
-                                    if !trades.is_empty() {
-                                        self.update_metrics(&trades, Side::Buy);
-                                        all_trades.extend(trades);
-                                    }
-                                }
-                                Err(e) => {
-                                    errors_encountered += 1;
-                                    log_engine_error(&e, Some("Hybrid mode historical processing"));
-                                    
-                                    if !e.is_recoverable() {
-                                        return Err(e);
-                                    }
-                                }
-                            }
-                        }
-                        Ok(None) => {
-                            // End of historical data, continue with synthetic only
-                        }
-                        Err(e) => {
-                            errors_encountered += 1;
-                            let engine_error = crate::error::EngineError::data(format!("Hybrid mode data error: {}", e));
-                            log_engine_error(&engine_error, Some("Hybrid mode data source"));
-                        }
-                    }
-                }
-                
-                // Then add some synthetic market making
-                if self.rng.gen::<f64>() < 0.5 {  // 50% chance of synthetic order
-                    let mm_orders = self.generate_market_making_orders();
-                    for order in mm_orders {
-                        orders_processed += 1;
-                        self.simulate_network_latency();
-                        
-                        if !self.net.should_drop(&mut self.rng) {
-                            let order_side = order.side;
-                            let order_id = order.id;
-                            
-                            match self.engine.place(order) {
-                                Ok(trades) => {
-                                    if !trades.is_empty() {
-                                        self.update_metrics(&trades, order_side);
-                                        all_trades.extend(trades);
-                                    }
-                                }
-                                Err(e) => {
-                                    errors_encountered += 1;
-                                    log_engine_error(&e, Some(&format!("Hybrid mode synthetic order {}", order_id)));
-                                    
-                                    if !e.is_recoverable() {
-                                        return Err(e);
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
+        }
+        assert_eq!(sim.volume_profiles().odd_lot_volume().trades, 0);
+        
+        // Manual orders go through the engine's lot-size check
+        let odd = Order::new_limit(1_000_000, Side::Buy, 150, price_utils::from_f64(90.0), now_ns());
+        assert!(matches!(sim.place_order(odd), Err(EngineError::OddLot { .. })));
+    }
+
+    #[test]
+    fn test_self_match_prevention_keeps_taker_flow_off_own_quotes() {
+        let config = SelfMatchConfig { enabled: true, mode: StpMode::CancelResting, ..SelfMatchConfig::default() };
+        assert!(config.validate().is_ok());
+        let twice = BTreeMap::from([("a".to_string(), vec![Agent::Taker]), ("b".to_string(), vec![Agent::Taker])]);
+        assert!(SelfMatchConfig { groups: twice, ..config.clone() }.validate().is_err());
+        
+        // The taker's own group's quote is cancelled and it lifts the manual one behind it
+        let mut sim = Simulator::new(TestOrderBook::new()).with_self_match(&config);
+        let ts = now_ns();
+        sim.place_as(Agent::MarketMaker, Order::new_limit(1, Side::Sell, 30, 1_001_000, ts)).unwrap();
+        sim.place_as(Agent::Manual, Order::new_limit(2, Side::Sell, 30, 1_002_000, ts)).unwrap();
+        let trades = sim.place_as(Agent::Taker, Order::new_market(3, Side::Buy, 20, ts)).unwrap();
+        assert_eq!(trades.iter().map(|t| t.maker_id).collect::<Vec<_>>(), vec![2]);
+        assert_eq!(sim.settle_pruned_orders(), 1);
+        assert_eq!(sim.self_matches(), 1);
+        assert!(!sim.resting_agents.contains_key(&1));
+        
+        // Cancelling the taker instead refuses it when it would only meet its own quote
+        let config = SelfMatchConfig { enabled: true, ..SelfMatchConfig::default() };
+        let mut sim = Simulator::new(TestOrderBook::new()).with_self_match(&config);
+        sim.place_as(Agent::MarketMaker, Order::new_limit(1, Side::Sell, 30, 1_001_000, ts)).unwrap();
+        assert_eq!(sim.place_as(Agent::Taker, Order::new_limit(3, Side::Buy, 20, 1_001_000, ts)).unwrap(), vec![]);
+        assert!(!sim.resting_agents.contains_key(&3));
+        assert_eq!(sim.self_matches(), 1);
+        assert_eq!(sim.engine.depth_at(Side::Sell, 1_001_000), 30);
+        
+        // Sources outside the group still trade with it
+        assert_eq!(sim.place_as(Agent::Manual, Order::new_market(4, Side::Buy, 10, ts)).unwrap().len(), 1);
+        
+        // Replayed orders take no group from the order placed before them
+        let desk = BTreeMap::from([("desk".to_string(), vec![Agent::Manual])]);
+        let mut sim = Simulator::new(TestOrderBook::new()).with_self_match(&SelfMatchConfig { groups: desk, ..config });
+        sim.place_as(Agent::Manual, Order::new_limit(5, Side::Sell, 10, 1_001_000, ts)).unwrap();
+        let replayed = Order::new_limit(order_ids::historical(6).unwrap(), Side::Buy, 10, 1_001_000, ts);
+        assert_eq!(sim.apply_replay_event(MarketEvent::OrderPlacement(replayed)).len(), 1);
+        assert_eq!(sim.self_matches(), 0);
+    }
+    
+    #[test]
+    fn test_manual_fills_are_collected_with_paper_fills() {
+        let mut sim = Simulator::new(TestOrderBook::new());
+        sim.record_paper_fills(true);
+        let ts = now_ns();
+        sim.place_as(Agent::Manual, Order::new_limit(1, Side::Sell, 30, 1_001_000, ts)).unwrap();
+        sim.place_as(Agent::Taker, Order::new_market(2, Side::Buy, 10, ts)).unwrap();
+        sim.place_as(Agent::Manual, Order::new_market(3, Side::Buy, 5, ts)).unwrap();
+        
+        let fills = sim.take_paper_fills();
+        assert_eq!(
+            fills.iter().map(|fill| (fill.order_id, fill.account.as_str(), fill.side, fill.qty)).collect::<Vec<_>>(),
+            vec![(1, MANUAL_ACCOUNT, Side::Sell, 10), (3, MANUAL_ACCOUNT, Side::Buy, 5), (1, MANUAL_ACCOUNT, Side::Sell, 5)]
+        );
+    }
+    
+    #[test]
+    fn test_agent_pnl_attribution() {
+        let mut sim = Simulator::new(TestOrderBook::new());
+        let ts = now_ns();
+        sim.place_as(Agent::Historical, Order::new_limit(order_ids::historical(1).unwrap(), Side::Buy, 100, 999_000, ts)).unwrap();
+        sim.place_as(Agent::MarketMaker, Order::new_limit(2, Side::Sell, 30, 1_001_000, ts)).unwrap();
+        
+        // Mid is 1_000_000; the taker lifts the market maker's ask
+        sim.place_as(Agent::Taker, Order::new_market(3, Side::Buy, 20, ts)).unwrap();
+        sim.update_metrics();
+        
+        let maker = &sim.agent_metrics()[&Agent::MarketMaker];
+        assert_eq!(maker.inventory, -20);
+        assert_eq!(maker.maker.volume, 20);
+        assert_eq!(maker.maker.spread_capture, 20 * 1_000);
+        let taker = &sim.agent_metrics()[&Agent::Taker];
+        assert_eq!(taker.taker.spread_capture, -20 * 1_000);
+        assert_eq!(taker.pnl, taker.taker.pnl);
+        assert_eq!(sim.get_metrics().maker.volume, 20);
+        
+        // The rest of the quote still belongs to the market maker
+        sim.place_as(Agent::Historical, Order::new_market(order_ids::derived(1), Side::Buy, 10, ts)).unwrap();
+        sim.update_metrics();
+        assert_eq!(sim.agent_metrics()[&Agent::MarketMaker].maker.volume, 30);
+        assert!(sim.resting_agents.is_empty());
+        
+        sim.reset_metrics();
+        assert!(sim.agent_metrics().is_empty());
+    }
+
+    #[test]
+    fn test_headline_metrics_classify_fills_by_role() {
+        let mut sim = Simulator::new(TestOrderBook::new());
+        let ts = now_ns();
+        sim.place_as(Agent::Historical, Order::new_limit(order_ids::historical(1).unwrap(), Side::Buy, 100, 999_000, ts)).unwrap();
+        sim.place_as(Agent::MarketMaker, Order::new_limit(2, Side::Sell, 30, 1_001_000, ts)).unwrap();
+
+        // Taker flow hits the market maker's resting ask
+        sim.place_as(Agent::Taker, Order::new_market(3, Side::Buy, 20, ts)).unwrap();
+        sim.update_metrics();
+        let metrics = sim.get_metrics();
+        assert_eq!((metrics.maker.volume, metrics.taker.volume), (20, 0));
+        assert_eq!(metrics.inventory, -20);
+        assert_eq!(metrics.maker.spread_capture, 20 * 1_000);
+
+        // The market maker takes the historical bid
+        sim.place_as(Agent::MarketMaker, Order::new_market(4, Side::Sell, 10, ts)).unwrap();
+        sim.update_metrics();
+        let metrics = sim.get_metrics();
+        assert_eq!((metrics.maker.volume, metrics.taker.volume), (20, 10));
+        assert_eq!(metrics.taker.spread_capture, -10 * 1_000);
+        assert_eq!(metrics.inventory, -30);
+
+        // Fills between other agents leave it alone
+        sim.place_as(Agent::Manual, Order::new_limit(5, Side::Sell, 10, 1_000_500, ts)).unwrap();
+        sim.place_as(Agent::Taker, Order::new_market(6, Side::Buy, 10, ts)).unwrap();
+        assert_eq!(sim.get_metrics().inventory, -30);
+    }
+
+    #[test]
+    fn test_refused_remainder_is_not_tracked() {
+        let mut sim = Simulator::new(TestOrderBook::new()).with_crossed_book_policy(CrossedBookPolicy::RejectIncoming);
+        sim.apply_engine_command(EngineCommand::SetMatchFilter(HybridInteraction::Shadow.match_filter()));
+        let ts = now_ns();
+        sim.place_as(Agent::Historical, Order::new_limit(order_ids::historical(1).unwrap(), Side::Sell, 10, 1_001_000, ts)).unwrap();
+        sim.place_as(Agent::MarketMaker, Order::new_limit(2, Side::Sell, 10, 1_001_000, ts)).unwrap();
+
+        // The fill against the market maker stands, but the rest would lock
+        // the historical ask it may not trade with
+        let trades = sim.place_order(Order::new_limit(3, Side::Buy, 30, 1_001_000, ts)).unwrap();
+        assert_eq!(trades.iter().map(|trade| (trade.maker_id, trade.qty)).collect::<Vec<_>>(), vec![(2, 10)]);
+        assert_eq!(sim.resting_qty(3), None);
+        assert!(sim.resting_agents.is_empty());
+
+        // An order the book takes is tracked at what it left resting
+        sim.place_order(Order::new_limit(4, Side::Buy, 30, 1_000_000, ts)).unwrap();
+        assert_eq!(sim.resting_qty(4), Some(30));
+        assert_eq!(sim.resting_agents[&4].remaining, 30);
+    }
+
+    #[test]
+    fn test_priority_fee_charged_to_order_owner() {
+        use crate::queue_priority::PriorityFeeLevel;
+
+        let mut sim = Simulator::new(OrderBook::<PriorityFeeLevel>::new());
+        let ts = now_ns();
+        sim.place_as(Agent::Historical, Order::new_limit(order_ids::historical(1).unwrap(), Side::Sell, 10, 1_001_000, ts)).unwrap();
+        sim.place_as(Agent::MarketMaker, Order::new_limit(2, Side::Sell, 10, 1_001_000, ts)).unwrap();
+
+        assert_eq!(sim.pay_priority_fee(2, 300), Ok(300));
+        let maker = &sim.agent_metrics()[&Agent::MarketMaker];
+        assert_eq!((maker.priority_fees, maker.cash), (300, -300));
+        assert!(sim.pay_priority_fee(99, 300).is_err());
+
+        // The market maker's ask now fills first
+        let trades = sim.place_as(Agent::Taker, Order::new_market(3, Side::Buy, 10, ts)).unwrap();
+        assert_eq!(trades[0].maker_id, 2);
+        assert_eq!(sim.engine.get_metrics().priority_fees, 300);
+
+        // A FIFO book takes no fee and charges nobody
+        let mut fifo = Simulator::new(TestOrderBook::new());
+        fifo.place_as(Agent::MarketMaker, Order::new_limit(2, Side::Sell, 10, 1_001_000, ts)).unwrap();
+        assert_eq!(fifo.pay_priority_fee(2, 300), Ok(0));
+        assert!(!fifo.agent_metrics().contains_key(&Agent::MarketMaker));
+    }
+
+    #[test]
+    fn test_saved_book_restores_orders_and_counters() {
+        let mut sim = Simulator::new(TestOrderBook::new());
+        let ts = now_ns();
+        sim.place_as(Agent::Historical, Order::new_limit(order_ids::historical(1).unwrap(), Side::Sell, 10, 1_001_000, ts)).unwrap();
+        sim.place_as(Agent::MarketMaker, Order::new_limit(2, Side::Sell, 10, 1_001_000, ts)).unwrap();
+        sim.place_as(Agent::MarketMaker, Order::new_limit(3, Side::Buy, 5, 999_000, ts)).unwrap();
+        sim.next_order_id = 4;
+        let image = sim.save_book();
+        assert_eq!(image.orders.iter().map(|saved| (saved.order.id, saved.agent)).collect::<Vec<_>>(),
+                   vec![(3, Some(Agent::MarketMaker)), (order_ids::historical(1).unwrap(), None), (2, Some(Agent::MarketMaker))]);
+
+        // Trade the book away and move the counters on
+        sim.place_as(Agent::Taker, Order::new_market(10, Side::Buy, 20, ts)).unwrap();
+        sim.next_order_id = 11;
+        assert!(sim.engine.depth_snapshot().asks.is_empty());
+
+        assert_eq!(sim.load_book(&image), Ok(3));
+        assert_eq!(sim.next_order_id, 4);
+        assert!(sim.current_time >= image.current_time);
+        let snapshot = sim.engine.depth_snapshot();
+        assert_eq!(snapshot.bids.iter().map(|level| (level.price, level.qty)).collect::<Vec<_>>(), vec![(999_000, 5)]);
+        assert_eq!(snapshot.asks.iter().map(|level| (level.price, level.qty)).collect::<Vec<_>>(), vec![(1_001_000, 20)]);
+
+        // Queue priority and fill attribution survive the restore
+        let trades = sim.place_as(Agent::Taker, Order::new_market(12, Side::Buy, 15, ts)).unwrap();
+        assert_eq!(trades.iter().map(|trade| (trade.maker_id, trade.qty)).collect::<Vec<_>>(),
+                   vec![(order_ids::historical(1).unwrap(), 10), (2, 5)]);
+        assert_eq!(sim.resting_agents[&2].remaining, 5);
+    }
+
+    #[test]
+    fn test_saved_book_keeps_fees_and_filter_and_loads_whole() {
+        use crate::queue_priority::PriorityFeeLevel;
+
+        let mut sim = Simulator::new(OrderBook::<PriorityFeeLevel>::new());
+        sim.set_mode(SimulationMode::Hybrid);
+        sim.set_hybrid_interaction(HybridInteraction::Shadow);
+        let ts = now_ns();
+        sim.place_as(Agent::MarketMaker, Order::new_limit(1, Side::Sell, 10, 1_001_000, ts)).unwrap();
+        sim.place_as(Agent::MarketMaker, Order::new_limit(2, Side::Sell, 10, 1_001_000, ts)).unwrap();
+        sim.pay_priority_fee(2, 300).unwrap();
+        let image = sim.save_book();
+        assert_eq!(image.interaction, Some(HybridInteraction::Shadow));
+        assert_eq!(image.orders.iter().map(|saved| (saved.order.id, saved.priority_fee)).collect::<Vec<_>>(), vec![(2, 300), (1, 0)]);
+
+        // Restored under the saved filter, a historical bid rests crossing
+        // the synthetic asks, and the fee keeps order 2 ahead of a smaller one
+        let mut restored = Simulator::new(OrderBook::<PriorityFeeLevel>::new());
+        restored.set_mode(SimulationMode::Hybrid);
+        assert_eq!(restored.load_book(&image), Ok(2));
+        assert_eq!(restored.hybrid_interaction(), HybridInteraction::Shadow);
+        let historical = Order::new_limit(order_ids::historical(3).unwrap(), Side::Buy, 5, 1_002_000, ts);
+        assert!(restored.place_as(Agent::Historical, historical).unwrap().is_empty());
+        restored.pay_priority_fee(1, 200).unwrap();
+        let trades = restored.place_as(Agent::Taker, Order::new_market(4, Side::Buy, 10, ts)).unwrap();
+        assert_eq!(trades.iter().map(|trade| trade.maker_id).collect::<Vec<_>>(), vec![2]);
+
+        // An image the engine refuses part of leaves the book as it was
+        let before = restored.save_book();
+        let mut bad = image.clone();
+        bad.next_order_id = 99;
+        bad.orders.push(ImageOrder { order: Order::new_limit(5, Side::Buy, 0, 999_000, ts), agent: None, owner: None, priority_fee: 0 });
+        assert!(restored.load_book(&bad).is_err());
+        let after = restored.save_book();
+        assert_eq!((after.orders, after.next_order_id), (before.orders, before.next_order_id));
+        assert_eq!(restored.resting_agents[&1].agent, Agent::MarketMaker);
+    }
+
+    #[test]
+    fn test_configured_agent_population() {
+        use crate::agents::{AgentRegistry, AgentSpec};
+
+        let specs = vec![
+            AgentSpec::new("market_maker").with_count(2).with_param("target_spread", 400),
+            AgentSpec::new("taker").with_count(2).with_seed(5).with_param("market_order_prob", 0.8),
+        ];
+        let run = || {
+            let registry = AgentRegistry::builtin(&MarketMakerConfig::default(), &OrderGenerationConfig::default());
+            let mut sim = Simulator::with_seed(TestOrderBook::new(), 7)
+                .with_agents(registry.build(&specs, 7).unwrap());
+            let trades = sim.run_steps(200).unwrap();
+            (sim, trades)
+        };
+
+        let (sim, trades) = run();
+        assert!(!trades.is_empty());
+        assert!(sim.agent_metrics()[&Agent::MarketMaker].maker.volume > 0);
+        assert!(sim.agent_metrics()[&Agent::Taker].taker.volume > 0);
+
+        // Agents only trade with each other, so their positions net out
+        let stats = sim.agent_stats();
+        assert_eq!(stats.iter().map(|agent| agent.id).collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+        assert_eq!(stats.iter().map(|agent| agent.metrics.inventory).sum::<i64>(), 0);
+        assert!(stats.iter().any(|agent| agent.metrics.inventory != 0));
+        assert!(stats.iter().all(|agent| agent.orders > 0));
+        assert_eq!(sim.snapshot().agents, stats);
+
+        // The same seeds give the same run
+        let (mut sim, replayed) = run();
+        assert_eq!(
+            trades.iter().map(|trade| (trade.price, trade.qty)).collect::<Vec<_>>(),
+            replayed.iter().map(|trade| (trade.price, trade.qty)).collect::<Vec<_>>()
+        );
+
+        // A paused agent stops sending orders
+        assert!(sim.set_agent_paused(3, true));
+        let orders = sim.agent_stats_for(3).unwrap().orders;
+        sim.run_steps(20).unwrap();
+        assert_eq!(sim.agent_stats_for(3).unwrap().orders, orders);
+        assert!(!sim.set_agent_paused(99, true));
+
+        // Removing an agent cancels its resting quotes
+        let removed = sim.remove_agent(1).unwrap();
+        assert_eq!((removed.id, removed.kind.as_str()), (1, "market_maker"));
+        assert!(sim.resting_agents.values().all(|resting| resting.owner != Some(1)));
+        assert!(sim.agent_stats_for(1).is_none());
+        assert!(sim.remove_agent(1).is_none());
+
+        // An agent added at runtime trades alongside the built-in flow
+        let registry = AgentRegistry::builtin(&MarketMakerConfig::default(), &OrderGenerationConfig::default());
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 7);
+        let id = sim.add_agent(registry.create(&AgentSpec::new("taker").with_seed(5)).unwrap());
+        sim.run_steps(200).unwrap();
+        assert!(sim.agent_stats_for(id).unwrap().orders > 0);
+        assert!(sim.agent_metrics()[&Agent::MarketMaker].maker.volume > 0);
+    }
+
+    #[test]
+    fn test_metrics_tracking() {
+        let engine = TestOrderBook::new();
+        let mut sim = Simulator::with_seed(engine, 42);
+        
+        let _initial_metrics = sim.get_metrics().clone();
+        
+        // Run simulation to generate trades
+        let _ = sim.run_steps(20);
+        
+        let final_metrics = sim.get_metrics();
+        
+        // Metrics should have been updated if trades occurred
+        // (exact values depend on random simulation, but a position can only
+        // come from trading, which moves cash)
+        assert!(final_metrics.inventory == 0 || final_metrics.cash != 0);
+    }
+
+    #[test]
+    fn test_spread_history_tracking() {
+        let engine = TestOrderBook::new();
+        let mut sim = Simulator::with_seed(engine, 42);
+        
+        // Run simulation to generate market activity
+        let _ = sim.run_steps(30);
+        
+        let snapshot = sim.snapshot();
+        
+        // Should have some spread history if trades occurred
+        // (depends on random simulation generating trades)
+        assert!(snapshot.recent_spreads.len() <= 400);  // Bounded by max size
+        
+        for (ts, spread) in &snapshot.recent_spreads {
+            assert!(*ts > 0);
+            assert!(*spread >= 0);  // Spread should be non-negative
+        }
+    }
+
+    #[test]
+    fn test_memory_budgets_bound_histories() {
+        let config = MemoryConfig {
+            trade_tape: 5,
+            metrics_history: 4,
+            closed_orders: 3,
+            ..Default::default()
+        };
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 42)
+            .with_memory_config(&config)
+            .with_spread_history(6);
+        let trades = sim.run_steps(200).unwrap();
+        assert!(trades.len() > 5);
+
+        let usage = sim.history_usage();
+        assert_eq!(usage["trade_tape"], HistoryUsage { len: 5, capacity: 5, evicted: trades.len() - 5 });
+        assert_eq!(usage["spread_history"].capacity, 6);
+        assert_eq!(usage["metrics_history"].capacity, 4);
+        assert_eq!(usage["paper_fills"].capacity, 3);
+        assert_eq!(sim.recent_trades(), trades[trades.len() - 5..]);
+
+        let before: usize = usage.values().map(|usage| usage.len).sum();
+        let dropped = sim.shed_history();
+        let after: usize = sim.history_usage().values().map(|usage| usage.len).sum();
+        assert_eq!(before - after, dropped);
+        assert_eq!(sim.recent_trades().len(), 3);
+    }
+
+    #[test]
+    fn test_inventory_bands_widen_and_shrink_quotes() {
+        use crate::agents::{AgentRegistry, AgentSpec};
+
+        let config = MarketMakerConfig {
+            target_spread: 100,
+            max_inventory: 100,
+            inventory_bands: vec![
+                InventoryBand { threshold: 0.5, spread_multiplier: 2.0, size_multiplier: 0.5 },
+                InventoryBand { threshold: 0.8, spread_multiplier: 4.0, size_multiplier: 0.25 },
+            ],
+            ..MarketMakerConfig::default()
+        };
+        assert!(config.validate_bands().is_ok());
+        assert_eq!((config.inventory_band(49), config.inventory_band(50), config.inventory_band(-80)), (0, 1, 2));
+        assert_eq!(config.quote_shape(0), (50, 100));
+        assert_eq!(config.quote_shape(60), (100, 50));
+        assert_eq!(config.quote_shape(-90), (200, 25));
+        let mut reversed = config.clone();
+        reversed.inventory_bands.reverse();
+        assert!(reversed.validate_bands().is_err());
+
+        // The built-in maker and a configured one both report band changes
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 42)
+            .with_market_maker_config(MarketMakerConfig { max_inventory: 200, ..config });
+        sim.run_steps(300).unwrap();
+        assert!(sim.market_maker_band().transitions > 0);
+
+        let bands = toml::Value::try_from(vec![
+            InventoryBand { threshold: 0.25, spread_multiplier: 2.0, size_multiplier: 0.5 },
+        ]).unwrap();
+        let registry = AgentRegistry::builtin(&MarketMakerConfig::default(), &OrderGenerationConfig::default());
+        let specs = [
+            AgentSpec::new("market_maker").with_param("max_inventory", 200).with_param("inventory_bands", bands),
+            AgentSpec::new("taker"),
+        ];
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 42).with_agents(registry.build(&specs, 7).unwrap());
+        sim.run_steps(300).unwrap();
+        let stats = sim.agent_stats();
+        assert!(stats[0].inventory_band.is_some_and(|status| status.transitions > 0));
+        assert_eq!(stats[1].inventory_band, None);
+    }
+
+    #[test]
+    fn test_two_sided_quotes_replace_rather_than_stack() {
+        use crate::agents::{AgentRegistry, AgentSpec};
+
+        let registry = AgentRegistry::builtin(&MarketMakerConfig::default(), &OrderGenerationConfig::default());
+        let specs = [
+            AgentSpec::new("market_maker").with_param("two_sided_quotes", true),
+            AgentSpec::new("taker"),
+        ];
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 42).with_agents(registry.build(&specs, 7).unwrap());
+        sim.record_events(true);
+        let maker = sim.agent_stats()[0].id;
+        for _ in 0..300 {
+            sim.run_steps(1).unwrap();
+            // The maker never has more than its one bid and one ask on the book
+            let resting = sim.engine.open_orders().iter()
+                .filter(|order| sim.resting_agents.get(&order.id).is_some_and(|resting| resting.owner == Some(maker)))
+                .count();
+            assert!(resting <= 2, "{} maker orders resting", resting);
+        }
+        let stats = sim.agent_stats();
+        assert!(stats[0].orders > 100);
+        assert!(stats[0].metrics.maker.volume > 0);
+
+        // Every leg placed and pulled is recorded, so the legs still resting
+        // are exactly those placed and not yet pulled
+        let (mut placed, mut pulled) = (HashSet::new(), HashSet::new());
+        for event in sim.take_recorded_events() {
+            match event {
+                MarketEvent::OrderPlacement(order) => { placed.insert(order.id); }
+                MarketEvent::OrderCancellation { order_id, .. } => { pulled.insert(order_id); }
+                _ => {}
             }
         }
+        assert!(!pulled.is_empty());
+        for order in sim.engine.open_orders().iter().filter(|order| sim.resting_agents.get(&order.id).is_some_and(|resting| resting.owner == Some(maker))) {
+            assert!(placed.contains(&order.id) && !pulled.contains(&order.id));
+        }
+
+        // Manual quotes are owned apart from the agents
+        let outcome = sim.quote_order(maker, Some((90_0000, 10)), Some((110_0000, 10))).unwrap();
+        assert_eq!(outcome.resting.len(), 2);
+        assert!(outcome.cancelled.is_empty());
+    }
+
+    #[test]
+    fn test_simulation_reset() {
+        let engine = TestOrderBook::new();
+        let mut sim = Simulator::with_seed(engine, 42);
+        
+        // Run simulation
+        let _ = sim.run_steps(10);
+        
+        // Reset simulation
+        sim.reset();
+        
+        // Check that state was reset
+        assert_eq!(sim.get_metrics().inventory, 0);
+        assert_eq!(sim.get_metrics().cash, 0);
+        assert_eq!(sim.get_metrics().pnl, 0);
+        assert!(sim.spread_history.is_empty());
+        assert_eq!(sim.next_order_id, 1);
+        assert!(sim.current_time() > 0);  // Time should be reset to a valid timestamp
+    }
+
+    #[test]
+    fn test_simulation_modes() {
+        let engine = TestOrderBook::new();
+        let mut sim = Simulator::new(engine);
         
-        // Update spread history if trades occurred
-        if !all_trades.is_empty() {
-            self.update_spread_history();
-        }
+        // Test mode setting
+        assert_eq!(sim.mode, SimulationMode::Synthetic);
         
-        // Log step completion metrics
-        let step_duration = step_start.elapsed();
-        if errors_encountered > 0 {
-            tracing::warn!("Simulation step completed with {} errors out of {} orders in {:?}", 
-                          errors_encountered, orders_processed, step_duration);
-        } else if orders_processed > 0 {
-            tracing::trace!("Simulation step: {} orders, {} trades in {:?}", 
-                           orders_processed, all_trades.len(), step_duration);
-        }
+        sim.set_mode(SimulationMode::Historical);
+        assert_eq!(sim.mode, SimulationMode::Historical);
         
-        // Log performance data for monitoring
-        let step_duration_ms = step_duration.as_millis() as f64;
-        log_data_ingestion("simulation_step", orders_processed, errors_encountered, step_duration_ms);
+        sim.set_mode(SimulationMode::Hybrid);
+        assert_eq!(sim.mode, SimulationMode::Hybrid);
+    }
+
+    #[test]
+    fn test_configuration_builders() {
+        let engine = TestOrderBook::new();
+        let net_model = NetModel::new(200_000, 100_000, 0.002, 0.02);
+        let mm_config = MarketMakerConfig {
+            target_spread: price_utils::from_f64(0.02),
+            max_inventory: 500,
+            order_size: 50,
+            mm_probability: 0.8,
+            inventory_skew: 0.002,
+            inventory_bands: Vec::new(),
+            two_sided_quotes: false,
+        };
+        let order_config = OrderGenerationConfig {
+            market_order_prob: 0.4,
+            mean_order_interval_ns: 2_000_000,
+            min_order_size: 5,
+            max_order_size: 200,
+            price_range_fraction: 0.03,
+            buy_probability: 0.5,
+            max_slippage_ticks: None,
+        };
         
-        Ok(all_trades)
+        let sim = Simulator::new(engine)
+            .with_network_model(net_model.clone())
+            .with_market_maker_config(mm_config.clone())
+            .with_order_generation_config(order_config.clone());
+        
+        assert_eq!(sim.net.order_path, net_model.order_path);
+        assert_eq!(sim.market_maker_config.target_spread, mm_config.target_spread);
+        assert_eq!(sim.order_gen_config.market_order_prob, order_config.market_order_prob);
     }
 
-    /// Run simulation for a specified number of steps
-    pub fn run_steps(&mut self, steps: usize) -> EngineResult<Vec<Trade>> {
-        let mut all_trades = Vec::new();
+    fn write_replay_file(events: &[MarketEvent]) -> tempfile::NamedTempFile {
+        use std::io::Write;
         
-        for _ in 0..steps {
-            let trades = self.step()?;
-            all_trades.extend(trades);
+        let mut file = tempfile::Builder::new().suffix(".jsonl").tempfile().unwrap();
+        for event in events {
+            writeln!(file, "{}", serde_json::to_string(event).unwrap()).unwrap();
         }
+        file.flush().unwrap();
+        file
+    }
+
+    #[test]
+    fn test_replay_mode_applies_only_book_events() {
+        use crate::data::JsonDataSource;
         
-        Ok(all_trades)
+        let file = write_replay_file(&[
+            MarketEvent::OrderPlacement(Order::new_limit(1, Side::Buy, 100, 490000, 1_000)),
+            MarketEvent::OrderPlacement(Order::new_limit(2, Side::Sell, 100, 510000, 2_000)),
+            MarketEvent::Trade { price: 500000, qty: 10, side: Side::Buy, timestamp: 3_000, trade_id: None },
+            MarketEvent::OrderModification { order_id: 1, new_qty: None, new_price: Some(495000), timestamp: 4_000 },
+            MarketEvent::OrderCancellation { order_id: 2, timestamp: 5_000, reason: None },
+        ]);
+        
+        let source = JsonDataSource::new(file.path()).unwrap();
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 42).with_replay_source(Box::new(source));
+        assert_eq!(sim.mode(), SimulationMode::Replay);
+        
+        // Clock anchors at the first event
+        assert!(sim.advance_replay(0).unwrap().is_empty());
+        assert_eq!(sim.engine.best_bid(), Some(490000));
+        assert_eq!(sim.engine.best_ask(), None);
+        assert_eq!(sim.current_time(), 1_000);
+        
+        // Trade prints are reported but never executed against the book
+        let trades = sim.advance_replay(2_000).unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].price, 500000);
+        assert_eq!(sim.engine.depth_at(Side::Buy, 490000), 100);
+        assert_eq!(sim.engine.depth_at(Side::Sell, 510000), 100);
+        assert_eq!(sim.get_metrics().inventory, 0);
+        
+        sim.advance_replay(2_000).unwrap();
+        assert_eq!(sim.engine.best_bid(), Some(495000));
+        assert_eq!(sim.engine.best_ask(), None);
+        assert!(sim.replay_status().finished);
     }
 
-    /// Get current market snapshot
-    pub fn snapshot(&self) -> DepthSnapshot {
-        let mut snapshot = self.engine.snapshot();
+    #[test]
+    fn test_unknown_order_references_are_counted_or_placeheld() {
+        use crate::data::JsonDataSource;
+        use crate::order_refs::UnmatchedOrderPolicy;
         
-        // Override with simulator's metrics and spread history
-        snapshot.metrics = self.metrics.clone();
-        snapshot.recent_spreads = self.recent_spreads.to_vec();
-        snapshot.ts = self.current_time;
+        // A partial-day file: orders 7 and 8 were resting before it starts
+        let events = [
+            MarketEvent::OrderPlacement(Order::new_limit(1, Side::Buy, 100, 490000, 1_000)),
+            MarketEvent::OrderPlacement(Order::new_limit(2, Side::Sell, 100, 510000, 2_000)),
+            MarketEvent::OrderCancellation { order_id: 7, timestamp: 3_000, reason: None },
+            MarketEvent::OrderModification { order_id: 8, new_qty: Some(40), new_price: Some(508000), timestamp: 4_000 },
+            MarketEvent::OrderModification { order_id: 8, new_qty: Some(30), new_price: None, timestamp: 5_000 },
+            // Order 3 fills at once, so later references to it are late rather than unknown
+            MarketEvent::OrderPlacement(Order::new_limit(3, Side::Sell, 20, 490000, 6_000)),
+            MarketEvent::OrderModification { order_id: 3, new_qty: Some(20), new_price: Some(495000), timestamp: 7_000 },
+            MarketEvent::OrderCancellation { order_id: 3, timestamp: 8_000, reason: None },
+        ];
+        let run = |policy: UnmatchedOrderPolicy| {
+            let file = write_replay_file(&events);
+            let source = JsonDataSource::new(file.path()).unwrap();
+            let mut sim = Simulator::with_seed(TestOrderBook::new(), 42)
+                .with_unmatched_order_policy(policy)
+                .with_replay_source(Box::new(source));
+            sim.advance_replay(0).unwrap();
+            sim.advance_replay(10_000).unwrap();
+            assert!(sim.replay_status().finished);
+            sim
+        };
         
-        snapshot
+        let ignored = run(UnmatchedOrderPolicy::Ignore);
+        let report = ignored.data_source_metadata().unwrap().unmatched_orders;
+        assert_eq!((report.cancels, report.modifications, report.distinct_orders, report.placeholders), (1, 2, 2, 0));
+        assert_eq!(report.sample_ids, vec![7, 8]);
+        assert_eq!(ignored.engine.best_ask(), Some(510000));
+        assert_eq!(ignored.engine.depth_at(Side::Buy, 490000), 80);
+        
+        // The placeholder rests as modified and later modifications find it
+        let placed = run(UnmatchedOrderPolicy::Placeholder);
+        let report = placed.data_source_metadata().unwrap().unmatched_orders;
+        assert_eq!((report.cancels, report.modifications, report.placeholders), (1, 1, 1));
+        assert_eq!(placed.engine.best_ask(), Some(508000));
+        assert_eq!(placed.engine.depth_at(Side::Sell, 508000), 30);
+        assert_eq!(placed.engine.depth_at(Side::Sell, 495000), 0);
     }
 
-    /// Place an order directly (for testing or manual intervention)
-    pub fn place_order(&mut self, order: Order) -> EngineResult<Vec<Trade>> {
-        use crate::logging::log_order_operation;
+    #[test]
+    fn test_replayed_events_are_stamped_when_read() {
+        use crate::data::JsonDataSource;
         
-        log_order_operation("MANUAL_PLACE", order.id, Some("Direct order placement"));
+        let file = write_replay_file(&[
+            MarketEvent::OrderPlacement(Order::new_limit(1, Side::Buy, 100, 490000, 1_000)),
+            MarketEvent::Heartbeat { timestamp: 2_000 },
+            MarketEvent::OrderPlacement(Order::new_limit(2, Side::Sell, 100, 510000, 3_000)),
+        ]);
+        let source = JsonDataSource::new(file.path()).unwrap();
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 42).with_replay_source(Box::new(source));
+        sim.advance_replay(0).unwrap();
+        assert!(sim.take_pipeline_stamps().is_empty());
         
-        match self.engine.place(order) {
-            Ok(trades) => {
-                if !trades.is_empty() {
-                    // Update metrics based on the order side (assume buy side for manual orders)
-                    self.update_metrics(&trades, Side::Buy);
-                    self.update_spread_history();
-                }
-                Ok(trades)
-            }
-            Err(e) => {
-                use crate::logging::log_engine_error;
-                log_engine_error(&e, Some("Manual order placement"));
-                Err(e)
-            }
+        // The last event is read ahead of the clock, and its wait for the
+        // clock counts towards ingest to match
+        sim.record_pipeline_stamps(true);
+        sim.advance_replay(1_000).unwrap();
+        assert!(sim.take_pipeline_stamps().is_empty());
+        std::thread::sleep(Duration::from_millis(2));
+        sim.advance_replay(1_000).unwrap();
+        let stamps = sim.take_pipeline_stamps();
+        assert_eq!(stamps.iter().map(|stamp| stamp.event_ts).collect::<Vec<_>>(), vec![3_000]);
+        assert!(stamps[0].match_ns - stamps[0].ingest_ns >= 2_000_000);
+        assert!(sim.take_pipeline_stamps().is_empty());
+    }
+
+    #[test]
+    fn test_replay_transport_controls() {
+        use crate::data::JsonDataSource;
+        
+        let file = write_replay_file(&[
+            MarketEvent::OrderPlacement(Order::new_limit(1, Side::Buy, 100, 490000, 1_000)),
+            MarketEvent::OrderPlacement(Order::new_limit(2, Side::Sell, 100, 510000, 2_000)),
+            MarketEvent::OrderCancellation { order_id: 1, timestamp: 3_000, reason: None },
+        ]);
+        
+        let source = JsonDataSource::new(file.path()).unwrap();
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 42).with_replay_source(Box::new(source));
+        
+        sim.advance_replay(5_000).unwrap();
+        assert_eq!(sim.engine.best_bid(), None);
+        
+        // An agent order on its way and one held by a gateway outage
+        let order = Order::new_limit(9, Side::Buy, 10, 480000, 4_000);
+        sim.in_flight.push(InFlightOrder {
+            due: 6_000,
+            owner: 1,
+            role: Agent::Taker,
+            order: order.clone(),
+            latency_ns: 1_000,
+            zero_latency: CostModel::from_snapshot(&sim.snapshot()).sweep(Side::Buy, 10),
+        });
+        sim.gateway = Gateway::new(vec![crate::gateway::GatewayOutage { start_ms: 0, duration_ms: 60_000 }], crate::gateway::OutagePolicy::Queue);
+        sim.gateway.poll(4_000);
+        assert!(sim.gateway.submit(PendingOrder { role: Agent::Taker, owner: Some(1), order }).unwrap().is_none());
+        
+        // Seeking rebuilds the book as of the target time, without either
+        sim.seek_replay(2_500).unwrap();
+        assert!(sim.in_flight.is_empty());
+        assert_eq!((sim.gateway.queued(), sim.gateway_down()), (0, false));
+        assert_eq!(sim.engine.best_bid(), Some(490000));
+        assert_eq!(sim.engine.best_ask(), Some(510000));
+        assert_eq!(sim.replay_status().clock, Some(2_500));
+        assert_eq!(sim.replay_status().events_applied, 2);
+        
+        // Paused replay does not move the clock
+        sim.pause_replay();
+        sim.step().unwrap();
+        sim.step().unwrap();
+        assert_eq!(sim.replay_status().clock, Some(2_500));
+        assert_eq!(sim.engine.best_bid(), Some(490000));
+        
+        assert!(sim.set_replay_speed(0.0).is_err());
+        assert!(sim.set_replay_speed(4.0).is_ok());
+        assert_eq!(sim.replay_status().speed, 4.0);
+    }
+
+    #[test]
+    fn test_attach_and_detach_data_source() {
+        use crate::data::JsonDataSource;
+        
+        let file = write_replay_file(&[
+            MarketEvent::OrderPlacement(Order::new_limit(1, Side::Buy, 100, 490000, 1_000)),
+        ]);
+        
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 42);
+        sim.run_steps(20).unwrap();
+        
+        let source = Box::new(JsonDataSource::new(file.path()).unwrap());
+        assert!(sim.attach_data_source(source, SimulationMode::Synthetic).is_err());
+        
+        // Switching to replay starts from an empty book
+        let source = Box::new(JsonDataSource::new(file.path()).unwrap());
+        assert!(sim.attach_data_source(source, SimulationMode::Replay).unwrap().is_none());
+        assert_eq!(sim.mode(), SimulationMode::Replay);
+        assert_eq!(sim.engine.best_ask(), None);
+        assert_eq!(sim.data_source_metadata().unwrap().source_type, "JSON");
+        
+        sim.advance_replay(0).unwrap();
+        assert_eq!(sim.engine.best_bid(), Some(490000));
+        
+        assert!(sim.detach_data_source().is_some());
+        assert_eq!(sim.mode(), SimulationMode::Synthetic);
+        assert!(!sim.has_data_source());
+        assert!(sim.step().is_ok());
+    }
+
+    #[test]
+    fn test_feed_silence_counts_heartbeats() {
+        use crate::data::JsonDataSource;
+        
+        let file = write_replay_file(&[
+            MarketEvent::Heartbeat { timestamp: 1_000 },
+            MarketEvent::OrderPlacement(Order::new_limit(1, Side::Buy, 100, 490000, 2_000)),
+        ]);
+        
+        let sim = Simulator::with_seed(TestOrderBook::new(), 42);
+        assert_eq!(sim.feed_silence(), None);
+        
+        let mut source = JsonDataSource::new(file.path()).unwrap();
+        source.set_paused(true).unwrap();
+        let mut sim_with_feed = Simulator::with_seed(TestOrderBook::new(), 42).with_data_source(Box::new(source));
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert!(sim_with_feed.feed_silence().unwrap() >= std::time::Duration::from_millis(20));
+        
+        // A heartbeat proves the feed is alive without touching the book
+        sim_with_feed.step().unwrap();
+        assert!(sim_with_feed.feed_silence().unwrap() < std::time::Duration::from_millis(20));
+        assert_eq!(sim_with_feed.current_time(), 1_000);
+        assert_eq!(sim_with_feed.engine.best_bid(), None);
+    }
+
+    #[test]
+    fn test_status_changes_are_recorded_as_activity() {
+        use crate::data::{JsonDataSource, MarketStatusType};
+        
+        let status = |status, timestamp| MarketEvent::MarketStatus { status, timestamp, message: Some("circuit breaker".to_string()) };
+        let file = write_replay_file(&[status(MarketStatusType::Open, 1_000), status(MarketStatusType::Halted, 2_000)]);
+        let source = JsonDataSource::new(file.path()).unwrap();
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 42).with_data_source(Box::new(source));
+        
+        // Nothing is collected until asked for
+        sim.step().unwrap();
+        assert!(sim.take_activity().is_empty());
+        
+        sim.record_activity(true);
+        sim.step().unwrap();
+        let activity = sim.take_activity();
+        assert_eq!(activity.len(), 1);
+        assert_eq!(activity[0].status, Some(MarketStatusType::Halted));
+        assert_eq!(activity[0].sim_time, Some(2_000));
+        assert!(sim.take_activity().is_empty());
+    }
+
+    #[test]
+    fn test_pre_open_orders_wait_for_the_open() {
+        use crate::auction::AuctionConfig;
+        use crate::data::{JsonDataSource, MarketStatusType};
+        
+        let status = |status, ms| MarketEvent::MarketStatus { status, timestamp: ms_to_ns(ms), message: None };
+        let limit = |id, side, qty, price, ms| MarketEvent::OrderPlacement(Order::new_limit(id, side, qty, price, ms_to_ns(ms)));
+        let file = write_replay_file(&[
+            status(MarketStatusType::PreMarket, 1),
+            limit(1, Side::Buy, 100, 101_0000, 2),
+            limit(2, Side::Sell, 60, 100_0000, 3),
+            limit(3, Side::Sell, 50, 102_0000, 4),
+            MarketEvent::OrderCancellation { order_id: 3, timestamp: ms_to_ns(5), reason: None },
+            status(MarketStatusType::Open, 6),
+        ]);
+        let source = JsonDataSource::new(file.path()).unwrap();
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 42)
+            .with_data_source(Box::new(source))
+            .with_auction(AuctionConfig { enabled: true, imbalance_interval_ms: 1, ..AuctionConfig::default() });
+        
+        // Orders queue up instead of matching, and the imbalance follows them
+        sim.run_steps(4).unwrap();
+        assert_eq!(sim.pre_open_orders(), 3);
+        assert_eq!(sim.engine.best_bid(), None);
+        let imbalance = *sim.auction_imbalance().unwrap();
+        assert_eq!(imbalance.ts, ms_to_ns(4));
+        assert_eq!((imbalance.indicative_price, imbalance.paired_qty), (Some(100_0000), 60));
+        assert_eq!((imbalance.imbalance_qty, imbalance.imbalance_side), (40, Some(Side::Buy)));
+        assert_eq!(sim.snapshot().auction, Some(imbalance));
+        
+        // A cancel removes the order from the queue
+        sim.step().unwrap();
+        assert_eq!(sim.pre_open_orders(), 2);
+        
+        // At the open the queue uncrosses at the indicative price and the
+        // rest enters the book
+        let trades = sim.step().unwrap();
+        assert_eq!(trades.iter().map(|trade| trade.qty).sum::<Qty>(), 60);
+        assert!(trades.iter().all(|trade| trade.price == 100_0000));
+        assert_eq!(sim.engine.best_bid(), Some(101_0000));
+        assert_eq!(sim.pre_open_orders(), 0);
+        assert_eq!(sim.snapshot().auction, None);
+
+        // Seeking back drops whatever was queued
+        sim.set_market_status(MarketStatusType::PreMarket, None);
+        sim.place_order(Order::new_limit(10, Side::Sell, 5, 103_0000, sim.current_time())).unwrap();
+        assert_eq!(sim.pre_open_orders(), 1);
+        sim.seek_replay(ms_to_ns(2)).unwrap();
+        assert_eq!(sim.pre_open_orders(), 0);
+    }
+
+    #[test]
+    fn test_historical_mode_survives_bad_record() {
+        use crate::data::{ErrorPolicy, JsonDataSource};
+        use std::io::Write;
+        
+        let trade = |timestamp| MarketEvent::Trade {
+            price: 490000,
+            qty: 10,
+            side: Side::Buy,
+            timestamp,
+            trade_id: None,
+        };
+        let mut file = write_replay_file(&[trade(1_000)]);
+        writeln!(file, "not json").unwrap();
+        writeln!(file, "{}", serde_json::to_string(&trade(2_000)).unwrap()).unwrap();
+        writeln!(file, "also not json").unwrap();
+        writeln!(file, "{}", serde_json::to_string(&trade(3_000)).unwrap()).unwrap();
+        file.flush().unwrap();
+        
+        // Fail-fast reports the bad line without abandoning historical mode
+        let mut source = JsonDataSource::new(file.path()).unwrap();
+        source.set_paused(true).unwrap();
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 42).with_data_source(Box::new(source));
+        assert!(sim.step().is_ok());
+        assert!(sim.step().is_err());
+        assert_eq!(sim.mode(), SimulationMode::Historical);
+        assert!(sim.step().is_ok());
+        assert_eq!(sim.current_time(), 2_000);
+        
+        // Skip drops both bad lines
+        let mut source = JsonDataSource::new(file.path()).unwrap();
+        source.set_paused(true).unwrap();
+        source.set_error_policy(ErrorPolicy::Skip).unwrap();
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 42).with_data_source(Box::new(source));
+        sim.run_steps(3).unwrap();
+        assert_eq!(sim.current_time(), 3_000);
+        assert_eq!(sim.data_source_metadata().unwrap().skipped_records, 2);
+    }
+
+    #[test]
+    fn test_historical_ids_do_not_collide_with_synthetic_orders() {
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 42);
+        sim.set_mode(SimulationMode::Hybrid);
+        
+        // A synthetic order and a historical order both numbered 1
+        sim.engine.place(Order::new_limit(1, Side::Buy, 100, 490000, now_ns())).unwrap();
+        sim.process_market_event(MarketEvent::OrderPlacement(
+            Order::new_limit(1, Side::Sell, 50, 510000, 1_000),
+        )).unwrap();
+        assert_eq!(sim.engine.best_bid(), Some(490000));
+        assert_eq!(sim.engine.best_ask(), Some(510000));
+        
+        // The historical cancel only removes the historical order
+        sim.process_market_event(MarketEvent::OrderCancellation {
+            order_id: 1,
+            timestamp: 2_000,
+            reason: None,
+        }).unwrap();
+        assert_eq!(sim.engine.best_bid(), Some(490000));
+        assert_eq!(sim.engine.best_ask(), None);
+        
+        // Ids that already use the tag bit are refused rather than aliased
+        assert_eq!(order_ids::historical(order_ids::HISTORICAL_BIT | 7), None);
+        assert_eq!(order_ids::source_id(order_ids::historical(7).unwrap()), 7);
+    }
+
+    #[test]
+    fn test_replayed_trades_keep_recorded_time() {
+        let taker = Order::new_market(2, Side::Buy, 10, 2_000);
+        let replay = |sim: &mut Simulator<TestOrderBook>| {
+            sim.set_mode(SimulationMode::Historical);
+            sim.process_market_event(MarketEvent::OrderPlacement(Order::new_limit(1, Side::Sell, 50, 510000, 1_000))).unwrap();
+            sim.process_market_event(MarketEvent::OrderPlacement(taker.clone())).unwrap()
+        };
+
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 42);
+        assert_eq!(replay(&mut sim)[0].ts, 2_000);
+
+        // Stamped from the wall clock, recorded times are replaced as before
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 42).with_trade_timestamps(TradeTimestamps::WallClock);
+        assert!(replay(&mut sim)[0].ts > 2_000);
+    }
+
+    #[test]
+    fn test_price_bands_hold_synthetic_orders_near_the_replay() {
+        use crate::price_bands::PriceBandConfig;
+
+        let config = PriceBandConfig { enabled: true, band_pct: 0.05, ..PriceBandConfig::default() };
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 42).with_price_bands(config);
+        sim.set_mode(SimulationMode::Hybrid);
+        let buy = Order::new_market(1, Side::Buy, 100, now_ns());
+        assert_eq!(sim.within_price_bands(None, buy.clone()), Some(buy.clone()));
+
+        // A replayed print sets the reference; a far historical ask is out of reach
+        sim.process_market_event(MarketEvent::Trade { price: 500000, qty: 10, side: Side::Buy, timestamp: 1_000, trade_id: None }).unwrap();
+        for (id, price) in [(2, 510000), (3, 600000)] {
+            sim.process_market_event(MarketEvent::OrderPlacement(Order::new_limit(id, Side::Sell, 50, price, 2_000))).unwrap();
         }
+        assert_eq!(sim.price_bands().unwrap().band().map(|band| (band.lower, band.upper)), Some((475000, 525000)));
+        assert_eq!(sim.within_price_bands(None, buy.clone()).unwrap().qty, 50);
+        assert_eq!(sim.within_price_bands(None, Order::new_limit(4, Side::Buy, 10, 600000, now_ns())), None);
+        assert_eq!(sim.price_bands().unwrap().stats().rejected, 1);
+
+        // Only hybrid mode bands synthetic flow
+        sim.set_mode(SimulationMode::Synthetic);
+        assert_eq!(sim.within_price_bands(None, buy.clone()), Some(buy));
     }
 
-    /// Reset simulation metrics
-    pub fn reset_metrics(&mut self) {
-        use crate::logging::log_startup;
+    #[test]
+    fn test_hybrid_interaction_policies() {
+        // Historical ask resting against a synthetic buyer
+        let cross = |interaction: HybridInteraction| {
+            let mut sim = Simulator::with_seed(TestOrderBook::new(), 42)
+                .with_hybrid_interaction(interaction);
+            sim.set_mode(SimulationMode::Hybrid);
+            sim.engine.set_match_filter(sim.hybrid_interaction().match_filter());
+            sim.process_market_event(MarketEvent::OrderPlacement(
+                Order::new_limit(1, Side::Sell, 50, 510000, 1_000),
+            )).unwrap();
+            sim.engine.place(Order::new_limit(2, Side::Buy, 50, 510000, now_ns())).unwrap()
+        };
         
-        self.metrics = Metrics::new();
-        self.recent_spreads.clear();
-        log_startup("Simulator", Some("Metrics reset"));
+        assert!(cross(HybridInteraction::Shadow).is_empty());
+        assert_eq!(cross(HybridInteraction::PriceTaking).len(), 1);
+        assert_eq!(cross(HybridInteraction::Interactive).len(), 1);
+        
+        // Historical trade prints never take resting synthetic liquidity
+        // under price-taking
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 42)
+            .with_hybrid_interaction(HybridInteraction::PriceTaking);
+        sim.set_mode(SimulationMode::Hybrid);
+        sim.engine.set_match_filter(sim.hybrid_interaction().match_filter());
+        sim.engine.place(Order::new_limit(1, Side::Buy, 50, 490000, now_ns())).unwrap();
+        let trades = sim.process_market_event(MarketEvent::Trade {
+            price: 490000,
+            qty: 50,
+            side: Side::Sell,
+            timestamp: 2_000,
+            trade_id: None,
+        }).unwrap();
+        assert!(trades.is_empty());
+        assert_eq!(sim.engine.best_bid(), Some(490000));
+    }
+
+    #[test]
+    fn test_ladder_clicks_join_pull_and_flatten() {
+        let mut sim = Simulator::new(TestOrderBook::new());
+        let ts = sim.current_time();
+        sim.place_as(Agent::MarketMaker, Order::new_limit(901, Side::Buy, 50, 990_000, ts)).unwrap();
+        sim.place_as(Agent::MarketMaker, Order::new_limit(902, Side::Sell, 50, 1_010_000, ts)).unwrap();
+        let join = |side, price| LadderAction::Join { side, qty: 20, price };
+
+        // Join the bid at the touch, and a new ask row inside the spread
+        let bid = sim.ladder_click(&join(Side::Buy, None)).unwrap().order_id.unwrap();
+        let ask = sim.ladder_click(&join(Side::Sell, Some(1_005_000))).unwrap().order_id.unwrap();
+        assert!(sim.ladder_click(&join(Side::Buy, Some(1_005_000))).is_err());
+        assert_eq!(sim.engine.depth_snapshot().bids[0].qty, 70);
+
+        // Pulling a row leaves other rows and other flow alone
+        let pulled = sim.ladder_click(&LadderAction::CancelAtPrice { price: 990_000 }).unwrap();
+        assert_eq!(pulled.cancelled, vec![bid]);
+        assert_eq!(sim.engine.depth_snapshot().bids[0].qty, 50);
+
+        // Once the ask is lifted, flattening buys the position back at market
+        sim.place_as(Agent::Taker, Order::new_market(903, Side::Buy, 20, ts)).unwrap();
+        assert_eq!(sim.ladder_position(), -20);
+        let flat = sim.ladder_click(&LadderAction::Flatten).unwrap();
+        assert!(flat.cancelled.is_empty() && flat.order_id.is_some_and(|order_id| order_id != ask));
+        assert_eq!(flat.trades.iter().map(|trade| (trade.maker_id, trade.qty)).collect::<Vec<_>>(), vec![(902, 20)]);
+        assert_eq!(flat.position, 0);
+    }
+
+    #[test]
+    fn test_ladder_flatten_is_refused_whole_and_spares_other_manual_orders() {
+        let mut sim = Simulator::new(TestOrderBook::new());
+        let ts = sim.current_time();
+        sim.place_as(Agent::MarketMaker, Order::new_limit(901, Side::Buy, 50, 990_000, ts)).unwrap();
+        let join = |price| LadderAction::Join { side: Side::Sell, qty: 20, price: Some(price) };
+        sim.ladder_click(&join(1_005_000)).unwrap();
+        sim.place_as(Agent::Taker, Order::new_market(902, Side::Buy, 20, ts)).unwrap();
+        let ask = sim.ladder_click(&join(1_006_000)).unwrap().order_id.unwrap();
+        assert_eq!(sim.ladder_position(), -20);
+
+        // Only the ladder's own ask is left to buy back from, so nothing is pulled
+        assert!(matches!(sim.ladder_click(&LadderAction::Flatten), Err(EngineError::NoLiquidity)));
+        assert!(sim.resting_qty(ask).is_some());
+
+        // A session's manual order is liquidity for the flatten, not one of its rows
+        let session_ask = sim.reserve_order_id();
+        sim.place_order(Order::new_limit(session_ask, Side::Sell, 30, 1_007_000, ts)).unwrap();
+        assert!(sim.ladder_click(&LadderAction::CancelAtPrice { price: 1_007_000 }).unwrap().cancelled.is_empty());
+        let flat = sim.ladder_click(&LadderAction::Flatten).unwrap();
+        assert_eq!(flat.cancelled, vec![ask]);
+        assert_eq!(flat.trades.iter().map(|trade| (trade.maker_id, trade.qty)).collect::<Vec<_>>(), vec![(session_ask, 20)]);
+        assert_eq!((flat.position, sim.resting_qty(session_ask)), (0, Some(10)));
+    }
+
+    #[test]
+    fn test_obligations_score_the_synthetic_maker() {
+        let config = ObligationConfig { enabled: true, session_ms: 20, ..ObligationConfig::default() };
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 7).with_obligations(config);
+        sim.run_steps(300).unwrap();
+
+        let monitor = sim.obligations().unwrap();
+        let reports: Vec<_> = monitor.reports().collect();
+        assert!(!reports.is_empty());
+        assert!(reports.iter().all(|report| report.maker == Maker::Synthetic));
+        for report in reports {
+            let scored = report.quoted_ns + report.one_sided_ns + report.wide_ns + report.thin_ns;
+            assert_eq!(scored, report.end - report.start);
+        }
+        assert_eq!(monitor.open_session().len(), 1);
+
+        // A reset starts scoring over
+        sim.reset();
+        let monitor = sim.obligations().unwrap();
+        assert_eq!(monitor.reports().count(), 0);
+        assert!(monitor.open_session().is_empty());
+    }
+
+    #[test]
+    fn test_settlement_reconciles_unless_trades_fail() {
+        use crate::agents::AgentRegistry;
+        use crate::settlement::SettlementConfig;
+
+        let config = SettlementConfig { enabled: true, cycle_ms: 10, lag_cycles: 1, fail_probability: 0.0, history: 10 };
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 7).with_settlement(config.clone());
+        sim.run_steps(300).unwrap();
+        let ledger = sim.settlement().unwrap();
+        assert!(ledger.stats().settled > 0);
+        assert_eq!(ledger.stats().cycles_with_breaks, 0);
+        assert_eq!(ledger.expected(), ledger.settled());
+        assert_eq!(ledger.settled().values().map(|holdings| holdings.position).sum::<i64>(), 0);
+
+        // An agent added at runtime settles under its own account, and
+        // every account's booked position matches the one held
+        let registry = AgentRegistry::builtin(&MarketMakerConfig::default(), &OrderGenerationConfig::default());
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 7).with_settlement(config.clone());
+        let id = sim.add_agent(registry.create(&AgentSpec::new("taker").with_seed(5)).unwrap());
+        sim.run_steps(300).unwrap();
+        let ledger = sim.settlement().unwrap();
+        assert!(ledger.booked().contains_key(&Account::of(Agent::Taker, Some(id))));
+        assert_eq!(ledger.stats().cycles_with_breaks, 0);
+        for (account, position) in sim.settlement_positions() {
+            assert_eq!(ledger.booked().get(&account).copied().unwrap_or_default(), position);
+        }
+
+        // Every due trade failing leaves both sides of the flow broken
+        let config = SettlementConfig { fail_probability: 1.0, ..config };
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 7).with_settlement(config);
+        sim.record_activity(true);
+        sim.run_steps(300).unwrap();
+        assert!(sim.settlement().unwrap().settled().is_empty());
+        let activity = sim.take_activity();
+        assert!(activity.iter().any(|event| event.kind == ActivityKind::Settlement && event.message.contains("MarketMaker")));
+    }
+
+    #[test]
+    fn test_ab_shadow_compares_every_engine_command() {
+        // A shadow book with nothing pinned never comes apart
+        let config = AbShadowConfig { enabled: true, ..AbShadowConfig::default() };
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 42).with_ab_shadow(config.clone(), OrderBook::new());
+        sim.run_steps(300).unwrap();
+        let report = sim.ab_report().unwrap();
+        assert!(report.commands > 0 && report.primary.trades > 0);
+        assert_eq!(report.primary, report.shadow);
+        assert_eq!(report.first_divergence, None);
+        assert!(report.depth.is_empty());
+        // Compaction reaches the shadow book without counting as a command
+        assert!(sim.compact().contains_key("ab_shadow"));
+        assert_eq!(sim.ab_report().unwrap().commands, report.commands);
+
+        // A shallower one does, while the primary keeps its depth
+        let shallow = AbShadowConfig { depth_limit: Some(DepthLimit::levels(2)), ..config };
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 42).with_ab_shadow(shallow, OrderBook::new());
+        sim.run_steps(300).unwrap();
+        let report = sim.ab_report().unwrap();
+        assert!(report.first_divergence.is_some());
+        assert!(report.depth.iter().any(|level| level.shadow == 0));
+        assert!(sim.engine.depth_snapshot().bids.len() > 2);
+        assert!(Simulator::new(TestOrderBook::new()).ab_report().is_none());
+    }
+
+    #[test]
+    fn test_shadow_book_follows_external_quotes() {
+        let config = ShadowConfig { enabled: true, levels: 2, level_spacing: 100, level_qty: 50, min_move: 1 };
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 42).with_shadow_book(config);
+        sim.set_mode(SimulationMode::Hybrid);
+        let quote = |bid, ask, bid_qty| MarketEvent::Quote { bid: Some(bid), ask: Some(ask), bid_qty, ask_qty: None, timestamp: 0 };
+        let levels = |levels: Vec<BookLevelPoint>| levels.iter().map(|level| (level.price, level.qty)).collect::<Vec<_>>();
+
+        // A local bid inside the external spread adds depth of its own
+        sim.place_order(Order::new_limit(1, Side::Buy, 20, 100_0100, 0)).unwrap();
+        assert!(sim.process_market_event(quote(99_9900, 100_0200, Some(30))).unwrap().is_empty());
+        let book = sim.snapshot();
+        assert_eq!(levels(book.bids), vec![(100_0100, 20), (99_9900, 30), (99_9800, 50)]);
+        assert_eq!(levels(book.asks), vec![(100_0200, 50), (100_0300, 50)]);
+
+        // The market moves through the local bid, which trades with the new ladder
+        let trades = sim.process_market_event(quote(100_0000, 100_0050, None)).unwrap();
+        assert_eq!(trades.iter().map(|trade| (trade.price, trade.qty)).collect::<Vec<_>>(), vec![(100_0100, 20)]);
+        assert!(order_ids::is_historical(trades[0].taker_id));
+        let book = sim.snapshot();
+        assert_eq!(levels(book.bids), vec![(100_0000, 50), (99_9900, 50)]);
+        assert_eq!(levels(book.asks), vec![(100_0050, 30), (100_0150, 50)]);
+
+        // Crossed quotes and quotes that have not moved leave the ladder alone
+        assert!(sim.process_market_event(quote(100_0100, 100_0050, None)).unwrap().is_empty());
+        assert!(sim.process_market_event(quote(100_0000, 100_0050, None)).unwrap().is_empty());
+        let shadow = sim.shadow_book().unwrap();
+        assert_eq!((shadow.anchor(), shadow.reanchors(), shadow.orders().len()), (Some((100_0000, 100_0050)), 2, 4));
+
+        // A reset pulls the ladder and the next quote anchors a new one
+        sim.reset();
+        assert!(sim.shadow_book().unwrap().anchor().is_none());
+        let book = sim.snapshot();
+        assert!(book.bids.is_empty() && book.asks.is_empty());
+        sim.process_market_event(quote(100_0000, 100_0050, None)).unwrap();
+        assert_eq!(sim.shadow_book().unwrap().orders().len(), 4);
+    }
+
+    #[test]
+    fn test_fill_accounting_splits_by_origin() {
+        let historical = order_ids::historical(1).unwrap();
+        let trade = |taker_id, maker_id, qty| Trade {
+            maker_id,
+            taker_id,
+            price: 500000,
+            qty,
+            ts: 0,
+            aggressor: Side::Buy,
+        };
+        
+        let mut fills = FillAccounting::default();
+        fills.record(&trade(2, 3, 10));
+        fills.record(&trade(2, historical, 20));
+        fills.record(&trade(2, historical, 5));
+        fills.record(&trade(order_ids::derived(4), 3, 30));
+        
+        assert_eq!(fills.synthetic, FillStats { trades: 1, qty: 10 });
+        assert_eq!(fills.synthetic_vs_historical, FillStats { trades: 2, qty: 25 });
+        assert_eq!(fills.historical_vs_synthetic, FillStats { trades: 1, qty: 30 });
+        assert_eq!(fills.historical, FillStats::default());
+    }
+
+    #[test]
+    fn test_hedger_flattens_inventory() {
+        let mut hedger = Hedger::new(HedgeConfig {
+            enabled: true,
+            interval_steps: 2,
+            inventory_threshold: 50,
+            fixed_cost: 100,
+            cost_bps: 10.0,
+        });
+        let mut metrics = Metrics::new();
+        metrics.update_trade(Side::Buy, 100, price_utils::from_f64(50.0));
+        hedger.set_external_price(price_utils::from_f64(51.0));
+
+        // First step is inside the interval, no hedge yet
+        assert!(hedger.on_step(&mut metrics, None, 1).is_none());
+        assert_eq!(metrics.inventory, 100);
+
+        let execution = hedger.on_step(&mut metrics, None, 2).unwrap();
+        assert_eq!(execution.side, Side::Sell);
+        assert_eq!(execution.qty, 100);
+        assert_eq!(execution.price, price_utils::from_f64(51.0));
+
+        // 100 * 510000 notional at 10 bps = 51000 ticks, plus 100 fixed
+        assert_eq!(execution.cost, 51_100);
+        assert_eq!(metrics.inventory, 0);
+        assert_eq!(metrics.cash, 1_000_000 - 51_100);
+        assert_eq!(metrics.pnl, metrics.cash);
+        assert_eq!(hedger.stats().hedge_count, 1);
+        assert_eq!(hedger.stats().total_cost, 51_100);
+    }
+
+    #[test]
+    fn test_hedger_threshold_and_fallback_mark() {
+        let mut hedger = Hedger::new(HedgeConfig {
+            enabled: true,
+            interval_steps: 1,
+            inventory_threshold: 50,
+            fixed_cost: 0,
+            cost_bps: 0.0,
+        });
+        let mut metrics = Metrics::new();
+        metrics.update_trade(Side::Sell, 20, 500000);
+
+        // Below threshold
+        assert!(hedger.on_step(&mut metrics, Some(500000), 1).is_none());
+
+        // No mark available at all
+        metrics.update_trade(Side::Sell, 40, 500000);
+        assert!(hedger.on_step(&mut metrics, None, 2).is_none());
+
+        // Falls back to the book mark when no external price is set
+        let execution = hedger.on_step(&mut metrics, Some(490000), 3).unwrap();
+        assert_eq!(execution.side, Side::Buy);
+        assert_eq!(execution.qty, 60);
+        assert_eq!(execution.price, 490000);
+        assert_eq!(metrics.inventory, 0);
+    }
+
+    #[test]
+    fn test_simulator_with_hedger() {
+        let engine = TestOrderBook::new();
+        let mut sim = Simulator::with_seed(engine, 42).with_hedger(HedgeConfig {
+            enabled: true,
+            interval_steps: 1,
+            inventory_threshold: 1,
+            fixed_cost: 0,
+            cost_bps: 0.0,
+        });
+
+        sim.run_steps(50).unwrap();
+
+        // Inventory is flattened at the end of every step
+        assert_eq!(sim.get_metrics().inventory, 0);
+        assert!(sim.hedger().is_some());
+    }
+
+    #[test]
+    fn test_funding_moves_cash_without_changing_the_flow() {
+        use crate::funding::FundingConfig;
+
+        let config = FundingConfig { enabled: true, interval_ms: 5, interest_rate: 0.001, ..FundingConfig::default() };
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 42).with_funding(config);
+        let mut control = Simulator::with_seed(TestOrderBook::new(), 42);
+        sim.run_steps(200).unwrap();
+        control.run_steps(200).unwrap();
+
+        assert!(sim.funding().unwrap().stats().settlements > 0);
+        let (metrics, baseline) = (sim.get_metrics(), control.get_metrics());
+        assert_eq!(metrics.inventory, baseline.inventory);
+        assert_eq!(metrics.cash - baseline.cash, metrics.funding);
+        assert_eq!(baseline.funding, 0);
+    }
+
+    #[test]
+    fn test_untouched_synthetic_liquidity_expires() {
+        let ttl = LiquidityTtlConfig { enabled: true, market_maker_ms: Some(20), taker_ms: None };
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 42).with_liquidity_ttl(ttl);
+        let mut control = Simulator::with_seed(TestOrderBook::new(), 42);
+        sim.record_events(true);
+        sim.run_steps(400).unwrap();
+        control.run_steps(400).unwrap();
+
+        assert!(sim.expired_orders() > 0);
+        assert_eq!(control.expired_orders(), 0);
+        let cancels = sim.take_recorded_events().iter()
+            .filter(|event| matches!(event, MarketEvent::OrderCancellation { .. }))
+            .count();
+        assert_eq!(cancels as u64, sim.expired_orders());
+
+        // No market maker quote outlives its TTL, while taker orders may
+        let now = sim.current_time();
+        assert!(sim.resting_agents.values()
+            .filter(|resting| resting.agent == Agent::MarketMaker)
+            .all(|resting| now - resting.touched_at < ms_to_ns(20)));
+        let depth = |sim: &Simulator<TestOrderBook>| {
+            let snapshot = sim.snapshot();
+            snapshot.bids.len() + snapshot.asks.len()
+        };
+        assert!(depth(&sim) < depth(&control));
+    }
+
+    #[test]
+    fn test_overrunning_steps_throttle_synthetic_flow() {
+        use crate::throttle::ThrottleConfig;
+
+        let config = ThrottleConfig { enabled: true, cooldown_steps: 1, ..ThrottleConfig::default() };
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 42).with_throttle(config);
+        let mut control = Simulator::with_seed(TestOrderBook::new(), 42);
+        sim.record_activity(true);
+        sim.record_events(true);
+        control.record_events(true);
+
+        let interval = Duration::from_millis(10);
+        for _ in 0..20 {
+            sim.observe_load(interval * 2, interval, 0);
+        }
+        assert_eq!(sim.throttle_rate(), 0.1);
+        let activity = sim.take_activity();
+        assert!(!activity.is_empty());
+        assert!(activity.iter().all(|event| event.kind == ActivityKind::Throttle));
+
+        sim.run_steps(200).unwrap();
+        control.run_steps(200).unwrap();
+        let placed = |sim: &mut Simulator<TestOrderBook>| sim.take_recorded_events().len();
+        assert!(placed(&mut sim) * 4 < placed(&mut control));
+
+        // Headroom brings the full rate back
+        for _ in 0..50 {
+            sim.observe_load(Duration::ZERO, interval, 0);
+        }
+        assert_eq!(sim.throttle_rate(), 1.0);
+        assert!(sim.take_activity().last().unwrap().message.starts_with("Synthetic order flow restored to 100%"));
     }
 
-    /// Get current simulation time
-    pub fn current_time(&self) -> u128 {
-        self.current_time
+    #[test]
+    fn test_depth_limit_prunes_synthetic_liquidity() {
+        let limit = DepthLimit::levels(3).with_policy(crate::engine::DepthPolicy::Prune);
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 42).with_depth_limit(limit);
+        sim.record_events(true);
+        sim.run_steps(300).unwrap();
+
+        let snapshot = sim.snapshot();
+        assert!(snapshot.bids.len() <= 3 && snapshot.asks.len() <= 3);
+        assert!(sim.pruned_orders() > 0);
+        let pruned: Vec<OrderId> = sim.take_recorded_events().iter()
+            .filter_map(|event| match event {
+                MarketEvent::OrderCancellation { order_id, reason: Some(reason), .. } if reason == "depth" => Some(*order_id),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(pruned.len() as u64, sim.pruned_orders());
+
+        // Owners forget pruned orders
+        assert!(pruned.iter().all(|order_id| !sim.resting_agents.contains_key(order_id)));
     }
 
-    /// Get simulation metrics
-    pub fn get_metrics(&self) -> &Metrics {
-        &self.metrics
-    }
+    #[test]
+    fn test_takers_size_market_orders_to_slippage_budget() {
+        let config = |max_slippage_ticks| OrderGenerationConfig {
+            market_order_prob: 1.0,
+            max_slippage_ticks,
+            ..OrderGenerationConfig::default()
+        };
+        let taker_fills = |sim: &Simulator<TestOrderBook>| sim.agent_metrics().get(&Agent::Taker)
+            .map_or(0, |metrics| metrics.taker.volume);
 
-    /// Reset simulation state
-    pub fn reset(&mut self) {
-        self.metrics = Metrics::new();
-        self.recent_spreads.clear();
-        self.current_time = now_ns();
-        self.next_order_id = 1;
-        
-        if let Some(ref mut data_source) = self.data_source {
-            let _ = data_source.reset();
+        // Any fill costs at least half the spread, so a zero budget never trades
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 42).with_order_generation_config(config(Some(0)));
+        let mut control = Simulator::with_seed(TestOrderBook::new(), 42).with_order_generation_config(config(None));
+        sim.run_steps(100).unwrap();
+        control.run_steps(100).unwrap();
+        assert_eq!(taker_fills(&sim), 0);
+        assert!(taker_fills(&control) > 0);
+
+        // Sized orders fit in the visible book
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 42).with_order_generation_config(config(Some(1_000_000)));
+        sim.run_steps(20).unwrap();
+        for _ in 0..20 {
+            let depth = |side: &[crate::engine::BookLevelPoint]| side.iter().map(|level| level.qty).sum::<Qty>();
+            let snapshot = sim.snapshot();
+            if let Some(order) = sim.generate_market_taker_order() {
+                let available = match order.side {
+                    Side::Buy => depth(&snapshot.asks),
+                    Side::Sell => depth(&snapshot.bids),
+                };
+                assert!(order.qty <= available);
+            }
         }
     }
 
-    /// Set simulation time (useful for testing)
-    pub fn set_time(&mut self, time: u128) {
-        self.current_time = time;
+    /// Forwards commands to a book and keeps a copy, as a remote engine would see them
+    struct LoggedEngine {
+        book: TestOrderBook,
+        commands: Vec<EngineCommand>,
     }
 
-    /// Check if simulation has more data to process (for historical mode)
-    pub fn has_more_data(&self) -> bool {
-        match &self.data_source {
-            Some(data_source) => !data_source.is_finished(),
-            None => true,  // Synthetic mode always has more data
+    impl CommandEngine for LoggedEngine {
+        fn execute(&mut self, command: EngineCommand) -> EngineResult<crate::engine::EngineEvent> {
+            self.commands.push(command.clone());
+            self.book.execute(command)
         }
-    }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::engine::OrderBook;
-    use crate::queue_fifo::FifoLevel;
-    use crate::types::price_utils;
+        fn best_bid_ask(&self) -> (Option<Price>, Option<Price>) {
+            self.book.best_bid_ask()
+        }
 
-    type TestOrderBook = OrderBook<FifoLevel>;
+        fn depth_snapshot(&self) -> DepthSnapshot {
+            self.book.depth_snapshot()
+        }
+
+        fn open_orders(&self) -> Vec<Order> {
+            self.book.open_orders()
+        }
+    }
 
     #[test]
-    fn test_simulator_creation() {
-        let engine = TestOrderBook::new();
-        let sim = Simulator::new(engine);
-        
-        assert_eq!(sim.mode, SimulationMode::Synthetic);
-        assert_eq!(sim.next_order_id, 1);
-        assert_eq!(sim.metrics.inventory, 0);
-        assert_eq!(sim.metrics.cash, 0);
-        assert_eq!(sim.metrics.pnl, 0);
+    fn test_latency_delays_agent_orders_and_accounts_cost() {
+        use crate::agents::{AgentRegistry, AgentSpec};
+        use crate::latency::LatencyConfig;
+
+        let specs = vec![
+            AgentSpec::new("market_maker").with_param("target_spread", 400),
+            AgentSpec::new("taker").with_seed(5).with_param("market_order_prob", 0.8),
+        ];
+        let registry = AgentRegistry::builtin(&MarketMakerConfig::default(), &OrderGenerationConfig::default());
+        let latency = LatencyConfig { enabled: true, decision_ns: 5_000_000 };
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 7)
+            .with_network_model(NetModel::new(0, 0, 0.0, 0.0))
+            .with_agents(registry.build(&specs, 7).unwrap())
+            .with_latency(latency);
+
+        // Decisions take longer than a step, so nothing has arrived yet
+        sim.step().unwrap();
+        assert!(sim.agent_stats().iter().all(|agent| agent.orders == 0));
+
+        sim.run_steps(300).unwrap();
+        let cost = sim.latency_cost().unwrap();
+        assert!(cost.actions > 0);
+        assert_eq!(cost.avg_latency_ns(), 5_000_000.0);
+        assert_eq!(cost.actions, sim.agent_stats().iter().map(|agent| agent.orders).sum::<u64>());
+        assert!(cost.zero_latency_qty > 0);
+        assert_eq!(sim.agent_stats().iter().map(|agent| agent.latency.actions).sum::<u64>(), cost.actions);
+
+        // Without latency accounting orders go straight to the book
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 7)
+            .with_agents(registry.build(&specs, 7).unwrap());
+        sim.step().unwrap();
+        assert!(sim.agent_stats().iter().any(|agent| agent.orders > 0));
+        assert!(sim.latency_cost().is_none());
     }
 
     #[test]
-    fn test_network_model() {
-        let net = NetModel::default();
-        let mut rng = StdRng::seed_from_u64(42);
-        
-        // Test latency simulation
-        let latency = net.simulate_latency(&mut rng);
-        assert!(latency > 0);
-        
-        // Test drop probability
-        let mut drops = 0;
-        for _ in 0..1000 {
-            if net.should_drop(&mut rng) {
-                drops += 1;
-            }
+    fn test_fill_reports_and_market_data_travel_separately() {
+        use crate::agents::{AgentInstance, AgentOrder, AgentSpec, MarketView, SyntheticAgent};
+        use std::sync::{Arc, Mutex};
+
+        /// Inventory and best offer the agent saw at each step
+        type Sightings = Arc<Mutex<Vec<(i64, Option<Price>)>>>;
+
+        /// Lifts the offer once, then watches its inventory and the offer
+        struct Lifter {
+            bought: bool,
+            seen: Sightings,
         }
-        // Should be approximately 1 drop (0.1% of 1000)
-        assert!(drops < 10);  // Allow some variance
-        
-        // Test reorder probability
-        let mut reorders = 0;
-        for _ in 0..1000 {
-            if net.should_reorder(&mut rng) {
-                reorders += 1;
+
+        impl SyntheticAgent for Lifter {
+            fn role(&self) -> Agent {
+                Agent::Taker
+            }
+
+            fn on_step(&mut self, market: &MarketView<'_>) -> Vec<AgentOrder> {
+                self.seen.lock().unwrap().push((market.inventory, market.best_ask));
+                if std::mem::replace(&mut self.bought, true) {
+                    return Vec::new();
+                }
+                vec![AgentOrder { side: Side::Buy, qty: market.whole_lots(1), price: None }]
             }
         }
-        // Should be approximately 10 reorders (1% of 1000)
-        assert!(reorders > 0 && reorders < 50);  // Allow some variance
-    }
 
-    #[test]
-    fn test_market_maker_config() {
-        let config = MarketMakerConfig::default();
-        
-        assert_eq!(config.target_spread, price_utils::from_f64(0.01));
-        assert_eq!(config.max_inventory, 1000);
-        assert_eq!(config.order_size, 100);
-        assert_eq!(config.mm_probability, 0.7);
+        // Which the agent notices first: its fill, or the offer leaving the book
+        let first_news = |spec: AgentSpec, net: NetModel| {
+            let seen = Arc::new(Mutex::new(Vec::new()));
+            let lifter = AgentInstance { spec, agent: Box::new(Lifter { bought: false, seen: seen.clone() }) };
+            let mut sim = Simulator::with_seed(TestOrderBook::new(), 7)
+                .with_network_model(net)
+                .with_agents(vec![lifter]);
+            sim.place_order(Order::new_limit(1_000, Side::Sell, 1, 101_0000, 0)).unwrap();
+            sim.run_steps(40).unwrap();
+            assert_eq!(sim.agent_stats_for(1).unwrap().metrics.inventory, 1);
+            let seen = seen.lock().unwrap().clone();
+            let filled = seen.iter().position(|&(inventory, _)| inventory == 1).unwrap();
+            let gone = seen.iter().position(|&(_, ask)| ask.is_none()).unwrap();
+            assert_ne!(filled, gone);
+            if filled < gone { "fill" } else { "tape" }
+        };
+
+        let fast_acks = NetModel::new(0, 0, 0.0, 0.0)
+            .with_return_paths(PathLatency::new(3_000_000, 0), PathLatency::new(10_000_000, 0));
+        assert_eq!(first_news(AgentSpec::new("lifter"), fast_acks.clone()), "fill");
+
+        // An agent's own paths override the network model's
+        let slow_acks = AgentSpec::new("lifter").with_ack_path(PathLatency::new(10_000_000, 0)).with_data_path(PathLatency::default());
+        assert_eq!(first_news(slow_acks, fast_acks), "tape");
     }
 
     #[test]
-    fn test_synthetic_simulation_step() {
-        let engine = TestOrderBook::new();
-        let mut sim = Simulator::with_seed(engine, 42);
-        
-        // Run a few simulation steps
-        for _ in 0..10 {
-            let result = sim.step();
-            assert!(result.is_ok());
+    fn test_busy_engine_throttles_with_retry_hints() {
+        use crate::agents::{AgentInstance, AgentOrder, AgentSpec, MarketView, SyntheticAgent};
+        use crate::gateway::{BusyConfig, GatewayEvent};
+        use std::sync::{Arc, Mutex};
+
+        struct Bidder {
+            hints: Arc<Mutex<Vec<u64>>>,
         }
-        
-        // Should have generated some market activity
-        let snapshot = sim.snapshot();
-        
-        // Check that we have some market structure
-        // (exact values depend on random seed, but should have some activity)
-        assert!(snapshot.ts > 0);
-    }
 
-    #[test]
-    fn test_market_making_order_generation() {
-        let engine = TestOrderBook::new();
-        let mut sim = Simulator::with_seed(engine, 42);
-        
-        // Generate market making orders
-        let orders = sim.generate_market_making_orders();
-        
-        // Should generate orders to create initial market
-        // (exact behavior depends on random seed and market state)
-        assert!(orders.len() <= 2);  // At most bid and ask
-        
-        for order in orders {
-            assert!(order.qty > 0);
-            assert!(order.id > 0);
-            assert!(order.is_limit());
+        impl SyntheticAgent for Bidder {
+            fn role(&self) -> Agent {
+                Agent::MarketMaker
+            }
+
+            fn on_step(&mut self, market: &MarketView<'_>) -> Vec<AgentOrder> {
+                vec![AgentOrder::limit(Side::Buy, market.whole_lots(1), 99_0000)]
+            }
+
+            fn on_gateway(&mut self, event: &GatewayEvent<AgentOrder>) {
+                if let GatewayEvent::Throttled { retry_after_ms, .. } = event {
+                    self.hints.lock().unwrap().push(*retry_after_ms);
+                }
+            }
         }
+
+        let hints = Arc::new(Mutex::new(Vec::new()));
+        let bidder = AgentInstance { spec: AgentSpec::new("bidder"), agent: Box::new(Bidder { hints: hints.clone() }) };
+        let busy = BusyConfig { enabled: true, orders_per_sec: 10.0, burst: 1, ..BusyConfig::default() };
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 7)
+            .with_network_model(NetModel::new(0, 0, 0.0, 0.0).with_busy(busy))
+            .with_agents(vec![bidder]);
+        sim.run_steps(20).unwrap();
+
+        // One order per 100ms gets through; the rest hear when to come back
+        let hints = hints.lock().unwrap().clone();
+        assert!(!hints.is_empty());
+        assert!(hints.iter().all(|hint| (1..=100).contains(hint)));
+        assert_eq!(sim.gateway_stats().throttled, hints.len() as u64);
+        assert!(sim.agent_stats_for(1).unwrap().orders >= 1);
+
+        let manual = |id| Order::new_limit(id, Side::Sell, 1, 101_0000, 0);
+        let retry_after_ms = match sim.place_order(manual(1_000)) {
+            Err(EngineError::Throttled { retry_after_ms }) => retry_after_ms,
+            other => panic!("expected a throttle refusal, got {:?}", other),
+        };
+        assert!(matches!(sim.preview_order(Side::Sell, 1, Some(101_0000)), Err(EngineError::Throttled { .. })));
+        sim.set_time(sim.current_time() + ms_to_ns(retry_after_ms));
+        // A preview does not use up the order it would be let through as
+        assert!(sim.preview_order(Side::Sell, 1, Some(101_0000)).is_ok());
+        assert!(sim.place_order(manual(1_001)).is_ok());
+
+        // The busy model's stream is separate, so the run's own draws are unchanged
+        let first_draw = |net: NetModel| Simulator::with_seed(TestOrderBook::new(), 7).with_network_model(net).rng.gen::<u64>();
+        let quiet = NetModel::new(0, 0, 0.0, 0.0);
+        assert_eq!(first_draw(quiet.clone().with_busy(busy)), first_draw(quiet));
     }
 
     #[test]
-    fn test_market_taker_order_generation() {
-        let engine = TestOrderBook::new();
-        let mut sim = Simulator::with_seed(engine, 42);
-        
-        // First establish some market structure
-        for _ in 0..5 {
-            let _ = sim.step();
+    fn test_gateway_outage_rejects_or_bursts_orders() {
+        use crate::agents::{AgentInstance, AgentOrder, AgentSpec, MarketView, SyntheticAgent};
+        use crate::gateway::{GatewayEvent, GatewayOutage, OutagePolicy};
+        use std::sync::{Arc, Mutex};
+
+        struct Bidder {
+            events: Arc<Mutex<Vec<&'static str>>>,
         }
-        
-        // Generate a market taker order
-        if let Some(order) = sim.generate_market_taker_order() {
-            assert!(order.qty > 0);
-            assert!(order.id > 0);
-            assert!(order.qty >= sim.order_gen_config.min_order_size);
-            assert!(order.qty <= sim.order_gen_config.max_order_size);
+
+        impl SyntheticAgent for Bidder {
+            fn role(&self) -> Agent {
+                Agent::MarketMaker
+            }
+
+            fn on_step(&mut self, market: &MarketView<'_>) -> Vec<AgentOrder> {
+                vec![AgentOrder::limit(Side::Buy, market.whole_lots(1), 99_0000)]
+            }
+
+            fn on_gateway(&mut self, event: &GatewayEvent<AgentOrder>) {
+                let event = match event {
+                    GatewayEvent::Disconnected => "down",
+                    GatewayEvent::Reconnected => "up",
+                    GatewayEvent::Rejected(_) => "rejected",
+                    GatewayEvent::Throttled { .. } => "throttled",
+                };
+                self.events.lock().unwrap().push(event);
+            }
         }
-    }
 
-    #[test]
-    fn test_metrics_tracking() {
-        let engine = TestOrderBook::new();
-        let mut sim = Simulator::with_seed(engine, 42);
-        
-        let _initial_metrics = sim.get_metrics().clone();
-        
-        // Run simulation to generate trades
-        let _ = sim.run_steps(20);
-        
-        let final_metrics = sim.get_metrics();
-        
-        // Metrics should have been updated if trades occurred
-        // (exact values depend on random simulation, but structure should be valid)
-        assert!(final_metrics.pnl != 0 || final_metrics.cash != 0 || final_metrics.inventory != 0 || 
-                (final_metrics.pnl == 0 && final_metrics.cash == 0 && final_metrics.inventory == 0));
+        let run = |policy| {
+            let events = Arc::new(Mutex::new(Vec::new()));
+            let bidder = AgentInstance { spec: AgentSpec::new("bidder"), agent: Box::new(Bidder { events: events.clone() }) };
+            let outage = GatewayOutage { start_ms: 0, duration_ms: 50 };
+            let net = NetModel::new(0, 0, 0.0, 0.0).with_outages(vec![outage], policy);
+            let mut sim = Simulator::with_seed(TestOrderBook::new(), 7)
+                .with_network_model(net)
+                .with_agents(vec![bidder]);
+
+            // A reset drops the outage in progress with its held orders
+            sim.run_steps(2).unwrap();
+            assert!(sim.gateway_down());
+            sim.reset();
+            assert!(!sim.gateway_down());
+            assert_eq!(*sim.gateway_stats(), Default::default());
+            events.lock().unwrap().clear();
+
+            sim.run_steps(2).unwrap();
+            assert!(sim.gateway_down());
+            let manual = Order::new_limit(1_000, Side::Sell, 1, 101_0000, sim.current_time());
+            assert!(matches!(sim.place_order(manual), Err(EngineError::GatewayUnavailable)));
+            assert!(matches!(sim.preview_order(Side::Sell, 1, Some(101_0000)), Err(EngineError::GatewayUnavailable)));
+
+            sim.set_time(sim.current_time() + 100_000_000);
+            sim.step().unwrap();
+            assert!(!sim.gateway_down());
+            let events = events.lock().unwrap().clone();
+            (sim, events)
+        };
+
+        // Rejected orders are lost and the agent hears about each one
+        let (sim, events) = run(OutagePolicy::Reject);
+        assert_eq!(events, vec!["down", "rejected", "rejected", "up"]);
+        assert_eq!(sim.gateway_stats().rejected, 2);
+        assert_eq!(sim.agent_stats_for(1).unwrap().orders, 1);
+
+        // Held orders all reach the book the moment the gateway comes back
+        let (sim, events) = run(OutagePolicy::Queue);
+        assert_eq!(events, vec!["down", "up"]);
+        assert_eq!((sim.gateway_stats().queued, sim.gateway_stats().released), (2, 2));
+        assert_eq!(sim.agent_stats_for(1).unwrap().orders, 3);
+        assert_eq!(sim.snapshot().bids.iter().map(|level| level.qty).sum::<u64>(), 3);
     }
 
     #[test]
-    fn test_spread_history_tracking() {
-        let engine = TestOrderBook::new();
-        let mut sim = Simulator::with_seed(engine, 42);
-        
-        // Run simulation to generate market activity
-        let _ = sim.run_steps(30);
-        
-        let snapshot = sim.snapshot();
-        
-        // Should have some spread history if trades occurred
-        // (depends on random simulation generating trades)
-        assert!(snapshot.recent_spreads.len() <= 400);  // Bounded by max size
-        
-        for (ts, spread) in &snapshot.recent_spreads {
-            assert!(*ts > 0);
-            assert!(*spread >= 0);  // Spread should be non-negative
+    fn test_profiled_steps_attribute_time_to_phases() {
+        let mut sim = Simulator::with_seed(TestOrderBook::new(), 7);
+        assert!(sim.take_step_profile().is_none());
+
+        sim.profile_steps(true);
+        let mut total = StepProfile::default();
+        for _ in 0..20 {
+            sim.step().unwrap();
+            let profile = sim.take_step_profile().unwrap();
+            for phase in StepPhase::ALL {
+                total.add(phase, std::time::Duration::from_nanos(profile.get(phase)));
+            }
         }
-    }
+        assert!(total.get(StepPhase::Generation) > 0);
+        assert!(total.get(StepPhase::Matching) > 0);
+        // Snapshots and broadcasts happen after the step, outside the simulator
+        assert_eq!(total.get(StepPhase::Broadcast), 0);
 
-    #[test]
-    fn test_simulation_reset() {
-        let engine = TestOrderBook::new();
-        let mut sim = Simulator::with_seed(engine, 42);
-        
-        // Run simulation
-        let _ = sim.run_steps(10);
-        
-        // Reset simulation
-        sim.reset();
-        
-        // Check that state was reset
-        assert_eq!(sim.get_metrics().inventory, 0);
-        assert_eq!(sim.get_metrics().cash, 0);
-        assert_eq!(sim.get_metrics().pnl, 0);
-        assert_eq!(sim.recent_spreads.len(), 0);
-        assert_eq!(sim.next_order_id, 1);
-        assert!(sim.current_time() > 0);  // Time should be reset to a valid timestamp
+        sim.profile_steps(false);
+        sim.step().unwrap();
+        assert!(sim.take_step_profile().is_none());
     }
 
     #[test]
-    fn test_simulation_modes() {
-        let engine = TestOrderBook::new();
-        let mut sim = Simulator::new(engine);
-        
-        // Test mode setting
-        assert_eq!(sim.mode, SimulationMode::Synthetic);
-        
-        sim.set_mode(SimulationMode::Historical);
-        assert_eq!(sim.mode, SimulationMode::Historical);
-        
-        sim.set_mode(SimulationMode::Hybrid);
-        assert_eq!(sim.mode, SimulationMode::Hybrid);
-    }
+    fn test_simulator_drives_command_engines() {
+        let logged = LoggedEngine { book: TestOrderBook::new(), commands: Vec::new() };
+        let analytics = AnalyticsConfig { order_ages: true, ..AnalyticsConfig::default() };
+        let mut sim = Simulator::with_seed(logged, 7).with_analytics_config(analytics.clone());
+        let mut reference = Simulator::with_seed(TestOrderBook::new(), 7).with_analytics_config(analytics);
+        sim.run_steps(50).unwrap();
+        reference.run_steps(50).unwrap();
 
-    #[test]
-    fn test_configuration_builders() {
-        let engine = TestOrderBook::new();
-        let net_model = NetModel::new(200_000, 100_000, 0.002, 0.02);
-        let mm_config = MarketMakerConfig {
-            target_spread: price_utils::from_f64(0.02),
-            max_inventory: 500,
-            order_size: 50,
-            mm_probability: 0.8,
-            inventory_skew: 0.002,
-        };
-        let order_config = OrderGenerationConfig {
-            market_order_prob: 0.4,
-            mean_order_interval_ns: 2_000_000,
-            min_order_size: 5,
-            max_order_size: 200,
-            price_range_fraction: 0.03,
+        let placed = sim.engine.commands.iter().filter(|c| matches!(c, EngineCommand::Place(_))).count();
+        assert!(placed > 0);
+
+        // Same seed, same commands, same book
+        let levels = |snapshot: DepthSnapshot| {
+            let side = |levels: Vec<crate::engine::BookLevelPoint>| levels.iter().map(|l| (l.price, l.qty)).collect::<Vec<_>>();
+            (side(snapshot.bids), side(snapshot.asks))
         };
-        
-        let sim = Simulator::new(engine)
-            .with_network_model(net_model.clone())
-            .with_market_maker_config(mm_config.clone())
-            .with_order_generation_config(order_config.clone());
-        
-        assert_eq!(sim.net.base_latency_ns, net_model.base_latency_ns);
-        assert_eq!(sim.market_maker_config.target_spread, mm_config.target_spread);
-        assert_eq!(sim.order_gen_config.market_order_prob, order_config.market_order_prob);
+        assert_eq!(levels(sim.snapshot()), levels(reference.snapshot()));
+        assert_eq!(sim.get_metrics().inventory, reference.get_metrics().inventory);
+        // Order ages from open orders agree with the book's own
+        let ages = reference.snapshot().order_ages.unwrap();
+        assert_eq!(sim.snapshot().order_ages, Some(ages.clone()));
+        assert_eq!(ages.bids.levels.len(), reference.snapshot().bids.len());
+        // Snapshots leave them out unless asked for
+        assert!(Simulator::with_seed(TestOrderBook::new(), 7).snapshot().order_ages.is_none());
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,521 @@
+//! Simulator on a dedicated thread
+//!
+//! The simulator runs on its own OS thread, so a long step or a burst of
+//! replayed events never holds up the async runtime that accepts WebSocket
+//! connections and answers health checks and REST calls. Everything else
+//! reaches it through a [`SimulatorHandle`]: [`SimulatorHandle::call`] queues a
+//! closure, the thread runs it between steps and the result comes back on a
+//! oneshot channel.
+//!
+//! Calls wait in one of two [`Lane`]s. Cancels go in their own lane, which
+//! the thread always empties first, so pulling orders is never stuck behind
+//! a backlog of new ones, as on exchanges under stress.
+//!
+//! Once [`SimulatorHandle::run`] starts stepping, the thread steps at its own
+//! interval and hands each step's trades and snapshot back over a bounded
+//! channel. Steps that fall due take precedence over queued calls, so a flood
+//! of requests cannot stall the simulation either, but after every step the
+//! thread serves the calls that waited on it, so steps that overrun their
+//! interval cannot starve calls. The thread never waits on the receiver:
+//! while the channel is full, new steps fold into the last one waiting.
+
+use std::collections::VecDeque;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+use metrics::gauge;
+use serde::Serialize;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc as async_mpsc, oneshot};
+use crate::activity::Activity;
+use crate::engine::{CommandEngine, DepthSnapshot};
+use crate::error::EngineResult;
+use crate::metrics::{EventStamp, StepPhase, StepProfile};
+use crate::mbo::OrderUpdate;
+use crate::paper::PaperFill;
+use crate::sim::Simulator;
+use crate::types::Trade;
+
+/// Work sent to the simulation thread
+type SimCall<E> = Box<dyn FnOnce(&mut Simulator<E>) + Send>;
+
+/// Requests the simulation thread serves
+enum Request<E: CommandEngine> {
+    /// Run a closure against the simulator
+    Call(SimCall<E>),
+    /// Start stepping at `interval`, sending every step to `steps`
+    Run {
+        interval: Duration,
+        steps: async_mpsc::Sender<EngineResult<StepOutput>>,
+    },
+}
+
+/// Queue a call waits in on its way to the simulation thread
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Lane {
+    /// Cancels, served ahead of everything else
+    Cancel,
+    /// New orders and every other call, in arrival order
+    Standard,
+}
+
+impl Lane {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Cancel => "cancel",
+            Self::Standard => "standard",
+        }
+    }
+}
+
+/// Calls waiting in each lane
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct LaneDepths {
+    pub cancel: usize,
+    pub standard: usize,
+}
+
+impl LaneDepths {
+    /// Calls waiting in all lanes
+    pub fn total(&self) -> usize {
+        self.cancel + self.standard
+    }
+}
+
+/// Waiting requests, by lane
+struct Queues<E: CommandEngine> {
+    cancel: VecDeque<Request<E>>,
+    standard: VecDeque<Request<E>>,
+    /// Live handles; the thread exits when this reaches zero
+    handles: usize,
+}
+
+impl<E: CommandEngine> Queues<E> {
+    fn lane(&mut self, lane: Lane) -> &mut VecDeque<Request<E>> {
+        match lane {
+            Lane::Cancel => &mut self.cancel,
+            Lane::Standard => &mut self.standard,
+        }
+    }
+
+    fn depths(&self) -> LaneDepths {
+        LaneDepths { cancel: self.cancel.len(), standard: self.standard.len() }
+    }
+
+    /// The next request, cancels first
+    fn pop(&mut self) -> Option<Request<E>> {
+        let lane = if self.cancel.is_empty() { Lane::Standard } else { Lane::Cancel };
+        let request = self.lane(lane).pop_front()?;
+        gauge!("command_lane_depth", self.lane(lane).len() as f64, "lane" => lane.name());
+        Some(request)
+    }
+}
+
+/// State shared between the handles and the thread
+struct Shared<E: CommandEngine> {
+    queues: Mutex<Queues<E>>,
+    ready: Condvar,
+}
+
+impl<E: CommandEngine> Shared<E> {
+    fn queues(&self) -> MutexGuard<'_, Queues<E>> {
+        // Calls run outside the lock, so it is never poisoned
+        self.queues.lock().unwrap()
+    }
+
+    fn push(&self, lane: Lane, request: Request<E>) {
+        let mut queues = self.queues();
+        queues.lane(lane).push_back(request);
+        gauge!("command_lane_depth", queues.lane(lane).len() as f64, "lane" => lane.name());
+        self.ready.notify_one();
+    }
+}
+
+/// One simulation step, as the thread hands it back
+#[derive(Debug, Clone)]
+pub struct StepOutput {
+    /// Trades the step executed
+    pub trades: Vec<Trade>,
+    /// The book right after the step
+    pub snapshot: DepthSnapshot,
+    /// How long the step took, snapshot included
+    pub duration: Duration,
+    /// Data source events the step applied, while the simulator stamps them
+    pub stamps: Vec<EventStamp>,
+    /// Changes to the resting orders, while the simulator diffs them
+    pub order_updates: Vec<OrderUpdate>,
+    /// Time the step spent in each phase up to its snapshot, while the
+    /// simulator profiles steps
+    pub profile: Option<StepProfile>,
+    /// Market activity the step recorded, while the simulator collects it
+    pub activity: Vec<Activity>,
+    /// Fills resting paper orders took, while the simulator collects them
+    pub paper_fills: Vec<PaperFill>,
+    /// How long the data feed had been silent when the step ended
+    pub feed_silence: Option<Duration>,
+}
+
+impl StepOutput {
+    /// Fold a later step into this one, as if they had run as one step
+    /// ending in the later one's book
+    fn absorb(&mut self, later: StepOutput) {
+        self.trades.extend(later.trades);
+        self.snapshot = later.snapshot;
+        self.duration += later.duration;
+        self.stamps.extend(later.stamps);
+        self.order_updates.extend(later.order_updates);
+        self.profile = later.profile;
+        self.activity.extend(later.activity);
+        self.paper_fills.extend(later.paper_fills);
+        self.feed_silence = later.feed_silence;
+    }
+}
+
+/// Cloneable handle to a simulator running on its own thread
+///
+/// The thread exits once every handle is dropped.
+pub struct SimulatorHandle<E: CommandEngine> {
+    shared: Arc<Shared<E>>,
+}
+
+impl<E: CommandEngine> Clone for SimulatorHandle<E> {
+    fn clone(&self) -> Self {
+        self.shared.queues().handles += 1;
+        Self { shared: self.shared.clone() }
+    }
+}
+
+impl<E: CommandEngine> Drop for SimulatorHandle<E> {
+    fn drop(&mut self) {
+        self.shared.queues().handles -= 1;
+        self.shared.ready.notify_one();
+    }
+}
+
+impl<E: CommandEngine> std::fmt::Debug for SimulatorHandle<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SimulatorHandle").finish_non_exhaustive()
+    }
+}
+
+impl<E: CommandEngine + 'static> SimulatorHandle<E>
+where
+    Simulator<E>: Send,
+{
+    /// Move `simulator` onto a new thread
+    pub fn spawn(simulator: Simulator<E>) -> Self {
+        let shared = Arc::new(Shared {
+            queues: Mutex::new(Queues { cancel: VecDeque::new(), standard: VecDeque::new(), handles: 1 }),
+            ready: Condvar::new(),
+        });
+        let thread_shared = shared.clone();
+        thread::Builder::new()
+            .name("simulator".to_string())
+            .spawn(move || serve(simulator, &thread_shared))
+            .expect("failed to spawn the simulation thread");
+        Self { shared }
+    }
+
+    /// Run `f` against the simulator and wait for its result
+    ///
+    /// Panics if `f` panics, as it would have run in the caller.
+    pub async fn call<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut Simulator<E>) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        self.call_in(Lane::Standard, f).await
+    }
+
+    /// Run `f` against the simulator from the given lane and wait for its result
+    pub async fn call_in<R, F>(&self, lane: Lane, f: F) -> R
+    where
+        F: FnOnce(&mut Simulator<E>) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (reply, result) = oneshot::channel();
+        let call: SimCall<E> = Box::new(move |simulator| {
+            let _ = reply.send(f(simulator));
+        });
+        self.shared.push(lane, Request::Call(call));
+        result.await.expect("simulation call panicked")
+    }
+
+    /// Queue `f` to run against the simulator without waiting for it
+    ///
+    /// Calls queued afterwards run after it.
+    pub fn post<F>(&self, f: F)
+    where
+        F: FnOnce(&mut Simulator<E>) + Send + 'static,
+    {
+        self.shared.push(Lane::Standard, Request::Call(Box::new(f)));
+    }
+
+    /// Calls waiting in each lane
+    pub fn lane_depths(&self) -> LaneDepths {
+        self.shared.queues().depths()
+    }
+
+    /// Start stepping every `interval`, receiving each step as it completes
+    ///
+    /// Up to `capacity` steps wait for the receiver; steps beyond that fold
+    /// into the last one waiting until the receiver catches up. Dropping the
+    /// receiver stops stepping. Calling this again replaces the interval and
+    /// the receiver.
+    pub fn run(&self, interval: Duration, capacity: usize) -> async_mpsc::Receiver<EngineResult<StepOutput>> {
+        let (steps, receiver) = async_mpsc::channel(capacity);
+        self.shared.push(Lane::Standard, Request::Run { interval, steps });
+        receiver
+    }
+}
+
+/// Stepping started by [`Request::Run`]
+struct Stepping {
+    interval: Duration,
+    steps: async_mpsc::Sender<EngineResult<StepOutput>>,
+    /// A step the full channel had no room for, which later ones fold into
+    held: Option<StepOutput>,
+}
+
+impl Stepping {
+    /// Hand a step to the receiver without waiting on it, returning whether
+    /// the receiver is still there
+    fn deliver(&mut self, output: EngineResult<StepOutput>) -> bool {
+        let output = match (self.held.take(), output) {
+            (Some(mut earlier), Ok(later)) => {
+                earlier.absorb(later);
+                Ok(earlier)
+            }
+            // The held step goes out ahead of the failure
+            (Some(earlier), Err(e)) => {
+                if !self.deliver(Ok(earlier)) {
+                    return false;
+                }
+                if self.held.is_some() {
+                    tracing::warn!("Step receiver is behind; dropping a failed step: {}", e);
+                    return true;
+                }
+                Err(e)
+            }
+            (None, output) => output,
+        };
+        match self.steps.try_send(output) {
+            Ok(()) => true,
+            Err(TrySendError::Full(Ok(step))) => {
+                self.held = Some(step);
+                true
+            }
+            Err(TrySendError::Full(Err(e))) => {
+                tracing::warn!("Step receiver is behind; dropping a failed step: {}", e);
+                true
+            }
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+
+    /// Steps handed over but not yet taken, counting one held back
+    fn backlog(&self) -> usize {
+        self.steps.max_capacity() - self.steps.capacity() + usize::from(self.held.is_some())
+    }
+}
+
+/// Run one step and gather what the thread hands back for it
+fn step<E: CommandEngine>(simulator: &mut Simulator<E>) -> EngineResult<StepOutput> {
+    let start = Instant::now();
+    simulator.step().map(|trades| {
+        let snapshot_start = Instant::now();
+        let snapshot = simulator.snapshot();
+        let order_updates = simulator.take_order_updates();
+        let profile = simulator.take_step_profile().map(|mut profile| {
+            profile.add(StepPhase::Snapshot, snapshot_start.elapsed());
+            profile
+        });
+        StepOutput {
+            trades,
+            snapshot,
+            duration: start.elapsed(),
+            stamps: simulator.take_pipeline_stamps(),
+            order_updates,
+            profile,
+            activity: simulator.take_activity(),
+            paper_fills: simulator.take_paper_fills(),
+            feed_silence: simulator.feed_silence(),
+        }
+    })
+}
+
+/// Serve a request taken from the queues
+fn apply<E: CommandEngine>(request: Request<E>, simulator: &mut Simulator<E>, stepping: &mut Option<Stepping>, next_step: &mut Instant) {
+    match request {
+        // The caller sees the panic when its reply is dropped
+        Request::Call(call) => {
+            let _ = catch_unwind(AssertUnwindSafe(|| call(simulator)));
+        }
+        Request::Run { interval, steps } => {
+            *stepping = Some(Stepping { interval, steps, held: None });
+            *next_step = Instant::now();
+        }
+    }
+}
+
+/// The simulation thread: serve calls, and step when stepping is on
+fn serve<E: CommandEngine>(mut simulator: Simulator<E>, shared: &Shared<E>) {
+    let mut stepping: Option<Stepping> = None;
+    let mut next_step = Instant::now();
+
+    loop {
+        if let Some(run) = &mut stepping {
+            let now = Instant::now();
+            if now >= next_step {
+                let output = step(&mut simulator);
+                if let Ok(step) = &output {
+                    // Calls waiting and steps the receiver has not taken yet
+                    let queued = shared.queues().depths().total() + run.backlog();
+                    simulator.observe_load(step.duration, run.interval, queued);
+                }
+                next_step = now + run.interval;
+                if !run.deliver(output) {
+                    stepping = None;
+                }
+
+                // Calls that waited on this step go before the next one
+                let waiting = shared.queues().depths().total();
+                for _ in 0..waiting {
+                    let Some(request) = shared.queues().pop() else {
+                        break;
+                    };
+                    apply(request, &mut simulator, &mut stepping, &mut next_step);
+                }
+                continue;
+            }
+        }
+
+        let request = {
+            let mut queues = shared.queues();
+            loop {
+                if let Some(request) = queues.pop() {
+                    break Some(request);
+                }
+                if queues.handles == 0 {
+                    return;
+                }
+                if stepping.is_none() {
+                    queues = shared.ready.wait(queues).unwrap();
+                    continue;
+                }
+                let until_step = next_step.saturating_duration_since(Instant::now());
+                if until_step.is_zero() {
+                    break None;
+                }
+                queues = shared.ready.wait_timeout(queues, until_step).unwrap().0;
+            }
+        };
+        if let Some(request) = request {
+            apply(request, &mut simulator, &mut stepping, &mut next_step);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::OrderBook;
+    use crate::queue_fifo::FifoLevel;
+
+    #[tokio::test]
+    async fn test_calls_and_steps_share_the_thread() {
+        let handle = SimulatorHandle::spawn(Simulator::new(OrderBook::<FifoLevel>::new()));
+        let caller = thread::current().id();
+        let (thread_name, mode) = handle
+            .call(move |simulator| {
+                assert_ne!(thread::current().id(), caller);
+                (thread::current().name().map(str::to_string), simulator.mode())
+            })
+            .await;
+        assert_eq!(thread_name.as_deref(), Some("simulator"));
+        assert_eq!(mode, crate::sim::SimulationMode::Synthetic);
+
+        let mut steps = handle.run(Duration::from_millis(1), 4);
+        for _ in 0..5 {
+            let output = steps.recv().await.unwrap().unwrap();
+            assert!(output.snapshot.ts > 0);
+        }
+
+        // Dropping the receiver stops the stepping
+        drop(steps);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let stopped_at = handle.call(|simulator| simulator.current_time()).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(handle.call(|simulator| simulator.current_time()).await, stopped_at);
+    }
+
+    #[tokio::test]
+    async fn test_calls_are_served_while_steps_overrun_and_the_receiver_lags() {
+        let handle = SimulatorHandle::spawn(Simulator::new(OrderBook::<FifoLevel>::new()));
+
+        // Every step overruns a zero interval, and the receiver is slower still
+        let mut steps = handle.run(Duration::ZERO, 2);
+        let receiver = tokio::spawn(async move {
+            for _ in 0..10 {
+                steps.recv().await.unwrap().unwrap();
+                tokio::time::sleep(Duration::from_millis(3)).await;
+            }
+        });
+        let mut times = Vec::new();
+        for _ in 0..20 {
+            let call = handle.call(|simulator| simulator.current_time());
+            times.push(tokio::time::timeout(Duration::from_secs(2), call).await.expect("call starved"));
+        }
+        assert!(times.windows(2).all(|pair| pair[0] <= pair[1]));
+        tokio::time::timeout(Duration::from_secs(10), receiver).await.expect("receiver starved").unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cancels_jump_the_order_backlog() {
+        let handle = SimulatorHandle::spawn(Simulator::new(OrderBook::<FifoLevel>::new()));
+        let served = Arc::new(Mutex::new(Vec::new()));
+
+        // Hold the thread until every call is queued
+        let (started, running) = oneshot::channel();
+        let (release, gate) = std::sync::mpsc::channel::<()>();
+        let busy = handle.clone();
+        let blocker = tokio::spawn(async move {
+            busy.call(move |_| {
+                started.send(()).unwrap();
+                gate.recv().unwrap()
+            }).await
+        });
+        running.await.unwrap();
+
+        let mut calls = Vec::new();
+        for (lane, name) in [(Lane::Standard, "place 1"), (Lane::Standard, "place 2"), (Lane::Cancel, "cancel")] {
+            let (caller, served) = (handle.clone(), served.clone());
+            calls.push(tokio::spawn(async move {
+                caller.call_in(lane, move |_| served.lock().unwrap().push(name)).await
+            }));
+            while handle.lane_depths().total() < calls.len() {
+                tokio::task::yield_now().await;
+            }
+        }
+        assert_eq!(handle.lane_depths(), LaneDepths { cancel: 1, standard: 2 });
+
+        release.send(()).unwrap();
+        blocker.await.unwrap();
+        for call in calls {
+            call.await.unwrap();
+        }
+        assert_eq!(*served.lock().unwrap(), vec!["cancel", "place 1", "place 2"]);
+        assert_eq!(handle.lane_depths(), LaneDepths::default());
+    }
+
+    #[tokio::test]
+    async fn test_a_panicking_call_leaves_the_thread_running() {
+        let handle = SimulatorHandle::spawn(Simulator::new(OrderBook::<FifoLevel>::new()));
+        let failing = handle.clone();
+        let panicked = tokio::spawn(async move { failing.call(|_| panic!("bad call")).await }).await;
+        assert!(panicked.is_err());
+        assert!(handle.call(|simulator| simulator.snapshot().bids.is_empty()).await);
+    }
+}
@@ -0,0 +1,307 @@
+//! Multi-resolution spread history
+//!
+//! Every spread sample goes into a short buffer of raw points, which the
+//! dashboard chart draws, and into a chain of aggregated tiers (by default
+//! 1 second, 10 seconds and 1 minute). Each tier keeps a bounded number of
+//! bars holding the count, minimum, maximum, mean and last spread over its
+//! interval. When a bar closes it is rolled up into the next coarser tier, so
+//! a long run keeps its whole spread history at falling resolution in fixed
+//! memory, instead of only its last few hundred samples.
+
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::memory::{CircularBuffer, Compaction, EvictionPolicy, HistoryUsage};
+
+/// One resolution of aggregated spread history
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SpreadTier {
+    /// Interval each bar covers (milliseconds)
+    pub resolution_ms: u64,
+    /// Bars kept before the oldest is dropped
+    pub points: usize,
+}
+
+impl SpreadTier {
+    /// 1 second for an hour, 10 seconds for six hours, 1 minute for a day
+    pub fn defaults() -> Vec<SpreadTier> {
+        vec![
+            SpreadTier { resolution_ms: 1_000, points: 3_600 },
+            SpreadTier { resolution_ms: 10_000, points: 2_160 },
+            SpreadTier { resolution_ms: 60_000, points: 1_440 },
+        ]
+    }
+
+    /// Check a tier chain: each resolution must be a whole multiple of the
+    /// one before it, so bars roll up without straddling boundaries
+    pub fn validate_chain(tiers: &[SpreadTier]) -> Result<(), String> {
+        for (index, tier) in tiers.iter().enumerate() {
+            if tier.resolution_ms == 0 {
+                return Err("Spread tier resolution must be greater than 0".to_string());
+            }
+            if tier.points == 0 {
+                return Err(format!("Spread tier at {}ms must keep at least 1 point", tier.resolution_ms));
+            }
+            if let Some(finer) = index.checked_sub(1).map(|previous| tiers[previous]) {
+                if tier.resolution_ms <= finer.resolution_ms || !tier.resolution_ms.is_multiple_of(finer.resolution_ms) {
+                    return Err(format!(
+                        "Spread tier at {}ms must be a larger multiple of the {}ms tier before it",
+                        tier.resolution_ms, finer.resolution_ms
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Spread over one interval
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SpreadBar {
+    /// Start of the interval (nanoseconds)
+    pub ts: u128,
+    pub samples: u64,
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub min: i64,
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub max: i64,
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub mean: f64,
+    /// Latest spread in the interval
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub last: i64,
+}
+
+impl SpreadBar {
+    fn sample(ts: u128, spread: i64) -> Self {
+        Self { ts, samples: 1, min: spread, max: spread, mean: spread as f64, last: spread }
+    }
+
+    fn merge(&mut self, other: &SpreadBar) {
+        let samples = self.samples + other.samples;
+        self.mean += (other.mean - self.mean) * other.samples as f64 / samples as f64;
+        self.samples = samples;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.last = other.last;
+    }
+}
+
+/// Bars of one tier, oldest first, including the one still open
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpreadSeries {
+    pub resolution_ms: u64,
+    pub capacity: usize,
+    /// Closed bars dropped to stay within the capacity
+    pub evicted: usize,
+    pub bars: Vec<SpreadBar>,
+}
+
+#[derive(Debug, Clone)]
+struct Tier {
+    resolution_ms: u64,
+    bars: CircularBuffer<SpreadBar>,
+    /// Bar still collecting
+    open: Option<SpreadBar>,
+}
+
+impl Tier {
+    fn new(tier: SpreadTier) -> Self {
+        Self { resolution_ms: tier.resolution_ms, bars: CircularBuffer::new(tier.points), open: None }
+    }
+
+    fn series(&self) -> SpreadSeries {
+        let usage = self.bars.usage();
+        let mut bars = self.bars.to_vec();
+        bars.extend(self.open);
+        SpreadSeries { resolution_ms: self.resolution_ms, capacity: usage.capacity, evicted: usage.evicted, bars }
+    }
+}
+
+/// Raw recent spreads plus aggregated tiers
+#[derive(Debug, Clone)]
+pub struct SpreadHistory {
+    /// Latest `(timestamp, spread)` samples
+    recent: CircularBuffer<(u128, i64)>,
+    /// Finest resolution first
+    tiers: Vec<Tier>,
+}
+
+impl Default for SpreadHistory {
+    fn default() -> Self {
+        Self::new(400, &SpreadTier::defaults())
+    }
+}
+
+impl SpreadHistory {
+    /// Keep `recent_points` raw samples and aggregate into `tiers`, which
+    /// should pass [`SpreadTier::validate_chain`]
+    pub fn new(recent_points: usize, tiers: &[SpreadTier]) -> Self {
+        Self {
+            recent: CircularBuffer::new(recent_points),
+            tiers: tiers.iter().copied().map(Tier::new).collect(),
+        }
+    }
+
+    /// Replace the tiers, dropping their bars
+    pub fn set_tiers(&mut self, tiers: &[SpreadTier]) {
+        self.tiers = tiers.iter().copied().map(Tier::new).collect();
+    }
+
+    /// Keep at most `points` raw samples
+    pub fn set_recent_capacity(&mut self, points: usize) {
+        self.recent.set_capacity(points);
+    }
+
+    /// Set how the raw samples evict once full; tiers always drop their
+    /// oldest bar, since the next tier already holds it at lower resolution
+    pub fn set_policy(&mut self, policy: EvictionPolicy) {
+        self.recent.set_policy(policy);
+    }
+
+    /// Record a spread observed at `ts` (nanoseconds)
+    pub fn push(&mut self, ts: u128, spread: i64) {
+        self.recent.push((ts, spread));
+        self.roll_up(0, SpreadBar::sample(ts, spread));
+    }
+
+    /// Add `bar` to tier `index`, closing its open bar into the next tier
+    /// once `bar` starts a new interval
+    fn roll_up(&mut self, index: usize, mut bar: SpreadBar) {
+        let Some(tier) = self.tiers.get_mut(index) else {
+            return;
+        };
+        let resolution_ns = tier.resolution_ms as u128 * 1_000_000;
+        bar.ts -= bar.ts % resolution_ns;
+        let closed = match tier.open.as_mut() {
+            // Samples from before the open bar (after a clock reset) stay in it
+            Some(open) if bar.ts <= open.ts => {
+                open.merge(&bar);
+                return;
+            }
+            _ => tier.open.replace(bar),
+        };
+        if let Some(closed) = closed {
+            tier.bars.push(closed);
+            self.roll_up(index + 1, closed);
+        }
+    }
+
+    /// Raw samples, oldest first
+    pub fn recent(&self) -> Vec<(u128, i64)> {
+        self.recent.to_vec()
+    }
+
+    /// Release capacity beyond the raw samples' and tiers' budgets
+    pub fn compact(&mut self) -> Compaction {
+        self.tiers.iter_mut().fold(self.recent.compact(), |total, tier| total + tier.bars.compact())
+    }
+
+    /// Occupancy of the raw samples
+    pub fn usage(&self) -> HistoryUsage {
+        self.recent.usage()
+    }
+
+    /// Number of raw samples
+    pub fn len(&self) -> usize {
+        self.recent.len()
+    }
+
+    /// Whether nothing has been recorded
+    pub fn is_empty(&self) -> bool {
+        self.recent.is_empty()
+    }
+
+    /// Drop the oldest raw samples, returning how many were dropped
+    pub fn evict_oldest(&mut self, count: usize) -> usize {
+        self.recent.evict_oldest(count)
+    }
+
+    /// Every tier, finest first
+    pub fn tiers(&self) -> Vec<SpreadSeries> {
+        self.tiers.iter().map(Tier::series).collect()
+    }
+
+    /// The tier at `resolution_ms`, if one is configured
+    pub fn series(&self, resolution_ms: u64) -> Option<SpreadSeries> {
+        self.tiers.iter()
+            .find(|tier| tier.resolution_ms == resolution_ms)
+            .map(Tier::series)
+    }
+
+    /// Spread over the whole run at the finest resolution that still covers
+    /// it: the raw samples until they start evicting, then the first tier
+    /// that has dropped nothing, or failing that the coarsest tier. Bars are
+    /// given as their mean.
+    pub fn overview(&self) -> Vec<(u128, i64)> {
+        if self.recent.usage().evicted == 0 || self.tiers.is_empty() {
+            return self.recent();
+        }
+        let tier = self.tiers.iter()
+            .find(|tier| tier.bars.usage().evicted == 0)
+            .unwrap_or(&self.tiers[self.tiers.len() - 1]);
+        tier.series().bars.iter().map(|bar| (bar.ts, bar.mean.round() as i64)).collect()
+    }
+
+    /// Drop every sample and bar
+    pub fn clear(&mut self) {
+        self.recent.clear();
+        for tier in &mut self.tiers {
+            tier.bars.clear();
+            tier.open = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: u128 = 1_000_000_000;
+
+    #[test]
+    fn test_bars_roll_up_into_coarser_tiers() {
+        let tiers = [
+            SpreadTier { resolution_ms: 1_000, points: 5 },
+            SpreadTier { resolution_ms: 10_000, points: 5 },
+        ];
+        let mut history = SpreadHistory::new(4, &tiers);
+        // Two samples a second for 25 seconds: spread 100 then 300
+        for second in 0..25 {
+            history.push(second * SECOND, 100);
+            history.push(second * SECOND + SECOND / 2, 300);
+        }
+
+        assert_eq!(history.recent().len(), 4);
+        let seconds = history.series(1_000).unwrap();
+        // Five closed bars kept, plus the open one
+        assert_eq!(seconds.bars.len(), 6);
+        assert_eq!(seconds.evicted, 19);
+        let bar = seconds.bars[0];
+        assert_eq!((bar.ts, bar.samples, bar.min, bar.max, bar.last), (19 * SECOND, 2, 100, 300, 300));
+        assert_eq!(bar.mean, 200.0);
+
+        // Closed seconds 0-23 have rolled up; second 20 onwards is still open
+        let tens = history.series(10_000).unwrap();
+        assert_eq!(tens.bars.iter().map(|bar| (bar.ts, bar.samples)).collect::<Vec<_>>(),
+                   vec![(0, 20), (10 * SECOND, 20), (20 * SECOND, 8)]);
+        assert!(history.series(60_000).is_none());
+
+        // The raw samples and the 1s tier have evicted, so the overview is the 10s tier
+        assert_eq!(history.overview(), vec![(0, 200), (10 * SECOND, 200), (20 * SECOND, 200)]);
+
+        history.clear();
+        assert!(history.is_empty());
+        assert!(history.tiers().iter().all(|series| series.bars.is_empty()));
+    }
+
+    #[test]
+    fn test_tier_chain_validation() {
+        assert!(SpreadTier::validate_chain(&SpreadTier::defaults()).is_ok());
+        assert!(SpreadTier::validate_chain(&[]).is_ok());
+        let tier = |resolution_ms, points| SpreadTier { resolution_ms, points };
+        assert!(SpreadTier::validate_chain(&[tier(0, 10)]).is_err());
+        assert!(SpreadTier::validate_chain(&[tier(1_000, 0)]).is_err());
+        assert!(SpreadTier::validate_chain(&[tier(1_000, 10), tier(1_500, 10)]).is_err());
+        assert!(SpreadTier::validate_chain(&[tier(10_000, 10), tier(1_000, 10)]).is_err());
+    }
+}
@@ -0,0 +1,279 @@
+//! Throughput soak testing
+//!
+//! Drives an engine with synthetic HFT order flow as fast as it will go for a
+//! fixed duration, reporting throughput, per-operation latency percentiles and
+//! process memory growth at a regular interval. This checks sustained
+//! performance outside Criterion, where warm caches and short runs flatter
+//! the numbers.
+
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use crate::engine::OrderBookEngine;
+use crate::error::EngineResult;
+use crate::generator::HftWorkload;
+use crate::memory::MemoryTracker;
+
+/// Values below this are bucketed exactly
+const LINEAR_BUCKETS: usize = 16;
+/// Mantissa bits kept for larger values, bounding the bucket error at 1/16
+const MANTISSA_BITS: u32 = 4;
+const BUCKET_COUNT: usize = LINEAR_BUCKETS + (64 - MANTISSA_BITS as usize) * LINEAR_BUCKETS;
+
+/// Fixed-size log-linear latency histogram in nanoseconds
+#[derive(Debug, Clone)]
+pub struct LatencyHistogram {
+    buckets: Vec<u64>,
+    count: u64,
+    max: u64,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self {
+            buckets: vec![0; BUCKET_COUNT],
+            count: 0,
+            max: 0,
+        }
+    }
+}
+
+impl LatencyHistogram {
+    /// Create an empty histogram
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn bucket(ns: u64) -> usize {
+        if ns < LINEAR_BUCKETS as u64 {
+            return ns as usize;
+        }
+        let exponent = 63 - ns.leading_zeros();
+        let shift = exponent - MANTISSA_BITS;
+        let mantissa = (ns >> shift) as usize - LINEAR_BUCKETS;
+        LINEAR_BUCKETS + shift as usize * LINEAR_BUCKETS + mantissa
+    }
+
+    /// Smallest value that falls in a bucket
+    fn bucket_floor(index: usize) -> u64 {
+        if index < LINEAR_BUCKETS {
+            return index as u64;
+        }
+        let shift = (index - LINEAR_BUCKETS) / LINEAR_BUCKETS;
+        let mantissa = (index - LINEAR_BUCKETS) % LINEAR_BUCKETS;
+        ((LINEAR_BUCKETS + mantissa) as u64) << shift
+    }
+
+    /// Record one latency sample
+    pub fn record(&mut self, ns: u64) {
+        self.buckets[Self::bucket(ns)] += 1;
+        self.count += 1;
+        self.max = self.max.max(ns);
+    }
+
+    /// Number of samples recorded
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Largest sample recorded
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    /// Latency at or below which `q` (0.0-1.0) of the samples fall, to within
+    /// the bucket resolution
+    pub fn percentile(&self, q: f64) -> u64 {
+        if self.count == 0 {
+            return 0;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (index, &count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Self::bucket_floor(index).min(self.max);
+            }
+        }
+        self.max
+    }
+
+    /// Add another histogram's samples to this one
+    pub fn merge(&mut self, other: &LatencyHistogram) {
+        for (bucket, count) in self.buckets.iter_mut().zip(&other.buckets) {
+            *bucket += count;
+        }
+        self.count += other.count;
+        self.max = self.max.max(other.max);
+    }
+
+    /// Remove all samples
+    pub fn clear(&mut self) {
+        self.buckets.iter_mut().for_each(|bucket| *bucket = 0);
+        self.count = 0;
+        self.max = 0;
+    }
+}
+
+/// Soak test settings
+#[derive(Debug, Clone)]
+pub struct StressConfig {
+    /// How long to push orders for
+    pub duration: Duration,
+    /// How often to emit a rolling report
+    pub report_interval: Duration,
+    /// Order flow to push through the engine
+    pub workload: HftWorkload,
+}
+
+impl Default for StressConfig {
+    fn default() -> Self {
+        Self {
+            duration: Duration::from_secs(30),
+            report_interval: Duration::from_secs(1),
+            workload: HftWorkload::new(),
+        }
+    }
+}
+
+/// Throughput, latency and memory over one reporting period
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct StressReport {
+    /// Wall time covered by the report
+    pub elapsed: Duration,
+    /// Operations applied
+    pub ops: u64,
+    /// Trades produced
+    pub trades: u64,
+    /// Operations per second
+    pub ops_per_sec: f64,
+    pub p50_ns: u64,
+    pub p99_ns: u64,
+    pub p999_ns: u64,
+    pub max_ns: u64,
+    /// Resident memory at the end of the period
+    pub rss_bytes: usize,
+    /// Resident memory growth since the run started
+    pub rss_growth_bytes: isize,
+}
+
+impl StressReport {
+    fn new(elapsed: Duration, ops: u64, trades: u64, latency: &LatencyHistogram, memory: &MemoryTracker) -> Self {
+        let secs = elapsed.as_secs_f64();
+        Self {
+            elapsed,
+            ops,
+            trades,
+            ops_per_sec: if secs > 0.0 { ops as f64 / secs } else { 0.0 },
+            p50_ns: latency.percentile(0.50),
+            p99_ns: latency.percentile(0.99),
+            p999_ns: latency.percentile(0.999),
+            max_ns: latency.max(),
+            rss_bytes: memory.current_usage(),
+            rss_growth_bytes: memory.usage_since_init(),
+        }
+    }
+}
+
+/// Run a soak test against `engine`, calling `on_report` after each
+/// reporting interval, and return the report for the whole run
+///
+/// Stops at the first operation the engine rejects.
+pub fn run<E: OrderBookEngine>(
+    engine: &mut E,
+    config: &StressConfig,
+    mut on_report: impl FnMut(&StressReport),
+) -> EngineResult<StressReport> {
+    let mut stream = config.workload.stream();
+    for order in stream.seed_book() {
+        engine.place(order)?;
+    }
+
+    let memory = MemoryTracker::new();
+    let mut total = LatencyHistogram::new();
+    let mut interval = LatencyHistogram::new();
+    let (mut total_ops, mut total_trades) = (0, 0);
+    let (mut interval_ops, mut interval_trades) = (0, 0);
+
+    let start = Instant::now();
+    let mut interval_start = start;
+    for op in stream {
+        let op_start = Instant::now();
+        let trades = op.apply(engine)?;
+        let now = Instant::now();
+        interval.record((now - op_start).as_nanos() as u64);
+        interval_ops += 1;
+        interval_trades += trades.len() as u64;
+
+        let done = now - start >= config.duration;
+        if done || now - interval_start >= config.report_interval {
+            on_report(&StressReport::new(now - interval_start, interval_ops, interval_trades, &interval, &memory));
+            total.merge(&interval);
+            total_ops += interval_ops;
+            total_trades += interval_trades;
+            interval.clear();
+            interval_ops = 0;
+            interval_trades = 0;
+            interval_start = now;
+        }
+        if done {
+            break;
+        }
+    }
+
+    Ok(StressReport::new(start.elapsed(), total_ops, total_trades, &total, &memory))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::OrderBook;
+    use crate::queue_fifo::FifoLevel;
+
+    #[test]
+    fn test_histogram_percentiles() {
+        let mut histogram = LatencyHistogram::new();
+        assert_eq!(histogram.percentile(0.5), 0);
+        for ns in 1..=1000 {
+            histogram.record(ns);
+        }
+        assert_eq!(histogram.count(), 1000);
+        assert_eq!(histogram.max(), 1000);
+
+        // Within the 1/16 bucket resolution
+        let p50 = histogram.percentile(0.5);
+        assert!((470..=500).contains(&p50), "p50 {}", p50);
+        let p99 = histogram.percentile(0.99);
+        assert!((930..=990).contains(&p99), "p99 {}", p99);
+        assert_eq!(histogram.percentile(1.0), 992);
+
+        let mut merged = LatencyHistogram::new();
+        merged.record(u64::MAX);
+        merged.merge(&histogram);
+        assert_eq!(merged.count(), 1001);
+        assert_eq!(merged.max(), u64::MAX);
+        assert!(merged.percentile(1.0) >= 1 << 63);
+
+        histogram.clear();
+        assert_eq!(histogram.count(), 0);
+    }
+
+    #[test]
+    fn test_run_reports_each_interval() {
+        let mut engine = OrderBook::<FifoLevel>::new();
+        let config = StressConfig {
+            duration: Duration::from_millis(200),
+            report_interval: Duration::from_millis(50),
+            workload: HftWorkload::new().with_seed(9),
+        };
+
+        let mut reports = Vec::new();
+        let total = run(&mut engine, &config, |report| reports.push(*report)).unwrap();
+
+        assert!(reports.len() >= 2);
+        assert_eq!(reports.iter().map(|report| report.ops).sum::<u64>(), total.ops);
+        assert_eq!(reports.iter().map(|report| report.trades).sum::<u64>(), total.trades);
+        assert!(total.elapsed >= config.duration);
+        assert!(total.ops_per_sec > 0.0);
+        assert!(total.p50_ns <= total.p99_ns && total.p99_ns <= total.max_ns);
+    }
+}
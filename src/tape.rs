@@ -0,0 +1,190 @@
+//! Block trade reporting on the public tape
+//!
+//! Real tape rules let very large trades print late so the parties can lay
+//! off the risk before the market sees the size. Trades of at least the
+//! configured block size are tagged, held back from public dissemination
+//! (snapshots, the relay feed and the streaming publisher) for the configured
+//! delay, and reported privately to the sessions that took part straight away.
+
+use std::collections::VecDeque;
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::engine::DepthSnapshot;
+use crate::memory::{shrink_deque, Compaction};
+use crate::types::{ExecutionSummary, Qty, Trade};
+
+/// Block trade threshold and public reporting delay
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct BlockTradeConfig {
+    /// Tag and delay block trades
+    pub enabled: bool,
+    /// Trades of at least this quantity are block trades
+    pub min_qty: Qty,
+    /// Wall-clock time a block trade is held back from the public tape
+    /// (milliseconds); 0 prints it at once, tagged
+    pub public_delay_ms: u64,
+}
+
+impl Default for BlockTradeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_qty: 1_000,
+            public_delay_ms: 5_000,
+        }
+    }
+}
+
+impl BlockTradeConfig {
+    /// Check the threshold
+    pub fn validate(&self) -> Result<(), String> {
+        if self.min_qty == 0 {
+            return Err("Block trade minimum quantity must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Block trade counts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TapeStats {
+    /// Block trades seen
+    pub blocks: u64,
+    /// Block trades printed on the public tape so far
+    pub printed: u64,
+}
+
+/// Decides which trades print on the public tape, and when
+#[derive(Debug, Clone)]
+pub struct TapeFilter {
+    config: BlockTradeConfig,
+    /// Block trades waiting to print, with the wall-clock time they are due
+    held: VecDeque<(u64, Trade)>,
+    stats: TapeStats,
+}
+
+impl TapeFilter {
+    /// Create a filter with the given threshold and delay
+    pub fn new(config: BlockTradeConfig) -> Self {
+        Self {
+            config,
+            held: VecDeque::new(),
+            stats: TapeStats::default(),
+        }
+    }
+
+    /// Release the capacity left over once held block trades have printed
+    pub fn compact(&mut self) -> Compaction {
+        shrink_deque(&mut self.held, 0)
+    }
+
+    /// Get the block trade configuration
+    pub fn config(&self) -> &BlockTradeConfig {
+        &self.config
+    }
+
+    /// Get block trade counts
+    pub fn stats(&self) -> &TapeStats {
+        &self.stats
+    }
+
+    /// Block trades waiting to print
+    pub fn held(&self) -> usize {
+        self.held.len()
+    }
+
+    /// Whether a trade is a block trade
+    pub fn is_block(&self, trade: &Trade) -> bool {
+        self.config.enabled && trade.qty >= self.config.min_qty
+    }
+
+    /// Rewrite a snapshot's trades into what the public tape shows at
+    /// `now_ms`, returning the block trades it executed for private reporting
+    ///
+    /// Block trades are taken out and held; those that are due print after
+    /// the snapshot's other trades and are listed in `block_trades`.
+    pub fn apply(&mut self, snapshot: &mut DepthSnapshot, now_ms: u64) -> Vec<Trade> {
+        if !self.config.enabled {
+            return Vec::new();
+        }
+
+        let (blocks, mut public): (Vec<Trade>, Vec<Trade>) = std::mem::take(&mut snapshot.trades)
+            .into_iter()
+            .partition(|trade| self.is_block(trade));
+        self.stats.blocks += blocks.len() as u64;
+        let due = now_ms.saturating_add(self.config.public_delay_ms);
+        self.held.extend(blocks.iter().map(|trade| (due, trade.clone())));
+
+        let mut printed = Vec::new();
+        while self.held.front().is_some_and(|(due, _)| *due <= now_ms) {
+            if let Some((_, trade)) = self.held.pop_front() {
+                printed.push(trade);
+            }
+        }
+        self.stats.printed += printed.len() as u64;
+        public.extend(printed.iter().cloned());
+
+        snapshot.executions = ExecutionSummary::aggregate(&public);
+        snapshot.trades = public;
+        snapshot.block_trades = printed;
+        blocks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{OrderBook, OrderBookEngine};
+    use crate::queue_fifo::FifoLevel;
+    use crate::types::Side;
+
+    fn snapshot(trades: Vec<Trade>) -> DepthSnapshot {
+        let mut snapshot = OrderBook::<FifoLevel>::new().snapshot();
+        snapshot.trades = trades;
+        snapshot
+    }
+
+    fn trade(taker_id: u64, qty: Qty) -> Trade {
+        Trade { maker_id: 1, taker_id, price: 100_0000, qty, ts: 0, aggressor: Side::Buy }
+    }
+
+    #[test]
+    fn test_block_trades_print_after_the_delay() {
+        let mut tape = TapeFilter::new(BlockTradeConfig { enabled: true, min_qty: 500, public_delay_ms: 100 });
+
+        // The block is reported privately at once but kept off the tape
+        let mut first = snapshot(vec![trade(2, 10), trade(3, 800)]);
+        let blocks = tape.apply(&mut first, 1_000);
+        assert_eq!(blocks, vec![trade(3, 800)]);
+        assert_eq!(first.trades, vec![trade(2, 10)]);
+        assert_eq!(first.executions.len(), 1);
+        assert!(first.block_trades.is_empty());
+        assert_eq!(tape.held(), 1);
+
+        let mut early = snapshot(vec![]);
+        tape.apply(&mut early, 1_050);
+        assert!(early.trades.is_empty());
+
+        // Once due it prints, tagged, after the step's own trades
+        let mut due = snapshot(vec![trade(4, 20)]);
+        assert!(tape.apply(&mut due, 1_100).is_empty());
+        assert_eq!(due.trades, vec![trade(4, 20), trade(3, 800)]);
+        assert_eq!(due.block_trades, vec![trade(3, 800)]);
+        assert_eq!(*tape.stats(), TapeStats { blocks: 1, printed: 1 });
+    }
+
+    #[test]
+    fn test_disabled_filter_leaves_the_tape_alone() {
+        let mut tape = TapeFilter::new(BlockTradeConfig { min_qty: 500, ..BlockTradeConfig::default() });
+        let mut unchanged = snapshot(vec![trade(2, 800)]);
+        assert!(tape.apply(&mut unchanged, 0).is_empty());
+        assert_eq!(unchanged.trades.len(), 1);
+
+        // No delay prints blocks at once, still tagged
+        let mut tape = TapeFilter::new(BlockTradeConfig { enabled: true, min_qty: 500, public_delay_ms: 0 });
+        let mut tagged = snapshot(vec![trade(2, 800)]);
+        assert_eq!(tape.apply(&mut tagged, 0).len(), 1);
+        assert_eq!(tagged.block_trades, tagged.trades);
+        assert!(BlockTradeConfig { min_qty: 0, ..BlockTradeConfig::default() }.validate().is_err());
+    }
+}
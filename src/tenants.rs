@@ -0,0 +1,129 @@
+//! Isolated books hosted by one server
+//!
+//! A classroom or team demo wants several books side by side, one per
+//! symbol, group or experiment, without a server process for each. Each
+//! `[[tenants]]` entry of the configuration runs its own simulator, built
+//! from the server's settings with the entry's `overrides` merged over them
+//! the way a profile is. Its endpoints live under `/t/{name}`, so its
+//! WebSocket is `/t/{name}/ws`, and nothing one tenant does reaches another's
+//! book, clients or timeline.
+//!
+//! A [`TenantQuota`] bounds what each tenant may use: connections, how fast
+//! its simulator steps, and how many agents it runs.
+
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::plugin::DynLevel;
+use crate::config::Config;
+use crate::engine::OrderBook;
+use crate::sim::Simulator;
+
+/// Sections that belong to the process rather than to one book
+pub const SHARED_SECTIONS: &[&str] = &[
+    "server", "tenants", "relay", "publisher", "redis", "metrics_store", "webhooks",
+    "recordings", "report", "features", "logging",
+];
+
+/// Resources one tenant may use
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct TenantQuota {
+    /// Concurrent connections across the tenant's endpoints
+    pub max_connections: usize,
+    /// Concurrent connections from one IP
+    pub max_connections_per_ip: Option<usize>,
+    /// Shortest step interval the tenant may configure (milliseconds)
+    pub min_step_interval_ms: u64,
+    /// Agents the tenant may run, configured and added at runtime together
+    pub max_agents: Option<usize>,
+}
+
+impl Default for TenantQuota {
+    fn default() -> Self {
+        Self {
+            max_connections: 50,
+            max_connections_per_ip: None,
+            min_step_interval_ms: 10,
+            max_agents: Some(20),
+        }
+    }
+}
+
+/// One isolated book and the settings it differs in
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct TenantConfig {
+    /// Name in the tenant's URLs, `/t/{name}/...`
+    pub name: String,
+    /// Settings overriding the server's for this tenant, written as in the
+    /// configuration file
+    #[serde(default)]
+    #[schemars(with = "BTreeMap<String, serde_json::Value>")]
+    pub overrides: toml::Table,
+    #[serde(default)]
+    pub quota: TenantQuota,
+}
+
+impl TenantConfig {
+    /// Check the name, the quota and that the overrides only touch the book
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() || !self.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(format!("Tenant name '{}' must be letters, digits, '-' or '_'", self.name));
+        }
+        if self.quota.max_connections == 0 {
+            return Err(format!("Tenant '{}' must allow at least one connection", self.name));
+        }
+        if let Some(section) = SHARED_SECTIONS.iter().find(|section| self.overrides.contains_key(**section)) {
+            return Err(format!("Tenant '{}' cannot override [{}], which the whole server shares", self.name, section));
+        }
+        Ok(())
+    }
+
+    /// Check the tenant's resolved settings against its quota
+    pub fn check_quota(&self, config: &Config) -> Result<(), String> {
+        if config.simulation.step_interval_ms < self.quota.min_step_interval_ms {
+            return Err(format!(
+                "Tenant '{}' steps every {}ms, faster than its quota of {}ms",
+                self.name, config.simulation.step_interval_ms, self.quota.min_step_interval_ms
+            ));
+        }
+        let agents: usize = config.agents.iter().map(|spec| spec.count).sum();
+        if let Some(max_agents) = self.quota.max_agents.filter(|&max_agents| agents > max_agents) {
+            return Err(format!("Tenant '{}' configures {} agents, over its quota of {}", self.name, agents, max_agents));
+        }
+        Ok(())
+    }
+}
+
+/// A tenant ready to serve: its entry, resolved settings and simulator
+pub struct Tenant {
+    pub config: TenantConfig,
+    /// The server's settings with the tenant's overrides applied
+    pub settings: Config,
+    pub simulator: Simulator<OrderBook<DynLevel>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tenant(name: &str, overrides: &str) -> TenantConfig {
+        TenantConfig { name: name.to_string(), overrides: toml::from_str(overrides).unwrap(), quota: TenantQuota::default() }
+    }
+
+    #[test]
+    fn test_tenant_settings_resolve_within_quota() {
+        let base = Config::default();
+        let seeded = tenant("class-a", "[simulation]\nrandom_seed = 7\nstep_interval_ms = 250\n");
+        let settings = base.tenant_config(&seeded).unwrap();
+        assert_eq!(settings.simulation.random_seed, Some(7));
+        assert_eq!(settings.simulation.step_interval_ms, 250);
+        assert_eq!(settings.server.port, base.server.port);
+
+        assert!(tenant("class b", "").validate().is_err());
+        assert!(tenant("ops", "[server]\nport = 9000\n").validate().is_err());
+        assert!(tenant("ops", "[features]\nenabled = true\n").validate().is_err());
+        let fast = tenant("fast", "[simulation]\nstep_interval_ms = 1\n");
+        assert!(base.tenant_config(&fast).unwrap_err().to_string().contains("quota"));
+    }
+}
@@ -0,0 +1,223 @@
+//! Auto-throttling of synthetic order flow
+//!
+//! A heavy configuration can make steps take longer than the step interval,
+//! or let requests pile up waiting for the simulation thread. Rather than fall
+//! further behind without saying so, the simulator can scale its synthetic
+//! order generation down while it is overloaded and back up once it has
+//! headroom again. It backs off by a factor and recovers in small increments,
+//! so the rate settles just under what the host can sustain.
+
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use std::time::Duration;
+
+/// When and how far to scale synthetic order generation
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ThrottleConfig {
+    /// Adjust the synthetic order rate under load
+    pub enabled: bool,
+    /// Back off when smoothed step time exceeds this share of the interval
+    pub high_water: f64,
+    /// Recover when smoothed step time is under this share of the interval
+    pub low_water: f64,
+    /// Back off when more calls than this are waiting for the simulator
+    pub max_queued_calls: usize,
+    /// Factor the rate is multiplied by when backing off
+    pub backoff: f64,
+    /// Share of the full rate added back when recovering
+    pub recovery: f64,
+    /// Lowest share of the full rate to throttle down to
+    pub min_rate: f64,
+    /// Steps to wait after an adjustment before the next one
+    pub cooldown_steps: u32,
+}
+
+impl Default for ThrottleConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            high_water: 0.8,
+            low_water: 0.5,
+            max_queued_calls: 32,
+            backoff: 0.5,
+            recovery: 0.1,
+            min_rate: 0.1,
+            cooldown_steps: 20,
+        }
+    }
+}
+
+impl ThrottleConfig {
+    /// Check the thresholds and factors
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.low_water > 0.0 && self.low_water < self.high_water) {
+            return Err("Throttle low water must be above 0 and below high water".to_string());
+        }
+        if !(self.backoff > 0.0 && self.backoff < 1.0) {
+            return Err("Throttle backoff must be between 0 and 1".to_string());
+        }
+        if !(self.recovery > 0.0 && self.recovery <= 1.0) {
+            return Err("Throttle recovery must be above 0 and at most 1".to_string());
+        }
+        if !(self.min_rate > 0.0 && self.min_rate <= 1.0) {
+            return Err("Throttle minimum rate must be above 0 and at most 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A change to the synthetic order rate
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ThrottleAdjustment {
+    /// Share of the full rate before the change
+    pub from: f64,
+    /// Share of the full rate after it
+    pub to: f64,
+    /// Smoothed step time as a share of the interval
+    pub load: f64,
+    /// Calls waiting for the simulator
+    pub queued: usize,
+}
+
+impl ThrottleAdjustment {
+    /// Whether the rate went down
+    pub fn is_backoff(&self) -> bool {
+        self.to < self.from
+    }
+}
+
+impl std::fmt::Display for ThrottleAdjustment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Synthetic order flow {} to {:.0}% (steps at {:.0}% of the interval, {} calls queued)",
+            if self.is_backoff() { "throttled" } else { "restored" },
+            self.to * 100.0,
+            self.load * 100.0,
+            self.queued,
+        )
+    }
+}
+
+/// Feedback controller for the synthetic order rate
+#[derive(Debug, Clone)]
+pub struct FlowThrottle {
+    config: ThrottleConfig,
+    /// Share of the full synthetic order rate currently let through
+    rate: f64,
+    /// Exponentially smoothed step time as a share of the interval
+    load: f64,
+    steps_since_adjustment: u32,
+}
+
+/// Weight of the latest step in the smoothed load
+const LOAD_SMOOTHING: f64 = 0.2;
+
+impl FlowThrottle {
+    /// Start at the full rate
+    pub fn new(config: ThrottleConfig) -> Self {
+        Self {
+            steps_since_adjustment: config.cooldown_steps,
+            config,
+            rate: 1.0,
+            load: 0.0,
+        }
+    }
+
+    /// Get the thresholds and factors
+    pub fn config(&self) -> &ThrottleConfig {
+        &self.config
+    }
+
+    /// Share of the full synthetic order rate currently let through
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// Smoothed step time as a share of the interval
+    pub fn load(&self) -> f64 {
+        self.load
+    }
+
+    /// Record how long a step took against its interval and how many calls
+    /// are waiting, returning the adjustment if the rate changes
+    pub fn observe(&mut self, step: Duration, interval: Duration, queued: usize) -> Option<ThrottleAdjustment> {
+        let load = step.as_secs_f64() / interval.as_secs_f64().max(f64::MIN_POSITIVE);
+        self.load += LOAD_SMOOTHING * (load - self.load);
+        self.steps_since_adjustment = self.steps_since_adjustment.saturating_add(1);
+        if self.steps_since_adjustment < self.config.cooldown_steps {
+            return None;
+        }
+
+        let overloaded = self.load > self.config.high_water || queued > self.config.max_queued_calls;
+        let idle = self.load < self.config.low_water && queued == 0;
+        let to = if overloaded {
+            (self.rate * self.config.backoff).max(self.config.min_rate)
+        } else if idle {
+            (self.rate + self.config.recovery).min(1.0)
+        } else {
+            self.rate
+        };
+        if to == self.rate {
+            return None;
+        }
+
+        let adjustment = ThrottleAdjustment { from: self.rate, to, load: self.load, queued };
+        self.rate = to;
+        self.steps_since_adjustment = 0;
+        Some(adjustment)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn throttle() -> FlowThrottle {
+        FlowThrottle::new(ThrottleConfig { enabled: true, cooldown_steps: 2, ..ThrottleConfig::default() })
+    }
+
+    const INTERVAL: Duration = Duration::from_millis(10);
+
+    #[test]
+    fn test_backs_off_under_load_and_recovers_with_headroom() {
+        let mut throttle = throttle();
+        // Slow steps push the smoothed load over the high water mark
+        let adjustments: Vec<_> = (0..20)
+            .filter_map(|_| throttle.observe(Duration::from_millis(15), INTERVAL, 0))
+            .collect();
+        assert!(adjustments.iter().all(ThrottleAdjustment::is_backoff));
+        assert_eq!(throttle.rate(), 0.1);
+        assert!(adjustments[0].to_string().starts_with("Synthetic order flow throttled to 50%"));
+
+        // Fast steps bring it back in small increments, never past full rate
+        let mut restored = 0;
+        for _ in 0..200 {
+            if let Some(adjustment) = throttle.observe(Duration::from_millis(1), INTERVAL, 0) {
+                assert!(!adjustment.is_backoff());
+                assert!(adjustment.to - adjustment.from <= 0.1 + f64::EPSILON);
+                restored += 1;
+            }
+        }
+        assert_eq!(throttle.rate(), 1.0);
+        assert!(restored >= 9);
+    }
+
+    #[test]
+    fn test_queue_backlog_backs_off_and_cooldown_spaces_adjustments() {
+        let mut throttle = throttle();
+        let backlog = throttle.config().max_queued_calls + 1;
+        let first = throttle.observe(Duration::ZERO, INTERVAL, backlog).unwrap();
+        assert_eq!((first.from, first.to, first.queued), (1.0, 0.5, backlog));
+
+        // Nothing changes until the cooldown has passed
+        assert_eq!(throttle.observe(Duration::ZERO, INTERVAL, backlog), None);
+        assert_eq!(throttle.observe(Duration::ZERO, INTERVAL, backlog).map(|adjustment| adjustment.to), Some(0.25));
+
+        // Calls still waiting hold the rate where it is
+        for _ in 0..10 {
+            assert_eq!(throttle.observe(Duration::ZERO, INTERVAL, 1), None);
+        }
+        assert_eq!(throttle.rate(), 0.25);
+    }
+}
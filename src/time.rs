@@ -1,13 +1,58 @@
+use std::cell::Cell;
+use std::marker::PhantomData;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+thread_local! {
+    /// Wall-clock isolation guards open on this thread
+    static WALL_CLOCK_ISOLATION: Cell<usize> = const { Cell::new(0) };
+}
+
 /// Get current timestamp in nanoseconds since Unix epoch
+///
+/// # Panics
+///
+/// Panics while a [`WallClockIsolation`] guard is open on this thread.
+#[track_caller]
 pub fn now_ns() -> u128 {
+    if wall_clock_isolated() {
+        panic!("now_ns() called under wall-clock isolation; timestamps here must come from the simulation clock");
+    }
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .expect("Time went backwards")
         .as_nanos()
 }
 
+/// Forbids [`now_ns`] on the current thread until dropped
+///
+/// A guardrail for deterministic runs: code that should only see injected
+/// simulation time runs under a guard, and any wall-clock read inside it
+/// panics at the caller. Guards nest.
+#[must_use = "isolation ends when the guard is dropped"]
+pub struct WallClockIsolation {
+    /// Tied to the thread whose counter it raised
+    _thread: PhantomData<*const ()>,
+}
+
+impl WallClockIsolation {
+    /// Start isolating this thread from the wall clock
+    pub fn enter() -> Self {
+        WALL_CLOCK_ISOLATION.with(|depth| depth.set(depth.get() + 1));
+        Self { _thread: PhantomData }
+    }
+}
+
+impl Drop for WallClockIsolation {
+    fn drop(&mut self) {
+        WALL_CLOCK_ISOLATION.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+/// Whether a [`WallClockIsolation`] guard is open on this thread
+pub fn wall_clock_isolated() -> bool {
+    WALL_CLOCK_ISOLATION.with(Cell::get) > 0
+}
+
 /// Convert milliseconds to nanoseconds
 pub fn ms_to_ns(ms: u64) -> u128 {
     ms as u128 * 1_000_000
@@ -39,6 +84,41 @@ pub fn format_ns(ns: u128) -> String {
     format!("{:.9}", secs)
 }
 
+/// Nanoseconds in a day
+pub const NANOS_PER_DAY: u128 = 86_400 * 1_000_000_000;
+
+/// Midnight UTC of a `YYYY-MM-DD` date in nanoseconds since the Unix epoch
+///
+/// Returns `None` for malformed dates and dates before 1970.
+pub fn date_to_ns(date: &str) -> Option<u128> {
+    let mut parts = date.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: u32 = parts.next()?.parse().ok()?;
+    let day: u32 = parts.next()?.parse().ok()?;
+
+    let leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return None,
+    };
+    if day == 0 || day > days_in_month {
+        return None;
+    }
+
+    // Days since the epoch, counting years from March so leap days come last
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) as i64 + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    u128::try_from(days).ok().map(|days| days * NANOS_PER_DAY)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -75,10 +155,38 @@ mod tests {
         assert!(elapsed_ns(ts1, ts2) > 0);
     }
 
+    #[test]
+    fn test_wall_clock_isolation_nests() {
+        let outer = WallClockIsolation::enter();
+        let inner = WallClockIsolation::enter();
+        drop(inner);
+        assert!(wall_clock_isolated());
+        let read = std::panic::catch_unwind(now_ns);
+        assert!(read.is_err());
+
+        drop(outer);
+        assert!(!wall_clock_isolated());
+        assert!(now_ns() > 0);
+
+        // Other threads are unaffected
+        let _guard = WallClockIsolation::enter();
+        assert!(std::thread::spawn(now_ns).join().is_ok());
+    }
+
     #[test]
     fn test_format_ns() {
         let ns = 1_500_000_000u128; // 1.5 seconds
         let formatted = format_ns(ns);
         assert!(formatted.contains("1.500000000"));
     }
+
+    #[test]
+    fn test_date_to_ns() {
+        assert_eq!(date_to_ns("1970-01-01"), Some(0));
+        assert_eq!(date_to_ns("2024-03-01"), Some(1_709_251_200 * 1_000_000_000));
+        assert_eq!(date_to_ns("2024-02-29").map(|ns| ns / NANOS_PER_DAY), Some(19_782));
+        assert_eq!(date_to_ns("2023-02-29"), None);
+        assert_eq!(date_to_ns("1969-12-31"), None);
+        assert_eq!(date_to_ns("2024-13-01"), None);
+    }
 }
\ No newline at end of file
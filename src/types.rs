@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 
 /// Unique identifier for orders
 pub type OrderId = u64;
@@ -38,9 +39,88 @@ pub struct Order {
 pub struct Trade {
     pub maker_id: OrderId,
     pub taker_id: OrderId,
+    #[serde(serialize_with = "crate::price_format::ticks")]
     pub price: Price,
     pub qty: Qty,
     pub ts: u128,
+    /// Side of the incoming order that took liquidity. Trades recorded
+    /// before it was kept read as buys
+    #[serde(default = "default_aggressor")]
+    pub aggressor: Side,
+}
+
+fn default_aggressor() -> Side {
+    Side::Buy
+}
+
+/// One maker's part in an execution
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MakerFill {
+    pub maker_id: OrderId,
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub price: Price,
+    pub qty: Qty,
+}
+
+/// The trades one aggressive order executed, taken together
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionSummary {
+    pub taker_id: OrderId,
+    pub aggressor: Side,
+    /// Total quantity filled
+    pub qty: Qty,
+    /// Volume-weighted fill price in ticks
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub avg_price: f64,
+    /// Distinct prices the order filled at
+    pub levels_swept: usize,
+    /// Time of the first fill
+    pub ts: u128,
+    /// Per-maker breakdown, in execution order
+    pub fills: Vec<MakerFill>,
+}
+
+impl ExecutionSummary {
+    /// Group trades into one summary per aggressive order
+    ///
+    /// Consecutive trades with the same taker and aggressor belong to one
+    /// order. Tape prints without a taker (id 0) are summarized one by one.
+    pub fn aggregate(trades: &[Trade]) -> Vec<Self> {
+        let mut summaries: Vec<Self> = Vec::new();
+        for trade in trades {
+            match summaries.last_mut() {
+                Some(summary) if trade.taker_id != 0
+                    && summary.taker_id == trade.taker_id
+                    && summary.aggressor == trade.aggressor => summary.add(trade),
+                _ => summaries.push(Self::new(trade)),
+            }
+        }
+        summaries
+    }
+
+    fn new(trade: &Trade) -> Self {
+        Self {
+            taker_id: trade.taker_id,
+            aggressor: trade.aggressor,
+            qty: trade.qty,
+            avg_price: trade.price as f64,
+            levels_swept: 1,
+            ts: trade.ts,
+            fills: vec![MakerFill { maker_id: trade.maker_id, price: trade.price, qty: trade.qty }],
+        }
+    }
+
+    fn add(&mut self, trade: &Trade) {
+        let qty = self.qty + trade.qty;
+        if qty > 0 {
+            self.avg_price = (self.avg_price * self.qty as f64 + trade.price as f64 * trade.qty as f64) / qty as f64;
+        }
+        self.qty = qty;
+        if self.fills.iter().all(|fill| fill.price != trade.price) {
+            self.levels_swept += 1;
+        }
+        self.fills.push(MakerFill { maker_id: trade.maker_id, price: trade.price, qty: trade.qty });
+    }
 }
 
 impl Order {
@@ -95,6 +175,63 @@ impl Side {
     }
 }
 
+/// Whether a fill provided or took liquidity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Liquidity {
+    /// Resting order that was executed against (passive)
+    Maker,
+    /// Incoming order that executed (aggressive)
+    Taker,
+}
+
+/// PnL from fills in one liquidity role
+///
+/// Each fill is valued against the mid just before it traded. The edge over
+/// that mid is spread capture; how the mid has moved since, applied to the
+/// position the fills built, is adverse selection. Together they make up the
+/// role's mark-to-market PnL.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RolePnl {
+    /// Quantity filled in this role
+    pub volume: Qty,
+    /// Position built by this role's fills
+    pub inventory: i64,
+    /// Cash from this role's fills in ticks
+    pub cash: i64,
+    /// Fills valued at the mid when they traded, in ticks
+    pub mid_value: i64,
+    /// Edge against the mid at fill time in ticks (positive when filled
+    /// better than the mid)
+    pub spread_capture: i64,
+    /// Mid move since the fills on the position they built, in ticks
+    /// (negative when the market moved against them)
+    pub adverse_selection: i64,
+    /// Mark-to-market PnL in ticks
+    pub pnl: i64,
+}
+
+impl RolePnl {
+    /// Record a fill, with the mid just before it traded
+    pub fn record(&mut self, contract: &ContractSpec, side: Side, qty: Qty, price: Price, mid: Price) {
+        let signed_qty = match side {
+            Side::Buy => qty as i64,
+            Side::Sell => -(qty as i64),
+        };
+        self.volume += qty;
+        self.inventory += signed_qty;
+        self.cash = self.cash.saturating_sub(contract.saturating_value_of(signed_qty, price));
+        self.mid_value = self.mid_value.saturating_add(contract.saturating_value_of(signed_qty, mid));
+        self.spread_capture = self.cash.saturating_add(self.mid_value);
+    }
+
+    /// Revalue the role's position at a mark price
+    pub fn mark(&mut self, contract: &ContractSpec, mark: Price) {
+        self.adverse_selection = contract.saturating_value_of(self.inventory, mark).saturating_sub(self.mid_value);
+        self.pnl = self.spread_capture.saturating_add(self.adverse_selection);
+    }
+}
+
 /// Trading performance metrics
 #[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Metrics {
@@ -104,6 +241,18 @@ pub struct Metrics {
     pub cash: i64,
     /// Mark-to-market PnL in ticks
     pub pnl: i64,
+    /// Share of the PnL from passive fills
+    #[serde(default)]
+    pub maker: RolePnl,
+    /// Share of the PnL from aggressive fills
+    #[serde(default)]
+    pub taker: RolePnl,
+    /// Net funding received (negative when paid), already included in cash
+    #[serde(default)]
+    pub funding: i64,
+    /// Priority fees paid for queue position, already deducted from cash
+    #[serde(default)]
+    pub priority_fees: u64,
 }
 
 impl Metrics {
@@ -114,61 +263,347 @@ impl Metrics {
 
     /// Update metrics after a trade execution
     pub fn update_trade(&mut self, side: Side, qty: Qty, price: Price) {
+        self.update_trade_for(&ContractSpec::default(), side, qty, price);
+    }
+
+    /// Update metrics after a trade in a contract with the given payoff
+    pub fn update_trade_for(&mut self, contract: &ContractSpec, side: Side, qty: Qty, price: Price) {
+        let value = contract.saturating_value_of(qty as i64, price);
         match side {
             Side::Buy => {
                 // Buying increases inventory, decreases cash
                 self.inventory += qty as i64;
-                self.cash -= (qty * price) as i64;
+                self.cash = self.cash.saturating_sub(value);
             }
             Side::Sell => {
                 // Selling decreases inventory, increases cash
                 self.inventory -= qty as i64;
-                self.cash += (qty * price) as i64;
+                self.cash = self.cash.saturating_add(value);
             }
         }
     }
 
+    /// Update metrics after a fill, splitting it into the maker or taker PnL
+    /// using the mid just before it traded
+    pub fn record_fill(&mut self, liquidity: Liquidity, side: Side, qty: Qty, price: Price, mid: Price) {
+        self.record_fill_for(&ContractSpec::default(), liquidity, side, qty, price, mid);
+    }
+
+    /// Record a fill in a contract with the given payoff
+    pub fn record_fill_for(&mut self, contract: &ContractSpec, liquidity: Liquidity, side: Side, qty: Qty, price: Price, mid: Price) {
+        self.update_trade_for(contract, side, qty, price);
+        match liquidity {
+            Liquidity::Maker => self.maker.record(contract, side, qty, price, mid),
+            Liquidity::Taker => self.taker.record(contract, side, qty, price, mid),
+        }
+    }
+
     /// Calculate mark-to-market PnL using current mid-price
     pub fn calculate_pnl(&mut self, mid_price_ticks: Option<Price>) {
+        self.calculate_pnl_for(&ContractSpec::default(), mid_price_ticks);
+    }
+
+    /// Calculate mark-to-market PnL for a contract with the given payoff
+    pub fn calculate_pnl_for(&mut self, contract: &ContractSpec, mid_price_ticks: Option<Price>) {
         if let Some(mid_price) = mid_price_ticks {
-            // PnL = cash + (inventory * current_price)
-            self.pnl = self.cash + (self.inventory * mid_price as i64);
+            // PnL = cash + (inventory * current contract value)
+            self.pnl = self.cash.saturating_add(contract.saturating_value_of(self.inventory, mid_price));
+            self.maker.mark(contract, mid_price);
+            self.taker.mark(contract, mid_price);
         } else {
             // No market price available, PnL is just cash position
             self.pnl = self.cash;
         }
     }
 
+    /// Pay a funding transfer (negative to receive one)
+    pub fn settle_funding(&mut self, paid: i64) {
+        self.cash -= paid;
+        self.pnl -= paid;
+        self.funding -= paid;
+    }
+
+    /// Pay a priority fee for a resting order's queue position
+    pub fn pay_priority_fee(&mut self, fee: u64) {
+        self.cash -= fee as i64;
+        self.pnl -= fee as i64;
+        self.priority_fees += fee;
+    }
+
     /// Get PnL as floating point value in currency units
     pub fn pnl_f64(&self) -> f64 {
-        self.pnl as f64 / 10000.0
+        price_utils::to_units(self.pnl)
     }
 
     /// Get cash as floating point value in currency units
     pub fn cash_f64(&self) -> f64 {
-        self.cash as f64 / 10000.0
+        price_utils::to_units(self.cash)
+    }
+}
+
+/// Order id namespaces
+///
+/// Order ids read from a data source are tagged with [`order_ids::HISTORICAL_BIT`]
+/// before they reach the engine. Ids the simulator generates count up from 1
+/// and never carry the tag, so historical and synthetic orders can share a
+/// book without a historical cancel hitting a synthetic order.
+pub mod order_ids {
+    use super::OrderId;
+
+    /// High bit marking an id as coming from historical data
+    pub const HISTORICAL_BIT: OrderId = 1 << 63;
+
+    /// Engine id for an order id taken from historical data, or `None` if the
+    /// source id already uses the tag bit
+    pub fn historical(source_id: OrderId) -> Option<OrderId> {
+        (source_id & HISTORICAL_BIT == 0).then_some(source_id | HISTORICAL_BIT)
+    }
+
+    /// Check whether an engine id belongs to the historical namespace
+    pub fn is_historical(id: OrderId) -> bool {
+        id & HISTORICAL_BIT != 0
+    }
+
+    /// The id as it appears in the source data
+    pub fn source_id(id: OrderId) -> OrderId {
+        id & !HISTORICAL_BIT
+    }
+
+    /// Id for an order the simulator derives from historical data (such as a
+    /// trade print), counted down from the top of the historical range so it
+    /// stays clear of the ids in the data itself
+    pub fn derived(sequence: u64) -> OrderId {
+        OrderId::MAX - (sequence & !HISTORICAL_BIT)
+    }
+
+    /// Id for an order placed from outside the simulator, such as by a client
+    /// session, counted down from the top of the synthetic range so it stays
+    /// clear of the simulator's own ids, which start over on a reset
+    pub fn session(sequence: u64) -> OrderId {
+        (HISTORICAL_BIT - 1) - (sequence & !HISTORICAL_BIT)
+    }
+}
+
+/// What to do with a quantity that is not a whole number of lots
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OddLotPolicy {
+    /// Refuse the order
+    #[default]
+    Reject,
+    /// Round the quantity down to whole lots, refusing orders under one lot
+    RoundDown,
+}
+
+/// Lot-size rule for an instrument
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct LotSize {
+    /// Quantity every order must be a multiple of (1 allows any quantity)
+    pub size: Qty,
+    /// Handling of quantities that are not a multiple of `size`
+    #[serde(default)]
+    pub odd_lots: OddLotPolicy,
+}
+
+impl Default for LotSize {
+    fn default() -> Self {
+        Self {
+            size: 1,
+            odd_lots: OddLotPolicy::Reject,
+        }
+    }
+}
+
+impl LotSize {
+    /// Lot-size rule rejecting odd lots
+    pub fn new(size: Qty) -> Self {
+        Self {
+            size: size.max(1),
+            odd_lots: OddLotPolicy::Reject,
+        }
+    }
+
+    /// Set how odd lots are handled
+    pub fn with_policy(mut self, odd_lots: OddLotPolicy) -> Self {
+        self.odd_lots = odd_lots;
+        self
+    }
+
+    /// Quantity left over after whole lots
+    pub fn odd_part(&self, qty: Qty) -> Qty {
+        qty % self.size.max(1)
+    }
+
+    /// Check whether a quantity is a whole number of lots
+    pub fn is_round_lot(&self, qty: Qty) -> bool {
+        self.odd_part(qty) == 0
+    }
+
+    /// Round a quantity down to whole lots
+    pub fn round_down(&self, qty: Qty) -> Qty {
+        qty - self.odd_part(qty)
+    }
+
+    /// Apply the odd-lot policy to an order quantity
+    pub fn normalize(&self, qty: Qty) -> crate::error::EngineResult<Qty> {
+        if self.is_round_lot(qty) {
+            return Ok(qty);
+        }
+        match self.odd_lots {
+            OddLotPolicy::RoundDown if qty >= self.size => Ok(self.round_down(qty)),
+            _ => Err(crate::error::EngineError::OddLot { qty, lot_size: self.size }),
+        }
+    }
+}
+
+/// How a contract's value follows its price
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Payoff {
+    /// Equity-style: a contract is worth its price, and cash, PnL, notional
+    /// and margin are in the quote currency (ticks)
+    #[default]
+    Linear,
+    /// Inverse futures-style: a contract is worth a fixed quote amount and
+    /// settles in the base asset, so its value moves with the inverse of the
+    /// price; cash, PnL, notional and margin are in base units
+    Inverse,
+}
+
+/// Payoff and size of the traded contract
+///
+/// Accounting values each contract through [`ContractSpec::value`], so the
+/// same cash and mark-to-market arithmetic works for either payoff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ContractSpec {
+    #[serde(default)]
+    pub payoff: Payoff,
+    /// Linear: quote ticks per tick of price per contract. Inverse: face
+    /// value of one contract in quote ticks
+    #[serde(default = "default_multiplier")]
+    pub multiplier: i64,
+}
+
+fn default_multiplier() -> i64 {
+    1
+}
+
+impl Default for ContractSpec {
+    fn default() -> Self {
+        Self {
+            payoff: Payoff::Linear,
+            multiplier: 1,
+        }
+    }
+}
+
+impl ContractSpec {
+    /// Base units per whole base asset for inverse contracts (satoshi scale)
+    pub const INVERSE_UNITS: i64 = 100_000_000;
+
+    /// Linear contract worth `multiplier` ticks per tick of price
+    pub fn linear(multiplier: i64) -> Self {
+        Self { payoff: Payoff::Linear, multiplier }
+    }
+
+    /// Inverse contract with a face value of `face_value` quote ticks
+    pub fn inverse(face_value: i64) -> Self {
+        Self { payoff: Payoff::Inverse, multiplier: face_value }
+    }
+
+    /// Check the contract size
+    ///
+    /// One contract has to be worth a whole number of settlement units at
+    /// every price the engine accepts, so the multiplier is bounded by the
+    /// highest price for a linear contract and the lowest for an inverse one.
+    pub fn validate(&self) -> Result<(), String> {
+        use crate::engine::{MAX_PRICE, MIN_PRICE};
+
+        if self.multiplier <= 0 {
+            return Err("Contract multiplier must be greater than 0".to_string());
+        }
+        let extreme = match self.payoff {
+            Payoff::Linear => MAX_PRICE,
+            Payoff::Inverse => MIN_PRICE,
+        };
+        if self.value(extreme).is_err() {
+            return Err(format!("Contract multiplier {} overflows the value of a contract at {} ticks", self.multiplier, extreme));
+        }
+        Ok(())
+    }
+
+    /// Signed value of one long contract at a price, in settlement units
+    ///
+    /// Buying moves cash by minus this and a position is marked at it. An
+    /// inverse contract is valued as minus its face value in base units,
+    /// which makes a long gain as the price rises, as it should.
+    pub fn value(&self, price: Price) -> crate::error::EngineResult<i64> {
+        self.value_of(1, price)
+    }
+
+    /// Signed value of `position` contracts at a price, in settlement units,
+    /// or an error when it does not fit
+    ///
+    /// The position is valued whole rather than contract by contract, so an
+    /// inverse position is rounded once instead of once per contract.
+    pub fn value_of(&self, position: i64, price: Price) -> crate::error::EngineResult<i64> {
+        let position = position as i128;
+        let value = match self.payoff {
+            Payoff::Linear => position.checked_mul(price as i128).and_then(|value| value.checked_mul(self.multiplier as i128)),
+            Payoff::Inverse => position
+                .checked_mul(self.multiplier as i128)
+                .and_then(|value| value.checked_mul(Self::INVERSE_UNITS as i128))
+                .map(|base| -(base / price.max(1) as i128)),
+        };
+        value
+            .and_then(|value| i64::try_from(value).ok())
+            .ok_or(crate::error::EngineError::ValueOverflow { qty: position.unsigned_abs() as Qty, price })
+    }
+
+    /// [`ContractSpec::value_of`] clamped to the i64 range, for accounting
+    /// fills that have already happened
+    pub fn saturating_value_of(&self, position: i64, price: Price) -> i64 {
+        self.value_of(position, price)
+            .unwrap_or(if (position > 0) == (self.payoff == Payoff::Linear) { i64::MAX } else { i64::MIN })
+    }
+
+    /// Absolute value of `qty` contracts at a price, in settlement units, or
+    /// an error when it does not fit
+    pub fn notional(&self, qty: Qty, price: Price) -> crate::error::EngineResult<i64> {
+        let overflow = crate::error::EngineError::ValueOverflow { qty, price };
+        let position = i64::try_from(qty).map_err(|_| overflow.clone())?;
+        self.value_of(position, price)?.checked_abs().ok_or(overflow)
     }
 }
 
 /// Price utility functions
 pub mod price_utils {
     use super::Price;
+    use crate::price_format::{PricePrecision, PRICE_DECIMALS};
+
+    /// Ticks in one unit of currency
+    const TICKS_PER_UNIT: f64 = 10_u64.pow(PRICE_DECIMALS) as f64;
 
     /// Convert price from floating point to integer ticks
     /// Assumes 4 decimal places (e.g., $100.25 -> 1002500)
     pub fn from_f64(price: f64) -> Price {
-        (price * 10000.0).round() as Price
+        (price * TICKS_PER_UNIT).round() as Price
     }
 
     /// Convert price from integer ticks to floating point
     /// Assumes 4 decimal places (e.g., 1002500 -> $100.25)
     pub fn to_f64(price: Price) -> f64 {
-        price as f64 / 10000.0
+        price as f64 / TICKS_PER_UNIT
+    }
+
+    /// Convert a signed tick amount, such as PnL or cash, to currency units
+    pub fn to_units(ticks: i64) -> f64 {
+        ticks as f64 / TICKS_PER_UNIT
     }
 
-    /// Format price as string with proper decimal places
+    /// Format price as an exact decimal string with four places
     pub fn format(price: Price) -> String {
-        format!("{:.4}", to_f64(price))
+        PricePrecision::default().format_price(price)
     }
 
     /// Calculate spread between bid and ask prices
@@ -178,7 +613,12 @@ pub mod price_utils {
 
     /// Calculate mid-price between bid and ask
     pub fn mid_price(bid: Price, ask: Price) -> f64 {
-        (bid as f64 + ask as f64) / 2.0 / 10000.0
+        (bid as f64 + ask as f64) / 2.0 / TICKS_PER_UNIT
+    }
+
+    /// Mid-price in whole ticks, rounded down
+    pub fn mid_ticks(bid: Price, ask: Price) -> Price {
+        bid / 2 + ask / 2 + (bid % 2 + ask % 2) / 2
     }
 }
 
@@ -186,6 +626,7 @@ pub mod price_utils {
 mod tests {
     use super::*;
     use super::price_utils::*;
+    use crate::error::EngineError;
 
     #[test]
     fn test_order_creation() {
@@ -206,6 +647,71 @@ mod tests {
         assert!(market_order.is_market());
     }
 
+    #[test]
+    fn test_lot_size_normalization() {
+        let lot = LotSize::new(100);
+        assert_eq!(lot.normalize(300), Ok(300));
+        assert!(lot.normalize(250).is_err());
+        assert_eq!(lot.odd_part(250), 50);
+
+        let rounding = lot.with_policy(OddLotPolicy::RoundDown);
+        assert_eq!(rounding.normalize(250), Ok(200));
+        assert!(rounding.normalize(50).is_err());
+
+        // The default allows any quantity
+        assert_eq!(LotSize::default().normalize(7), Ok(7));
+    }
+
+    #[test]
+    fn test_role_pnl_decomposition() {
+        let mut metrics = Metrics::new();
+        // Passive buy one tick under a 10000 mid, then an aggressive sell one
+        // tick under the mid after the market drops 3 ticks
+        metrics.record_fill(Liquidity::Maker, Side::Buy, 10, 9999, 10000);
+        metrics.record_fill(Liquidity::Taker, Side::Sell, 10, 9996, 9997);
+        metrics.calculate_pnl(Some(9997));
+
+        assert_eq!(metrics.maker.spread_capture, 10);
+        assert_eq!(metrics.maker.adverse_selection, -30);
+        assert_eq!(metrics.maker.pnl, -20);
+        assert_eq!(metrics.taker.spread_capture, -10);
+        assert_eq!(metrics.taker.pnl, -10);
+        assert_eq!(metrics.pnl, metrics.maker.pnl + metrics.taker.pnl);
+    }
+
+    #[test]
+    fn test_inverse_contract_accounting() {
+        // $1 contracts, long 1000 at $50,000 then marked at $55,000
+        let contract = ContractSpec::inverse(from_f64(1.0) as i64);
+        assert_eq!(contract.value(from_f64(50_000.0)), Ok(-2_000));
+        assert_eq!(contract.notional(1000, from_f64(50_000.0)), Ok(2_000_000));
+
+        let mut metrics = Metrics::new();
+        metrics.update_trade_for(&contract, Side::Buy, 1000, from_f64(50_000.0));
+        metrics.calculate_pnl_for(&contract, Some(from_f64(55_000.0)));
+        // 1000 * (1/50000 - 1/55000) BTC, rounded once for the whole position
+        assert_eq!(metrics.pnl, 181_819);
+
+        // A short loses as the price rises
+        let mut short = Metrics::new();
+        short.update_trade_for(&contract, Side::Sell, 1000, from_f64(50_000.0));
+        short.calculate_pnl_for(&contract, Some(from_f64(55_000.0)));
+        assert_eq!(short.pnl, -181_819);
+
+        // The default is the plain linear arithmetic
+        let linear = ContractSpec::default();
+        assert_eq!(linear.value(9999), Ok(9999));
+        assert_eq!(ContractSpec::linear(10).notional(3, 100), Ok(3_000));
+
+        // Values past i64 are errors, and multipliers that overflow one
+        // contract at the engine's price bounds are refused
+        assert!(matches!(ContractSpec::linear(1_000).notional(1_000_000_000, 100_000_000), Err(EngineError::ValueOverflow { .. })));
+        assert!(ContractSpec::linear(1_000).validate().is_ok());
+        assert!(ContractSpec::linear(i64::MAX / 1_000).validate().is_err());
+        assert!(ContractSpec::inverse(i64::MAX / 1_000).validate().is_err());
+        assert!(ContractSpec::inverse(0).validate().is_err());
+    }
+
     #[test]
     fn test_side_opposite() {
         assert_eq!(Side::Buy.opposite(), Side::Sell);
@@ -223,6 +729,10 @@ mod tests {
         let ask = from_f64(100.05);
         assert_eq!(spread(bid, ask), 500); // 5 cents in ticks
         assert_eq!(mid_price(bid, ask), 100.025);
+        assert_eq!(mid_ticks(bid, ask), 1_000_250);
+        assert_eq!(mid_ticks(u64::MAX, u64::MAX - 2), u64::MAX - 1);
+        assert_eq!(mid_ticks(3, 4), 3);
+        assert_eq!(to_units(-12_345), -1.2345);
     }
 
     #[test]
@@ -238,10 +748,15 @@ mod tests {
             price: from_f64(50.25),
             qty: 50,
             ts: 1000,
+            aggressor: Side::Sell,
         };
         let json = serde_json::to_string(&trade).unwrap();
         let deserialized: Trade = serde_json::from_str(&json).unwrap();
         assert_eq!(trade, deserialized);
+
+        // Trades saved without an aggressor still load
+        let old: Trade = serde_json::from_str(r#"{"maker_id":1,"taker_id":2,"price":502500,"qty":50,"ts":1000}"#).unwrap();
+        assert_eq!(old.aggressor, Side::Buy);
     }
 
     #[test]
@@ -306,10 +821,30 @@ mod tests {
             inventory: 100,
             cash: -5000000,
             pnl: 1000000,
+            ..Default::default()
         };
         
         let json = serde_json::to_string(&metrics).unwrap();
         let deserialized: Metrics = serde_json::from_str(&json).unwrap();
         assert_eq!(metrics, deserialized);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_execution_summaries_group_trades_by_taker() {
+        let trade = |maker_id, taker_id, price, qty, aggressor| Trade { maker_id, taker_id, price, qty, ts: 1000, aggressor };
+        let trades = [
+            trade(1, 10, 101_0000, 30, Side::Buy),
+            trade(2, 10, 101_0000, 20, Side::Buy),
+            trade(3, 10, 102_0000, 50, Side::Buy),
+            trade(4, 11, 99_0000, 10, Side::Sell),
+            trade(0, 0, 99_0000, 5, Side::Sell),
+            trade(0, 0, 99_0000, 5, Side::Sell),
+        ];
+        let summaries = ExecutionSummary::aggregate(&trades);
+        assert_eq!(summaries.iter().map(|s| (s.taker_id, s.qty, s.levels_swept)).collect::<Vec<_>>(),
+                   vec![(10, 100, 2), (11, 10, 1), (0, 5, 1), (0, 5, 1)]);
+        assert!((summaries[0].avg_price - 101_5000.0).abs() < 1e-9);
+        assert_eq!(summaries[0].fills.iter().map(|fill| fill.maker_id).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert!(ExecutionSummary::aggregate(&[]).is_empty());
+    }
+}
@@ -0,0 +1,188 @@
+//! Notional velocity checks for trading accounts
+//!
+//! Each owner may submit at most a configured notional value of orders in any
+//! rolling time window. This limits how fast an account can build exposure,
+//! independently of how many messages it sends.
+
+use std::collections::{HashMap, VecDeque};
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::error::{EngineError, EngineResult};
+use crate::time::secs_to_ns;
+
+/// Velocity limit shared by every owner
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct VelocityConfig {
+    /// Enforce the notional limit
+    pub enabled: bool,
+    /// Most notional an owner may submit within one window (price ticks x qty,
+    /// or base units for inverse contracts)
+    pub max_notional: u64,
+    /// Length of the rolling window in seconds
+    pub window_secs: u64,
+}
+
+impl Default for VelocityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_notional: 10_000_000_000, // $1,000,000 in ticks
+            window_secs: 10,
+        }
+    }
+}
+
+impl VelocityConfig {
+    /// Check the configured limit
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_notional == 0 {
+            return Err("Velocity max notional must be greater than 0".to_string());
+        }
+        if self.window_secs == 0 {
+            return Err("Velocity window must be at least 1 second".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Submission counters for one owner
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VelocityCounters {
+    /// Orders that passed the check
+    pub accepted: u64,
+    /// Orders refused by the check
+    pub rejected: u64,
+    /// Notional of the refused orders
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub rejected_notional: u64,
+    /// Notional submitted within the current window
+    #[serde(serialize_with = "crate::price_format::ticks")]
+    pub window_notional: u64,
+}
+
+/// Rolling-window notional limiter keyed by owner
+#[derive(Debug, Clone)]
+pub struct NotionalThrottle {
+    config: VelocityConfig,
+    /// Accepted submissions still inside the window, oldest first
+    windows: HashMap<String, VecDeque<(u128, u64)>>,
+    counters: HashMap<String, VelocityCounters>,
+}
+
+impl NotionalThrottle {
+    /// Create a throttle with the given limit
+    pub fn new(config: VelocityConfig) -> Self {
+        Self {
+            config,
+            windows: HashMap::new(),
+            counters: HashMap::new(),
+        }
+    }
+
+    /// Get the velocity configuration
+    pub fn config(&self) -> &VelocityConfig {
+        &self.config
+    }
+
+    /// Admit `notional` for `owner` at `ts`, or refuse it if it would take the
+    /// owner's window total past the limit
+    ///
+    /// Only admitted notional counts towards later checks.
+    pub fn check(&mut self, owner: &str, notional: u64, ts: u128) -> EngineResult<()> {
+        let window_start = ts.saturating_sub(secs_to_ns(self.config.window_secs as f64));
+        let window = self.windows.entry(owner.to_string()).or_default();
+        while window.front().is_some_and(|&(submitted, _)| submitted <= window_start) {
+            window.pop_front();
+        }
+        let window_notional: u64 = window.iter().map(|&(_, notional)| notional).sum();
+
+        let counters = self.counters.entry(owner.to_string()).or_default();
+        if window_notional.saturating_add(notional) > self.config.max_notional {
+            counters.rejected += 1;
+            counters.rejected_notional = counters.rejected_notional.saturating_add(notional);
+            counters.window_notional = window_notional;
+            return Err(EngineError::VelocityLimit {
+                owner: owner.to_string(),
+                notional,
+                window_notional,
+                limit: self.config.max_notional,
+            });
+        }
+
+        window.push_back((ts, notional));
+        counters.accepted += 1;
+        counters.window_notional = window_notional + notional;
+        Ok(())
+    }
+
+    /// Counters for one owner
+    pub fn counters(&self, owner: &str) -> Option<VelocityCounters> {
+        self.counters.get(owner).copied()
+    }
+
+    /// Counters for every owner that has submitted
+    pub fn all_counters(&self) -> &HashMap<String, VelocityCounters> {
+        &self.counters
+    }
+
+    /// Forget all submissions and counters
+    pub fn reset(&mut self) {
+        self.windows.clear();
+        self.counters.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn throttle() -> NotionalThrottle {
+        NotionalThrottle::new(VelocityConfig {
+            enabled: true,
+            max_notional: 1_000,
+            window_secs: 10,
+        })
+    }
+
+    #[test]
+    fn test_rolling_window_limit() {
+        let mut throttle = throttle();
+        assert!(throttle.check("alice", 600, secs_to_ns(1.0)).is_ok());
+        assert!(throttle.check("alice", 400, secs_to_ns(5.0)).is_ok());
+
+        let err = throttle.check("alice", 1, secs_to_ns(9.0)).unwrap_err();
+        assert_eq!(err, EngineError::VelocityLimit {
+            owner: "alice".to_string(),
+            notional: 1,
+            window_notional: 1_000,
+            limit: 1_000,
+        });
+        // Other owners have their own window
+        assert!(throttle.check("bob", 1_000, secs_to_ns(9.0)).is_ok());
+
+        // Once the first submission ages out its notional is available again
+        assert!(throttle.check("alice", 600, secs_to_ns(11.0)).is_ok());
+        assert!(throttle.check("alice", 1, secs_to_ns(12.0)).is_err());
+
+        let counters = throttle.counters("alice").unwrap();
+        assert_eq!(counters.accepted, 3);
+        assert_eq!(counters.rejected, 2);
+        assert_eq!(counters.rejected_notional, 2);
+        assert_eq!(counters.window_notional, 1_000);
+    }
+
+    #[test]
+    fn test_validate_and_reset() {
+        assert!(VelocityConfig::default().validate().is_ok());
+        assert!(VelocityConfig { window_secs: 0, ..Default::default() }.validate().is_err());
+        assert!(VelocityConfig { max_notional: 0, ..Default::default() }.validate().is_err());
+
+        let mut throttle = throttle();
+        assert!(throttle.check("alice", 2_000, 0).is_err());
+        // Refused notional saturates rather than overflowing
+        assert!(throttle.check("alice", u64::MAX, 0).is_err());
+        assert_eq!(throttle.counters("alice").unwrap().rejected_notional, u64::MAX);
+        throttle.reset();
+        assert!(throttle.counters("alice").is_none());
+    }
+}
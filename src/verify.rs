@@ -0,0 +1,167 @@
+//! Regression baselines for simulation runs
+//!
+//! A [`Baseline`] pins the outcome of a deterministic run: a seeded
+//! synthetic run of a fixed number of steps, or the replay of a data file.
+//! Repeating the run and checking it against the baseline tells a fork of
+//! the engine whether its behavior changed: trade count, volume and the final
+//! book hash must match exactly, the PnL within the baseline's tolerance.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::engine::OrderBook;
+use crate::error::EngineResult;
+use crate::generator::DataGenerator;
+use crate::queue::QueueDiscipline;
+use crate::sim::Simulator;
+use crate::types::Trade;
+
+/// What a run ended with
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunOutcome {
+    pub trades: u64,
+    pub volume: u64,
+    /// State hash of the final book, in hex
+    pub final_hash: String,
+    /// Market maker PnL in ticks
+    pub pnl: i64,
+}
+
+impl RunOutcome {
+    /// Outcome of a run that produced `trades` and left `simulator` behind
+    pub fn of<D: QueueDiscipline>(simulator: &Simulator<OrderBook<D>>, trades: &[Trade]) -> Self {
+        Self {
+            trades: trades.len() as u64,
+            volume: trades.iter().map(|trade| trade.qty).sum(),
+            final_hash: format!("{:016x}", simulator.engine.state_hash()),
+            pnl: simulator.get_metrics().pnl,
+        }
+    }
+}
+
+/// Run `steps` synthetic steps from a fixed start time, so the outcome
+/// depends only on the seed and configuration
+pub fn run_synthetic<D: QueueDiscipline>(simulator: &mut Simulator<OrderBook<D>>, steps: usize) -> EngineResult<RunOutcome> {
+    simulator.set_time(DataGenerator::DEFAULT_START_TIME);
+    let trades = simulator.run_steps(steps)?;
+    Ok(RunOutcome::of(simulator, &trades))
+}
+
+/// Replay a simulator's replay source to the end
+pub fn run_replay<D: QueueDiscipline>(simulator: &mut Simulator<OrderBook<D>>) -> EngineResult<RunOutcome> {
+    let trades = simulator.advance_replay(u128::MAX)?;
+    Ok(RunOutcome::of(simulator, &trades))
+}
+
+/// A result that differs from the baseline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub field: &'static str,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: expected {}, got {}", self.field, self.expected, self.actual)
+    }
+}
+
+/// How to repeat a run and what it should end with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Baseline {
+    /// Random seed of the run
+    pub seed: u64,
+    /// Steps of a synthetic run; unused when replaying a data file
+    pub steps: usize,
+    /// Data file replayed instead of synthetic flow
+    #[serde(default)]
+    pub data_file: Option<PathBuf>,
+    /// How far the PnL may drift from the expected PnL, in ticks
+    #[serde(default)]
+    pub pnl_tolerance: u64,
+    pub expected: RunOutcome,
+}
+
+impl Baseline {
+    /// Read a baseline file
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        serde_json::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Write the baseline as pretty JSON
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let contents = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(path, contents)
+    }
+
+    /// Results of `actual` that differ from the expected outcome
+    pub fn check(&self, actual: &RunOutcome) -> Vec<Mismatch> {
+        let expected = &self.expected;
+        let mut mismatches = Vec::new();
+        let mut compare = |field, matches: bool, expected: String, actual: String| {
+            if !matches {
+                mismatches.push(Mismatch { field, expected, actual });
+            }
+        };
+        compare("trades", expected.trades == actual.trades, expected.trades.to_string(), actual.trades.to_string());
+        compare("volume", expected.volume == actual.volume, expected.volume.to_string(), actual.volume.to_string());
+        compare("final_hash", expected.final_hash == actual.final_hash, expected.final_hash.clone(), actual.final_hash.clone());
+        compare(
+            "pnl",
+            expected.pnl.abs_diff(actual.pnl) <= self.pnl_tolerance,
+            format!("{} ± {}", expected.pnl, self.pnl_tolerance),
+            actual.pnl.to_string(),
+        );
+        mismatches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue_fifo::FifoLevel;
+
+    fn seeded_run(seed: u64) -> RunOutcome {
+        let mut simulator = Simulator::with_seed(OrderBook::<FifoLevel>::new(), seed);
+        run_synthetic(&mut simulator, 300).unwrap()
+    }
+
+    #[test]
+    fn test_seeded_runs_match_their_baseline() {
+        let outcome = seeded_run(7);
+        assert!(outcome.trades > 0);
+        let mut baseline = Baseline { seed: 7, steps: 300, data_file: None, pnl_tolerance: 0, expected: outcome.clone() };
+        assert_eq!(baseline.check(&seeded_run(7)), vec![]);
+
+        let path = std::env::temp_dir().join(format!("verify_baseline_{}.json", std::process::id()));
+        baseline.save(&path).unwrap();
+        assert_eq!(Baseline::load(&path).unwrap(), baseline);
+        std::fs::remove_file(&path).unwrap();
+
+        // A behavior change shows up field by field; PnL only past its tolerance
+        let drifted = RunOutcome { volume: outcome.volume + 1, pnl: outcome.pnl + 3, ..outcome.clone() };
+        let fields = |mismatches: Vec<Mismatch>| mismatches.iter().map(|m| m.field).collect::<Vec<_>>();
+        assert_eq!(fields(baseline.check(&drifted)), vec!["volume", "pnl"]);
+        baseline.pnl_tolerance = 3;
+        assert_eq!(fields(baseline.check(&drifted)), vec!["volume"]);
+        assert_ne!(seeded_run(8), outcome);
+    }
+
+    #[test]
+    fn test_final_hash_is_pinned_across_builds() {
+        use crate::engine::OrderBookEngine;
+        use crate::time::now_ns;
+        use crate::types::{Order, Side};
+
+        // Baselines are stored, so the hash of a known book must never move
+        let mut book = OrderBook::<FifoLevel>::new();
+        book.place(Order::new_limit(1, Side::Buy, 100, 990_000, now_ns())).unwrap();
+        book.place(Order::new_limit(2, Side::Buy, 50, 990_000, now_ns())).unwrap();
+        book.place(Order::new_limit(3, Side::Sell, 70, 1_010_000, now_ns())).unwrap();
+        assert_eq!(format!("{:016x}", book.state_hash()), "0b14dca22578b499");
+    }
+}
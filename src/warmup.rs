@@ -0,0 +1,209 @@
+//! Warm-up before measurement
+//!
+//! A run starts from an empty book, and its first spreads and fills say more
+//! about the empty start than about the market being simulated. The warm-up
+//! controller watches the book step by step until it looks steady (enough
+//! levels on each side, a spread that has stopped swinging) so that metrics
+//! collection can start from there.
+
+use std::collections::VecDeque;
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+
+/// Steady-state criteria the book must meet before metrics collection starts
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct WarmupConfig {
+    /// Run the warm-up before serving
+    pub enabled: bool,
+    /// Price levels each side must have
+    #[serde(default)]
+    pub min_levels_per_side: usize,
+    /// Largest variance of the spread (ticks squared) over the window; unset
+    /// only requires a two-sided book throughout the window
+    #[serde(default)]
+    pub max_spread_variance: Option<f64>,
+    /// Consecutive steps the criteria are checked over
+    pub window_steps: usize,
+    /// Give up and start measuring anyway after this many steps
+    pub max_steps: usize,
+}
+
+impl Default for WarmupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_levels_per_side: 5,
+            max_spread_variance: Some(100.0),
+            window_steps: 50,
+            max_steps: 10_000,
+        }
+    }
+}
+
+impl WarmupConfig {
+    /// Check the criteria
+    pub fn validate(&self) -> Result<(), String> {
+        if self.window_steps == 0 {
+            return Err("Warm-up window must be at least 1 step".to_string());
+        }
+        if self.max_steps < self.window_steps {
+            return Err("Warm-up max steps must be at least the window".to_string());
+        }
+        if self.max_spread_variance.is_some_and(|variance| variance.is_nan() || variance < 0.0) {
+            return Err("Warm-up max spread variance must not be negative".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Where a run's warm-up ended and measurement began
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WarmupBoundary {
+    /// Steps run before measurement started
+    pub steps: usize,
+    /// Simulation time measurement started at (nanoseconds)
+    pub ts: u128,
+    /// Whether the criteria were met, rather than `max_steps` running out
+    pub converged: bool,
+    /// Spread variance over the final window (ticks squared)
+    pub spread_variance: Option<f64>,
+    /// Price levels on each side at the boundary
+    pub bid_levels: usize,
+    pub ask_levels: usize,
+}
+
+/// Tracks the book across warm-up steps and decides when it is steady
+#[derive(Debug, Clone)]
+pub struct WarmupController {
+    config: WarmupConfig,
+    /// Spreads of the most recent steps, oldest first
+    spreads: VecDeque<i64>,
+    steps: usize,
+}
+
+impl WarmupController {
+    /// Create a controller for the given criteria
+    pub fn new(config: WarmupConfig) -> Self {
+        Self {
+            spreads: VecDeque::with_capacity(config.window_steps),
+            config,
+            steps: 0,
+        }
+    }
+
+    /// Get the warm-up criteria
+    pub fn config(&self) -> &WarmupConfig {
+        &self.config
+    }
+
+    /// Steps observed so far
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Whether the step budget has run out
+    pub fn exhausted(&self) -> bool {
+        self.steps >= self.config.max_steps
+    }
+
+    /// Record the book after a step and report whether it is steady
+    ///
+    /// A step with a one-sided or empty book restarts the window.
+    pub fn observe(&mut self, spread: Option<i64>, bid_levels: usize, ask_levels: usize) -> bool {
+        self.steps += 1;
+        match spread {
+            Some(spread) => {
+                if self.spreads.len() == self.config.window_steps {
+                    self.spreads.pop_front();
+                }
+                self.spreads.push_back(spread);
+            }
+            None => self.spreads.clear(),
+        }
+
+        self.spreads.len() == self.config.window_steps
+            && bid_levels >= self.config.min_levels_per_side
+            && ask_levels >= self.config.min_levels_per_side
+            && self.config.max_spread_variance.is_none_or(|max| {
+                self.spread_variance().is_some_and(|variance| variance <= max)
+            })
+    }
+
+    /// Population variance of the spreads in the current window
+    pub fn spread_variance(&self) -> Option<f64> {
+        if self.spreads.is_empty() {
+            return None;
+        }
+        let n = self.spreads.len() as f64;
+        let mean = self.spreads.iter().map(|&spread| spread as f64).sum::<f64>() / n;
+        Some(self.spreads.iter().map(|&spread| (spread as f64 - mean).powi(2)).sum::<f64>() / n)
+    }
+
+    /// The boundary to record once the warm-up stops
+    pub fn boundary(&self, ts: u128, converged: bool, bid_levels: usize, ask_levels: usize) -> WarmupBoundary {
+        WarmupBoundary {
+            steps: self.steps,
+            ts,
+            converged,
+            spread_variance: self.spread_variance(),
+            bid_levels,
+            ask_levels,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(window_steps: usize) -> WarmupConfig {
+        WarmupConfig {
+            enabled: true,
+            min_levels_per_side: 2,
+            max_spread_variance: Some(1.0),
+            window_steps,
+            max_steps: 100,
+        }
+    }
+
+    #[test]
+    fn test_steady_after_a_full_quiet_window() {
+        let mut controller = WarmupController::new(config(3));
+        assert!(!controller.observe(Some(10), 2, 2));
+        assert!(!controller.observe(Some(10), 2, 2));
+        assert!(controller.observe(Some(11), 2, 2));
+        assert_eq!(controller.steps(), 3);
+
+        // Too few levels on one side keeps it warming up
+        assert!(!controller.observe(Some(10), 1, 5));
+    }
+
+    #[test]
+    fn test_volatile_or_one_sided_book_restarts() {
+        let mut controller = WarmupController::new(config(3));
+        for spread in [10, 40, 10] {
+            assert!(!controller.observe(Some(spread), 5, 5));
+        }
+        assert!(controller.spread_variance().unwrap() > 1.0);
+
+        // A one-sided book empties the window
+        assert!(!controller.observe(None, 5, 0));
+        assert_eq!(controller.spread_variance(), None);
+        for _ in 0..2 {
+            assert!(!controller.observe(Some(10), 5, 5));
+        }
+        assert!(controller.observe(Some(10), 5, 5));
+
+        let boundary = controller.boundary(7, true, 5, 5);
+        assert_eq!(boundary.steps, 7);
+        assert_eq!(boundary.spread_variance, Some(0.0));
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_criteria() {
+        assert!(WarmupConfig::default().validate().is_ok());
+        assert!(WarmupConfig { window_steps: 0, ..WarmupConfig::default() }.validate().is_err());
+        assert!(WarmupConfig { max_steps: 10, ..WarmupConfig::default() }.validate().is_err());
+        assert!(WarmupConfig { max_spread_variance: Some(-1.0), ..WarmupConfig::default() }.validate().is_err());
+    }
+}
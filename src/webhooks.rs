@@ -0,0 +1,407 @@
+//! Outbound webhooks for fills and risk events
+//!
+//! External systems that only need to react to a few events (a paper order
+//! filling, a risk check refusing an order, the market halting, order entry
+//! tripping, settlement leaving a reconciliation break) can be sent them as HTTP POSTs instead of holding a WebSocket
+//! open. Each endpoint picks the events it wants. The body is the JSON
+//! [`WebhookEvent`]; when the endpoint has a secret, the
+//! `X-Orderbook-Signature` header carries `sha256=` and the hex HMAC-SHA256
+//! of the body under that secret, so the receiver can check the sender.
+//!
+//! Delivery is best effort: each endpoint has a bounded queue drained by one
+//! worker, so an endpoint receives its events in order. A failed POST is
+//! retried a few times and then logged, events that find the queue full are
+//! dropped with a warning, and nothing is queued across restarts.
+
+use std::collections::HashMap;
+use std::time::Duration;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use sha2::Sha256;
+use crate::activity::{Activity, ActivityKind};
+use crate::data::MarketStatusType;
+use crate::paper::PaperFill;
+use crate::protocol::ApiError;
+use crate::types::{Price, Qty, Side};
+
+/// Header carrying the body's signature
+pub const SIGNATURE_HEADER: &str = "X-Orderbook-Signature";
+/// Header naming the event
+pub const EVENT_HEADER: &str = "X-Orderbook-Event";
+
+/// Events a webhook can be sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEventKind {
+    /// A paper order filled, on submission or later while resting
+    Fill,
+    /// A paper or manual order was refused by a risk control: the margin
+    /// or velocity checks, or self-trade prevention
+    RiskRejection,
+    /// The market halted, or resumed after a halt
+    Halt,
+    /// Order entry went down or was throttled, or came back
+    CircuitBreaker,
+    /// A settlement cycle closed with an account that did not reconcile
+    ReconciliationBreak,
+}
+
+impl WebhookEventKind {
+    /// Name sent in the event header
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Fill => "fill",
+            Self::RiskRejection => "risk_rejection",
+            Self::Halt => "halt",
+            Self::CircuitBreaker => "circuit_breaker",
+            Self::ReconciliationBreak => "reconciliation_break",
+        }
+    }
+}
+
+/// One webhook receiver
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct WebhookEndpoint {
+    /// URL events are POSTed to
+    pub url: String,
+    /// Events to send; every event when empty
+    #[serde(default)]
+    pub events: Vec<WebhookEventKind>,
+    /// Key for the HMAC-SHA256 signature header; unsigned when unset
+    #[serde(default)]
+    pub secret: Option<String>,
+}
+
+impl WebhookEndpoint {
+    /// Whether the endpoint wants this kind of event
+    pub fn wants(&self, kind: WebhookEventKind) -> bool {
+        self.events.is_empty() || self.events.contains(&kind)
+    }
+}
+
+/// Outbound webhook settings
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct WebhookConfig {
+    /// Send webhooks
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub endpoints: Vec<WebhookEndpoint>,
+    /// Give up on a POST after this long
+    pub timeout_ms: u64,
+    /// Attempts after the first failed POST
+    pub retries: u32,
+    /// Deliveries each endpoint may have waiting before new ones are dropped
+    #[serde(default = "default_queue_capacity")]
+    pub queue_capacity: usize,
+}
+
+fn default_queue_capacity() -> usize {
+    256
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoints: Vec::new(),
+            timeout_ms: 2000,
+            retries: 2,
+            queue_capacity: default_queue_capacity(),
+        }
+    }
+}
+
+impl WebhookConfig {
+    /// Check the settings
+    pub fn validate(&self) -> Result<(), String> {
+        if self.endpoints.is_empty() {
+            return Err("Webhooks need at least one endpoint".to_string());
+        }
+        if self.timeout_ms == 0 {
+            return Err("Webhook timeout must be greater than 0".to_string());
+        }
+        if self.queue_capacity == 0 {
+            return Err("Webhook queue capacity must be greater than 0".to_string());
+        }
+        for endpoint in &self.endpoints {
+            if !endpoint.url.starts_with("http://") && !endpoint.url.starts_with("https://") {
+                return Err(format!("Webhook URL '{}' must be http or https", endpoint.url));
+            }
+            if endpoint.secret.as_deref().is_some_and(str::is_empty) {
+                return Err(format!("Webhook secret for {} must not be empty", endpoint.url));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What a webhook event reports
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WebhookPayload {
+    Fill(PaperFill),
+    RiskRejection {
+        account: String,
+        side: Side,
+        qty: Qty,
+        /// Limit price; `None` for a market order
+        price: Option<Price>,
+        error: ApiError,
+    },
+    Halt {
+        /// True when the market halted, false when it left the halt
+        halted: bool,
+        status: MarketStatusType,
+        message: String,
+    },
+    CircuitBreaker {
+        /// `gateway` for outages, `throttle` for load shedding
+        source: ActivityKind,
+        message: String,
+    },
+    ReconciliationBreak {
+        message: String,
+    },
+}
+
+impl WebhookPayload {
+    /// The kind of event endpoints filter on
+    pub fn kind(&self) -> WebhookEventKind {
+        match self {
+            Self::Fill(_) => WebhookEventKind::Fill,
+            Self::RiskRejection { .. } => WebhookEventKind::RiskRejection,
+            Self::Halt { .. } => WebhookEventKind::Halt,
+            Self::CircuitBreaker { .. } => WebhookEventKind::CircuitBreaker,
+            Self::ReconciliationBreak { .. } => WebhookEventKind::ReconciliationBreak,
+        }
+    }
+}
+
+/// Body of a webhook POST
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookEvent {
+    /// Numbered in the order events were raised, across endpoints
+    pub seq: u64,
+    /// Wall-clock time the event was raised (milliseconds since the epoch)
+    pub time_ms: u64,
+    /// Simulation time it happened at, when it comes from the market
+    #[serde(default)]
+    pub sim_time: Option<u128>,
+    #[serde(flatten)]
+    pub payload: WebhookPayload,
+}
+
+/// One POST to make
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebhookDelivery {
+    pub url: String,
+    pub kind: WebhookEventKind,
+    pub body: Vec<u8>,
+    /// Value of the signature header, for endpoints with a secret
+    pub signature: Option<String>,
+}
+
+/// Hex HMAC-SHA256 of a body, as sent in the signature header
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Turns events into signed deliveries for the endpoints that want them
+#[derive(Debug, Clone)]
+pub struct Webhooks {
+    config: WebhookConfig,
+    client: reqwest::Client,
+    next_seq: u64,
+    halted: bool,
+    /// Queue of each endpoint's worker, started on its first delivery
+    queues: HashMap<String, tokio::sync::mpsc::Sender<WebhookDelivery>>,
+}
+
+impl Webhooks {
+    /// Build the HTTP client for the configured endpoints
+    pub fn from_config(config: WebhookConfig) -> Result<Self, String> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .build()
+            .map_err(|e| format!("Failed to build webhook client: {}", e))?;
+        Ok(Self { config, client, next_seq: 1, halted: false, queues: HashMap::new() })
+    }
+
+    /// The webhook settings
+    pub fn config(&self) -> &WebhookConfig {
+        &self.config
+    }
+
+    /// Client to deliver with
+    pub fn client(&self) -> reqwest::Client {
+        self.client.clone()
+    }
+
+    /// Number an event and build a delivery for each endpoint that wants it
+    pub fn deliveries(&mut self, payload: WebhookPayload, time_ms: u64, sim_time: Option<u128>) -> Vec<WebhookDelivery> {
+        let kind = payload.kind();
+        if !self.config.endpoints.iter().any(|endpoint| endpoint.wants(kind)) {
+            return Vec::new();
+        }
+        let event = WebhookEvent { seq: self.next_seq, time_ms, sim_time, payload };
+        self.next_seq += 1;
+        let body = match serde_json::to_vec(&event) {
+            Ok(body) => body,
+            Err(e) => {
+                tracing::warn!("Failed to serialize webhook event: {}", e);
+                return Vec::new();
+            }
+        };
+        self.config.endpoints.iter()
+            .filter(|endpoint| endpoint.wants(kind))
+            .map(|endpoint| WebhookDelivery {
+                url: endpoint.url.clone(),
+                kind,
+                signature: endpoint.secret.as_deref().map(|secret| sign(secret, &body)),
+                body: body.clone(),
+            })
+            .collect()
+    }
+
+    /// Queue deliveries on their endpoints' workers, starting a worker the
+    /// first time its endpoint is used. Must be called within a Tokio runtime.
+    pub fn dispatch(&mut self, deliveries: Vec<WebhookDelivery>) {
+        for delivery in deliveries {
+            let queue = self.queues.entry(delivery.url.clone()).or_insert_with(|| {
+                spawn_worker(self.client.clone(), self.config.retries, self.config.queue_capacity)
+            });
+            match queue.try_send(delivery) {
+                Ok(()) => {}
+                Err(tokio::sync::mpsc::error::TrySendError::Full(delivery)) => {
+                    tracing::warn!("Webhook queue for {} is full; dropping a {} event", delivery.url, delivery.kind.as_str());
+                }
+                Err(tokio::sync::mpsc::error::TrySendError::Closed(delivery)) => {
+                    tracing::warn!("Webhook worker for {} stopped; dropping a {} event", delivery.url, delivery.kind.as_str());
+                }
+            }
+        }
+    }
+
+    /// The webhook event an activity event raises, if any: halts, the end
+    /// of a halt, gateway outages and throttling, and reconciliation breaks
+    pub fn payload_for(&mut self, activity: &Activity) -> Option<WebhookPayload> {
+        match activity.kind {
+            ActivityKind::MarketStatus => {
+                let status = activity.status?;
+                let halted = status == MarketStatusType::Halted;
+                if !halted && !self.halted {
+                    return None;
+                }
+                self.halted = halted;
+                Some(WebhookPayload::Halt { halted, status, message: activity.message.clone() })
+            }
+            ActivityKind::Gateway | ActivityKind::Throttle => Some(WebhookPayload::CircuitBreaker {
+                source: activity.kind,
+                message: activity.message.clone(),
+            }),
+            ActivityKind::Settlement => Some(WebhookPayload::ReconciliationBreak { message: activity.message.clone() }),
+            _ => None,
+        }
+    }
+}
+
+/// Start a worker that POSTs an endpoint's deliveries one at a time, in the
+/// order they were queued
+fn spawn_worker(client: reqwest::Client, retries: u32, capacity: usize) -> tokio::sync::mpsc::Sender<WebhookDelivery> {
+    let (sender, mut receiver) = tokio::sync::mpsc::channel::<WebhookDelivery>(capacity);
+    tokio::spawn(async move {
+        while let Some(delivery) = receiver.recv().await {
+            if let Err(e) = deliver(&client, &delivery, retries).await {
+                tracing::warn!("{}", e);
+            }
+        }
+    });
+    sender
+}
+
+/// POST a delivery, retrying failures up to `retries` times
+pub async fn deliver(client: &reqwest::Client, delivery: &WebhookDelivery, retries: u32) -> Result<(), String> {
+    let mut attempt = 0;
+    loop {
+        let mut request = client.post(&delivery.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, delivery.kind.as_str())
+            .body(delivery.body.clone());
+        if let Some(signature) = &delivery.signature {
+            request = request.header(SIGNATURE_HEADER, signature);
+        }
+        let result = request.send().await.and_then(reqwest::Response::error_for_status);
+        match result {
+            Ok(_) => return Ok(()),
+            Err(e) if attempt >= retries => {
+                return Err(format!("Webhook {} failed after {} attempts: {}", delivery.url, attempt + 1, e));
+            }
+            Err(_) => {
+                attempt += 1;
+                tokio::time::sleep(Duration::from_millis(100 << attempt.min(6))).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn endpoint(url: &str, events: Vec<WebhookEventKind>, secret: Option<&str>) -> WebhookEndpoint {
+        WebhookEndpoint { url: url.to_string(), events, secret: secret.map(str::to_string) }
+    }
+
+    #[test]
+    fn test_sign_matches_hmac_sha256() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_deliveries_go_to_endpoints_that_want_them() {
+        let mut webhooks = Webhooks::from_config(WebhookConfig {
+            enabled: true,
+            endpoints: vec![
+                endpoint("http://fills.example/hook", vec![WebhookEventKind::Fill], Some("key")),
+                endpoint("http://ops.example/hook", vec![], None),
+            ],
+            ..WebhookConfig::default()
+        }).unwrap();
+        assert!(webhooks.config().validate().is_ok());
+
+        let fill = PaperFill { order_id: 1, account: "alice".to_string(), side: Side::Buy, price: 100_0000, qty: 5, ts: 9 };
+        let deliveries = webhooks.deliveries(WebhookPayload::Fill(fill), 1_000, Some(9));
+        assert_eq!(deliveries.len(), 2);
+        assert_eq!(deliveries[0].signature.as_deref(), Some(sign("key", &deliveries[0].body).as_str()));
+        assert_eq!(deliveries[1].signature, None);
+        let body: serde_json::Value = serde_json::from_slice(&deliveries[0].body).unwrap();
+        assert_eq!((body["seq"].as_u64(), body["event"].as_str(), body["account"].as_str()), (Some(1), Some("fill"), Some("alice")));
+
+        // Only the catch-all endpoint hears about halts; resuming is reported
+        // once and other status changes are not
+        let status = |status| Activity::market_status(status, 5, None);
+        assert_eq!(webhooks.payload_for(&status(MarketStatusType::Open)), None);
+        let halt = webhooks.payload_for(&status(MarketStatusType::Halted)).unwrap();
+        let deliveries = webhooks.deliveries(halt, 2_000, Some(5));
+        assert_eq!(deliveries.iter().map(|delivery| delivery.url.as_str()).collect::<Vec<_>>(), vec!["http://ops.example/hook"]);
+        assert!(matches!(webhooks.payload_for(&status(MarketStatusType::Open)), Some(WebhookPayload::Halt { halted: false, .. })));
+        assert_eq!(webhooks.payload_for(&status(MarketStatusType::Closed)), None);
+        assert!(matches!(
+            webhooks.payload_for(&Activity::new(ActivityKind::Gateway, "Gateway down")),
+            Some(WebhookPayload::CircuitBreaker { source: ActivityKind::Gateway, .. })
+        ));
+        assert_eq!(
+            webhooks.payload_for(&Activity::new(ActivityKind::Settlement, "break")).map(|payload| payload.kind()),
+            Some(WebhookEventKind::ReconciliationBreak)
+        );
+        assert_eq!(webhooks.payload_for(&Activity::new(ActivityKind::Error, "boom")), None);
+    }
+}